@@ -0,0 +1,148 @@
+"""
+Derived products computed from each snapshot as it's written.
+
+An analysis is a subclass of `AnalysisBase`, which computes a dictionary of
+products (derived fields, reductions over regions, or anything else) from
+the snapshot dictionary. Analyses are selected by name with the driver's
+`--analysis` flag, and each one's products are written next to the snapshot,
+as `snap.0000.<name>.pk`. Analyses defined outside the main codebase are
+loaded from the modules listed under `analyses` in the `extensions` section
+of the .sailfish file, like setups. Analyses can also be computed later from
+the checkpoints of a run, with `sailfish replay`; see `sailfish.replay`.
+"""
+
+from abc import ABC, abstractmethod
+from math import pi
+from sailfish.setup_base import dash_case
+
+
+class AnalysisError(Exception):
+    """Something went wrong during analysis configuration"""
+
+
+class AnalysisBase(ABC):
+    """
+    Base class for snapshot-time analyses.
+    """
+
+    @classmethod
+    def dash_case_class_name(cls):
+        """
+        Return a `dash-case` name of this analysis class, which is the name
+        used to select it, and in the names of its product files.
+        """
+        return dash_case(cls.__name__)
+
+    @classmethod
+    def find_analysis_class(cls, name):
+        """
+        Finds an analysis class with the given dash-case name. If no analysis
+        is found, an `AnalysisError` exception is raised.
+        """
+        match = lambda s: s.dash_case_class_name() == name
+        try:
+            return next(filter(match, cls.__subclasses__()))
+        except StopIteration:
+            names = ", ".join(s.dash_case_class_name() for s in cls.__subclasses__())
+            raise AnalysisError(f"no analysis named {name}, the analyses are {names}")
+
+    @abstractmethod
+    def products(self, snapshot):
+        """
+        Return a dictionary of products derived from a snapshot.
+
+        The snapshot is the dictionary written to the snapshot file, with the
+        primitive data as a host array in its `primitive` item. When analyses
+        are replayed from checkpoints, it's the checkpoint's contents, which
+        include the recorded `face_fluxes`, if any. An analysis
+        which doesn't apply to a snapshot (e.g. because of its mesh type)
+        should raise `AnalysisError`.
+        """
+        pass
+
+
+class RadialProfiles(AnalysisBase):
+    """
+    Azimuthally averaged profiles of each primitive field, and of the radial
+    mass flux, on 2D Cartesian meshes centered on the origin.
+    """
+
+    num_bins = 200
+
+    def products(self, snapshot):
+        import numpy as np
+        from sailfish.fields import field_index
+        from sailfish.mesh import PlanarCartesian2DMesh
+        from sailfish.physics.viscous_disk import azimuthal_average
+        from sailfish.spacetime import mesh_radii
+
+        mesh = snapshot["mesh"]
+        fields = snapshot["primitive_fields"]
+
+        if not isinstance(mesh, PlanarCartesian2DMesh):
+            raise AnalysisError("radial profiles need a 2D Cartesian mesh")
+
+        outer_radius = min(-mesh.x0, mesh.x1, -mesh.y0, mesh.y1)
+
+        if outer_radius <= 0.0:
+            raise AnalysisError("radial profiles need a mesh containing the origin")
+
+        faces = np.linspace(0.0, outer_radius, self.num_bins + 1)
+        radii = 0.5 * (faces[1:] + faces[:-1])
+        prim = snapshot["primitive"].astype(np.float64)
+        r, x, y = mesh_radii(mesh)
+
+        sigma = prim[..., field_index(fields, "sigma")]
+        vx = prim[..., field_index(fields, "vx")]
+        vy = prim[..., field_index(fields, "vy")]
+        vr = (x * vx + y * vy) / np.maximum(r, 1e-12)
+
+        profiles = {
+            f["name"]: azimuthal_average(r, prim[..., n], faces)
+            for n, f in enumerate(fields)
+        }
+        mdot = -2.0 * pi * radii * azimuthal_average(r, sigma * vr, faces)
+
+        return dict(radius=radii, faces=faces, mdot=mdot, **profiles)
+
+
+class EdgeFluxes(AnalysisBase):
+    """
+    The rates at which mass, momentum, and tracers leave a 2D Cartesian mesh
+    through each of its edges, from the face fluxes recorded by solvers with
+    the option `record_face_fluxes`.
+
+    The face fluxes are only written to checkpoints, so this analysis is
+    computed with `sailfish replay`. The rates are averages over the interval
+    since the previous checkpoint, and are exact: the loss of mass from the
+    mesh over the interval is the total rate times its duration, less the
+    mass added by source terms.
+    """
+
+    def products(self, snapshot):
+        from sailfish.mesh import PlanarCartesian2DMesh
+
+        mesh = snapshot["mesh"]
+        fluxes = snapshot.get("face_fluxes")
+
+        if fluxes is None:
+            raise AnalysisError("edge fluxes need the face fluxes in a checkpoint")
+
+        if not isinstance(mesh, PlanarCartesian2DMesh):
+            raise AnalysisError("edge fluxes need a 2D Cartesian mesh")
+
+        fx = fluxes["x"]
+        fy = fluxes["y"]
+        edges = dict(
+            left=-fx[0].sum(axis=0) * mesh.dy,
+            right=fx[-1].sum(axis=0) * mesh.dy,
+            bottom=-fy[:, 0].sum(axis=0) * mesh.dx,
+            top=fy[:, -1].sum(axis=0) * mesh.dx,
+        )
+
+        return dict(
+            start_time=fluxes["start_time"],
+            end_time=fluxes["end_time"],
+            total=sum(edges.values()),
+            **edges,
+        )
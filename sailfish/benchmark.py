@@ -0,0 +1,153 @@
+"""
+Micro-benchmarks of the solver kernels and host patch operations.
+
+Each benchmark times one operation of the `SolverBase` interface on a
+setup's solver, at several mesh resolutions: a full iteration (`advance`),
+the wavespeed reduction, the time series reductions, the copy of the
+primitive data to the host, and the re-derivation of the guard zones and
+conserved data (`reinitialize`). The timings are the median of a few
+rounds, measured with `sailfish.kernel.system.measure_time`, so GPU work is
+timed on the device.
+
+Results are rows of dictionaries, which can be written to a JSON file and
+compared to a baseline from an earlier build, to catch performance
+regressions in refactors:
+
+.. code-block:: bash
+
+    sailfish kitp-code-comparison --micro-bench -o base
+    # ... change the code ...
+    sailfish kitp-code-comparison --micro-bench --bench-baseline base/bench.json
+"""
+
+import json
+import pathlib
+from statistics import median
+from sailfish.kernel.system import measure_time
+
+DEFAULT_RESOLUTIONS = (64, 128, 256)
+
+
+def time_operation(operation, mode, repeat=10, rounds=5):
+    """
+    Return the median over `rounds` of the time per call of `operation`,
+    called `repeat` times in each round, in seconds.
+    """
+    operation()  # warm up, e.g. build the kernels
+    timings = []
+
+    for _ in range(rounds):
+        with measure_time(mode) as duration:
+            for _ in range(repeat):
+                operation()
+        timings.append(duration() / repeat)
+
+    return median(timings)
+
+
+def micro_benchmarks(
+    setup_name,
+    resolutions=DEFAULT_RESOLUTIONS,
+    execution_mode=None,
+    num_patches=None,
+    model_parameters=None,
+    solver_options=None,
+    repeat=10,
+):
+    """
+    Time the solver operations for a setup at each of the resolutions, and
+    return a list of rows with the operation `name`, the `resolution`, the
+    `seconds` per call, and the throughput `Mzps` in millions of zones per
+    second.
+    """
+    from sailfish.interactive import Simulation
+
+    mode = execution_mode or "cpu"
+    rows = list()
+
+    for resolution in resolutions:
+        sim = Simulation(
+            setup_name,
+            model_parameters=model_parameters,
+            solver_options=solver_options,
+            execution_mode=mode,
+            resolution=resolution,
+            num_patches=num_patches,
+        )
+        solver = sim.solver
+        dx = sim.mesh.min_spacing(sim.time)
+
+        # A small time step keeps the solution close to the initial one over
+        # the repeated iterations, so every round does the same work.
+        dt = solver.recommended_timestep(dx, sim.cfl_number) * 1e-3
+        operations = dict(
+            advance=lambda: solver.advance(dt),
+            wavespeed=solver.maximum_wavespeed,
+            reductions=solver.reductions,
+            primitive_array=solver.primitive_array,
+            reinitialize=solver.reinitialize,
+        )
+
+        for name, operation in operations.items():
+            seconds = time_operation(operation, mode, repeat)
+            rows.append(
+                dict(
+                    name=name,
+                    resolution=resolution,
+                    seconds=seconds,
+                    Mzps=sim.mesh.num_total_zones / seconds * 1e-6,
+                )
+            )
+
+    return rows
+
+
+def print_benchmarks(rows, regressions=()):
+    """
+    Print a table of benchmark rows, marking the ones in `regressions`.
+    """
+    slower = {(r["name"], r["resolution"]): r["ratio"] for r in regressions}
+
+    print()
+    print(f"{'operation':<18}{'resolution':>12}{'seconds':>14}{'Mzps':>12}")
+    print("-" * 64)
+
+    for row in rows:
+        ratio = slower.get((row["name"], row["resolution"]))
+        note = f"  {ratio:.2f}x slower" if ratio is not None else ""
+        print(
+            f"{row['name']:<18}{row['resolution']:>12}{row['seconds']:>14.3e}"
+            f"{row['Mzps']:>12.3f}{note}"
+        )
+    print()
+
+
+def write_benchmarks(filename, rows):
+    pathlib.Path(filename).parent.mkdir(parents=True, exist_ok=True)
+
+    with open(filename, "w") as f:
+        json.dump(rows, f, indent=4)
+
+
+def read_benchmarks(filename):
+    with open(filename) as f:
+        return json.load(f)
+
+
+def compare_benchmarks(rows, baseline, tolerance=0.25):
+    """
+    Return the rows which are slower than the row of the same operation and
+    resolution in the baseline by more than the fractional `tolerance`, with
+    the `ratio` of their times added. Rows missing from the baseline are
+    skipped.
+    """
+    reference = {(r["name"], r["resolution"]): r["seconds"] for r in baseline}
+    regressions = list()
+
+    for row in rows:
+        seconds = reference.get((row["name"], row["resolution"]))
+
+        if seconds is not None and row["seconds"] > seconds * (1.0 + tolerance):
+            regressions.append(dict(row, ratio=row["seconds"] / seconds))
+
+    return regressions
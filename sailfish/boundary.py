@@ -0,0 +1,176 @@
+"""
+Boundary conditions at the edges of a 2D mesh.
+
+A setup's `boundary_condition` is either one mode for every edge, e.g.
+`"outflow"`, a pair of modes for the x and y axes, e.g. `("periodic",
+"reflecting")`, or a pair of `(lower, upper)` pairs, one for each axis, e.g.
+`(("fixed", "outflow"), "periodic")`. The modes are:
+
+- `periodic`: the guard zones are copied from the opposite edge; this must be
+  set on both edges of an axis
+- `outflow`: the guard zones are copied from the outermost zone
+- `outflow-linear`: the guard zones are extrapolated linearly from the two
+  outermost zones, except that the density and pressure fall back to the
+  outermost zone's value where the extrapolation would not be positive
+- `outflow-clamped`, `outflow-linear-clamped`: as above, and then the
+  velocity component normal to the edge is set to zero in guard zones where
+  it points into the domain, so the boundary can't drive an artificial
+  inflow; `diode` is an alias of `outflow-clamped`
+- `reflecting`: the guard zones mirror the zones inside the edge, with the
+  velocity component normal to the edge reversed
+- `fixed`: the guard zones keep the setup's primitive state at their
+  coordinates, e.g. for inflow; `inflow` is an alias
+
+The `cbdgam_2d` solver accepts all of these. The `cbdiso_2d` solver accepts
+the outflow modes, on any edge, or its own non-reflecting `characteristic`
+mode on every edge. The other solvers
+have their own boundary conditions, e.g. the 1D solvers take `outflow`,
+`inflow`, `reflect`, `fixed`, or `periodic` on each end.
+"""
+
+from enum import Enum
+
+
+class BoundaryCondition(Enum):
+    PERIODIC = "periodic"
+    OUTFLOW = "outflow"
+    OUTFLOW_LINEAR = "outflow-linear"
+    OUTFLOW_CLAMPED = "outflow-clamped"
+    OUTFLOW_LINEAR_CLAMPED = "outflow-linear-clamped"
+    REFLECTING = "reflecting"
+    FIXED = "fixed"
+
+
+    @property
+    def is_outflow(self):
+        """
+        Whether this is one of the outflow modes.
+        """
+        return self in OUTFLOW_MODES
+
+    @property
+    def extrapolation_order(self):
+        """
+        The order of the extrapolation into the guard zones of an outflow mode.
+        """
+        return 1 if self in LINEAR_OUTFLOW_MODES else 0
+
+    @property
+    def is_clamped(self):
+        """
+        Whether an outflow mode clamps the velocity to prevent inflow.
+        """
+        return self in CLAMPED_OUTFLOW_MODES
+
+
+OUTFLOW_MODES = (
+    BoundaryCondition.OUTFLOW,
+    BoundaryCondition.OUTFLOW_LINEAR,
+    BoundaryCondition.OUTFLOW_CLAMPED,
+    BoundaryCondition.OUTFLOW_LINEAR_CLAMPED,
+)
+LINEAR_OUTFLOW_MODES = (
+    BoundaryCondition.OUTFLOW_LINEAR,
+    BoundaryCondition.OUTFLOW_LINEAR_CLAMPED,
+)
+CLAMPED_OUTFLOW_MODES = (
+    BoundaryCondition.OUTFLOW_CLAMPED,
+    BoundaryCondition.OUTFLOW_LINEAR_CLAMPED,
+)
+BOUNDARY_CONDITION_ALIASES = dict(
+    inflow="fixed", reflect="reflecting", diode="outflow-clamped"
+)
+
+
+def boundary_condition(mode):
+    """
+    Return the `BoundaryCondition` named by a string, or raise `ValueError`.
+    """
+    if isinstance(mode, BoundaryCondition):
+        return mode
+
+    try:
+        return BoundaryCondition(BOUNDARY_CONDITION_ALIASES.get(mode, mode))
+    except ValueError:
+        names = ", ".join(bc.value for bc in BoundaryCondition)
+        raise ValueError(f"unknown boundary condition {mode}, the modes are {names}")
+
+
+def edge_conditions(modes, num_axes=2):
+    """
+    Return a tuple with a `(lower, upper)` pair of `BoundaryCondition` values
+    for each axis, from a boundary condition in any of the forms a setup can
+    give it. A `ValueError` is raised for unknown modes, or if only one edge
+    of an axis is periodic.
+    """
+    if isinstance(modes, (str, BoundaryCondition)):
+        modes = (modes,) * num_axes
+
+    if len(modes) != num_axes:
+        raise ValueError(f"boundary condition {modes} must have {num_axes} axes")
+
+    edges = []
+
+    for axis, mode in enumerate(modes):
+        if isinstance(mode, (str, BoundaryCondition)):
+            mode = (mode, mode)
+
+        lower, upper = (boundary_condition(m) for m in mode)
+        periodic = [m == BoundaryCondition.PERIODIC for m in (lower, upper)]
+
+        if periodic[0] != periodic[1]:
+            raise ValueError(f"axis {axis} must be periodic on both edges or neither")
+
+        edges.append((lower, upper))
+
+    return tuple(edges)
+
+
+def fill_guard_zones(primitive, axis, side, mode, num_guard):
+    """
+    Fill the guard zones on the lower (`side=0`) or upper (`side=1`) edge of
+    a patch's primitive array, along the given axis, whose fields 1 and 2 are
+    the x and y velocities. A linear extrapolation falls back to the
+    outermost zone for the other fields (densities, pressures, and tracers)
+    where it would not be positive. Fixed guard zones are left as they are,
+    and periodic x-boundaries are set with the internal boundary condition
+    between patches.
+    """
+    ng = num_guard
+    a = primitive if axis == 0 else primitive.swapaxes(0, 1)
+    n = a.shape[0]
+
+    if mode.is_outflow:
+        edge, inner = (ng, ng + 1) if side == 0 else (n - ng - 1, n - ng - 2)
+        outward = -1 if side == 0 else +1
+        guard = slice(None, ng) if side == 0 else slice(-ng, None)
+
+        for k in range(1, ng + 1):
+            if mode.extrapolation_order == 0:
+                a[edge + outward * k] = a[edge]
+            else:
+                p = a[edge] + k * (a[edge] - a[inner])
+                accept = p > 0.0
+                accept[..., 1:3] = True
+                a[edge + outward * k] = p * accept + a[edge] * ~accept
+
+        if mode.is_clamped:
+            v = a[guard, :, axis + 1]
+            v *= v * outward > 0.0
+
+    elif mode == BoundaryCondition.REFLECTING:
+        for i in range(ng):
+            if side == 0:
+                a[i] = a[2 * ng - 1 - i]
+            else:
+                a[n - 1 - i] = a[n - 2 * ng + i]
+        if side == 0:
+            a[:ng, :, axis + 1] *= -1.0
+        else:
+            a[-ng:, :, axis + 1] *= -1.0
+
+    elif mode == BoundaryCondition.PERIODIC and axis == 1:
+        if side == 0:
+            a[:ng] = a[-2 * ng : -ng]
+        else:
+            a[-ng:] = a[ng : 2 * ng]
@@ -0,0 +1,208 @@
+"""
+Functions to collect the results of many runs into a campaign manifest.
+
+A campaign is a directory tree of run output directories, for example from a
+parameter survey. Each run directory holds checkpoint files written by the
+driver, which embed the setup name, model parameters, mesh, and the recorded
+time series. The manifest has one row per run, with the run's parameters and
+the time-averaged value of each time series diagnostic, and is written as CSV
+or JSON.
+"""
+
+import os
+import re
+from logging import getLogger
+
+logger = getLogger(__name__)
+
+CHKPT_EXPR = re.compile(r"chkpt\.([0-9]+|final)\.pk$")
+
+
+def newest_checkpoint(filenames):
+    """
+    Return the newest checkpoint from a list of file names, or `None`.
+
+    A final checkpoint is newer than any numbered one.
+    """
+    matches = list(filter(None, (CHKPT_EXPR.search(f) for f in filenames)))
+
+    if not matches:
+        return None
+
+    key = lambda m: float("inf") if m.group(1) == "final" else int(m.group(1))
+    return max(matches, key=key).string
+
+
+def find_runs(root):
+    """
+    Generate the newest checkpoint in each directory below `root`.
+    """
+    for dirpath, dirnames, filenames in os.walk(root):
+        dirnames.sort()
+        filename = newest_checkpoint(filenames)
+
+        if filename is not None:
+            yield os.path.join(dirpath, filename)
+
+
+def diagnostic_names(chkpt):
+    """
+    Return a list of column names for the time series in a checkpoint.
+
+    The names are derived from the setup's diagnostics, e.g. `mdot_1` for
+    the mass accretion rate on the first point mass. If the setup can't be
+    constructed, or its diagnostics don't match the time series, the names
+    are `diagnostic_0`, `diagnostic_1`, and so on.
+    """
+    from sailfish.setup_base import SetupBase
+
+    timeseries = chkpt.get("timeseries") or []
+    num_columns = len(timeseries[0]) if timeseries else 0
+
+    try:
+        setup_class = SetupBase.find_setup_class(chkpt["setup_name"])
+        diagnostics = setup_class(**chkpt["model_parameters"]).diagnostics
+    except Exception:
+        diagnostics = []
+
+    names = []
+
+    for d in diagnostics:
+        name = d["quantity"]
+        if d.get("which_mass") is not None:
+            name += f"_{d['which_mass']}"
+        if d.get("radial_cut") is not None:
+            name += "_r{}-{}".format(*d["radial_cut"])
+        if d.get("hill_cut") is not None:
+            name += f"_hill{d['hill_cut']}"
+        while name in names:
+            name += "_"
+        names.append(name)
+
+    if len(names) != num_columns:
+        names = [f"diagnostic_{n}" for n in range(num_columns)]
+
+    return names
+
+
+def time_averages(timeseries, names, since=None):
+    """
+    Return a dictionary of the time-averaged value of each diagnostic.
+
+    If one of the diagnostics is named `time`, the averages are weighted by
+    the time intervals between samples (trapezoid rule), and samples before
+    the time `since` are excluded. Otherwise each sample has equal weight.
+    Complex-valued diagnostics are reported as the modulus of their average.
+    """
+    import numpy as np
+
+    if not timeseries:
+        return dict()
+
+    data = np.array(timeseries)
+
+    if "time" in names:
+        t = data[:, names.index("time")].real
+        if since is not None:
+            data = data[t >= since]
+            t = t[t >= since]
+    else:
+        t = None
+
+    result = dict()
+
+    for n, name in enumerate(names):
+        if name == "time" or len(data) == 0:
+            continue
+
+        y = data[:, n]
+
+        if t is not None and len(t) > 1 and t[-1] > t[0]:
+            mean = np.sum(0.5 * (y[1:] + y[:-1]) * np.diff(t)) / (t[-1] - t[0])
+        else:
+            mean = np.mean(y)
+
+        result[f"mean_{name}"] = float(abs(mean) if np.iscomplexobj(mean) else mean)
+
+    if t is not None and len(t) > 0:
+        result["average_start_time"] = float(t[0])
+        result["average_end_time"] = float(t[-1])
+
+    return result
+
+
+def run_record(filename, since=None):
+    """
+    Return a dictionary describing the run that wrote a checkpoint file.
+    """
+    from sailfish.driver import load_checkpoint
+
+    chkpt = load_checkpoint(filename)
+    record = dict(
+        run_directory=os.path.dirname(filename),
+        checkpoint=os.path.basename(filename),
+        setup_name=chkpt.get("setup_name"),
+        solver=chkpt.get("solver"),
+        time=chkpt.get("time"),
+        iteration=chkpt.get("iteration"),
+        mesh=str(chkpt.get("mesh")),
+    )
+
+    for key, val in (chkpt.get("model_parameters") or dict()).items():
+        record[f"param.{key}"] = val
+
+    names = diagnostic_names(chkpt)
+    record.update(time_averages(chkpt.get("timeseries"), names, since=since))
+
+    return record
+
+
+def write_manifest(records, filename):
+    """
+    Write a list of run records to a CSV or JSON file, by its extension.
+
+    Runs without a value in some column (e.g. a model parameter which only
+    some of the setups have) are left blank there in CSV, and omit the key
+    in JSON.
+    """
+    import csv, json
+
+    if filename.endswith(".json"):
+        with open(filename, "w") as f:
+            json.dump(records, f, indent=4, default=str)
+
+    elif filename.endswith(".csv"):
+        columns = []
+        for record in records:
+            columns.extend(k for k in record if k not in columns)
+
+        with open(filename, "w", newline="") as f:
+            writer = csv.DictWriter(f, fieldnames=columns)
+            writer.writeheader()
+            writer.writerows(records)
+    else:
+        raise ValueError(f"manifest file must be .csv or .json, got {filename}")
+
+    logger.info(f"write manifest {filename} with {len(records)} runs")
+
+
+def make_manifest(root, filename=None, since=None):
+    """
+    Scan a campaign directory, and write its manifest.
+
+    The manifest is written to `filename`, which defaults to `manifest.csv`
+    in the campaign directory. Runs whose checkpoint can't be loaded are
+    skipped with a warning. The list of run records is returned.
+    """
+    import sailfish.setups
+
+    records = []
+
+    for chkpt_file in find_runs(root):
+        try:
+            records.append(run_record(chkpt_file, since=since))
+        except Exception as e:
+            logger.warning(f"skipping {chkpt_file}: {e}")
+
+    write_manifest(records, filename or os.path.join(root, "manifest.csv"))
+    return records
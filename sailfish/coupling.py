@@ -0,0 +1,294 @@
+"""
+Couple solvers with different physics in neighboring regions of one mesh.
+
+Some problems need a different equation of state, or other physics, in
+different parts of the domain, e.g. an adiabatic inner region inside an
+isothermal outer one. A setup describes this with its `regions` property,
+which is a list of :py:class:`Region`, from the first edge of the mesh to
+the last. The mesh is split at the face nearest the outer edge of each
+region, and each region is solved by an ordinary solver on its part of the
+mesh, with the setup's physics updated by the region's.
+
+The regions take their Runge-Kutta stages together. Before each stage, the
+two-zone halos next to each interface are copied from the neighboring region,
+so each region sees the other's primitive data there, in the same way as the
+patches of one solver do. The fluxes each region computes through the
+interface differ, since each uses its own physics, so after every iteration
+both are replaced by their average, and the zones next to the interface are
+corrected by the difference, as in the refluxing step of mesh refinement.
+Mass, momentum, and the passive scalar are then conserved exactly across the
+interface. The energy of an isothermal region is only its kinetic energy,
+so its energy flux is left out of the average; where one side is
+isothermal, the energy flux is that of the adiabatic side.
+
+So far, `euler_1d` regions can be coupled, on planar cartesian or
+(non-expanding) log spherical meshes. The solution and primitive data are
+those of the regions, concatenated, so checkpoints look like those of a
+single solver.
+"""
+
+from logging import getLogger
+from typing import NamedTuple
+from sailfish.distributed import RankSetup, rank_mesh
+from sailfish.mesh import LogSphericalMesh, PlanarCartesianMesh
+from sailfish.solver_base import SolverBase
+from sailfish.subdivide import to_host
+
+logger = getLogger(__name__)
+
+COUPLED_SOLVERS = ("euler_1d",)
+
+ENERGY = 2
+
+
+class Region(NamedTuple):
+    """
+    A part of the mesh which is solved with its own physics.
+    """
+
+    outer: float = None
+    """ Coordinate of the outer edge of the region, or None for the last one """
+
+    physics: dict = dict()
+    """ Physics parameters which update those of the setup in this region """
+
+    solver: str = None
+    """ Name of the region's solver, or None for the setup's solver """
+
+
+def region_mesh(mesh, index_range):
+    """
+    Return the part of a 1D mesh in the given index range.
+    """
+    if isinstance(mesh, PlanarCartesianMesh):
+        return rank_mesh(mesh, index_range)
+
+    i0, i1 = index_range
+    faces = mesh.faces(i0, i1)
+
+    # The outer radius is half a zone past the last face, so that the number
+    # of zones it implies isn't rounded down.
+    k = 1.0 / mesh.num_zones_per_decade
+    sub = mesh._replace(r0=faces[0], r1=faces[-1] * 10 ** (0.5 * k))
+    assert sub.shape == (i1 - i0,)
+    return sub
+
+
+def interface_indexes(mesh, regions):
+    """
+    Return the index of the face nearest the outer edge of each region but
+    the last, and check that the regions are in order and not empty.
+    """
+    faces = mesh.faces()
+    indexes = list()
+
+    if len(regions) < 2:
+        raise ValueError("coupling needs at least two regions")
+
+    for region in regions[:-1]:
+        if region.outer is None:
+            raise ValueError("only the last region can omit its outer edge")
+
+        distance = [abs(x - region.outer) for x in faces]
+        indexes.append(distance.index(min(distance)))
+
+    if any(a >= b for a, b in zip([0] + indexes, indexes + [len(faces) - 1])):
+        raise ValueError(f"regions must be in order and not empty, got {regions}")
+
+    return indexes
+
+
+class Solver(SolverBase):
+    """
+    Coordinates the solvers of the regions of a mesh, exchanging halos and
+    matching the fluxes through the interfaces between them.
+    """
+
+    def __init__(
+        self,
+        regions,
+        setup=None,
+        mesh=None,
+        solution=None,
+        physics=dict(),
+        options=dict(),
+        **kwargs,
+    ):
+        from sailfish.solvers import make_solver
+
+        if type(mesh) not in (PlanarCartesianMesh, LogSphericalMesh):
+            raise ValueError("coupling needs a 1D cartesian or spherical mesh")
+
+        if getattr(mesh, "polar_grid", False):
+            raise ValueError("coupling does not support a polar grid")
+
+        indexes = interface_indexes(mesh, regions)
+        ranges = list(zip([0] + indexes, indexes + [mesh.shape[0]]))
+
+        self.mesh = mesh
+        self.ranges = ranges
+        self.regions = list()
+        self.region_physics = list()
+        self._physics = dict(physics)
+
+        for n, (region, (i0, i1)) in enumerate(zip(regions, ranges)):
+            solver_name = region.solver or setup.solver
+
+            if solver_name not in COUPLED_SOLVERS:
+                raise ValueError(f"solver {solver_name} can't be coupled")
+
+            region_physics = dict(physics, **region.physics)
+            solver = make_solver(
+                solver_name,
+                region_physics,
+                dict(options or dict(), record_face_fluxes=True),
+                setup=RankSetup(setup, n > 0, n < len(regions) - 1),
+                mesh=region_mesh(mesh, (i0, i1)),
+                solution=solution[i0:i1] if solution is not None else None,
+                **kwargs,
+            )
+            self.regions.append(solver)
+            self.region_physics.append(region_physics)
+            logger.info(f"region {n} solves zones [{i0}, {i1}) with {region_physics}")
+
+        self.num_guard = self.regions[0].num_guard
+
+    def energy_weight(self, n):
+        """
+        Return the weight of region `n` in the average of the energy fluxes
+        through its interfaces, which is zero if it's isothermal.
+        """
+        isothermal = self.region_physics[n].get("isothermal_sound_speed")
+        return 0.0 if isothermal is not None else 1.0
+
+    def set_bc(self, array):
+        """
+        Fill the guard zones of each region, and then copy the halos next to
+        each interface from the neighboring region.
+        """
+        ng = self.num_guard
+
+        for region in self.regions:
+            region.set_bc(array)
+
+        for a, b in zip(self.regions[:-1], self.regions[1:]):
+            pa = a.patches[-1]
+            pb = b.patches[0]
+
+            with pb.execution_context:
+                halo_b = to_host(getattr(pb, array)[ng : 2 * ng])
+
+            with pa.execution_context:
+                ua = getattr(pa, array)
+                halo_a = to_host(ua[-2 * ng : -ng])
+                ua[-ng:] = pa.xp.asarray(halo_b)
+
+            with pb.execution_context:
+                getattr(pb, array)[:ng] = pb.xp.asarray(halo_a)
+
+    def match_fluxes(self):
+        """
+        Replace the fluxes each region recorded through an interface over the
+        latest iteration by a common one, and correct the zones next to it.
+        """
+        ng = self.num_guard
+
+        for n, (a, b) in enumerate(zip(self.regions[:-1], self.regions[1:])):
+            pa = a.patches[-1]
+            pb = b.patches[0]
+            fa = to_host(pa.face_flux[-1])
+            fb = to_host(pb.face_flux[0])
+            flux = 0.5 * (fa + fb)
+            wa = self.energy_weight(n)
+            wb = self.energy_weight(n + 1)
+
+            if wa + wb > 0.0:
+                flux[ENERGY] = (wa * fa[ENERGY] + wb * fb[ENERGY]) / (wa + wb)
+
+            with pa.execution_context:
+                pa.conserved1[-ng - 1] += pa.xp.asarray(fa - flux)
+
+            with pb.execution_context:
+                pb.conserved1[ng] += pb.xp.asarray(flux - fb)
+
+    def concat(self, arrays):
+        import numpy as np
+
+        return np.concatenate([to_host(a) for a in arrays], axis=0)
+
+    @property
+    def solution(self):
+        return self.concat([region.solution for region in self.regions])
+
+    @property
+    def primitive(self):
+        return self.concat([region.primitive for region in self.regions])
+
+    @property
+    def primitive_fields(self):
+        return self.regions[0].primitive_fields
+
+    def primitive_array(self, guard_zones=False):
+        if not guard_zones:
+            return self.concat([r.primitive_array() for r in self.regions])
+
+        ng = self.num_guard
+
+        for region in self.regions:
+            for patch in region.patches:
+                patch.recompute_primitive()
+
+        self.set_bc("primitive1")
+        arrays = [
+            region.primitive_array(guard_zones=False) for region in self.regions
+        ]
+        first = self.regions[0].patches[0].primitive1[:ng]
+        last = self.regions[-1].patches[-1].primitive1[-ng:]
+        return self.concat([first] + arrays + [last])
+
+    def conserved_array(self):
+        return self.concat([region.conserved_array() for region in self.regions])
+
+    @property
+    def time(self):
+        return self.regions[0].time
+
+    @property
+    def nominal_time(self):
+        return self.regions[0].nominal_time
+
+    @property
+    def options(self):
+        return self.regions[0].options
+
+    @property
+    def physics(self):
+        return self._physics
+
+    @property
+    def recommended_cfl(self):
+        return min(region.recommended_cfl for region in self.regions)
+
+    @property
+    def maximum_cfl(self):
+        return min(region.maximum_cfl for region in self.regions)
+
+    def maximum_wavespeed(self):
+        return max(region.maximum_wavespeed() for region in self.regions)
+
+    def advance(self, dt):
+        for region in self.regions:
+            region.new_iteration()
+
+        for b in self.regions[0].runge_kutta_parameters:
+            for region in self.regions:
+                for patch in region.patches:
+                    patch.recompute_primitive()
+
+            self.set_bc("primitive1")
+
+            for region in self.regions:
+                for patch in region.patches:
+                    patch.advance_rk(b, dt)
+
+        self.match_fluxes()
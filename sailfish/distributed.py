@@ -0,0 +1,255 @@
+"""
+Distribute a solver over the ranks of an MPI job.
+
+Runs are launched with e.g. `mpiexec -n 4 sailfish shocktube --mpi`, and
+need the `mpi4py` package. The mesh is tiled along its first axis with
+`tile`, and each rank solves its own part, with an ordinary solver on a
+sub-mesh; that solver can in turn have several patches. Whenever the solver
+fills the guard zones of its patches, the two-zone halos on the edges the
+rank shares with its neighbors are exchanged with nonblocking sends and
+receives, and overwrite the boundary condition the solver applied there.
+
+The time step, and the time series diagnostics, are reduced over all the
+ranks, so every rank takes the same steps. The solution and primitive data
+are gathered on every rank, so checkpoints from a distributed run are the
+same as those from a serial one, and can be restarted either way; only the
+first rank writes the output files, while the others make the same solver
+calls for them (see `sailfish.driver.collect_outputs`). The wall time limit
+is checked against the slowest rank's clock, so all ranks stop together.
+
+So far, the solvers `euler_1d` and `srhd_1d` can be distributed, on planar
+cartesian meshes. The 2D solvers read the full extent of their mesh for
+e.g. the buffer zone, so they would need to be given it separately.
+"""
+
+from logging import getLogger
+from sailfish.mesh import PlanarCartesianMesh
+from sailfish.solver_base import SolverBase
+from sailfish.subdivide import to_host
+
+logger = getLogger(__name__)
+
+DISTRIBUTED_SOLVERS = ("euler_1d", "srhd_1d")
+
+
+def world():
+    """
+    Return the MPI communicator of all the ranks in the job.
+    """
+    from mpi4py import MPI
+
+    return MPI.COMM_WORLD
+
+
+def rank_mesh(mesh, index_range):
+    """
+    Return the part of a planar cartesian mesh in the given index range.
+    """
+    i0, i1 = index_range
+    faces = mesh.faces(i0, i1)
+    return PlanarCartesianMesh(faces[0], faces[-1], i1 - i0)
+
+
+class RankSetup:
+    """
+    A setup which has outflow boundary conditions on the edges a rank shares
+    with its neighbors, and is otherwise the same as another one. The guard
+    zones there are overwritten by the halo exchange, but e.g. fixed
+    boundaries would stop the solver from updating the zones next to them.
+    """
+
+    def __init__(self, setup, interior_left, interior_right):
+        self.setup = setup
+        self.interior_left = interior_left
+        self.interior_right = interior_right
+
+    def __getattr__(self, name):
+        return getattr(self.setup, name)
+
+    @property
+    def boundary_condition(self):
+        bc = self.setup.boundary_condition
+
+        if isinstance(bc, str):
+            bc = bc, bc
+
+        bcl, bcr = bc
+        return (
+            "outflow" if self.interior_left else bcl,
+            "outflow" if self.interior_right else bcr,
+        )
+
+
+class Solver(SolverBase):
+    """
+    Wraps the solver of one rank's part of the mesh, and makes the global
+    quantities it reports the same on every rank.
+    """
+
+    def __init__(
+        self,
+        solver_name,
+        comm=None,
+        setup=None,
+        mesh=None,
+        solution=None,
+        physics=dict(),
+        options=dict(),
+        **kwargs,
+    ):
+        from sailfish.solvers import make_solver
+
+        if solver_name not in DISTRIBUTED_SOLVERS:
+            raise ValueError(f"solver {solver_name} can't be distributed")
+
+        if not isinstance(mesh, PlanarCartesianMesh):
+            raise ValueError("distributed runs need a planar cartesian mesh")
+
+        comm = comm or world()
+        tiles = mesh.tile(comm.size)
+        i0, i1 = tiles[comm.rank]
+
+        self.comm = comm
+        self.mesh = mesh
+        self.tiles = tiles
+        self.solver = make_solver(
+            solver_name,
+            physics,
+            options,
+            setup=RankSetup(setup, comm.rank > 0, comm.rank < comm.size - 1),
+            mesh=rank_mesh(mesh, (i0, i1)),
+            solution=solution[i0:i1] if solution is not None else None,
+            **kwargs,
+        )
+        self.num_guard = self.solver.num_guard
+        self.solver_set_bc = self.solver.set_bc
+        self.solver.set_bc = self.set_bc
+
+        logger.info(f"rank {comm.rank} of {comm.size} solves zones [{i0}, {i1})")
+
+    def set_bc(self, array):
+        """
+        Fill the guard zones of the wrapped solver, and then exchange the
+        halos on the edges this rank shares with its neighbors.
+        """
+        from mpi4py import MPI
+        import numpy as np
+
+        self.solver_set_bc(array)
+
+        ng = self.num_guard
+        rank, size = self.comm.rank, self.comm.size
+        first = self.solver.patches[0]
+        last = self.solver.patches[-1]
+        requests = list()
+        received = list()
+
+        for patch, neighbor, inner, outer in [
+            (first, rank - 1, slice(ng, 2 * ng), slice(None, ng)),
+            (last, rank + 1, slice(-2 * ng, -ng), slice(-ng, None)),
+        ]:
+            if not 0 <= neighbor < size:
+                continue
+
+            with patch.execution_context:
+                data = getattr(patch, array)
+                send = np.ascontiguousarray(to_host(data[inner]))
+
+            recv = np.empty_like(send)
+            tag_send = 0 if neighbor < rank else 1
+            tag_recv = 1 - tag_send
+            requests.append(self.comm.Isend(send, dest=neighbor, tag=tag_send))
+            requests.append(self.comm.Irecv(recv, source=neighbor, tag=tag_recv))
+            received.append((patch, outer, send, recv))
+
+        MPI.Request.Waitall(requests)
+
+        for patch, outer, _, recv in received:
+            with patch.execution_context:
+                getattr(patch, array)[outer] = patch.xp.asarray(recv)
+
+    def gather(self, local):
+        """
+        Return the concatenation on the first axis of an array from each
+        rank, on every rank.
+        """
+        import numpy as np
+
+        return np.concatenate(self.comm.allgather(local), axis=0)
+
+    @property
+    def solution(self):
+        return self.gather(self.solver.solution)
+
+    @property
+    def primitive(self):
+        return self.gather(self.solver.primitive)
+
+    @property
+    def primitive_fields(self):
+        return self.solver.primitive_fields
+
+    def primitive_array(self, guard_zones=False):
+        local = self.solver.primitive_array(guard_zones)
+
+        if guard_zones:
+            ng = self.num_guard
+            lower = ng if self.comm.rank > 0 else 0
+            upper = -ng if self.comm.rank < self.comm.size - 1 else None
+            local = local[lower:upper]
+
+        return self.gather(local)
+
+    def conserved_array(self):
+        return self.gather(self.solver.conserved_array())
+
+    @property
+    def time(self):
+        return self.solver.time
+
+    @property
+    def nominal_time(self):
+        return self.solver.nominal_time
+
+    @property
+    def options(self):
+        return self.solver.options
+
+    @property
+    def physics(self):
+        return self.solver.physics
+
+    @property
+    def recommended_cfl(self):
+        return self.solver.recommended_cfl
+
+    @property
+    def maximum_cfl(self):
+        return self.solver.maximum_cfl
+
+    def maximum_wavespeed(self):
+        from mpi4py import MPI
+
+        return self.comm.allreduce(self.solver.maximum_wavespeed(), op=MPI.MAX)
+
+    def recommended_timestep(self, min_spacing, cfl_number):
+        from mpi4py import MPI
+
+        dt = self.solver.recommended_timestep(min_spacing, cfl_number)
+        return self.comm.allreduce(dt, op=MPI.MIN)
+
+    def advance(self, dt):
+        self.solver.advance(dt)
+
+    @property
+    def reduction_names(self):
+        return self.solver.reduction_names
+
+    @property
+    def reduction_units(self):
+        return self.solver.reduction_units
+
+    def reductions(self):
+        from mpi4py import MPI
+
+        return [self.comm.allreduce(x, op=MPI.SUM) for x in self.solver.reductions()]
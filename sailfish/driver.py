@@ -2,13 +2,23 @@
 Library functions and command-line access to the simulation driver.
 """
 
-import os, pickle, pathlib
+import os, pickle, pathlib, sys
 from typing import NamedTuple, Dict
 from logging import getLogger
-from sailfish.event import Recurrence, RecurringEvent, ParseRecurrenceError
+from sailfish.event import (
+    ActivityMonitor,
+    ActivityRule,
+    LINEAR,
+    ParseRecurrenceError,
+    Recurrence,
+    RecurringEvent,
+)
+from sailfish.analysis import AnalysisBase, AnalysisError
+from sailfish.parameters import check_parameters, solver_parameters
 from sailfish.setup_base import SetupBase, SetupError
 from sailfish.solver_base import SolverBase
 from sailfish.solvers import (
+    SolverError,
     SolverInitializationError,
     register_solver_extension,
     make_solver,
@@ -17,6 +27,21 @@ from sailfish.solvers import (
 logger = getLogger(__name__)
 user_build_config = dict()
 
+"""
+Exit codes of the command line interface, for batch systems and workflow
+managers. A run which hits its wall time limit writes a checkpoint, whose
+path is recorded in the status file, and can be resubmitted from it.
+"""
+EXIT_FINISHED = 0
+EXIT_FAILED = 1
+EXIT_WALL_LIMIT = 3
+
+"""
+Fraction of the milestone interval within which the solver time is taken to
+be on a milestone.
+"""
+MILESTONE_TOLERANCE = 1e-9
+
 
 class ConfigurationError(Exception):
     """An invalid runtime configuration"""
@@ -54,6 +79,34 @@ def keyed_value(item):
         raise ConfigurationError(f"badly formed model parameter {item}")
 
 
+def keyed_encoding(item):
+    """
+    Return a key, val pair from a "field=encoding" string, where the value
+    is parsed by `sailfish.fields.FieldEncoding.from_str`.
+    """
+    from sailfish.fields import FieldEncoding
+
+    try:
+        key, val = item.split("=", 1)
+        return key, FieldEncoding.from_str(val)
+
+    except ValueError as e:
+        raise ConfigurationError(f"badly formed field encoding {item}: {e}")
+
+
+def code_units(text):
+    """
+    Return the code units parsed by `sailfish.units.CodeUnits.from_str`.
+    """
+    from sailfish.units import CodeUnits
+
+    try:
+        return CodeUnits.from_str(text)
+
+    except ValueError as e:
+        raise ConfigurationError(f"badly formed code units {text}: {e}")
+
+
 def first_not_none(*args):
     for arg in args:
         if arg is not None:
@@ -90,6 +143,87 @@ def update_where_none(new, old, frozen=[]):
     return type(new)(**new_dict)
 
 
+def dict_differences(old_dict, new_dict, prefix=""):
+    """
+    Return a list of `(key, old, new)` tuples for the items which differ
+    between two dictionaries, including items missing from one of them.
+    Nested dictionaries are compared item by item, with dotted keys.
+    """
+    differences = []
+
+    for key in sorted(set(old_dict) | set(new_dict), key=str):
+        old_val = old_dict.get(key)
+        new_val = new_dict.get(key)
+
+        if type(old_val) is dict and type(new_val) is dict:
+            differences.extend(dict_differences(old_val, new_val, f"{prefix}{key}."))
+        elif old_val != new_val:
+            differences.append((f"{prefix}{key}", old_val, new_val))
+
+    return differences
+
+
+def restart_mismatches(chkpt, setup, mesh):
+    """
+    Return a list of `(what, old, new)` tuples for the aspects of a run which
+    can't change on restart, and which differ from the checkpoint: the
+    solver, and the mesh type, shape, and extent.
+    """
+    mismatches = []
+    old_mesh = chkpt["mesh"]
+
+    if chkpt["solver"] != setup.solver:
+        mismatches.append(("solver", chkpt["solver"], setup.solver))
+
+    if type(old_mesh) is not type(mesh):
+        mismatches.append(("mesh type", type(old_mesh).__name__, type(mesh).__name__))
+    else:
+        if old_mesh.topology.shape != mesh.topology.shape:
+            mismatches.append(
+                ("mesh shape", old_mesh.topology.shape, mesh.topology.shape)
+            )
+        old_extent = old_mesh.geometry.lower, old_mesh.geometry.upper
+        new_extent = mesh.geometry.lower, mesh.geometry.upper
+
+        if old_extent != new_extent:
+            mismatches.append(("mesh extent", old_extent, new_extent))
+
+    return mismatches
+
+
+def field_mismatches(chkpt, solver):
+    """
+    Return a list with a `(what, old, new)` tuple if the solver's primitive
+    fields differ from those in the checkpoint, or an empty list. Older
+    checkpoints which don't list their fields are not checked.
+    """
+    if "primitive_fields" not in chkpt:
+        return []
+
+    old_fields = [f["name"] for f in chkpt["primitive_fields"]]
+    new_fields = [f.name for f in solver.primitive_fields]
+
+    if old_fields != new_fields:
+        return [("primitive fields", old_fields, new_fields)]
+    else:
+        return []
+
+
+def check_restart_consistency(mismatches, force=False):
+    """
+    Log the given restart mismatches, and raise a `ConfigurationError` if
+    there are any, unless `force=True`.
+    """
+    for what, old, new in mismatches:
+        logger.warning(f"restart changes {what}: {old} -> {new}")
+
+    if mismatches and not force:
+        what = ", ".join(m[0] for m in mismatches)
+        raise ConfigurationError(
+            f"checkpoint has a different {what}; use --force to restart anyway"
+        )
+
+
 # The functions below were written to allow state to be written in terms of
 # builtin Python objects (no sailfish application classes). That would be good
 # practice because then pickle files can be opened on systems that don't have
@@ -140,6 +274,29 @@ def update_where_none(new, old, frozen=[]):
 #         return d
 
 
+def unit_conversions(state):
+    """
+    Return the unit conversions of the primitive fields and diagnostics,
+    which are written to each output file, or `None` if the run has no code
+    units; see `sailfish.units`.
+    """
+    from sailfish.units import conversion_table
+
+    if state.driver.code_units is None:
+        return None
+
+    fields = [(f.name, f.units) for f in state.solver.primitive_fields]
+    diagnostics = zip(state.solver.reduction_names, state.solver.reduction_units)
+    reference_time = state.setup.reference_time_scale
+
+    try:
+        return conversion_table(
+            state.driver.code_units, fields, diagnostics, reference_time
+        )
+    except ValueError as e:
+        raise ConfigurationError(e)
+
+
 def write_checkpoint(number, outdir, state):
     """
     Write the simulation state to a file, as a pickle.
@@ -155,14 +312,68 @@ def write_checkpoint(number, outdir, state):
         pathlib.Path(outdir).mkdir(parents=True, exist_ok=True)
         filename = os.path.join(outdir, filename)
 
-    state_checkpoint_dict = dict(
+    state_checkpoint_dict = checkpoint_dict(state)
+
+    with open(filename, "wb") as chkpt:
+        logger.info(f"write checkpoint {chkpt.name}")
+        pickle.dump(state_checkpoint_dict, chkpt)
+
+    return filename
+
+
+def checkpoint_dict(state):
+    """
+    Return the dictionary of the simulation state which is written to
+    checkpoints.
+    """
+    from sailfish.fields import field_dicts
+    from sailfish.kernel.system import build_info
+
+    state.synchronize()
+
+    if state.driver.chkpt_guard_zones:
+        primitive = state.solver.primitive_array(guard_zones=True)
+        num_guard = state.solver.num_guard
+    else:
+        primitive = state.solver.primitive
+        num_guard = 0
+
+    if state.driver.chkpt_conserved:
+        # The solution item is still what the solver restarts from. Solvers
+        # which evolve primitive data recompute the conserved data from it at
+        # the start of every iteration, so restarts are exact either way.
+        try:
+            extra_items = dict(conserved=state.solver.conserved_array())
+        except NotImplementedError as e:
+            raise ConfigurationError(e)
+    else:
+        extra_items = dict()
+
+    diagnostics = state.setup.checkpoint_diagnostics(state.solver.time)
+    diagnostics.update(state.solver.checkpoint_diagnostics())
+
+    return dict(
         iteration=state.iteration,
         time=state.solver.time,
         timestep_dt=state.timestep_dt,
         cfl_number=state.cfl_number,
         solution=state.solver.solution,
-        primitive=state.solver.primitive,
+        primitive=primitive,
+        primitive_num_guard=num_guard,
+        primitive_fields=field_dicts(state.solver.primitive_fields),
+        # Taking the face fluxes resets their average, so each checkpoint has
+        # those since the previous one.
+        face_fluxes=state.solver.face_fluxes(),
+        orbit_averager=state.orbit_averager,
+        spacetime=state.spacetime,
+        activity_monitor=state.activity_monitor,
+        phase_folder=state.phase_folder,
+        observables=state.observables,
+        build_info=build_info(
+            state.execution_mode, state.driver.execution_mode or "cpu"
+        ),
         timeseries=state.timeseries,
+        timeseries_names=state.solver.reduction_names,
         solver=state.setup.solver,
         solver_options=state.solver.options,
         event_states=state.event_states,
@@ -170,12 +381,346 @@ def write_checkpoint(number, outdir, state):
         model_parameters=state.setup.model_parameter_dict(),
         setup_name=state.setup.dash_case_class_name(),
         mesh=state.mesh,
-        **state.setup.checkpoint_diagnostics(state.solver.time),
+        unit_conversions=unit_conversions(state),
+        **diagnostics,
+        **extra_items,
     )
 
-    with open(filename, "wb") as chkpt:
-        logger.info(f"write checkpoint {chkpt.name}")
-        pickle.dump(state_checkpoint_dict, chkpt)
+
+def collect_outputs(name, state, final_chkpt=False):
+    """
+    Make the solver calls of the outputs of an event, without writing them.
+
+    In a distributed run, only the first rank writes the output files, but
+    the reductions, and the gathering of the solution and the primitive data,
+    are collective, so the other ranks call this in place of the writers.
+    """
+    if name == "timeseries":
+        append_timeseries(state)
+    elif name in ["checkpoint", "milestone", "wall_limit"]:
+        checkpoint_dict(state)
+    elif name == "end" and final_chkpt:
+        checkpoint_dict(state)
+    elif name == "snapshot":
+        state.solver.primitive_array()
+    elif name == "timestep_limiters":
+        try:
+            state.solver.timestep_limiters()
+        except NotImplementedError as e:
+            raise ConfigurationError(e)
+
+
+def write_snapshot(
+    number,
+    outdir,
+    state,
+    precision="float64",
+    analyses=(),
+    encodings=None,
+    file_format="pickle",
+):
+    """
+    Write the rest-frame primitive data and run details to a file, as a
+    pickle.
+
+    Snapshots are for analysis rather than restarts: they don't carry the
+    solver state, and the primitive data can be stored in single precision, as
+    recorded by the `precision` item, which halves the file size.
+    Checkpoints are always written in double precision.
+
+    The `encodings` are a dict of `sailfish.fields.FieldEncoding`, keyed by
+    field name. If it's given, each field is stored as its own array in the
+    `primitive_columns` item, with its encoding, or the `precision` if it has
+    none, in the `field_encodings` item, which
+    :py:class:`sailfish.products.Product` decodes. There's no `primitive` item
+    then, so the file's `format_version` is 2, which older readers reject.
+
+    The products of each of the named `analyses` are written to their own
+    files next to the snapshot, e.g. `snap.0000.radial-profiles.pk`. They're
+    computed from the data before it's encoded.
+
+    With a `file_format` of "npz" or "hdf5", the primitive data and metadata
+    are written with `sailfish.io` instead, to `snap.0000.npz` or
+    `snap.0000.h5`, which don't need sailfish to read. With "vti" or "xdmf",
+    they're written for ParaView or VisIt, to `snap.0000.vti`, or to
+    `snap.0000.xdmf` and the raw arrays in `snap.0000.bin`.
+
+    Return the names of the files written, the data file first.
+    """
+    from sailfish.fields import FieldEncoding, field_dicts
+    from sailfish.io import SNAPSHOT_EXTENSIONS, SNAPSHOT_WRITERS
+    from sailfish.products import SNAPSHOT_FORMAT_VERSION
+
+    if precision not in ["float32", "float64"]:
+        raise ConfigurationError(f"unknown snapshot precision {precision}")
+
+    if file_format not in SNAPSHOT_EXTENSIONS:
+        raise ConfigurationError(f"unknown snapshot format {file_format}")
+
+    if encodings and file_format != "pickle":
+        raise ConfigurationError("snapshot field encodings need the pickle format")
+
+    fields = state.solver.primitive_fields
+    names = [f.name for f in fields]
+
+    for name in encodings or dict():
+        if name not in names:
+            raise ConfigurationError(
+                f"no field {name} to encode, the fields are {', '.join(names)}"
+            )
+
+    filename = f"snap.{number:04d}.pk"
+
+    if outdir is not None:
+        pathlib.Path(outdir).mkdir(parents=True, exist_ok=True)
+        filename = os.path.join(outdir, filename)
+
+    data_filename = os.path.splitext(filename)[0] + SNAPSHOT_EXTENSIONS[file_format]
+    written_files = [data_filename]
+
+    if file_format == "xdmf":
+        written_files.append(os.path.splitext(data_filename)[0] + ".bin")
+
+    diagnostics = state.setup.checkpoint_diagnostics(state.solver.time)
+    diagnostics.update(state.solver.checkpoint_diagnostics())
+    primitive = state.solver.primitive_array()
+
+    snapshot_dict = dict(
+        iteration=state.iteration,
+        time=state.solver.time,
+        primitive=primitive.astype(precision),
+        primitive_fields=field_dicts(fields),
+        precision=precision,
+        solver=state.setup.solver,
+        model_parameters=state.setup.model_parameter_dict(),
+        setup_name=state.setup.dash_case_class_name(),
+        mesh=state.mesh,
+        unit_conversions=unit_conversions(state),
+        **diagnostics,
+    )
+
+    if encodings:
+        import numpy as np
+
+        written = dict(snapshot_dict)
+        columns = list()
+        field_encodings = list()
+        del written["primitive"]
+
+        for n, name in enumerate(names):
+            encoding = encodings.get(name, FieldEncoding())
+            encoding = encoding._replace(dtype=encoding.dtype or precision)
+            column = encoding.encode(primitive[..., n])
+
+            if np.isinf(column).any() and np.isfinite(primitive[..., n]).all():
+                logger.warning(f"field {name} overflows {encoding.dtype} in snapshot")
+
+            columns.append(column)
+            field_encodings.append(encoding._asdict())
+
+        written.update(
+            format_version=SNAPSHOT_FORMAT_VERSION,
+            primitive_columns=columns,
+            field_encodings=field_encodings,
+        )
+        description = f"{precision}, encoded {', '.join(encodings)}"
+    else:
+        written = snapshot_dict
+        description = precision
+
+    if file_format == "pickle":
+        with open(filename, "wb") as snap:
+            logger.info(f"write snapshot {snap.name} ({description})")
+            pickle.dump(written, snap)
+    else:
+        writer = SNAPSHOT_WRITERS[file_format]
+        logger.info(f"write snapshot {data_filename} ({description})")
+
+        try:
+            writer(data_filename, snapshot_dict, state.solver.physics)
+        except ValueError as e:
+            raise ConfigurationError(e)
+
+    for name in analyses:
+        analysis = AnalysisBase.find_analysis_class(name)()
+        products_filename = f"{os.path.splitext(filename)[0]}.{name}.pk"
+
+        try:
+            products = analysis.products(snapshot_dict)
+        except AnalysisError as e:
+            logger.warning(f"analysis {name} skipped: {e}")
+            continue
+
+        with open(products_filename, "wb") as outf:
+            logger.info(f"write analysis products {outf.name}")
+            pickle.dump(products, outf)
+
+        written_files.append(products_filename)
+
+    return written_files
+
+
+def write_timestep_limiters(number, outdir, state):
+    """
+    Write the solver's map of time step limiters to a file, as a pickle, and
+    log the most restrictive zone.
+    """
+    try:
+        limiters = state.solver.timestep_limiters()
+    except NotImplementedError as e:
+        raise ConfigurationError(e)
+
+    x, y = limiters["location"]
+    logger.info(
+        f"time step limited to {limiters['min_dt']:.3e} by the {limiters['term']} "
+        f"term in zone {limiters['zone']} at ({x:.4f}, {y:.4f})"
+    )
+
+    filename = os.path.join(outdir or ".", f"limiters.{number:04d}.pk")
+    pathlib.Path(outdir or ".").mkdir(parents=True, exist_ok=True)
+    limiters.update(iteration=state.iteration, time=state.solver.time)
+
+    with open(filename, "wb") as f:
+        logger.info(f"write time step limiters {f.name}")
+        pickle.dump(limiters, f)
+
+
+def check_sink_resolution(stats, min_zones, under_resolved):
+    """
+    Log a warning for each sink in `stats`, as returned by the solver's
+    `sink_resolution`, whose radius has come to span fewer than `min_zones`
+    zones, and a message for each one which is resolved again. The sinks
+    which were under-resolved before are in the set `under_resolved`, and
+    those which are now are returned.
+    """
+    result = set()
+
+    for sink in stats:
+        which = sink["which"]
+        x, y = sink["location"]
+
+        if sink["zones_per_radius"] < min_zones:
+            result.add(which)
+
+            if which not in under_resolved:
+                logger.warning(
+                    f"sink {which} is under-resolved at ({x:.4f}, {y:.4f}): its "
+                    f"radius spans {sink['zones_per_radius']:.2f} zones, "
+                    f"fewer than {min_zones}, so its accretion rate is unreliable"
+                )
+        elif which in under_resolved:
+            logger.info(f"sink {which} is resolved again at ({x:.4f}, {y:.4f})")
+
+    return result
+
+
+def spacetime_point_masses(setup, solver):
+    """
+    Return a tuple of the point masses at the solver time, from the checkpoint
+    diagnostics of the setup, updated by those of the solver.
+    """
+    diagnostics = setup.checkpoint_diagnostics(solver.time)
+    diagnostics.update(solver.checkpoint_diagnostics())
+    point_masses = diagnostics.get("point_masses", ())
+
+    if hasattr(point_masses, "mass"):
+        return (point_masses,)
+
+    return tuple(point_masses)
+
+
+def write_spacetime_diagram(outdir, state):
+    """
+    Write the arrays of the spacetime diagram to spacetime.pk, as a pickle.
+    """
+    filename = os.path.join(outdir or ".", "spacetime.pk")
+    pathlib.Path(outdir or ".").mkdir(parents=True, exist_ok=True)
+
+    with open(filename, "wb") as f:
+        logger.info(f"write spacetime diagram {f.name}")
+        pickle.dump(state.spacetime.arrays(), f)
+
+
+def write_observables(outdir, state):
+    """
+    Write the arrays of the synthetic observables to observables.pk, as a
+    pickle.
+    """
+    filename = os.path.join(outdir or ".", "observables.pk")
+    pathlib.Path(outdir or ".").mkdir(parents=True, exist_ok=True)
+
+    with open(filename, "wb") as f:
+        logger.info(f"write synthetic observables {f.name}")
+        pickle.dump(state.observables.arrays(), f)
+
+
+class TimeseriesOutput:
+    """
+    Writes the time series samples to timeseries.sfts in the output directory
+    as they become available.
+
+    The newest sample's reductions are usually still in progress, so it's
+    left for the next write unless `complete=True`. The file is continued
+    from the `num_existing` samples the run started with, e.g. from a
+    checkpoint; records beyond those are from an earlier run, and are
+    replaced.
+    """
+
+    def __init__(self, outdir, num_existing):
+        self.filename = os.path.join(outdir or ".", "timeseries.sfts")
+        self.num_existing = num_existing
+        self.writer = None
+
+    def write(self, state, complete=False):
+        from sailfish.timeseries import TimeseriesWriter, sample_columns
+
+        end = len(state.timeseries) - (0 if complete else 1)
+
+        if end <= 0:
+            return
+
+        samples = state.synchronize(end)
+
+        if self.writer is None:
+            pathlib.Path(self.filename).parent.mkdir(parents=True, exist_ok=True)
+            columns = sample_columns(
+                state.solver.reduction_names,
+                state.solver.reduction_units,
+                samples[0],
+            )
+            try:
+                self.writer = TimeseriesWriter(
+                    self.filename,
+                    columns,
+                    self.num_existing,
+                    unit_conversions(state),
+                )
+            except ValueError as e:
+                raise ConfigurationError(e)
+
+        start = self.writer.num_records
+
+        if start < end:
+            self.writer.append(samples[start:end])
+            logger.info(f"write timeseries samples {start + 1}-{end} {self.filename}")
+
+
+def write_status_file(filename, status):
+    """
+    Write a dictionary of the run status to a JSON file.
+
+    The file is replaced atomically, by writing to a temporary file in the
+    same directory and renaming it, so a process polling the file never sees
+    it partially written.
+    """
+    import json
+
+    temp = f"{filename}.tmp"
+
+    with open(temp, "w") as f:
+        json.dump(status, f, indent=4)
+
+    os.replace(temp, filename)
 
 
 def load_checkpoint(chkpt_file):
@@ -209,12 +754,24 @@ def newest_chkpt_in_directory(directory_name):
     raise ConfigurationError("the specified directory did not have a usable checkpoint")
 
 
+def next_milestone_time(time, interval):
+    """
+    Return the first whole multiple of the milestone interval after the given
+    time. A time within round-off of a milestone counts as being on it, so a
+    run restarted from a milestone checkpoint doesn't write it again.
+    """
+    return (int(time / interval + MILESTONE_TOLERANCE) + 1) * interval
+
+
 def append_timeseries(state):
     """
     Append to the driver state timeseries for post-processing.
+
+    The sample may hold deferred reductions, which are still being computed
+    on the GPU; see `DriverState.synchronize`.
     """
 
-    reductions = state.solver.reductions()
+    reductions = state.solver.deferred_reductions()
 
     if reductions:
         state.timeseries.append(reductions)
@@ -228,21 +785,92 @@ def append_timeseries(state):
 class DriverArgs(NamedTuple):
     """
     Contains data used by the driver.
+
+    Instances are pickled into checkpoints, and pickle restores the fields by
+    position, so new fields are added at the end to keep older checkpoints
+    readable.
     """
 
     setup_name: str = None
+    """ Name of the setup, or None on a restart """
     chkpt_file: str = None
+    """ Checkpoint file to restart from """
     model_parameters: dict = None
+    """ Model parameters given to the setup """
     solver_options: dict = None
+    """ Options passed to the solver """
     cfl_number: float = None
+    """ CFL parameter, the solver's recommendation if None """
     end_time: float = None
+    """ When to end the simulation, the setup's if None """
     execution_mode: str = None
+    """ Execution mode [cpu|omp|threads|checked|gpu] """
     fold: int = None
+    """ Iterations between messages and side effects """
     resolution: int = None
+    """ Grid resolution, the setup's default if None """
     num_patches: int = None
+    """ Number of patches for domain decomposition """
     events: Dict[str, Recurrence] = dict()
+    """ Recurrence rules of the events, by name """
     new_timestep_cadence: int = None
+    """ Iterations between recomputing the timestep dt """
     verbose_output: str = ""
+    """ Solver structs or kernel usage to print """
+    check_symmetry: bool = False
+    """ Measure the mirror-symmetry error after every iteration """
+    chkpt_guard_zones: bool = False
+    """ Write primitive data to checkpoints with the guard zones """
+    secular_duration: float = None
+    """ Time skipped by each secular fast-forward step """
+    orbit_averages: bool = False
+    """ Record the time series averaged over each binary orbit """
+    live_view_port: int = None
+    """ TCP port of the live view server """
+    live_view_rate: float = None
+    """ Maximum live view frames per second """
+    wall_time_limit: float = None
+    """ Hours after which to checkpoint and exit """
+    chkpt_conserved: bool = False
+    """ Also write the conserved variables to checkpoints """
+    snapshot_precision: str = None
+    """ Floating point precision of snapshot data """
+    spacetime_bins: int = None
+    """ Number of radial bins in spacetime diagrams """
+    analyses: list = None
+    """ Analyses whose products are written with each snapshot """
+    force_restart: bool = False
+    """ Restart even if the solver, mesh, or fields differ """
+    deterministic: bool = False
+    """ Build and run so that repeated runs are bitwise identical """
+    snapshot_activity: ActivityRule = None
+    """ Rule shortening the snapshot interval during activity """
+    phase_bins: int = None
+    """ Number of orbital phase bins of the folded time series """
+    observer: dict = None
+    """ Viewing geometry of the synthetic observables """
+    distributed: bool = False
+    """ Distribute the mesh over the ranks of an MPI job """
+    gpu_devices: list = None
+    """ IDs of the GPUs to assign patches to """
+    snapshot_fields: dict = None
+    """ Encodings of snapshot fields, by name """
+    snapshot_format: str = None
+    """ File format of snapshots """
+    sink_resolution: float = None
+    """ Minimum zones per sink radius before warning """
+    execution_fallback: bool = False
+    """ Run in the best available execution mode if the requested one isn't """
+    memory_map_directory: str = None
+    """ Directory of the files backing large host arrays (CPU modes) """
+    milestone_orbits: int = None
+    """ Binary orbits between milestone checkpoints """
+    code_units: object = None
+    """ Physical values of the code units, as a `sailfish.units.CodeUnits` """
+    resample: str = None
+    """ Method to resample a restart onto a new resolution [plm|bilinear] """
+    numa_policy: str = None
+    """ Placement of host memory pages in omp mode [first-touch|interleave] """
 
     def from_namespace(args):
         """
@@ -293,6 +921,28 @@ class DriverState(NamedTuple):
     setup: SetupBase
     cfl_number: float
     timestep_dt: float
+    orbit_averager: object = None
+    spacetime: object = None
+    activity_monitor: object = None
+    phase_folder: object = None
+    observables: object = None
+    execution_mode: str = "cpu"
+
+    def synchronize(self, end=None):
+        """
+        Wait for any deferred reductions in the time series, and replace them
+        with their values. Event handlers which read the time series should
+        call this first. Return the time series.
+
+        If `end` is given, only the samples before that index are resolved.
+        """
+        from sailfish.subdivide import DeferredReduction, resolve
+
+        for n, sample in enumerate(self.timeseries[:end]):
+            if any(isinstance(x, DeferredReduction) for x in sample):
+                self.timeseries[n] = [resolve(x) for x in sample]
+
+        return self.timeseries
 
 
 def simulate(driver):
@@ -307,7 +957,15 @@ def simulate(driver):
     pause points, defined by the `events` dictionary.
     """
     from sailfish import __version__ as version
-    from sailfish.kernel.system import configure_build, log_system_info, measure_time
+    from sailfish.kernel.system import (
+        build_config,
+        build_info,
+        configure_build,
+        log_system_info,
+        log_numa_placement,
+        measure_time,
+        resolve_execution_mode,
+    )
     from sailfish.event import Recurrence
     from sailfish import solvers
 
@@ -330,9 +988,15 @@ def simulate(driver):
 
         iteration = 0
         time = setup.start_time
+        chkpt = None
         event_states = {name: RecurringEvent() for name in driver.events}
         solution = None
         timeseries = list()
+        orbit_averager = None
+        spacetime = None
+        activity_monitor = None
+        phase_folder = None
+        observables = None
         dt = None
 
     elif driver.chkpt_file:
@@ -346,7 +1010,8 @@ def simulate(driver):
         logger.info(f"load checkpoint {driver.chkpt_file}")
         chkpt = load_checkpoint(driver.chkpt_file)
         setup_class = SetupBase.find_setup_class(chkpt["setup_name"])
-        driver = update_where_none(driver, chkpt["driver"], frozen=["resolution"])
+        frozen = [] if driver.resample else ["resolution"]
+        driver = update_where_none(driver, chkpt["driver"], frozen=frozen)
 
         update_dict_where_none(
             driver.model_parameters,
@@ -390,6 +1055,24 @@ def simulate(driver):
         except KeyError:
             logger.warning("older checkpoint version: no timeseries")
 
+        orbit_averager = chkpt.get("orbit_averager")
+        spacetime = chkpt.get("spacetime")
+        activity_monitor = chkpt.get("activity_monitor")
+        phase_folder = chkpt.get("phase_folder")
+        observables = chkpt.get("observables")
+
+        for key, old, new in dict_differences(
+            chkpt["model_parameters"], setup.model_parameter_dict()
+        ):
+            logger.info(f"restart changes model parameter {key}: {old} -> {new}")
+
+        excluded = ["setup_name", "chkpt_file", "model_parameters", "solver_options"]
+        for key, old, new in dict_differences(
+            chkpt["driver"]._asdict(), driver._asdict()
+        ):
+            if key.split(".")[0] not in excluded:
+                logger.info(f"restart changes driver argument {key}: {old} -> {new}")
+
         for event in driver.events:
             if event not in event_states:
                 event_states[event] = RecurringEvent()
@@ -416,12 +1099,85 @@ def simulate(driver):
     the platform (Linux or MacOS), but in the future these should also be
     extensible by a system-specific rc-style configuration file.
     """
-    configure_build(**user_build_config, execution_mode=driver.execution_mode)
-    log_system_info(driver.execution_mode or "cpu")
+    build_options = dict(user_build_config)
+
+    try:
+        mode = resolve_execution_mode(
+            driver.execution_mode or "cpu", driver.execution_fallback
+        )
+    except ValueError as e:
+        raise ConfigurationError(f"{e} (use --mode-fallback to run in another)")
+
+    if driver.deterministic:
+        build_options["deterministic"] = True
+
+    if driver.gpu_devices is not None:
+        build_options["gpu_devices"] = driver.gpu_devices
+
+    if driver.memory_map_directory is not None:
+        build_options["memory_map_directory"] = driver.memory_map_directory
+
+    if driver.numa_policy is not None:
+        build_options["numa_policy"] = driver.numa_policy
+
+    try:
+        configure_build(**build_options, execution_mode=mode)
+    except ValueError as e:
+        raise ConfigurationError(e)
+    log_system_info(mode)
+
+    if chkpt is not None and build_config["deterministic"]:
+        for key, old, new in dict_differences(
+            chkpt.get("build_info", dict()), build_info(mode)
+        ):
+            logger.warning(
+                f"build detail {key} differs from the checkpoint ({old} -> {new}), "
+                "the trajectory may not be reproduced"
+            )
 
-    mode = driver.execution_mode or "cpu"
     fold = driver.fold or 10
     mesh = setup.mesh(driver.resolution)
+
+    try:
+        mesh.validate()
+    except ValueError as e:
+        raise ConfigurationError(f"invalid mesh {mesh}: {e}")
+
+    if chkpt is not None:
+        mismatches = restart_mismatches(chkpt, setup, mesh)
+
+        if driver.resample and any(m[0] == "mesh shape" for m in mismatches):
+            """
+            A restart at a new resolution with `--resample` moves the solution
+            onto the new mesh, by restriction or prolongation; see
+            `sailfish.resample`. The spacetime diagram and the synthetic
+            observables are binned on the old mesh, so they start over.
+            """
+            from sailfish.resample import RESAMPLED_SOLVERS, resample_solution
+
+            if setup.solver not in RESAMPLED_SOLVERS or setup.regions is not None:
+                raise ConfigurationError(f"can't resample a {setup.solver} solution")
+
+            try:
+                solution = resample_solution(
+                    solution, chkpt["mesh"], mesh, driver.resample
+                )
+            except ValueError as e:
+                raise ConfigurationError(e)
+
+            logger.info(f"resample ({driver.resample}) {chkpt['mesh']} -> {mesh}")
+            mismatches = [m for m in mismatches if m[0] != "mesh shape"]
+            spacetime = None
+            observables = None
+
+        check_restart_consistency(mismatches, driver.force_restart)
+
+    try:
+        for name in driver.analyses or []:
+            AnalysisBase.find_analysis_class(name)
+    except AnalysisError as e:
+        raise ConfigurationError(e)
+
     end_time = first_not_none(driver.end_time, setup.default_end_time, float("inf"))
     reference_time = setup.reference_time_scale
     new_timestep_cadence = driver.new_timestep_cadence or 1
@@ -436,52 +1192,424 @@ def simulate(driver):
     ):
         logger.info(f"options struct (cmdline -> solver) {driver.solver_options}")
 
-    solver = make_solver(
-        setup.solver,
-        setup.physics,
-        driver.solver_options,
-        setup=setup,
-        mesh=mesh,
-        time=time,
-        solution=solution,
-        num_patches=driver.num_patches or 1,
-        mode=mode,
-    )
-
-    if driver.cfl_number is not None and driver.cfl_number > solver.maximum_cfl:
-        raise ConfigurationError(
-            f"cfl number {driver.cfl_number} "
-            f"is greater than {solver.maximum_cfl}, "
-            f"max allowed by solver {setup.solver}"
-        )
+    if setup.regions is None:
+        try:
+            parameters = solver_parameters(setup.solver)
+            check_parameters(parameters, driver.solver_options or dict(), "option")
+        except ValueError as e:
+            raise ConfigurationError(e)
 
-    cfl_number = driver.cfl_number or solver.recommended_cfl
+    if driver.distributed and setup.regions is not None:
+        raise ConfigurationError("a setup with coupled regions can't be distributed")
 
-    for name, event in driver.events.items():
-        logger.info(f"recurrence for {name} event is {event}")
+    if driver.distributed and setup.refinement is not None:
+        raise ConfigurationError("a setup with refined regions can't be distributed")
 
-    logger.info(f"run until t={end_time}")
-    logger.info(f"CFL number is {cfl_number}")
-    logger.info(f"simulation time / user time is {reference_time:0.4f}")
-    logger.info(f"recompute dt every {new_timestep_cadence} iterations")
-    setup.print_model_parameters(newlines=True, logger=main_logger)
+    if setup.regions is not None and setup.refinement is not None:
+        raise ConfigurationError("a setup can't have both coupled and refined regions")
 
-    def grab_state():
+    if driver.distributed:
         """
-        Collect items from the driver and solver state, as well as run
-        details, sufficient for restarts and post processing.
+        A distributed run gives each MPI rank its own part of the mesh, with
+        a solver wrapped so that the time step and diagnostics are global,
+        and the solution is gathered on every rank. See
+        `sailfish.distributed`.
         """
-        return DriverState(
-            iteration=iteration,
-            driver=driver,
-            mesh=mesh,
-            timeseries=timeseries,
-            event_states=event_states,
-            solver=solver,
-            setup=setup,
-            cfl_number=cfl_number,
-            timestep_dt=dt,
-        )
+        from sailfish.distributed import Solver as DistributedSolver
+
+        try:
+            solver = DistributedSolver(
+                setup.solver,
+                physics=setup.physics,
+                options=driver.solver_options,
+                setup=setup,
+                mesh=mesh,
+                time=time,
+                solution=solution,
+                num_patches=driver.num_patches or 1,
+                mode=mode,
+            )
+        except ValueError as e:
+            raise ConfigurationError(e)
+    elif setup.regions is not None:
+        """
+        A setup with regions is solved by a coordinator, which couples a
+        solver for each region across the interfaces between them. See
+        `sailfish.coupling`.
+        """
+        from sailfish.coupling import Solver as CoupledSolver
+
+        try:
+            solver = CoupledSolver(
+                setup.regions,
+                physics=setup.physics,
+                options=driver.solver_options,
+                setup=setup,
+                mesh=mesh,
+                time=time,
+                solution=solution,
+                num_patches=driver.num_patches or 1,
+                mode=mode,
+            )
+        except ValueError as e:
+            raise ConfigurationError(e)
+    elif setup.refinement is not None:
+        """
+        A setup with refined regions is solved by a solver for each level of
+        refinement, which exchange guard zones, restricted solutions, and
+        fluxes across the level boundaries. See `sailfish.refinement`.
+        """
+        from sailfish.refinement import Solver as RefinedSolver
+
+        try:
+            solver = RefinedSolver(
+                setup.refinement,
+                physics=setup.physics,
+                options=driver.solver_options,
+                setup=setup,
+                mesh=mesh,
+                time=time,
+                solution=solution,
+                num_patches=driver.num_patches or 1,
+                mode=mode,
+            )
+        except ValueError as e:
+            raise ConfigurationError(e)
+    else:
+        solver = make_solver(
+            setup.solver,
+            setup.physics,
+            driver.solver_options,
+            setup=setup,
+            mesh=mesh,
+            time=time,
+            solution=solution,
+            num_patches=driver.num_patches or 1,
+            mode=mode,
+        )
+
+    if chkpt is not None:
+        for key, old, new in dict_differences(chkpt["solver_options"], solver.options):
+            logger.info(f"restart changes solver option {key}: {old} -> {new}")
+
+        check_restart_consistency(field_mismatches(chkpt, solver), driver.force_restart)
+
+    if mode == "omp":
+        log_numa_placement()
+
+    if "kernels" in driver.verbose_output:
+        from sailfish.kernel.library import loaded_libraries
+
+        if mode != "gpu":
+            logger.info("kernel resource usage is only reported in gpu mode")
+
+        for lib in loaded_libraries:
+            lib.log_kernel_report()
+
+    if "timing" in driver.verbose_output:
+        from sailfish.kernel.library import enable_kernel_timing, kernel_timings
+
+        logger.info("time each kernel, and report the share of each fold")
+        enable_kernel_timing()
+
+    if driver.cfl_number is not None and driver.cfl_number > solver.maximum_cfl:
+        raise ConfigurationError(
+            f"cfl number {driver.cfl_number} "
+            f"is greater than {solver.maximum_cfl}, "
+            f"max allowed by solver {setup.solver}"
+        )
+
+    cfl_number = driver.cfl_number or solver.recommended_cfl
+
+    if driver.check_symmetry:
+        """
+        In symmetry-checking mode, the mirror-symmetry error of the solution
+        is measured after every iteration, and the largest value in each fold
+        is logged with the iteration message. The initial condition has to be
+        symmetric (to round-off in the zone center coordinates) for the
+        measurement to be meaningful.
+        """
+        from sailfish.symmetry import check_symmetric_mesh, max_symmetry_error
+
+        try:
+            check_symmetric_mesh(mesh)
+        except ValueError as e:
+            raise ConfigurationError(e)
+
+        initial_error = max_symmetry_error(solver.primitive_array())
+
+        if initial_error > 1e-10:
+            raise ConfigurationError(
+                f"initial condition is not mirror-symmetric (error {initial_error:.3e})"
+            )
+        logger.info(f"symmetry check enabled, initial error {initial_error:.3e}")
+
+    if driver.sink_resolution is not None:
+        """
+        With the sink resolution check, the resolution of each sink, and the
+        Mach number and time step limit around it, are measured after every
+        fold and logged with the iteration message. A warning is logged when
+        a sink radius comes to span fewer than the given number of zones, as
+        the masses move or the mesh changes, since that silently corrupts its
+        accretion rate.
+        """
+        try:
+            sink_stats = solver.sink_resolution()
+        except NotImplementedError as e:
+            raise ConfigurationError(e)
+
+        under_resolved = check_sink_resolution(
+            sink_stats, driver.sink_resolution, set()
+        )
+        logger.info(f"sink resolution check enabled, {len(sink_stats)} sinks")
+
+    if "secular" in driver.events:
+        """
+        In secular mode, the solver periodically fast-forwards the disk by
+        `secular_duration` with an azimuthally averaged 1D model. The time of
+        the last occurrence of each linearly recurring event is advanced by
+        the same amount, so the events keep their spacing in 2D run time
+        rather than catching up on the skipped interval.
+        """
+        if driver.secular_duration is None:
+            raise ConfigurationError("the secular event needs a secular duration")
+        logger.info(f"secular steps fast-forward by {driver.secular_duration}")
+
+    if driver.orbit_averages and orbit_averager is None:
+        """
+        Orbit averages integrate the solver reductions every iteration, over
+        windows of one orbital period of the setup's binary, starting at the
+        current time. The completed orbits are written to checkpoints.
+        """
+        from sailfish.orbit_average import OrbitAverager
+
+        try:
+            period = setup.orbital_elements.period
+        except AttributeError:
+            raise ConfigurationError("orbit averages need a setup with a binary orbit")
+
+        orbit_averager = OrbitAverager(period, start_time=solver.time)
+
+    if orbit_averager is not None:
+        reductions = solver.reductions()
+
+        if not reductions:
+            raise ConfigurationError("orbit averages need solver reductions")
+
+        orbit_averager.add(solver.time, reductions)
+        logger.info(f"orbit averages over period {orbit_averager.period:0.4f}")
+
+    if driver.milestone_orbits is not None:
+        """
+        Milestone checkpoints are written every `milestone_orbits` periods of
+        the setup's binary, counted from t=0. The iteration before each one is
+        shortened to land on it exactly, so runs branched from a milestone
+        start from the same, phase-aligned state. They're tagged by the orbit
+        number, as chkpt.orbit.0500.pk, and aren't picked by restarts from a
+        directory, or pruned by the uploader.
+        """
+        try:
+            period = setup.orbital_elements.period
+        except AttributeError:
+            raise ConfigurationError("milestones need a setup with a binary orbit")
+
+        if driver.milestone_orbits < 1:
+            raise ConfigurationError("milestones must be at least one orbit apart")
+
+        milestone_interval = driver.milestone_orbits * period
+        milestone_time = next_milestone_time(solver.time, milestone_interval)
+        logger.info(f"milestone checkpoints every {driver.milestone_orbits} orbits")
+    else:
+        milestone_time = None
+
+    if driver.phase_bins is not None and phase_folder is None:
+        """
+        Phase folding integrates the solver reductions every iteration over
+        bins of the binary orbital phase, and accumulates the mean and the
+        orbit-to-orbit variance of each bin. The folder is written to
+        checkpoints; its `profiles()` method returns the folded curves. See
+        `sailfish.orbit_average.PhaseFolder`.
+        """
+        from sailfish.orbit_average import PhaseFolder
+
+        try:
+            period = setup.orbital_elements.period
+        except AttributeError:
+            raise ConfigurationError("phase folding needs a setup with a binary orbit")
+
+        try:
+            phase_folder = PhaseFolder(period, driver.phase_bins, solver.time)
+        except ValueError as e:
+            raise ConfigurationError(e)
+
+    if phase_folder is not None:
+        reductions = solver.reductions()
+
+        if not reductions:
+            raise ConfigurationError("phase folding needs solver reductions")
+
+        phase_folder.add(solver.time, reductions)
+        logger.info(f"phase folding on {phase_folder.num_bins} bins per orbit")
+
+    if "spacetime" in driver.events:
+        """
+        On each spacetime event, the azimuthally averaged profiles of surface
+        density and radial mass flux, and the mass-weighted profile and disk
+        average of the eccentricity vector, are appended to a spacetime
+        diagram. The eccentricity is relative to the point masses in the
+        checkpoint diagnostics, so it follows a live binary. The diagram is
+        written to checkpoints, and to spacetime.pk by the command line
+        interface. See `sailfish.spacetime`.
+        """
+        from sailfish.mesh import PlanarCartesian2DMesh
+        from sailfish.spacetime import SpacetimeDiagram
+
+        if not isinstance(mesh, PlanarCartesian2DMesh):
+            raise ConfigurationError("spacetime diagrams need a 2D cartesian mesh")
+
+        try:
+            spacetime_fields = [solver.field_index(f) for f in ["sigma", "vx", "vy"]]
+        except KeyError as e:
+            raise ConfigurationError(f"spacetime diagrams need {e}")
+
+        if spacetime is None:
+            try:
+                spacetime = SpacetimeDiagram(mesh, driver.spacetime_bins or 200)
+            except ValueError as e:
+                raise ConfigurationError(e)
+
+    if "observables" in driver.events:
+        """
+        On each observables event, a sky image and a line profile of a
+        Sigma^2 emission proxy are computed on the devices, for the viewing
+        geometry given by the `observer` parameters, and appended to a record
+        of synthetic observables. It is written to checkpoints, and to
+        observables.pk by the command line interface. See
+        `sailfish.observables`.
+        """
+        from sailfish.mesh import PlanarCartesian2DMesh
+        from sailfish.observables import Observer, SyntheticObservables
+
+        if not isinstance(mesh, PlanarCartesian2DMesh):
+            raise ConfigurationError("synthetic observables need a 2D cartesian mesh")
+
+        try:
+            observer = Observer(**driver.observer or dict())
+            observer.validate()
+        except (TypeError, ValueError) as e:
+            raise ConfigurationError(f"bad observer: {e}")
+
+        try:
+            solver.rest_frame_patches()
+        except NotImplementedError as e:
+            raise ConfigurationError(e)
+
+        if observables is not None and observables.observer != observer:
+            logger.warning("the observer has changed, restarting synthetic observables")
+            observables = None
+
+        if observables is None:
+            observables = SyntheticObservables(mesh, observer)
+
+    if driver.snapshot_activity is not None:
+        """
+        With an activity rule, the snapshot interval is shortened while a
+        time series column (or its rate of change) is large, e.g. during an
+        accretion flare, and restored when it's quiet again. The monitor is
+        updated from each time series sample, so its responsiveness is set by
+        the timeseries event cadence.
+        """
+        rule = driver.snapshot_activity
+
+        if "snapshot" not in driver.events or "timeseries" not in driver.events:
+            raise ConfigurationError("snapshot activity needs snapshot and timeseries")
+
+        if rule.column not in solver.reduction_names:
+            raise ConfigurationError(
+                f"no time series column named {rule.column}, the columns are "
+                f"{solver.reduction_names}"
+            )
+
+        if activity_monitor is None or activity_monitor.rule != rule:
+            column_index = solver.reduction_names.index(rule.column)
+            activity_monitor = ActivityMonitor(rule, column_index)
+
+        from sailfish.subdivide import resolve
+
+        logger.info(f"snapshot activity rule is {rule}")
+    else:
+        activity_monitor = None
+
+    if driver.live_view_port is not None:
+        """
+        Downsampled frames of the primitive data are streamed to any viewers
+        connected to the live view port, at most live_view_rate times per
+        (wall-clock) second, and only between folds. See `sailfish.live`.
+        A frame is published at the end of the fold after it's taken, so
+        that solvers which download it on a second stream (the cbdiso_2d
+        `copy_stream` option) overlap the download with that fold.
+        """
+        import time as wall_clock
+        from sailfish.live import FrameServer
+        from sailfish.subdivide import resolve
+
+        live_server = FrameServer(driver.live_view_port)
+        live_interval = 1.0 / (driver.live_view_rate or 2.0)
+        live_last_frame = 0.0
+        live_frame = None
+    else:
+        live_server = None
+        live_frame = None
+
+    if driver.wall_time_limit is not None:
+        """
+        With a wall time limit (in hours), the run stops at the end of the
+        first fold after which another fold of the same duration would exceed
+        the limit, and the final event is "wall_limit" rather than "end". The
+        limit is measured from this point, so it should leave a margin for
+        startup and for writing the checkpoint.
+        """
+        from time import perf_counter
+
+        wall_start = perf_counter()
+        logger.info(f"wall time limit is {driver.wall_time_limit} hours")
+
+    final_event = "end"
+
+    for name, event in driver.events.items():
+        logger.info(f"recurrence for {name} event is {event}")
+
+    logger.info(f"run until t={end_time}")
+    logger.info(f"CFL number is {cfl_number}")
+
+    if driver.code_units is not None:
+        logger.info(f"code units are {driver.code_units}")
+    logger.info(f"simulation time / user time is {reference_time:0.4f}")
+    logger.info(f"recompute dt every {new_timestep_cadence} iterations")
+    setup.print_model_parameters(newlines=True, logger=main_logger)
+
+    def grab_state():
+        """
+        Collect items from the driver and solver state, as well as run
+        details, sufficient for restarts and post processing.
+        """
+        return DriverState(
+            iteration=iteration,
+            driver=driver,
+            mesh=mesh,
+            timeseries=timeseries,
+            event_states=event_states,
+            solver=solver,
+            setup=setup,
+            cfl_number=cfl_number,
+            timestep_dt=dt,
+            orbit_averager=orbit_averager,
+            spacetime=spacetime,
+            activity_monitor=activity_monitor,
+            phase_folder=phase_folder,
+            observables=observables,
+            execution_mode=mode,
+        )
 
     while True:
         siml_time = solver.time
@@ -496,27 +1624,204 @@ def simulate(driver):
         for name in event_states:
             event = driver.events[name]
             state = event_states[name]
+
+            if name == "snapshot" and activity_monitor is not None:
+                event = activity_monitor.recurrence(event)
+
             if event_states[name].is_due(user_time, event):
                 event_states[name] = state.next(user_time, event)
-                yield name, state.number, grab_state()
+
+                if name == "secular":
+                    try:
+                        solver.secular_step(driver.secular_duration * reference_time)
+                    except (NotImplementedError, ValueError) as e:
+                        raise ConfigurationError(e)
+                    if orbit_averager is not None:
+                        orbit_averager.interrupt(solver.time)
+                    if phase_folder is not None:
+                        phase_folder.interrupt(solver.time)
+                    if milestone_time is not None:
+                        milestone_time = next_milestone_time(
+                            solver.time, milestone_interval
+                        )
+                    for key, s in event_states.items():
+                        if driver.events[key].kind == LINEAR and s.number > 0:
+                            last_time = s.last_time + driver.secular_duration
+                            event_states[key] = s._replace(last_time=last_time)
+                elif name == "spacetime":
+                    primitive = solver.primitive_array()
+                    spacetime.add(
+                        user_time,
+                        mesh,
+                        *(primitive[..., q] for q in spacetime_fields),
+                        point_masses=spacetime_point_masses(setup, solver),
+                    )
+                elif name == "observables":
+                    observables.add(user_time, solver.rest_frame_patches())
+                else:
+                    event_data = dict(
+                        kind="event", name=name, number=state.number, time=user_time
+                    )
+                    logger.info(
+                        f"event {name} {state.number}",
+                        extra=dict(data=event_data, json_only=True),
+                    )
+                    num_samples = len(timeseries)
+                    yield name, state.number, grab_state()
+
+                    if (
+                        name == "timeseries"
+                        and activity_monitor is not None
+                        and len(timeseries) > num_samples
+                    ):
+                        value = resolve(timeseries[-1][activity_monitor.column_index])
+                        if activity_monitor.add(user_time, value):
+                            activity = "on" if activity_monitor.active else "off"
+                            logger.info(
+                                f"snapshot activity {activity} at t={user_time:0.3f}, "
+                                f"level {activity_monitor.level:.3e}"
+                            )
+
+        if milestone_time is not None and milestone_time - solver.time <= (
+            MILESTONE_TOLERANCE * milestone_interval
+        ):
+            orbit = round(milestone_time / milestone_interval) * driver.milestone_orbits
+            milestone_time += milestone_interval
+            yield "milestone", orbit, grab_state()
 
         if end_time is not None and user_time >= end_time:
             break
 
+        fold_symmetry_error = 0.0
+
         with measure_time(mode) as fold_time:
-            for _ in range(fold):
+            for num_steps in range(1, fold + 1):
+                # The recommended timestep reads the maximum wavespeed, which
+                # waits for the device. Apart from the fold timing, it's the only
+                # synchronization in the step path; its frequency is the cadence.
                 if dt is None or (iteration % new_timestep_cadence == 0):
                     dx = mesh.min_spacing(siml_time)
-                    dt = dx / solver.maximum_wavespeed() * cfl_number
-                solver.advance(dt)
+                    dt = solver.recommended_timestep(dx, cfl_number)
+
+                # The fold ends early on a milestone, which is written before
+                # the next one starts.
+                if milestone_time is not None:
+                    step_dt = min(dt, milestone_time - solver.time)
+                else:
+                    step_dt = dt
+
+                solver.advance(step_dt)
                 iteration += 1
 
-        Mzps = mesh.num_total_zones / fold_time() * 1e-6 * fold
-        main_logger.info(
-            f"[{iteration:04d}] t={user_time:0.3f} dt={dt:.3e} Mzps={Mzps:.3f}"
+                if orbit_averager is not None or phase_folder is not None:
+                    reductions = solver.reductions()
+
+                if orbit_averager is not None:
+                    orbit_averager.add(solver.time, reductions)
+
+                if phase_folder is not None:
+                    phase_folder.add(solver.time, reductions)
+
+                if driver.check_symmetry:
+                    error = max_symmetry_error(solver.primitive_array())
+                    fold_symmetry_error = max(fold_symmetry_error, error)
+
+                if step_dt < dt:
+                    break
+
+        Mzps = mesh.num_total_zones / fold_time() * 1e-6 * num_steps
+        message = f"[{iteration:04d}] t={user_time:0.3f} dt={dt:.3e} Mzps={Mzps:.3f}"
+
+        if driver.check_symmetry:
+            message += f" sym={fold_symmetry_error:.3e}"
+
+        step_data = dict(
+            kind="step", iteration=iteration, time=user_time, dt=dt, Mzps=Mzps
         )
 
-    yield "end", None, grab_state()
+        if driver.check_symmetry:
+            step_data["symmetry_error"] = fold_symmetry_error
+
+        if driver.sink_resolution is not None:
+            sink_stats = solver.sink_resolution()
+            under_resolved = check_sink_resolution(
+                sink_stats, driver.sink_resolution, under_resolved
+            )
+            step_data["sinks"] = sink_stats
+
+            for sink in sink_stats:
+                message += (
+                    f" sink{sink['which']}={sink['zones_per_radius']:.2f}z"
+                    f"/M{sink['mach_number']:.1f}"
+                )
+
+        if "timing" in driver.verbose_output:
+            step_data["kernels"] = kernel_timings()
+
+        budget = solver.conservation_budget()
+
+        if budget is not None:
+            step_data["conservation"] = dict(
+                terms={k: v.tolist() for k, v in budget["terms"].items()},
+                residual=budget["residual"].tolist(),
+            )
+            mass = max(abs(budget["final"][0]), 1e-300)
+            message += f" dM={budget['residual'][0] / mass:+.1e}"
+
+        main_logger.info(message, extra=dict(data=step_data))
+
+        for timing in step_data.get("kernels", []):
+            logger.info(
+                f"+-- {timing['kernel']}: {timing['calls']} calls, "
+                f"{timing['seconds'] / fold_time():.1%} of the fold, "
+                f"Mzps={timing['Mzps']:.3f}"
+            )
+
+        if budget is not None:
+            terms = budget["terms"].items()
+            logger.info(
+                "+-- mass change by "
+                + ", ".join(f"{k} {v[0]:+.3e}" for k, v in terms if v[0] != 0.0)
+            )
+
+        if live_frame is not None:
+            primitive, frame_time, frame_iteration = live_frame
+            live_frame = None
+            live_server.publish(
+                resolve(primitive),
+                frame_time,
+                frame_iteration,
+                fields=[f.name for f in solver.primitive_fields],
+            )
+
+        if live_server is not None and live_server.num_clients:
+            if wall_clock.time() - live_last_frame >= live_interval:
+                live_last_frame = wall_clock.time()
+                primitive = solver.deferred_primitive_array()
+                live_frame = (primitive, user_time, iteration)
+
+        if driver.wall_time_limit is not None:
+            wall_time = perf_counter() - wall_start
+            projected = wall_time + fold_time()
+
+            if driver.distributed:
+                # The ranks' clocks differ, so they agree on the latest one,
+                # and all stop on the same iteration.
+                from mpi4py import MPI
+
+                projected = solver.comm.allreduce(projected, op=MPI.MAX)
+
+            if projected > driver.wall_time_limit * 3600.0:
+                logger.info(f"reached wall time limit after {wall_time:.1f}s")
+                final_event = "wall_limit"
+                break
+
+    if live_server is not None:
+        live_server.close()
+
+    state = grab_state()
+    state.synchronize()
+    yield final_event, None, state
 
 
 def run(setup_name, quiet=True, **kwargs):
@@ -530,7 +1835,7 @@ def run(setup_name, quiet=True, **kwargs):
     """
     import sailfish.setups
 
-    if "events" in kwargs:
+    if "events" in kwargs or "milestone_orbits" in kwargs:
         raise ValueError("events are not supported")
 
     driver = DriverArgs(setup_name=setup_name, **kwargs)
@@ -543,7 +1848,85 @@ def run(setup_name, quiet=True, **kwargs):
     return next(simulate(driver))[2]
 
 
-def init_logging():
+def available_execution_modes():
+    """
+    Return the execution modes which can be tried on this system.
+
+    The cpu, checked, and threads modes are always available. The omp mode is
+    listed if the compiler supports OpenMP, and the gpu mode if cupy is
+    installed and finds a device; see `execution_mode_problem`.
+    """
+    from sailfish.kernel.system import EXECUTION_MODES, execution_mode_problem
+
+    return [mode for mode in EXECUTION_MODES if execution_mode_problem(mode) is None]
+
+
+def verify_modes(setup_name, end_time=None, **kwargs):
+    """
+    Run a short simulation in each available execution mode, and compare.
+
+    The simulation is run to `end_time`, which defaults to 1% of the setup's
+    default end time. The first mode to succeed (normally cpu) is the
+    reference; for each other mode the largest difference in the primitive
+    data (relative to its largest absolute value) is reported, along with the
+    throughput in millions of zone updates per second. A table is printed,
+    and the rows are also returned as a list of dictionaries. A mode that
+    fails is reported, rather than raising an exception.
+    """
+    import time
+    import numpy as np
+    import sailfish.setups
+
+    if end_time is None:
+        setup = SetupBase.find_setup_class(setup_name)(
+            **kwargs.get("model_parameters") or dict()
+        )
+        end_time = 0.01 * first_not_none(setup.default_end_time, 1.0)
+
+    reference = None
+    rows = list()
+
+    for mode in available_execution_modes():
+        row = dict(mode=mode, status="ok", iterations=0, Mzps=0.0, difference=None)
+
+        try:
+            start = time.perf_counter()
+            state = run(setup_name, execution_mode=mode, end_time=end_time, **kwargs)
+            elapsed = time.perf_counter() - start
+            primitive = state.solver.primitive_array()
+            zones = state.mesh.num_total_zones * state.iteration
+            row.update(iterations=state.iteration, Mzps=zones / elapsed * 1e-6)
+
+            if reference is None:
+                reference = primitive
+                row.update(status="reference", difference=0.0)
+            elif primitive.shape != reference.shape:
+                row.update(status="shape mismatch")
+            else:
+                scale = max(np.max(np.abs(reference)), 1e-300)
+                row.update(difference=np.max(np.abs(primitive - reference)) / scale)
+
+        except Exception as e:
+            row.update(status=f"failed: {e}")
+
+        rows.append(row)
+
+    print()
+    print(f"{'mode':<10}{'iterations':>12}{'Mzps':>12}{'difference':>14}  status")
+    print("-" * 64)
+
+    for row in rows:
+        diff = "-" if row["difference"] is None else f"{row['difference']:.3e}"
+        print(
+            f"{row['mode']:<10}{row['iterations']:>12}{row['Mzps']:>12.3f}"
+            f"{diff:>14}  {row['status']}"
+        )
+    print()
+
+    return rows
+
+
+def init_logging(log_format="text"):
     """
     Convenience method to enable logging to standard output.
 
@@ -556,7 +1939,16 @@ def init_logging():
     place, but it doesn't want the driver to be silent, then invoking this
     function will do it for you. Note this function is also invoked by the
     `run` function if :code:`quiet=False` is passed to it.
+
+    With `log_format="json"`, each log record is written as a JSON object on
+    its own line, with the logger name, level, message, and a wall clock
+    timestamp. Records of iteration messages, events, and time series samples
+    also have a `data` item, with a `kind` key ("step", "event", or
+    "timeseries") and the numbers in the record. Some records are only
+    emitted in JSON format, and are marked with `json_only` in their extra
+    attributes.
     """
+    import json
     from sys import stdout
     from logging import StreamHandler, Formatter, getLogger, INFO
 
@@ -571,8 +1963,33 @@ def init_logging():
             else:
                 return f"[{name}:{record.levelname.lower()}] {record.msg}"
 
+    class JsonFormatter(Formatter):
+        def format(self, record):
+            entry = dict(
+                logger=record.name,
+                level=record.levelname.lower(),
+                message=str(record.msg).strip(),
+                wall_time=record.created,
+            )
+            if hasattr(record, "data"):
+                entry["data"] = record.data
+            return json.dumps(entry, default=json_value)
+
+    def json_value(x):
+        try:
+            return x.tolist()
+        except AttributeError:
+            return str(x)
+
     handler = StreamHandler(stdout)
-    handler.setFormatter(RunFormatter())
+
+    if log_format == "json":
+        handler.setFormatter(JsonFormatter())
+    elif log_format == "text":
+        handler.setFormatter(RunFormatter())
+        handler.addFilter(lambda record: not getattr(record, "json_only", False))
+    else:
+        raise ConfigurationError(f"unknown log format {log_format}")
 
     root_logger = getLogger()
     root_logger.addHandler(handler)
@@ -581,7 +1998,8 @@ def init_logging():
 
 def load_user_config():
     """
-    Initialize user extensions: setups and solvers outside the main codebase.
+    Initialize user extensions: setups, solvers, analyses, and upload hooks
+    outside the main codebase.
 
     This function is called by the `main` entry point and the `run` API function
     to load custom setups provided by the user. Extensions are defined in the
@@ -601,6 +2019,18 @@ def load_user_config():
         except KeyError:
             pass
 
+        try:
+            for analysis_extension in config["extensions"]["analyses"].split():
+                import_module(analysis_extension)
+        except KeyError:
+            pass
+
+        try:
+            for upload_extension in config["extensions"]["uploads"].split():
+                import_module(upload_extension)
+        except KeyError:
+            pass
+
         try:
             for solver_extension in config["extensions"]["solvers"].split():
                 register_solver_extension(solver_extension)
@@ -620,6 +2050,109 @@ def load_user_config():
         raise ConfigurationError(e)
 
 
+RUN_CONFIG_EXTENSIONS = (".toml", ".yaml", ".yml", ".json")
+EVALUATED_TABLES = ("--model", "--solver", "--observer")
+
+
+def load_run_config(filename):
+    """
+    Return the items of a run configuration file, in TOML, YAML, or JSON
+    format, by its extension. Reading TOML needs Python 3.11 or later, and
+    reading YAML needs the `pyyaml` package. Raises `ConfigurationError` if
+    the file can't be read or parsed.
+    """
+    try:
+        if filename.endswith(".toml"):
+            import tomllib
+
+            with open(filename, "rb") as f:
+                return tomllib.load(f)
+
+        elif filename.endswith((".yaml", ".yml")):
+            import yaml
+
+            try:
+                with open(filename) as f:
+                    return yaml.safe_load(f) or dict()
+            except yaml.YAMLError as e:
+                raise ValueError(e)
+
+        else:
+            import json
+
+            with open(filename) as f:
+                return json.load(f)
+
+    except ImportError as e:
+        raise ConfigurationError(f"can't read run configuration {filename}: {e}")
+
+    except (OSError, ValueError) as e:
+        raise ConfigurationError(f"bad run configuration {filename}: {e}")
+
+
+def run_config_arguments(config, known_options=None):
+    """
+    Return the command line arguments equivalent to a run configuration.
+
+    The `setup` item is the setup name, or a checkpoint to restart from, and
+    the other items are named after the long command line options, with
+    underscores or dashes, e.g. `end_time` or `mode`. Tables, like `model`
+    and `solver`, become lists of key-value pairs, lists become repeated
+    options, like `analysis`, and booleans are flags. The strings in the
+    tables of Python-evaluated values (`model`, `solver`, and `observer`) are
+    quoted, so they stay strings. Raises
+    `ConfigurationError` for an item which isn't in `known_options`, if
+    that's given.
+
+    .. code-block:: toml
+
+        setup = "circumbinary-disk"
+        resolution = 512
+        mode = "gpu"
+        end_time = 500.0
+        checkpoint = 10.0
+        timeseries = 0.1
+        outdir = "data"
+
+        [model]
+        mach_number = 20.0
+        buffer_driving_rate = 100.0
+        sink_model = "torque_free"
+
+        [solver]
+        plm_theta = 1.5
+    """
+    config = dict(config)
+
+    try:
+        argv = [str(config.pop("setup"))]
+    except KeyError:
+        raise ConfigurationError("the run configuration has no setup")
+
+    for key, value in config.items():
+        option = "--" + key.replace("_", "-")
+
+        if known_options is not None and option not in known_options:
+            raise ConfigurationError(f"unknown item {key} in the run configuration")
+
+        if isinstance(value, dict):
+            quote = option in EVALUATED_TABLES
+            argv.append(option)
+            argv.extend(
+                f"{k}={repr(v) if quote and isinstance(v, str) else v}"
+                for k, v in value.items()
+            )
+        elif isinstance(value, list):
+            for v in value:
+                argv.extend([option, str(v)])
+        elif value is True:
+            argv.append(option)
+        elif value is not False:
+            argv.extend([option, str(value)])
+
+    return argv
+
+
 def main():
     """
     General-purpose command line interface.
@@ -630,7 +2163,9 @@ def main():
 
     class MakeDict(argparse.Action):
         def __call__(self, parser, namespace, values, option_string=None):
-            setattr(namespace, self.dest, dict(values))
+            entries = dict(getattr(namespace, self.dest) or dict())
+            entries.update(values)
+            setattr(namespace, self.dest, entries)
 
     def add_dict_entry(key):
         class AddDictEntry(argparse.Action):
@@ -652,13 +2187,38 @@ def main():
     parser.add_argument(
         "command",
         nargs="?",
-        help="setup name or restart file (if directory, then load newest checkpoint)",
+        help="setup name, run configuration file (.toml, .yaml, or .json), or "
+        "restart file (if directory, then load newest checkpoint)",
+    )
+    parser.add_argument(
+        "command_target",
+        nargs="?",
+        metavar="target",
+        help="setup name for verify-modes, directory for the campaign command, or "
+        "checkpoint or run directory for the replay command",
     )
     parser.add_argument(
         "--describe",
         action="store_true",
         help="print a description of the setup and exit",
     )
+    parser.add_argument(
+        "--list-parameters",
+        action="store_true",
+        help="print the runtime parameters of the driver, and of the setup and its "
+        "solver if a setup is given, and exit",
+    )
+    parser.add_argument(
+        "--micro-bench",
+        action="store_true",
+        help="time the solver operations, write bench.json to the outdir, and exit",
+    )
+    parser.add_argument(
+        "--bench-baseline",
+        metavar="F",
+        type=str,
+        help="bench.json of an earlier build; fail if any operation is 25%% slower",
+    )
     parser.add_argument(
         "--resolution",
         "-n",
@@ -673,6 +2233,12 @@ def main():
         dest="num_patches",
         help="number of patches for domain decomposition",
     )
+    parser.add_argument(
+        "--mpi",
+        action="store_true",
+        dest="distributed",
+        help="distribute the mesh over the ranks of an MPI job",
+    )
     parser.add_argument(
         "--cfl",
         dest="cfl_number",
@@ -702,11 +2268,69 @@ def main():
         default=dict(),
         help="a sequence of events and recurrence rules to be emitted",
     )
+    parser.add_argument(
+        "--check-symmetry",
+        action="store_true",
+        help="measure the mirror-symmetry error after every iteration",
+    )
+    parser.add_argument(
+        "--sink-resolution",
+        metavar="N",
+        type=float,
+        help="log the resolution of each sink after every fold, and warn when a "
+        "sink radius spans fewer than N zones",
+    )
+    parser.add_argument(
+        "--manifest",
+        metavar="FILE",
+        help="manifest file (.csv or .json) written by the campaign command",
+    )
+    parser.add_argument(
+        "--average-since",
+        metavar="T",
+        type=float,
+        help="start time of the diagnostic averages in the campaign manifest",
+    )
     parser.add_argument(
         "--restart-dir",
         action="store_true",
         help="the command argument is a directory; restart from newest checkpoint therein",
     )
+    parser.add_argument(
+        "--deterministic",
+        action="store_true",
+        default=None,
+        help="build and run so that repeated runs are bitwise identical",
+    )
+    parser.add_argument(
+        "--force",
+        action="store_true",
+        dest="force_restart",
+        help="restart even if the solver, mesh, or fields differ from the checkpoint",
+    )
+    parser.add_argument(
+        "--resample",
+        choices=["plm", "bilinear"],
+        help="restart at a new --resolution, resampling the checkpoint onto it "
+        "(a finer mesh is prolonged with the given method)",
+    )
+    parser.add_argument(
+        "--report",
+        action="store_true",
+        help="write an HTML summary of the run to report.html in the outdir",
+    )
+    parser.add_argument(
+        "--upload",
+        metavar="URL",
+        help="upload each checkpoint and snapshot in the background, to a "
+        "directory or to s3://bucket/prefix or gs://bucket/prefix",
+    )
+    parser.add_argument(
+        "--upload-prune",
+        action="store_true",
+        help="delete the local copy of each uploaded file, except the newest "
+        "checkpoint",
+    )
     parser.add_argument(
         "--final-chkpt",
         action="store_true",
@@ -721,6 +2345,162 @@ def main():
         dest="events",
         help="checkpoint recurrence [<delta>|<log:mul>]",
     )
+    parser.add_argument(
+        "--snapshot",
+        metavar="S",
+        type=Recurrence.from_str,
+        action=add_dict_entry("snapshot"),
+        dest="events",
+        help="recurrence of primitive data snapshots [<delta>|<log:mul>]",
+    )
+    parser.add_argument(
+        "--snapshot-activity",
+        metavar="A",
+        type=ActivityRule.from_str,
+        help="shorten the snapshot interval while a time series column is active "
+        "[<value|rate>:<column>:<threshold>[:<factor>]]",
+    )
+    parser.add_argument(
+        "--snapshot-precision",
+        choices=["float32", "float64"],
+        help="floating point precision of snapshot data (default float64)",
+    )
+    parser.add_argument(
+        "--snapshot-format",
+        choices=["pickle", "npz", "hdf5", "vti", "xdmf"],
+        help="file format of snapshots; npz and hdf5 files can be read without "
+        "sailfish, and vti and xdmf files opened in ParaView (default pickle)",
+    )
+    parser.add_argument(
+        "--snapshot-fields",
+        nargs="*",
+        metavar="F=E",
+        type=keyed_encoding,
+        action=MakeDict,
+        help="store snapshot fields with their own transform, scale, and dtype, "
+        "e.g. sigma=log10:float16 vx=float32:scale=10",
+    )
+    parser.add_argument(
+        "--units",
+        metavar="U",
+        type=code_units,
+        dest="code_units",
+        help="physical values of the code units, written to the output files as "
+        "cgs and SI conversions, e.g. length=1au:mass=1msun (G = 1 fixes time)",
+    )
+    parser.add_argument(
+        "--analysis",
+        metavar="A",
+        action="append",
+        dest="analyses",
+        help="an analysis whose products are written with each snapshot, or by "
+        "the replay command",
+    )
+    parser.add_argument(
+        "--timestep-limiters",
+        metavar="T",
+        type=Recurrence.from_str,
+        action=add_dict_entry("timestep_limiters"),
+        dest="events",
+        help="recurrence of maps of the term limiting dt in each zone",
+    )
+    parser.add_argument(
+        "--spacetime",
+        metavar="S",
+        type=Recurrence.from_str,
+        action=add_dict_entry("spacetime"),
+        dest="events",
+        help="recurrence of radial profile samples for spacetime diagrams",
+    )
+    parser.add_argument(
+        "--spacetime-bins",
+        metavar="N",
+        type=int,
+        help="number of radial bins in spacetime diagrams (default 200)",
+    )
+    parser.add_argument(
+        "--observables",
+        metavar="S",
+        type=Recurrence.from_str,
+        action=add_dict_entry("observables"),
+        dest="events",
+        help="recurrence of synthetic images and line profiles (2D disks)",
+    )
+    parser.add_argument(
+        "--observer",
+        nargs="*",
+        metavar="K=V",
+        type=keyed_value,
+        action=MakeDict,
+        help="viewing geometry of the synthetic observables, e.g. inclination=30",
+    )
+    parser.add_argument(
+        "--secular",
+        metavar="S",
+        type=Recurrence.from_str,
+        action=add_dict_entry("secular"),
+        dest="events",
+        help="recurrence of 1D viscous fast-forward steps [<delta>|<log:mul>]",
+    )
+    parser.add_argument(
+        "--secular-duration",
+        metavar="T",
+        type=float,
+        help="time skipped by each secular fast-forward step",
+    )
+    parser.add_argument(
+        "--orbit-averages",
+        action="store_true",
+        help="record the time series diagnostics averaged over each binary orbit",
+    )
+    parser.add_argument(
+        "--phase-bins",
+        metavar="N",
+        type=int,
+        help="fold the time series diagnostics on N bins of the binary orbital phase",
+    )
+    parser.add_argument(
+        "--live-view",
+        metavar="PORT",
+        type=int,
+        dest="live_view_port",
+        help="stream downsampled frames to viewers connecting on this TCP port",
+    )
+    parser.add_argument(
+        "--live-view-rate",
+        metavar="R",
+        type=float,
+        help="maximum live view frames per second (default 2)",
+    )
+    parser.add_argument(
+        "--wall-limit",
+        metavar="H",
+        type=float,
+        dest="wall_time_limit",
+        help="hours after which to write chkpt.wall.pk and exit with code 3",
+    )
+    parser.add_argument(
+        "--milestones",
+        metavar="N",
+        type=int,
+        dest="milestone_orbits",
+        help="write chkpt.orbit.NNNN.pk at every N-th whole binary orbit",
+    )
+    parser.add_argument(
+        "--status-file",
+        metavar="FILE",
+        help="JSON file updated with the run status, for workflow managers",
+    )
+    parser.add_argument(
+        "--chkpt-guard-zones",
+        action="store_true",
+        help="write primitive data to checkpoints with the outer guard zones",
+    )
+    parser.add_argument(
+        "--chkpt-conserved",
+        action="store_true",
+        help="also write the conserved variables to checkpoints",
+    )
     parser.add_argument(
         "--timeseries",
         "-t",
@@ -776,14 +2556,22 @@ def main():
         metavar="P",
         type=str,
         default="",
-        help="detailed print solver structs [physics,options]",
+        help="print solver structs, gpu kernel usage, or kernel timing "
+        "[physics,options,kernels,timing]",
+    )
+    parser.add_argument(
+        "--log-format",
+        choices=["text", "json"],
+        default="text",
+        help="write log messages as text, or as one JSON object per line",
     )
     exec_group = parser.add_mutually_exclusive_group()
     exec_group.add_argument(
         "--mode",
         dest="execution_mode",
-        choices=["cpu", "omp", "gpu"],
-        help="execution mode",
+        choices=["cpu", "omp", "threads", "gpu", "checked"],
+        help="execution mode (threads is multi-core without OpenMP, checked is "
+        "serial, with kernel bounds checks)",
     )
     exec_group.add_argument(
         "--use-omp",
@@ -801,17 +2589,169 @@ def main():
         const="gpu",
         help="gpu acceleration",
     )
+    parser.add_argument(
+        "--mode-fallback",
+        dest="execution_fallback",
+        action="store_true",
+        default=None,
+        help="if the execution mode is unavailable (e.g. no gpu or OpenMP), run in "
+        "the best available one, with a warning",
+    )
+    parser.add_argument(
+        "--gpus",
+        metavar="I,J",
+        type=lambda s: [int(d) for d in s.split(",")],
+        dest="gpu_devices",
+        help="ids of the GPUs to assign patches to (default all visible)",
+    )
+    parser.add_argument(
+        "--memory-map",
+        metavar="DIR",
+        dest="memory_map_directory",
+        help="back large host arrays with files in DIR, for CPU runs larger than "
+        "the RAM",
+    )
+    parser.add_argument(
+        "--numa-policy",
+        choices=["default", "first-touch", "interleave"],
+        help="placement of host memory pages on the NUMA nodes in omp mode, "
+        "overriding the numa_policy of the .sailfish file",
+    )
 
-    try:
-        init_logging()
-        load_user_config()
+    """
+    The status file, if requested, is written when the run starts, after each
+    event, and on exit. Its "state" item is "running", "finished",
+    "wall_limit", or "failed", and agrees with the exit code returned.
+    """
+    exit_code = EXIT_FAILED
+    status_file = None
+    status = dict(state="running", exit_code=None, error=None, checkpoint=None)
+
+    def update_status(state=None):
+        if state is not None:
+            status.update(
+                iteration=state.iteration,
+                time=state.solver.time / state.setup.reference_time_scale,
+            )
+        if status_file is not None:
+            write_status_file(status_file, status)
 
+    try:
         args = parser.parse_args()
 
+        if args.command is not None and args.command.endswith(RUN_CONFIG_EXTENSIONS):
+            """
+            The items of a run configuration file are the command line
+            options they're named after, and options given on the command
+            line after the file take precedence, including individual model
+            parameters and solver options.
+            """
+            known_options = {s for a in parser._actions for s in a.option_strings}
+            config = load_run_config(args.command)
+            argv = sys.argv[1:]
+            argv.remove(args.command)
+            args = parser.parse_args(run_config_arguments(config, known_options) + argv)
+
+        status_file = args.status_file
+
+        exit_code = EXIT_FINISHED
+
+        init_logging(args.log_format)
+        load_user_config()
+
         if args.describe and args.command is not None:
             setup_name = args.command.split(":")[0]
             SetupBase.find_setup_class(setup_name).describe_class()
 
+        elif args.list_parameters:
+            from sailfish.parameters import format_parameters, runtime_parameters
+
+            driver = {k: v for k, v in vars(args).items() if k in DriverArgs._fields}
+            setup = None
+
+            if args.command is not None:
+                parts = args.command.split(":")
+                model_parameters = dict(keyed_value(a) for a in parts[1:])
+                model_parameters.update(args.model_parameters)
+                setup = SetupBase.find_setup_class(parts[0])(**model_parameters)
+
+            parameters = runtime_parameters(setup, driver, args.solver_options)
+
+            for line in format_parameters(parameters):
+                print(line)
+
+        elif args.command == "verify-modes":
+            if args.command_target is None:
+                raise ConfigurationError("verify-modes needs a setup name")
+
+            parts = args.command_target.split(":")
+            model_parameters = dict(keyed_value(a) for a in parts[1:])
+            model_parameters.update(args.model_parameters)
+            verify_modes(
+                parts[0],
+                end_time=args.end_time,
+                model_parameters=model_parameters,
+                solver_options=args.solver_options,
+                resolution=args.resolution,
+                num_patches=args.num_patches,
+                cfl_number=args.cfl_number,
+                fold=args.fold,
+            )
+
+        elif args.command == "campaign":
+            from sailfish.campaign import make_manifest
+
+            if args.command_target is None:
+                raise ConfigurationError("campaign needs a run directory")
+
+            make_manifest(
+                args.command_target,
+                filename=args.manifest,
+                since=args.average_since,
+            )
+
+        elif args.command == "replay":
+            from sailfish.replay import replay
+
+            if args.command_target is None:
+                raise ConfigurationError("replay needs a checkpoint or run directory")
+
+            try:
+                replay(args.command_target, args.analyses)
+            except (ValueError, AnalysisError) as e:
+                raise ConfigurationError(e)
+
+        elif args.micro_bench:
+            from sailfish.benchmark import micro_benchmarks, print_benchmarks
+            from sailfish.benchmark import compare_benchmarks, read_benchmarks
+            from sailfish.benchmark import write_benchmarks, DEFAULT_RESOLUTIONS
+
+            parts = (args.command or "kitp-code-comparison").split(":")
+            model_parameters = dict(keyed_value(a) for a in parts[1:])
+            model_parameters.update(args.model_parameters)
+            resolutions = [args.resolution] if args.resolution else DEFAULT_RESOLUTIONS
+            rows = micro_benchmarks(
+                parts[0],
+                resolutions=resolutions,
+                execution_mode=args.execution_mode,
+                num_patches=args.num_patches,
+                model_parameters=model_parameters,
+                solver_options=args.solver_options,
+            )
+
+            if args.bench_baseline is not None:
+                baseline = read_benchmarks(args.bench_baseline)
+                regressions = compare_benchmarks(rows, baseline)
+            else:
+                regressions = []
+
+            outdir = args.output_directory or "."
+            print_benchmarks(rows, regressions)
+            write_benchmarks(os.path.join(outdir, "bench.json"), rows)
+
+            if regressions:
+                status["error"] = f"{len(regressions)} benchmarks slower than baseline"
+
         elif args.command is None:
             print("specify setup:")
             for setup in SetupBase.__subclasses__():
@@ -837,39 +2777,196 @@ def main():
             else:
                 events_dict = dict()
 
+            if driver.distributed:
+                from sailfish.distributed import world
+
+                writes_output = world().rank == 0
+            else:
+                writes_output = True
+
+            if not writes_output:
+                status_file = None
+
+            exit_code = EXIT_FAILED
+            update_status()
+
+            if args.report and writes_output:
+                from sailfish.report import RunRecorder
+
+                recorder = RunRecorder()
+                getLogger().addHandler(recorder)
+
+            if args.upload is not None and writes_output:
+                from sailfish.upload import UploadError, UploadHook, Uploader
+
+                try:
+                    hook = UploadHook.find_hook_class(args.upload)(args.upload)
+                except UploadError as e:
+                    raise ConfigurationError(e)
+
+                uploader = Uploader(hook, prune=args.upload_prune)
+                logger.info(f"upload checkpoints and snapshots to {args.upload}")
+            else:
+                uploader = None
+
+            timeseries_output = None
+
             for name, number, state in simulate(driver):
+                if not writes_output:
+                    collect_outputs(name, state, args.final_chkpt)
+
+                    if name == "end":
+                        exit_code = EXIT_FINISHED
+                    elif name == "wall_limit":
+                        exit_code = EXIT_WALL_LIMIT
+                    continue
+
+                if timeseries_output is None:
+                    timeseries_output = TimeseriesOutput(outdir, len(state.timeseries))
+
+                written_files = list()
+
                 if name == "timeseries":
                     append_timeseries(state)
+                    timeseries_output.write(state)
+
+                    if args.log_format == "json" and state.timeseries:
+                        sample = state.synchronize()[-1]
+                        time = state.solver.time / state.setup.reference_time_scale
+                        sample_data = dict(
+                            kind="timeseries",
+                            time=time,
+                            names=state.solver.reduction_names,
+                            values=sample,
+                        )
+                        logger.info(
+                            f"timeseries sample {len(state.timeseries)}",
+                            extra=dict(data=sample_data, json_only=True),
+                        )
                 elif name == "checkpoint":
-                    write_checkpoint(number, outdir, state)
+                    status["checkpoint"] = write_checkpoint(number, outdir, state)
+                    written_files.append(status["checkpoint"])
+                elif name == "milestone":
+                    milestone = f"orbit.{number:04d}"
+                    status["checkpoint"] = write_checkpoint(milestone, outdir, state)
+                    written_files.append(status["checkpoint"])
+                elif name == "snapshot":
+                    precision = state.driver.snapshot_precision or "float64"
+                    analyses = state.driver.analyses or []
+                    encodings = state.driver.snapshot_fields
+                    file_format = state.driver.snapshot_format or "pickle"
+                    written_files += write_snapshot(
+                        number,
+                        outdir,
+                        state,
+                        precision,
+                        analyses,
+                        encodings,
+                        file_format,
+                    )
+                elif name == "timestep_limiters":
+                    write_timestep_limiters(number, outdir, state)
                 elif name == "end":
                     if args.final_chkpt:
-                        write_checkpoint("final", outdir, state)
+                        status["checkpoint"] = write_checkpoint("final", outdir, state)
+                        written_files.append(status["checkpoint"])
+                    exit_code = EXIT_FINISHED
+                elif name == "wall_limit":
+                    status["checkpoint"] = write_checkpoint("wall", outdir, state)
+                    written_files.append(status["checkpoint"])
+                    exit_code = EXIT_WALL_LIMIT
                 elif name in events_dict:
                     events_dict[name](number, outdir, state, logger)
                 else:
                     logger.warning(f"unrecognized event {name}")
 
+                if uploader is not None:
+                    uploader.submit(written_files)
+
+                if state.spacetime is not None and name in [
+                    "checkpoint",
+                    "milestone",
+                    "end",
+                    "wall_limit",
+                ]:
+                    write_spacetime_diagram(outdir, state)
+
+                if state.observables is not None and name in [
+                    "checkpoint",
+                    "milestone",
+                    "end",
+                    "wall_limit",
+                ]:
+                    write_observables(outdir, state)
+
+                if name in ["checkpoint", "milestone", "end", "wall_limit"]:
+                    timeseries_output.write(state, complete=True)
+
+                update_status(state)
+
+            if args.report and writes_output:
+                from sailfish.report import write_report
+
+                getLogger().removeHandler(recorder)
+                pathlib.Path(outdir).mkdir(parents=True, exist_ok=True)
+                filename = os.path.join(outdir, "report.html")
+                checkpoint = status["checkpoint"]
+                report_status = dict(exit_code=exit_code, checkpoint=checkpoint)
+                logger.info(f"write run report {filename}")
+                write_report(filename, state, recorder, report_status)
+
+            if uploader is not None:
+                logger.info("wait for the uploads to finish")
+                num_failed = uploader.close()
+
+                if num_failed:
+                    logger.warning(f"{num_failed} files failed to upload, kept locally")
+
     except ConfigurationError as e:
-        print(f"bad configuration: {e}")
+        status["error"] = f"bad configuration: {e}"
 
     except ExtensionError as e:
-        print(f"bad extension: {e}")
+        status["error"] = f"bad extension: {e}"
 
     except SetupError as e:
-        print(f"setup error: {e}")
+        status["error"] = f"setup error: {e}"
 
     except ParseRecurrenceError as e:
-        print(f"parse error: {e}")
+        status["error"] = f"parse error: {e}"
 
     except SolverInitializationError as e:
-        print(f"solver initialization error: {e}")
+        status["error"] = f"solver initialization error: {e}"
+
+    except SolverError as e:
+        status["error"] = f"invalid solution: {e}"
 
     except OSError as e:
-        print(f"file system error: {e}")
+        status["error"] = f"file system error: {e}"
 
     except ModuleNotFoundError as e:
-        print(f"unsatisfied dependency: {e}")
+        status["error"] = f"unsatisfied dependency: {e}"
 
     except KeyboardInterrupt:
+        status["error"] = "interrupted"
         print("")
+
+    except Exception as e:
+        status["error"] = f"{type(e).__name__}: {e}"
+        raise
+
+    finally:
+        if status["error"] is not None:
+            exit_code = EXIT_FAILED
+
+        states = {
+            EXIT_FINISHED: "finished",
+            EXIT_WALL_LIMIT: "wall_limit",
+            EXIT_FAILED: "failed",
+        }
+        status.update(state=states[exit_code], exit_code=exit_code)
+        update_status()
+
+        if status["error"] not in [None, "interrupted"]:
+            print(status["error"])
+
+    return exit_code
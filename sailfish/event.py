@@ -83,3 +83,94 @@ class RecurringEvent(NamedTuple):
         return self._replace(
             last_time=self.next_time(time, recurrence), number=self.number + 1
         )
+
+
+class ActivityRule(NamedTuple):
+    """
+    Rule for speeding up a recurring event while a monitored time series
+    column is active.
+
+    The monitored quantity is either the `value` of the column, or its
+    `rate` of change between consecutive samples. The column is active once
+    the magnitude of the quantity exceeds `threshold`, and stays active until
+    it falls below `release` times the threshold; while active, the event's
+    interval is divided by `factor`. Rules are written as
+    `<value|rate>:<column>:<threshold>[:<factor>]`.
+    """
+
+    quantity: str = "rate"
+    column: str = None
+    threshold: float = None
+    factor: float = 10.0
+    release: float = 0.5
+
+    @classmethod
+    def from_str(cls, string=None):
+        try:
+            quantity, column, threshold, *factor = string.split(":")
+            if quantity in ["value", "rate"] and len(factor) <= 1:
+                rule = ActivityRule(quantity, column, float(threshold))
+                if factor:
+                    rule = rule._replace(factor=float(factor[0]))
+                return rule
+        except ValueError:  # parse float failed or wrong number of arguments
+            pass
+
+        raise ParseRecurrenceError(f"badly formed activity rule {string}")
+
+    def __str__(self):
+        return (
+            f"interval / {self.factor} while |{self.quantity} of {self.column}| > "
+            f"{self.threshold}, until below {self.release * self.threshold}"
+        )
+
+
+class ActivityMonitor:
+    """
+    State of an activity rule: the last sample of the monitored column, and
+    whether it's active. Instances are pickled into checkpoints, so restarted
+    runs keep the cadence of the continuous run.
+    """
+
+    def __init__(self, rule, column_index):
+        self.rule = rule
+        self.column_index = column_index
+        self.last_time = None
+        self.last_value = None
+        self.level = 0.0
+        self.active = False
+
+    def __repr__(self):
+        return f"<ActivityMonitor {self.rule} active={self.active}>"
+
+    def add(self, time, value):
+        """
+        Update the activity level from the value of the monitored column in a
+        time series sample taken at the given time. Return True if the
+        monitor switched on or off.
+        """
+        if self.rule.quantity == "value":
+            self.level = abs(value)
+        elif self.last_time is not None and time > self.last_time:
+            self.level = abs(value - self.last_value) / (time - self.last_time)
+
+        self.last_time = time
+        self.last_value = value
+
+        was_active = self.active
+
+        if self.level > self.rule.threshold:
+            self.active = True
+        elif self.level < self.rule.release * self.rule.threshold:
+            self.active = False
+
+        return self.active != was_active
+
+    def recurrence(self, recurrence):
+        """
+        Return the recurrence to use for the event at the current activity.
+        """
+        if self.active:
+            return recurrence._replace(interval=recurrence.interval / self.rule.factor)
+        else:
+            return recurrence
@@ -0,0 +1,143 @@
+"""
+Names and units of the solution fields, so output files describe themselves.
+
+Each solver lists the fields of its primitive data, in the order of the last
+array axis, as its `primitive_fields` property. The list is written to
+checkpoints as `primitive_fields`, so analysis code can look up a field by
+name with `field_index` rather than hard-coding its position for each solver.
+Units are given in terms of the code units of length, mass, and time.
+Snapshots can store each field with its own `FieldEncoding`, e.g. as the
+logarithm of the surface density in half precision.
+"""
+
+from typing import NamedTuple
+
+
+class Field(NamedTuple):
+    """
+    Describes one quantity in a solution array.
+    """
+
+    name: str
+    description: str
+    units: str = "1"
+
+
+def field_index(fields, name):
+    """
+    Return the position of the field with the given name in a field list.
+
+    The list may contain `Field` instances, or dictionaries with a `name`
+    item, like the field lists written to checkpoints.
+    """
+    names = [f["name"] if isinstance(f, dict) else f.name for f in fields]
+
+    try:
+        return names.index(name)
+    except ValueError:
+        raise KeyError(f"no field named {name}, the fields are {', '.join(names)}")
+
+
+def field_dicts(fields):
+    """
+    Return a field list as plain dictionaries, for writing to output files.
+    """
+    return [f._asdict() for f in fields]
+
+
+def tracer_fields(num_tracers):
+    """
+    Return fields for a number of passive scalars, named `tracer0`,
+    `tracer1`, and so on.
+    """
+    return [
+        Field(f"tracer{n}", f"concentration of passive scalar {n}")
+        for n in range(num_tracers)
+    ]
+
+
+SURFACE_DENSITY = Field("sigma", "surface density", "mass / length^2")
+VELOCITY_X = Field("vx", "velocity in the x direction", "length / time")
+VELOCITY_Y = Field("vy", "velocity in the y direction", "length / time")
+VELOCITY_R = Field("vr", "radial velocity", "length / time")
+VELOCITY_PHI = Field("vphi", "azimuthal velocity", "length / time")
+VERTICALLY_INTEGRATED_PRESSURE = Field(
+    "pressure", "vertically integrated pressure", "mass / time^2"
+)
+DENSITY = Field("rho", "mass density", "mass / length^3")
+GAS_PRESSURE = Field("pressure", "gas pressure", "mass / length / time^2")
+SECOND_PRESSURE = Field(
+    "pressure2",
+    "vertically integrated pressure of the second energy component",
+    "mass / time^2",
+)
+SECOND_GAS_PRESSURE = Field(
+    "pressure2", "pressure of the second energy component", "mass / length / time^2"
+)
+
+
+ENCODING_DTYPES = ("float16", "float32", "float64")
+ENCODING_TRANSFORMS = ("linear", "log10")
+
+
+class FieldEncoding(NamedTuple):
+    """
+    How one field is stored in a snapshot, to fit a storage budget.
+
+    The stored value is `scale * f(x) + offset`, with `f` the `transform`
+    (the identity, or `log10` for positive fields), in the given `dtype`, or
+    the precision of the rest of the snapshot if it's `None`.
+    The scale and offset can e.g. convert to physical units, or keep a field
+    in the range of `float16`. Snapshots record the encoding of each field,
+    so readers can invert it.
+    """
+
+    transform: str = "linear"
+    scale: float = 1.0
+    offset: float = 0.0
+    dtype: str = None
+
+    @classmethod
+    def from_str(cls, text):
+        """
+        Parse an encoding from colon-separated items, which are a transform,
+        a dtype, `scale=S`, or `offset=O`, in any order, e.g.
+        `log10:float16` or `float32:scale=1e3`.
+        """
+        encoding = cls()
+
+        for item in text.split(":"):
+            key, _, value = item.partition("=")
+
+            if key in ENCODING_TRANSFORMS and not value:
+                encoding = encoding._replace(transform=key)
+            elif key in ENCODING_DTYPES and not value:
+                encoding = encoding._replace(dtype=key)
+            elif key in ("scale", "offset") and value:
+                encoding = encoding._replace(**{key: float(value)})
+            else:
+                raise ValueError(f"bad field encoding item '{item}' in '{text}'")
+
+        if encoding.scale == 0.0:
+            raise ValueError("the scale of a field encoding can't be zero")
+
+        return encoding
+
+    def encode(self, x):
+        """
+        Return the stored values of an array of a field.
+        """
+        import numpy as np
+
+        y = np.log10(x) if self.transform == "log10" else np.asarray(x)
+        return (self.scale * y + self.offset).astype(self.dtype)
+
+    def decode(self, y):
+        """
+        Return the field values from an array of stored values, in double
+        precision.
+        """
+        import numpy as np
+
+        x = (np.asarray(y, dtype=np.float64) - self.offset) / self.scale
+        return 10.0**x if self.transform == "log10" else x
@@ -0,0 +1,360 @@
+"""
+Step a simulation incrementally, e.g. from a Jupyter notebook.
+
+The `simulate` generator in the driver runs a simulation from start to end,
+pausing at events. A `Simulation` instance instead holds a solver, and is
+advanced a given number of iterations at a time, so the solution can be
+plotted or inspected between calls:
+
+.. code-block:: python
+
+    from sailfish.interactive import Simulation
+
+    sim = Simulation("kitp-code-comparison", resolution=200)
+    sim.step(100)
+    sim.step(until=1.0)
+    plt.imshow(sim.field("sigma"), origin="lower", extent=sim.extent)
+
+An interrupt (e.g. the Jupyter stop button) during `step` is deferred until
+the current iteration has finished, so the solver is always left in a
+consistent state, and can be stepped again.
+
+The state can be saved with `write_checkpoint`, in the driver's checkpoint
+format, and resumed either with `Simulation.from_checkpoint` or from the
+command line. A simulation resumed from a checkpoint takes the same steps as
+one which was never interrupted.
+"""
+
+import signal
+import threading
+from functools import partial
+from logging import getLogger
+from sailfish.setup_base import SetupBase
+
+logger = getLogger(__name__)
+
+
+class Simulation:
+    """
+    A solver for a setup, which is advanced on request.
+
+    The keyword arguments have the same meaning as the `DriverArgs` fields
+    of the same name. Events, checkpoints, and time series are not handled
+    here; the solver is available as the `solver` attribute for any other
+    use.
+    """
+
+    def __init__(
+        self,
+        setup_name,
+        model_parameters=None,
+        solver_options=None,
+        execution_mode=None,
+        resolution=None,
+        num_patches=None,
+        cfl_number=None,
+        new_timestep_cadence=None,
+    ):
+        import sailfish.setups
+
+        self.setup = SetupBase.find_setup_class(setup_name)(
+            **model_parameters or dict()
+        )
+        self.resolution = resolution or self.setup.default_resolution
+        self._build(
+            self.setup.mesh(self.resolution),
+            self.setup.start_time,
+            None,
+            solver_options,
+            execution_mode,
+            num_patches,
+            cfl_number,
+            new_timestep_cadence,
+        )
+
+    @classmethod
+    def from_checkpoint(cls, filename, execution_mode=None, num_patches=None):
+        """
+        Return a simulation restored from a checkpoint file, written either by
+        `write_checkpoint` or by the driver.
+
+        The setup, mesh, solver options, iteration, and time step are those
+        of the checkpoint. The execution mode and number of patches are also
+        taken from the checkpoint unless they're given, since they don't
+        change the solution.
+        """
+        from sailfish.products import load_checkpoint
+        import sailfish.setups
+
+        chkpt = load_checkpoint(filename)
+        driver = chkpt["driver"]
+        sim = cls.__new__(cls)
+        sim.setup = SetupBase.find_setup_class(chkpt["setup_name"])(
+            **chkpt["model_parameters"]
+        )
+        sim.resolution = driver.resolution
+        sim._build(
+            chkpt["mesh"],
+            chkpt["time"],
+            chkpt["solution"],
+            chkpt["solver_options"],
+            execution_mode or driver.execution_mode,
+            num_patches or driver.num_patches,
+            chkpt["cfl_number"],
+            driver.new_timestep_cadence,
+        )
+        sim.iteration = chkpt["iteration"]
+        sim.dt = chkpt.get("timestep_dt")
+        return sim
+
+    def _build(
+        self,
+        mesh,
+        time,
+        solution,
+        solver_options,
+        execution_mode,
+        num_patches,
+        cfl_number,
+        new_timestep_cadence,
+    ):
+        from sailfish.driver import ConfigurationError, load_user_config
+        from sailfish.driver import user_build_config
+        from sailfish.kernel.system import configure_build, resolve_execution_mode
+        from sailfish.solvers import make_solver
+
+        try:
+            self.mode = resolve_execution_mode(execution_mode or "cpu")
+        except ValueError as e:
+            raise ConfigurationError(e)
+
+        load_user_config()
+        configure_build(**user_build_config, execution_mode=self.mode)
+
+        self.mesh = mesh
+        self.mesh.validate()
+        self.num_patches = num_patches or 1
+
+        if self.setup.regions is not None:
+            from sailfish.coupling import Solver as CoupledSolver
+
+            solver_class = partial(CoupledSolver, self.setup.regions)
+        elif self.setup.refinement is not None:
+            from sailfish.refinement import Solver as RefinedSolver
+
+            solver_class = partial(RefinedSolver, self.setup.refinement)
+        else:
+            solver_class = partial(make_solver, self.setup.solver)
+
+        self.solver = solver_class(
+            physics=self.setup.physics,
+            options=solver_options,
+            setup=self.setup,
+            mesh=self.mesh,
+            time=time,
+            solution=solution,
+            num_patches=self.num_patches,
+            mode=self.mode,
+        )
+
+        if cfl_number is not None and cfl_number > self.solver.maximum_cfl:
+            raise ConfigurationError(
+                f"cfl number {cfl_number} is greater than {self.solver.maximum_cfl}, "
+                f"max allowed by solver {self.setup.solver}"
+            )
+
+        self.cfl_number = cfl_number or self.solver.recommended_cfl
+        self.new_timestep_cadence = new_timestep_cadence or 1
+        self.iteration = 0
+        self.dt = None
+
+    def __repr__(self):
+        return (
+            f"<Simulation {self.setup.__class__.__name__} "
+            f"iteration={self.iteration} time={self.user_time:0.3f}>"
+        )
+
+    @property
+    def time(self):
+        """
+        The simulation time, in code units.
+        """
+        return self.solver.time
+
+    @property
+    def user_time(self):
+        """
+        The simulation time, in units of the setup's reference time scale.
+        """
+        return self.solver.time / self.setup.reference_time_scale
+
+    @property
+    def parameters(self):
+        """
+        The model parameters of the setup and the options of the solver, as a
+        list of :py:class:`sailfish.parameters.RuntimeParameter` with their
+        current values.
+        """
+        from sailfish.parameters import setup_parameters, solver_parameters
+
+        parameters = setup_parameters(self.setup)
+
+        if self.setup.regions is None:
+            parameters += solver_parameters(self.setup.solver, self.solver.options)
+
+        return parameters
+
+    def step(self, num_iterations=1, until=None):
+        """
+        Advance the solution by the given number of iterations, or if `until`
+        is given, until that time in units of the setup's reference time
+        scale.
+
+        If an interrupt arrives during the call, it takes effect after the
+        iteration in progress, and `step` returns early. Return the number of
+        iterations which were taken.
+        """
+        interrupted = threading.Event()
+        iterations = 0
+
+        def handler(signum, frame):
+            interrupted.set()
+
+        def done():
+            if until is not None:
+                return self.user_time >= until
+            else:
+                return iterations == num_iterations
+
+        # Signal handlers can only be installed from the main thread. In other
+        # threads an interrupt is never delivered to this code anyway.
+        main_thread = threading.current_thread() is threading.main_thread()
+
+        if main_thread:
+            previous = signal.signal(signal.SIGINT, handler)
+
+        try:
+            while not done():
+                if interrupted.is_set():
+                    logger.info(f"interrupted at iteration {self.iteration}")
+                    break
+                self.advance()
+                iterations += 1
+        finally:
+            if main_thread:
+                signal.signal(signal.SIGINT, previous)
+
+        return iterations
+
+    def advance(self):
+        """
+        Take a single iteration, with the time step computed like the driver.
+        """
+        if self.dt is None or self.iteration % self.new_timestep_cadence == 0:
+            dx = self.mesh.min_spacing(self.time)
+            self.dt = self.solver.recommended_timestep(dx, self.cfl_number)
+
+        self.solver.advance(self.dt)
+        self.iteration += 1
+
+    def field(self, index):
+        """
+        Return a host array of one primitive variable, in the rest frame. The
+        variable is given by its position, or by its name in the solver's
+        `primitive_fields`, e.g. `sim.field("sigma")`.
+
+        For 2D meshes the array is transposed, so that the first axis is `y`,
+        which is what `matplotlib.pyplot.imshow` expects; use it together
+        with `origin="lower"` and `extent=sim.extent`. For 1D meshes, plot the
+        array against `sim.zone_centers`.
+        """
+        if isinstance(index, str):
+            index = self.solver.field_index(index)
+
+        primitive = self.solver.primitive_array()
+
+        if primitive.ndim == 3:
+            return primitive[:, :, index].T
+        else:
+            return primitive[:, index]
+
+    def sample(self, x, y):
+        """
+        Return the rest-frame primitive data interpolated at points, as an
+        array of shape `(n, nq)`, e.g. `sim.sample([1.0, 1.5], [0.0, 0.0])`,
+        or to move tracer particles with the flow between calls to `step`.
+        """
+        return self.solver.sample(x, y)
+
+    def set_primitive(self, primitive):
+        """
+        Replace the solution with a host array of rest-frame primitive data,
+        in the layout returned by `solver.primitive_array()` (not transposed
+        like `field`), e.g. to perturb a relaxed state. The solver re-derives
+        its guard zones and other cached data, and the time step is
+        recomputed at the next iteration.
+        """
+        self.solver.set_primitive(primitive)
+        self.dt = None
+
+    def reset(self, primitive=None, time=None):
+        """
+        Return the solver to new initial data, without compiling its kernels
+        or allocating its arrays again, e.g. to run many short simulations
+        from an outer loop. The arguments are as for `solver.reset`; by
+        default the simulation starts over from where it was built. The
+        iteration count is reset, and the time step is recomputed.
+        """
+        self.solver.reset(primitive, time)
+        self.iteration = 0
+        self.dt = None
+
+    def write_checkpoint(self, number, outdir=None):
+        """
+        Write the simulation state to `chkpt.<number>.pk` in `outdir` (the
+        working directory by default), and return the file name.
+
+        The file can be resumed with `Simulation.from_checkpoint`, or run
+        further by the driver, e.g. `sailfish chkpt.0000.pk --end-time 10`.
+        Device data is copied to the host first.
+        """
+        from sailfish.driver import DriverArgs, DriverState, write_checkpoint
+
+        driver = DriverArgs(
+            setup_name=self.setup.dash_case_class_name(),
+            model_parameters=self.setup.model_parameter_dict(),
+            solver_options=self.solver.options,
+            cfl_number=self.cfl_number,
+            execution_mode=self.mode,
+            resolution=self.resolution,
+            num_patches=self.num_patches,
+            new_timestep_cadence=self.new_timestep_cadence,
+            events=dict(),
+        )
+        state = DriverState(
+            iteration=self.iteration,
+            driver=driver,
+            mesh=self.mesh,
+            timeseries=list(),
+            event_states=dict(),
+            solver=self.solver,
+            setup=self.setup,
+            cfl_number=self.cfl_number,
+            timestep_dt=self.dt,
+            execution_mode=self.mode,
+        )
+        return write_checkpoint(number, outdir, state)
+
+    @property
+    def extent(self):
+        """
+        The bounds `(x0, x1, y0, y1)` of a 2D mesh, for `imshow`.
+        """
+        return (self.mesh.x0, self.mesh.x1, self.mesh.y0, self.mesh.y1)
+
+    @property
+    def zone_centers(self):
+        """
+        The zone center coordinates of a 1D mesh.
+        """
+        return self.mesh.zone_centers(self.time)
@@ -0,0 +1,350 @@
+"""
+Write snapshot data to files which can be read without sailfish.
+
+Snapshots are pickles by default, which need sailfish to be importable to
+load. The writers here store the same rest-frame primitive data in HDF5 or
+numpy `.npz` files instead, with the simulation time and iteration, the mesh
+geometry, the setup and its model parameters, the equation of state, and
+the point masses, so the data can be read directly with h5py, yt, or numpy.
+The driver writes them with `--snapshot-format hdf5` or `npz`.
+
+In HDF5 files, the primitive data is the `primitive` dataset, with the field
+names in its `fields` attribute, and the metadata are attributes of the root
+group and of the groups `mesh`, `model_parameters`, `eos`, and
+`point_masses/0`, `point_masses/1`, and so on. The unit conversions of a
+run with code units (see `sailfish.units`) are a JSON string in the root
+attribute `unit_conversions`. Writing HDF5 needs the h5py package. In `.npz`
+files, the primitive data is the `primitive` array, and the metadata is a
+JSON string in the `metadata` array. Items without a value (`None`) are left
+out of both.
+
+For visualization in ParaView or VisIt, the primitive data can also be
+written as VTK image data (`--snapshot-format vti`), or as an XDMF file
+which describes the raw arrays in a binary file next to it
+(`--snapshot-format xdmf`), one cell-centered scalar for each field. VTK
+image data needs evenly spaced zones, so it's only written for cartesian
+meshes. The XDMF grid is made of the zone vertex coordinates from the mesh,
+so log-spherical meshes are drawn at their proper positions in the x-z
+plane, with `x = r sin(theta)` and `z = r cos(theta)`. Both
+have the simulation time, and the XDMF file has the metadata as a JSON
+string in its `metadata` information item.
+"""
+
+import json
+import os
+from base64 import b64encode
+from enum import Enum
+from html import escape
+
+SNAPSHOT_FORMATS = ("pickle", "npz", "hdf5", "vti", "xdmf")
+
+SNAPSHOT_EXTENSIONS = dict(
+    pickle=".pk", npz=".npz", hdf5=".h5", vti=".vti", xdmf=".xdmf"
+)
+
+EOS_PARAMETERS = (
+    "eos_type",
+    "sound_speed",
+    "mach_number",
+    "gamma_law_index",
+    "isothermal_sound_speed",
+)
+
+
+def plain_value(x):
+    """
+    Return a value as a number, string, or list of them, with enum members
+    replaced by their names, or `None` if it can't be written as metadata.
+    """
+    if isinstance(x, Enum):
+        return x.name
+    if isinstance(x, (bool, int, float, str)):
+        return x
+    if isinstance(x, (list, tuple)):
+        items = [plain_value(y) for y in x]
+        return None if None in items else items
+    return None
+
+
+def plain_dict(d):
+    """
+    Return the items of a dict which can be written as metadata.
+    """
+    items = {key: plain_value(value) for key, value in d.items()}
+    return {key: value for key, value in items.items() if value is not None}
+
+
+def snapshot_metadata(snapshot, physics=dict()):
+    """
+    Return the metadata of a snapshot dictionary, as written by the driver,
+    and the solver's physics parameters, as a dict of plain values.
+    """
+    mesh = snapshot["mesh"]
+    geometry = mesh.geometry
+    point_masses = snapshot.get("point_masses") or ()
+
+    if hasattr(point_masses, "_asdict"):
+        point_masses = (point_masses,)
+
+    if snapshot.get("unit_conversions") is not None:
+        extra_items = dict(unit_conversions=snapshot["unit_conversions"])
+    else:
+        extra_items = dict()
+
+    return dict(
+        time=snapshot["time"],
+        iteration=snapshot["iteration"],
+        setup_name=snapshot.get("setup_name"),
+        solver=snapshot.get("solver"),
+        fields=[f["name"] for f in snapshot["primitive_fields"]],
+        mesh=plain_dict(
+            dict(
+                type=type(mesh).__name__,
+                coordinates=geometry.coordinates,
+                lower=geometry.lower,
+                upper=geometry.upper,
+                spacing=geometry.spacing,
+                shape=mesh.shape,
+            )
+        ),
+        model_parameters=plain_dict(snapshot.get("model_parameters") or dict()),
+        eos=plain_dict({k: v for k, v in physics.items() if k in EOS_PARAMETERS}),
+        point_masses=[plain_dict(m._asdict()) for m in point_masses],
+        **extra_items,
+    )
+
+
+def write_npz(filename, snapshot, physics=dict()):
+    """
+    Write the primitive data and metadata of a snapshot to a `.npz` file.
+    """
+    import numpy as np
+
+    metadata = snapshot_metadata(snapshot, physics)
+
+    with open(filename, "wb") as f:
+        np.savez(f, primitive=snapshot["primitive"], metadata=json.dumps(metadata))
+
+
+def write_hdf5(filename, snapshot, physics=dict()):
+    """
+    Write the primitive data and metadata of a snapshot to an HDF5 file.
+    """
+    import h5py
+
+    metadata = snapshot_metadata(snapshot, physics)
+
+    with h5py.File(filename, "w") as f:
+        dataset = f.create_dataset("primitive", data=snapshot["primitive"])
+        dataset.attrs["fields"] = metadata["fields"]
+
+        for key in ("time", "iteration", "setup_name", "solver"):
+            if metadata[key] is not None:
+                f.attrs[key] = metadata[key]
+
+        if "unit_conversions" in metadata:
+            f.attrs["unit_conversions"] = json.dumps(metadata["unit_conversions"])
+
+        for name in ("mesh", "model_parameters", "eos"):
+            f.create_group(name).attrs.update(metadata[name])
+
+        for n, point_mass in enumerate(metadata["point_masses"]):
+            f.create_group(f"point_masses/{n}").attrs.update(point_mass)
+
+
+def read_snapshot_file(filename):
+    """
+    Return the primitive data and metadata of a file written by `write_npz`
+    or `write_hdf5`, as a dictionary like the one `snapshot_metadata`
+    returns, with the primitive data in its `primitive` item.
+    """
+    if filename.endswith(SNAPSHOT_EXTENSIONS["npz"]):
+        import numpy as np
+
+        with np.load(filename) as f:
+            metadata = json.loads(str(f["metadata"]))
+            return dict(metadata, primitive=f["primitive"])
+
+    import h5py
+
+    with h5py.File(filename, "r") as f:
+        result = {key: value for key, value in f.attrs.items()}
+
+        if "unit_conversions" in result:
+            result["unit_conversions"] = json.loads(result["unit_conversions"])
+
+        groups = ("mesh", "model_parameters", "eos")
+        result.update({name: dict(f[name].attrs) for name in groups})
+        result["point_masses"] = [
+            dict(f[f"point_masses/{n}"].attrs)
+            for n in range(len(f.get("point_masses", [])))
+        ]
+        result["fields"] = list(f["primitive"].attrs["fields"])
+        result["primitive"] = f["primitive"][...]
+        return result
+
+
+def mesh_vertices(mesh, time):
+    """
+    Return the coordinates of the zone vertices of a mesh, as a pair of
+    arrays `(x, y)`, with one more element than the mesh on each axis. On 1D
+    meshes, the `y` coordinates are zero.
+    """
+    import numpy as np
+
+    if mesh.geometry.coordinates == "log_spherical":
+        r = np.array(mesh.radial_vertices(time))
+
+        if len(mesh.shape) == 1:
+            return r, np.zeros_like(r)
+
+        q = np.array(mesh.polar_vertices)[None, :]
+        return r[:, None] * np.sin(q), r[:, None] * np.cos(q)
+
+    if mesh.geometry.coordinates == "log_polar":
+        r = np.array(mesh.radial_faces())[:, None]
+        phi = mesh.dphi * np.arange(mesh.shape[1] + 1)[None, :]
+        return r * np.cos(phi), r * np.sin(phi)
+
+    if len(mesh.shape) == 1:
+        x = np.array(mesh.faces())
+        return x, np.zeros_like(x)
+
+    (x0, y0), (dx, dy) = mesh.geometry.lower, mesh.geometry.spacing
+    ni, nj = mesh.shape
+    x = x0 + dx * np.arange(ni + 1)[:, None]
+    y = y0 + dy * np.arange(nj + 1)[None, :]
+    return np.broadcast_arrays(x, y)
+
+
+def write_vti(filename, snapshot, physics=dict()):
+    """
+    Write the primitive data of a snapshot on a cartesian mesh to a VTK image
+    data file. Raise a `ValueError` if the mesh isn't cartesian.
+    """
+    import numpy as np
+
+    mesh = snapshot["mesh"]
+    geometry = mesh.geometry
+
+    if geometry.coordinates != "cartesian":
+        raise ValueError("VTK image data needs a cartesian mesh, use xdmf instead")
+
+    primitive = snapshot["primitive"]
+    shape = tuple(mesh.shape) + (0, 0)
+    lower = tuple(geometry.lower) + (0.0, 0.0)
+    spacing = tuple(geometry.spacing) + (1.0, 1.0)
+    extent = " ".join(f"0 {n}" for n in shape[:3])
+    vtk_type = "Float32" if primitive.dtype == np.float32 else "Float64"
+
+    def encoded(array):
+        # VTK arrays run fastest along x, and binary data is base64 with a
+        # byte count in front.
+        data = np.ascontiguousarray(array.T).astype(f"<{primitive.dtype.str[1:]}")
+        header = np.array([data.nbytes], dtype="<u8").tobytes()
+        return b64encode(header + data.tobytes()).decode()
+
+    lines = [
+        '<?xml version="1.0"?>',
+        '<VTKFile type="ImageData" version="1.0" byte_order="LittleEndian" '
+        'header_type="UInt64">',
+        f'<ImageData WholeExtent="{extent}" '
+        f'Origin="{" ".join(map(str, lower[:3]))}" '
+        f'Spacing="{" ".join(map(str, spacing[:3]))}">',
+        "<FieldData>",
+        '<DataArray type="Float64" Name="TimeValue" NumberOfTuples="1" '
+        f'format="ascii">{snapshot["time"]!r}</DataArray>',
+        "</FieldData>",
+        f'<Piece Extent="{extent}">',
+        "<CellData>",
+    ]
+
+    for n, field in enumerate(snapshot["primitive_fields"]):
+        lines.append(
+            f'<DataArray type="{vtk_type}" Name="{escape(field["name"])}" '
+            f'format="binary">{encoded(primitive[..., n])}</DataArray>'
+        )
+
+    lines += ["</CellData>", "</Piece>", "</ImageData>", "</VTKFile>", ""]
+
+    with open(filename, "w") as f:
+        f.write("\n".join(lines))
+
+
+def write_xdmf(filename, snapshot, physics=dict()):
+    """
+    Write the primitive data and metadata of a snapshot to an XDMF file, and
+    the arrays it describes to a raw binary file with the same name and the
+    extension `.bin`.
+    """
+    import numpy as np
+
+    mesh = snapshot["mesh"]
+    primitive = snapshot["primitive"]
+    metadata = snapshot_metadata(snapshot, physics)
+    x, y = mesh_vertices(mesh, snapshot["time"])
+    binary_filename = os.path.splitext(filename)[0] + ".bin"
+    binary_name = os.path.basename(binary_filename)
+    offset = 0
+    items = list()
+
+    def data_item(array):
+        # Each array is appended to the binary file, and referred to by its
+        # offset there.
+        nonlocal offset
+        array = np.ascontiguousarray(array)
+        number_type = "Int" if array.dtype.kind == "i" else "Float"
+        dims = " ".join(map(str, array.shape))
+        items.append(array.astype(array.dtype.newbyteorder("<")))
+        item = (
+            f'<DataItem Format="Binary" Endian="Little" Seek="{offset}" '
+            f'NumberType="{number_type}" Precision="{array.itemsize}" '
+            f'Dimensions="{dims}">{binary_name}</DataItem>'
+        )
+        offset += array.nbytes
+        return item
+
+    if len(mesh.shape) == 1:
+        ni = mesh.shape[0]
+        connectivity = np.stack([np.arange(ni), np.arange(1, ni + 1)], axis=-1)
+        topology = (
+            f'<Topology TopologyType="Polyline" NumberOfElements="{ni}" '
+            f'NodesPerElement="2">{data_item(connectivity)}</Topology>'
+        )
+    else:
+        dims = " ".join(str(n + 1) for n in mesh.shape)
+        topology = f'<Topology TopologyType="2DSMesh" Dimensions="{dims}"/>'
+
+    lines = [
+        '<?xml version="1.0"?>',
+        '<Xdmf Version="3.0">',
+        "<Domain>",
+        '<Grid Name="primitive" GridType="Uniform">',
+        f'<Time Value="{snapshot["time"]!r}"/>',
+        f'<Information Name="metadata" Value="{escape(json.dumps(metadata))}"/>',
+        topology,
+        '<Geometry GeometryType="X_Y">',
+        data_item(x),
+        data_item(y),
+        "</Geometry>",
+    ]
+
+    for n, field in enumerate(snapshot["primitive_fields"]):
+        lines += [
+            f'<Attribute Name="{escape(field["name"])}" AttributeType="Scalar" '
+            'Center="Cell">',
+            data_item(primitive[..., n]),
+            "</Attribute>",
+        ]
+
+    lines += ["</Grid>", "</Domain>", "</Xdmf>", ""]
+
+    with open(binary_filename, "wb") as f:
+        for array in items:
+            f.write(array.tobytes())
+
+    with open(filename, "w") as f:
+        f.write("\n".join(lines))
+
+
+SNAPSHOT_WRITERS = dict(npz=write_npz, hdf5=write_hdf5, vti=write_vti, xdmf=write_xdmf)
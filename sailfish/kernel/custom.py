@@ -0,0 +1,184 @@
+"""
+Load user-supplied source terms through a stable "custom physics" ABI.
+
+A custom source term adds physics to a solver without changes to sailfish. It
+is a C function which returns the rates of change of the conserved quantities
+in a zone, from the zone's position and primitive state. Version 1 of the ABI
+is the function
+
+.. code-block:: c
+
+    void sailfish_custom_source_term(
+        double time,
+        double x,
+        double y,
+        int num_fields,
+        const double *primitive,
+        double *rate,
+        int num_parameters,
+        const double *parameters);
+
+where `primitive` has the `num_fields` primitive fields of the zone at
+`(x, y)`, in the solver's order, e.g. surface density, the two velocities,
+and any tracers for the cbdiso_2d solver, and `rate` has room for the same
+number of conserved quantities, and is zero on entry. The `parameters` are
+numbers given with the source term, so one library can serve several runs.
+
+The function is supplied in one of two ways. A shared library (`.so` or
+`.dylib`) exports it along with `int sailfish_custom_abi_version(void)`,
+which returns 1, and is linked to a small JIT-compiled module which calls it
+in every zone, so it runs in the cpu, omp, threads, and checked modes. For the
+gpu mode, or to skip building a library, the function is given in a source
+file (`.c` or `.cu`) which declares it `PRIVATE` (`static __device__` on GPUs)
+and defines `SAILFISH_CUSTOM_ABI_VERSION` to 1; the file is compiled into the
+module itself, in any mode.
+
+The source term is applied by the solver as a first-order split step after
+each iteration. The loop here assumes the conserved quantities are the
+density, and the density times each of the other primitive fields, as in the
+isothermal solvers, and leaves masked zones unchanged.
+"""
+
+from ctypes import CDLL
+from os.path import abspath, isfile
+from .library import Library
+
+CUSTOM_ABI_VERSION = 1
+
+CUSTOM_MAX_FIELDS = 16
+
+SHARED_LIBRARY_EXTENSIONS = (".so", ".dylib")
+
+CUSTOM_SOURCE_LOOP = r"""
+#define CUSTOM_MAX_FIELDS 16
+
+#ifdef SAILFISH_CUSTOM_SHARED
+#define SAILFISH_CUSTOM_ABI_VERSION 1
+void sailfish_custom_source_term(
+    double time,
+    double x,
+    double y,
+    int num_fields,
+    const double *primitive,
+    double *rate,
+    int num_parameters,
+    const double *parameters);
+#endif
+
+#if SAILFISH_CUSTOM_ABI_VERSION != 1
+#error "a custom source term must implement version 1 of the ABI"
+#endif
+
+PUBLIC void custom_source_step(
+    int ni,
+    int nj,
+    double patch_xl, // mesh
+    double patch_xr,
+    double patch_yl,
+    double patch_yr,
+    double time,
+    double dt,
+    double density_floor,
+    int num_fields, // :: $ <= 16
+    int num_parameters,
+    double *parameters, // :: $.size >= num_parameters
+    double *primitive, // :: $.shape == (ni + 4, nj + 4, num_fields)
+    int mask_is_enabled,
+    double *zone_mask) // :: mask_is_enabled == 0 or $.shape == (ni, nj)
+{
+    int ng = 2; // number of guard zones
+    index_t si = num_fields * (nj + 2 * ng);
+    index_t sj = num_fields;
+
+    double dx = (patch_xr - patch_xl) / ni;
+    double dy = (patch_yr - patch_yl) / nj;
+
+    FOR_EACH_2D(ni, nj)
+    {
+        if (mask_is_enabled && zone_mask[(index_t) i * nj + j] == 0.0)
+        {
+            SKIP_ZONE;
+        }
+
+        double xc = patch_xl + (i + 0.5) * dx;
+        double yc = patch_yl + (j + 0.5) * dy;
+        double *pc = &primitive[(i + ng) * si + (j + ng) * sj];
+        double rate[CUSTOM_MAX_FIELDS] = {0.0};
+
+        sailfish_custom_source_term(
+            time, xc, yc, num_fields, pc, rate, num_parameters, parameters);
+
+        double sigma = pc[0] + dt * rate[0];
+
+        if (sigma < density_floor)
+        {
+            sigma = density_floor;
+        }
+
+        for (int q = 1; q < num_fields; ++q)
+        {
+            pc[q] = (pc[0] * pc[q] + dt * rate[q]) / sigma;
+        }
+        pc[0] = sigma;
+    }
+}
+"""
+
+
+def abi_version(filename):
+    """
+    Return the custom physics ABI version of a shared library, or raise a
+    `ValueError` if it can't be loaded or doesn't say.
+    """
+    try:
+        return CDLL(filename).sailfish_custom_abi_version()
+    except OSError as e:
+        raise ValueError(f"could not load custom source library: {e}")
+    except AttributeError:
+        raise ValueError(f"{filename} does not export sailfish_custom_abi_version")
+
+
+class CustomSource:
+    """
+    A custom source term, from a shared library or a source file, compiled
+    into a kernel library for the given execution mode.
+    """
+
+    def __init__(self, filename, parameters=(), mode="cpu"):
+        if not isfile(filename):
+            raise ValueError(f"custom source file {filename} does not exist")
+
+        filename = abspath(filename)
+
+        if filename.endswith(SHARED_LIBRARY_EXTENSIONS):
+            if mode == "gpu":
+                raise ValueError("a custom source library can't run on GPUs")
+
+            version = abi_version(filename)
+
+            if version != CUSTOM_ABI_VERSION:
+                raise ValueError(
+                    f"{filename} implements custom physics ABI version {version}, "
+                    f"expected {CUSTOM_ABI_VERSION}"
+                )
+
+            code = CUSTOM_SOURCE_LOOP
+            define_macros = dict(SAILFISH_CUSTOM_SHARED=1)
+            libraries = [filename]
+        else:
+            with open(filename) as f:
+                code = f.read() + CUSTOM_SOURCE_LOOP
+
+            define_macros = dict()
+            libraries = []
+
+        self.filename = filename
+        self.parameters = [float(p) for p in parameters]
+        self.lib = Library(
+            code,
+            mode=mode,
+            name="custom_source",
+            debug=False,
+            define_macros=define_macros,
+            libraries=libraries,
+        )
@@ -0,0 +1,107 @@
+"""
+Utilities to exercise individual kernel phases on tiny patches, and to run
+the test scripts.
+
+A solver's C code is a single translation unit, so its `PRIVATE` functions
+(reconstruction, Riemann solvers, source terms) can be reached by appending
+small `PUBLIC` probe kernels to the source before it's compiled. The probes
+are only part of the test build; they are never added to the solver's own
+source file.
+"""
+
+
+def patch_fixture(f, shape, extent, num_fields, num_guard=2, xp=None):
+    """
+    Return an array of primitive data sampled from an analytic function.
+
+    The function `f(x, y)` returns a sequence of `num_fields` values at the
+    point `(x, y)`. The patch has `shape = (ni, nj)` interior zones, covering
+    the region `extent = ((x0, x1), (y0, y1))`. The returned array has shape
+    `(ni + 2 * ng, nj + 2 * ng, num_fields)`, and the guard zones are sampled
+    from `f` as well, as though they were interior zones of a larger patch.
+    """
+    if xp is None:
+        import numpy as xp
+
+    ni, nj = shape
+    ng = num_guard
+    (x0, x1), (y0, y1) = extent
+    dx = (x1 - x0) / ni
+    dy = (y1 - y0) / nj
+    primitive = xp.zeros([ni + 2 * ng, nj + 2 * ng, num_fields])
+
+    for i in range(-ng, ni + ng):
+        for j in range(-ng, nj + ng):
+            x = x0 + (i + 0.5) * dx
+            y = y0 + (j + 0.5) * dy
+            primitive[i + ng, j + ng] = f(x, y)
+
+    return primitive
+
+
+def zone_fixture(values, num_zones=1, xp=None):
+    """
+    Return an array of shape `(num_zones, len(values))`, with each row
+    equal to `values`. This is the input format of a rank-1 probe kernel.
+    """
+    if xp is None:
+        import numpy as xp
+
+    return xp.array([list(values)] * num_zones, dtype=float)
+
+
+def library_with_probes(solver_source, probe_code, mode="cpu"):
+    """
+    Compile a solver's C source, with probe kernels appended to it.
+
+    The `solver_source` argument is the path to a solver's .c file. The probe
+    code may call any of the functions defined in that file. The library is
+    built in debug mode, so kernel argument constraints are checked.
+    """
+    from .library import Library
+
+    with open(solver_source) as f:
+        code = f.read()
+
+    return Library(code + probe_code, mode=mode, name="probes", debug=True)
+
+
+def parse_test_args(modes=("cpu", "omp", "gpu"), parser=None):
+    """
+    Parse the command line of a test script, and configure the build for its
+    execution mode.
+
+    The scripts take a `--mode` option, one of `modes`, and the options of
+    `parser`, if one is given. OpenMP is enabled in the build only in omp
+    mode.
+    """
+    import argparse
+    from .system import configure_build
+
+    parser = parser or argparse.ArgumentParser()
+    parser.add_argument("--mode", default="cpu", choices=list(modes))
+    args = parser.parse_args()
+
+    configure_build(enable_openmp=args.mode == "omp")
+    return args
+
+
+def assert_close(actual, expected, what, tol=1e-12):
+    """
+    Raise an `AssertionError` unless `actual` is within `tol` of `expected`.
+
+    The tolerance is both absolute and relative to the magnitude of
+    `expected`. Numbers are compared directly, and anything else is compared
+    element-wise as an array, so the test scripts that only check scalars
+    don't need numpy.
+    """
+    if isinstance(actual, (int, float)) and isinstance(expected, (int, float)):
+        close = abs(actual - expected) <= tol * (1.0 + abs(expected))
+    else:
+        import numpy as np
+
+        close = np.allclose(actual, expected, rtol=tol, atol=tol)
+
+    if not close:
+        raise AssertionError(f"{what}: expected {expected}, got {actual}")
+
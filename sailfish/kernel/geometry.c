@@ -0,0 +1,138 @@
+/*
+MODULE: geometry
+
+DESCRIPTION:
+  Face areas, cell volumes, and the integrals behind geometric source terms,
+  shared by the solvers whose kernels take a coordinate system argument.
+  Solvers include this file with `#include "geometry.c"`, which the Library
+  class expands in place. A solver's flux update is written once in terms of
+  these functions, and the coordinate system is a kernel argument, so new
+  geometries are added here rather than as copies of a solver's kernels.
+
+  The 1D functions take the positions of the faces, which may move with the
+  mesh. The 2D functions take the corners of a face, or the lower and upper
+  coordinates of a cell. In spherical coordinates, the first coordinate is
+  the radius, and the second (in 2D) is the polar angle. In planar polar
+  coordinates, they're the cylindrical radius and the azimuth, and areas and
+  volumes are per unit length in z, as in 2D cartesian coordinates.
+*/
+
+
+#define COORDS_CARTESIAN 0
+#define COORDS_SPHERICAL 1
+#define COORDS_SPHERICAL_POLAR 2
+#define COORDS_PLANAR_POLAR 3
+#define GEOMETRY_PI 3.141592653589793
+
+// 1D spherical areas and volumes are those of full shells divided by 4 pi,
+// i.e. per steradian.
+#define NOMINAL_FOUR_PI 1.0
+
+
+// ============================ 1D ============================================
+// ============================================================================
+PRIVATE double face_area_1d(int coords, double x)
+{
+    switch (coords) {
+        case COORDS_CARTESIAN: return 1.0;
+        case COORDS_SPHERICAL: return x * x * NOMINAL_FOUR_PI;
+    }
+    return 0.0;
+}
+
+PRIVATE double cell_volume_1d(int coords, double x0, double x1)
+{
+    switch (coords) {
+        case COORDS_CARTESIAN: return x1 - x0;
+        case COORDS_SPHERICAL: return (pow(x1, 3.0) - pow(x0, 3.0)) * NOMINAL_FOUR_PI / 3.0;
+    }
+    return 0.0;
+}
+
+/**
+ * The geometric source of the radial momentum in a zone, per unit pressure:
+ * the difference of its face areas, which balances the pressure forces on
+ * the faces of a zone with uniform pressure. It's zero in cartesian
+ * coordinates.
+ */
+PRIVATE double pressure_source_weight_1d(int coords, double x0, double x1)
+{
+    return face_area_1d(coords, x1) - face_area_1d(coords, x0);
+}
+
+
+// ============================ 2D ============================================
+// ============================================================================
+/**
+ * The area of the face between the points (x0, y0) and (x1, y1), which are
+ * on a line of constant x or y, per unit length along z in cartesian
+ * coordinates, and swept around the polar axis in spherical-polar ones.
+ */
+PRIVATE double face_area_2d(int coords, double x0, double x1, double y0, double y1)
+{
+    switch (coords) {
+        case COORDS_CARTESIAN: {
+            return (x1 - x0) + (y1 - y0);
+        }
+        case COORDS_SPHERICAL_POLAR: {
+            double R0 = x0 * sin(y0);
+            double R1 = x1 * sin(y1);
+            double z0 = x0 * cos(y0);
+            double z1 = x1 * cos(y1);
+            double dR = R1 - R0;
+            double dz = z1 - z0;
+            return GEOMETRY_PI * (R0 + R1) * sqrt(dR * dR + dz * dz);
+        }
+        case COORDS_PLANAR_POLAR: {
+            // A radial face is an arc, and an azimuthal face a segment.
+            return (x1 - x0) + 0.5 * (x0 + x1) * (y1 - y0);
+        }
+    }
+    return 0.0;
+}
+
+PRIVATE double cell_volume_2d(int coords, double x0, double x1, double y0, double y1)
+{
+    switch (coords) {
+        case COORDS_CARTESIAN: {
+            return (x1 - x0) * (y1 - y0);
+        }
+        case COORDS_SPHERICAL_POLAR: {
+            return -(x1 * x1 * x1 - x0 * x0 * x0) * (cos(y1) - cos(y0)) * 2.0 * GEOMETRY_PI / 3.0;
+        }
+        case COORDS_PLANAR_POLAR: {
+            return 0.5 * (x1 * x1 - x0 * x0) * (y1 - y0);
+        }
+    }
+    return 0.0;
+}
+
+/**
+ * The volume integrals of a cell from which the geometric source terms of
+ * the momentum are made, in spherical-polar coordinates: w[0] is
+ * pi (r1^2 - r0^2) (cos(q1) - cos(q0)), and w[1] is pi (r1^2 - r0^2)
+ * (sin(q1) - sin(q0)). In planar polar coordinates, w[0] is the integral of
+ * 1 / r, (r1 - r0) (phi1 - phi0), which balances the pressure forces on the
+ * faces of a zone with uniform pressure, and w[1] is zero. Both are zero in
+ * cartesian coordinates.
+ */
+PRIVATE void metric_source_weights_2d(int coords, double x0, double x1, double y0, double y1, double *w)
+{
+    switch (coords) {
+        case COORDS_SPHERICAL_POLAR: {
+            double dr2 = x1 * x1 - x0 * x0;
+            w[0] = GEOMETRY_PI * dr2 * (cos(y1) - cos(y0));
+            w[1] = GEOMETRY_PI * dr2 * (sin(y1) - sin(y0));
+            break;
+        }
+        case COORDS_PLANAR_POLAR: {
+            w[0] = (x1 - x0) * (y1 - y0);
+            w[1] = 0.0;
+            break;
+        }
+        default: {
+            w[0] = 0.0;
+            w[1] = 0.0;
+        }
+    }
+}
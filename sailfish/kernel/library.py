@@ -4,29 +4,52 @@ Defines a `Library` utility class to encapulsate CPU/GPU compiled kernels.
 CPU modules are built with the cffi module. Build products including the .so
 file itself are placed in this module's __pycache__ directory, and stored for
 reuse based on the SHA value of the source code and #define macros. GPU
-modules are JIT-compiled with cupy. No caching is presently done for the GPU
-modules.
+modules are JIT-compiled with cupy, which keeps them in its own kernel cache.
+The execution modes and the kernel source conventions are described in
+doc/source/kernels.rst.
 """
 
 from platform import system
-from ctypes import c_double, c_int, POINTER, CDLL
+from ctypes import c_double, c_float, c_int, POINTER, CDLL
 from hashlib import sha256
 from logging import getLogger
-from os import listdir
+from math import prod
+from os import close, ftruncate, listdir, unlink
 from os.path import join, dirname
+from tempfile import mkstemp
+from re import compile as compile_regex
+from weakref import WeakSet
 
 from .parse_api import parse_api
-from .system import build_config, measure_time
+from .system import build_config, measure_time, thread_pool
 
 logger = getLogger(__name__)
 THREAD_BLOCK_SIZE_1D = (64,)
 THREAD_BLOCK_SIZE_2D = (8, 8)
 THREAD_BLOCK_SIZE_3D = (4, 4, 4)
+CHECKED_MODE_PADDING = 256
+CHECKED_MODE_SENTINEL = 0x7FF4DEADBEEFCAFE  # a signaling NaN bit pattern
+CHECKED_MODE_SENTINEL_32 = 0x7FA5CAFE  # the same, in single precision
+MEMORY_MAP_MIN_BYTES = 1 << 24
+PRECISIONS = dict(float32=4, float64=8)
 
 KERNEL_LIB_HEADER = r"""
 #define EXEC_CPU 0
 #define EXEC_OMP 1
 #define EXEC_GPU 2
+#define EXEC_THREADS 3
+
+// Offsets into arrays of zones, which can exceed the range of int on large
+// meshes: index_t strides promote the offset arithmetic to 64 bits.
+typedef long long index_t;
+
+// The floating point type of kernels which can be compiled in either
+// precision.
+#ifdef SINGLE_PRECISION
+typedef float real;
+#else
+typedef double real;
+#endif
 
 #if (EXEC_MODE != EXEC_GPU)
 #include <math.h>
@@ -39,6 +62,7 @@ KERNEL_LIB_HEADER = r"""
 #endif
 
 #if (EXEC_MODE == EXEC_CPU)
+#define SKIP_ZONE continue
 #define FOR_EACH_1D(NI) \
 for (int i = 0; i < NI; ++i) \
 
@@ -52,22 +76,54 @@ for (int j = 0; j < NJ; ++j) \
 for (int k = 0; k < NK; ++k) \
 
 #elif (EXEC_MODE == EXEC_OMP)
+#define SKIP_ZONE continue
 #define FOR_EACH_1D(NI) \
-_Pragma("omp parallel for") \
+_Pragma("omp parallel for schedule(static)") \
 for (int i = 0; i < NI; ++i) \
 
 #define FOR_EACH_2D(NI, NJ) \
-_Pragma("omp parallel for") \
+_Pragma("omp parallel for schedule(static)") \
 for (int i = 0; i < NI; ++i) \
 for (int j = 0; j < NJ; ++j) \
 
 #define FOR_EACH_3D(NI, NJ, NK) \
-_Pragma("omp parallel for") \
+_Pragma("omp parallel for schedule(static)") \
 for (int i = 0; i < NI; ++i) \
 for (int j = 0; j < NJ; ++j) \
 for (int k = 0; k < NK; ++k) \
 
+#elif (EXEC_MODE == EXEC_THREADS)
+// Each thread which calls a kernel is assigned a chunk of the first axis by
+// a call to sailfish_set_thread_chunk; the chunk is thread-local, so several
+// threads can run the same kernel at once, on different rows.
+static __thread int thread_chunk_index = 0;
+static __thread int thread_chunk_count = 1;
+
+void sailfish_set_thread_chunk(int index, int count)
+{
+    thread_chunk_index = index;
+    thread_chunk_count = count;
+}
+
+#define CHUNK_BEGIN(NI) \
+(int) ((index_t) (NI) * thread_chunk_index / thread_chunk_count)
+#define CHUNK_END(NI) \
+(int) ((index_t) (NI) * (thread_chunk_index + 1) / thread_chunk_count)
+#define SKIP_ZONE continue
+#define FOR_EACH_1D(NI) \
+for (int i = CHUNK_BEGIN(NI); i < CHUNK_END(NI); ++i) \
+
+#define FOR_EACH_2D(NI, NJ) \
+for (int i = CHUNK_BEGIN(NI); i < CHUNK_END(NI); ++i) \
+for (int j = 0; j < NJ; ++j) \
+
+#define FOR_EACH_3D(NI, NJ, NK) \
+for (int i = CHUNK_BEGIN(NI); i < CHUNK_END(NI); ++i) \
+for (int j = 0; j < NJ; ++j) \
+for (int k = 0; k < NK; ++k) \
+
 #elif (EXEC_MODE == EXEC_GPU)
+#define SKIP_ZONE return
 #define FOR_EACH_1D(NI) \
 int i = threadIdx.x + blockIdx.x * blockDim.x; \
 if (i >= NI) return; \
@@ -86,6 +142,142 @@ if (i >= NI || j >= NJ || k >= NK) return; \
 #endif
 """
 
+FIRST_TOUCH_CODE = r"""
+PUBLIC void first_touch_copy(
+    int ni,
+    int nj,
+    real *src, // :: $.size == ni * nj
+    real *dst) // :: $.size == ni * nj
+{
+    FOR_EACH_2D(ni, nj)
+    {
+        index_t n = (index_t) i * nj + j;
+        dst[n] = src[n];
+    }
+}
+"""
+
+first_touch_libraries = dict()
+loaded_libraries = WeakSet()
+kernel_timing_enabled = False
+
+
+INCLUDE_DIRECTIVE = compile_regex(r'^[ \t]*#include[ \t]+"(\w+\.c)"[ \t]*$')
+
+
+def expand_includes(code, included=None):
+    """
+    Return kernel code in which each line `#include "name.c"` is replaced by
+    the contents of that file in this module's directory. Files are expanded
+    recursively, and those already included are skipped.
+    """
+    if included is None:
+        included = set()
+
+    result = []
+
+    for line in code.splitlines():
+        match = INCLUDE_DIRECTIVE.match(line)
+
+        if match is None:
+            result.append(line)
+        elif match.group(1) not in included:
+            included.add(match.group(1))
+
+            with open(join(dirname(__file__), match.group(1))) as f:
+                result.append(expand_includes(f.read(), included))
+
+    return "\n".join(result)
+
+
+def specialize(code, constants):
+    """
+    Return kernel code in which the scalar arguments named in `constants` are
+    overwritten by those values at the start of each public kernel.
+
+    The code is expected to follow the conventions of `parse_api`, with the
+    opening brace of each kernel on the line after its argument list.
+    """
+    from .parse_api import scan
+
+    if not constants:
+        return code
+
+    result = []
+    names = []
+
+    def lines():
+        for line in code.splitlines():
+            result.append(line)
+            yield line
+
+    # The scanner reads one line at a time, so the line which triggered an
+    # event is the last one in the result when the event is received.
+    for event, value in scan(lines()):
+        if event == "start_symbol":
+            names = []
+        elif event == "argument" and not value[0].endswith("*"):
+            names.append(value[1])
+        elif event == "end_symbol":
+            if result[-1].strip() != "{":
+                raise ValueError(f"expected a kernel body, got {result[-1]}")
+            for name in names:
+                if name in constants:
+                    result.append(f"    {name} = {c_literal(constants[name])};")
+
+    return "\n".join(result)
+
+
+def c_literal(value):
+    """
+    Return a C literal for an int, float, or bool value.
+    """
+    from math import isfinite
+
+    if type(value) in (bool, int):
+        return str(int(value))
+    if type(value) is float and isfinite(value):
+        return repr(value)
+    raise ValueError(f"constants must be int, bool, or finite float, got {value}")
+
+
+class KernelTiming:
+    """
+    The number of calls, zones updated, and seconds spent in each kernel of a
+    library. GPU durations are kept pending until read, so that recording
+    them doesn't wait for the device.
+    """
+
+    def __init__(self):
+        self.calls = dict()
+        self.zones = dict()
+        self.seconds = dict()
+        self.pending = list()
+
+    def record(self, name, shape, duration, cpu_mode):
+        self.calls[name] = self.calls.get(name, 0) + 1
+        self.zones[name] = self.zones.get(name, 0) + prod(shape)
+
+        if cpu_mode:
+            self.seconds[name] = self.seconds.get(name, 0.0) + duration()
+        else:
+            self.pending.append((name, duration))
+
+    def collect(self):
+        """
+        Return a dict of `(calls, zones, seconds)` for each kernel called since
+        the last collection, and start over.
+        """
+        for name, duration in self.pending:
+            self.seconds[name] = self.seconds.get(name, 0.0) + duration()
+
+        result = {
+            name: (self.calls[name], self.zones[name], self.seconds.get(name, 0.0))
+            for name in self.calls
+        }
+        self.__init__()
+        return result
+
 
 class KernelInvocation:
     """
@@ -98,7 +290,6 @@ class KernelInvocation:
 
     def __call__(self, *args):
         lib = self.kernel.lib
-        rank = len(self.shape)
         name = self.kernel.symbol.name
         spec = self.kernel.symbol.args
 
@@ -109,12 +300,38 @@ class KernelInvocation:
 
         args = list(self.shape) + list(args)
 
+        for n, arg in enumerate(spec):
+            if arg.name in lib.constants and args[n] != lib.constants[arg.name]:
+                raise ValueError(
+                    f"kernel {name} was compiled with {arg.name}="
+                    f"{lib.constants[arg.name]}, got {args[n]}"
+                )
+
         if lib.debug:
-            validate_types(args, tuple(spec), name, lib.xp)
+            validate_types(args, tuple(spec), name, lib.xp, lib.precision)
             validate_constraints(args, tuple(spec), name)
 
-        if lib.cpu_mode:
-            kernel(*to_ctypes(args, spec))
+        if lib.timing is None:
+            self.launch(kernel, args)
+        else:
+            with measure_time(lib.mode) as duration:
+                self.launch(kernel, args)
+
+            lib.timing.record(name, self.shape, duration, lib.cpu_mode)
+
+    def launch(self, kernel, args):
+        lib = self.kernel.lib
+        rank = len(self.shape)
+        name = self.kernel.symbol.name
+        spec = self.kernel.symbol.args
+
+        if lib.checked:
+            checked_call(kernel, args, spec, name, lib.precision)
+        elif lib.threaded:
+            ctypes_args = list(to_ctypes(args, spec, lib.precision))
+            threaded_call(lib.module, kernel, ctypes_args)
+        elif lib.cpu_mode:
+            kernel(*to_ctypes(args, spec, lib.precision))
         else:
             if rank == 1:
                 (ti,) = bs = THREAD_BLOCK_SIZE_1D
@@ -160,22 +377,63 @@ class Kernel:
 class Library:
     """
     Builds and maintains (in memory) a CPU or GPU dynamically compiled module.
+
+    The `constants` map the names of scalar kernel arguments to values. Each
+    kernel with an argument of that name overwrites it with the constant on
+    entry, so the compiler can fold the value into the code, as if it were a
+    compile-time switch. The kernel signatures are unchanged, and each
+    invocation checks that the arguments agree with the constants.
+
+    CPU modules can be linked to existing shared `libraries`, whose functions
+    the kernel code declares and calls (see `sailfish.kernel.custom`). With
+    `precision="float32"`, the `real` type of the kernel code is `float`
+    rather than `double`, and the `zeros` and `array` methods allocate arrays
+    of that precision for its `real *` arguments.
     """
 
     def __init__(
-        self, code=None, mode="cpu", name="module", debug=True, define_macros=dict()
+        self,
+        code=None,
+        mode="cpu",
+        name="module",
+        debug=True,
+        define_macros=dict(),
+        constants=dict(),
+        libraries=(),
+        precision="float64",
     ):
-        code = f"{KERNEL_LIB_HEADER} {code}"
+        if precision not in PRECISIONS:
+            raise ValueError(f"precision must be float32 or float64, got {precision}")
+
+        if precision == "float32":
+            define_macros = dict(define_macros, SINGLE_PRECISION=1)
+
+        code = expand_includes(code)
+        code = f"{KERNEL_LIB_HEADER} {specialize(code, constants)}"
+        debug = debug or mode == "checked"
         logger.info(f"debug mode {'enabled' if debug else 'disabled'}")
-        logger.info(f"prepare {name} for {mode} execution")
+        logger.info(f"prepare {name} for {mode} execution in {precision}")
 
         with measure_time(mode) as prep_time:
             self.debug = debug
+            self.precision = precision
+            self.constants = dict(constants)
+            self.checked = mode == "checked"
+            self.threaded = mode == "threads"
+            self.mode = mode
             self.cpu_mode = mode != "gpu"
             self.api = parse_api(code)
 
             if self.cpu_mode:
-                self.load_cpu_module(code, name, mode=mode, define_macros=define_macros)
+                self.load_cpu_module(
+                    code,
+                    name,
+                    mode=mode,
+                    define_macros=define_macros,
+                    libraries=libraries,
+                )
+            elif libraries:
+                raise ValueError("shared libraries can't be linked to GPU modules")
             else:
                 self.load_gpu_module(code, define_macros)
 
@@ -184,7 +442,16 @@ class Library:
         for symbol in self.api:
             logger.info(f"+-- {symbol}")
 
-    def load_cpu_module(self, code, name, mode="cpu", define_macros=dict()):
+        for key, val in constants.items():
+            logger.info(f"+-- constant {key}={val}")
+
+        self.name = name
+        self.timing = KernelTiming() if kernel_timing_enabled else None
+        loaded_libraries.add(self)
+
+    def load_cpu_module(
+        self, code, name, mode="cpu", define_macros=dict(), libraries=()
+    ):
         import cffi
         import numpy
 
@@ -200,8 +467,12 @@ class Library:
             # https://cffi.readthedocs.io/en/latest/using.html#windows-calling-conventions
             raise ValueError("CPU execution mode not supported on windows")
 
-        exec_mode = dict(cpu=0, omp=1)[mode]
+        exec_mode = dict(cpu=0, omp=1, checked=0, threads=3)[mode]
         define_macros = list(define_macros.items()) + [("EXEC_MODE", exec_mode)]
+        link_args = list()
+
+        for library in libraries:
+            link_args += [library, f"-Wl,-rpath,{dirname(library)}"]
 
         ffi = cffi.FFI()
         ffi.set_source(
@@ -209,7 +480,7 @@ class Library:
             code,
             define_macros=define_macros,
             extra_compile_args=build_config["extra_compile_args"],
-            extra_link_args=build_config["extra_link_args"],
+            extra_link_args=build_config["extra_link_args"] + link_args,
         )
 
         # Build a hash for the compiled library based on code, define
@@ -218,6 +489,7 @@ class Library:
         sha.update(code.encode("utf-8"))
         sha.update(str(define_macros).encode("utf-8"))
         sha.update(str(build_config).encode("utf-8"))
+        sha.update(str(link_args).encode("utf-8"))
         cache_dir = join(dirname(__file__), "__pycache__", sha.hexdigest())
 
         try:
@@ -237,24 +509,231 @@ class Library:
     def load_gpu_module(self, code, define_macros):
         import cupy
 
-        options = tuple(f"-D {k}={v}" for k, v in define_macros.items()) + (
-            "-D EXEC_MODE=2",
+        options = tuple(f"-D{k}={v}" for k, v in define_macros.items()) + (
+            "-DEXEC_MODE=2",
+            f"-DTHREAD_BLOCK_SIZE_2D_I={THREAD_BLOCK_SIZE_2D[0]}",
+            f"-DTHREAD_BLOCK_SIZE_2D_J={THREAD_BLOCK_SIZE_2D[1]}",
         )
-        module = cupy.RawModule(code=code, options=options)
+
+        if build_config["deterministic"]:
+            if cupy.cuda.runtime.is_hip:
+                options += ("-ffp-contract=off",)
+            else:
+                options += ("--fmad=false",)
+
+        backend = "nvcc" if build_config["gpu_compiler"] == "offline" else "nvrtc"
+        module = cupy.RawModule(code=code, options=options, backend=backend)
         module.compile()
         self.module = module
         self.xp = cupy
 
+    def zeros(self, shape):
+        """
+        Return an array of zeros, allocated for this library's execution mode,
+        in its precision. In CPU modes, a large array may be memory-mapped
+        (see `host_zeros`).
+        """
+        if self.cpu_mode:
+            return host_zeros(shape, self.precision)
+        else:
+            return self.xp.zeros(shape, dtype=self.precision)
+
+    def array(self, a):
+        """
+        Return a copy of `a`, allocated for this library's execution mode, and
+        converted to its precision.
+
+        In omp mode with the first-touch NUMA policy, the copy is made by a
+        parallel loop over the first axis, which is partitioned across threads
+        in the same way as the 1D, 2D, or 3D kernel loops. Memory pages are
+        then placed on the NUMA node of the thread which will later operate on
+        them.
+        """
+        if self.cpu_mode and is_memory_mapped(a.shape, self.precision):
+            dst = host_zeros(a.shape, self.precision)
+            dst[...] = a
+            return dst
+
+        if self.mode != "omp" or build_config["numa_policy"] != "first-touch":
+            return self.xp.array(a, dtype=self.precision)
+
+        if self.precision not in first_touch_libraries:
+            first_touch_libraries[self.precision] = Library(
+                FIRST_TOUCH_CODE,
+                mode="omp",
+                name="first_touch",
+                debug=False,
+                precision=self.precision,
+            )
+
+        src = self.xp.ascontiguousarray(a, dtype=self.precision)
+        dst = self.xp.empty_like(src)
+        shape = (src.shape[0], src.size // src.shape[0]) if src.ndim else (1, 1)
+        first_touch_libraries[self.precision].first_touch_copy[shape](src, dst)
+        return dst
+
+    def kernel_report(self):
+        """
+        Return a list of dictionaries with the resource usage of each kernel.
+
+        In GPU mode, each item has the kernel's registers per thread, static
+        shared, constant, and local (spill) memory in bytes, the thread block
+        size it's launched with, and the theoretical occupancy: the fraction
+        of the device's maximum resident threads per multiprocessor which are
+        active, given the block size and the kernel's resources. Low
+        occupancy due to register pressure is a common reason for a card
+        performing below its memory bandwidth. In CPU modes, the list is
+        empty.
+        """
+        if self.cpu_mode:
+            return []
+
+        import cupy
+
+        device = cupy.cuda.Device()
+        max_threads = device.attributes["MaxThreadsPerMultiProcessor"]
+        block_sizes = {
+            1: THREAD_BLOCK_SIZE_1D,
+            2: THREAD_BLOCK_SIZE_2D,
+            3: THREAD_BLOCK_SIZE_3D,
+        }
+        report = []
+
+        for symbol in self.api.values():
+            kernel = self.module.get_function(symbol.name)
+            block = block_sizes[symbol.rank]
+            threads = 1
+
+            for b in block:
+                threads *= b
+
+            blocks = cupy.cuda.driver.occupancyMaxActiveBlocksPerMultiprocessor(
+                kernel.ptr, threads, 0
+            )
+            report.append(
+                dict(
+                    kernel=symbol.name,
+                    registers=kernel.num_regs,
+                    shared_bytes=kernel.shared_size_bytes,
+                    const_bytes=kernel.const_size_bytes,
+                    local_bytes=kernel.local_size_bytes,
+                    block_size=block,
+                    occupancy=blocks * threads / max_threads,
+                )
+            )
+        return report
+
+    def log_kernel_report(self):
+        """
+        Log the resource usage of each kernel, see `kernel_report`.
+        """
+        for r in self.kernel_report():
+            logger.info(
+                f"{self.name}.{r['kernel']}: {r['registers']} registers, "
+                f"{r['shared_bytes']} B shared, {r['local_bytes']} B local, "
+                f"block {r['block_size']}, occupancy {r['occupancy']:.0%}"
+            )
+
     def __getattr__(self, symbol):
         return Kernel(self, self.api[symbol])
 
 
-def to_ctypes(args, spec):
+def enable_kernel_timing():
+    """
+    Time every invocation of the kernels of the loaded libraries, and of
+    libraries loaded from now on; see `kernel_timings`.
+    """
+    global kernel_timing_enabled
+    kernel_timing_enabled = True
+
+    for lib in loaded_libraries:
+        if lib.timing is None:
+            lib.timing = KernelTiming()
+
+
+def kernel_timings():
+    """
+    Return a list of dictionaries with the kernels of the loaded libraries
+    invoked since the last call, or since `enable_kernel_timing`, with the
+    kernel name `library.kernel`, the number of calls, the seconds spent in
+    them, and the zone updates per second in millions, `Mzps`. The list is
+    sorted by the seconds, descending. In GPU mode, this waits for the last
+    timed kernel to finish.
+    """
+    report = list()
+
+    for lib in loaded_libraries:
+        if lib.timing is None:
+            continue
+
+        for name, (calls, zones, seconds) in lib.timing.collect().items():
+            report.append(
+                dict(
+                    kernel=f"{lib.name}.{name}",
+                    calls=calls,
+                    seconds=seconds,
+                    Mzps=zones / seconds * 1e-6 if seconds > 0.0 else 0.0,
+                )
+            )
+
+    return sorted(report, key=lambda r: -r["seconds"])
+
+
+def is_memory_mapped(shape, precision="float64"):
+    """
+    Return whether `host_zeros` backs an array of this shape with a file.
+    """
+    directory = build_config["memory_map_directory"]
+    size = PRECISIONS[precision] * prod(shape)
+    return directory is not None and size >= MEMORY_MAP_MIN_BYTES
+
+
+def host_zeros(shape, precision="float64"):
+    """
+    Return a host array of zeros, of 64-bit floats, or of the given
+    precision.
+
+    If the build is configured with a `memory_map_directory`, an array of at
+    least `MEMORY_MAP_MIN_BYTES` is backed by an anonymous file there: the
+    file is unlinked once it's mapped, so it's removed when the array is
+    freed, or if the process dies. The operating system is advised that the
+    pages are accessed sequentially, so it reads ahead of the kernels and
+    evicts the pages behind them, rather than swapping. The kernels traverse
+    the arrays in memory order, and in omp mode each thread is given a
+    contiguous block of rows, so this holds in every CPU mode.
+    """
+    import mmap
+    import numpy as np
+
+    if not is_memory_mapped(shape, precision):
+        return np.zeros(shape, dtype=precision)
+
+    size = PRECISIONS[precision] * prod(shape)
+    directory = build_config["memory_map_directory"]
+    fd, path = mkstemp(prefix="sailfish-", suffix=".bin", dir=directory)
+
+    try:
+        unlink(path)
+        ftruncate(fd, size)
+        buffer = mmap.mmap(fd, size)
+    finally:
+        close(fd)
+
+    if hasattr(mmap, "MADV_SEQUENTIAL"):
+        buffer.madvise(mmap.MADV_SEQUENTIAL)
+
+    return np.frombuffer(buffer, dtype=precision).reshape(shape)
+
+
+def to_ctypes(args, spec, precision="float64"):
     """
     Coerce a sequence of values to their appropriate ctype.
 
-    The expected type is determined from the `spec` list.
+    The expected type is determined from the `spec` list, and the type of
+    `real` arguments from the library precision.
     """
+    c_real = c_float if precision == "float32" else c_double
+
     for arg, (typename, _, _) in zip(args, spec):
         if typename == "int":
             yield c_int(arg)
@@ -262,6 +741,80 @@ def to_ctypes(args, spec):
             yield c_double(arg)
         elif typename == "double*":
             yield arg.ctypes.data_as(POINTER(c_double))
+        elif typename == "real":
+            yield c_real(arg)
+        elif typename == "real*":
+            yield arg.ctypes.data_as(POINTER(c_real))
+
+
+def threaded_call(module, kernel, args):
+    """
+    Call a kernel compiled in threads mode on each of the threads of the
+    pool, with the first axis divided evenly between them. The kernel is
+    called through ctypes, which releases the GIL, so the chunks run at once.
+    """
+    pool, num_threads = thread_pool()
+
+    def call_chunk(index):
+        module.sailfish_set_thread_chunk(index, num_threads)
+        kernel(*args)
+
+    for _ in pool.map(call_chunk, range(num_threads)):
+        pass
+
+
+def checked_call(kernel, args, spec, symbol, precision="float64"):
+    """
+    Invoke a CPU kernel with aliasing and out-of-bounds write detection.
+
+    Each array argument is copied into the interior of a larger buffer, whose
+    margins are filled with a sentinel bit pattern. The sentinel is a
+    signaling NaN, so out-of-bounds reads tend to poison the kernel output.
+    After the kernel returns, the margins are checked to be intact, and the
+    buffer interiors are copied back to the original arrays.
+    """
+    import numpy as np
+
+    pointers = ("double*", "real*")
+    arrays = [(a, name) for a, (t, name, _) in zip(args, spec) if t in pointers]
+
+    for n, (a, name_a) in enumerate(arrays):
+        for b, name_b in arrays[n + 1 :]:
+            if np.may_share_memory(a, b):
+                raise ValueError(f"arguments {name_a} and {name_b} to {symbol} alias")
+
+    ng = CHECKED_MODE_PADDING
+    buffers = dict()
+
+    def sentinel(a):
+        if a.dtype == np.float32:
+            return np.int32, CHECKED_MODE_SENTINEL_32
+        return np.int64, CHECKED_MODE_SENTINEL
+
+    for a, name in arrays:
+        bits, value = sentinel(a)
+        buf = np.empty(a.size + 2 * ng, dtype=a.dtype)
+        buf.view(bits)[...] = value
+        buf[ng:-ng] = a.reshape(-1)
+        buffers[id(a)] = buf
+
+    padded_args = [
+        buffers[id(a)][ng:-ng] if t in pointers else a
+        for a, (t, _, _) in zip(args, spec)
+    ]
+    kernel(*to_ctypes(padded_args, spec, precision))
+
+    for a, name in arrays:
+        buf = buffers[id(a)]
+        bits, value = sentinel(a)
+        bits = buf.view(bits)
+
+        if (bits[:ng] != value).any():
+            raise IndexError(f"{symbol} wrote before the start of argument {name}")
+        if (bits[-ng:] != value).any():
+            raise IndexError(f"{symbol} wrote past the end of argument {name}")
+
+        a.reshape(-1)[...] = buf[ng:-ng]
 
 
 def type_error(sym, n, a, b):
@@ -280,7 +833,7 @@ def layout_error(sym, n):
     return TypeError(f"arg {n} to {sym} is not c-contiguous")
 
 
-def validate_types(args, spec, symbol, xp):
+def validate_types(args, spec, symbol, xp, precision="float64"):
     if len(args) != len(spec):
         raise arglen_error(symbol, args, spec)
 
@@ -291,11 +844,15 @@ def validate_types(args, spec, symbol, xp):
         elif typename == "double":
             if type(arg) not in [float, xp.float64]:
                 raise type_error(symbol, n, arg, "float64")
-        elif typename == "double*":
+        elif typename == "real":
+            if type(arg) not in [float, xp.float64, xp.float32]:
+                raise type_error(symbol, n, arg, "float")
+        elif typename in ("double*", "real*"):
+            dtype = precision if typename == "real*" else "float64"
             if type(arg) is not xp.ndarray:
                 raise type_error(symbol, n, arg, "ndarray")
-            if arg.dtype != xp.float64:
-                raise dtype_error(symbol, n, arg, "float64")
+            if arg.dtype != dtype:
+                raise dtype_error(symbol, n, arg, dtype)
             if not arg.flags["C_CONTIGUOUS"]:
                 raise layout_error(symbol, n)
 
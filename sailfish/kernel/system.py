@@ -5,8 +5,10 @@ Functions for querying compute capabilities and configuring JIT builds.
 import contextlib
 import logging
 import multiprocessing
+import os
 import platform
 import time
+from functools import lru_cache
 
 try:
     from contextlib import nullcontext
@@ -42,14 +44,45 @@ build_config = {
     "enable_openmp": True,
     "extra_compile_args": [],
     "extra_link_args": [],
+    "numa_policy": "default",
+    "deterministic": False,
+    "gpu_devices": None,
+    "gpu_backend": "auto",
+    "gpu_compiler": "rtc",
+    "num_threads": None,
+    "memory_map_directory": None,
 }
 
+NUMA_POLICIES = ["default", "first-touch", "interleave"]
+GPU_BACKENDS = ["auto", "cuda", "hip"]
+GPU_COMPILERS = ["rtc", "offline"]
+
+
+def openmp_arguments():
+    """
+    Return the compile and link arguments which enable OpenMP on this
+    platform, or empty lists if they are unknown.
+    """
+    if platform.system() == "Darwin":
+        return ["-Xpreprocessor", "-fopenmp"], ["-L/usr/local/lib", "-lomp"]
+    elif platform.system() == "Linux":
+        return ["-fopenmp", "-std=c99"], ["-fopenmp"]
+    else:
+        return [], []
+
 
 def configure_build(
     enable_openmp=True,
     extra_compile_args=None,
     extra_link_args=None,
     execution_mode=None,
+    numa_policy="default",
+    deterministic=False,
+    gpu_devices=None,
+    gpu_backend="auto",
+    gpu_compiler="rtc",
+    num_threads=None,
+    memory_map_directory=None,
 ):
     """
     Initiate the `build_config` module-level variable.
@@ -58,11 +91,65 @@ def configure_build(
     Windows platforms and specific Linux flavors should be added soon. The
     keyword arguemnts may be Python objects, or strings to facilitate passing
     values right from a configparser instance.
+
+    The `numa_policy` argument only has an effect in omp mode. With
+    "first-touch", patch arrays are initialized from a parallel loop, so that
+    memory pages land on the NUMA node of the thread which operates on them.
+    With "interleave", pages are distributed round-robin over all the NUMA
+    nodes (this requires libnuma). It's set in the `[build]` section of the
+    .sailfish file, or with the driver's `--numa-policy` flag.
+
+    With `deterministic=True`, the build is configured so repeated runs of the
+    same code on the same system give bitwise identical results: floating
+    point contraction (fused multiply-add) is disabled for CPU and GPU
+    kernels, so the arithmetic doesn't depend on what the compiler chose to
+    fuse, the OpenMP thread count is fixed and dynamic thread adjustment is
+    turned off, and cupy's CUB-accelerated reductions are replaced by its own
+    reduction kernels, which have a fixed summation order. The kernels update
+    each zone independently, and the time series reductions are summed over
+    patches in a fixed order, so nothing else depends on scheduling.
+
+    The `gpu_devices` argument is a list of the ids of the GPUs to use in gpu
+    mode, or a comma-separated string of them, e.g. "0,2"; by default all the
+    visible devices are used. The patches of a solver are assigned to them
+    round-robin, and peer-to-peer access is enabled between them where the
+    hardware supports it, so guard zones are copied directly between devices
+    (see `copy_between_devices`).
+
+    GPU kernels run on NVIDIA devices with a CUDA build of cupy, and on AMD
+    devices with a ROCm build of it. The `gpu_backend` argument may be "cuda"
+    or "hip" to insist on one of them, in which case gpu mode fails if the
+    installed cupy targets the other; with "auto" (the default) the backend is
+    whatever cupy was built for. With `gpu_compiler="rtc"` (the default),
+    kernels are compiled at runtime by NVRTC or hiprtc, and with "offline"
+    they are compiled by nvcc or hipcc, which have to be on the path.
+
+    The `num_threads` argument is the number of threads kernels are run on in
+    the threads execution mode; by default it's the number of cores.
+
+    The `memory_map_directory` argument is a directory in which to back the
+    large host arrays of the CPU modes with memory-mapped files, so a run
+    can be larger than the RAM, e.g. for a one-off convergence check at high
+    resolution; the page cache then decides what stays in memory. It has to
+    be on a fast local disk, and it's ignored in gpu mode (see
+    `sailfish.kernel.library.host_zeros`).
     """
 
+    if numa_policy not in NUMA_POLICIES:
+        raise ValueError(f"numa_policy must be one of {NUMA_POLICIES}")
+
+    if gpu_backend not in GPU_BACKENDS:
+        raise ValueError(f"gpu_backend must be one of {GPU_BACKENDS}")
+
+    if gpu_compiler not in GPU_COMPILERS:
+        raise ValueError(f"gpu_compiler must be one of {GPU_COMPILERS}")
+
     if type(enable_openmp) is str:
         enable_openmp = {"True": True, "False": False}[enable_openmp]
 
+    if type(deterministic) is str:
+        deterministic = {"True": True, "False": False}[deterministic]
+
     enable_openmp = enable_openmp and execution_mode == "omp"
 
     if type(extra_compile_args) is str:
@@ -71,22 +158,8 @@ def configure_build(
     if type(extra_link_args) is str:
         extra_link_args = extra_link_args.split()
 
-    if platform.system() == "Darwin":
-        logger.info("configure JIT build for MacOS")
-        sys_compile_args = ["-Xpreprocessor", "-fopenmp"]
-        sys_link_args = ["-L/usr/local/lib", "-lomp"]
-    elif platform.system() == "Linux":
-        logger.info("configure JIT build for Linux")
-        sys_compile_args = ["-fopenmp", "-std=c99"]
-        sys_link_args = ["-fopenmp"]
-    elif platform.system() == "Windows":
-        logger.info("configure JIT build for Windows")
-        sys_compile_args = []
-        sys_link_args = []
-    else:
-        logger.info("configure JIT build for unknown system")
-        sys_compile_args = []
-        sys_link_args = []
+    logger.info(f"configure JIT build for {platform.system() or 'unknown system'}")
+    sys_compile_args, sys_link_args = openmp_arguments()
 
     if enable_openmp:
         build_config["extra_compile_args"] = extra_compile_args or sys_compile_args
@@ -95,19 +168,138 @@ def configure_build(
     build_config["enable_openmp"] = enable_openmp
     logger.info(f"OpenMP is {'enabled' if enable_openmp else 'disabled'}")
 
+    if deterministic:
+        compile_args = build_config["extra_compile_args"]
+
+        if platform.system() != "Windows" and "-ffp-contract=off" not in compile_args:
+            build_config["extra_compile_args"] = compile_args + ["-ffp-contract=off"]
+
+        os.environ.setdefault("OMP_NUM_THREADS", str(multiprocessing.cpu_count()))
+        os.environ["OMP_DYNAMIC"] = "false"
+        os.environ["CUPY_ACCELERATORS"] = ""
+        logger.info(
+            f"deterministic mode with {os.environ['OMP_NUM_THREADS']} OpenMP threads"
+        )
+
+    build_config["deterministic"] = deterministic
+
+    if execution_mode == "omp" and numa_policy == "interleave":
+        try:
+            set_numa_interleave()
+        except OSError as e:
+            logger.warning(f"could not set interleave policy ({e}), using default")
+            numa_policy = "default"
+
+    build_config["numa_policy"] = numa_policy if execution_mode == "omp" else "default"
+
+    if type(gpu_devices) is str:
+        gpu_devices = [int(d) for d in gpu_devices.split(",")]
+
+    if gpu_devices is not None and not gpu_devices:
+        raise ValueError("gpu_devices must not be empty")
+
+    if gpu_devices is not None and len(set(gpu_devices)) != len(gpu_devices):
+        raise ValueError(f"gpu_devices {gpu_devices} has repeated devices")
+
+    build_config["gpu_devices"] = gpu_devices
+    build_config["gpu_backend"] = gpu_backend
+    build_config["gpu_compiler"] = gpu_compiler
+
+    if num_threads is not None and int(num_threads) < 1:
+        raise ValueError("num_threads must be positive")
+
+    build_config["num_threads"] = None if num_threads is None else int(num_threads)
+
+    if memory_map_directory is not None and not os.path.isdir(memory_map_directory):
+        raise ValueError(
+            f"memory_map_directory {memory_map_directory} is not a directory"
+        )
+
+    if execution_mode == "gpu":
+        memory_map_directory = None
+
+    build_config["memory_map_directory"] = memory_map_directory
+
+    if execution_mode == "gpu":
+        backend = installed_gpu_backend()
+
+        if gpu_backend not in ("auto", backend):
+            raise ValueError(
+                f"gpu_backend is {gpu_backend}, but cupy is built for {backend}"
+            )
+
+        logger.info(f"gpu backend is {backend}, with {gpu_compiler} compilation")
+        enable_peer_access()
+
+
+def set_numa_interleave():
+    """
+    Interleave future memory allocations for this process over all NUMA nodes.
+
+    An `OSError` is raised if libnuma cannot be loaded, or reports that NUMA
+    is not available on the system.
+    """
+    from ctypes import CDLL, c_void_p
+
+    libnuma = CDLL("libnuma.so.1")
+
+    if libnuma.numa_available() < 0:
+        raise OSError("NUMA is not available")
+
+    all_nodes = c_void_p.in_dll(libnuma, "numa_all_nodes_ptr")
+    libnuma.numa_set_interleave_mask(all_nodes)
+
+
+def numa_num_nodes():
+    """
+    Return the number of NUMA nodes on the system, or 1 if it is unknown.
+    """
+    import re
+    from os import listdir
+
+    try:
+        nodes = listdir("/sys/devices/system/node")
+        return max(1, sum(1 for n in nodes if re.fullmatch(r"node\d+", n)))
+    except FileNotFoundError:
+        return 1
+
+
+def numa_placement():
+    """
+    Return a dictionary mapping NUMA node index to the number of memory pages
+    this process has placed there.
+
+    The page counts are read from `/proc/self/numa_maps`, which is only
+    available on Linux. If it cannot be read, an empty dictionary is returned.
+    """
+    import re
+
+    placement = dict()
+    expr = re.compile(r"\bN(\d+)=(\d+)")
+
+    try:
+        with open("/proc/self/numa_maps") as f:
+            for line in f:
+                for node, pages in expr.findall(line):
+                    placement[int(node)] = placement.get(int(node), 0) + int(pages)
+    except OSError:
+        pass
+
+    return placement
+
 
 def get_array_module(mode):
     """
     Return either the numpy or cupy module, depending on the value of mode.
 
-    If mode is "cpu" or "omp", then the `numpy` module is returned. Otherwise
-    if mode is "gpu" then `cupy` is returned. The `cupy` documentation
-    recommends assigning whichever module is returned to a variable called
-    `xp`, and using that variable to access functions that are common to both,
-    for example use :code:`xp.zeros(100)`. This pattern facilitates writing
-    CPU-GPU agnostic code.
+    If mode is "cpu", "omp", "threads", or "checked", then the `numpy` module is
+    returned. Otherwise if mode is "gpu" then `cupy` is returned. The `cupy`
+    documentation recommends assigning whichever module is returned to a
+    variable called `xp`, and using that variable to access functions that are
+    common to both, for example use :code:`xp.zeros(100)`. This pattern
+    facilitates writing CPU-GPU agnostic code.
     """
-    if mode in ["cpu", "omp"]:
+    if mode in ["cpu", "omp", "threads", "checked"]:
         import numpy
 
         return numpy
@@ -116,33 +308,227 @@ def get_array_module(mode):
 
         return cupy
     else:
-        raise ValueError(f"unknown execution mode {mode}, must be [cpu|omp|gpu]")
+        raise ValueError(
+            f"unknown execution mode {mode}, must be [cpu|omp|threads|gpu|checked]"
+        )
+
+
+_thread_pool = None
+
+
+def thread_pool():
+    """
+    Return a pool of threads to run kernels on in the threads execution mode,
+    and the number of them. The pool is created when it's first needed, and
+    again if the build configuration changes the number of threads.
+    """
+    from concurrent.futures import ThreadPoolExecutor
+
+    global _thread_pool
+    num_threads = build_config["num_threads"] or multiprocessing.cpu_count()
+
+    if _thread_pool is None or _thread_pool[1] != num_threads:
+        if _thread_pool is not None:
+            _thread_pool[0].shutdown()
+
+        _thread_pool = ThreadPoolExecutor(num_threads), num_threads
+
+    return _thread_pool
+
+
+def installed_gpu_backend():
+    """
+    Return "hip" if the installed cupy is a ROCm build, and "cuda" otherwise.
+    """
+    from cupy.cuda.runtime import is_hip
+
+    return "hip" if is_hip else "cuda"
+
+
+def gpu_devices():
+    """
+    Return the list of ids of the GPUs to use, from the build configuration,
+    or all of the visible devices.
+    """
+    if build_config["gpu_devices"] is not None:
+        return list(build_config["gpu_devices"])
+
+    from cupy.cuda.runtime import getDeviceCount
+
+    return list(range(getDeviceCount()))
+
+
+def enable_peer_access():
+    """
+    Enable peer-to-peer memory access between each pair of the GPUs in use
+    which supports it. A `ValueError` is raised if a device isn't visible.
+    """
+    from cupy.cuda import Device
+    from cupy.cuda.runtime import CUDARuntimeError, getDeviceCount
+    from cupy.cuda.runtime import deviceCanAccessPeer, deviceEnablePeerAccess
+
+    devices = gpu_devices()
+    count = getDeviceCount()
+
+    if not all(0 <= d < count for d in devices):
+        raise ValueError(f"gpu_devices {devices} are not all among {count} devices")
+
+    for a in devices:
+        for b in devices:
+            if a != b and deviceCanAccessPeer(a, b):
+                with Device(a):
+                    try:
+                        deviceEnablePeerAccess(b)
+                    except CUDARuntimeError:
+                        pass  # already enabled
+
+    if len(devices) > 1:
+        logger.info(f"gpu devices in use: {devices}")
 
 
 def execution_context(mode, device_id=None):
     """
     Return a context manager appropriate for the given exuction mode.
 
-    If `mode` is "gpu", then a specific device id may be provided to specify
-    the GPU onto which kernel launches should be spawned.
+    If `mode` is "gpu", then a device index may be provided to specify the
+    GPU onto which kernel launches should be spawned. It is an index into the
+    devices in use (see `gpu_devices`), which is the device id unless the
+    build configuration selects some of them.
     """
-    if mode in ["cpu", "omp"]:
+    if mode in ["cpu", "omp", "threads", "checked"]:
         return nullcontext()
 
     elif mode == "gpu":
         from cupy.cuda import Device
 
-        return Device(device_id)
+        if device_id is None:
+            return Device()
+
+        return Device(gpu_devices()[device_id])
 
 
 def num_devices(mode):
-    if mode in ["cpu", "omp"]:
+    if mode in ["cpu", "omp", "threads", "checked"]:
         return 1
 
     elif mode == "gpu":
-        from cupy.cuda.runtime import getDeviceCount
+        return len(gpu_devices())
+
+
+EXECUTION_MODES = ["cpu", "checked", "threads", "omp", "gpu"]
+
+# The modes to try, in order, in place of one that's unavailable.
+FALLBACK_EXECUTION_MODES = dict(gpu=["omp", "cpu"], omp=["cpu"])
+
+
+@lru_cache(maxsize=None)
+def openmp_is_available():
+    """
+    Return true if the C compiler builds and links a program with OpenMP,
+    using the platform's OpenMP arguments. The result is cached.
+    """
+    import subprocess
+    import sysconfig
+    import tempfile
+
+    compile_args, link_args = openmp_arguments()
+
+    if not compile_args:
+        return False
+
+    cc = os.environ.get("CC") or sysconfig.get_config_var("CC") or "cc"
+    code = "#include <omp.h>\nint main(void) { return omp_get_max_threads() < 1; }\n"
+
+    with tempfile.TemporaryDirectory() as tmp:
+        source = os.path.join(tmp, "omp.c")
+        binary = os.path.join(tmp, "omp")
+
+        with open(source, "w") as f:
+            f.write(code)
+
+        command = cc.split() + compile_args + [source, "-o", binary] + link_args
+
+        try:
+            return subprocess.run(command, capture_output=True).returncode == 0
+        except OSError:
+            return False
+
+
+def execution_mode_problem(mode):
+    """
+    Return the reason an execution mode can't be used on this system, or
+    `None` if it can. The gpu mode needs cupy and a visible device, and the
+    omp mode needs a compiler with OpenMP; the other modes always work.
+    """
+    if mode not in EXECUTION_MODES:
+        return f"unknown execution mode {mode}"
+
+    if mode == "gpu":
+        try:
+            from cupy.cuda.runtime import getDeviceCount
+        except ImportError:
+            return "cupy is not installed"
+
+        try:
+            if getDeviceCount() == 0:
+                return "no gpu device is visible"
+        except Exception as e:
+            return f"no gpu device is usable ({e})"
+
+    if mode == "omp" and not openmp_is_available():
+        return "the C compiler doesn't support OpenMP"
+
+    return None
+
+
+def resolve_execution_mode(mode, fallback=False):
+    """
+    Return the execution mode to run in, when `mode` is requested.
+
+    That's the requested mode if it's available. Otherwise a `ValueError` is
+    raised, unless `fallback` is true, in which case the first available mode
+    of `FALLBACK_EXECUTION_MODES` is returned, with a warning, or the cpu
+    mode; this way a run doesn't fail later on, while its solver is built.
+    """
+    problem = execution_mode_problem(mode)
+
+    if problem is None:
+        return mode
 
-        return getDeviceCount()
+    if not fallback:
+        raise ValueError(f"execution mode {mode} is unavailable: {problem}")
+
+    candidates = FALLBACK_EXECUTION_MODES.get(mode, [])
+    effective = next((m for m in candidates if not execution_mode_problem(m)), "cpu")
+    banner = "*" * 72
+    logger.warning(banner)
+    logger.warning(f"execution mode {mode} is unavailable: {problem}")
+    logger.warning(f"falling back to execution mode {effective}")
+    logger.warning(banner)
+    return effective
+
+
+def copy_between_devices(dst, src):
+    """
+    Copy the array `src` into `dst`, which have the same shape and may be on
+    different GPUs. The copy is direct if the devices are the same, or if
+    `dst`'s device has peer access to `src`'s; otherwise it is staged through
+    the host. The current device must be that of `dst`.
+    """
+    try:
+        dst_device, src_device = dst.device.id, src.device.id
+    except AttributeError:
+        dst[...] = src
+        return
+
+    from cupy.cuda.runtime import deviceCanAccessPeer
+
+    if dst_device == src_device or deviceCanAccessPeer(dst_device, src_device):
+        dst[...] = src
+    else:
+        import cupy
+
+        dst[...] = cupy.asarray(src.get())
 
 
 def log_system_info(mode):
@@ -159,6 +545,67 @@ def log_system_info(mode):
         logger.info(f"gpu devices: {num_devices}x {gpu_devices}")
     logger.info(f"compute cores: {multiprocessing.cpu_count()}")
 
+    if mode == "threads":
+        logger.info(f"kernel threads: {thread_pool()[1]}")
+
+    if build_config["memory_map_directory"] is not None:
+        logger.info(f"memory-mapped arrays in: {build_config['memory_map_directory']}")
+
+    if mode == "omp":
+        logger.info(f"numa nodes: {numa_num_nodes()}")
+        logger.info(f"numa policy: {build_config['numa_policy']}")
+
+
+def build_info(mode, requested_mode=None):
+    """
+    Return a dictionary of the details of the software and system which a
+    run's trajectory can depend on, to be recorded with its output.
+
+    If the run fell back from the `requested_mode` to the execution `mode`,
+    the requested one is recorded too.
+    """
+    import sys
+    from sailfish import __version__
+
+    info = dict(
+        sailfish_version=__version__,
+        python_version=platform.python_version(),
+        platform=platform.platform(),
+        machine=platform.machine(),
+        execution_mode=mode,
+        build_config=dict(build_config),
+        omp_num_threads=os.environ.get("OMP_NUM_THREADS"),
+    )
+
+    if requested_mode is not None and requested_mode != mode:
+        info["requested_execution_mode"] = requested_mode
+
+    for module_name in ["numpy", "cffi", "cupy"]:
+        module = sys.modules.get(module_name)
+
+        if module is not None:
+            info[f"{module_name}_version"] = getattr(module, "__version__", None)
+
+    if mode == "gpu":
+        from cupy.cuda.runtime import getDeviceProperties, runtimeGetVersion
+
+        info["gpu_backend"] = installed_gpu_backend()
+        info["cuda_runtime_version"] = runtimeGetVersion()
+        info["gpu_device"] = getDeviceProperties(0)["name"].decode("utf-8")
+
+    return info
+
+
+def log_numa_placement():
+    """
+    Log the number of memory pages the process has placed on each NUMA node.
+    """
+    placement = numa_placement()
+
+    if placement:
+        pages = " ".join(f"N{n}={p}" for n, p in sorted(placement.items()))
+        logger.info(f"numa page placement: {pages}")
+
 
 @contextlib.contextmanager
 def measure_time(mode: str) -> float:
@@ -172,12 +619,25 @@ def measure_time(mode: str) -> float:
         with measure_time() as duration:
             expensive_function()
         print(f"execution took {duration()} seconds")
+
+    In GPU mode, the time is measured with events recorded on the current
+    stream, so leaving the context does not synchronize the device. Calling
+    `duration` waits for the work queued inside the context to complete.
     """
-    try:
+    if mode == "gpu":
+        from cupy.cuda import Event, get_elapsed_time
+
+        start, end = Event(), Event()
+        start.record()
+
+        def duration():
+            end.synchronize()
+            return get_elapsed_time(start, end) * 1e-3
+
+        try:
+            yield duration
+        finally:
+            end.record()
+    else:
         start = time.perf_counter()
         yield lambda: time.perf_counter() - start
-    finally:
-        if mode == "gpu":
-            from cupy.cuda.runtime import deviceSynchronize
-
-            deviceSynchronize()
@@ -0,0 +1,169 @@
+"""
+Stream downsampled frames of a running simulation over a TCP socket.
+
+A `FrameServer` accepts any number of viewer connections, by default only
+from the local host, and sends each of them a frame of the primitive data
+whenever the driver publishes one. A frame is a message made of a 4-byte
+big-endian header length, a JSON header with the simulation time, iteration,
+field names, array shape, and data type, and the array data in C order.
+Frames are sent from a thread per viewer, and a viewer which falls behind has
+frames dropped rather than slowing down the simulation. A remote viewer can
+connect through an ssh tunnel to the port.
+
+The `receive_frames` generator is a minimal client, e.g. for a Jupyter
+notebook, which yields the header and array of each frame.
+"""
+
+import json
+import socket
+import struct
+import threading
+from logging import getLogger
+from queue import Queue, Empty, Full
+
+logger = getLogger(__name__)
+
+
+def downsample(array, max_size):
+    """
+    Return a strided view of `array` with at most `max_size` zones along each
+    spatial axis. The last axis is the field index, and is not downsampled.
+    """
+    index = tuple(slice(None, None, -(-n // max_size)) for n in array.shape[:-1])
+    return array[index]
+
+
+def encode_frame(header, array):
+    """
+    Return the bytes of a frame message.
+    """
+    import numpy as np
+
+    data = np.ascontiguousarray(array, dtype=np.float32)
+    header = dict(header, shape=list(data.shape), dtype="float32")
+    head = json.dumps(header).encode("utf-8")
+    return struct.pack(">I", len(head)) + head + data.tobytes()
+
+
+class FrameServer:
+    """
+    Listens for viewer connections, and sends them the published frames.
+    """
+
+    def __init__(self, port, host="localhost", max_size=256, queue_size=2):
+        self.max_size = max_size
+        self.queue_size = queue_size
+        self.clients = []
+        self.lock = threading.Lock()
+        self.listener = socket.create_server((host, port))
+        self.port = self.listener.getsockname()[1]
+        thread = threading.Thread(target=self.accept, daemon=True)
+        thread.start()
+        logger.info(f"live view frames served on port {self.port}")
+
+    def accept(self):
+        while True:
+            try:
+                conn, address = self.listener.accept()
+            except OSError:
+                return
+
+            queue = Queue(maxsize=self.queue_size)
+            with self.lock:
+                self.clients.append(queue)
+
+            thread = threading.Thread(target=self.send, args=(conn, queue), daemon=True)
+            thread.start()
+            logger.info(f"live viewer connected from {address[0]}")
+
+    def send(self, conn, queue):
+        try:
+            with conn:
+                while True:
+                    message = queue.get()
+
+                    if message is None:
+                        break
+                    try:
+                        conn.sendall(message)
+                    except OSError:
+                        break
+        finally:
+            with self.lock:
+                self.clients.remove(queue)
+            logger.info("live viewer disconnected")
+
+    @property
+    def num_clients(self):
+        with self.lock:
+            return len(self.clients)
+
+    def publish(self, primitive, time, iteration, fields=[]):
+        """
+        Send a downsampled copy of the primitive data to each viewer. The
+        field names, if given, are sent in the frame header.
+
+        If a viewer's queue is full, its oldest frame is dropped. Nothing is
+        done if there are no viewers.
+        """
+        if not self.num_clients:
+            return
+
+        header = dict(time=time, iteration=iteration, fields=list(fields))
+        message = encode_frame(header, downsample(primitive, self.max_size))
+
+        with self.lock:
+            for queue in self.clients:
+                try:
+                    queue.put_nowait(message)
+                except Full:
+                    try:
+                        queue.get_nowait()
+                    except Empty:
+                        pass
+                    queue.put_nowait(message)
+
+    def close(self):
+        """
+        Stop accepting viewers, and have the viewer threads disconnect after
+        the frame they're sending. Their queued frames are dropped, so that
+        the end of the stream can always be queued.
+        """
+        self.listener.close()
+
+        with self.lock:
+            for queue in self.clients:
+                try:
+                    while True:
+                        queue.get_nowait()
+                except Empty:
+                    pass
+                queue.put_nowait(None)
+
+
+def receive_frames(host="localhost", port=5555):
+    """
+    Connect to a frame server, and generate `(header, array)` pairs.
+    """
+    import numpy as np
+
+    def read(conn, n):
+        data = bytearray()
+        while len(data) < n:
+            chunk = conn.recv(n - len(data))
+            if not chunk:
+                raise EOFError
+            data.extend(chunk)
+        return bytes(data)
+
+    with socket.create_connection((host, port)) as conn:
+        while True:
+            try:
+                (size,) = struct.unpack(">I", read(conn, 4))
+                header = json.loads(read(conn, size).decode("utf-8"))
+                count = int(np.prod(header["shape"]))
+                data = read(conn, 4 * count)
+            except EOFError:
+                return
+            array = np.frombuffer(data, dtype=np.float32).reshape(header["shape"])
+            yield header, array
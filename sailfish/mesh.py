@@ -1,9 +1,180 @@
 """
 Contains classes for different mesh geometries and dimensionalities.
+
+Each mesh class stores the few numbers it's defined by as named tuple fields,
+which keeps meshes small and stable in checkpoint files. The fields are not
+checked for consistency when a mesh is created; instead, a mesh describes
+itself as a `MeshGeometry` (coordinate system, extent, and spacing) and a
+`MeshTopology` (zone counts and guard zone depth), and its `validate` method
+checks both. The driver validates the setup's mesh before building a solver,
+and the class method constructors validate the meshes they return.
+
+A log-polar mesh covers an annulus in the plane, with zones whose radial
+width grows in proportion to the radius, so a disk around a single mass is
+resolved most finely near its inner edge, and no zones are spent in the
+corners of a square domain; its `coordinates` are `log_polar`.
+
+Solvers decompose a mesh into patches along its first axis with its `tile`
+method. Each patch is advanced on its own device, and its guard zones are
+exchanged with its neighbors' before each stage.
 """
 
-from typing import NamedTuple
-from math import log, log10, pi
+from typing import NamedTuple, Tuple
+from math import cos, exp, log, log10, pi, sin
+from numbers import Integral
+from sailfish.subdivide import subdivide
+
+# Kernels loop over zones with int indices, so the number of zones along each
+# axis, including guard zones, must not exceed this. Offsets into arrays of
+# zones are 64-bit (`index_t`), so the total array size is limited by memory.
+MAX_KERNEL_INDEX = 2**31 - 1
+MAX_ARRAY_SIZE = 2**63 - 1
+
+# Zones more elongated than this in one direction are almost certainly a
+# mistake in the mesh parameters.
+MAX_ZONE_ASPECT_RATIO = 100.0
+
+# Codes of the coordinate systems known to the kernels' geometry functions,
+# in sailfish/kernel/geometry.c.
+KERNEL_COORDS_CARTESIAN = 0
+KERNEL_COORDS_SPHERICAL = 1
+KERNEL_COORDS_SPHERICAL_POLAR = 2
+KERNEL_COORDS_PLANAR_POLAR = 3
+
+
+class MeshGeometry(NamedTuple):
+    """
+    The coordinate system, extent, and zone spacing of a mesh.
+
+    The `lower` and `upper` tuples are the coordinates of the domain corners,
+    and `spacing` has the zone width along each axis. For log-spherical and
+    log-polar meshes, the radial spacing is relative, `dr / r`, so that it
+    can be compared with the polar or azimuthal spacing in radians.
+    """
+
+    coordinates: str
+    lower: Tuple[float, ...]
+    upper: Tuple[float, ...]
+    spacing: Tuple[float, ...]
+
+    @property
+    def kernel_coordinates(self):
+        """
+        The code of the coordinate system in the kernels' geometry functions,
+        which compute face areas, cell volumes, and geometric source terms.
+        """
+        if self.coordinates == "cartesian":
+            return KERNEL_COORDS_CARTESIAN
+        elif self.coordinates == "log_polar":
+            return KERNEL_COORDS_PLANAR_POLAR
+        elif len(self.lower) == 1:
+            return KERNEL_COORDS_SPHERICAL
+        else:
+            return KERNEL_COORDS_SPHERICAL_POLAR
+
+    def validate(self):
+        """
+        Raise a `ValueError` unless the extent and spacing are positive, and
+        the zone aspect ratio is reasonable.
+        """
+        for lo, hi in zip(self.lower, self.upper):
+            if not hi > lo:
+                raise ValueError(f"mesh upper bound {hi} is not above lower bound {lo}")
+
+        if self.coordinates == "log_spherical" and not self.lower[0] > 0.0:
+            raise ValueError("log-spherical mesh inner radius must be positive")
+
+        if self.coordinates == "log_polar" and not self.lower[0] > 0.0:
+            raise ValueError("log-polar mesh inner radius must be positive")
+
+        if not all(d > 0.0 for d in self.spacing):
+            raise ValueError(f"mesh spacing {self.spacing} must be positive")
+
+        if max(self.spacing) / min(self.spacing) > MAX_ZONE_ASPECT_RATIO:
+            raise ValueError(
+                f"mesh zone aspect ratio exceeds {MAX_ZONE_ASPECT_RATIO}, "
+                f"the spacing is {self.spacing}"
+            )
+
+
+class MeshTopology(NamedTuple):
+    """
+    The number of zones along each axis of a mesh, and the guard zone depth.
+    """
+
+    shape: Tuple[int, ...]
+    num_guard: int = 2
+
+    @property
+    def num_zones(self):
+        tot = 1
+        for n in self.shape:
+            tot *= n
+        return tot
+
+    def validate(self, num_fields=4):
+        """
+        Raise a `ValueError` unless the zone counts are positive integers, and
+        an array of `num_fields` values per zone, with guard zones, can be
+        indexed by the kernels.
+        """
+        if not all(isinstance(n, Integral) and n > 0 for n in self.shape):
+            raise ValueError(f"mesh shape {self.shape} must be positive integers")
+
+        size = num_fields
+        for n in self.shape:
+            if n + 2 * self.num_guard > MAX_KERNEL_INDEX:
+                raise ValueError(
+                    f"mesh shape {self.shape} is too large: kernels can loop "
+                    f"over {MAX_KERNEL_INDEX} zones on each axis"
+                )
+            size *= n + 2 * self.num_guard
+
+        if size > MAX_ARRAY_SIZE:
+            raise ValueError(
+                f"mesh shape {self.shape} is too large: arrays of {num_fields} "
+                f"fields need {size} values, and kernels can index {MAX_ARRAY_SIZE}"
+            )
+
+    def tile(self, num_patches):
+        """
+        Return a list of the `(i0, i1)` index ranges of `num_patches`
+        contiguous patches, which divide the first axis as evenly as
+        possible.
+
+        Each patch's guard zones are filled from its neighbors' interior
+        zones, so a `ValueError` is raised if a patch would be narrower than
+        the guard zone depth.
+        """
+        if not isinstance(num_patches, Integral) or num_patches < 1:
+            raise ValueError(f"num_patches must be positive, got {num_patches}")
+
+        if self.shape[0] < num_patches * self.num_guard:
+            raise ValueError(
+                f"mesh shape {self.shape} is too small for {num_patches} patches, "
+                f"which need at least {self.num_guard} zones each on axis 0"
+            )
+
+        return list(subdivide(self.shape[0], num_patches))
+
+
+class Face(NamedTuple):
+    """
+    A zone face: its index, the axis it's normal to, the coordinates of its
+    center, its unit normal vector (pointing toward increasing index, in the
+    mesh coordinate basis), and its area.
+
+    The index `(i, j)` of a face normal to axis 0 means it's the left face of
+    zone `(i, j)`, so it ranges up to `ni` inclusive on that axis. In 1D
+    cartesian meshes the area is per unit area of the transverse plane, and
+    in 2D cartesian meshes it's per unit length in z.
+    """
+
+    index: Tuple[int, ...]
+    axis: int
+    position: Tuple[float, ...]
+    normal: Tuple[float, ...]
+    area: float
 
 
 class PlanarCartesianMesh(NamedTuple):
@@ -27,6 +198,35 @@ class PlanarCartesianMesh(NamedTuple):
     def min_spacing(self, time=None):
         return self.dx
 
+    @property
+    def geometry(self):
+        return MeshGeometry("cartesian", (self.x0,), (self.x1,), (self.dx,))
+
+    @property
+    def topology(self):
+        return MeshTopology(self.shape)
+
+    def tile(self, num_patches, num_guard=2):
+        """
+        Return the index ranges of a decomposition into patches; see
+        `MeshTopology.tile`.
+        """
+        return MeshTopology(self.shape, num_guard).tile(num_patches)
+
+    def validate(self, num_fields=4):
+        self.topology.validate(num_fields)
+        self.geometry.validate()
+        return self
+
+    @classmethod
+    def from_geometry(cls, geometry, topology):
+        """
+        Return a validated mesh with the given extent and zone count; the
+        spacing of the geometry is ignored.
+        """
+        (x0,), (x1,), (n,) = geometry.lower, geometry.upper, topology.shape
+        return cls(x0, x1, n).validate()
+
     @property
     def shape(self):
         return (self.num_zones,)
@@ -42,12 +242,32 @@ class PlanarCartesianMesh(NamedTuple):
     def zone_centers(self, t, i0=0, i1=None):
         return [self.zone_center(t, i) for i in range(i0, i1 or self.shape[0])]
 
+    def cell_at(self, x):
+        """
+        Return the index of the zone containing the point `x`, or `None` if
+        the point is outside the mesh. A point on a face is in the zone to its
+        right.
+        """
+        if not self.x0 <= x < self.x1:
+            return None
+        return min(int((x - self.x0) / self.dx), self.num_zones - 1)
+
     def faces(self, i0=0, i1=None):
         if i1 is None:
             i1 = self.shape[0]
         x0, dx = self.x0, self.dx
         return [x0 + i * dx for i in range(i0, i1 + 1)]
 
+    def iter_faces(self, axis=0, time=None):
+        """
+        Generate the `Face` of each zone face, from left to right.
+        """
+        if axis != 0:
+            raise ValueError("a 1D mesh only has faces normal to axis 0")
+
+        for i, x in enumerate(self.faces()):
+            yield Face((i,), 0, (x,), (1.0,), 1.0)
+
 
 class LogSphericalMesh(NamedTuple):
     """
@@ -90,6 +310,40 @@ class LogSphericalMesh(NamedTuple):
     def polar_spacing(self):
         return self.polar_extent / self.num_polar_zones
 
+    @property
+    def geometry(self):
+        dlogr = 10 ** (1.0 / self.num_zones_per_decade) - 1.0
+
+        if not self.polar_grid:
+            return MeshGeometry("log_spherical", (self.r0,), (self.r1,), (dlogr,))
+        else:
+            return MeshGeometry(
+                "log_spherical",
+                (self.r0, 0.0),
+                (self.r1, self.polar_extent),
+                (dlogr, self.polar_spacing),
+            )
+
+    @property
+    def topology(self):
+        return MeshTopology(self.shape)
+
+    def tile(self, num_patches, num_guard=2):
+        """
+        Return the index ranges of a decomposition into patches; see
+        `MeshTopology.tile`.
+        """
+        return MeshTopology(self.shape, num_guard).tile(num_patches)
+
+    def validate(self, num_fields=4):
+        if not 0.0 < self.r0 < self.r1:
+            raise ValueError(f"radii must satisfy 0 < r0 < r1, got {self.r0}, {self.r1}")
+        if self.num_zones_per_decade <= 0:
+            raise ValueError("num_zones_per_decade must be positive")
+        self.topology.validate(num_fields)
+        self.geometry.validate()
+        return self
+
     def scale_factor(self, time):
         """
         Return the scale factor at a given time.
@@ -171,6 +425,182 @@ class LogSphericalMesh(NamedTuple):
         """
         return [j * self.polar_spacing for j in range(self.num_polar_zones + 1)]
 
+    def iter_faces(self, axis=0, time=None):
+        """
+        Generate the `Face` of each zone face normal to the given axis, 0 for
+        radial and 1 for polar. Positions are `(r, theta)` and areas are
+        proper, at the given time, or comoving if the time is `None`.
+
+        The faces of 1D meshes are complete spheres; radial faces of 2D
+        meshes are bands of a sphere, and polar faces are cones.
+        """
+        a = 1.0 if time is None else self.scale_factor(time)
+        r = [a * r for r in self.faces()]
+
+        if not self.polar_grid:
+            if axis != 0:
+                raise ValueError("a 1D mesh only has faces normal to axis 0")
+
+            for i, rf in enumerate(r):
+                yield Face((i,), 0, (rf,), (1.0,), 4.0 * pi * rf**2)
+
+        elif axis == 0:
+            q = self.polar_vertices
+
+            for i, rf in enumerate(r):
+                for j in range(self.num_polar_zones):
+                    qc = 0.5 * (q[j] + q[j + 1])
+                    da = 2.0 * pi * rf**2 * (cos(q[j]) - cos(q[j + 1]))
+                    yield Face((i, j), 0, (rf, qc), (1.0, 0.0), da)
+
+        elif axis == 1:
+            for i in range(self.num_radial_zones):
+                rc = 0.5 * (r[i] + r[i + 1])
+                for j, qf in enumerate(self.polar_vertices):
+                    da = pi * (r[i + 1] ** 2 - r[i] ** 2) * sin(qf)
+                    yield Face((i, j), 1, (rc, qf), (0.0, 1.0), da)
+
+        else:
+            raise ValueError("a 2D mesh has faces normal to axis 0 or 1")
+
+
+class LogPolar2DMesh(NamedTuple):
+    """
+    A 2D mesh of an annulus `r0 < r < r1` in the plane, with logarithmic
+    radial binning and `nj` azimuthal zones.
+
+    The number of radial zones is the one which makes the zones closest to
+    square, i.e. the relative radial spacing `dr / r` close to the azimuthal
+    spacing. The first axis is the radius, and the second the azimuth, from
+    zero to `2 pi`. Zone positions are returned in cartesian coordinates, as
+    by `PlanarCartesian2DMesh`, so setups can sample either mesh.
+    """
+
+    r0: float = 0.1
+    r1: float = 10.0
+    nj: int = 256
+
+    def __str__(self):
+        return f"<log polar 2d: ({self.r0} -> {self.r1}), shape {self.shape}>"
+
+    @property
+    def geometry(self):
+        return MeshGeometry(
+            "log_polar",
+            (self.r0, 0.0),
+            (self.r1, 2.0 * pi),
+            (self.dlogr, self.dphi),
+        )
+
+    @property
+    def topology(self):
+        return MeshTopology(self.shape)
+
+    def tile(self, num_patches, num_guard=2):
+        """
+        Return the index ranges of a decomposition into patches; see
+        `MeshTopology.tile`.
+        """
+        return MeshTopology(self.shape, num_guard).tile(num_patches)
+
+    def validate(self, num_fields=4):
+        if not 0.0 < self.r0 < self.r1:
+            raise ValueError(
+                f"radii must satisfy 0 < r0 < r1, got {self.r0}, {self.r1}"
+            )
+        self.topology.validate(num_fields)
+        self.geometry.validate()
+        return self
+
+    @property
+    def ni(self):
+        return max(1, round(log(self.r1 / self.r0) / self.dphi))
+
+    @property
+    def dlogr(self):
+        return log(self.r1 / self.r0) / self.ni
+
+    @property
+    def dphi(self):
+        return 2.0 * pi / self.nj
+
+    @property
+    def shape(self):
+        return self.ni, self.nj
+
+    @property
+    def num_total_zones(self):
+        return self.ni * self.nj
+
+    def min_spacing(self, time=None):
+        r0, r1 = self.radial_faces(0, 1)
+        return min(r1 - r0, r0 * self.dphi)
+
+    def zone_size(self, x, y):
+        """
+        Return the size of the zone containing the point `(x, y)`, the larger
+        of its radial and azimuthal widths.
+        """
+        r = (x * x + y * y) ** 0.5
+        return r * max(self.dlogr, self.dphi)
+
+    def radial_faces(self, i0=0, i1=None):
+        """
+        Return the radii of the faces of the zones in an index range, one
+        more than the number of zones.
+        """
+        if i1 is None:
+            i1 = self.ni
+        return [self.r0 * exp(i * self.dlogr) for i in range(i0, i1 + 1)]
+
+    def zone_radius(self, i):
+        """
+        Return the radius of the center of the zones `(i, j)`, which is half
+        way between their faces in `log(r)`.
+        """
+        return self.r0 * exp((i + 0.5) * self.dlogr)
+
+    def cell_coordinates(self, i, j):
+        r = self.zone_radius(i)
+        phi = (j + 0.5) * self.dphi
+        return r * cos(phi), r * sin(phi)
+
+    def cell_center_arrays(self, xp=None):
+        """
+        Return two arrays of the mesh shape, with the zone center x and y
+        coordinates. The arrays are allocated with the array module `xp`,
+        which is numpy by default.
+        """
+        if xp is None:
+            import numpy as xp
+
+        r = self.r0 * xp.exp((xp.arange(self.ni) + 0.5) * self.dlogr)
+        phi = (xp.arange(self.nj) + 0.5) * self.dphi
+        return r[:, None] * xp.cos(phi), r[:, None] * xp.sin(phi)
+
+    def iter_faces(self, axis=0, time=None):
+        """
+        Generate the `Face` of each zone face normal to the given axis, 0 for
+        radial and 1 for azimuthal. Positions are `(r, phi)`, normals are in
+        the `(r, phi)` basis, and areas are per unit length in z.
+        """
+        ni, nj = self.shape
+        r = self.radial_faces()
+        dphi = self.dphi
+
+        if axis == 0:
+            for i in range(ni + 1):
+                for j in range(nj):
+                    phi = (j + 0.5) * dphi
+                    yield Face((i, j), 0, (r[i], phi), (1.0, 0.0), r[i] * dphi)
+        elif axis == 1:
+            for i in range(ni):
+                rc = self.zone_radius(i)
+                for j in range(nj + 1):
+                    yield Face((i, j), 1, (rc, j * dphi), (0.0, 1.0), r[i + 1] - r[i])
+        else:
+            raise ValueError("a 2D mesh has faces normal to axis 0 or 1")
+
 
 class PlanarCartesian2DMesh(NamedTuple):
     """
@@ -191,6 +621,41 @@ class PlanarCartesian2DMesh(NamedTuple):
     def __str__(self):
         return f"<planar cartesian 2d: ({self.x0} -> {self.x1}) x ({self.y0} -> {self.y1}), shape {self.shape}>"
 
+    @property
+    def geometry(self):
+        return MeshGeometry(
+            "cartesian",
+            (self.x0, self.y0),
+            (self.x1, self.y1),
+            (self.dx, self.dy),
+        )
+
+    @property
+    def topology(self):
+        return MeshTopology(self.shape)
+
+    def tile(self, num_patches, num_guard=2):
+        """
+        Return the index ranges of a decomposition into patches; see
+        `MeshTopology.tile`.
+        """
+        return MeshTopology(self.shape, num_guard).tile(num_patches)
+
+    def validate(self, num_fields=4):
+        self.topology.validate(num_fields)
+        self.geometry.validate()
+        return self
+
+    @classmethod
+    def from_geometry(cls, geometry, topology):
+        """
+        Return a validated mesh with the given extent and zone counts; the
+        spacing of the geometry is ignored.
+        """
+        (x0, y0), (x1, y1) = geometry.lower, geometry.upper
+        ni, nj = topology.shape
+        return cls(x0, y0, x1, y1, ni, nj).validate()
+
     @classmethod
     def centered_square(cls, domain_radius, resolution):
         x0 = -domain_radius
@@ -199,9 +664,7 @@ class PlanarCartesian2DMesh(NamedTuple):
         y1 = +domain_radius
         ni = resolution
         nj = resolution
-        dx = 2.0 * domain_radius / resolution
-        dy = 2.0 * domain_radius / resolution
-        return PlanarCartesian2DMesh(x0, y0, x1, y1, ni, nj)
+        return PlanarCartesian2DMesh(x0, y0, x1, y1, ni, nj).validate()
 
     @classmethod
     def centered_rectangle(cls, height, resolution, aspect: int):
@@ -213,7 +676,7 @@ class PlanarCartesian2DMesh(NamedTuple):
         y1 = +0.5 * height
         ni = resolution * aspect
         nj = resolution
-        return PlanarCartesian2DMesh(x0, y0, x1, y1, ni, nj)
+        return PlanarCartesian2DMesh(x0, y0, x1, y1, ni, nj).validate()
 
     @property
     def dx(self):
@@ -230,6 +693,15 @@ class PlanarCartesian2DMesh(NamedTuple):
     def min_spacing(self, time=None):
         return min(self.dx, self.dy)
 
+    def zone_size(self, x, y):
+        """
+        Return the size of the zone containing the point `(x, y)`.
+
+        The mesh is uniform, so this is the larger of the zone spacings
+        everywhere; meshes with variable resolution return the local value.
+        """
+        return max(self.dx, self.dy)
+
     @property
     def num_total_zones(self):
         return self.ni * self.nj
@@ -239,6 +711,31 @@ class PlanarCartesian2DMesh(NamedTuple):
         y = self.y0 + (j + 0.5) * self.dy
         return x, y
 
+    def cell_at(self, x, y):
+        """
+        Return the index `(i, j)` of the zone containing the point `(x, y)`,
+        or `None` if the point is outside the mesh. A point on a face is in
+        the zone to its right (or above it).
+        """
+        if not (self.x0 <= x < self.x1 and self.y0 <= y < self.y1):
+            return None
+        i = min(int((x - self.x0) / self.dx), self.ni - 1)
+        j = min(int((y - self.y0) / self.dy), self.nj - 1)
+        return i, j
+
+    def cell_center_arrays(self, xp=None):
+        """
+        Return two arrays of the mesh shape, with the zone center x and y
+        coordinates. The arrays are allocated with the array module `xp`,
+        which is numpy by default.
+        """
+        if xp is None:
+            import numpy as xp
+
+        x = self.x0 + (xp.arange(self.ni) + 0.5) * self.dx
+        y = self.y0 + (xp.arange(self.nj) + 0.5) * self.dy
+        return xp.meshgrid(x, y, indexing="ij")
+
     def vertex_coordinates(self, i, j):
         """
         Return the position of the lower-left corner of zone (i, j).
@@ -247,6 +744,54 @@ class PlanarCartesian2DMesh(NamedTuple):
         y = self.y0 + j * self.dy
         return x, y
 
+    def iter_faces(self, axis=0, time=None):
+        """
+        Generate the `Face` of each zone face normal to the given axis, in
+        the order of the face index, with `j` the fastest.
+        """
+        ni, nj = self.shape
+        dx, dy = self.dx, self.dy
+
+        if axis == 0:
+            for i in range(ni + 1):
+                for j in range(nj):
+                    x, y = self.vertex_coordinates(i, j)
+                    yield Face((i, j), 0, (x, y + 0.5 * dy), (1.0, 0.0), dy)
+        elif axis == 1:
+            for i in range(ni):
+                for j in range(nj + 1):
+                    x, y = self.vertex_coordinates(i, j)
+                    yield Face((i, j), 1, (x + 0.5 * dx, y), (0.0, 1.0), dx)
+        else:
+            raise ValueError("a 2D mesh has faces normal to axis 0 or 1")
+
+    def interpolation_stencil(self, x, y):
+        """
+        Return the bilinear interpolation stencils of points, for zone data.
+
+        The points are arrays of coordinates. The result is a tuple `(i, j,
+        wx, wy)` of arrays: the indexes of the zone at the lower-left of the
+        four zone centers surrounding each point, and the weights of the zones
+        to its right and above it. Within half a zone of the domain edges the
+        weights are clamped, so the data is extrapolated as a constant. A
+        `ValueError` is raised if a point is outside the mesh.
+        """
+        import numpy as np
+
+        x = np.atleast_1d(np.asarray(x, dtype=float))
+        y = np.atleast_1d(np.asarray(y, dtype=float))
+
+        if np.any((x < self.x0) | (x > self.x1) | (y < self.y0) | (y > self.y1)):
+            raise ValueError("sample points must be inside the mesh")
+
+        fx = (x - self.x0) / self.dx - 0.5
+        fy = (y - self.y0) / self.dy - 0.5
+        i = np.clip(np.floor(fx).astype(int), 0, max(self.ni - 2, 0))
+        j = np.clip(np.floor(fy).astype(int), 0, max(self.nj - 2, 0))
+        wx = np.clip(fx - i, 0.0, 1.0) if self.ni > 1 else np.zeros_like(x)
+        wy = np.clip(fy - j, 0.0, 1.0) if self.nj > 1 else np.zeros_like(y)
+        return i, j, wx, wy
+
     def sub_mesh(self, di, dj):
         """
         Return a new mesh that is a subset of this one.
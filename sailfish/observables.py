@@ -0,0 +1,158 @@
+"""
+Accumulate synthetic observables of a 2D disk: emission maps and line profiles.
+
+The emission is a proxy for an optically thin line or continuum whose
+emissivity scales as the square of the surface density, so each zone emits
+:math:`\\Sigma^2 \\, dA`. An observer views the disk plane at an inclination
+`i` from its normal, from the direction of the azimuth `phi` in the disk
+plane. Each zone is projected onto the sky: along the line of nodes
+(perpendicular to `phi`) its sky coordinate is unchanged, and along `phi` it
+is foreshortened by `cos i`. Its line-of-sight velocity is
+:math:`v_{los} = -\\sin i \\, (v_x \\cos \\phi + v_y \\sin \\phi)`, positive
+away from the observer (red-shifted), in the rest frame.
+
+On each sample, a sky image (the emission binned on a square grid of pixels
+covering the mesh) and a line profile (the emission binned in line-of-sight
+velocity) are computed on the devices the solution is on, and only those
+small arrays, and the total luminosity, are copied to the host. Emission at
+velocities outside the range of the profile is left out of it, but not out
+of the image or the luminosity.
+"""
+
+from math import cos, pi, sin
+from typing import NamedTuple
+
+
+class Observer(NamedTuple):
+    """
+    The viewing geometry, and the resolution of the synthetic observables.
+    """
+
+    inclination: float = 60.0
+    """ Angle in degrees between the line of sight and the disk normal """
+
+    azimuth: float = 0.0
+    """ Azimuth in degrees of the observer's direction in the disk plane """
+
+    image_bins: int = 64
+    """ Number of pixels along each axis of the sky image """
+
+    velocity_bins: int = 100
+    """ Number of bins in the line profile """
+
+    velocity_max: float = 2.0
+    """ The line profile spans line-of-sight velocities in +/- this value """
+
+    def validate(self):
+        """
+        Raise a `ValueError` if a parameter is out of range.
+        """
+        if not 0.0 <= self.inclination <= 90.0:
+            raise ValueError(f"inclination must be in [0, 90], got {self.inclination}")
+        if self.image_bins < 1 or self.velocity_bins < 1:
+            raise ValueError("image_bins and velocity_bins must be positive")
+        if self.velocity_max <= 0.0:
+            raise ValueError(f"velocity_max must be positive, got {self.velocity_max}")
+
+    def project(self, x, y, vx, vy):
+        """
+        Return the sky coordinates and line-of-sight velocity of points in the
+        disk plane, with the given velocities.
+        """
+        i = self.inclination * pi / 180.0
+        phi = self.azimuth * pi / 180.0
+        sky_x = -x * sin(phi) + y * cos(phi)
+        sky_y = (x * cos(phi) + y * sin(phi)) * cos(i)
+        v_los = -(vx * cos(phi) + vy * sin(phi)) * sin(i)
+        return sky_x, sky_y, v_los
+
+
+class SyntheticObservables:
+    """
+    Sky images, line profiles, and luminosities at a sequence of times.
+
+    The image covers a square of the sky which contains the whole mesh, for
+    any viewing geometry. Instances are pickled into checkpoints, so
+    restarted runs keep extending the same record.
+    """
+
+    def __init__(self, mesh, observer=Observer()):
+        import numpy as np
+
+        observer.validate()
+        corners = [(x, y) for x in (mesh.x0, mesh.x1) for y in (mesh.y0, mesh.y1)]
+        extent = max((x * x + y * y) ** 0.5 for x, y in corners)
+        vmax = observer.velocity_max
+
+        self.observer = observer
+        self.image_edges = np.linspace(-extent, extent, observer.image_bins + 1)
+        self.velocity_edges = np.linspace(-vmax, vmax, observer.velocity_bins + 1)
+        self.zone_area = mesh.dx * mesh.dy
+        self.times = list()
+        self.luminosity = list()
+        self.images = list()
+        self.line_profiles = list()
+
+    def __repr__(self):
+        return f"<SyntheticObservables {self.observer} samples={len(self.times)}>"
+
+    def add(self, time, patches):
+        """
+        Add a sample of the observables, from the rest-frame patch data
+        returned by the solver's `rest_frame_patches` method.
+        """
+        from sailfish.subdivide import to_host
+
+        def launch(patch):
+            xp = patch["xp"]
+            x, y = xp.broadcast_arrays(patch["x"], patch["y"])
+            sky_x, sky_y, v_los = self.observer.project(x, y, patch["vx"], patch["vy"])
+            weight = (patch["sigma"] ** 2 * self.zone_area).ravel()
+            image_edges = xp.asarray(self.image_edges)
+            velocity_edges = xp.asarray(self.velocity_edges)
+            image = xp.histogram2d(
+                sky_x.ravel(),
+                sky_y.ravel(),
+                bins=(image_edges, image_edges),
+                weights=weight,
+            )[0]
+            line = xp.histogram(v_los.ravel(), bins=velocity_edges, weights=weight)[0]
+            return image, line, weight.sum()
+
+        # All of the patch reductions are launched before any result is
+        # copied, so the devices aren't waited on one at a time.
+        results = list()
+
+        for patch in patches:
+            with patch["execution_context"]:
+                results.append(launch(patch))
+
+        host = list()
+
+        for patch, result in zip(patches, results):
+            with patch["execution_context"]:
+                host.append([to_host(a) for a in result])
+
+        self.times.append(time)
+        self.images.append(sum(r[0] for r in host))
+        self.line_profiles.append(sum(r[1] for r in host))
+        self.luminosity.append(float(sum(r[2] for r in host)))
+
+    def arrays(self):
+        """
+        Return a dictionary of the sample times, the pixel and velocity bin
+        edges, the luminosities, the `(num_times, image_bins, image_bins)`
+        images (indexed by sky x, then sky y), the `(num_times,
+        velocity_bins)` line profiles, and the observer parameters.
+        """
+        import numpy as np
+
+        return dict(
+            time=np.array(self.times),
+            image_edges=self.image_edges,
+            velocity_edges=self.velocity_edges,
+            luminosity=np.array(self.luminosity),
+            image=np.array(self.images),
+            line_profile=np.array(self.line_profiles),
+            observer=self.observer._asdict(),
+        )
@@ -0,0 +1,229 @@
+"""
+Accumulate time series diagnostics over phase-locked binary orbit windows.
+
+Diagnostics like the torque on a binary, or the accretion rate onto each
+mass, vary on the orbital period. Averaging them over fixed time intervals
+which are not a multiple of the period aliases that variability into the
+average. Here the diagnostics are integrated every iteration, and averaged
+over windows `[t0 + n P, t0 + (n + 1) P)` of exactly one orbital period `P`.
+
+The diagnostics can also be folded on the orbital phase, to get their mean
+curve over an orbit (e.g. of the accretion rate onto each mass), and its
+orbit-to-orbit variance. That needs them at a cadence much finer than the
+period, which would be too expensive to store as a time series, so the
+folding is done as the run goes, by :py:class:`PhaseFolder`.
+"""
+
+from logging import getLogger
+
+logger = getLogger(__name__)
+
+
+class OrbitAverager:
+    """
+    Integrates a sequence of diagnostic samples over consecutive orbits.
+
+    Samples are added with `add`, at increasing times. Between two samples
+    the diagnostics are interpolated linearly (trapezoid rule), and an
+    interval which crosses the end of an orbit is split there. Each completed
+    orbit is appended to `orbits` as a dictionary with the orbit number,
+    start and end times, and the list of orbit-averaged diagnostic values,
+    and logged unless `quiet=True`. Instances are pickled into checkpoints,
+    so restarted runs continue the window that was in progress.
+    """
+
+    quiet = False
+
+    def __init__(self, period, start_time=0.0, quiet=False):
+        if period <= 0.0:
+            raise ValueError("the orbital period must be positive")
+
+        self.period = period
+        self.quiet = quiet
+        self.start_time = start_time
+        self.orbits = list()
+        self._number = 0
+        self._last = None
+        self._integral = None
+
+    def __repr__(self):
+        return f"<OrbitAverager period={self.period} orbits={len(self.orbits)}>"
+
+    @property
+    def window_start(self):
+        return self.start_time + self._number * self.period
+
+    @property
+    def window_end(self):
+        return self.window_start + self.period
+
+    def add(self, time, values):
+        """
+        Add a sample of the diagnostics at the given time.
+
+        The part of the integral before the current window is discarded, and
+        samples which are not later than the previous one are ignored. Return
+        the list of orbits completed by this sample.
+        """
+        values = list(values)
+        completed = list()
+
+        if self._last is None:
+            self._last = (time, values)
+            return completed
+
+        if time <= self._last[0]:
+            return completed
+
+        t0, v0 = self._last
+
+        while time >= self.window_end:
+            t1 = self.window_end
+            v1 = interpolate(t0, v0, time, values, t1)
+            self.accumulate(t0, v0, t1, v1)
+            completed.append(self.close_window())
+            t0, v0 = t1, v1
+
+        self.accumulate(t0, v0, time, values)
+        self._last = (time, values)
+        self.orbits.extend(completed)
+
+        for orbit in completed:
+            if not self.quiet:
+                logger.info(f"complete orbit average {orbit['orbit']}")
+
+        return completed
+
+    def interrupt(self, time):
+        """
+        Discard the window in progress, and resume with the first complete
+        orbit after `time`.
+
+        This is for discontinuities in the solution, such as a secular
+        fast-forward step, which should not be integrated over.
+        """
+        while self.window_start < time:
+            self._number += 1
+
+        self._last = None
+        self._integral = None
+
+    def accumulate(self, t0, v0, t1, v1):
+        if t1 <= self.window_start:
+            return
+
+        if t0 < self.window_start:
+            v0 = interpolate(t0, v0, t1, v1, self.window_start)
+            t0 = self.window_start
+
+        dt = t1 - t0
+        terms = [0.5 * (a + b) * dt for a, b in zip(v0, v1)]
+
+        if self._integral is None:
+            self._integral = terms
+        else:
+            self._integral = [a + b for a, b in zip(self._integral, terms)]
+
+    def close_window(self):
+        integral = self._integral or []
+        orbit = dict(
+            orbit=self._number,
+            start_time=self.window_start,
+            end_time=self.window_end,
+            values=[x / self.period for x in integral],
+        )
+        self._number += 1
+        self._integral = None
+        return orbit
+
+
+class PhaseFolder:
+    """
+    Accumulates diagnostic samples in bins of the orbital phase.
+
+    The orbit is divided into `num_bins` equal phase bins, with phase zero
+    at time zero, which is periapse for the setups' orbits. Samples are
+    integrated over each bin like `OrbitAverager` integrates them over an
+    orbit, and the average over each pass through a bin is added to the
+    running mean and variance of that bin (with Welford's algorithm). The
+    variance is that of the bin averages from one orbit to the next. The
+    first pass starts at the first bin boundary after `start_time`.
+    """
+
+    def __init__(self, period, num_bins, start_time=0.0):
+        if num_bins < 1:
+            raise ValueError("the number of phase bins must be positive")
+
+        self.period = period
+        self.num_bins = num_bins
+        self.counts = [0] * num_bins
+        self.means = [None] * num_bins
+        self.m2 = [None] * num_bins
+        self._averager = OrbitAverager(period / num_bins, quiet=True)
+        self._averager.interrupt(start_time)
+
+    def __repr__(self):
+        return f"<PhaseFolder period={self.period} bins={self.num_bins}>"
+
+    def add(self, time, values):
+        """
+        Add a sample of the diagnostics at the given time.
+        """
+        for window in self._averager.add(time, values):
+            n = window["orbit"] % self.num_bins
+            self.accumulate(n, window["values"])
+
+            if n == self.num_bins - 1:
+                logger.info(f"complete phase fold pass {self.counts[n]}")
+
+        # The bin windows are folded in, and not kept.
+        self._averager.orbits.clear()
+
+    def interrupt(self, time):
+        """
+        Discard the pass through a bin in progress, and resume at the next
+        bin boundary after `time`.
+        """
+        self._averager.interrupt(time)
+
+    def accumulate(self, n, values):
+        self.counts[n] += 1
+        count = self.counts[n]
+
+        if self.means[n] is None:
+            self.means[n] = list(values)
+            self.m2[n] = [0.0 * x for x in values]
+            return
+
+        mean, m2 = self.means[n], self.m2[n]
+        delta = [x - m for x, m in zip(values, mean)]
+        mean = [m + d / count for m, d in zip(mean, delta)]
+        m2 = [s + d * (x - m) for s, d, x, m in zip(m2, delta, values, mean)]
+        self.means[n], self.m2[n] = mean, m2
+
+    def profiles(self):
+        """
+        Return the phase-folded curves as a dictionary: the bin center
+        `phase` in orbits, the number of passes `count` through each bin, and
+        the `mean` and sample `variance` of each diagnostic in each bin. The
+        last two are lists over bins of lists over diagnostics, with `None`
+        for bins with too few passes.
+        """
+        variance = [
+            [s / (c - 1) for s in m2] if c > 1 else None
+            for c, m2 in zip(self.counts, self.m2)
+        ]
+        return dict(
+            phase=[(n + 0.5) / self.num_bins for n in range(self.num_bins)],
+            count=list(self.counts),
+            mean=list(self.means),
+            variance=variance,
+        )
+
+
+def interpolate(t0, v0, t1, v1, t):
+    """
+    Linearly interpolate between two lists of samples.
+    """
+    w = (t - t0) / (t1 - t0)
+    return [a + (b - a) * w for a, b in zip(v0, v1)]
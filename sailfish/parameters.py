@@ -0,0 +1,226 @@
+"""
+List the runtime parameters of the driver, the setups, and the solvers.
+
+The driver, each setup, and each solver take named parameters at runtime:
+those of the driver are the fields of `DriverArgs`, a setup's are its model
+parameters, and a solver's are the fields of the `Options` named tuple in its
+module, given with `--solver key=value`. The functions here describe all of
+them in the same way, as `RuntimeParameter` instances with the name, type,
+default and current values, and description of each parameter. They are
+printed by the driver's `--list-parameters` option, used to check the
+solver options of a run before the solver is built, and can be used to
+inspect a configuration from Python, e.g. in a notebook:
+
+.. code-block:: python
+
+    from sailfish.parameters import solver_parameters
+
+    for p in solver_parameters("cbdiso_2d"):
+        print(p.name, p.default, p.about)
+
+The description of a named tuple field is its attribute docstring, the
+string after the field in the class body, like that of
+`SolverBase.num_guard`. Fields without one have an empty description.
+"""
+
+from ast import AnnAssign, Assign, ClassDef, Constant, Expr, parse
+from difflib import get_close_matches
+from functools import lru_cache
+from inspect import getsource
+from re import sub
+from textwrap import dedent
+from typing import Any, NamedTuple
+
+
+class RuntimeParameter(NamedTuple):
+    """
+    A runtime parameter of the driver, a setup, or a solver.
+    """
+
+    scope: str
+    name: str
+    type: str
+    default: Any
+    value: Any
+    about: str
+
+
+@lru_cache(maxsize=None)
+def attribute_docs(cls):
+    """
+    Return a dict of the attribute docstrings in the body of a class, by the
+    attribute name. If the source of the class can't be found, the dict is
+    empty.
+    """
+    try:
+        tree = parse(dedent(getsource(cls)))
+    except (OSError, TypeError):
+        return dict()
+
+    body = next(node for node in tree.body if isinstance(node, ClassDef)).body
+    docs = dict()
+
+    for node, next_node in zip(body, body[1:]):
+        if isinstance(node, AnnAssign):
+            targets = [node.target]
+        elif isinstance(node, Assign):
+            targets = node.targets
+        else:
+            continue
+
+        if (
+            isinstance(next_node, Expr)
+            and isinstance(next_node.value, Constant)
+            and isinstance(next_node.value.value, str)
+        ):
+            for target in targets:
+                if hasattr(target, "id"):
+                    docs[target.id] = next_node.value.value.strip()
+
+    return docs
+
+
+def type_name(annotation):
+    """
+    Return the name of a type annotation, e.g. `float` or `Dict[str, int]`.
+    """
+    if isinstance(annotation, type):
+        return annotation.__name__
+
+    return sub(r"(\w+\.)+", "", str(annotation))
+
+
+def named_tuple_parameters(cls, scope, values=None):
+    """
+    Return a list of the fields of a named tuple class as runtime parameters.
+    The current values are taken from `values`, an instance of the class or
+    a dict, and are the defaults where it has none.
+    """
+    if values is None:
+        values = dict()
+    elif hasattr(values, "_asdict"):
+        values = values._asdict()
+
+    docs = attribute_docs(cls)
+    annotations = getattr(cls, "__annotations__", dict())
+    defaults = cls._field_defaults
+
+    return [
+        RuntimeParameter(
+            scope=scope,
+            name=name,
+            type=type_name(annotations.get(name, Any)),
+            default=defaults.get(name),
+            value=values.get(name, defaults.get(name)),
+            about=docs.get(name, ""),
+        )
+        for name in cls._fields
+    ]
+
+
+def driver_parameters(driver=None):
+    """
+    Return the runtime parameters of the driver, with the values of a
+    `DriverArgs` instance if one is given.
+    """
+    from sailfish.driver import DriverArgs
+
+    return named_tuple_parameters(DriverArgs, "driver", driver)
+
+
+def setup_parameters(setup):
+    """
+    Return the model parameters of a setup class, or of a setup instance with
+    its current values.
+    """
+    cls = setup if isinstance(setup, type) else type(setup)
+
+    return [
+        RuntimeParameter(
+            scope="setup",
+            name=key,
+            type=type(default).__name__,
+            default=default,
+            value=getattr(setup, key) if setup is not cls else default,
+            about=about,
+        )
+        for key, default, about in cls.default_model_parameters()
+    ]
+
+
+def solver_parameters(solver_name, options=None):
+    """
+    Return the options of the named solver, with the values in `options` if
+    given. The list is empty if the solver's module defines no `Options`.
+    """
+    from sailfish.solvers import solver_module
+
+    options_class = getattr(solver_module(solver_name), "Options", None)
+
+    if options_class is None:
+        return list()
+
+    return named_tuple_parameters(options_class, "solver", options)
+
+
+def runtime_parameters(setup=None, driver=None, solver_options=None):
+    """
+    Return the runtime parameters of the driver, and if a setup instance is
+    given, those of the setup and of its solver.
+    """
+    parameters = driver_parameters(driver)
+
+    if setup is not None:
+        parameters += setup_parameters(setup)
+        parameters += solver_parameters(setup.solver, solver_options)
+
+    return parameters
+
+
+def check_parameters(parameters, values, what="parameter"):
+    """
+    Raise a `ValueError` if a dict of values has a key which isn't one of the
+    runtime parameters, with the closest names as suggestions, or a value
+    of the wrong type for a parameter of type `bool`, `int`, `float`, or
+    `str`. Integers are accepted for `float` parameters.
+    """
+    by_name = {p.name: p for p in parameters}
+    accepted = dict(bool=(bool,), int=(int,), float=(int, float), str=(str,))
+
+    for key, value in values.items():
+        if key not in by_name:
+            matches = get_close_matches(key, by_name)
+            hint = f", did you mean {' or '.join(matches)}?" if matches else ""
+            raise ValueError(f"unknown {what} {key}{hint}")
+
+        types = accepted.get(by_name[key].type)
+
+        if types is not None and value is not None:
+            if not isinstance(value, types) or (
+                isinstance(value, bool) and bool not in types
+            ):
+                raise ValueError(
+                    f"{what} {key} has type {by_name[key].type} "
+                    f"(got {type(value).__name__})"
+                )
+
+
+def format_parameters(parameters):
+    """
+    Return lines of text listing runtime parameters, grouped by their scope,
+    with the name, type, value, and description of each.
+    """
+    lines = list()
+    scope = None
+
+    for p in parameters:
+        if p.scope != scope:
+            if lines:
+                lines.append("")
+
+            scope = p.scope
+            lines += [f"{scope} parameters:", ""]
+
+        lines.append(f"{p.name:.<28s} {p.type:<8} {str(p.value):<12} {p.about}")
+
+    return lines
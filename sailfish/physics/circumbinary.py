@@ -1,18 +1,92 @@
-from typing import NamedTuple, List, Callable, Union
+from typing import NamedTuple, List, Callable, Tuple, Union
 from enum import Enum
+from math import cos, hypot, pi, sin
+from sailfish.physics import kepler
+from sailfish.physics.reservoir import Reservoir
+from sailfish.physics.roche import SOURCE_REGIONS
 
 
 class SinkModel(Enum):
+    """
+    How momentum is removed along with mass inside a point mass sink radius.
+
+    - `inactive`: no mass or momentum is removed
+    - `acceleration_free`: the momentum removed has the gas velocity, so the
+      gas velocity is unchanged
+    - `torque_free`: the momentum removed has the gas velocity with the
+      component tangential to the point mass, in its rest frame, left out;
+      the sink exerts no torque on the gas about the point mass
+    - `force_free`: only mass is removed, so the gas is sped up
+    """
+
     INACTIVE = 0
     ACCELERATION_FREE = 1
     TORQUE_FREE = 2
     FORCE_FREE = 3
 
+    @classmethod
+    def from_str(cls, name):
+        """
+        Return the sink model with the given name, in either snake or dash
+        case (e.g. `torque_free` or `torque-free`), or raise `ValueError`.
+        """
+        if isinstance(name, cls):
+            return name
+
+        try:
+            return cls[name.replace("-", "_").upper()]
+        except KeyError:
+            names = ", ".join(m.name.lower() for m in cls)
+            raise ValueError(f"unknown sink model {name}, the models are {names}")
+
+
+class SofteningModel(Enum):
+    """
+    How the gravity of a point mass is softened within its softening length.
+
+    - `plummer`: the potential is `-M / sqrt(r^2 + h^2)`, which falls off to
+      the Newtonian one only as `h^2 / r^2`
+    - `spline`: the cubic spline kernel of `Springel et al. (2001)`_, with
+      the radius `2.8 h`; the potential is Newtonian beyond it, and has the
+      Plummer depth `-M / h` at the point mass
+    - `none`: the Newtonian potential, singular at the point mass
+
+    .. _Springel et al. (2001): https://ui.adsabs.harvard.edu/abs/2001NewA....6...79S
+    """
+
+    PLUMMER = 0
+    SPLINE = 1
+    NONE = 2
+
+    @classmethod
+    def from_str(cls, name):
+        """
+        Return the softening model with the given name, in any case, or raise
+        `ValueError`.
+        """
+        if isinstance(name, cls):
+            return name
+
+        try:
+            return cls[name.upper()]
+        except KeyError:
+            names = ", ".join(m.name.lower() for m in cls)
+            raise ValueError(f"unknown softening model {name}, the models are {names}")
+
+
+"""
+The spline kernel radius in softening lengths, and the squared distance below
+which the potential and acceleration are cut off, as in the kernels.
+"""
+SPLINE_RADIUS = 2.8
+MIN_RADIUS_SQUARED = 1e-24
+
 
 class EquationOfState(Enum):
     GLOBALLY_ISOTHERMAL = 1
     LOCALLY_ISOTHERMAL = 2
     GAMMA_LAW = 3
+    PROFILE_ISOTHERMAL = 4
 
 
 class ViscosityModel(Enum):
@@ -21,9 +95,41 @@ class ViscosityModel(Enum):
     CONSTANT_ALPHA = 2
 
 
+class CoolingModel(Enum):
+    """
+    How the gas temperature is relaxed toward its target, e.g. to mimic the
+    radiative cooling and heating of a disk, in the solvers with an energy
+    equation.
+
+    - `none`: the temperature is not relaxed
+    - `beta`: the cooling time is `cooling_beta` times the inverse Keplerian
+      frequency about the total mass at the origin
+    - `constant_time`: the cooling time is `cooling_time` everywhere
+    """
+
+    NONE = 0
+    BETA = 1
+    CONSTANT_TIME = 2
+
+    @classmethod
+    def from_str(cls, name):
+        """
+        Return the cooling model with the given name, in either snake or dash
+        case (e.g. `constant_time` or `constant-time`), or raise `ValueError`.
+        """
+        if isinstance(name, cls):
+            return name
+
+        try:
+            return cls[name.replace("-", "_").upper()]
+        except KeyError:
+            names = ", ".join(m.name.lower() for m in cls)
+            raise ValueError(f"unknown cooling model {name}, the models are {names}")
+
+
 class Diagnostic(NamedTuple):
     quantity: str
-    """ time, mdot, ldot, mass_moment, eccentricity_vector """
+    """ One of the keys of DIAGNOSTIC_UNITS, e.g. mdot, torque, or angular_momentum """
 
     gravity: bool = False
     """ Whether to include the gravity term (if applicable) """
@@ -32,11 +138,137 @@ class Diagnostic(NamedTuple):
     """ Whether to include the accretion term (if applicable) """
 
     which_mass: Union[int, str] = None
-    """ 1, 2, or 'both' """
+    """ 1, 2, or 'both'; for roche_mass, 1 or 2 selects the Roche lobe """
 
     radial_cut: tuple = None
     """ None is ok, or a radial annulus to include e.g. (1.0, 2.0) """
 
+    hill_cut: float = None
+    """ None is ok, or a number of secondary Hill radii inside which to exclude """
+
+    which_probe: int = None
+    """ None is ok, or the index of a probe mass to use in place of which_mass """
+
+    mode_number: int = 1
+    """ Mode number along the x-axis, for the vy_mode quantity """
+
+    source_region: str = None
+    """ None is ok, or one of minidisks, cavity, disk, or buffer to include """
+
+    cavity_radius: float = 2.0
+    """ Radius of the cavity in binary separations, for the source_region """
+
+    @property
+    def label(self):
+        """
+        A short name for the diagnostic, e.g. `torque_2_grav_hill1`, to label
+        time series columns.
+        """
+        parts = [self.quantity]
+
+        if self.which_probe is not None:
+            parts.append(f"probe{self.which_probe}")
+        elif self.which_mass is not None:
+            parts.append(f"{self.which_mass}")
+        if self.gravity:
+            parts.append("grav")
+        if self.accretion:
+            parts.append("acc")
+        if self.radial_cut is not None:
+            parts.append(f"r{self.radial_cut[0]:g}-{self.radial_cut[1]:g}")
+        if self.hill_cut is not None:
+            parts.append(f"hill{self.hill_cut:g}")
+        if self.quantity == "vy_mode":
+            parts.append(f"m{self.mode_number}")
+        if self.source_region is not None:
+            parts.append(self.source_region)
+
+        return "_".join(parts)
+
+    @property
+    def units(self):
+        """
+        The units of the diagnostic, in terms of the code units of length,
+        mass, and time, to describe time series columns.
+        """
+        return DIAGNOSTIC_UNITS.get(self.quantity, "")
+
+
+DIAGNOSTIC_UNITS = dict(
+    time="reference time",
+    mdot="mass / time",
+    fx="mass length / time^2",
+    fy="mass length / time^2",
+    torque="mass length^2 / time^2",
+    power="mass length^2 / time^3",
+    sigma_m1="mass",
+    eccentricity_vector="mass",
+    angular_momentum="mass length^2 / time",
+    mass="mass",
+    vy_mode="length^3 / time",
+    roche_mass="mass",
+    roche_mdot="mass / time",
+)
+
+
+def accretion_diagnostics(radial_cut=None):
+    """
+    Return the standard diagnostics of binary accretion, as dictionaries for
+    a setup's physics: the time, the accretion rate onto each point mass, the
+    gravitational and accretion torques on each, and the total mass and
+    angular momentum of the gas, optionally within a `radial_cut`. The
+    solvers reduce them on the device, so only the sums are copied to the
+    host.
+    """
+    sums = dict() if radial_cut is None else dict(radial_cut=radial_cut)
+    return [
+        dict(quantity="time"),
+        dict(quantity="mdot", which_mass=1, accretion=True),
+        dict(quantity="mdot", which_mass=2, accretion=True),
+        dict(quantity="torque", which_mass=1, gravity=True),
+        dict(quantity="torque", which_mass=2, gravity=True),
+        dict(quantity="torque", which_mass=1, accretion=True),
+        dict(quantity="torque", which_mass=2, accretion=True),
+        dict(quantity="mass", **sums),
+        dict(quantity="angular_momentum", **sums),
+    ]
+
+
+def torque_decomposition_diagnostics(cavity_radius=2.0):
+    """
+    Return the gravitational torques on each point mass, split by the region
+    of the gas exerting them, as dictionaries for a setup's physics: the
+    minidisks in the Roche lobes, the rest of the cavity within
+    `cavity_radius` binary separations of the center of mass, the outer
+    disk, and the buffer zone (see
+    :py:meth:`sailfish.physics.roche.RocheGeometry.source_region_index`).
+    The torques of the regions add up to the total gravitational torque.
+    """
+    return [
+        dict(
+            quantity="torque",
+            which_mass=which_mass,
+            gravity=True,
+            source_region=region,
+            cavity_radius=cavity_radius,
+        )
+        for which_mass in (1, 2)
+        for region in SOURCE_REGIONS
+    ]
+
+
+def hill_mask(x, y, m1, m2, hill_cut):
+    """
+    Return a mask of the coordinates `x` and `y` which is zero within
+    `hill_cut` Hill radii of the secondary point mass `m2`, and one elsewhere,
+    for diagnostics with a `hill_cut`.
+    """
+    dx = m2.position_x - m1.position_x
+    dy = m2.position_y - m1.position_y
+    r_hill = (dx**2 + dy**2) ** 0.5 * (m2.mass / m1.mass / 3.0) ** (1 / 3)
+    r2 = ((x - m2.position_x) ** 2 + (y - m2.position_y) ** 2) ** 0.5
+    return r2 > hill_cut * r_hill
+
 
 class PointMass(NamedTuple):
     r"""
@@ -85,6 +317,9 @@ class PointMass(NamedTuple):
     softening_length: float = 0.0
     """ Gravitational softening length """
 
+    softening_model: SofteningModel = SofteningModel.PLUMMER
+    """ How the potential is softened within the softening length """
+
     sink_model: SinkModel = SinkModel.INACTIVE
     """ The equation used to control how momentum is subtracted """
 
@@ -94,6 +329,248 @@ class PointMass(NamedTuple):
     sink_radius: float = 0.0
     """ The sink radius: how far from the particle the sink extends """
 
+    def potential(self, r2, xp):
+        """
+        Return the softened potential at the squared distances `r2`, an
+        array module `xp` (numpy or cupy) array, from the point mass.
+        """
+        m, h = self.mass, self.softening_length
+        r2 = xp.maximum(r2, MIN_RADIUS_SQUARED)
+
+        if self.softening_model == SofteningModel.NONE or h == 0.0:
+            return -m / xp.sqrt(r2)
+
+        if self.softening_model == SofteningModel.PLUMMER:
+            return -m / xp.sqrt(r2 + h * h)
+
+        h = SPLINE_RADIUS * h
+        r = xp.sqrt(r2)
+        u = r / h
+        inner = -2.8 + u * u * (16.0 / 3.0 + u * u * (6.4 * u - 9.6))
+        outer = u * u * (32.0 / 3.0 + u * (-16.0 + u * (9.6 - 32.0 / 15.0 * u)))
+        outer = -3.2 + 1.0 / (15.0 * u) + outer
+        w = xp.where(u < 0.5, inner, outer)
+        return xp.where(u < 1.0, m / h * w, -m / r)
+
+    def acceleration(self, r2, xp):
+        """
+        Return the magnitude of the softened gravitational acceleration at
+        the squared distances `r2` from the point mass, divided by the
+        distance, which is the squared Keplerian frequency there.
+        """
+        m, h = self.mass, self.softening_length
+        r2 = xp.maximum(r2, MIN_RADIUS_SQUARED)
+
+        if self.softening_model == SofteningModel.NONE or h == 0.0:
+            return m * r2**-1.5
+
+        if self.softening_model == SofteningModel.PLUMMER:
+            return m * (r2 + h * h) ** -1.5
+
+        h = SPLINE_RADIUS * h
+        r = xp.sqrt(r2)
+        u = r / h
+        inner = 32.0 / 3.0 + u * u * (32.0 * u - 38.4)
+        outer = 64.0 / 3.0 - 48.0 * u + 38.4 * u * u - 32.0 / 3.0 * u**3
+        outer = outer - 1.0 / (15.0 * u**3)
+        w = xp.where(u < 0.5, inner, outer)
+        return xp.where(u < 1.0, m / h**3 * w, m / (r2 * r))
+
+
+class TwoBodyProblem(NamedTuple):
+    """
+    Two point masses orbiting under their mutual gravity, from their state at
+    a given time.
+
+    Solvers with live point masses (see :obj:`Physics.live_point_masses`)
+    keep one of these instead of calling the setup's point mass function
+    after the start of the run. The positions and velocities at later times
+    are integrated from this state with
+    :py:meth:`sailfish.physics.kepler.OrbitalState.advance`, and the other
+    properties of the point masses, such as their sinks, are kept. The
+    masses are constant, and the gas does not act on the orbit.
+    """
+
+    time: float
+    """ The time of the state """
+
+    point_masses: Tuple[PointMass, PointMass]
+    """ The two point masses at that time """
+
+    @property
+    def orbital_state(self):
+        """
+        The masses, positions, and velocities as a
+        :py:class:`sailfish.physics.kepler.OrbitalState`.
+        """
+        return kepler.OrbitalState(
+            *(
+                kepler.PointMass(
+                    m.mass, m.position_x, m.position_y, m.velocity_x, m.velocity_y
+                )
+                for m in self.point_masses
+            )
+        )
+
+    def at(self, time):
+        """
+        Return the problem with its state advanced to a later time. Earlier
+        times are not integrated back to, and return the problem unchanged.
+        """
+        if time <= self.time:
+            return self
+
+        state = self.orbital_state.advance(time - self.time)
+        point_masses = tuple(
+            m._replace(
+                position_x=c.position_x,
+                position_y=c.position_y,
+                velocity_x=c.velocity_x,
+                velocity_y=c.velocity_y,
+            )
+            for m, c in zip(self.point_masses, state)
+        )
+        return TwoBodyProblem(time, point_masses)
+
+
+class LivePointMasses:
+    """
+    A point mass function for solvers with live point masses.
+
+    Instances replace the setup's callback in a solver's physics. They map
+    the time of each Runge-Kutta stage to the two point masses at that time,
+    integrated from the state at the start of the iteration, so all of the
+    patches see the same masses in a stage. The solver calls
+    `new_iteration` at the start of every iteration, to move that state
+    forward.
+    """
+
+    def __init__(self, two_body):
+        self.two_body = two_body
+        self.cache = dict()
+
+    def __call__(self, time):
+        if time not in self.cache:
+            self.cache[time] = self.two_body.at(time).point_masses
+        return self.cache[time]
+
+    def new_iteration(self, time):
+        self.two_body = self.two_body.at(time)
+        self.cache = dict()
+
+    @classmethod
+    def start(cls, physics, time, two_body=None):
+        """
+        Return live point masses for a solver, from the state in a restarted
+        solution, or else from the point mass function at the given time. A
+        `ValueError` is raised unless there are two point masses.
+        """
+        if two_body is None:
+            two_body = TwoBodyProblem(time, tuple(physics.point_masses(time)))
+
+        if min(m.mass for m in two_body.point_masses) <= 0.0:
+            raise ValueError("live point masses need two massive point masses")
+
+        return cls(two_body)
+
+
+class RotatingFrame(NamedTuple):
+    """
+    A frame rotating rigidly about the origin, at angular frequency `omega`.
+
+    The frame axes coincide with the inertial ones at time zero. Solvers on
+    a rotating mesh use it to rotate the point masses into the frame, and to
+    convert velocities between the frames: a velocity `v` in the inertial
+    frame is `v - omega x r` in this one, after rotating both to the frame
+    axes.
+    """
+
+    omega: float = 0.0
+
+    def angle(self, time):
+        return self.omega * time
+
+    def to_frame(self, time, x, y):
+        """
+        Rotate a vector in the inertial axes to the frame axes at a time.
+        """
+        c, s = cos(self.angle(time)), sin(self.angle(time))
+        return c * x + s * y, c * y - s * x
+
+    def from_frame(self, time, x, y):
+        """
+        Rotate a vector in the frame axes to the inertial axes at a time.
+        """
+        c, s = cos(self.angle(time)), sin(self.angle(time))
+        return c * x - s * y, c * y + s * x
+
+    def mesh_velocity(self, x, y):
+        """
+        Return the inertial velocity of the frame at a point given in frame
+        coordinates, in the frame axes. The coordinates can be arrays.
+        """
+        return -self.omega * y, self.omega * x
+
+    def point_mass(self, time, mass):
+        """
+        Return a point mass with its position and velocity in the frame.
+        """
+        x, y = self.to_frame(time, mass.position_x, mass.position_y)
+        vx, vy = self.to_frame(time, mass.velocity_x, mass.velocity_y)
+        wx, wy = self.mesh_velocity(x, y)
+        return mass._replace(
+            position_x=x,
+            position_y=y,
+            velocity_x=vx - wx,
+            velocity_y=vy - wy,
+        )
+
+
+class FramePointMasses:
+    """
+    A point mass function which gives the masses of another one in a
+    rotating frame. Solvers on a rotating mesh replace the point and probe
+    mass functions with instances of this class, so everything that reads
+    the masses sees them in the frame of the mesh.
+    """
+
+    def __init__(self, frame, function):
+        self.frame = frame
+        self.function = function
+
+    def __call__(self, time):
+        return tuple(self.frame.point_mass(time, m) for m in self.function(time))
+
+
+class InclinedPointMasses:
+    """
+    A point mass function which gives the masses of another one as seen in a
+    plane inclined to theirs, by an angle in degrees about the x axis.
+
+    The positions and velocities are projected onto the plane, and the
+    height of each mass above it is added in quadrature to its softening
+    length, so the gravity in the plane is that of the mass at its true
+    distance. The two-layer solver gives a layer of an inclined disk point
+    masses of this kind.
+    """
+
+    def __init__(self, inclination, function):
+        self.inclination = inclination
+        self.function = function
+
+    def __call__(self, time):
+        c = cos(self.inclination * pi / 180.0)
+        s = sin(self.inclination * pi / 180.0)
+
+        return tuple(
+            m._replace(
+                position_y=m.position_y * c,
+                velocity_y=m.velocity_y * c,
+                softening_length=hypot(m.softening_length, m.position_y * s),
+            )
+            for m in self.function(time)
+        )
+
 
 class Physics(NamedTuple):
     """
@@ -113,6 +590,13 @@ class Physics(NamedTuple):
        supports the former two modes, and the cbdgam_2d solver only upports
        the last mode.
 
+       In locally isothermal mode, the potential is the combined one of all
+       the point masses, so the disk is hotter near each of them. The
+       profile isothermal mode instead takes the sound speed from a
+       user-supplied function of the distance to the origin,
+       :obj:`sound_speed_profile`, e.g. a measured or modeled temperature
+       profile (cbdiso_2d only).
+
     2. Gravitating point masses
 
        Point masses can be optionally provided to model stars or black holes.
@@ -122,17 +606,29 @@ class Physics(NamedTuple):
        momentum (see the :obj:`PointMass` struct above for details). The point
        masses are supplied to the solver implicitly through a callback
        function, mapping the simulation time to a sequence of particles.
-       Currently, solvers support either zero, one, or two particles.
+       Currently, solvers support either zero, one, or two particles. If
+       :obj:`live_point_masses` is set, the callback is only used for the two
+       particles at the start of the run, and the solver integrates their
+       orbit from there (see :obj:`TwoBodyProblem`).
 
     3. Viscosity model
 
        Two different viscosity models are nominally supported: constant-nu,
-       and constant alpha. Currently the isothermal solver only supports
-       constant-nu viscosity.
-
-    4. Thermal cooling
-
-       Todo.
+       and constant alpha. The cbdiso_2d solver supports both; the cbdgam_2d
+       solver only supports constant alpha.
+
+    4. Thermal cooling (cbdgam_2d only)
+
+       The :obj:`cooling_coefficient` sets the strength of an optically thick
+       radiative cooling term, which the kernels apply in each stage. The
+       :obj:`cooling_model` instead relaxes the temperature, `p / sigma`,
+       toward a target on a cooling time, beta cooling or a constant one (see
+       :obj:`CoolingModel`), as an exact exponential decay in a split step
+       after each iteration, so the gas is heated where it's colder than the
+       target, too. The target is given by the :obj:`cooling_target`
+       function of the coordinates, or else it's the orbital temperature
+       `-phi / mach_number^2` of the combined potential `phi` of the point
+       masses, as in locally isothermal mode.
 
     5. An outer buffer zone
 
@@ -148,6 +644,25 @@ class Physics(NamedTuple):
        between the domain radius (the half-width of a square domain), extending
        inwards by an amount specified by the :obj:`buffer_onset_width`
        parameter.
+
+    6. Inner and target buffers (cbdiso_2d only)
+
+       If :obj:`inner_buffer_radius` is non-zero, the solution inside that
+       radius is damped toward its initial state, at a rate which ramps up
+       from zero at that radius to the :obj:`buffer_driving_rate` times the
+       Keplerian frequency at that radius (or times one, without point
+       masses) at the origin, e.g. to absorb waves at the edge of an inner
+       cavity. A setup can also drive the solution toward
+       any state, with a rate given in each zone, through its
+       `buffer_target` method; where both apply, the setup's state is used.
+
+    7. Self-gravity (cbdiso_2d only)
+
+       If :obj:`self_gravity_constant` is non-zero, the gas is accelerated by
+       its own gravity, that of a razor-thin disk softened by
+       :obj:`self_gravity_softening`, computed by FFT on the mesh (see
+       `sailfish.physics.self_gravity`), as a split step after each
+       iteration. The point masses don't feel the gas.
     """
 
     eos_type: EquationOfState = EquationOfState.GLOBALLY_ISOTHERMAL
@@ -183,15 +698,63 @@ class Physics(NamedTuple):
     point_mass_function: Callable[[float], List[PointMass]] = None
     """ Callback function to supply point masses as a function of time """
 
+    probe_mass_function: Callable[[float], List[PointMass]] = None
+    """ Callback function to supply non-interacting probe masses """
+
     cooling_coefficient: float = 0.0
     """ Strength of the cooling term """
 
     constant_softening: bool = True
     """ If local disk height is ignored in gravitational softening """
 
+    softening_zones: float = None
+    """ If given, the softening length in units of the local zone size """
+
+    external_gravity: float = 0.0
+    """ Uniform gravitational acceleration in the -y direction """
+
     diagnostics: List[Diagnostic] = []
     """ Physics diagnostics to be returned when reductions are computed """
 
+    live_point_masses: bool = False
+    """ If the solver integrates the orbit of the two point masses itself """
+
+    num_tracers: int = 0
+    """ Number of passive scalars advected with the gas (cbdiso_2d only) """
+
+    reservoir: Reservoir = None
+    """ A 0D model of the gas inside an excised center (cbdiso_2d only) """
+
+    custom_source: str = None
+    """ A library or source file with a custom source term (cbdiso_2d only) """
+
+    custom_source_parameters: List[float] = []
+    """ The parameters passed to the custom source term """
+
+    sound_speed_profile: Callable[[float], float] = None
+    """ Sound speed as a function of radius, if EOS type is profile isothermal """
+
+    inner_buffer_radius: float = 0.0
+    """ If non-zero, the radius inside which the solution is damped """
+
+    self_gravity_constant: float = 0.0
+    """ If non-zero, the gravitational constant of the gas (cbdiso_2d only) """
+
+    self_gravity_softening: float = 0.0
+    """ Softening length of the gas's gravity, e.g. the disk scale height """
+
+    cooling_model: CoolingModel = CoolingModel.NONE
+    """ How the temperature is relaxed toward its target (cbdgam_2d only) """
+
+    cooling_beta: float = 10.0
+    """ The cooling time times the Keplerian frequency, for beta cooling """
+
+    cooling_time: float = 1.0
+    """ The cooling time, for constant-time cooling """
+
+    cooling_target: Callable[[float, float], float] = None
+    """ The target temperature as a function of x and y, or None (see above) """
+
     @property
     def num_particles(self):
         if self.point_mass_function is None:
@@ -199,6 +762,45 @@ class Physics(NamedTuple):
         else:
             return len(self.point_mass_function(0.0))
 
+    def probe_masses(self, time):
+        """
+        Generate the list of probe masses from the simulation time and the
+        supplied callback.
+
+        Probe masses don't act on the gas in any way: they have no gravity and
+        no sink. The solvers only use them in diagnostics, to record the force
+        the gas would exert on a point mass moving along the probe's
+        trajectory. The trajectory, and the mass and softening length used
+        for the force, are given by the callback.
+        """
+        if self.probe_mass_function is None:
+            return []
+
+        return list(self.probe_mass_function(time))
+
+    def resolved_point_masses(self, time, mesh):
+        """
+        Generate two point masses, with softening lengths adapted to a mesh.
+
+        If :obj:`softening_zones` is given, each point mass's softening length
+        is that number times the size of the zone the point mass is in. The
+        softening is re-evaluated every time the masses are generated, so it
+        follows a point mass across regions of different resolution.
+        Otherwise the point masses are returned as they are.
+        """
+        masses = self.point_masses(time)
+
+        if self.softening_zones is None:
+            return masses
+
+        return tuple(
+            m._replace(
+                softening_length=self.softening_zones
+                * mesh.zone_size(m.position_x, m.position_y)
+            )
+            for m in masses
+        )
+
     def point_masses(self, time):
         """
         Generate two point masses from the simulation time and supplied
@@ -1,5 +1,20 @@
 """
 Code to solve the Kepler two-body problem, and its inverse.
+
+The functions :py:func:`solve_kepler_equation`, :py:func:`true_anomaly`, and
+:py:func:`relative_orbit` are also meant for use on their own, by setups and
+post-processing scripts which need positions on an eccentric orbit. They are
+checked by `scripts/test_kepler.py`.
+
+:py:meth:`OrbitalState.advance` integrates the two-body problem numerically
+instead, with sub-steps and optional forces from the gas, for orbits which
+are evolved along with the hydrodynamics rather than prescribed by a setup's
+point mass function.
+
+:py:class:`EvolvingOrbit` is a prescribed orbit whose semi-major axis and
+eccentricity change at constant rates, for experiments on a slowly shrinking
+or circularizing binary, where the rates are given rather than measured from
+the gas.
 """
 
 from typing import NamedTuple
@@ -23,7 +38,7 @@ class PointMass(NamedTuple):
     velocity_y: float
 
     @property
-    def kinetic_energy(self) -> float:
+    def kinetic_energy(p) -> float:
         """
         The kinetic energy of a point mass
         """
@@ -32,7 +47,7 @@ class PointMass(NamedTuple):
         return 0.5 * p.mass * (vx * vx + vy * vy)
 
     @property
-    def angular_momentum(self) -> float:
+    def angular_momentum(p) -> float:
         """
         The angular momentum of a point mass
         """
@@ -43,7 +58,7 @@ class PointMass(NamedTuple):
         return p.mass * (x * vy - y * vx)
 
     def gravitational_potential(
-        self, x: float, y: float, softening_length: float
+        p, x: float, y: float, softening_length: float
     ) -> float:
         """
         Return the gravitational potential of a point mass, with softening.
@@ -51,7 +66,7 @@ class PointMass(NamedTuple):
         dx = x - p.position_x
         dy = y - p.position_y
         r2 = dx * dx + dy * dy
-        s2 = softening_length.powi(2)
+        s2 = softening_length ** 2.0
         return -NEWTON_G * p.mass / sqrt(r2 + s2)
 
     def gravitational_acceleration(
@@ -69,7 +84,7 @@ class PointMass(NamedTuple):
         return (ax, ay)
 
     def perturb(
-        self, dm: float = 0.0, dpx: float = 0.0, dpy: float = 0.0
+        p, dm: float = 0.0, dpx: float = 0.0, dpy: float = 0.0
     ) -> "PointMass":
         """
         Perturb the mass and momentum of a point mass.
@@ -81,8 +96,8 @@ class PointMass(NamedTuple):
         """
         return p._replace(
             mass=p.mass + dm,
-            velocity_x=velocity_x + (dpx - p.velocity_x * dm) / p.mass,
-            velocity_y=velocity_y + (dpy - p.velocity_y * dm) / p.mass,
+            velocity_x=p.velocity_x + (dpx - p.velocity_x * dm) / p.mass,
+            velocity_y=p.velocity_y + (dpy - p.velocity_y * dm) / p.mass,
         )
 
 
@@ -95,14 +110,14 @@ class OrbitalState(NamedTuple):
         """
         The sum of the two point masses
         """
-        self[0].mass + self[1].mass
+        return self[0].mass + self[1].mass
 
     @property
     def mass_ratio(self) -> float:
         """
         The system mass ratio, secondary / primary
         """
-        self[1].mass / self[0].mass
+        return self[1].mass / self[0].mass
 
     @property
     def separation(self) -> float:
@@ -122,7 +137,7 @@ class OrbitalState(NamedTuple):
         """
         The system total energy
         """
-        return self.kinetic_energy - G * self[0].mass * self[1].mass / self.separation
+        return self.kinetic_energy - NEWTON_G * self[0].mass * self[1].mass / self.separation
 
     @property
     def kinetic_energy(self) -> float:
@@ -174,8 +189,61 @@ class OrbitalState(NamedTuple):
         """
 
         return OrbitalState(
-            self[0].perturb_mass_and_momentum(dm1, dpx1, dpy1),
-            self[1].perturb_mass_and_momentum(dm2, dpx2, dpy2),
+            self[0].perturb(dm1, dpx1, dpy1),
+            self[1].perturb(dm2, dpx2, dpy2),
+        )
+
+    def advance(
+        self, dt: float, eta: float = 0.005, external_acceleration=None
+    ) -> "OrbitalState":
+        """
+        Return the state after a time `dt`, integrating the two-body motion
+        with as many leapfrog sub-steps as are needed.
+
+        This is meant for live orbits which are advanced once per hydro step:
+        the sub-step is `eta` times the dynamical time `sqrt(r^3 / G M)` at
+        the current separation, so close passages of eccentric orbits are
+        resolved without shrinking the hydro time step. The optional
+        `external_acceleration` is a pair of 4-tuples `(ax1, ay1, ax2,
+        ay2)`, the accelerations of the components due to the gas at the
+        start and end of the step; they're interpolated linearly in time
+        between the sub-steps.
+        """
+        m1 = self[0].mass
+        m2 = self[1].mass
+        x = [self[0].position_x, self[0].position_y]
+        x += [self[1].position_x, self[1].position_y]
+        v = [self[0].velocity_x, self[0].velocity_y]
+        v += [self[1].velocity_x, self[1].velocity_y]
+
+        def acceleration(x, t):
+            dx = x[2] - x[0]
+            dy = x[3] - x[1]
+            r3 = (dx * dx + dy * dy) ** 1.5
+            a = [NEWTON_G * m2 * dx / r3, NEWTON_G * m2 * dy / r3]
+            a += [-NEWTON_G * m1 * dx / r3, -NEWTON_G * m1 * dy / r3]
+
+            if external_acceleration is not None:
+                a0, a1 = external_acceleration
+                w = t / dt
+                a = [a[n] + (1.0 - w) * a0[n] + w * a1[n] for n in range(4)]
+            return a
+
+        t = 0.0
+        a = acceleration(x, t)
+
+        while t < dt:
+            r = ((x[2] - x[0]) ** 2 + (x[3] - x[1]) ** 2) ** 0.5
+            h = min(eta * sqrt(r**3 / (NEWTON_G * (m1 + m2))), dt - t)
+            v = [v[n] + 0.5 * h * a[n] for n in range(4)]
+            x = [x[n] + h * v[n] for n in range(4)]
+            t = t + h if t + h < dt else dt
+            a = acceleration(x, t)
+            v = [v[n] + 0.5 * h * a[n] for n in range(4)]
+
+        return OrbitalState(
+            PointMass(m1, x[0], x[1], v[0], v[1]),
+            PointMass(m2, x[2], x[3], v[2], v[3]),
         )
 
     def orbital_parameters(self, t: float) -> ("OrbitalElements", "OrbitalOrientation"):
@@ -359,11 +427,7 @@ class OrbitalElements(NamedTuple):
         """
         p = self.period
         t = time_since_periapse - self.period * floor(time_since_periapse / p)
-        e = self.eccentricity
-        n = self.omega * t  # n := mean anomaly M
-        f = lambda k: k - e * sin(k) - n  # k := eccentric anomaly E
-        g = lambda k: 1.0 - e * cos(k)
-        return solve_newton_rapheson(f, g, n)
+        return solve_kepler_equation(self.omega * t, self.eccentricity)
 
     def orbital_state(self, time_since_periapse: float) -> OrbitalState:
         """
@@ -411,6 +475,146 @@ class OrbitalElements(NamedTuple):
         return OrbitalState(c1, c2)
 
 
+class EvolvingOrbit(NamedTuple):
+    """
+    A two-body orbit whose semi-major axis and eccentricity change linearly in
+    time from the given initial elements.
+
+    The mean anomaly is the integral of the orbital frequency of the changing
+    semi-major axis, so the orbital phase stays continuous, and the state at
+    each time is that of the osculating Kepler orbit, with the periapse on the
+    x-axis. The rates are assumed to be slow compared to the orbital
+    frequency: the velocities leave out the terms proportional to them.
+    """
+
+    elements: OrbitalElements
+    semimajor_axis_rate: float = 0.0
+    eccentricity_rate: float = 0.0
+
+    def elements_at(self, time: float) -> OrbitalElements:
+        """
+        Return the orbital elements at a time since the initial periapse.
+        Raise a `ValueError` if the orbit has shrunk to nothing, or left the
+        range of bound eccentricities.
+        """
+        a = self.elements.semimajor_axis + self.semimajor_axis_rate * time
+        e = self.elements.eccentricity + self.eccentricity_rate * time
+
+        if a <= 0.0:
+            raise ValueError(f"the semi-major axis has shrunk to zero at t={time}")
+
+        if not 0.0 <= e < 1.0:
+            raise ValueError(f"the eccentricity {e} at t={time} is outside [0, 1)")
+
+        return self.elements._replace(semimajor_axis=a, eccentricity=e)
+
+    def mean_anomaly(self, time: float) -> float:
+        """
+        Return the mean anomaly at a time since the initial periapse.
+        """
+        a0 = self.elements.semimajor_axis
+        a1 = self.elements_at(time).semimajor_axis
+        gm = NEWTON_G * self.elements.total_mass
+
+        # The integral of sqrt(G M / a^3) for a linear in time, written so
+        # that it has no cancellation for small rates.
+        return 2.0 * sqrt(gm) * time / (sqrt(a0 * a1) * (sqrt(a0) + sqrt(a1)))
+
+    def orbital_state(self, time: float) -> OrbitalState:
+        """
+        Compute the orbital state vector at a time since the initial periapse.
+        """
+        elements = self.elements_at(time)
+        E = solve_kepler_equation(self.mean_anomaly(time), elements.eccentricity)
+        return elements.orbital_state_from_eccentric_anomaly(E)
+
+
+def solve_kepler_equation(
+    mean_anomaly: float, eccentricity: float, tolerance: float = 1e-14
+) -> float:
+    """
+    Return the eccentric anomaly `E` which solves Kepler's equation,
+    `M = E - e sin(E)`, for the mean anomaly `M` and eccentricity `e`.
+
+    Any eccentricity `0 <= e < 1` is supported. The solution is in the same
+    orbit as the mean anomaly, i.e. within `e` of it. Newton iterations are
+    safeguarded by bisection, so they converge even for `e` close to one and
+    `M` close to periapse, where Newton's method from the usual starting guess
+    overshoots.
+    """
+    e = eccentricity
+
+    if not 0.0 <= e < 1.0:
+        raise ValueError(f"eccentricity must be in [0, 1), got {e}")
+
+    # Reduce M to [-pi, pi). The solution is bracketed by [M, M + e] if
+    # M >= 0, and by [M - e, M] otherwise.
+    k = floor((mean_anomaly + pi) / (2.0 * pi))
+    m = mean_anomaly - 2.0 * pi * k
+    lo, hi = (m, m + e) if m >= 0.0 else (m - e, m)
+    x = min(max(m + 0.85 * e * (1.0 if m >= 0.0 else -1.0), lo), hi)
+
+    for _ in range(100):
+        f = x - e * sin(x) - m
+
+        if f > 0.0:
+            hi = x
+        else:
+            lo = x
+
+        dx = f / (1.0 - e * cos(x))
+        y = x - dx
+
+        if not lo <= y <= hi:
+            y = 0.5 * (lo + hi)
+
+        if abs(y - x) <= tolerance or f == 0.0:
+            return y + 2.0 * pi * k
+
+        x = y
+
+    raise ValueError(f"no solution for M={mean_anomaly}, e={e}")
+
+
+def true_anomaly(eccentric_anomaly: float, eccentricity: float) -> float:
+    """
+    Return the true anomaly from the eccentric anomaly.
+    """
+    e = eccentricity
+    E = eccentric_anomaly
+    s = sqrt(1.0 + e) * sin(0.5 * E)
+    c = sqrt(1.0 - e) * cos(0.5 * E)
+    return 2.0 * atan2(s, c)
+
+
+def relative_orbit(
+    mean_anomaly: float,
+    eccentricity: float,
+    semimajor_axis: float = 1.0,
+    total_mass: float = 1.0,
+) -> (float, float, float, float):
+    """
+    Return the position and velocity `(x, y, vx, vy)` of one body relative
+    to the other, on a Kepler orbit at the given mean anomaly.
+
+    The frame is the one used by :py:class:`OrbitalElements`: the vector
+    points from the primary to the secondary, periapse is on the negative
+    x-axis, and the orbit is counter-clockwise.
+    """
+    a = semimajor_axis
+    e = eccentricity
+    n = sqrt(NEWTON_G * total_mass / a / a / a)
+    E = solve_kepler_equation(mean_anomaly, e)
+    ck = cos(E)
+    sk = sin(E)
+    dEdt = n / (1.0 - e * ck)
+    x = a * (e - ck)
+    y = -a * sqrt(1.0 - e * e) * sk
+    vx = a * sk * dEdt
+    vy = -a * sqrt(1.0 - e * e) * ck * dEdt
+    return x, y, vx, vy
+
+
 def solve_newton_rapheson(f, g, x: float) -> float:
     n = 0
     while abs(f(x)) > 1e-15:
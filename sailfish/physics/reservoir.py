@@ -0,0 +1,87 @@
+"""
+A 0D model of the inner disk, for runs with an excised center.
+
+Runs which excise the center of a disk (with a mask of active zones) lose
+track of the gas which flows into the hole. The `Reservoir` keeps it: the
+mass and angular momentum which flow in through the excision boundary are
+stored, and drained onto the central object on a viscous time, each unit of
+drained mass carrying a given specific angular momentum, e.g. the accretion
+eigenvalue of a binary. Angular momentum which isn't drained spins up the
+reservoir.
+
+The reservoir feeds back on the disk through the state of the excised zones,
+which are a fixed boundary for their neighbors: they have the reservoir's
+mean surface density, and rotate with its specific angular momentum at the
+excision radius, up to the Keplerian speed there. The fluxes through the
+boundary, including the viscous torque of the reservoir on the disk, are
+those of the hydro scheme, and they're what the reservoir is changed by, so
+the disk, the reservoir, and the accreted gas conserve the total mass and
+angular momentum, less what leaves through the outer boundary.
+"""
+
+from math import exp, pi
+from typing import NamedTuple
+
+
+class Reservoir(NamedTuple):
+    """
+    The state of the gas inside the excision radius.
+    """
+
+    radius: float
+    """ The excision radius """
+
+    viscous_time: float
+    """ The e-folding time on which the mass is drained onto the center """
+
+    accreted_specific_angular_momentum: float = 0.0
+    """ The angular momentum per unit mass carried by the drained gas """
+
+    mass: float = 0.0
+    """ The stored mass """
+
+    angular_momentum: float = 0.0
+    """ The stored angular momentum """
+
+    accreted_mass: float = 0.0
+    """ The total mass drained onto the center """
+
+    accreted_angular_momentum: float = 0.0
+    """ The total angular momentum drained onto the center """
+
+    @property
+    def surface_density(self):
+        """
+        The mean surface density of the stored gas.
+        """
+        return self.mass / (pi * self.radius**2)
+
+    def edge_velocity(self, central_mass):
+        """
+        The azimuthal velocity at the excision radius, from the specific
+        angular momentum of the stored gas, and no faster than a Keplerian
+        orbit around the given mass.
+        """
+        if self.mass <= 0.0:
+            return 0.0
+
+        v = self.angular_momentum / self.mass / self.radius
+        v_kepler = (central_mass / self.radius) ** 0.5
+        return max(-v_kepler, min(v, v_kepler))
+
+    def advance(self, dt, mass_inflow, angular_momentum_inflow):
+        """
+        Return the reservoir after an interval `dt`, in which the given mass
+        and angular momentum (not rates) flowed in through its edge.
+        """
+        mass = self.mass + mass_inflow
+        drained = max(mass, 0.0) * (1.0 - exp(-dt / self.viscous_time))
+        drained_l = drained * self.accreted_specific_angular_momentum
+        angular_momentum = self.angular_momentum + angular_momentum_inflow
+
+        return self._replace(
+            mass=mass - drained,
+            angular_momentum=angular_momentum - drained_l,
+            accreted_mass=self.accreted_mass + drained,
+            accreted_angular_momentum=self.accreted_angular_momentum + drained_l,
+        )
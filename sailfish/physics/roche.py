@@ -0,0 +1,204 @@
+"""
+Roche lobes of a binary, for classifying zones by the component they belong to.
+
+The zones are also classified by the region of the disk they are in, in
+:py:obj:`SOURCE_REGIONS` order: the minidisks inside the two Roche lobes, the
+rest of the cavity, the outer disk, and the buffer zone at the edge of the
+domain, should the torques on the binary be split by the gas exerting them.
+
+The Roche potential is that of the two point masses, plus the centrifugal
+potential in a frame rotating about the center of mass. Here it's evaluated
+from the instantaneous masses and separation, with the angular frequency of a
+circular orbit at that separation, which is exact for circular binaries and
+a reasonable approximation of the lobes of mildly eccentric ones.
+
+Coordinates along the binary axis are in units of the separation, measured
+from the primary toward the secondary, so the primary is at 0 and the
+secondary at 1.
+"""
+
+from math import log, sqrt
+from typing import NamedTuple
+from sailfish.physics.kepler import NEWTON_G
+
+SOURCE_REGIONS = ("minidisks", "cavity", "disk", "buffer")
+
+
+def bisect(f, a, b, tol=1e-14):
+    """
+    Return a root of `f` in `[a, b]`, where `f(a)` and `f(b)` differ in sign.
+    """
+    fa = f(a)
+
+    while b - a > tol:
+        c = 0.5 * (a + b)
+        fc = f(c)
+
+        if (fc < 0.0) == (fa < 0.0):
+            a, fa = c, fc
+        else:
+            b = c
+
+    return 0.5 * (a + b)
+
+
+def lagrange_points(mass_ratio):
+    """
+    Return the positions of the collinear Lagrange points L1, L2, and L3
+    along the binary axis, for the given mass ratio `m2 / m1`.
+    """
+    mu2 = mass_ratio / (1.0 + mass_ratio)
+    mu1 = 1.0 - mu2
+
+    def force(x):
+        return mu1 * x / abs(x) ** 3 + mu2 * (x - 1.0) / abs(x - 1.0) ** 3 - (x - mu2)
+
+    l1 = bisect(force, 1e-12, 1.0 - 1e-12)
+    l2 = bisect(force, 1.0 + 1e-12, 3.0)
+    l3 = bisect(force, -3.0, -1e-12)
+    return l1, l2, l3
+
+
+def eggleton_radius(mass_ratio):
+    """
+    Return the Eggleton (1983) approximation to the volume-equivalent radius
+    of the Roche lobe of the component with mass ratio `q = m / m_other`, in
+    units of the separation.
+    """
+    q23 = mass_ratio ** (2.0 / 3.0)
+    return 0.49 * q23 / (0.6 * q23 + log(1.0 + mass_ratio ** (1.0 / 3.0)))
+
+
+class RocheGeometry(NamedTuple):
+    """
+    The Roche lobe geometry of a binary at one instant.
+    """
+
+    m1: object
+    m2: object
+
+    @property
+    def separation(self):
+        dx = self.m2.position_x - self.m1.position_x
+        dy = self.m2.position_y - self.m1.position_y
+        return sqrt(dx * dx + dy * dy)
+
+    @property
+    def axis(self):
+        """
+        The unit vector from the primary toward the secondary.
+        """
+        a = self.separation
+        dx = self.m2.position_x - self.m1.position_x
+        dy = self.m2.position_y - self.m1.position_y
+        return dx / a, dy / a
+
+    @property
+    def center_of_mass(self):
+        m1, m2 = self.m1, self.m2
+        m = m1.mass + m2.mass
+        x = (m1.position_x * m1.mass + m2.position_x * m2.mass) / m
+        y = (m1.position_y * m1.mass + m2.position_y * m2.mass) / m
+        vx = (m1.velocity_x * m1.mass + m2.velocity_x * m2.mass) / m
+        vy = (m1.velocity_y * m1.mass + m2.velocity_y * m2.mass) / m
+        return x, y, vx, vy
+
+    @property
+    def omega(self):
+        return sqrt(NEWTON_G * (self.m1.mass + self.m2.mass) / self.separation**3)
+
+    @property
+    def lagrange_points(self):
+        return lagrange_points(self.m2.mass / self.m1.mass)
+
+    def potential(self, x, y):
+        """
+        Return the Roche potential at the points `(x, y)`, which may be
+        scalars or arrays.
+        """
+        xc, yc, _, _ = self.center_of_mass
+        r1 = ((x - self.m1.position_x) ** 2 + (y - self.m1.position_y) ** 2) ** 0.5
+        r2 = ((x - self.m2.position_x) ** 2 + (y - self.m2.position_y) ** 2) ** 0.5
+        rc2 = (x - xc) ** 2 + (y - yc) ** 2
+        phi1 = -NEWTON_G * self.m1.mass / r1
+        phi2 = -NEWTON_G * self.m2.mass / r2
+        return phi1 + phi2 - 0.5 * self.omega**2 * rc2
+
+    def l1_potential(self):
+        l1, _, _ = self.lagrange_points
+        nx, ny = self.axis
+        a = self.separation
+        x = self.m1.position_x + l1 * a * nx
+        y = self.m1.position_y + l1 * a * ny
+        return self.potential(x, y)
+
+    @property
+    def smaller_lobe_radius(self):
+        """
+        The Eggleton radius of the smaller of the two Roche lobes.
+        """
+        q = min(self.m1.mass, self.m2.mass) / max(self.m1.mass, self.m2.mass)
+        return eggleton_radius(q) * self.separation
+
+    def l1_plane_coordinates(self, x, y):
+        """
+        Return the coordinates of the points `(x, y)` relative to L1: the
+        signed distance from the plane through L1 normal to the binary axis,
+        negative on the primary's side, and the position along that plane.
+        """
+        l1, _, _ = self.lagrange_points
+        nx, ny = self.axis
+        dx = x - self.m1.position_x - l1 * self.separation * nx
+        dy = y - self.m1.position_y - l1 * self.separation * ny
+        return dx * nx + dy * ny, dy * nx - dx * ny
+
+    def lobe_index(self, x, y):
+        """
+        Return an integer array of the Roche lobe containing each of the
+        points `(x, y)`: 1 for the primary's lobe, 2 for the secondary's, and
+        0 outside both.
+
+        The region where the potential is below its L1 value also includes
+        everything far from the binary, so points further from the center of
+        mass than the nearer of L2 and L3 are excluded, which leaves exactly
+        the two lobes.
+        """
+        _, l2, l3 = self.lagrange_points
+        xc, yc, _, _ = self.center_of_mass
+        a = self.separation
+        mu2 = self.m2.mass / (self.m1.mass + self.m2.mass)
+        r_max = min(l2 - mu2, mu2 - l3) * a
+        rc = ((x - xc) ** 2 + (y - yc) ** 2) ** 0.5
+        inside = (self.potential(x, y) < self.l1_potential()) * (rc < r_max)
+        side = self.l1_plane_coordinates(x, y)[0] < 0.0
+        return inside * (2 - side)
+
+    def frame_velocity(self, x, y):
+        """
+        Return the velocity of the co-rotating frame at the points `(x, y)`.
+        """
+        xc, yc, vxc, vyc = self.center_of_mass
+        omega = self.omega
+        return vxc - omega * (y - yc), vyc + omega * (x - xc)
+
+    def source_region_index(self, x, y, cavity_radius, buffer_radius=0.0):
+        """
+        Return an integer array of the index into :py:obj:`SOURCE_REGIONS` of
+        the region containing each of the points `(x, y)`.
+
+        The cavity extends to `cavity_radius` binary separations from the
+        center of mass, and the buffer starts at `buffer_radius` from the
+        origin, or nowhere if it's zero. The buffer takes precedence over the
+        Roche lobes, and the lobes over the cavity.
+        """
+        xc, yc, _, _ = self.center_of_mass
+        rc = ((x - xc) ** 2 + (y - yc) ** 2) ** 0.5
+        lobe = self.lobe_index(x, y) > 0
+        cavity = rc < cavity_radius * self.separation
+        index = 2 - 2 * lobe - cavity * (1 - lobe)
+
+        if buffer_radius > 0.0:
+            buffer = (x * x + y * y) ** 0.5 > buffer_radius
+            index = index + buffer * (3 - index)
+
+        return index
@@ -0,0 +1,146 @@
+"""
+Rotation curves for the initial azimuthal velocity of disks.
+
+A rotation curve gives the circular speed :math:`v_c(r)` of a test particle
+in the gravitational field, which is Keplerian for a point mass, but not for
+a disk whose own gravity matters, or one orbiting a mass distribution. Gas in
+a disk with a radial pressure gradient orbits at a different speed, in
+centrifugal balance with gravity and pressure together:
+
+.. math::
+    \\frac{v_\\phi^2}{r} = \\frac{v_c^2}{r} + \\frac{1}{\\Sigma}
+    \\frac{d \\mathcal{P}}{d r} \\, .
+
+Rotation curves are given by strings, so they can be setup model parameters:
+
+- `keplerian`: :math:`v_c = \\sqrt{G M / r}`
+- `power-law:<v0>:<q>`: :math:`v_c = v_0 (r / a)^q`, e.g. `power-law:1.0:0`
+  for a flat curve with unit speed
+- `table:<filename>`: interpolated linearly from a text file with columns of
+  radius and speed (lines starting with `#` are skipped); the speed is
+  constant inside the first radius, and Keplerian beyond the last one
+"""
+
+from bisect import bisect_right
+from math import sqrt
+from typing import NamedTuple, Tuple
+from sailfish.physics.kepler import NEWTON_G
+
+
+class RotationCurveError(ValueError):
+    """A rotation curve was mis-specified"""
+
+
+def read_rotation_table(filename):
+    """
+    Return a tuple of radii and a tuple of speeds, read from a text file
+    with two columns. Raise `RotationCurveError` if the radii are not
+    positive and increasing.
+    """
+    radii, speeds = [], []
+
+    with open(filename) as f:
+        for line in f:
+            line = line.strip()
+
+            if not line or line.startswith("#"):
+                continue
+
+            try:
+                r, v = (float(x) for x in line.split()[:2])
+            except ValueError:
+                raise RotationCurveError(f"bad line in rotation table {filename}")
+
+            radii.append(r)
+            speeds.append(v)
+
+    if len(radii) < 2:
+        raise RotationCurveError(f"rotation table {filename} needs two rows or more")
+
+    if radii[0] <= 0.0 or any(b <= a for a, b in zip(radii, radii[1:])):
+        raise RotationCurveError(f"radii in {filename} must be positive, increasing")
+
+    return tuple(radii), tuple(speeds)
+
+
+class RotationCurve(NamedTuple):
+    """
+    A circular speed profile `v_c(r)`, and the azimuthal speed of a disk in
+    equilibrium with it.
+    """
+
+    kind: str = "keplerian"
+    speed: float = 1.0
+    index: float = -0.5
+    radii: Tuple[float, ...] = ()
+    speeds: Tuple[float, ...] = ()
+    gm: float = 1.0
+
+    @classmethod
+    def from_str(cls, spec, gm=1.0):
+        """
+        Return a rotation curve from a string, in one of the forms listed in
+        the module documentation, or raise `RotationCurveError`.
+        """
+        kind, *args = spec.split(":")
+
+        try:
+            if kind == "keplerian" and not args:
+                return cls(gm=gm)
+            elif kind == "power-law" and len(args) == 2:
+                return cls(kind, speed=float(args[0]), index=float(args[1]), gm=gm)
+            elif kind == "table" and len(args) == 1:
+                radii, speeds = read_rotation_table(args[0])
+                return cls(kind, radii=radii, speeds=speeds, gm=gm)
+        except (OSError, ValueError) as e:
+            raise RotationCurveError(f"bad rotation curve {spec}: {e}")
+
+        raise RotationCurveError(
+            f"rotation curve must be keplerian, power-law:<v0>:<q>, or "
+            f"table:<filename>, got {spec}"
+        )
+
+    def __str__(self):
+        if self.kind == "power-law":
+            return f"power-law:{self.speed}:{self.index}"
+        return self.kind
+
+    def circular_speed(self, r):
+        """
+        Return the circular speed at radius `r`.
+        """
+        if self.kind == "keplerian":
+            return sqrt(NEWTON_G * self.gm / r)
+
+        if self.kind == "power-law":
+            return self.speed * r**self.index
+
+        radii, speeds = self.radii, self.speeds
+
+        if r <= radii[0]:
+            return speeds[0]
+
+        if r >= radii[-1]:
+            return speeds[-1] * sqrt(radii[-1] / r)
+
+        n = bisect_right(radii, r)
+        w = (r - radii[n - 1]) / (radii[n] - radii[n - 1])
+        return speeds[n - 1] * (1.0 - w) + speeds[n] * w
+
+    def azimuthal_speed(self, r, sigma=None, pressure=None, dr=1e-4):
+        """
+        Return the azimuthal speed at radius `r` of gas in centrifugal
+        equilibrium with gravity and the radial pressure gradient. The
+        surface density and pressure are functions of radius; the pressure
+        gradient is taken by centered differences over `dr` times `r`. If
+        either function is omitted, the circular speed is returned. Where
+        the pressure gradient outweighs gravity, the speed is zero.
+        """
+        vc = self.circular_speed(r)
+
+        if sigma is None or pressure is None:
+            return vc
+
+        h = dr * r
+        dpdr = (pressure(r + h) - pressure(r - h)) / (2.0 * h)
+        return sqrt(max(vc * vc + r * dpdr / sigma(r), 0.0))
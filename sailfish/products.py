@@ -0,0 +1,284 @@
+"""
+Read the products of a run: checkpoints, snapshots, analysis products, and
+time series files.
+
+The readers are built on the same definitions the driver writes with, e.g.
+the field lists in `sailfish.fields` and the file format in
+`sailfish.timeseries`, so analysis scripts don't need their own parsers,
+which drift out of sync with the writers. Arrays are returned as views of
+the loaded data rather than copies where possible; snapshot data written in
+single precision stays in single precision. Snapshot fields which were
+stored with a `sailfish.fields.FieldEncoding` are decoded to double
+precision when they're read, also by indexing, e.g. `snap["primitive"]`;
+such snapshots have no primitive array of their own, so they're marked with
+a `format_version` of 2, and a file from a newer version than the readers
+know is rejected. Fields and time series columns of runs with code
+units can be read in cgs or SI units, from the unit conversions in the file;
+see `sailfish.units`.
+
+.. code-block:: python
+
+    from sailfish.products import load_product
+
+    snap = load_product("snap.0010.pk")
+    plt.imshow(snap.field("sigma").T, origin="lower", extent=snap.extent)
+    sigma_cgs = snap.field("sigma", system="cgs")
+"""
+
+import os
+import pickle
+import re
+from sailfish.fields import FieldEncoding, field_index
+
+SNAPSHOT_FORMAT_VERSION = 2
+
+
+class Product:
+    """
+    The contents of a checkpoint or snapshot file.
+
+    Items of the file are available by indexing, e.g. `product["mesh"]`,
+    and the common ones as attributes.
+    """
+
+    def __init__(self, filename, contents):
+        version = contents.get("format_version", 1)
+
+        if version > SNAPSHOT_FORMAT_VERSION:
+            raise ValueError(
+                f"{filename} has format version {version}, but this version of "
+                f"sailfish reads at most {SNAPSHOT_FORMAT_VERSION}"
+            )
+
+        self.filename = filename
+        self.contents = contents
+
+    def __repr__(self):
+        return (
+            f"<Product {self.kind} {os.path.basename(self.filename)} "
+            f"iteration={self.iteration} time={self.time:0.3f}>"
+        )
+
+    def __getitem__(self, key):
+        if key == "primitive" and self.field_encodings is not None:
+            return self.primitive
+        return self.contents[key]
+
+    def __contains__(self, key):
+        return key in self.contents or key == "primitive"
+
+    def keys(self):
+        return self.contents.keys()
+
+    @property
+    def kind(self):
+        """
+        Either "checkpoint" or "snapshot".
+        """
+        return "checkpoint" if "solution" in self.contents else "snapshot"
+
+    @property
+    def time(self):
+        return self.contents["time"]
+
+    @property
+    def iteration(self):
+        return self.contents["iteration"]
+
+    @property
+    def mesh(self):
+        return self.contents["mesh"]
+
+    @property
+    def fields(self):
+        """
+        The list of primitive field dictionaries, or an empty list for files
+        written before the fields were recorded.
+        """
+        return self.contents.get("primitive_fields", [])
+
+    @property
+    def num_guard(self):
+        """
+        The number of guard zones on the primitive data in the file.
+        """
+        return self.contents.get("primitive_num_guard", 0)
+
+    @property
+    def field_encodings(self):
+        """
+        The `FieldEncoding` of each field, or `None` if the primitive data is
+        stored as one array.
+        """
+        encodings = self.contents.get("field_encodings")
+
+        if encodings is None:
+            return None
+
+        return [FieldEncoding(**e) for e in encodings]
+
+    @property
+    def unit_conversions(self):
+        """
+        The unit conversions of the run, or `None` if it had no code units.
+        """
+        return self.contents.get("unit_conversions")
+
+    @property
+    def primitive(self):
+        """
+        The primitive data, without any guard zones. This is a view of the
+        array in the file, or if the fields were encoded separately, a new
+        array of the decoded fields.
+        """
+        if self.field_encodings is not None:
+            import numpy as np
+
+            return np.stack([self.field(n) for n in range(len(self.fields))], -1)
+
+        primitive = self.contents["primitive"]
+        ng = self.num_guard
+
+        if ng == 0:
+            return primitive
+
+        return primitive[tuple(slice(ng, -ng) for _ in range(primitive.ndim - 1))]
+
+    def field(self, name, system=None):
+        """
+        Return a view of one primitive variable, given by its name in the
+        field list (e.g. "sigma"), or its position. An encoded field is
+        decoded to a new array. If the `system` is "cgs" or "si", the field
+        is converted to it, to a new array.
+        """
+        from sailfish.units import convert
+
+        index = name if isinstance(name, int) else field_index(self.fields, name)
+        encodings = self.field_encodings
+
+        if encodings is not None:
+            values = encodings[index].decode(self.contents["primitive_columns"][index])
+        else:
+            values = self.primitive[..., index]
+
+        if system is None:
+            return values
+
+        name = self.fields[index]["name"]
+        return convert(self.unit_conversions, name, values, system)
+
+    @property
+    def extent(self):
+        """
+        The bounds `(x0, x1, y0, y1)` of a 2D mesh, for `imshow`.
+        """
+        mesh = self.mesh
+        return (mesh.x0, mesh.x1, mesh.y0, mesh.y1)
+
+    def timeseries(self):
+        """
+        Return the time series in a checkpoint as a dictionary of numpy
+        arrays, keyed by the names in `timeseries_names`.
+        """
+        import numpy as np
+
+        if self.kind != "checkpoint":
+            raise ValueError("only checkpoints have time series")
+
+        samples = self.contents.get("timeseries", [])
+        names = self.contents.get("timeseries_names", [])
+
+        if samples and len(names) != len(samples[0]):
+            names = [f"column{n}" for n in range(len(samples[0]))]
+
+        return {
+            name: np.array([s[n] for s in samples]) for n, name in enumerate(names)
+        }
+
+
+def load_pickle(filename):
+    with open(filename, "rb") as f:
+        return pickle.load(f)
+
+
+def load_product(filename):
+    """
+    Load any product of a run, by its file name. Checkpoints and snapshots
+    are returned as `Product` instances, analysis products as a dictionary,
+    and time series files as a `sailfish.timeseries.TimeseriesFile`.
+    """
+    from sailfish.timeseries import read_timeseries
+
+    if filename.endswith(".sfts"):
+        return read_timeseries(filename)
+
+    contents = load_pickle(filename)
+
+    if isinstance(contents, dict) and (
+        "primitive" in contents or "primitive_columns" in contents
+    ):
+        return Product(filename, contents)
+    else:
+        return contents
+
+
+def load_checkpoint(filename):
+    """
+    Load a checkpoint file, or raise `ValueError` if it's another product.
+    """
+    product = load_product(filename)
+
+    if not isinstance(product, Product) or product.kind != "checkpoint":
+        raise ValueError(f"{filename} is not a checkpoint")
+
+    return product
+
+
+def load_snapshot(filename):
+    """
+    Load a snapshot file, or raise `ValueError` if it's another product.
+    """
+    product = load_product(filename)
+
+    if not isinstance(product, Product) or product.kind != "snapshot":
+        raise ValueError(f"{filename} is not a snapshot")
+
+    return product
+
+
+def list_products(directory="."):
+    """
+    Return a dictionary of the product file names in a run directory, sorted
+    in the order they were written: `checkpoints`, `snapshots`, `analyses`
+    (a dictionary of file lists, keyed by analysis name), and `timeseries`
+    (the time series file, or None). The final and wall time checkpoints,
+    `chkpt.final.pk` and `chkpt.wall.pk`, are listed after the numbered ones.
+    """
+    expr = re.compile(r"(chkpt|snap)\.([0-9]+|final|wall)\.(?:([a-z0-9-]+)\.)?pk$")
+    checkpoints, snapshots, analyses = [], [], dict()
+
+    for name in os.listdir(directory):
+        match = expr.match(name)
+
+        if match is None:
+            continue
+
+        kind, number, analysis = match.groups()
+        order = int(number) if number.isdigit() else float("inf")
+        item = (order, os.path.join(directory, name))
+
+        if kind == "chkpt" and analysis is None:
+            checkpoints.append(item)
+        elif kind == "snap" and analysis is None and number.isdigit():
+            snapshots.append(item)
+        elif kind == "snap" and number.isdigit():
+            analyses.setdefault(analysis, []).append(item)
+
+    timeseries = os.path.join(directory, "timeseries.sfts")
+
+    return dict(
+        checkpoints=[p for _, p in sorted(checkpoints)],
+        snapshots=[p for _, p in sorted(snapshots)],
+        analyses={k: [p for _, p in sorted(v)] for k, v in analyses.items()},
+        timeseries=timeseries if os.path.exists(timeseries) else None,
+    )
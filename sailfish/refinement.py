@@ -0,0 +1,548 @@
+"""
+Static mesh refinement: nested levels of 2x-refined patches.
+
+Resolving the sinks of a binary takes a fine mesh near the point masses, but
+the disk around them is smooth, and a uniform mesh fine enough for the sinks
+is mostly wasted. A setup can instead refine rectangular regions of the mesh
+with its `refinement` property, a list of :py:class:`RefinedRegion`. A region
+of level 1 is covered by a patch with twice the resolution of the setup's
+mesh, a region of level 2 by one with twice the resolution of the level 1
+region containing it, and so on. Each region is snapped outward to the faces
+of its parent, and must lie at least two parent zones inside it, and two
+parent zones away from the other regions of its level.
+
+Each level is solved by an ordinary solver on its own part of the mesh, and
+the levels take their Runge-Kutta stages together, with the same time step,
+limited by the finest level; the levels aren't subcycled. Before each stage,
+the guard zones of a refined patch are filled by prolongation from its
+parent, with a limited linear interpolation of the primitive data, which
+replaces the boundary condition the patch's own solver applied. After each
+iteration, from the finest level to the base, the parent zones covered by a
+refined patch are replaced by the average of its conserved data over the
+child zones (restriction), and the parent zones next to the patch are
+corrected by the difference between the parent's flux through the level
+boundary and the average of the child's fluxes through the same face
+(refluxing). The parent's recorded face fluxes there are replaced by the
+child's, so mass, momentum, and the passive scalars are conserved exactly
+across level boundaries, and the face flux outputs are consistent.
+
+The solution, diagnostics, and outputs are those of the base level, which
+holds the restricted solution, so checkpoints look like those of a single
+solver. On a restart, the refined levels are prolonged from the base, and
+lose their sub-zone detail. So far, the `cbdiso_2d` solver can be refined,
+without live point masses, an inner reservoir, custom sources, self-gravity,
+or step retries; the Keplerian buffer zone is applied on the base level only,
+so the refined regions must lie inside its onset radius.
+"""
+
+from logging import getLogger
+from math import ceil, floor
+from typing import NamedTuple, Tuple
+from sailfish.mesh import PlanarCartesian2DMesh
+from sailfish.solver_base import SolverBase
+from sailfish.solvers import check_invalid_zones
+from sailfish.subdivide import to_host
+
+logger = getLogger(__name__)
+
+REFINED_SOLVERS = ("cbdiso_2d",)
+
+# The refined regions keep this many parent zones away from the edges of
+# their parent, and from one another, so that the prolongation stencils of
+# their guard zones only read parent zones outside the other regions.
+REFINEMENT_MARGIN = 2
+
+
+class RefinedRegion(NamedTuple):
+    """
+    A rectangular region of the mesh covered by a 2x-refined patch.
+    """
+
+    lower: Tuple[float, float]
+    """ Coordinates of the lower left corner of the region """
+
+    upper: Tuple[float, float]
+    """ Coordinates of the upper right corner of the region """
+
+    level: int = 1
+    """ Refinement level: 1 refines the base mesh, 2 a level-1 region, etc. """
+
+
+class Level(NamedTuple):
+    """
+    A solver for one refined region, with the index of its parent level in
+    the list of levels, and the index range it covers on the parent mesh.
+    """
+
+    solver: SolverBase
+    parent: int
+    box: Tuple[int, int, int, int]
+
+
+def snap_region(parent_mesh, region):
+    """
+    Return the index range `(i0, i1, j0, j1)` of the parent zones covering a
+    region, snapped outward to the parent faces, and the 2x-refined mesh of
+    those zones. A `ValueError` is raised if the region is too close to the
+    edges of its parent.
+    """
+    (x0, y0), (x1, y1) = region.lower, region.upper
+    m = parent_mesh
+    i0 = floor((x0 - m.x0) / m.dx + 1e-10)
+    i1 = ceil((x1 - m.x0) / m.dx - 1e-10)
+    j0 = floor((y0 - m.y0) / m.dy + 1e-10)
+    j1 = ceil((y1 - m.y0) / m.dy - 1e-10)
+    k = REFINEMENT_MARGIN
+
+    if not (x0 < x1 and y0 < y1):
+        raise ValueError(f"refined region {region} is empty")
+
+    if i0 < k or j0 < k or i1 > m.ni - k or j1 > m.nj - k:
+        raise ValueError(
+            f"refined region {region} must be {k} zones inside its parent {m}"
+        )
+
+    sub = m.sub_mesh((i0, i1), (j0, j1))
+    return (i0, i1, j0, j1), sub._replace(ni=2 * sub.ni, nj=2 * sub.nj)
+
+
+def boxes_overlap(a, b, margin):
+    """
+    Return true if two index ranges `(i0, i1, j0, j1)` overlap, or are closer
+    than `margin` zones apart.
+    """
+    return (
+        a[0] < b[1] + margin
+        and b[0] < a[1] + margin
+        and a[2] < b[3] + margin
+        and b[2] < a[3] + margin
+    )
+
+
+def minmod(a, b, xp):
+    return 0.5 * (xp.sign(a) + xp.sign(b)) * xp.minimum(xp.abs(a), xp.abs(b))
+
+
+def prolong(coarse, xp):
+    """
+    Return the fine data of a 2x refinement of coarse primitive data, with a
+    limited linear interpolation in each coarse zone. The outermost coarse
+    zones are only read by the slopes, so the result covers the others.
+    """
+    c = coarse[1:-1, 1:-1]
+    gx = minmod(coarse[2:, 1:-1] - c, c - coarse[:-2, 1:-1], xp)
+    gy = minmod(coarse[1:-1, 2:] - c, c - coarse[1:-1, :-2], xp)
+
+    def refine(a):
+        return xp.repeat(xp.repeat(a, 2, axis=0), 2, axis=1)
+
+    sx = xp.asarray([-0.25, 0.25] * c.shape[0])[:, None, None]
+    sy = xp.asarray([-0.25, 0.25] * c.shape[1])[None, :, None]
+    return refine(c) + refine(gx) * sx + refine(gy) * sy
+
+
+def primitive_to_conserved(p, xp):
+    u = p.copy()
+    u[..., 1:] *= p[..., :1]
+    return u
+
+
+def conserved_to_primitive(u, density_floor, velocity_ceiling, xp):
+    # As the kernels do, with the density floor and the velocity ceiling.
+    p = u.copy()
+    rho = xp.maximum(u[..., 0], density_floor)
+    p[..., 0] = rho
+    p[..., 1:] /= rho[..., None]
+    p[..., 1:3] = xp.clip(p[..., 1:3], -velocity_ceiling, velocity_ceiling)
+    return p
+
+
+def patch_rows(solver, i0, i1):
+    """
+    Yield each patch of a solver with interior zones in the global rows
+    `[i0, i1)`, with the first global row of the patch, and the range of
+    those rows in it.
+    """
+    for patch in solver.patches:
+        a, b = patch.index_range
+        lo, hi = max(a, i0), min(b, i1)
+
+        if lo < hi:
+            yield patch, a, lo, hi
+
+
+def read_interior(solver, i0, i1, j0, j1):
+    """
+    Return a host copy of the primitive data of a solver's interior zones
+    `[i0, i1) x [j0, j1)`, read across its patches.
+    """
+    import numpy as np
+
+    ng = solver.num_guard
+    parts = list()
+
+    for patch, a, lo, hi in patch_rows(solver, i0, i1):
+        with patch.execution_context:
+            rows = slice(lo - a + ng, hi - a + ng)
+            parts.append(to_host(patch.primitive1[rows, j0 + ng : j1 + ng]))
+
+    return np.concatenate(parts)
+
+
+def write_interior(solver, i0, j0, data):
+    """
+    Write host primitive data to a solver's interior zones, starting at the
+    global zone `(i0, j0)`, across its patches.
+    """
+    ng = solver.num_guard
+    i1 = i0 + data.shape[0]
+    j1 = j0 + data.shape[1]
+
+    for patch, a, lo, hi in patch_rows(solver, i0, i1):
+        with patch.execution_context:
+            rows = slice(lo - a + ng, hi - a + ng)
+            patch.primitive1[rows, j0 + ng : j1 + ng] = patch.xp.asarray(
+                data[lo - i0 : hi - i0]
+            )
+
+
+class Solver(SolverBase):
+    """
+    Coordinates the solvers of the levels of a refined mesh, prolonging their
+    guard zones, and restricting and refluxing their solutions.
+    """
+
+    def __init__(
+        self,
+        refinement,
+        setup=None,
+        mesh=None,
+        solution=None,
+        physics=dict(),
+        options=dict(),
+        **kwargs,
+    ):
+        from sailfish.solvers import make_solver
+
+        if setup.solver not in REFINED_SOLVERS:
+            raise ValueError(f"solver {setup.solver} can't be refined")
+
+        if not isinstance(mesh, PlanarCartesian2DMesh):
+            raise ValueError("refinement needs a planar cartesian 2D mesh")
+
+        options = dict(options or dict(), record_face_fluxes=True)
+        base = make_solver(
+            setup.solver,
+            dict(physics),
+            options,
+            setup=setup,
+            mesh=mesh,
+            solution=solution,
+            **kwargs,
+        )
+        self.mesh = mesh
+        self.levels = [Level(base, None, None)]
+        self.regions = [None]
+        self.num_guard = base.num_guard
+        self.num_iterations = 0
+        child_kwargs = dict(kwargs, num_patches=1)
+        child_physics = dict(physics, buffer_is_enabled=False)
+        onset = mesh.x1 - base.physics.buffer_onset_width
+
+        for region in sorted(refinement, key=lambda r: r.level):
+            parent = self.parent_index(region)
+            box, child_mesh = snap_region(self.levels[parent].solver.mesh, region)
+
+            for level in self.levels[1:]:
+                if level.parent == parent and boxes_overlap(
+                    level.box, box, REFINEMENT_MARGIN
+                ):
+                    raise ValueError(f"refined region {region} overlaps another")
+
+            if base.physics.buffer_is_enabled:
+                m = child_mesh
+                r2 = max(m.x0**2, m.x1**2) + max(m.y0**2, m.y1**2)
+
+                if r2 > onset * onset:
+                    raise ValueError(
+                        f"refined region {region} reaches into the buffer zone"
+                    )
+
+            child = make_solver(
+                setup.solver,
+                dict(child_physics),
+                options,
+                setup=setup,
+                mesh=child_mesh,
+                **child_kwargs,
+            )
+            self.levels.append(Level(child, parent, box))
+            self.regions.append(region)
+            logger.info(f"level {region.level} refines {box} with {child_mesh}")
+
+        for level in self.levels:
+            s = level.solver
+
+            if s.live_point_masses is not None or s.reservoir is not None:
+                raise ValueError("refinement does not support these features")
+            if s.custom_source is not None:
+                raise ValueError("refinement does not support a custom source")
+            if s.self_gravity is not None:
+                raise ValueError("refinement does not support self-gravity")
+
+        if base.options.max_step_retries:
+            raise ValueError("refinement does not support step retries")
+
+        if solution is not None:
+            for level in self.levels[1:]:
+                self.fill_from_parent(level)
+
+        for level in reversed(self.levels[1:]):
+            self.restrict(level)
+
+    def parent_index(self, region):
+        """
+        Return the index of the level whose region contains a refined region,
+        which must be one level coarser.
+        """
+        if region.level == 1:
+            return 0
+
+        for n, other in enumerate(self.regions):
+            if other is not None and other.level == region.level - 1:
+                (x0, y0), (x1, y1) = other.lower, other.upper
+                (u0, v0), (u1, v1) = region.lower, region.upper
+
+                if x0 <= u0 and u1 <= x1 and y0 <= v0 and v1 <= y1:
+                    return n
+
+        raise ValueError(f"refined region {region} isn't inside a coarser one")
+
+    def prolonged(self, level, ring):
+        """
+        Return the fine primitive data prolonged from the parent of a level,
+        over its region and `ring` parent zones around it.
+        """
+        import numpy as np
+
+        i0, i1, j0, j1 = level.box
+        k = ring + 1
+        parent = self.levels[level.parent].solver
+        coarse = read_interior(parent, i0 - k, i1 + k, j0 - k, j1 + k)
+        return prolong(coarse, np)
+
+    def fill_from_parent(self, level):
+        """
+        Replace the solution of a refined level by the prolongation of its
+        parent's, e.g. on a restart, when only the base level was saved.
+        """
+        solver = level.solver
+        write_interior(solver, 0, 0, self.prolonged(level, 0))
+        self.fill_guard_zones(level)
+        solver.reinitialize()
+
+    def fill_guard_zones(self, level):
+        """
+        Overwrite the guard zones of a refined level by prolongation from the
+        interior zones of its parent.
+        """
+        ng = self.num_guard
+        fine = self.prolonged(level, ng // 2)
+        patch = level.solver.patches[0]
+
+        with patch.execution_context:
+            p = patch.primitive1
+            f = patch.xp.asarray(fine)
+            p[:ng] = f[:ng]
+            p[-ng:] = f[-ng:]
+            p[:, :ng] = f[:, :ng]
+            p[:, -ng:] = f[:, -ng:]
+
+    def set_bc(self, array):
+        """
+        Fill the guard zones of each level, from the base to the finest, with
+        those of the refined levels prolonged from their parents.
+        """
+        for level in self.levels:
+            level.solver.set_bc(array)
+
+            if level.parent is not None:
+                self.fill_guard_zones(level)
+
+    def restrict(self, level):
+        """
+        Replace the parent zones covered by a refined level by the average of
+        its conserved data.
+        """
+        import numpy as np
+
+        i0, i1, j0, j1 = level.box
+        parent = self.levels[level.parent].solver
+        child = level.solver
+        floor = child.options.density_floor
+        ceiling = child.options.velocity_ceiling
+        fine = read_interior(child, 0, child.mesh.ni, 0, child.mesh.nj)
+        u = primitive_to_conserved(fine, np)
+        u = u.reshape(i1 - i0, 2, j1 - j0, 2, u.shape[-1]).mean(axis=(1, 3))
+        write_interior(parent, i0, j0, conserved_to_primitive(u, floor, ceiling, np))
+
+    def reflux(self, level):
+        """
+        Correct the parent zones next to a refined level by the difference
+        between the parent's fluxes through the level boundary over the
+        latest iteration and the average of the child's, and record the
+        child's as the parent's.
+        """
+        ng = self.num_guard
+        i0, i1, j0, j1 = level.box
+        parent = self.levels[level.parent].solver
+        child = level.solver.patches[0]
+        floor = parent.options.density_floor
+        ceiling = parent.options.velocity_ceiling
+        dx, dy = parent.mesh.dx, parent.mesh.dy
+
+        with child.execution_context:
+            fx = to_host(child.face_flux_x)
+            fy = to_host(child.face_flux_y)
+
+        nq = fx.shape[-1]
+        fine_x = [f.reshape(j1 - j0, 2, nq).mean(axis=1) for f in (fx[0], fx[-1])]
+        fine_y = [
+            f.reshape(i1 - i0, 2, nq).mean(axis=1) for f in (fy[:, 0], fy[:, -1])
+        ]
+
+        # The side of each level boundary: the face index on the parent, the
+        # rows and columns of the parent zones outside it, the sign of the
+        # correction, and the child's averaged flux.
+        sides = [
+            (0, i0, (i0 - 1, i0), (j0, j1), +1.0 / dx, fine_x[0]),
+            (0, i1, (i1, i1 + 1), (j0, j1), -1.0 / dx, fine_x[1]),
+            (1, j0, (i0, i1), (j0 - 1, j0), +1.0 / dy, fine_y[0]),
+            (1, j1, (i0, i1), (j1, j1 + 1), -1.0 / dy, fine_y[1]),
+        ]
+
+        for axis, face, (r0, r1), (c0, c1), weight, fine in sides:
+            for patch, a, lo, hi in patch_rows(parent, r0, r1):
+                xp = patch.xp
+
+                with patch.execution_context:
+                    if axis == 0:
+                        coarse = patch.face_flux_x[face - a, j0:j1]
+                        f = xp.asarray(fine)[None]
+                        coarse = coarse[None]
+                    else:
+                        coarse = patch.face_flux_y[lo - a : hi - a, face : face + 1]
+                        f = xp.asarray(fine[lo - i0 : hi - i0])[:, None]
+
+                    rows = slice(lo - a + ng, hi - a + ng)
+                    prim = patch.primitive1[rows, c0 + ng : c1 + ng]
+                    u = primitive_to_conserved(prim, xp) + weight * (coarse - f)
+                    prim[...] = conserved_to_primitive(u, floor, ceiling, xp)
+                    coarse[...] = f
+
+    @property
+    def solution(self):
+        return self.levels[0].solver.solution
+
+    @property
+    def primitive(self):
+        return self.levels[0].solver.primitive
+
+    @property
+    def primitive_fields(self):
+        return self.levels[0].solver.primitive_fields
+
+    def primitive_array(self, guard_zones=False):
+        return self.levels[0].solver.primitive_array(guard_zones)
+
+    def conserved_array(self):
+        return self.levels[0].solver.conserved_array()
+
+    def sample(self, x, y):
+        return self.levels[0].solver.sample(x, y)
+
+    def rest_frame_patches(self):
+        return self.levels[0].solver.rest_frame_patches()
+
+    def checkpoint_diagnostics(self):
+        return self.levels[0].solver.checkpoint_diagnostics()
+
+    def face_fluxes(self):
+        return self.levels[0].solver.face_fluxes()
+
+    @property
+    def time(self):
+        return self.levels[0].solver.time
+
+    @property
+    def nominal_time(self):
+        return self.levels[0].solver.nominal_time
+
+    @property
+    def options(self):
+        return self.levels[0].solver.options
+
+    @property
+    def physics(self):
+        return self.levels[0].solver.physics
+
+    @property
+    def recommended_cfl(self):
+        return self.levels[0].solver.recommended_cfl
+
+    @property
+    def maximum_cfl(self):
+        return self.levels[0].solver.maximum_cfl
+
+    def maximum_wavespeed(self):
+        return max(level.solver.maximum_wavespeed() for level in self.levels)
+
+    def recommended_timestep(self, min_spacing, cfl_number):
+        # The driver passes the spacing of the base mesh; each level is
+        # limited by its own.
+        return min(
+            s.recommended_timestep(s.mesh.min_spacing(), cfl_number)
+            for s in (level.solver for level in self.levels)
+        )
+
+    def timestep_limiters(self):
+        return self.levels[0].solver.timestep_limiters()
+
+    def sink_resolution(self):
+        return self.levels[-1].solver.sink_resolution()
+
+    @property
+    def reduction_names(self):
+        return self.levels[0].solver.reduction_names
+
+    @property
+    def reduction_units(self):
+        return self.levels[0].solver.reduction_units
+
+    def reductions(self):
+        return self.levels[0].solver.reductions()
+
+    def deferred_reductions(self):
+        return self.levels[0].solver.deferred_reductions()
+
+    def advance(self, dt):
+        for level in self.levels:
+            level.solver.new_iteration()
+
+        for b in self.levels[0].solver.runge_kutta_parameters:
+            self.set_bc("primitive1")
+
+            for level in self.levels:
+                for patch in level.solver.patches:
+                    patch.advance_rk(b, dt)
+
+        for level in reversed(self.levels[1:]):
+            self.reflux(level)
+            self.restrict(level)
+
+        self.num_iterations += 1
+        cadence = self.options["invalid_state_cadence"]
+
+        if cadence and self.num_iterations % cadence == 0:
+            for level in self.levels:
+                check_invalid_zones(level.solver.patches, level.solver.mesh, self.time)
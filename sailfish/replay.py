@@ -0,0 +1,87 @@
+"""
+Functions to re-derive analyses from the checkpoints of a completed run.
+
+Analyses are normally computed from each snapshot as it's written, so an
+analysis added after a run has finished would need the run to be repeated.
+Replay computes them from the run's checkpoints instead, without advancing
+the solution: each checkpoint's primitive data and run details are handed
+to the analyses in place of a snapshot, along with the `face_fluxes` which
+solvers with the option `record_face_fluxes` write to checkpoints. Those are
+the exact fluxes of the Runge-Kutta updates, averaged since the previous
+checkpoint, up to and including the last step before it, so analyses of the
+transport through the mesh (e.g. `edge-fluxes`) give the same results as they
+would have during the run. The products are written next to each checkpoint,
+as `chkpt.0000.<name>.pk`.
+"""
+
+import os
+import pickle
+from logging import getLogger
+from sailfish.analysis import AnalysisBase, AnalysisError
+from sailfish.campaign import CHKPT_EXPR
+from sailfish.products import load_checkpoint
+
+logger = getLogger(__name__)
+
+
+def checkpoint_files(directory):
+    """
+    Return the checkpoint files in a directory, in the order they were
+    written, with the final checkpoint last.
+    """
+    matches = list(filter(None, (CHKPT_EXPR.search(f) for f in os.listdir(directory))))
+    key = lambda m: float("inf") if m.group(1) == "final" else int(m.group(1))
+    return [os.path.join(directory, m.string) for m in sorted(matches, key=key)]
+
+
+def replay_snapshot(chkpt):
+    """
+    Return the dictionary an analysis receives for a checkpoint, which is its
+    contents with the primitive data as a host array without guard zones.
+    """
+    return dict(chkpt.contents, primitive=chkpt.primitive, primitive_num_guard=0)
+
+
+def replay(target, analyses):
+    """
+    Compute the named analyses from a checkpoint file, or from each of the
+    checkpoints in a directory, and write their products next to each one.
+
+    An analysis which doesn't apply to a checkpoint is skipped with a
+    warning, e.g. one which needs face fluxes, if the run didn't record them.
+    Returns the list of product files written.
+    """
+    if not analyses:
+        raise ValueError("replay needs at least one analysis")
+
+    classes = [AnalysisBase.find_analysis_class(name) for name in analyses]
+
+    if os.path.isdir(target):
+        filenames = checkpoint_files(target)
+    else:
+        filenames = [target]
+
+    if not filenames:
+        raise ValueError(f"no checkpoints to replay in {target}")
+
+    written = list()
+
+    for filename in filenames:
+        snapshot = replay_snapshot(load_checkpoint(filename))
+
+        for name, cls in zip(analyses, classes):
+            products_filename = f"{os.path.splitext(filename)[0]}.{name}.pk"
+
+            try:
+                products = cls().products(snapshot)
+            except AnalysisError as e:
+                logger.warning(f"analysis {name} skipped for {filename}: {e}")
+                continue
+
+            with open(products_filename, "wb") as outf:
+                logger.info(f"write analysis products {outf.name}")
+                pickle.dump(products, outf)
+
+            written.append(products_filename)
+
+    return written
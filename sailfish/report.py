@@ -0,0 +1,236 @@
+"""
+Write a self-contained HTML summary of a run, for a quick look at its results.
+
+The report has the run's configuration, a performance summary, plots of the
+time series, and the log of events and warnings. Plots are drawn as inline
+SVG by `line_plot`, so the file needs no other files or plotting packages to
+view, and can be sent to collaborators as it is. The driver writes it to
+`report.html` in the output directory at the end of a run with `--report`.
+
+The `RunRecorder` is a logging handler which collects the performance
+numbers and events from the driver's log records while the run is going.
+"""
+
+import html
+import time
+from logging import Handler, WARNING
+
+MAX_PLOTS = 12
+
+PLOT_WIDTH = 480
+PLOT_HEIGHT = 240
+PLOT_MARGIN = 48
+
+
+class RunRecorder(Handler):
+    """
+    A logging handler which keeps the step and event records of the driver,
+    and the messages of warnings and errors.
+    """
+
+    def __init__(self):
+        super().__init__()
+        self.start_time = time.time()
+        self.steps = list()
+        self.events = list()
+        self.warnings = list()
+
+    def emit(self, record):
+        data = getattr(record, "data", None)
+        kind = data.get("kind") if isinstance(data, dict) else None
+
+        if kind == "step":
+            self.steps.append(data)
+        elif kind == "event":
+            self.events.append(data)
+
+        if record.levelno >= WARNING:
+            name = record.name.replace("sailfish.", "")
+            self.warnings.append(f"[{name}] {record.getMessage()}")
+
+    @property
+    def wall_time(self):
+        """
+        The number of seconds since the recorder was created.
+        """
+        return time.time() - self.start_time
+
+
+def line_plot(x, y, title, xlabel=""):
+    """
+    Return an SVG element with a line plot of `y` against `x`, with the
+    data range labeled on each axis.
+    """
+    w, h, m = PLOT_WIDTH, PLOT_HEIGHT, PLOT_MARGIN
+    pairs = [(a, b) for a, b in zip(x, y) if a == a and b == b]
+
+    if not pairs:
+        return f"<p>{html.escape(title)}: no data</p>"
+
+    x0, x1 = min(a for a, _ in pairs), max(a for a, _ in pairs)
+    y0, y1 = min(b for _, b in pairs), max(b for _, b in pairs)
+    sx = (w - 2 * m) / ((x1 - x0) or 1.0)
+    sy = (h - 2 * m) / ((y1 - y0) or 1.0)
+    points = " ".join(
+        f"{m + (a - x0) * sx:.1f},{h - m - (b - y0) * sy:.1f}" for a, b in pairs
+    )
+    title = html.escape(title)
+    xlabel = html.escape(xlabel)
+
+    return "\n".join(
+        [
+            f'<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}">',
+            f'<rect x="{m}" y="{m}" width="{w - 2 * m}" height="{h - 2 * m}" '
+            'fill="none" stroke="#888"/>',
+            f'<polyline points="{points}" fill="none" stroke="#1f77b4"/>',
+            f'<text x="{w / 2}" y="{m - 12}" text-anchor="middle">{title}</text>',
+            f'<text x="{w / 2}" y="{h - 12}" text-anchor="middle">{xlabel}</text>',
+            f'<text x="{m}" y="{h - m + 16}" text-anchor="middle">{x0:.4g}</text>',
+            f'<text x="{w - m}" y="{h - m + 16}" text-anchor="middle">{x1:.4g}</text>',
+            f'<text x="{m - 4}" y="{h - m}" text-anchor="end">{y0:.4g}</text>',
+            f'<text x="{m - 4}" y="{m + 10}" text-anchor="end">{y1:.4g}</text>',
+            "</svg>",
+        ]
+    )
+
+
+def table(rows, header=None):
+    """
+    Return an HTML table with the given rows of values, escaped.
+    """
+    lines = ["<table>"]
+
+    if header is not None:
+        cells = "".join(f"<th>{html.escape(str(c))}</th>" for c in header)
+        lines.append(f"<tr>{cells}</tr>")
+
+    for row in rows:
+        cells = "".join(f"<td>{html.escape(str(c))}</td>" for c in row)
+        lines.append(f"<tr>{cells}</tr>")
+
+    lines.append("</table>")
+    return "\n".join(lines)
+
+
+def configuration_rows(state):
+    """
+    Return the rows of the configuration table: the setup, the driver
+    arguments which were given, the model parameters, and solver options.
+    """
+    rows = [("setup", state.setup.dash_case_class_name())]
+    rows += [
+        (key, value)
+        for key, value in state.driver._asdict().items()
+        if value is not None and key not in ("model_parameters", "solver_options")
+    ]
+    rows += [
+        (f"model.{key}", value)
+        for key, value in state.setup.model_parameter_dict().items()
+    ]
+    rows += [
+        (f"solver.{key}", value)
+        for key, value in (state.solver.options or dict()).items()
+    ]
+    return rows
+
+
+def performance_rows(recorder, state):
+    """
+    Return the rows of the performance table, from the recorded steps.
+    """
+    mzps = [s["Mzps"] for s in recorder.steps]
+    rows = [
+        ("iterations", state.iteration),
+        ("zones", state.mesh.num_total_zones),
+        ("wall time", f"{recorder.wall_time:.1f} s"),
+    ]
+
+    if mzps:
+        rows += [
+            ("mean Mzps", f"{sum(mzps) / len(mzps):.3f}"),
+            ("min Mzps", f"{min(mzps):.3f}"),
+            ("max Mzps", f"{max(mzps):.3f}"),
+        ]
+
+    return rows
+
+
+def timeseries_plots(state):
+    """
+    Return SVG plots of the time series columns against the `time` column,
+    if there is one, or otherwise the sample number.
+    """
+    samples = state.synchronize()
+    names = state.solver.reduction_names
+
+    if not samples:
+        return ["<p>no time series samples were recorded</p>"]
+
+    num_columns = len(samples[0])
+    names = [names[n] if n < len(names) else f"column{n}" for n in range(num_columns)]
+    columns = [[complex(s[n]).real for s in samples] for n in range(len(names))]
+
+    if "time" in names:
+        x = columns[names.index("time")]
+        xlabel = "time"
+    else:
+        x = list(range(len(samples)))
+        xlabel = "sample"
+
+    plots = [
+        line_plot(x, y, name, xlabel)
+        for name, y in zip(names, columns)
+        if name != "time"
+    ]
+    return plots[:MAX_PLOTS]
+
+
+def write_report(filename, state, recorder, status=None):
+    """
+    Write the HTML report of a run to a file, from its final driver state,
+    the `RunRecorder` which watched it, and optionally the run status.
+    """
+    user_time = state.solver.time / state.setup.reference_time_scale
+    title = html.escape(f"sailfish run: {state.setup.dash_case_class_name()}")
+    status_rows = [(key, value) for key, value in (status or dict()).items()]
+    status_rows += [("iteration", state.iteration), ("time", f"{user_time:.6g}")]
+    performance = performance_rows(recorder, state)
+    mzps_plot = line_plot(
+        [s["iteration"] for s in recorder.steps],
+        [s["Mzps"] for s in recorder.steps],
+        "Mzps",
+        "iteration",
+    )
+    events = [(e["name"], e["number"], f"{e['time']:.6g}") for e in recorder.events]
+    warnings = "".join(f"<li>{html.escape(w)}</li>" for w in recorder.warnings)
+
+    sections = [
+        f"<h1>{title}</h1>",
+        f"<p>written {time.strftime('%Y-%m-%d %H:%M:%S')}</p>",
+        "<h2>Status</h2>",
+        table(status_rows),
+        "<h2>Configuration</h2>",
+        table(configuration_rows(state)),
+        "<h2>Performance</h2>",
+        table(performance),
+        mzps_plot if recorder.steps else "",
+        "<h2>Time series</h2>",
+        *timeseries_plots(state),
+        "<h2>Events</h2>",
+        table(events, ("event", "number", "time")) if events else "<p>none</p>",
+        "<h2>Warnings</h2>",
+        f"<ul>{warnings}</ul>" if warnings else "<p>none</p>",
+    ]
+    style = (
+        "body { font-family: sans-serif; max-width: 1000px; margin: auto; } "
+        "td, th { padding: 2px 12px; text-align: left; } "
+        "svg { font-size: 11px; margin: 4px; }"
+    )
+
+    with open(filename, "w") as f:
+        f.write("<!DOCTYPE html>\n<html>\n<head>\n<meta charset='utf-8'>\n")
+        f.write(f"<title>{title}</title>\n<style>{style}</style>\n</head>\n<body>\n")
+        f.write("\n".join(sections))
+        f.write("\n</body>\n</html>\n")
+
+    return filename
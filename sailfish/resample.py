@@ -0,0 +1,133 @@
+"""
+Resampling of a solution onto a mesh of another resolution.
+
+A long run can be continued at a higher resolution once the flow has settled,
+instead of being started over: a checkpoint is restarted with a new
+`--resolution` and the `--resample` option, and its solution is moved onto
+the new mesh by `resample_solution`. The two meshes must be 2D cartesian
+meshes with the same extent, and the numbers of zones along each axis must
+be integer multiples of one another. So far, the solutions of the `cbdiso_2d`
+solver can be resampled, including those with live point masses or a
+reservoir, whose other items are kept as they are.
+
+Each axis is resampled in turn, on the mass-weighted data (the density, and
+the density times the other fields), which for the isothermal solvers are
+the conserved quantities. A coarser axis is restricted by averaging the child
+zones of each zone, which conserves the totals exactly. A finer axis is
+prolonged with a linear profile in each zone, in one of two ways: `plm`
+limits the slope with the minmod of the differences to the neighbors, like
+the refinement of `sailfish.refinement`, so it's conservative, and creates no
+new extrema; `bilinear` interpolates between the zone centers, from the
+neighbor on each side of a child zone, which is smoother than `plm` but
+neither conservative nor monotone near discontinuities. The outermost zones
+use their own data in place of the missing neighbors. Prolonging and then
+restricting with `plm` restores the original data, to round-off.
+
+The result has no sub-zone detail, so the finer run takes some time to grow
+the structure the new resolution can hold.
+"""
+
+from sailfish.mesh import PlanarCartesian2DMesh
+from sailfish.refinement import minmod
+
+RESAMPLE_METHODS = ("plm", "bilinear")
+RESAMPLED_SOLVERS = ("cbdiso_2d",)
+
+
+def mass_weighted(primitive):
+    u = primitive.copy()
+    u[..., 1:] *= primitive[..., :1]
+    return u
+
+
+def from_mass_weighted(u):
+    p = u.copy()
+    p[..., 1:] /= u[..., :1]
+    return p
+
+
+def restrict_axis(u, n, axis, xp):
+    """
+    Return the data along one axis averaged over groups of `n` zones.
+    """
+    u = xp.moveaxis(u, axis, 0)
+    u = u.reshape((u.shape[0] // n, n) + u.shape[1:]).mean(axis=1)
+    return xp.moveaxis(u, 0, axis)
+
+
+def prolong_axis(u, n, axis, method, xp):
+    """
+    Return the data along one axis refined by `n`, with a linear profile in
+    each zone, made with the given method.
+    """
+    u = xp.moveaxis(u, axis, 0)
+    padded = xp.concatenate([u[:1], u, u[-1:]])
+    left = (u - padded[:-2])[:, None]
+    right = (padded[2:] - u)[:, None]
+    offsets = (xp.arange(n) + 0.5) / n - 0.5
+    offsets = offsets.reshape((1, n) + (1,) * (u.ndim - 1))
+
+    if method == "plm":
+        fine = u[:, None] + offsets * minmod(left, right, xp)
+    else:
+        fine = u[:, None] + xp.where(offsets < 0.0, offsets * left, offsets * right)
+
+    fine = fine.reshape((u.shape[0] * n,) + u.shape[1:])
+    return xp.moveaxis(fine, 0, axis)
+
+
+def resample(primitive, old_mesh, new_mesh, method="plm"):
+    """
+    Return primitive data on `old_mesh` (with the field index last) resampled
+    onto `new_mesh`, restricting the coarser axes and prolonging the finer
+    ones with the given method, `plm` or `bilinear`. Raise a `ValueError` if
+    the meshes can't be resampled onto one another.
+    """
+    import numpy as np
+
+    if method not in RESAMPLE_METHODS:
+        raise ValueError(f"unknown resample method {method}, expected plm or bilinear")
+
+    for mesh in (old_mesh, new_mesh):
+        if not isinstance(mesh, PlanarCartesian2DMesh):
+            raise ValueError(f"can only resample 2D cartesian meshes, not {mesh}")
+
+    old_extent = old_mesh.geometry.lower, old_mesh.geometry.upper
+    new_extent = new_mesh.geometry.lower, new_mesh.geometry.upper
+
+    if old_extent != new_extent:
+        raise ValueError(f"can't resample mesh extent {old_extent} to {new_extent}")
+
+    old_shape = old_mesh.topology.shape
+    new_shape = new_mesh.topology.shape
+
+    if primitive.shape[:-1] != old_shape:
+        raise ValueError(f"primitive shape {primitive.shape} is not {old_shape}")
+
+    u = mass_weighted(np.asarray(primitive, dtype=float))
+
+    for axis, (a, b) in enumerate(zip(old_shape, new_shape)):
+        if max(a, b) % min(a, b):
+            raise ValueError(f"can't resample {a} zones to {b} along axis {axis}")
+        if b > a:
+            u = prolong_axis(u, b // a, axis, method, np)
+        elif b < a:
+            u = restrict_axis(u, a // b, axis, np)
+
+    return from_mass_weighted(u)
+
+
+def resample_solution(solution, old_mesh, new_mesh, method="plm"):
+    """
+    Return a checkpointed solution, either the primitive array or a dict of
+    it (`primitive`) and other items, with the primitive data resampled from
+    `old_mesh` onto `new_mesh`.
+    """
+    if isinstance(solution, dict):
+        resampled = dict(solution)
+        resampled["primitive"] = resample(
+            solution["primitive"], old_mesh, new_mesh, method
+        )
+        return resampled
+
+    return resample(solution, old_mesh, new_mesh, method)
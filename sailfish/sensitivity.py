@@ -0,0 +1,117 @@
+"""
+Experimental: evolve the derivatives of a solution with respect to a few
+model parameters, along with the solution itself.
+
+The derivative :math:`s = \\partial u / \\partial p` of the primitive data with
+respect to a parameter obeys the tangent-linear of the solver's update,
+:math:`s_{n+1} = J_u s_n + J_p`, where the Jacobians are those of one
+iteration. They are never formed; instead their product with the direction
+:math:`(s_n, 1)` is evaluated each iteration, by a central difference of two
+solvers which are given the states :math:`u_n \\pm h s_n`, and built with
+their setup's parameter at :math:`p \\pm h`. Both take the time step of the
+unperturbed solution. This stays accurate over a long run, whereas a finite
+difference of two whole simulations loses the derivative once their
+trajectories separate nonlinearly, and it costs two extra solvers per
+parameter rather than a new run for each calibration step. The initial
+derivative is the central difference of the setups' initial conditions.
+
+.. code-block:: python
+
+    from sailfish.sensitivity import TangentLinear
+
+    sim = TangentLinear("circumbinary-disk", ["nu", "sink_rate"], resolution=100)
+    sim.step(until=1.0)
+    dsigma_dnu = sim.derivative("nu")[..., 0]
+
+The derivative of the time step itself is ignored, and the update is not
+differentiable where floors, limiters, or wave speed estimates switch
+branches, so derivatives are most meaningful in smooth flows. The solver has
+to support `set_primitive`, which the 2D disk solvers do.
+"""
+
+from logging import getLogger
+from sailfish.interactive import Simulation
+
+logger = getLogger(__name__)
+
+
+class TangentLinear(Simulation):
+    """
+    A simulation which also evolves the derivatives of its primitive data
+    with respect to the model parameters named in `parameters`.
+
+    The perturbation of each parameter is `relative_step` times its value,
+    or `relative_step` itself for a parameter which is zero. The other
+    keyword arguments are those of :py:class:`sailfish.interactive.Simulation`.
+    """
+
+    def __init__(
+        self,
+        setup_name,
+        parameters,
+        model_parameters=None,
+        relative_step=1e-6,
+        **kwargs,
+    ):
+        super().__init__(setup_name, model_parameters=model_parameters, **kwargs)
+
+        self.branches = dict()
+        self.derivatives = dict()
+        self.steps = dict()
+        kwargs["resolution"] = self.resolution
+
+        for name in parameters:
+            try:
+                value = float(getattr(self.setup, name))
+            except (AttributeError, TypeError, ValueError):
+                raise ValueError(f"{name} is not a numerical model parameter")
+
+            h = relative_step * (abs(value) or 1.0)
+            branches = [
+                Simulation(
+                    setup_name,
+                    model_parameters=dict(model_parameters or dict(), **{name: p}),
+                    **kwargs,
+                )
+                for p in (value + h, value - h)
+            ]
+
+            if any(b.mesh != self.mesh for b in branches):
+                raise ValueError(f"the mesh depends on {name}")
+
+            plus, minus = (b.solver.primitive_array() for b in branches)
+            self.branches[name] = branches
+            self.derivatives[name] = (plus - minus) / (2.0 * h)
+            self.steps[name] = h
+            logger.info(f"evolve the derivative with respect to {name}, h={h:.3e}")
+
+    def derivative(self, name):
+        """
+        Return the derivative of the rest-frame primitive data with respect to
+        the named parameter, as a host array in the layout of
+        `solver.primitive_array()`.
+        """
+        return self.derivatives[name]
+
+    def advance(self):
+        """
+        Take a single iteration of the solution and of each derivative.
+        """
+        primitive = self.solver.primitive_array()
+        super().advance()
+
+        for name, (plus, minus) in self.branches.items():
+            h = self.steps[name]
+            s = self.derivatives[name]
+
+            try:
+                plus.solver.set_primitive(primitive + h * s)
+                minus.solver.set_primitive(primitive - h * s)
+            except NotImplementedError as e:
+                raise ValueError(f"tangent-linear mode needs set_primitive: {e}")
+
+            plus.solver.advance(self.dt)
+            minus.solver.advance(self.dt)
+            up = plus.solver.primitive_array()
+            um = minus.solver.primitive_array()
+            self.derivatives[name] = (up - um) / (2.0 * h)
@@ -29,6 +29,13 @@ class Parameter(NamedTuple):
 param = Parameter
 
 
+def dash_case(name):
+    """
+    Return the `dash-case` form of a `CamelCase` class name.
+    """
+    return "".join(["-" + c.lower() if c.isupper() else c for c in name]).lstrip("-")
+
+
 class SetupBase(ABC):
     """
     Abstract base class to describe a simulation model setup.
@@ -98,9 +105,7 @@ class SetupBase(ABC):
         Dash case is used in configuration, including the setup name passed to
         the driver, and written in checkpoint files.
         """
-        return "".join(
-            ["-" + c.lower() if c.isupper() else c for c in cls.__name__]
-        ).lstrip("-")
+        return dash_case(cls.__name__)
 
     @classmethod
     def find_setup_class(cls, name):
@@ -177,6 +182,39 @@ class SetupBase(ABC):
         """
         pass
 
+    def primitive_array(self, mesh, num_fields, time=None, num_guard=0):
+        """
+        Return the primitive data at the zone centers of a mesh, as a host
+        array of shape `mesh.shape + (num_fields,)`.
+
+        With `num_guard` guard zones, the array is padded by that many zones
+        on each side of every axis, and the setup is sampled at the centers
+        of the guard zones too, which is the layout of a solver's
+        `primitive_array(guard_zones=True)`. The time is the setup's start
+        time by default. Fields the setup doesn't write are zero.
+        """
+        import numpy as np
+        from sailfish.mesh import LogSphericalMesh
+
+        t = self.start_time if time is None else time
+        ng = num_guard
+        shape = tuple(n + 2 * ng for n in mesh.shape)
+        primitive = np.zeros(shape + (num_fields,))
+
+        for index in np.ndindex(*shape):
+            i = tuple(n - ng for n in index)
+
+            if len(i) == 1:
+                coordinate = mesh.zone_center(t, i[0])
+            elif isinstance(mesh, LogSphericalMesh):
+                coordinate = mesh.cell_coordinates(t, *i)
+            else:
+                coordinate = mesh.cell_coordinates(*i)
+
+            self.primitive(t, coordinate, primitive[index])
+
+        return primitive
+
     @abstractmethod
     def mesh(self, resolution: int):
         """
@@ -195,6 +233,55 @@ class SetupBase(ABC):
         """
         pass
 
+    def active_zones(self, mesh):
+        """
+        Return a boolean array of the zones to be evolved, or `None` for all.
+
+        A setup can override this to skip the zones of a mesh which don't
+        matter to the problem, for example the corners of a square domain
+        around a disk. The array has the shape of the mesh. Zones outside the
+        mask are not updated by the solver, and act as a fixed boundary for
+        the zones next to them. Only the cbdiso_2d solver uses the mask.
+        """
+        return None
+
+    def buffer_target(self, mesh):
+        """
+        Return a pair of arrays `(rate, primitive)`, of a state toward which
+        the solution is driven, or `None` for no such buffer.
+
+        A setup can override this to damp the solution toward any state, for
+        example the inflow of a wind tunnel, or a disk with an inner cavity,
+        where the Keplerian buffer zone doesn't apply. The `rate` array has
+        the shape of the mesh, and is the inverse of the damping time in each
+        zone, zero outside the buffer. The `primitive` array has the shape of
+        the mesh and three fields, the surface density and velocities of the
+        target state, in the rest frame. Only the cbdiso_2d solver uses the
+        target.
+        """
+        return None
+
+    @property
+    def regions(self):
+        """
+        Return a list of :py:class:`sailfish.coupling.Region`, to solve parts
+        of the mesh with different physics, or `None` to use one solver.
+
+        The regions are solved by coupled instances of the setup's solver,
+        each with the setup's physics updated by that of the region; see
+        `sailfish.coupling`.
+        """
+        return None
+
+    @property
+    def refinement(self):
+        """
+        Return a list of :py:class:`sailfish.refinement.RefinedRegion`, to
+        cover parts of the mesh with nested levels of 2x-refined patches, or
+        `None` for a uniform mesh; see `sailfish.refinement`.
+        """
+        return None
+
     @property
     def physics(self):
         """
@@ -214,7 +301,8 @@ class SetupBase(ABC):
         This method must be overridden, and the mode must be supported by the
         solver. 1D solvers should accept either a single return value
         specifying the BC mode at both the domain edges, or a pair of modes,
-        one for each edge.
+        one for each edge. The forms accepted by 2D solvers, which can set
+        each edge separately, are described in `sailfish.boundary`.
         """
         pass
 
@@ -4,3 +4,6 @@ from .envelope_shock import *
 from .exploding_star import *
 from .circumbinary_disk import *
 from .binary_bondi import *
+from .instabilities import *
+from .planet_disk import *
+from .viscous_ring import *
@@ -13,7 +13,7 @@ as Bondi-Hoyle-Lyttleton accretion. Some references below:
 from sailfish.mesh import PlanarCartesian2DMesh
 from sailfish.physics.circumbinary import SinkModel, PointMass, EquationOfState
 from sailfish.physics.kepler import OrbitalElements
-from sailfish.setup_base import SetupBase, param
+from sailfish.setup_base import SetupBase, SetupError, param
 
 
 class BinaryBondi(SetupBase):
@@ -31,12 +31,20 @@ class BinaryBondi(SetupBase):
     bh_sep = param(0.25, "black hole semi-major axis")
     bh_mass = param(1.0, "mass of the primary")
     sink_radius = param(0.05, "black hole sink radius")
-    sink_model = param("acceleration_free", "sink prescription")
+    sink_model = param(
+        "acceleration_free", "sink [inactive|acceleration_free|force_free|torque_free]"
+    )
     sink_rate = param(1e2, "sink rate")
     mass_ratio = param(1.0, "system mass ratio: [0.0-1.0]")
     eccentricity = param(0.0, "eccentricity")
     softening_length = param(0.05, "gravitational softening length", mutable=True)
 
+    def validate(self):
+        try:
+            SinkModel.from_str(self.sink_model)
+        except ValueError as e:
+            raise SetupError(e)
+
     def primitive(self, t, coords, primitive):
         primitive[0] = 1.0
         primitive[1] = self.wind_vel
@@ -87,14 +95,14 @@ class BinaryBondi(SetupBase):
         return (
             PointMass(
                 softening_length=self.softening_length,
-                sink_model=SinkModel[self.sink_model.upper()],
+                sink_model=SinkModel.from_str(self.sink_model),
                 sink_rate=self.sink_rate,
                 sink_radius=self.sink_radius,
                 **m1._asdict(),
             ),
             PointMass(
                 softening_length=self.softening_length,
-                sink_model=SinkModel[self.sink_model.upper()],
+                sink_model=SinkModel.from_str(self.sink_model),
                 sink_rate=self.sink_rate,
                 sink_radius=self.sink_radius,
                 **m2._asdict(),
@@ -2,17 +2,26 @@
 2D disk setups for binary problems.
 """
 
+from functools import lru_cache
 from math import sqrt, exp, pi
-from sailfish.mesh import LogSphericalMesh, PlanarCartesian2DMesh
+from sailfish.boundary import OUTFLOW_MODES
+from sailfish.mesh import LogPolar2DMesh, LogSphericalMesh, PlanarCartesian2DMesh
 from sailfish.physics.circumbinary import (
+    accretion_diagnostics,
+    CoolingModel,
     EquationOfState,
     PointMass,
     SinkModel,
+    SofteningModel,
+    torque_decomposition_diagnostics,
     ViscosityModel,
 )
-from sailfish.physics.kepler import OrbitalElements
+from sailfish.physics.kepler import EvolvingOrbit, OrbitalElements
+from sailfish.physics.rotation import RotationCurve, RotationCurveError
 from sailfish.setup_base import SetupBase, SetupError, param
 
+OUTER_BOUNDARY_MODES = [m.value for m in OUTFLOW_MODES] + ["characteristic"]
+
 
 class CircumbinaryDisk(SetupBase):
     r"""
@@ -38,6 +47,89 @@ class CircumbinaryDisk(SetupBase):
     .. math::
         \Sigma \propto r^{-3/5}, \, \mathcal{P} \propto r^{-3/2}
 
+    The initial azimuthal velocity follows the `rotation_curve`, which is
+    Keplerian by default, or may be a power law or a table of speeds, e.g. for
+    a self-gravitating disk (see `sailfish.physics.rotation`). If
+    `pressure_supported` is true, the velocity is corrected for the radial
+    pressure gradient, so the disk starts in centrifugal equilibrium; the
+    isothermal sound speed is that of a single mass at the origin, or that of
+    the `sound_speed_profile`.
+
+    In isothermal mode, the sound speed is set by the `mach_number` and the
+    combined potential of the two point masses, unless `sound_speed_profile`
+    is given, which sets it as a function of radius instead: it's written
+    like a rotation curve, `power-law:<c0>:<q>` for :math:`c_0 (r / a)^q`, or
+    `table:<file>` with columns of radius and sound speed, which falls off as
+    :math:`r^{-1/2}` beyond the table.
+
+    The binary is on a prescribed Kepler orbit, unless `live_binary` is true,
+    in which case the solver integrates the orbit from its initial state,
+    under the measured forces from the gas. The prescribed orbit can instead
+    evolve, with its semi-major axis and eccentricity changing at the given
+    `semimajor_axis_rate` and `eccentricity_rate` per initial orbit; the
+    point masses follow it at each Runge-Kutta stage (see
+    `sailfish.physics.kepler.EvolvingOrbit`).
+
+    The gravity of each point mass is softened within the `softening_length`
+    by the `softening_model` (isothermal only for models other than
+    plummer): a Plummer potential, a cubic spline kernel which is Newtonian
+    beyond 2.8 softening lengths, or none (see
+    `sailfish.physics.circumbinary.SofteningModel`).
+
+    In gamma-law mode, the `cooling_model` can also relax the temperature
+    toward the orbital temperature of the `mach_number`, with beta cooling
+    on `cooling_beta` times the inverse Keplerian frequency, or on the fixed
+    `cooling_time` in orbits (see `sailfish.physics.circumbinary.Physics`).
+
+    If `which_diagnostics` is `torques` (isothermal only), the accretion
+    diagnostics are followed by the gravitational torque on each point mass
+    from each region of the gas: the minidisks in the Roche lobes, the rest of
+    the cavity out to two binary separations, the outer disk, and the buffer.
+
+    If `tracer_radius` is non-zero (isothermal only), a passive scalar tags
+    the gas which starts inside that radius with concentration 1, and the
+    gas outside it with 0, so the accreted gas can be traced to its origin in
+    the inner or outer disk.
+
+    If `two_layer` is true (isothermal only), the upper and lower halves of
+    the disk are evolved as two coupled layers by the cbdiso2l_2d solver,
+    with the coupling rates `layer_drag_rate` and `layer_exchange_rate`, and
+    the upper layer inclined by `layer_inclination` degrees to the binary.
+
+    If `reservoir_radius` is non-zero (isothermal only), the zones inside that
+    radius are excised into a 0D reservoir (see `sailfish.physics.reservoir`),
+    which stores the gas flowing into the hole and drains it onto the binary
+    on a time of `reservoir_viscous_time` orbits, with the specific angular
+    momentum `reservoir_accreted_l`. It starts with the disk's initial surface
+    density at its edge, rotating at the initial speed of the disk there.
+
+    If `custom_source` is given (isothermal only), it's a shared library or a
+    source file with a per-zone source term, which is called with the numbers
+    in `custom_source_parameters` (see `sailfish.kernel.custom`).
+
+    If `refinement_levels` is non-zero (isothermal only), the mesh is refined
+    near the binary by that many nested squares centered on the origin, each
+    with twice the resolution of the one containing it, and half its size;
+    the finest has the half side length `refinement_radius`. See
+    `sailfish.refinement`.
+
+    If `self_gravity` is non-zero (isothermal only), it's the gravitational
+    constant of the gas, which is accelerated by its own gravity, softened on
+    the scale `self_gravity_softening`, e.g. to follow a gravitationally
+    unstable disk; see `sailfish.physics.self_gravity`. With the unit disk
+    density of the isothermal mode, the disk's Toomre parameter is set by
+    the `mach_number` and `self_gravity` together.
+
+    If `inner_buffer_radius` is non-zero (isothermal only), the solution
+    inside that radius is damped toward the initial disk, e.g. to absorb the
+    waves launched into a wide cavity; see `sailfish.physics.circumbinary`.
+
+    The `outer_boundary` is either one of the outflow modes of
+    `sailfish.boundary`, e.g. outflow or outflow-linear-clamped, or
+    characteristic (isothermal only), which is non-reflecting; with
+    `buffer_is_enabled=False` it replaces the buffer zone, so the disk has no
+    buffer torque.
+
     .. _Shakura & Sunyaev (1973): https://ui.adsabs.harvard.edu/abs/1973A%26A....24..337S
     .. _Goodman (2003): https://ui.adsabs.harvard.edu/abs/2003MNRAS.339..937G
     """
@@ -45,71 +137,400 @@ class CircumbinaryDisk(SetupBase):
     eos = param("isothermal", "EOS type: either isothermal or gamma-law")
     domain_radius = param(12.0, "half side length of the square computational domain")
     mach_number = param(10.0, "orbital Mach number (isothermal)", mutable=True)
+    sound_speed_profile = param("", "if given, cs(r) [power-law:<c0>:<q>|table:<file>]")
     eccentricity = param(0.0, "orbital eccentricity of the binary", mutable=True)
+    semimajor_axis_rate = param(0.0, "change of the semi-major axis per orbit")
+    eccentricity_rate = param(0.0, "change of the eccentricity per orbit")
     mass_ratio = param(1.0, "component mass ratio m2 / m1 <= 1", mutable=True)
     sink_rate = param(10.0, "component sink rate", mutable=True)
     sink_radius = param(0.05, "component sink radius", mutable=True)
     softening_length = param(0.05, "gravitational softening length", mutable=True)
+    softening_zones = param(
+        0.0, "softening length in zones (overrides softening_length)", mutable=True
+    )
+    softening_model = param(
+        "plummer", "gravitational softening [plummer|spline|none]", mutable=True
+    )
     buffer_is_enabled = param(True, "whether the buffer zone is enabled", mutable=True)
+    inner_buffer_radius = param(
+        0.0, "if non-zero, damp waves inside this radius (isothermal)", mutable=True
+    )
+    outer_boundary = param("outflow", "outflow[-linear][-clamped] or characteristic")
     sink_model = param(
-        "torque_free", "sink [acceleration_free|force_free|torque_free]", mutable=True
+        "torque_free",
+        "sink [inactive|acceleration_free|force_free|torque_free]",
+        mutable=True,
     )
     initial_sigma = param(1.0, "initial disk surface density at r=a (gamma-law)")
     initial_pressure = param(1e-2, "initial disk surface pressure at r=a (gamma-law)")
     cooling_coefficient = param(0.0, "strength of the cooling term (gamma-law)")
+    cooling_model = param("none", "none, beta, or constant-time relaxation (gamma-law)")
+    cooling_beta = param(10.0, "cooling time times the Keplerian frequency (beta)")
+    cooling_time = param(1.0, "cooling time, in orbits (constant-time)")
     alpha = param(0.1, "alpha-viscosity parameter (gamma-law)")
     nu = param(0.001, "kinematic viscosity parameter (isothermal)")
+    isothermal_alpha = param(0.0, "alpha-viscosity, replaces nu if > 0 (isothermal)")
+    pre_relax_orbits = param(0.0, "orbits of 1D viscous pre-relaxation (isothermal)")
+    pre_relax_torque = param(0.01, "binary torque coefficient in the pre-relaxation")
     constant_softening = param(True, "whether to use constant softening (gamma-law)")
     gamma_law_index = param(5.0 / 3.0, "adiabatic index (gamma-law)")
-    which_diagnostics = param("none", "diagnostics set to get from solver [none|mdots]")
+    which_diagnostics = param(
+        "none", "diagnostics set to get from solver [none|mdots|accretion|torques]"
+    )
+    mask_radius = param(0.0, "if non-zero, skip zones beyond this radius (isothermal)")
+    rotation_curve = param(
+        "keplerian", "initial v_phi [keplerian|power-law:<v0>:<q>|table:<file>]"
+    )
+    pressure_supported = param(False, "whether v_phi balances the pressure gradient")
+    live_binary = param(False, "whether the solver integrates the binary orbit")
+    tracer_radius = param(0.0, "if non-zero, tag gas starting inside this radius")
+    reservoir_radius = param(0.0, "if non-zero, excise zones inside this radius")
+    reservoir_viscous_time = param(1.0, "reservoir drain time, in orbits")
+    reservoir_accreted_l = param(0.0, "specific angular momentum of drained gas")
+    custom_source = param("", "library or source file with a custom source term")
+    custom_source_parameters = param("", "comma-separated custom source parameters")
+    two_layer = param(False, "evolve two coupled layers of the disk (isothermal)")
+    layer_drag_rate = param(0.0, "decay rate of the layers' velocity difference")
+    layer_exchange_rate = param(0.0, "decay rate of the layers' density difference")
+    layer_inclination = param(0.0, "inclination of the upper layer in degrees")
+    refinement_levels = param(0, "levels of nested 2x-refined squares (isothermal)")
+    refinement_radius = param(1.5, "half side length of the finest refined square")
+    self_gravity = param(0.0, "gravitational constant of the gas, 0 for none (isothermal)")
+    self_gravity_softening = param(0.1, "softening length of the gas's gravity")
 
     def validate(self):
         if not self.is_isothermal and not self.is_gamma_law:
             raise SetupError(f"eos must be isothermal or gamma-law, got {self.eos}")
-        if self.which_diagnostics not in ["none", "mdots"]:
+        if self.pre_relax_orbits > 0.0 and not self.is_isothermal:
+            raise SetupError("pre-relaxation is only supported for eos=isothermal")
+        if self.mask_radius < 0.0:
+            raise SetupError(f"mask_radius must be non-negative, got {self.mask_radius}")
+        if self.isothermal_alpha < 0.0:
+            raise SetupError("isothermal_alpha must be non-negative")
+        if self.isothermal_alpha > 0.0 and self.pre_relax_orbits > 0.0:
+            raise SetupError("pre-relaxation is only supported for constant nu")
+        if self.mask_radius > 0.0 and not self.is_isothermal:
+            raise SetupError("zone masking is only supported for eos=isothermal")
+        if self.tracer_radius < 0.0:
+            raise SetupError("tracer_radius must be non-negative")
+        if self.tracer_radius > 0.0 and not self.is_isothermal:
+            raise SetupError("tracers are only supported for eos=isothermal")
+        if self.reservoir_radius < 0.0 or self.reservoir_viscous_time <= 0.0:
+            raise SetupError("the reservoir radius and viscous time must be positive")
+        if self.reservoir_radius > 0.0 and (not self.is_isothermal or self.two_layer):
+            raise SetupError("the inner reservoir is only supported for cbdiso_2d")
+        if 0.0 < self.mask_radius <= self.reservoir_radius:
+            raise SetupError("mask_radius must be larger than reservoir_radius")
+        if self.inner_buffer_radius < 0.0:
+            raise SetupError("inner_buffer_radius must be non-negative")
+        if self.inner_buffer_radius > 0.0 and (
+            not self.is_isothermal or self.two_layer
+        ):
+            raise SetupError("the inner buffer is only supported for cbdiso_2d")
+        if self.custom_source and not self.is_isothermal:
+            raise SetupError("custom sources are only supported for eos=isothermal")
+        try:
+            self.custom_source_parameter_list
+        except ValueError:
+            raise SetupError("custom_source_parameters must be comma-separated numbers")
+        if self.two_layer and not self.is_isothermal:
+            raise SetupError("the two-layer model is only supported for eos=isothermal")
+        if self.two_layer and (self.live_binary or self.softening_zones):
+            raise SetupError("two_layer needs a prescribed binary and fixed softening")
+        if self.outer_boundary not in OUTER_BOUNDARY_MODES:
+            raise SetupError(
+                "outer_boundary must be an outflow mode or characteristic, "
+                f"got {self.outer_boundary}"
+            )
+        if self.outer_boundary == "characteristic" and not self.is_isothermal:
+            raise SetupError("characteristic boundaries need eos=isothermal")
+        if self.which_diagnostics not in ["none", "mdots", "accretion", "torques"]:
             raise SetupError(
-                f"which_diagnostics must be none or mdots, got {self.which_diagnostics}"
+                "which_diagnostics must be none, mdots, accretion, or torques, "
+                f"got {self.which_diagnostics}"
             )
+        if self.which_diagnostics == "torques" and not self.is_isothermal:
+            raise SetupError("torque diagnostics by source region need eos=isothermal")
+        try:
+            self.rotation()
+        except RotationCurveError as e:
+            raise SetupError(e)
+        if self.sound_speed_profile:
+            if not self.is_isothermal:
+                raise SetupError("sound_speed_profile needs eos=isothermal")
+            try:
+                kind = self.sound_speed().kind
+            except RotationCurveError as e:
+                raise SetupError(e)
+            if kind == "keplerian":
+                raise SetupError("sound_speed_profile must be a power-law or a table")
+        if self.self_gravity_softening < 0.0:
+            raise SetupError("self_gravity_softening must be non-negative")
+        if self.self_gravity != 0.0 and (not self.is_isothermal or self.two_layer):
+            raise SetupError("self-gravity is only supported for cbdiso_2d")
+        if self.self_gravity != 0.0 and self.refinement_levels > 0:
+            raise SetupError("self-gravity is not supported with mesh refinement")
+        if self.refinement_levels < 0 or self.refinement_radius <= 0.0:
+            raise SetupError("the refinement levels and radius must be positive")
+        if self.refinement_levels > 0 and (not self.is_isothermal or self.two_layer):
+            raise SetupError("mesh refinement is only supported for cbdiso_2d")
+        if self.refinement_levels > 0 and (self.live_binary or self.softening_zones):
+            raise SetupError("mesh refinement needs a prescribed binary and softening")
+        if self.refinement_levels > 0 and (
+            self.reservoir_radius > 0.0 or self.custom_source
+        ):
+            raise SetupError("mesh refinement doesn't support a reservoir or source")
+        if self.refinement_radius * 2**self.refinement_levels >= 2 * self.domain_radius:
+            raise SetupError("the refined squares must be inside the domain")
+        try:
+            SinkModel.from_str(self.sink_model)
+        except ValueError as e:
+            raise SetupError(e)
+        try:
+            softening = SofteningModel.from_str(self.softening_model)
+        except ValueError as e:
+            raise SetupError(e)
+        if softening != SofteningModel.PLUMMER and not self.is_isothermal:
+            raise SetupError("softening models other than plummer need eos=isothermal")
+        try:
+            cooling = CoolingModel.from_str(self.cooling_model)
+        except ValueError as e:
+            raise SetupError(e)
+        if cooling != CoolingModel.NONE and not self.is_gamma_law:
+            raise SetupError("temperature relaxation is only supported for gamma-law")
+        if self.cooling_beta <= 0.0 or self.cooling_time <= 0.0:
+            raise SetupError("cooling_beta and cooling_time must be positive")
+        if not 0.0 <= self.eccentricity < 1.0:
+            raise SetupError("eccentricity must be in [0, 1)")
+        if self.is_evolving_orbit and self.live_binary:
+            raise SetupError("an evolving orbit is prescribed, it can't be live")
+
+    @property
+    def custom_source_parameter_list(self):
+        return [float(p) for p in self.custom_source_parameters.split(",") if p]
 
     @property
     def is_isothermal(self):
         return self.eos == "isothermal"
 
+    @property
+    def is_evolving_orbit(self):
+        return self.semimajor_axis_rate != 0.0 or self.eccentricity_rate != 0.0
+
     @property
     def is_gamma_law(self):
         return self.eos == "gamma-law"
 
+    @property
+    def isothermal_eos_type(self):
+        if self.sound_speed_profile:
+            return EquationOfState.PROFILE_ISOTHERMAL
+        else:
+            return EquationOfState.LOCALLY_ISOTHERMAL
+
+    @property
+    def sound_speed_function(self):
+        if self.sound_speed_profile:
+            return self.sound_speed().circular_speed
+        else:
+            return None
+
+    @property
+    def isothermal_viscosity_model(self):
+        if self.isothermal_alpha > 0.0:
+            return ViscosityModel.CONSTANT_ALPHA
+        elif self.nu > 0.0:
+            return ViscosityModel.CONSTANT_NU
+        else:
+            return ViscosityModel.NONE
+
     def primitive(self, t, coords, primitive):
-        GM = 1.0
         x, y = coords
         r = sqrt(x * x + y * y)
         r_softened = sqrt(x * x + y * y + self.softening_length * self.softening_length)
         phi_hat_x = -y / max(r, 1e-12)
         phi_hat_y = +x / max(r, 1e-12)
+        vp = self.azimuthal_speed(r_softened)
 
-        if self.is_isothermal:
-            primitive[0] = self.initial_sigma
-            primitive[1] = sqrt(GM / r_softened) * phi_hat_x
-            primitive[2] = sqrt(GM / r_softened) * phi_hat_y
+        if self.is_isothermal and self.pre_relax_orbits > 0.0:
+            import numpy as np
+
+            rc, sigma, vr = self.relaxed_disk()
+            vr = np.interp(r, rc, vr, left=0.0)
+            primitive[0] = np.interp(r, rc, sigma, left=sigma[0])
+            primitive[1] = vp * phi_hat_x + vr * phi_hat_y
+            primitive[2] = vp * phi_hat_y - vr * phi_hat_x
+
+        elif self.is_isothermal:
+            primitive[0] = self.sigma_profile(r_softened)
+            primitive[1] = vp * phi_hat_x
+            primitive[2] = vp * phi_hat_y
+
+        elif self.is_gamma_law:
+            primitive[0] = self.sigma_profile(r_softened)
+            primitive[1] = vp * phi_hat_x
+            primitive[2] = vp * phi_hat_y
+            primitive[3] = self.pressure_profile(r_softened)
+
+        if self.is_isothermal and self.tracer_radius > 0.0:
+            primitive[3] = float(r < self.tracer_radius)
+
+    def sigma_profile(self, r_softened):
+        """
+        Return the initial surface density at the given softened radius.
+        """
+        if self.is_isothermal and self.pre_relax_orbits > 0.0:
+            import numpy as np
+
+            rc, sigma, _ = self.relaxed_disk()
+            eps = self.softening_length
+            r = sqrt(max(r_softened * r_softened - eps * eps, 0.0))
+            return float(np.interp(r, rc, sigma, left=sigma[0]))
+
+        elif self.is_isothermal:
+            return self.initial_sigma
 
         elif self.is_gamma_law:
             # See eq. (A2) from Goodman (2003)
-            primitive[0] = (
+            return (
                 self.initial_sigma
                 * r_softened ** (-3.0 / 5.0)
                 * (0.0001 + 0.9999 * exp(-((1.0 / r_softened) ** 30)))
             )
-            primitive[1] = sqrt(GM / r_softened) * phi_hat_x
-            primitive[2] = sqrt(GM / r_softened) * phi_hat_y
-            primitive[3] = (
+
+    def pressure_profile(self, r_softened):
+        """
+        Return the initial vertically integrated pressure at the given
+        softened radius.
+        """
+        if self.is_isothermal and self.sound_speed_profile:
+            cs = self.sound_speed().circular_speed(r_softened)
+            return self.sigma_profile(r_softened) * cs * cs
+
+        elif self.is_isothermal:
+            GM = 1.0
+            cs2 = GM / r_softened / self.mach_number**2
+            return self.sigma_profile(r_softened) * cs2
+
+        elif self.is_gamma_law:
+            return (
                 self.initial_pressure
                 * r_softened ** (-3.0 / 2.0)
                 * (0.0001 + 0.9999 * exp(-((1.0 / r_softened) ** 30)))
             )
 
+    @lru_cache(maxsize=None)
+    def rotation(self):
+        """
+        Return the `RotationCurve` named by the rotation_curve parameter.
+        """
+        return RotationCurve.from_str(self.rotation_curve)
+
+    @lru_cache(maxsize=None)
+    def sound_speed(self):
+        """
+        Return the sound speed profile, as a `RotationCurve` whose circular
+        speed is the sound speed, or `None` if the Mach number sets it.
+        """
+        if not self.sound_speed_profile:
+            return None
+        return RotationCurve.from_str(self.sound_speed_profile)
+
+    def azimuthal_speed(self, r_softened):
+        """
+        Return the initial azimuthal speed at the given softened radius,
+        corrected for the pressure gradient if `pressure_supported` is true.
+        """
+        if self.pressure_supported:
+            return self.rotation().azimuthal_speed(
+                r_softened, self.sigma_profile, self.pressure_profile
+            )
+        else:
+            return self.rotation().circular_speed(r_softened)
+
+    @lru_cache(maxsize=None)
+    def relaxed_disk(self):
+        """
+        Return the zone radii, surface density, and radial velocity of an
+        axisymmetric disk, evolved from the uniform initial surface density
+        for `pre_relax_orbits` binary orbits with the 1D viscous disk solver.
+
+        The binary's tidal torque is modeled with the Armitage & Natarajan
+        (2002) prescription, with coefficient `pre_relax_torque`, which opens
+        a cavity of roughly the right size. The 1D disk covers the whole
+        domain, from half the binary separation to its corners; inside that,
+        the surface density is that of the innermost zone.
+        """
+        import numpy as np
+        from sailfish.physics.viscous_disk import ViscousDisk1D
+
+        disk = ViscousDisk1D(
+            viscosity=lambda r: self.nu,
+            inner_radius=0.5,
+            outer_radius=1.5 * self.domain_radius,
+            num_zones=1000,
+            mass_ratio=self.mass_ratio,
+            torque_coefficient=self.pre_relax_torque,
+            aspect_ratio=1.0 / self.mach_number,
+        )
+        rc, _ = disk.radii()
+        sigma = self.initial_sigma * np.ones_like(rc)
+        sigma = disk.evolve(sigma, 2.0 * pi * self.pre_relax_orbits)
+        sigma = np.maximum(sigma, 1e-4 * self.initial_sigma)
+        return rc, sigma, disk.radial_velocity(sigma)
+
     def mesh(self, resolution):
         return PlanarCartesian2DMesh.centered_square(self.domain_radius, resolution)
 
+    def active_zones(self, mesh):
+        if self.mask_radius == 0.0 and self.reservoir_radius == 0.0:
+            return None
+
+        from sailfish.spacetime import mesh_radii
+
+        r, _, _ = mesh_radii(mesh)
+        active = r >= self.reservoir_radius
+
+        if self.mask_radius > 0.0:
+            active &= r < self.mask_radius
+
+        return active
+
+    @property
+    def refinement(self):
+        if self.refinement_levels == 0:
+            return None
+
+        from sailfish.refinement import RefinedRegion
+
+        regions = list()
+
+        for level in range(1, self.refinement_levels + 1):
+            r = self.refinement_radius * 2 ** (self.refinement_levels - level)
+            regions.append(RefinedRegion((-r, -r), (r, r), level))
+
+        return regions
+
+    @property
+    def reservoir(self):
+        if self.reservoir_radius == 0.0:
+            return None
+
+        from sailfish.physics.reservoir import Reservoir
+
+        r = self.reservoir_radius
+        edge = [0.0] * 4
+        self.primitive(0.0, (r, 0.0), edge)
+        mass = pi * r * r * edge[0]
+
+        return Reservoir(
+            radius=r,
+            viscous_time=2.0 * pi * self.reservoir_viscous_time,
+            accreted_specific_angular_momentum=self.reservoir_accreted_l,
+            mass=mass,
+            angular_momentum=mass * r * edge[2],
+        )
+
     @property
     def default_resolution(self):
         return 512
@@ -118,20 +539,29 @@ class CircumbinaryDisk(SetupBase):
     def physics(self):
         if self.is_isothermal:
             return dict(
-                eos_type=EquationOfState.LOCALLY_ISOTHERMAL,
+                eos_type=self.isothermal_eos_type,
                 mach_number=self.mach_number,
+                sound_speed_profile=self.sound_speed_function,
                 point_mass_function=self.point_masses,
                 buffer_is_enabled=self.buffer_is_enabled,
                 buffer_driving_rate=100.0,
                 buffer_onset_width=1.0,
+                inner_buffer_radius=self.inner_buffer_radius,
                 cooling_coefficient=0.0,
                 constant_softening=self.constant_softening,
-                viscosity_model=ViscosityModel.CONSTANT_NU
-                if self.nu > 0.0
-                else ViscosityModel.NONE,
+                softening_zones=self.softening_zones or None,
+                viscosity_model=self.isothermal_viscosity_model,
                 viscosity_coefficient=self.nu,
-                alpha=0.0,
+                alpha=self.isothermal_alpha,
                 diagnostics=self.diagnostics,
+                live_point_masses=self.live_binary,
+                num_tracers=int(self.tracer_radius > 0.0),
+                reservoir=self.reservoir,
+                custom_source=self.custom_source or None,
+                custom_source_parameters=self.custom_source_parameter_list,
+                self_gravity_constant=self.self_gravity,
+                self_gravity_softening=self.self_gravity_softening,
+                **self.layer_coupling,
             )
 
         elif self.is_gamma_law:
@@ -143,18 +573,28 @@ class CircumbinaryDisk(SetupBase):
                 buffer_driving_rate=1000.0,  # default value in circumbinary.py
                 buffer_onset_width=0.1,  # default value in circumbinary.py
                 cooling_coefficient=self.cooling_coefficient,
+                cooling_model=CoolingModel.from_str(self.cooling_model),
+                cooling_beta=self.cooling_beta,
+                cooling_time=2.0 * pi * self.cooling_time,
+                mach_number=self.mach_number,
                 constant_softening=self.constant_softening,
+                softening_zones=self.softening_zones or None,
                 viscosity_model=ViscosityModel.CONSTANT_ALPHA
                 if self.alpha > 0.0
                 else ViscosityModel.NONE,
                 viscosity_coefficient=0.0,
                 alpha=self.alpha,
                 diagnostics=self.diagnostics,
+                live_point_masses=self.live_binary,
             )
 
     @property
     def diagnostics(self):
-        if self.which_diagnostics != "none":
+        if self.which_diagnostics == "accretion":
+            return accretion_diagnostics()
+        elif self.which_diagnostics == "torques":
+            return accretion_diagnostics() + torque_decomposition_diagnostics()
+        elif self.which_diagnostics != "none":
             return [
                 dict(quantity="time"),
                 dict(quantity="mdot", which_mass=1, accretion=True),
@@ -163,16 +603,31 @@ class CircumbinaryDisk(SetupBase):
         else:
             return []
 
+    @property
+    def layer_coupling(self):
+        """
+        The physics parameters of the two-layer model, if it's enabled.
+        """
+        if not self.two_layer:
+            return dict()
+        return dict(
+            layer_drag_rate=self.layer_drag_rate,
+            layer_exchange_rate=self.layer_exchange_rate,
+            layer_inclination=self.layer_inclination,
+        )
+
     @property
     def solver(self):
-        if self.is_isothermal:
+        if self.is_isothermal and self.two_layer:
+            return "cbdiso2l_2d"
+        elif self.is_isothermal:
             return "cbdiso_2d"
         elif self.is_gamma_law:
             return "cbdgam_2d"
 
     @property
     def boundary_condition(self):
-        return "outflow"
+        return self.outer_boundary
 
     @property
     def default_end_time(self):
@@ -191,20 +646,38 @@ class CircumbinaryDisk(SetupBase):
             eccentricity=self.eccentricity,
         )
 
+    @property
+    def orbit(self):
+        """
+        The binary's orbit, which evolves from the `orbital_elements` at the
+        given rates per initial orbit.
+        """
+        period = self.orbital_elements.period
+        return EvolvingOrbit(
+            self.orbital_elements,
+            semimajor_axis_rate=self.semimajor_axis_rate / period,
+            eccentricity_rate=self.eccentricity_rate / period,
+        )
+
     def point_masses(self, time):
-        m1, m2 = self.orbital_elements.orbital_state(time)
+        if self.is_evolving_orbit:
+            m1, m2 = self.orbit.orbital_state(time)
+        else:
+            m1, m2 = self.orbital_elements.orbital_state(time)
 
         return (
             PointMass(
                 softening_length=self.softening_length,
-                sink_model=SinkModel[self.sink_model.upper()],
+                softening_model=SofteningModel.from_str(self.softening_model),
+                sink_model=SinkModel.from_str(self.sink_model),
                 sink_rate=self.sink_rate,
                 sink_radius=self.sink_radius,
                 **m1._asdict(),
             ),
             PointMass(
                 softening_length=self.softening_length,
-                sink_model=SinkModel[self.sink_model.upper()],
+                softening_model=SofteningModel.from_str(self.softening_model),
+                sink_model=SinkModel.from_str(self.sink_model),
                 sink_rate=self.sink_rate,
                 sink_radius=self.sink_radius,
                 **m2._asdict(),
@@ -225,7 +698,7 @@ class KitpCodeComparison(SetupBase):
     single_point_mass = param(False, "put one point mass at the origin (no binary)")
     sink_model = param(
         "acceleration_free",
-        "sink [acceleration_free|force_free|torque_free]",
+        "sink [inactive|acceleration_free|force_free|torque_free]",
         mutable=True,
     )
     domain_radius = param(8.0, "half side length of the square computational domain")
@@ -238,6 +711,10 @@ class KitpCodeComparison(SetupBase):
     def validate(self):
         if self.which_diagnostics not in ["kitp", "forces"]:
             raise SetupError("Unknown option for diagnostics.")
+        try:
+            SinkModel.from_str(self.sink_model)
+        except ValueError as e:
+            raise SetupError(e)
 
     def primitive(self, t, coords, primitive):
         x, y = coords
@@ -346,7 +823,7 @@ class KitpCodeComparison(SetupBase):
         if self.single_point_mass:
             return PointMass(
                 softening_length=self.softening_length,
-                sink_model=SinkModel[self.sink_model.upper()],
+                sink_model=SinkModel.from_str(self.sink_model),
                 sink_rate=self.sink_rate,
                 sink_radius=self.sink_radius,
                 mass=1.0,
@@ -357,14 +834,14 @@ class KitpCodeComparison(SetupBase):
             return (
                 PointMass(
                     softening_length=self.softening_length,
-                    sink_model=SinkModel[self.sink_model.upper()],
+                    sink_model=SinkModel.from_str(self.sink_model),
                     sink_rate=self.sink_rate,
                     sink_radius=self.sink_radius,
                     **m1._asdict(),
                 ),
                 PointMass(
                     softening_length=self.softening_length,
-                    sink_model=SinkModel[self.sink_model.upper()],
+                    sink_model=SinkModel.from_str(self.sink_model),
                     sink_rate=self.sink_rate,
                     sink_radius=self.sink_radius,
                     **m2._asdict(),
@@ -389,10 +866,10 @@ class MassTransferBinary(SetupBase):
     buffer_onset_width = param(0.25, "buffer ramp distance", mutable=True)
     sink_model = param(
         "acceleration_free",
-        "sink [acceleration_free|force_free|torque_free]",
+        "sink [inactive|acceleration_free|force_free|torque_free]",
         mutable=True,
     )
-    which_diagnostics = param("torques", "[torques|forces]")
+    which_diagnostics = param("torques", "[torques|forces|roche]")
 
     def validate(self):
         for x in self.sink_rate + self.sink_radius + self.softening_length:
@@ -400,8 +877,12 @@ class MassTransferBinary(SetupBase):
                 raise ValueError(
                     "sink_rate, sink_radius, and softening_length parameters must be float"
                 )
-        if self.which_diagnostics not in ["torques", "forces"]:
+        if self.which_diagnostics not in ["torques", "forces", "roche"]:
             raise SetupError("Unknown option for diagnostics.")
+        try:
+            SinkModel.from_str(self.sink_model)
+        except ValueError as e:
+            raise SetupError(e)
 
     def primitive(self, t, coords, primitive):
         x, y = coords
@@ -451,6 +932,16 @@ class MassTransferBinary(SetupBase):
                 dict(quantity="fy", which_mass=2, gravity=True),
                 dict(quantity="fy", which_mass=2, accretion=True),
             ]
+        elif self.which_diagnostics == "roche":
+            return [
+                dict(quantity="time"),
+                dict(quantity="mdot", which_mass=1, accretion=True),
+                dict(quantity="mdot", which_mass=2, accretion=True),
+                dict(quantity="roche_mass", which_mass=1),
+                dict(quantity="roche_mass", which_mass=2),
+                dict(quantity="roche_mdot"),
+                dict(quantity="mass"),
+            ]
 
     @property
     def physics(self):
@@ -499,14 +990,14 @@ class MassTransferBinary(SetupBase):
         return (
             PointMass(
                 softening_length=self.softening_length[0],
-                sink_model=SinkModel[self.sink_model.upper()],
+                sink_model=SinkModel.from_str(self.sink_model),
                 sink_rate=self.sink_rate[0],
                 sink_radius=self.sink_radius[0],
                 **m1._asdict(),
             ),
             PointMass(
                 softening_length=self.softening_length[1],
-                sink_model=SinkModel[self.sink_model.upper()],
+                sink_model=SinkModel.from_str(self.sink_model),
                 sink_rate=self.sink_rate[1],
                 sink_radius=self.sink_radius[1],
                 **m2._asdict(),
@@ -531,10 +1022,16 @@ class EccentricSingleDisk(SetupBase):
     buffer_onset_width = param(0.25, "buffer ramp distance", mutable=True)
     sink_model = param(
         "acceleration_free",
-        "sink [acceleration_free|force_free|torque_free]",
+        "sink [inactive|acceleration_free|force_free|torque_free]",
         mutable=True,
     )
 
+    def validate(self):
+        try:
+            SinkModel.from_str(self.sink_model)
+        except ValueError as e:
+            raise SetupError(e)
+
     def primitive(self, t, coords, primitive):
         x, y = coords
         r = sqrt(x * x + y * y)
@@ -597,7 +1094,114 @@ class EccentricSingleDisk(SetupBase):
     def point_masses(self, time):
         return PointMass(
             softening_length=self.softening_length,
-            sink_model=SinkModel[self.sink_model.upper()],
+            sink_model=SinkModel.from_str(self.sink_model),
+            sink_rate=self.sink_rate,
+            sink_radius=self.sink_radius,
+            mass=1.0,
+        )
+
+
+class UniformTorus(SetupBase):
+    """
+    A torus of uniform surface density in orbit around a single point mass.
+
+    The surface density is one between `inner_radius` and `outer_radius`, and
+    the background `sigma` elsewhere. The velocity is Keplerian everywhere,
+    and the gas is locally isothermal, so the edges of the torus spread under
+    pressure, and under the viscosity `nu`, while its bulk stays in orbit.
+    It checks the transport of angular momentum in a disk, and the sink of
+    the point mass, apart from the torques of a binary.
+
+    With `coordinates=log_polar`, the torus is run with the `cbdisolp_2d`
+    solver on a log-polar mesh, from `mesh_inner_radius` to `domain_radius`,
+    with `resolution` azimuthal zones. The gas inside the inner radius, and
+    so the sink, is then off the mesh, but the inside of the torus is
+    resolved more finely for the same number of zones.
+    """
+
+    inner_radius = param(1.0, "inner radius of the torus")
+    outer_radius = param(2.0, "outer radius of the torus")
+    domain_radius = param(4.0, "half side length of the square computational domain")
+    coordinates = param("cartesian", "mesh coordinates [cartesian|log_polar]")
+    mesh_inner_radius = param(0.25, "inner radius of a log-polar mesh")
+    sigma = param(1e-4, "background surface density")
+    mach_number = param(20.0, "orbital Mach number", mutable=True)
+    nu = param(0.0, "kinematic viscosity parameter", mutable=True)
+    sink_rate = param(10.0, "point mass sink rate", mutable=True)
+    sink_radius = param(0.05, "point mass sink radius", mutable=True)
+    softening_length = param(0.05, "point mass softening length", mutable=True)
+
+    def validate(self):
+        if not 0.0 < self.inner_radius < self.outer_radius < self.domain_radius:
+            raise SetupError(
+                "radii must satisfy 0 < inner_radius < outer_radius < domain_radius"
+            )
+        if self.sigma <= 0.0:
+            raise SetupError("sigma must be positive")
+        if self.coordinates not in ("cartesian", "log_polar"):
+            raise SetupError("coordinates must be cartesian or log_polar")
+        if self.coordinates == "log_polar":
+            if not 0.0 < self.mesh_inner_radius < self.inner_radius:
+                raise SetupError("mesh_inner_radius must be in (0, inner_radius)")
+            if self.nu != 0.0:
+                raise SetupError("the log-polar solver has no viscosity, nu must be 0")
+
+    def primitive(self, t, coords, primitive):
+        x, y = coords
+        r = sqrt(x * x + y * y)
+        omega = 1.0 / sqrt(r**3 + self.softening_length**3)
+
+        if self.inner_radius <= r <= self.outer_radius:
+            primitive[0] = 1.0
+        else:
+            primitive[0] = self.sigma
+
+        primitive[1] = omega * -y
+        primitive[2] = omega * +x
+
+    def mesh(self, resolution):
+        if self.coordinates == "log_polar":
+            r0, r1 = self.mesh_inner_radius, self.domain_radius
+            return LogPolar2DMesh(r0, r1, resolution)
+        return PlanarCartesian2DMesh.centered_square(self.domain_radius, resolution)
+
+    @property
+    def default_resolution(self):
+        return 256
+
+    @property
+    def physics(self):
+        return dict(
+            eos_type=EquationOfState.LOCALLY_ISOTHERMAL,
+            mach_number=self.mach_number,
+            point_mass_function=self.point_masses,
+            viscosity_coefficient=self.nu,
+            viscosity_model=ViscosityModel.CONSTANT_NU
+            if self.nu > 0.0
+            else ViscosityModel.NONE,
+            diagnostics=self.diagnostics,
+        )
+
+    @property
+    def solver(self):
+        return "cbdisolp_2d" if self.coordinates == "log_polar" else "cbdiso_2d"
+
+    @property
+    def boundary_condition(self):
+        return "outflow"
+
+    @property
+    def default_end_time(self):
+        return 10.0
+
+    @property
+    def reference_time_scale(self):
+        return 2.0 * pi
+
+    def point_masses(self, time):
+        return PointMass(
+            softening_length=self.softening_length,
+            sink_model=SinkModel.ACCELERATION_FREE,
             sink_rate=self.sink_rate,
             sink_radius=self.sink_radius,
             mass=1.0,
@@ -0,0 +1,184 @@
+"""
+Fluid instability setups, with diagnostics to measure linear growth rates.
+
+Each setup records the `vy_mode` diagnostic, the complex amplitude of the
+seeded Fourier mode of the y-velocity in the upper half of the domain. In
+the linear phase its magnitude grows as `exp(sigma * t)`, and the measured
+`sigma` can be compared with the `linear_growth_rate` property of the setup.
+"""
+
+from math import pi, sin, cos, exp, sqrt, tanh
+from sailfish.mesh import PlanarCartesian2DMesh
+from sailfish.physics.circumbinary import EquationOfState
+from sailfish.setup_base import SetupBase, SetupError, param
+
+__all__ = ["KelvinHelmholtz", "RayleighTaylor"]
+
+
+class KelvinHelmholtz(SetupBase):
+    """
+    A periodic double shear layer, seeded with a single mode in `vy`.
+
+    The domain is the unit square, periodic in both directions. The fluid in
+    the central band `|y| < 1/4` moves with velocity `+u/2` in the x-direction,
+    and the rest with `-u/2`. The bands have densities `rho_inner` and
+    `rho_outer`, and the shear layers have a tanh profile of width `a`. The
+    perturbation `vy = A sin(2 pi m x)` is localized around each layer.
+
+    The growth rate of a vortex sheet in incompressible flow is
+    `k u sqrt(rho1 rho2) / (rho1 + rho2)`. It's approached when the layer is
+    thin compared with the wavelength, and the flow is subsonic; the default
+    pressure gives a Mach number of about 0.2.
+    """
+
+    shear_velocity = param(1.0, "velocity difference u across each shear layer")
+    rho_inner = param(2.0, "density of the central band")
+    rho_outer = param(1.0, "density of the outer bands")
+    pressure = param(10.0, "uniform initial pressure")
+    layer_width = param(0.01, "width a of the tanh shear layer profile")
+    amplitude = param(0.01, "amplitude A of the vy perturbation")
+    mode_number = param(2, "number m of perturbation wavelengths in the domain")
+
+    def primitive(self, t, coords, primitive):
+        x, y = coords
+        a = self.layer_width
+        u = self.shear_velocity
+        f = 0.5 * (tanh((y + 0.25) / a) - tanh((y - 0.25) / a))
+        g = exp(-(((y + 0.25) / 0.05) ** 2)) + exp(-(((y - 0.25) / 0.05) ** 2))
+
+        primitive[0] = self.rho_outer + (self.rho_inner - self.rho_outer) * f
+        primitive[1] = u * (f - 0.5)
+        primitive[2] = self.amplitude * sin(2.0 * pi * self.mode_number * x) * g
+        primitive[3] = self.pressure
+
+    def mesh(self, resolution):
+        return PlanarCartesian2DMesh.centered_square(0.5, resolution)
+
+    @property
+    def linear_growth_rate(self):
+        k = 2.0 * pi * self.mode_number
+        r1 = self.rho_inner
+        r2 = self.rho_outer
+        return k * self.shear_velocity * sqrt(r1 * r2) / (r1 + r2)
+
+    @property
+    def physics(self):
+        return dict(
+            eos_type=EquationOfState.GAMMA_LAW,
+            gamma_law_index=5 / 3,
+            diagnostics=self.diagnostics,
+        )
+
+    @property
+    def diagnostics(self):
+        return [
+            dict(quantity="time"),
+            dict(quantity="vy_mode", mode_number=self.mode_number),
+        ]
+
+    @property
+    def solver(self):
+        return "cbdgam_2d"
+
+    @property
+    def boundary_condition(self):
+        return "periodic"
+
+    @property
+    def default_resolution(self):
+        return 256
+
+    @property
+    def default_end_time(self):
+        return 2.0
+
+    def validate(self):
+        if self.layer_width <= 0.0:
+            raise SetupError("layer_width must be positive")
+        if type(self.mode_number) is not int or self.mode_number < 1:
+            raise SetupError("mode_number must be a positive integer")
+
+
+class RayleighTaylor(SetupBase):
+    """
+    A heavy fluid on top of a light one, in a uniform gravitational field.
+
+    The domain is `[-1/4, 1/4] x [-3/4, 3/4]`, periodic in x, with reflecting
+    walls at the top and bottom. The fluids have densities `rho_heavy` and
+    `rho_light`, meet at `y = 0`, and are in hydrostatic equilibrium under
+    the acceleration `g` in the -y direction. The interface is perturbed by
+    `vy = A cos(k x) exp(-(y / 0.1)^2)`, with one wavelength across the domain.
+
+    The growth rate is `sqrt(At g k)`, where `At = (rho_heavy - rho_light) /
+    (rho_heavy + rho_light)` is the Atwood number.
+    """
+
+    rho_heavy = param(2.0, "density of the upper fluid")
+    rho_light = param(1.0, "density of the lower fluid")
+    gravity = param(0.1, "gravitational acceleration g in the -y direction")
+    pressure = param(2.5, "pressure at the interface")
+    amplitude = param(0.01, "amplitude A of the vy perturbation")
+
+    def primitive(self, t, coords, primitive):
+        x, y = coords
+        k = 2.0 * pi / 0.5
+        rho = self.rho_heavy if y > 0.0 else self.rho_light
+
+        primitive[0] = rho
+        primitive[1] = 0.0
+        primitive[2] = self.amplitude * cos(k * x) * exp(-((y / 0.1) ** 2))
+        primitive[3] = self.pressure - rho * self.gravity * y
+
+    def mesh(self, resolution):
+        ni = resolution
+        nj = resolution * 3
+        return PlanarCartesian2DMesh(-0.25, -0.75, 0.25, 0.75, ni, nj)
+
+    @property
+    def atwood_number(self):
+        rh = self.rho_heavy
+        rl = self.rho_light
+        return (rh - rl) / (rh + rl)
+
+    @property
+    def linear_growth_rate(self):
+        k = 2.0 * pi / 0.5
+        return sqrt(self.atwood_number * self.gravity * k)
+
+    @property
+    def physics(self):
+        return dict(
+            eos_type=EquationOfState.GAMMA_LAW,
+            gamma_law_index=1.4,
+            external_gravity=self.gravity,
+            diagnostics=self.diagnostics,
+        )
+
+    @property
+    def diagnostics(self):
+        return [
+            dict(quantity="time"),
+            dict(quantity="vy_mode", mode_number=1),
+        ]
+
+    @property
+    def solver(self):
+        return "cbdgam_2d"
+
+    @property
+    def boundary_condition(self):
+        return "periodic", "reflecting"
+
+    @property
+    def default_resolution(self):
+        return 64
+
+    @property
+    def default_end_time(self):
+        return 12.0
+
+    def validate(self):
+        if self.rho_heavy <= self.rho_light:
+            raise SetupError("rho_heavy must exceed rho_light")
+        if self.pressure - self.rho_heavy * self.gravity * 0.75 <= 0.0:
+            raise SetupError("pressure is too small to support the heavy fluid")
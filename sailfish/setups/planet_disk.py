@@ -0,0 +1,194 @@
+"""
+Setups for planet-disk interaction problems.
+"""
+
+from math import sqrt, exp, pi, sin, cos
+from sailfish.mesh import PlanarCartesian2DMesh
+from sailfish.physics.circumbinary import (
+    EquationOfState,
+    PointMass,
+    SinkModel,
+    ViscosityModel,
+)
+from sailfish.physics.kepler import OrbitalElements
+from sailfish.setup_base import SetupBase, SetupError, param
+
+__all__ = ["DeValBorro"]
+
+
+class DeValBorro(SetupBase):
+    """
+    The disk-planet code comparison problem of `de Val-Borro et al. (2006)`_.
+
+    A planet of mass ratio `q` (1e-3 for the Jupiter case, and 1e-4 for the
+    Neptune case) is held on a fixed circular orbit of radius 1 in a disk of
+    uniform surface density `0.002 / pi`, with aspect ratio `h = 0.05` and
+    kinematic viscosity `nu = 1e-5`. The planet potential is softened over
+    `0.6 h` and the planet does not accrete.
+
+    The benchmark was specified on a polar mesh covering `0.4 < r < 2.5`,
+    with wave-damping zones at `r < 0.5` and `r > 2.1`. The binary solvers
+    use a square Cartesian mesh, so here the outer damping zone is the solver
+    buffer, which starts at `r = 2.1`, and the inner disk edge at `r = 0.4`
+    is modeled by a cavity with a sink on the star. Results inside the inner
+    damping zone should not be compared with the published ones.
+
+    The diagnostics include the gravitational torque on the planet, once from
+    the whole disk, and once with the benchmark's torque cutoff applied, i.e.
+    excluding the disk material within `torque_hill_cut` Hill radii of the
+    planet. With `num_probes > 0`, the torque is also recorded on that many
+    probe masses, on circular orbits at a range of radii, which don't act on
+    the disk.
+
+    .. _de Val-Borro et al. (2006): https://ui.adsabs.harvard.edu/abs/2006MNRAS.370..529D
+    """
+
+    mass_ratio = param(1e-3, "planet-to-star mass ratio q")
+    aspect_ratio = param(0.05, "disk aspect ratio h = H / r")
+    nu = param(1e-5, "kinematic viscosity coefficient")
+    inner_radius = param(0.4, "radius of the inner disk edge")
+    torque_hill_cut = param(1.0, "Hill radii around the planet excluded from torque")
+    star_sink_rate = param(10.0, "sink rate of the star, to absorb the inner cavity")
+    num_probes = param(0, "number of probe masses on orbits between r=0.6 and 2")
+
+    def validate(self):
+        if not 0.0 < self.mass_ratio < 0.1:
+            raise SetupError("mass_ratio must be between 0 and 0.1")
+        if self.aspect_ratio <= 0.0:
+            raise SetupError("aspect_ratio must be positive")
+
+    @property
+    def star_softening_length(self):
+        return 0.25 * self.inner_radius
+
+    @property
+    def initial_sigma(self):
+        return 0.002 / pi
+
+    def primitive(self, t, coords, primitive):
+        GM = 1.0
+        x, y = coords
+        r = sqrt(x * x + y * y)
+        r_softened = sqrt(r * r + self.star_softening_length**2)
+        h = self.aspect_ratio
+        phi_hat_x = -y / max(r, 1e-12)
+        phi_hat_y = +x / max(r, 1e-12)
+
+        # With uniform density and the locally isothermal sound speed h v_k,
+        # the pressure gradient reduces the orbital velocity by sqrt(1 - h^2).
+        vp = sqrt(GM / r_softened * (1.0 - h * h))
+        r_inner = self.inner_radius
+        cavity = 1e-4 + (1.0 - 1e-4) * exp(-((r_inner / r_softened) ** 30))
+
+        primitive[0] = self.initial_sigma * cavity
+        primitive[1] = vp * phi_hat_x
+        primitive[2] = vp * phi_hat_y
+
+    def mesh(self, resolution):
+        return PlanarCartesian2DMesh.centered_square(2.5, resolution)
+
+    @property
+    def default_resolution(self):
+        return 800
+
+    @property
+    def physics(self):
+        return dict(
+            eos_type=EquationOfState.LOCALLY_ISOTHERMAL,
+            mach_number=1.0 / self.aspect_ratio,
+            point_mass_function=self.point_masses,
+            probe_mass_function=self.probe_masses,
+            buffer_is_enabled=True,
+            buffer_driving_rate=100.0,
+            buffer_onset_width=0.4,
+            viscosity_model=ViscosityModel.CONSTANT_NU,
+            viscosity_coefficient=self.nu,
+            diagnostics=self.diagnostics,
+        )
+
+    @property
+    def diagnostics(self):
+        return [
+            dict(quantity="time"),
+            dict(quantity="torque", which_mass=2, gravity=True),
+            dict(
+                quantity="torque",
+                which_mass=2,
+                gravity=True,
+                hill_cut=self.torque_hill_cut,
+            ),
+            dict(quantity="mdot", which_mass=1, accretion=True),
+        ] + [dict(quantity="torque", which_probe=n) for n in range(self.num_probes)]
+
+    @property
+    def solver(self):
+        return "cbdiso_2d"
+
+    @property
+    def boundary_condition(self):
+        return "outflow"
+
+    @property
+    def default_end_time(self):
+        return 100.0
+
+    @property
+    def reference_time_scale(self):
+        return 2.0 * pi
+
+    @property
+    def orbital_elements(self):
+        return OrbitalElements(
+            semimajor_axis=1.0,
+            total_mass=1.0 + self.mass_ratio,
+            mass_ratio=self.mass_ratio,
+            eccentricity=0.0,
+        )
+
+    def point_masses(self, time):
+        m1, m2 = self.orbital_elements.orbital_state(time)
+
+        return (
+            PointMass(
+                softening_length=self.star_softening_length,
+                sink_model=SinkModel.TORQUE_FREE,
+                sink_rate=self.star_sink_rate,
+                sink_radius=self.star_softening_length,
+                **m1._asdict(),
+            ),
+            PointMass(
+                softening_length=0.6 * self.aspect_ratio,
+                sink_model=SinkModel.INACTIVE,
+                **m2._asdict(),
+            ),
+        )
+
+    @property
+    def probe_radii(self):
+        n = self.num_probes
+        return [0.6 + 1.4 * i / max(n - 1, 1) for i in range(n)]
+
+    def probe_masses(self, time):
+        """
+        Return probe masses with the planet's mass and softening, on circular
+        orbits about the star, to map the disk torque as a function of radius
+        in one run. They are placed in the planet's orbital phase at time 0.
+        """
+        probes = []
+
+        for r in self.probe_radii:
+            omega = sqrt(1.0 / r**3)
+            probes.append(
+                PointMass(
+                    mass=self.mass_ratio,
+                    position_x=-r * cos(omega * time),
+                    position_y=-r * sin(omega * time),
+                    velocity_x=+r * omega * sin(omega * time),
+                    velocity_y=-r * omega * cos(omega * time),
+                    softening_length=0.6 * self.aspect_ratio * r,
+                )
+            )
+        return probes
+
+    def checkpoint_diagnostics(self, time):
+        return dict(point_masses=self.point_masses(time))
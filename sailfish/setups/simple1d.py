@@ -6,7 +6,14 @@ from math import pi, sin
 from sailfish.setup_base import SetupBase, SetupError, param
 from sailfish.mesh import PlanarCartesianMesh, LogSphericalMesh
 
-__all__ = ["Advection", "Burgers", "DensityWave", "Shocktube", "Wind"]
+__all__ = [
+    "Advection",
+    "Burgers",
+    "DensityWave",
+    "NestedShocktube",
+    "Shocktube",
+    "Wind",
+]
 
 
 class Advection(SetupBase):
@@ -73,8 +80,13 @@ class Shocktube(SetupBase):
     """
     Discontinuous initial data, with uniform density and pressure to either
     side of the discontintuity at x=0.5.
+
+    The gas is relativistic, or with `newtonian=true`, it's evolved with the
+    `euler_1d` solver, and has adiabatic index 1.4.
     """
 
+    newtonian = param(False, "use the Newtonian euler_1d solver")
+
     def primitive(self, t, x, primitive):
         if x < 0.5:
             primitive[0] = 1.0
@@ -88,7 +100,11 @@ class Shocktube(SetupBase):
 
     @property
     def solver(self):
-        return "srhd_1d"
+        return "euler_1d" if self.newtonian else "srhd_1d"
+
+    @property
+    def physics(self):
+        return dict(gamma_law_index=1.4) if self.newtonian else dict()
 
     @property
     def boundary_condition(self):
@@ -99,6 +115,62 @@ class Shocktube(SetupBase):
         return 0.25
 
 
+class NestedShocktube(SetupBase):
+    """
+    The shocktube of the `Shocktube` setup, with the Newtonian solver, in a
+    closed tube, where the gas beyond `interface` is isothermal.
+
+    The inner region is adiabatic with index 1.4, and the outer one has the
+    sound speed of the initial right state, so the shock crosses from one
+    equation of state into the other. With `outer_eos=gamma-law` both
+    regions are adiabatic, and the solution is that of one solver. See
+    `sailfish.coupling`.
+    """
+
+    interface = param(0.75, "position of the interface between the regions")
+    outer_eos = param("isothermal", "equation of state beyond the interface")
+
+    def primitive(self, t, x, primitive):
+        Shocktube.primitive(self, t, x, primitive)
+
+    def mesh(self, num_zones):
+        return PlanarCartesianMesh(0.0, 1.0, num_zones)
+
+    @property
+    def solver(self):
+        return "euler_1d"
+
+    @property
+    def regions(self):
+        from sailfish.coupling import Region
+
+        if self.outer_eos == "isothermal":
+            outer = dict(isothermal_sound_speed=(0.125 / 0.1) ** 0.5)
+        else:
+            outer = dict()
+
+        return [Region(self.interface), Region(physics=outer)]
+
+    @property
+    def physics(self):
+        return dict(gamma_law_index=1.4)
+
+    @property
+    def boundary_condition(self):
+        return "reflect"
+
+    @property
+    def default_end_time(self):
+        return 0.25
+
+    def validate(self):
+        if not 0.5 < self.interface < 1.0:
+            raise SetupError("interface must be between 0.5 and 1.0")
+
+        if self.outer_eos not in ("isothermal", "gamma-law"):
+            raise SetupError("outer_eos must be isothermal or gamma-law")
+
+
 class DensityWave(SetupBase):
     """
     A sinusoidal variation of the gas density, with possible uniform
@@ -108,6 +180,7 @@ class DensityWave(SetupBase):
     wavenumber = param(1, "wavenumber of the sinusoid")
     amplitude = param(0.1, "amplitude of the density variation")
     velocity = param(0.0, "speed of the wave (gamma-beta if relativistic)")
+    newtonian = param(False, "use the Newtonian euler_1d solver")
 
     def primitive(self, t, x, primitive):
         k = self.wavenumber * 2.0 * pi
@@ -123,7 +196,7 @@ class DensityWave(SetupBase):
 
     @property
     def solver(self):
-        return "srhd_1d"
+        return "euler_1d" if self.newtonian else "srhd_1d"
 
     @property
     def boundary_condition(self):
@@ -140,10 +213,11 @@ class DensityWave(SetupBase):
 
 class Wind(SetupBase):
     """
-    A cold, spherically symmetric relativistic wind.
+    A cold, spherically symmetric wind, relativistic unless `newtonian=true`.
     """
 
     velocity = param(1.0, "velocity of the wind (gamma-beta if relativistic)")
+    newtonian = param(False, "use the Newtonian euler_1d solver")
 
     def primitive(self, t, r, primitive):
         primitive[0] = 1.0 / r**2
@@ -155,7 +229,7 @@ class Wind(SetupBase):
 
     @property
     def solver(self):
-        return "srhd_1d"
+        return "euler_1d" if self.newtonian else "srhd_1d"
 
     @property
     def boundary_condition(self):
@@ -5,9 +5,15 @@ Validation setups for various 2D solvers
 from sailfish.mesh import LogSphericalMesh, PlanarCartesian2DMesh
 from sailfish.physics.circumbinary import EquationOfState
 from sailfish.setup_base import SetupBase, param
-from math import exp
+from math import exp, pi
 
-__all__ = ["UniformPolar", "CylindricalExplosion"]
+__all__ = [
+    "UniformPolar",
+    "CylindricalExplosion",
+    "FourBlasts",
+    "SedovBlast",
+    "RiemannQuadrants",
+]
 
 
 class UniformPolar(SetupBase):
@@ -112,3 +118,183 @@ class CylindricalExplosion(SetupBase):
             raise ValueError(f"eos must be isothermal or gamma-law, got {self.eos}")
         if self.use_dg and not self.is_isothermal:
             raise ValueError("DG mode is only available for eos=isothermal")
+
+
+class FourBlasts(SetupBase):
+    """
+    Four identical blasts placed symmetrically about the origin.
+
+    The blasts are centered at `(+-d, +-d)`, so the problem is symmetric under
+    reflections through both axes and under exchange of the axes. Their shocks
+    collide along the axes and the diagonals, which makes any directional bias
+    in the flux kernels apparent. Run it with the `--check-symmetry` driver
+    flag to measure the symmetry error after every iteration.
+    """
+
+    eos = param("isothermal", "EOS type: either isothermal or gamma-law")
+    separation = param(0.5, "distance d of each blast center from the axes")
+
+    def primitive(self, t, coords, primitive):
+        x, y = coords
+        d = self.separation
+        f = 0.0
+
+        for sx, sy in [(-1, -1), (-1, 1), (1, -1), (1, 1)]:
+            r = ((x - sx * d) ** 2 + (y - sy * d) ** 2) ** 0.5
+            f += exp(-((r / 0.125) ** 6.0))
+
+        primitive[0] = 0.1 + 0.9 * f
+
+        if self.eos == "gamma-law":
+            primitive[3] = 0.125 + 0.875 * f
+
+    def mesh(self, resolution):
+        return PlanarCartesian2DMesh.centered_square(1.0, resolution)
+
+    @property
+    def physics(self):
+        if self.eos == "isothermal":
+            return dict(eos_type=EquationOfState.GLOBALLY_ISOTHERMAL, sound_speed=1.0)
+        else:
+            return dict(eos_type=EquationOfState.GAMMA_LAW, gamma_law_index=5 / 3)
+
+    @property
+    def solver(self):
+        return "cbdiso_2d" if self.eos == "isothermal" else "cbdgam_2d"
+
+    @property
+    def boundary_condition(self):
+        return "outflow"
+
+    @property
+    def default_resolution(self):
+        return 200
+
+    @property
+    def default_end_time(self):
+        return 0.5
+
+    def validate(self):
+        if self.eos not in ["isothermal", "gamma-law"]:
+            raise ValueError(f"eos must be isothermal or gamma-law, got {self.eos}")
+
+
+class SedovBlast(SetupBase):
+    """
+    A point explosion in a cold uniform gas, for the `euler_2d` solver.
+
+    The blast energy `E` is deposited as thermal energy in the zones within
+    `blast_radius` of the origin. In planar 2D geometry the blast is a
+    cylindrical one, whose shock radius grows self-similarly as
+    `xi (E t^2 / rho)^(1/4)`, with `xi` close to 1 for gamma = 1.4. This
+    radius is the `shock_radius` method, for comparison with the solution.
+    The strong shock makes this a good test of the `artificial_viscosity`
+    solver option.
+    """
+
+    energy = param(1.0, "blast energy E, per unit length along z")
+    blast_radius = param(0.02, "radius of the region the energy is deposited in")
+    ambient_density = param(1.0, "density rho of the surrounding gas")
+    ambient_pressure = param(1e-5, "pressure of the surrounding gas")
+    gamma_law_index = param(1.4, "adiabatic index")
+
+    def primitive(self, t, coords, primitive):
+        x, y = coords
+        r = (x * x + y * y) ** 0.5
+        area = pi * self.blast_radius**2
+        p_blast = (self.gamma_law_index - 1.0) * self.energy / area
+
+        primitive[0] = self.ambient_density
+        primitive[1] = 0.0
+        primitive[2] = 0.0
+        primitive[3] = p_blast if r < self.blast_radius else self.ambient_pressure
+
+    def mesh(self, resolution):
+        return PlanarCartesian2DMesh.centered_square(0.5, resolution)
+
+    def shock_radius(self, t):
+        return 1.004 * (self.energy * t**2 / self.ambient_density) ** 0.25
+
+    @property
+    def physics(self):
+        return dict(
+            eos_type=EquationOfState.GAMMA_LAW, gamma_law_index=self.gamma_law_index
+        )
+
+    @property
+    def solver(self):
+        return "euler_2d"
+
+    @property
+    def boundary_condition(self):
+        return "outflow"
+
+    @property
+    def default_resolution(self):
+        return 256
+
+    @property
+    def default_end_time(self):
+        return 0.1
+
+    def validate(self):
+        if self.blast_radius <= 0.0:
+            raise ValueError("blast_radius must be positive")
+        if min(self.energy, self.ambient_density, self.ambient_pressure) <= 0.0:
+            raise ValueError("energy, density, and pressure must be positive")
+
+
+class RiemannQuadrants(SetupBase):
+    """
+    A 2D Riemann problem, for the `euler_2d` solver.
+
+    Four constant states in the quadrants around the origin: configuration 3
+    of Lax & Liu (1998), in which four shocks interact to form a mushroom
+    shaped jet along the diagonal. The domain is `[-1/2, 1/2]^2`, and the
+    states meet at `(x0, x0)`; the usual choice is the center, but with `x0 =
+    0.3` the jet has more room to develop, as in Liska & Wendroff (2003),
+    who run it to `t = 0.8`.
+    """
+
+    x0 = param(0.0, "coordinate of the point where the four states meet")
+
+    def primitive(self, t, coords, primitive):
+        x, y = coords
+        right = x > self.x0
+        top = y > self.x0
+
+        if right and top:
+            primitive[:] = [1.5, 0.0, 0.0, 1.5]
+        elif top:
+            primitive[:] = [0.5323, 1.206, 0.0, 0.3]
+        elif right:
+            primitive[:] = [0.5323, 0.0, 1.206, 0.3]
+        else:
+            primitive[:] = [0.138, 1.206, 1.206, 0.029]
+
+    def mesh(self, resolution):
+        return PlanarCartesian2DMesh.centered_square(0.5, resolution)
+
+    @property
+    def physics(self):
+        return dict(eos_type=EquationOfState.GAMMA_LAW, gamma_law_index=1.4)
+
+    @property
+    def solver(self):
+        return "euler_2d"
+
+    @property
+    def boundary_condition(self):
+        return "outflow"
+
+    @property
+    def default_resolution(self):
+        return 400
+
+    @property
+    def default_end_time(self):
+        return 0.3
+
+    def validate(self):
+        if not -0.5 < self.x0 < 0.5:
+            raise ValueError("x0 must be inside the domain")
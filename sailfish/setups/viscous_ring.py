@@ -0,0 +1,193 @@
+"""
+The viscous ring test of Lynden-Bell & Pringle (1974).
+"""
+
+from math import sqrt
+from sailfish.mesh import PlanarCartesian2DMesh
+from sailfish.physics.circumbinary import (
+    EquationOfState,
+    PointMass,
+    SinkModel,
+    ViscosityModel,
+)
+from sailfish.physics.viscous_disk import (
+    ViscousDisk1D,
+    azimuthal_average,
+    viscous_ring_surface_density,
+)
+from sailfish.setup_base import SetupBase, SetupError, param
+
+__all__ = ["ViscousRing"]
+
+
+class ViscousRing(SetupBase):
+    """
+    A ring of gas spreading under a constant kinematic viscosity.
+
+    The ring has unit mass and radius, around a unit central mass, and
+    starts from the analytic solution at the viscous time `initial_tau`
+    (:math:`\\tau = 12 \\nu t / r_0^2`), when it has a finite width, rather
+    than from a thin ring which a mesh can't resolve. The start time of the
+    run is the time of that solution, so its surface density can be compared
+    with the analytic one at any later time, with `profile_error`. The gas
+    is cold, with a Mach number high enough that the pressure hardly affects
+    the spreading, and starts with the Keplerian velocity and the viscous
+    radial drift of the analytic solution. The origin is a sink, which
+    absorbs the gas which drains inward.
+
+    The solution is the Green's function of the 1D viscous disk equation, so
+    the test applies equally to a 1D solver: `evolve_1d` advances the initial
+    profile with :py:class:`sailfish.physics.viscous_disk.ViscousDisk1D`. The
+    2D runs are on the Cartesian mesh of the cbdiso_2d solver, and are
+    compared by their azimuthally averaged profile, from `radial_profile`.
+    """
+
+    nu = param(1e-3, "kinematic viscosity coefficient")
+    mach_number = param(40.0, "orbital Mach number of the gas")
+    initial_tau = param(0.02, "viscous time 12 nu t / r0^2 of the initial ring")
+    final_tau = param(0.1, "viscous time 12 nu t / r0^2 at the end of the run")
+    sigma_floor = param(1e-5, "surface density of the background")
+    domain_radius = param(2.5, "half-width of the square domain")
+    sink_radius = param(0.1, "radius of the central sink")
+
+    def validate(self):
+        if self.nu <= 0.0:
+            raise SetupError("nu must be positive")
+        if not 0.0 < self.initial_tau < self.final_tau:
+            raise SetupError("need 0 < initial_tau < final_tau")
+        if self.sigma_floor <= 0.0:
+            raise SetupError("sigma_floor must be positive")
+
+    def tau_time(self, tau):
+        """
+        Return the time at which the analytic ring has the viscous time `tau`.
+        """
+        return tau / (12.0 * self.nu)
+
+    def analytic_surface_density(self, r, time):
+        """
+        Return the surface density of the analytic solution at a radius and
+        time, without the background.
+        """
+        return viscous_ring_surface_density(r, time, viscosity=self.nu)
+
+    def analytic_radial_velocity(self, r, time):
+        """
+        Return the radial velocity of the analytic solution, :math:`v_r =
+        -3 / (\\Sigma r^{1/2}) \\partial_r (\\nu \\Sigma r^{1/2})`, by central
+        differences.
+        """
+        dr = 1e-4 * r
+        g0 = self.analytic_surface_density(r - dr, time) * sqrt(r - dr)
+        g1 = self.analytic_surface_density(r + dr, time) * sqrt(r + dr)
+        sigma = self.analytic_surface_density(r, time)
+        return -3.0 * self.nu * (g1 - g0) / (2.0 * dr) / (sigma * sqrt(r) + 1e-300)
+
+    def primitive(self, t, coords, primitive):
+        x, y = coords
+        r = sqrt(x * x + y * y)
+        r_softened = sqrt(r * r + self.softening_length**2)
+        sigma = self.analytic_surface_density(r, t)
+
+        # The drift is weighted by the ring's share of the density, so the
+        # background is at rest, apart from its orbit.
+        vp = sqrt(1.0 / r_softened)
+        vr = self.analytic_radial_velocity(r, t) * sigma / (sigma + self.sigma_floor)
+        r_hat_x = x / max(r, 1e-12)
+        r_hat_y = y / max(r, 1e-12)
+
+        primitive[0] = sigma + self.sigma_floor
+        primitive[1] = vr * r_hat_x - vp * r_hat_y
+        primitive[2] = vr * r_hat_y + vp * r_hat_x
+
+    def evolve_1d(self, time, num_zones=400, num_steps=200):
+        """
+        Return the zone radii and surface density of the analytic initial
+        profile evolved to a time with the 1D viscous disk solver. The inner
+        boundary is near the origin, where the surface density vanishes.
+        """
+        import numpy as np
+
+        disk = ViscousDisk1D(
+            viscosity=lambda r: self.nu,
+            inner_radius=0.1,
+            outer_radius=4.0,
+            num_zones=num_zones,
+        )
+        rc, _ = disk.radii()
+        t0 = self.start_time
+        sigma = np.array([self.analytic_surface_density(r, t0) for r in rc])
+        return rc, disk.evolve(sigma, time - t0, num_steps)
+
+    def radial_profile(self, mesh, sigma, num_bins=64):
+        """
+        Return the bin radii and azimuthally averaged surface density of the
+        2D data `sigma` on the mesh, less the background, out to the edge of
+        the domain.
+        """
+        import numpy as np
+
+        x, y = mesh.cell_center_arrays()
+        faces = np.geomspace(self.sink_radius, self.domain_radius, num_bins + 1)
+        profile = azimuthal_average((x * x + y * y) ** 0.5, sigma, faces)
+        return (faces[1:] * faces[:-1]) ** 0.5, profile - self.sigma_floor
+
+    def profile_error(self, r, sigma, time, r_min=0.5, r_max=1.8):
+        """
+        Return the L1 error of a radial surface density profile against the
+        analytic solution at a time, relative to the ring mass between radii
+        `r_min` and `r_max`, where most of it is until `tau` approaches one.
+        """
+        import numpy as np
+
+        r = np.asarray(r)
+        exact = np.array([self.analytic_surface_density(ri, time) for ri in r])
+        weight = r * np.gradient(r) * ((r >= r_min) & (r <= r_max))
+        return (np.abs(sigma - exact) * weight).sum() / (exact * weight).sum()
+
+    def mesh(self, resolution):
+        return PlanarCartesian2DMesh.centered_square(self.domain_radius, resolution)
+
+    @property
+    def softening_length(self):
+        return 0.5 * self.sink_radius
+
+    def point_masses(self, time):
+        return PointMass(
+            mass=1.0,
+            softening_length=self.softening_length,
+            sink_model=SinkModel.ACCELERATION_FREE,
+            sink_rate=10.0,
+            sink_radius=self.sink_radius,
+        )
+
+    @property
+    def physics(self):
+        return dict(
+            eos_type=EquationOfState.LOCALLY_ISOTHERMAL,
+            mach_number=self.mach_number,
+            point_mass_function=self.point_masses,
+            buffer_is_enabled=False,
+            viscosity_model=ViscosityModel.CONSTANT_NU,
+            viscosity_coefficient=self.nu,
+        )
+
+    @property
+    def solver(self):
+        return "cbdiso_2d"
+
+    @property
+    def boundary_condition(self):
+        return "outflow"
+
+    @property
+    def start_time(self):
+        return self.tau_time(self.initial_tau)
+
+    @property
+    def default_end_time(self):
+        return self.tau_time(self.final_tau)
+
+    @property
+    def default_resolution(self):
+        return 200
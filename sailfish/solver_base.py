@@ -1,5 +1,13 @@
 """
 The `Solver` class maintains physics state and implements a solution scheme.
+
+The driver, the interactive `Simulation`, and the output files only use a
+solver through the `SolverBase` interface, so a new solver module plugs into
+them without changes. The abstract members are required. The others have
+defaults, which a solver overrides to describe its fields and time series
+(`primitive_fields`, `reduction_names`), to choose its own time step
+(`recommended_timestep`), and to support optional features like face fluxes
+or secular steps.
 """
 
 from abc import ABC, abstractmethod
@@ -10,6 +18,9 @@ class SolverBase(ABC):
     Base class for solver implementations.
     """
 
+    num_guard = 0
+    """ The number of guard zones on the outer edges of the primitive data """
+
     @property
     @abstractmethod
     def solution(self):
@@ -43,6 +54,182 @@ class SolverBase(ABC):
         """
         pass
 
+    @property
+    def primitive_fields(self):
+        """
+        Return a list of :py:class:`sailfish.fields.Field` instances naming
+        the primitive variables, in the order of the last axis of the
+        primitive data. The default is an empty list, for solvers which don't
+        describe their fields.
+        """
+        return []
+
+    def field_index(self, name):
+        """
+        Return the position of the named field in the primitive data.
+        """
+        from sailfish.fields import field_index
+
+        return field_index(self.primitive_fields, name)
+
+    def primitive_array(self, guard_zones=False):
+        """
+        Return primitive hydrodynamic data on the host, with or without guard
+        zones.
+
+        With `guard_zones=False` the array covers only the interior zones of
+        the mesh, e.g. it has shape `(ni, nj, nq)` for a 2D solver. With
+        `guard_zones=True` it also includes the guard zones on the outer edges
+        of the domain, e.g. shape `(ni + 2 * ng, nj + 2 * ng, nq)`, where `ng`
+        is the solver's `num_guard` attribute. The default implementation
+        supports only interior zones; solvers with guard zones override it.
+
+        In gpu mode this is an explicit download. The solvers keep their
+        solution arrays on the devices for the whole run, and launch the
+        kernels of successive steps back to back, so only scalar reductions
+        such as the maximum wavespeed reach the host between steps; this
+        should be called for outputs and diagnostics, not every iteration.
+        """
+        from sailfish.subdivide import to_host
+
+        if guard_zones:
+            raise NotImplementedError(f"{type(self)} does not output guard zones")
+
+        primitive = self.primitive
+        return to_host(primitive if primitive is not None else self.solution)
+
+    def conserved_array(self):
+        """
+        Return conserved hydrodynamic data on the host, for the interior zones.
+
+        Solvers which evolve primitive data convert their current solution
+        (in the frame of the solution array, i.e. without removing a frame
+        boost) with the same kernel used at the start of each iteration; the
+        ones which evolve conserved data return it directly. The default is to
+        raise `NotImplementedError`.
+        """
+        raise NotImplementedError(f"{type(self)} does not output conserved data")
+
+    def total_conserved(self):
+        """
+        Return the total mass and momentum on the mesh, as a host array.
+
+        Solvers which can audit their conservation sum the conserved data of
+        the active zones, times the zone areas. The default is to raise
+        `NotImplementedError`.
+        """
+        raise NotImplementedError(f"{type(self)} does not total conserved data")
+
+    def conservation_budget(self):
+        """
+        Return the changes of the total mass and momentum by each kind of term
+        since the previous call, or `None`.
+
+        Solvers with a conservation audit return a dictionary with the totals
+        at the start and end of the interval, the change by each term, e.g. the
+        sinks, buffers, and boundary fluxes, and the residual change which the
+        terms don't account for. The default, for solvers without an audit, is
+        to return `None`.
+        """
+        return None
+
+    def sample(self, x, y):
+        """
+        Return the rest-frame primitive data interpolated at points.
+
+        The points are arrays of coordinates (or numbers), and the result is
+        a host array of shape `(n, nq)`, with the fields of `primitive_array`.
+        Solvers on cartesian meshes interpolate bilinearly between the zone
+        centers, on the devices the data is on, so the primitive array isn't
+        copied to the host; this is for coupling to e.g. particle or tracer
+        integrators. Solvers on other meshes, and the default, raise
+        `NotImplementedError`.
+        """
+        raise NotImplementedError(f"{type(self)} does not support sampling")
+
+    def rest_frame_patches(self):
+        """
+        Return the rest-frame surface density and velocity of each patch, on
+        the device the patch is on.
+
+        The result is a list with a dictionary for each patch, of the
+        interior `sigma`, `vx`, and `vy` arrays, the zone center coordinates
+        `x` and `y` (arrays which broadcast against them), the array module
+        `xp`, and the patch's `execution_context`. This is for diagnostics
+        which reduce the solution without copying it to the host, e.g.
+        :py:mod:`sailfish.observables`. The default is to raise
+        `NotImplementedError`.
+        """
+        raise NotImplementedError(f"{type(self)} does not expose its patches")
+
+    def set_primitive(self, primitive):
+        """
+        Replace the solution with primitive data on the interior zones.
+
+        The data has the shape and frame of `primitive_array()`, i.e. it has
+        no guard zones and any frame boost is added back by the solver. After
+        the data is written, the solver calls `reinitialize`. The default is
+        to raise `NotImplementedError`.
+        """
+        raise NotImplementedError(f"{type(self)} does not support set_primitive")
+
+    def set_source_function(self, function):
+        """
+        Add a source term given by a Python function of the time, the zone
+        coordinates, and the primitive data, which returns the rates of
+        change of the conserved quantities, for prototyping new physics
+        without changes to the kernels. Solvers document the arrays it's
+        called with. The default is to raise `NotImplementedError`.
+        """
+        raise NotImplementedError(f"{type(self)} does not support source functions")
+
+    def set_post_advance_hook(self, hook):
+        """
+        Call `hook(patch, time)` with each of the solver's patches at the end
+        of every `advance`, e.g. to monitor or modify the solution. The default
+        is to raise `NotImplementedError`.
+        """
+        raise NotImplementedError(f"{type(self)} does not support advance hooks")
+
+    def reset(self, primitive=None, time=None):
+        """
+        Return the solver to new initial data at a new time, without building
+        it again.
+
+        This is for applications which run many short solves in an outer
+        loop, e.g. parameter fitting or the control of sink parameters, where
+        compiling the kernels and allocating the patches for each solve would
+        dominate. The `primitive` data is as for `set_primitive`; solvers
+        document what they use if it's not given. Mutable setup parameters
+        which the solver reads as it runs, like those of the point masses,
+        can be changed between solves. The default is to raise
+        `NotImplementedError`.
+        """
+        raise NotImplementedError(f"{type(self)} does not support reset")
+
+    def reinitialize(self):
+        """
+        Re-derive all of the solver state computed from the solution: the
+        guard zones, the conserved data, and any diagnostic accumulators.
+
+        Solvers call this at the end of construction, which covers restarts
+        from a checkpoint, and whenever the solution is modified other than
+        by `advance`, so that the next iteration doesn't depend on the order
+        in which the cached data would otherwise have been refreshed. The
+        default does nothing.
+        """
+        pass
+
+    def checkpoint_diagnostics(self):
+        """
+        Return a dict of post-processing data to include in checkpoint files.
+
+        The items are merged over the setup's checkpoint diagnostics, e.g. so
+        a solver which integrates the point masses itself records where they
+        really are. The default is an empty dict.
+        """
+        return dict()
+
     @property
     @abstractmethod
     def time(self):
@@ -51,6 +238,40 @@ class SolverBase(ABC):
         """
         pass
 
+    @property
+    def nominal_time(self):
+        """
+        Return the simulation time accumulated without compensated summation.
+
+        Solvers which keep time with a :py:class:`sailfish.clock.Clock` return
+        the plain floating point sum of the time steps here, which can differ
+        from `time` by the accumulated round-off on long runs. It's not
+        checkpointed, so a restarted solver starts it over from `time`. The
+        default is to return `time`.
+        """
+        return self.time
+
+    def face_fluxes(self):
+        """
+        Return the time-averaged fluxes through the mesh faces, or `None`.
+
+        Solvers which can record the Godunov fluxes return a dictionary with
+        the fluxes averaged over the interval since the previous call, and
+        reset the average. The default, for solvers which don't record them,
+        is to return `None`.
+        """
+        return None
+
+    def secular_step(self, duration):
+        """
+        Fast-forward the solution by `duration` with a reduced model.
+
+        Solvers for viscous disks can evolve an azimuthally averaged disk
+        over a long interval, and re-seed the solution from it. The default
+        is to raise `NotImplementedError`.
+        """
+        raise NotImplementedError(f"{type(self)} does not support secular steps")
+
     @property
     @abstractmethod
     def options(self) -> dict:
@@ -106,14 +327,34 @@ class SolverBase(ABC):
         Return the largest wavespeed on the grid.
 
         This function is not implemented as a property, to emphasize that it
-        might be relatively expensive to compute.
+        might be relatively expensive to compute. Solvers should reduce the
+        wavespeeds on the device the data is on, and copy only the result to
+        the host. With several patches, the patch reductions should all be
+        launched before any of them is read, e.g. with
+        :py:func:`sailfish.subdivide.lazy_reduce`, so devices aren't waited on
+        one at a time.
         """
         pass
 
+    def recommended_timestep(self, min_spacing, cfl_number):
+        """
+        Return the time step to use for the next iterations.
+
+        The driver passes the smallest zone spacing of the mesh, and the CFL
+        number. The default is the CFL condition on the largest wavespeed.
+        Solvers with other constraints on the time step, e.g. from viscosity
+        or source terms, can override this.
+        """
+        return min_spacing / self.maximum_wavespeed() * cfl_number
+
     @abstractmethod
     def advance(self, dt):
         """
         Advance the solution state by one iteration.
+
+        Solvers which detect invalid solution states, like a negative density,
+        raise a :py:class:`sailfish.solvers.SolverError` instead of going on
+        with them.
         """
         pass
 
@@ -126,3 +367,90 @@ class SolverBase(ABC):
         to the solver by the setup when the setup is first constructed.
         """
         pass
+
+    def timestep_limiters(self):
+        """
+        Return a map of the time step limit in each zone, and of the term
+        which sets it, for diagnosing a collapsing time step.
+
+        The result is a dictionary with `dt` and `limiter` arrays over the
+        mesh, the list of term `names` indexed by `limiter`, and the `zone`,
+        `location`, `term`, and `min_dt` of the most restrictive zone. The
+        default is to raise `NotImplementedError`.
+        """
+        raise NotImplementedError(f"{type(self)} does not map time step limiters")
+
+    def sink_resolution(self):
+        """
+        Return a list of the resolution statistics of each sink, for catching
+        sinks which become under-resolved.
+
+        Each item is a dictionary with the index of the point mass (`which`),
+        its `location`, the number of zones across the sink radius
+        (`zones_per_radius`), and the number of zone centers inside it
+        (`zones_inside`), and for the region around the sink, the largest
+        `mach_number`, and the smallest time step limit (`min_dt`) and its
+        `term`, as in `timestep_limiters`. The default is to raise
+        `NotImplementedError`.
+        """
+        raise NotImplementedError(f"{type(self)} does not report sink resolution")
+
+    @property
+    def reduction_names(self):
+        """
+        Return a list of names for the measurements returned by `reductions`,
+        or an empty list if they're not named. The names are written to
+        checkpoints as `timeseries_names`.
+        """
+        return []
+
+    @property
+    def reduction_units(self):
+        """
+        Return a list of units for the measurements returned by `reductions`,
+        in the same order as `reduction_names`, or an empty list if they're
+        not given.
+        """
+        return []
+
+    def deferred_reductions(self):
+        """
+        Return the same measurements as `reductions`, without waiting for
+        them to be computed.
+
+        GPU solvers can return some of the measurements as
+        :py:class:`sailfish.subdivide.DeferredReduction` instances, so that
+        recording a time series sample doesn't synchronize the device. The
+        default is to return `reductions()`.
+        """
+        return self.reductions()
+
+    def deferred_primitive_array(self):
+        """
+        Return the same data as `primitive_array()`, without waiting for it to
+        be copied to the host.
+
+        GPU solvers can return a :py:class:`sailfish.subdivide.DeferredCopy`,
+        whose download runs on a second stream while the next steps are
+        computed; `sailfish.subdivide.resolve` reads either. The default is
+        to return `primitive_array()`.
+        """
+        return self.primitive_array()
+
+    def synchronize(self):
+        """
+        Wait for the work launched by `advance` to finish.
+
+        On the GPU, `advance` can return as soon as its kernels are queued,
+        so the host runs ahead of the device until it reads a result, like
+        the maximum wavespeed for the next time step. The default does
+        nothing, for solvers which compute on the host.
+        """
+        pass
+
+    def is_done(self):
+        """
+        Return whether the work launched by `advance` has finished, without
+        waiting for it. The default is `True`.
+        """
+        return True
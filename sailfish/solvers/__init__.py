@@ -2,11 +2,75 @@
 Physics solvers and solution schemes.
 """
 
+from logging import getLogger
+
+logger = getLogger(__name__)
+
 
 class SolverInitializationError(Exception):
     """An invalid runtime configuration"""
 
 
+INVALID_STATES = {1: "negative density", 2: "non-finite data", 3: "negative pressure"}
+""" The kinds of invalid solution state, by the flags the kernels write """
+
+
+class SolverError(Exception):
+    """An invalid solution state, found while advancing the solution"""
+
+    def __init__(self, kind, zone, location, time):
+        x, y = location
+        super().__init__(f"{kind} in zone {zone} at x={x:.4f} y={y:.4f} t={time:.4f}")
+        self.kind = kind
+        self.zone = zone
+        self.location = location
+        self.time = time
+
+
+def check_invalid_zones(patches, mesh, time):
+    """
+    Raise a `SolverError` for a zone which the kernels of a patch have flagged
+    as invalid. Each patch has the flags in its `invalid_zones`
+    array, which starts at the mesh index `index_range[0]`, and is zero
+    where the solution is valid.
+    """
+    for patch in patches:
+        with patch.execution_context:
+            flags = patch.invalid_zones
+            k = int(flags.argmax())
+            flag = int(flags.reshape(-1)[k])
+
+        if flag != 0:
+            i, j = divmod(k, flags.shape[1])
+            i += patch.index_range[0]
+            location = mesh.cell_coordinates(i, j)
+            raise SolverError(INVALID_STATES[flag], (i, j), location, time)
+
+
+def advance_with_retries(solver, dt, retries):
+    """
+    Take a step of `dt` with `solver.take_step`, and check the solver's
+    patches for invalid zones. If there are any, the state returned by
+    `solver.save_step` before the step is restored with
+    `solver.restore_step`, and the step is taken again as two steps of
+    `dt / 2`, each of them retried in the same way, up to `retries` times.
+    The `SolverError` is raised if a step of the smallest size fails.
+    """
+    saved = solver.save_step()
+
+    try:
+        solver.take_step(dt)
+        check_invalid_zones(solver.patches, solver.mesh, solver.time)
+    except SolverError as e:
+        if retries == 0:
+            raise
+
+        logger.warning(f"{e}, retry with dt={0.5 * dt:.3e}")
+        solver.restore_step(saved)
+        advance_with_retries(solver, 0.5 * dt, retries - 1)
+        advance_with_retries(solver, 0.5 * dt, retries - 1)
+
+
 __solver_extension_modules = list()
 
 
@@ -15,9 +79,10 @@ def register_solver_extension(solver_name):
     __solver_extension_modules.append(solver_name)
 
 
-def make_solver(name, physics, options, **kwargs):
+def solver_module(name):
     """
-    Find a solver with the given name and construct it.
+    Return the module of the solver with the given name, or raise a
+    `SolverInitializationError` if there is none.
     """
     from importlib import import_module
     from . import srhd_1d
@@ -25,7 +90,11 @@ def make_solver(name, physics, options, **kwargs):
     from . import scdg_1d
     from . import cbdgam_2d
     from . import cbdiso_2d
+    from . import cbdiso2l_2d
+    from . import cbdisolp_2d
     from . import cbdisodg_2d
+    from . import euler_1d
+    from . import euler_2d
 
     solvers = dict(
         srhd_1d=srhd_1d,
@@ -33,13 +102,29 @@ def make_solver(name, physics, options, **kwargs):
         scdg_1d=scdg_1d,
         cbdgam_2d=cbdgam_2d,
         cbdiso_2d=cbdiso_2d,
+        cbdiso2l_2d=cbdiso2l_2d,
+        cbdisolp_2d=cbdisolp_2d,
         cbdisodg_2d=cbdisodg_2d,
+        euler_1d=euler_1d,
+        euler_2d=euler_2d,
     )
     for ext_name in __solver_extension_modules:
         solvers[ext_name] = import_module(ext_name)
 
     try:
-        return solvers[name].Solver(
+        return solvers[name]
+    except KeyError:
+        raise SolverInitializationError(f"no solver named {name}")
+
+
+def make_solver(name, physics, options, **kwargs):
+    """
+    Find a solver with the given name and construct it.
+    """
+    module = solver_module(name)
+
+    try:
+        return module.Solver(
             physics=physics or dict(), options=options or dict(), **kwargs
         )
     except (TypeError, ValueError) as e:
@@ -3,16 +3,23 @@ MODULE: cbdgam_2d
 
 DESCRIPTION: Energy-conserving solver for a binary accretion problem in 2D
   planar cartesian coordinates.
-
-TODO:
-    + add plm_theta as a solver option (currently it's hard-coded)
 */
 
 
 // ============================ PHYSICS =======================================
 // ============================================================================
+// With NCONS=5, a second energy component (e.g. electrons, or a stand-in for
+// radiation) is evolved with the gas, through its entropy `p2 / rho^gamma2`
+// per unit mass. It's advected, heated and cooled adiabatically, removed
+// with the mass by the sinks, and exchanges energy with the gas, but doesn't
+// contribute to the pressure.
+#ifndef NCONS
 #define NCONS 4
-#define PLM_THETA 1.5
+#endif
+
+#ifndef SECOND_ADIABATIC_GAMMA
+#define SECOND_ADIABATIC_GAMMA (5.0 / 3.0)
+#endif
 
 
 // ============================ MATH ==========================================
@@ -24,19 +31,19 @@ TODO:
 #define sign(x) copysign(1.0, x)
 #define minabs(a, b, c) min3(fabs(a), fabs(b), fabs(c))
 
-PRIVATE double plm_gradient_scalar(double yl, double y0, double yr)
+PRIVATE double plm_gradient_scalar(double yl, double y0, double yr, double theta)
 {
-    double a = (y0 - yl) * PLM_THETA;
+    double a = (y0 - yl) * theta;
     double b = (yr - yl) * 0.5;
-    double c = (yr - y0) * PLM_THETA;
+    double c = (yr - y0) * theta;
     return 0.25 * fabs(sign(a) + sign(b)) * (sign(a) + sign(c)) * minabs(a, b, c);
 }
 
-PRIVATE void plm_gradient(double *yl, double *y0, double *yr, double *g)
+PRIVATE void plm_gradient(double *yl, double *y0, double *yr, double *g, double theta)
 {
     for (int q = 0; q < NCONS; ++q)
     {
-        g[q] = plm_gradient_scalar(yl[q], y0[q], yr[q]);
+        g[q] = plm_gradient_scalar(yl[q], y0[q], yr[q], theta);
     }
 }
 
@@ -53,6 +60,7 @@ struct PointMass {
     double sink_rate;
     double sink_radius;
     int sink_model;
+    int sink_update;
 };
 
 struct PointMassList {
@@ -67,9 +75,40 @@ struct KeplerianBuffer {
     double outer_radius;
     double onset_width;
     int is_enabled;
+    int update;
 };
 
 
+// ============================ STIFF SOURCE TERMS ============================
+// ============================================================================
+// The updates of the relaxation terms, the sinks and the buffer, which drive
+// the solution toward a state at some rate: explicit (the forward Euler
+// update of the RK stage), sub-cycled in steps of at most SUBCYCLE_LIMIT /
+// rate, or exponential. The term is linear in the conserved state over a
+// stage, so the sub-steps are taken in closed form, and either of the last
+// two is stable for any rate * dt.
+#define SOURCE_UPDATE_EXPLICIT 0
+#define SOURCE_UPDATE_SUBCYCLE 1
+#define SOURCE_UPDATE_EXPONENTIAL 2
+#define SUBCYCLE_LIMIT 0.5
+
+PRIVATE double relaxation_time_step(double rate, double dt, int update)
+{
+    // The time step which the explicit update of a relaxation at the given
+    // rate is taken with, to give the update chosen.
+    if (rate <= 0.0 || update == SOURCE_UPDATE_EXPLICIT)
+    {
+        return dt;
+    }
+    if (update == SOURCE_UPDATE_SUBCYCLE)
+    {
+        int n = (int) ceil(rate * dt / SUBCYCLE_LIMIT);
+        return (1.0 - pow(1.0 - rate * dt / n, n)) / rate;
+    }
+    return -expm1(-rate * dt) / rate;
+}
+
+
 // ============================ GRAVITY =======================================
 // ============================================================================
 PRIVATE double disk_height(
@@ -151,6 +190,7 @@ PRIVATE void point_mass_source_term(
     double fx = -fgrav_numerator * dx;
     double fy = -fgrav_numerator * dy;
     double mdot = sigma * sink_rate * -1.0;
+    double sink_dt = relaxation_time_step(sink_rate, dt, mass->sink_update);
 
     switch (mass->sink_model)
     {
@@ -158,10 +198,10 @@ PRIVATE void point_mass_source_term(
         {
             double vx = prim[1];
             double vy = prim[2];
-            delta_cons[0] = dt * mdot;
-            delta_cons[1] = dt * mdot * prim[1] + dt * fx;
-            delta_cons[2] = dt * mdot * prim[2] + dt * fy;
-            delta_cons[3] = dt * (mdot * eps + 0.5 * mdot * (vx * vx + vy * vy)) + dt * (fx * vx + fy * vy);
+            delta_cons[0] = sink_dt * mdot;
+            delta_cons[1] = sink_dt * mdot * prim[1] + dt * fx;
+            delta_cons[2] = sink_dt * mdot * prim[2] + dt * fy;
+            delta_cons[3] = sink_dt * (mdot * eps + 0.5 * mdot * (vx * vx + vy * vy)) + dt * (fx * vx + fy * vy);
             break;
         }
         case 2: // torque-free
@@ -175,17 +215,17 @@ PRIVATE void point_mass_source_term(
             double dvdotrhat = (vx - vx0) * rhatx + (vy - vy0) * rhaty;
             double vxstar = dvdotrhat * rhatx + vx0;
             double vystar = dvdotrhat * rhaty + vy0;
-            delta_cons[0] = dt * mdot;
-            delta_cons[1] = dt * mdot * vxstar + dt * fx;
-            delta_cons[2] = dt * mdot * vystar + dt * fy;
-            delta_cons[3] = dt * (mdot * eps + 0.5 * mdot * (vxstar * vxstar + vystar * vystar)) + dt * (fx * vx + fy * vy);
+            delta_cons[0] = sink_dt * mdot;
+            delta_cons[1] = sink_dt * mdot * vxstar + dt * fx;
+            delta_cons[2] = sink_dt * mdot * vystar + dt * fy;
+            delta_cons[3] = sink_dt * (mdot * eps + 0.5 * mdot * (vxstar * vxstar + vystar * vystar)) + dt * (fx * vx + fy * vy);
             break;
         }
         case 3: // force-free
         {
             double vx = prim[1];
             double vy = prim[2];
-            delta_cons[0] = dt * mdot;
+            delta_cons[0] = sink_dt * mdot;
             delta_cons[1] = dt * fx;
             delta_cons[2] = dt * fy;
             delta_cons[3] = dt * (fx * vx + fy * vy);
@@ -200,6 +240,11 @@ PRIVATE void point_mass_source_term(
             break;
         }
     }
+
+#if (NCONS > 4)
+    // the second component's entropy is carried away with the mass
+    delta_cons[4] = delta_cons[0] * prim[4] * pow(sigma, -SECOND_ADIABATIC_GAMMA);
+#endif
 }
 
 PRIVATE void point_masses_source_term(
@@ -230,7 +275,7 @@ PRIVATE void point_masses_source_term(
 // ============================================================================
 PRIVATE double sound_speed_squared(
     double gamma_law_index,
-    double *prim)
+    const double *prim)
 {
     return prim[3] / prim[0] * gamma_law_index;
 }
@@ -262,14 +307,18 @@ PRIVATE void buffer_source_term(
             double kinetic_energy = 0.5 * (px * px + py * py) / surface_density;
             double energy = surface_pressure / (gamma_law_index - 1.0) + kinetic_energy;
             double u0[NCONS] = {surface_density, px, py, energy};
+#if (NCONS > 4)
+            u0[4] = surface_pressure * pow(surface_density, 1.0 - SECOND_ADIABATIC_GAMMA);
+#endif
 
             double omega_outer = sqrt(central_mass * pow(onset_radius, -3.0));
             //double buffer_rate = driving_rate * omega_outer * max2(rc, 1.0);
             double buffer_rate = driving_rate * omega_outer * (rc - onset_radius) / (outer_radius - onset_radius);
+            double buffer_dt = relaxation_time_step(buffer_rate, dt, buffer->update);
 
             for (int q = 0; q < NCONS; ++q)
             {
-                cons[q] -= (cons[q] - u0[q]) * buffer_rate * dt;
+                cons[q] -= (cons[q] - u0[q]) * buffer_rate * buffer_dt;
             }
         }
     }
@@ -316,6 +365,46 @@ PRIVATE void cooling_term(
     cons[3] += sigma * (eps_cooled - eps);
 }
 
+PRIVATE void energy_exchange_term(
+    double exchange_rate,
+    double dt,
+    double *prim,
+    double *cons)
+{
+#if (NCONS > 4)
+    // Relaxes the temperature of the second component toward that of the
+    // gas, dp2/dt = exchange_rate * (p - p2), at fixed density; the gas
+    // loses the energy the second component gains.
+    double rho = prim[0];
+    double dp2 = exchange_rate * (prim[3] - prim[4]) * dt;
+    cons[3] -= dp2 / (SECOND_ADIABATIC_GAMMA - 1.0);
+    cons[4] += dp2 * pow(rho, 1.0 - SECOND_ADIABATIC_GAMMA);
+#endif
+}
+
+PRIVATE double invalid_state(const double *cons)
+{
+    // Returns the flag of an invalid conserved state, which the floors would
+    // otherwise hide: 1 for a negative density, 2 for non-finite data, and 3
+    // for a negative pressure. See sailfish.solvers.INVALID_STATES.
+    for (int q = 0; q < NCONS; ++q)
+    {
+        if (!isfinite(cons[q]))
+        {
+            return 2.0;
+        }
+    }
+    if (cons[0] < 0.0)
+    {
+        return 1.0;
+    }
+    if (cons[3] - 0.5 * (cons[1] * cons[1] + cons[2] * cons[2]) / cons[0] < 0.0)
+    {
+        return 3.0;
+    }
+    return 0.0;
+}
+
 PRIVATE void conserved_to_primitive(
     const double *cons,
     double *prim,
@@ -330,18 +419,28 @@ PRIVATE void conserved_to_primitive(
     double vy = sign(cons[2]) * min2(fabs(cons[2] / cons[0]), velocity_ceiling);
     double rho = cons[0];
 
+#if (NCONS > 4)
+    double pres2 = max2(pressure_floor, cons[4] * pow(cons[0], SECOND_ADIABATIC_GAMMA - 1.0));
+#endif
+
     if (cons[0] < density_floor)
     {
         rho = density_floor;
         vx = 0.0;
         vy = 0.0;
         pres = pressure_floor;
+#if (NCONS > 4)
+        pres2 = pressure_floor;
+#endif
     }
 
     prim[0] = rho;
     prim[1] = vx;
     prim[2] = vy;
     prim[3] = pres;
+#if (NCONS > 4)
+    prim[4] = pres2;
+#endif
 }
 
 PRIVATE void primitive_to_conserved(const double *prim, double *cons, double gamma_law_index)
@@ -359,6 +458,9 @@ PRIVATE void primitive_to_conserved(const double *prim, double *cons, double gam
     cons[1] = px;
     cons[2] = py;
     cons[3] = en;
+#if (NCONS > 4)
+    cons[4] = prim[4] * pow(rho, 1.0 - SECOND_ADIABATIC_GAMMA);
+#endif
 }
 
 PRIVATE double primitive_to_velocity(const double *prim, int direction)
@@ -384,6 +486,9 @@ PRIVATE void primitive_to_flux(
     flux[1] = vn * cons[1] + pressure * (direction == 0);
     flux[2] = vn * cons[2] + pressure * (direction == 1);
     flux[3] = vn * (cons[3] + pressure);
+#if (NCONS > 4)
+    flux[4] = vn * cons[4];
+#endif
 }
 
 PRIVATE void primitive_to_outer_wavespeeds(
@@ -408,24 +513,73 @@ PRIVATE double primitive_max_wavespeed(const double *prim, double cs2)
     return max2(ax, ay);
 }
 
-PRIVATE void riemann_hlle(const double *pl, const double *pr, double *flux, double cs2, int direction, double gamma_law_index)
+PRIVATE void hll_wavespeeds(
+    const double *pl,
+    const double *pr,
+    const double *ul,
+    const double *ur,
+    double cs2,
+    int direction,
+    int wavespeed_estimator,
+    double gamma_law_index,
+    double *am,
+    double *ap)
+{
+    double al[2];
+    double ar[2];
+
+    switch (wavespeed_estimator)
+    {
+        case 1: // Einfeldt: Roe-averaged velocity and enthalpy, bounded by the outer states
+        {
+            double sl = sqrt(pl[0]);
+            double sr = sqrt(pr[0]);
+            double vxroe = (sl * pl[1] + sr * pr[1]) / (sl + sr);
+            double vyroe = (sl * pl[2] + sr * pr[2]) / (sl + sr);
+            double hl = (ul[3] + pl[3]) / pl[0];
+            double hr = (ur[3] + pr[3]) / pr[0];
+            double hroe = (sl * hl + sr * hr) / (sl + sr);
+            double vnroe = direction == 0 ? vxroe : vyroe;
+            double croe = sqrt(max2(0.0, (gamma_law_index - 1.0) * (hroe - 0.5 * (vxroe * vxroe + vyroe * vyroe))));
+            primitive_to_outer_wavespeeds(pl, al, sound_speed_squared(gamma_law_index, pl), direction);
+            primitive_to_outer_wavespeeds(pr, ar, sound_speed_squared(gamma_law_index, pr), direction);
+            *am = min3(0.0, al[0], vnroe - croe);
+            *ap = max3(0.0, ar[1], vnroe + croe);
+            break;
+        }
+        case 2: // exact eigenvalues of each face state
+        {
+            primitive_to_outer_wavespeeds(pl, al, sound_speed_squared(gamma_law_index, pl), direction);
+            primitive_to_outer_wavespeeds(pr, ar, sound_speed_squared(gamma_law_index, pr), direction);
+            *am = min3(0.0, al[0], ar[0]);
+            *ap = max3(0.0, al[1], ar[1]);
+            break;
+        }
+        default: // Davis, with the sound speed of the neighboring zone
+        {
+            primitive_to_outer_wavespeeds(pl, al, cs2, direction);
+            primitive_to_outer_wavespeeds(pr, ar, cs2, direction);
+            *am = min3(0.0, al[0], ar[0]);
+            *ap = max3(0.0, al[1], ar[1]);
+            break;
+        }
+    }
+}
+
+PRIVATE void riemann_hlle(const double *pl, const double *pr, double *flux, double cs2, int direction, int wavespeed_estimator, double gamma_law_index)
 {
     double ul[NCONS];
     double ur[NCONS];
     double fl[NCONS];
     double fr[NCONS];
-    double al[2];
-    double ar[2];
+    double am;
+    double ap;
 
     primitive_to_conserved(pl, ul, gamma_law_index);
     primitive_to_conserved(pr, ur, gamma_law_index);
     primitive_to_flux(pl, ul, fl, direction);
     primitive_to_flux(pr, ur, fr, direction);
-    primitive_to_outer_wavespeeds(pl, al, cs2, direction);
-    primitive_to_outer_wavespeeds(pr, ar, cs2, direction);
-
-    const double am = min3(0.0, al[0], ar[0]);
-    const double ap = max3(0.0, al[1], ar[1]);
+    hll_wavespeeds(pl, pr, ul, ur, cs2, direction, wavespeed_estimator, gamma_law_index, &am, &ap);
 
     for (int q = 0; q < NCONS; ++q)
     {
@@ -433,6 +587,96 @@ PRIVATE void riemann_hlle(const double *pl, const double *pr, double *flux, doub
     }
 }
 
+PRIVATE void riemann_hllc(const double *pl, const double *pr, double *flux, double cs2, int direction, int wavespeed_estimator, double gamma_law_index)
+{
+    // The HLL wavespeeds bound a middle region, which is split by a contact
+    // wave into two states of equal pressure and normal velocity (Toro 2009,
+    // section 10.4). The transverse velocity and the second entropy (if
+    // NCONS > 4) jump across the contact, rather than being averaged as in
+    // HLLE, so shear layers and contact discontinuities are less diffused.
+    double ul[NCONS];
+    double ur[NCONS];
+    double fl[NCONS];
+    double fr[NCONS];
+    double am;
+    double ap;
+
+    primitive_to_conserved(pl, ul, gamma_law_index);
+    primitive_to_conserved(pr, ur, gamma_law_index);
+    primitive_to_flux(pl, ul, fl, direction);
+    primitive_to_flux(pr, ur, fr, direction);
+    hll_wavespeeds(pl, pr, ul, ur, cs2, direction, wavespeed_estimator, gamma_law_index, &am, &ap);
+
+    double vl = primitive_to_velocity(pl, direction);
+    double vr = primitive_to_velocity(pr, direction);
+    double ml = pl[0] * (am - vl);
+    double mr = pr[0] * (ap - vr);
+    double vstar = (pr[3] - pl[3] + ml * vl - mr * vr) / (ml - mr);
+
+    // The wavespeeds are bounded by zero, so the face is always inside the
+    // middle region, on the side of the contact given by its speed.
+    const double *pk = vstar >= 0.0 ? pl : pr;
+    const double *uk = vstar >= 0.0 ? ul : ur;
+    const double *fk = vstar >= 0.0 ? fl : fr;
+    double sk = vstar >= 0.0 ? am : ap;
+    double vk = vstar >= 0.0 ? vl : vr;
+    double mk = vstar >= 0.0 ? ml : mr;
+    double d = mk / (sk - vstar);
+    double ustar[NCONS];
+
+    ustar[0] = d;
+    ustar[1] = d * (direction == 0 ? vstar : pk[1]);
+    ustar[2] = d * (direction == 1 ? vstar : pk[2]);
+    ustar[3] = d * (uk[3] / pk[0] + (vstar - vk) * (vstar + pk[3] / mk));
+#if (NCONS > 4)
+    ustar[4] = d * uk[4] / pk[0];
+#endif
+
+    for (int q = 0; q < NCONS; ++q)
+    {
+        flux[q] = fk[q] + sk * (ustar[q] - uk[q]);
+    }
+}
+
+PRIVATE void riemann_flux(const double *pl, const double *pr, double *flux, double cs2, int direction, int wavespeed_estimator, int riemann_solver, double gamma_law_index)
+{
+    switch (riemann_solver)
+    {
+        case 1:
+            riemann_hllc(pl, pr, flux, cs2, direction, wavespeed_estimator, gamma_law_index);
+            break;
+        default:
+            riemann_hlle(pl, pr, flux, cs2, direction, wavespeed_estimator, gamma_law_index);
+            break;
+    }
+}
+
+PRIVATE void artificial_viscosity_flux(
+    const double *pl,
+    const double *pr,
+    double *flux,
+    int direction,
+    double quadratic_coefficient,
+    double linear_coefficient,
+    double gamma_law_index)
+{
+    // Von Neumann-Richtmyer artificial viscosity: an extra pressure-like
+    // momentum flux, and its work on the gas, at faces where the zones on
+    // either side are approaching each other. It's zero elsewhere, so it's
+    // confined to shocks and other strongly compressive flows.
+    double dv = pr[direction + 1] - pl[direction + 1];
+
+    if (dv < 0.0)
+    {
+        double rho = 0.5 * (pl[0] + pr[0]);
+        double cs = sqrt(0.5 * (sound_speed_squared(gamma_law_index, pl) + sound_speed_squared(gamma_law_index, pr)));
+        double vn = 0.5 * (pl[direction + 1] + pr[direction + 1]);
+        double q = rho * (quadratic_coefficient * dv * dv - linear_coefficient * cs * dv);
+        flux[direction + 1] += q;
+        flux[3] += q * vn;
+    }
+}
+
 
 // ============================ PUBLIC API ====================================
 // ============================================================================
@@ -443,9 +687,9 @@ PUBLIC void cbdgam_2d_advance_rk(
     double patch_xr,
     double patch_yl,
     double patch_yr,
-    double *conserved_rk, // :: $.shape == (ni + 4, nj + 4, 4)
-    double *primitive_rd, // :: $.shape == (ni + 4, nj + 4, 4)
-    double *primitive_wr, // :: $.shape == (ni + 4, nj + 4, 4)
+    double *conserved_rk, // :: $.shape[:2] == (ni + 4, nj + 4)
+    double *primitive_rd, // :: $.shape[:2] == (ni + 4, nj + 4)
+    double *primitive_wr, // :: $.shape[:2] == (ni + 4, nj + 4)
     double gamma_law_index,
     double buffer_surface_density,
     double buffer_surface_pressure,
@@ -454,6 +698,7 @@ PUBLIC void cbdgam_2d_advance_rk(
     double buffer_outer_radius,
     double buffer_onset_width,
     int buffer_is_enabled,
+    int buffer_update, // :: $ in [0, 1, 2]
     double x1, // point mass 1
     double y1,
     double vx1,
@@ -472,6 +717,7 @@ PUBLIC void cbdgam_2d_advance_rk(
     double sink_rate2,
     double sink_radius2,
     int sink_model2,
+    int sink_update, // :: $ in [0, 1, 2]
     double alpha, // other
     double a,
     double dt,
@@ -480,7 +726,15 @@ PUBLIC void cbdgam_2d_advance_rk(
     double mach_ceiling,
     double density_floor,
     double pressure_floor,
-    int constant_softening)
+    double *invalid_zones, // :: $.shape == (ni, nj)
+    int constant_softening,
+    int wavespeed_estimator, // :: $ in [0, 1, 2]
+    int riemann_solver, // :: $ in [0, 1]
+    double plm_theta, // :: $ >= 1.0 and $ <= 2.0
+    double external_gravity,
+    double av_quadratic, // :: $ >= 0.0
+    double av_linear, // :: $ >= 0.0
+    double exchange_rate) // :: $ >= 0.0
 {
     struct KeplerianBuffer buffer = {
         buffer_surface_density,
@@ -489,18 +743,19 @@ PUBLIC void cbdgam_2d_advance_rk(
         buffer_driving_rate,
         buffer_outer_radius,
         buffer_onset_width,
-        buffer_is_enabled
+        buffer_is_enabled,
+        buffer_update
     };
-    struct PointMass m1 = {x1, y1, vx1, vy1, mass1, softening_length1, sink_rate1, sink_radius1, sink_model1};
-    struct PointMass m2 = {x2, y2, vx2, vy2, mass2, softening_length2, sink_rate2, sink_radius2, sink_model2};
+    struct PointMass m1 = {x1, y1, vx1, vy1, mass1, softening_length1, sink_rate1, sink_radius1, sink_model1, sink_update};
+    struct PointMass m2 = {x2, y2, vx2, vy2, mass2, softening_length2, sink_rate2, sink_radius2, sink_model2, sink_update};
     struct PointMassList mass_list = {{m1, m2}};
 
     double dx = (patch_xr - patch_xl) / ni;
     double dy = (patch_yr - patch_yl) / nj;
 
     int ng = 2; // number of guard zones
-    int si = NCONS * (nj + 2 * ng);
-    int sj = NCONS;
+    index_t si = NCONS * (nj + 2 * ng);
+    index_t sj = NCONS;
 
     FOR_EACH_2D(ni, nj)
     {
@@ -531,19 +786,19 @@ PUBLIC void cbdgam_2d_advance_rk(
         //                 kj
         // ------------------------------------------------------------------------
 
-        int ncc = (i     + ng) * si + (j     + ng) * sj;
-        int nli = (i - 1 + ng) * si + (j     + ng) * sj;
-        int nri = (i + 1 + ng) * si + (j     + ng) * sj;
-        int nlj = (i     + ng) * si + (j - 1 + ng) * sj;
-        int nrj = (i     + ng) * si + (j + 1 + ng) * sj;
-        int nki = (i - 2 + ng) * si + (j     + ng) * sj;
-        int nti = (i + 2 + ng) * si + (j     + ng) * sj;
-        int nkj = (i     + ng) * si + (j - 2 + ng) * sj;
-        int ntj = (i     + ng) * si + (j + 2 + ng) * sj;
-        int nll = (i - 1 + ng) * si + (j - 1 + ng) * sj;
-        int nlr = (i - 1 + ng) * si + (j + 1 + ng) * sj;
-        int nrl = (i + 1 + ng) * si + (j - 1 + ng) * sj;
-        int nrr = (i + 1 + ng) * si + (j + 1 + ng) * sj;
+        index_t ncc = (i     + ng) * si + (j     + ng) * sj;
+        index_t nli = (i - 1 + ng) * si + (j     + ng) * sj;
+        index_t nri = (i + 1 + ng) * si + (j     + ng) * sj;
+        index_t nlj = (i     + ng) * si + (j - 1 + ng) * sj;
+        index_t nrj = (i     + ng) * si + (j + 1 + ng) * sj;
+        index_t nki = (i - 2 + ng) * si + (j     + ng) * sj;
+        index_t nti = (i + 2 + ng) * si + (j     + ng) * sj;
+        index_t nkj = (i     + ng) * si + (j - 2 + ng) * sj;
+        index_t ntj = (i     + ng) * si + (j + 2 + ng) * sj;
+        index_t nll = (i - 1 + ng) * si + (j - 1 + ng) * sj;
+        index_t nlr = (i - 1 + ng) * si + (j + 1 + ng) * sj;
+        index_t nrl = (i + 1 + ng) * si + (j - 1 + ng) * sj;
+        index_t nrr = (i + 1 + ng) * si + (j + 1 + ng) * sj;
 
         double *un = &conserved_rk[ncc];
         double *pcc = &primitive_rd[ncc];
@@ -580,16 +835,16 @@ PUBLIC void cbdgam_2d_advance_rk(
         double gxcc[NCONS];
         double gycc[NCONS];
 
-        plm_gradient(pki, pli, pcc, gxli);
-        plm_gradient(pli, pcc, pri, gxcc);
-        plm_gradient(pcc, pri, pti, gxri);
-        plm_gradient(pkj, plj, pcc, gylj);
-        plm_gradient(plj, pcc, prj, gycc);
-        plm_gradient(pcc, prj, ptj, gyrj);
-        plm_gradient(pll, pli, plr, gyli);
-        plm_gradient(prl, pri, prr, gyri);
-        plm_gradient(pll, plj, prl, gxlj);
-        plm_gradient(plr, prj, prr, gxrj);
+        plm_gradient(pki, pli, pcc, gxli, plm_theta);
+        plm_gradient(pli, pcc, pri, gxcc, plm_theta);
+        plm_gradient(pcc, pri, pti, gxri, plm_theta);
+        plm_gradient(pkj, plj, pcc, gylj, plm_theta);
+        plm_gradient(plj, pcc, prj, gycc, plm_theta);
+        plm_gradient(pcc, prj, ptj, gyrj, plm_theta);
+        plm_gradient(pll, pli, plr, gyli, plm_theta);
+        plm_gradient(prl, pri, prr, gyri, plm_theta);
+        plm_gradient(pll, plj, prl, gxlj, plm_theta);
+        plm_gradient(plr, prj, prr, gxrj, plm_theta);
 
         for (int q = 0; q < NCONS; ++q)
         {
@@ -616,10 +871,18 @@ PUBLIC void cbdgam_2d_advance_rk(
         double cs2rj = sound_speed_squared(gamma_law_index, prj);
         double hcc = disk_height(&mass_list, xc, yc, pcc);
 
-        riemann_hlle(plim, plip, fli, cs2li, 0, gamma_law_index);
-        riemann_hlle(prim, prip, fri, cs2ri, 0, gamma_law_index);
-        riemann_hlle(pljm, pljp, flj, cs2lj, 1, gamma_law_index);
-        riemann_hlle(prjm, prjp, frj, cs2rj, 1, gamma_law_index);
+        riemann_flux(plim, plip, fli, cs2li, 0, wavespeed_estimator, riemann_solver, gamma_law_index);
+        riemann_flux(prim, prip, fri, cs2ri, 0, wavespeed_estimator, riemann_solver, gamma_law_index);
+        riemann_flux(pljm, pljp, flj, cs2lj, 1, wavespeed_estimator, riemann_solver, gamma_law_index);
+        riemann_flux(prjm, prjp, frj, cs2rj, 1, wavespeed_estimator, riemann_solver, gamma_law_index);
+
+        if (av_quadratic > 0.0 || av_linear > 0.0)
+        {
+            artificial_viscosity_flux(pli, pcc, fli, 0, av_quadratic, av_linear, gamma_law_index);
+            artificial_viscosity_flux(pcc, pri, fri, 0, av_quadratic, av_linear, gamma_law_index);
+            artificial_viscosity_flux(plj, pcc, flj, 1, av_quadratic, av_linear, gamma_law_index);
+            artificial_viscosity_flux(pcc, prj, frj, 1, av_quadratic, av_linear, gamma_law_index);
+        }
 
         if (alpha > 0.0)
         {
@@ -670,6 +933,11 @@ PUBLIC void cbdgam_2d_advance_rk(
         buffer_source_term(&buffer, xc, yc, dt, ucc, gamma_law_index);
         point_masses_source_term(&mass_list, xc, yc, dt, pcc, hcc, ucc, constant_softening, gamma_law_index);
         cooling_term(cooling_coefficient, mach_ceiling, dt, pcc, ucc, gamma_law_index);
+        energy_exchange_term(exchange_rate, dt, pcc, ucc);
+
+        // uniform external gravity, in the -y direction
+        ucc[2] -= dt * pcc[0] * external_gravity;
+        ucc[3] -= dt * pcc[0] * pcc[2] * external_gravity;
 
         for (int q = 0; q < NCONS; ++q)
         {
@@ -678,6 +946,12 @@ PUBLIC void cbdgam_2d_advance_rk(
         }
 
         double *pout = &primitive_wr[ncc];
+        double invalid = invalid_state(ucc);
+
+        if (invalid != 0.0)
+        {
+            invalid_zones[(index_t) i * nj + j] = invalid;
+        }
         conserved_to_primitive(ucc, pout, velocity_ceiling, density_floor, pressure_floor, gamma_law_index);
     }
 }
@@ -685,20 +959,20 @@ PUBLIC void cbdgam_2d_advance_rk(
 PUBLIC void cbdgam_2d_wavespeed(
     int ni,
     int nj,
-    double *primitive, // :: $.shape == (ni + 4, nj + 4, 4)
+    double *primitive, // :: $.shape[:2] == (ni + 4, nj + 4)
     double *wavespeed, // :: $.shape == (ni + 4, nj + 4)
     double gamma_law_index)
 {
     int ng = 2; // number of guard zones
-    int si = NCONS * (nj + 2 * ng);
-    int sj = NCONS;
-    int ti = nj + 2 * ng;
-    int tj = 1;
+    index_t si = NCONS * (nj + 2 * ng);
+    index_t sj = NCONS;
+    index_t ti = nj + 2 * ng;
+    index_t tj = 1;
 
     FOR_EACH_2D(ni, nj)
     {
-        int np = (i + ng) * si + (j + ng) * sj;
-        int na = (i + ng) * ti + (j + ng) * tj;
+        index_t np = (i + ng) * si + (j + ng) * sj;
+        index_t na = (i + ng) * ti + (j + ng) * tj;
 
         double *pc = &primitive[np];
         double cs2 = sound_speed_squared(gamma_law_index, pc);
@@ -710,17 +984,17 @@ PUBLIC void cbdgam_2d_wavespeed(
 PUBLIC void cbdgam_2d_primitive_to_conserved(
     int ni,
     int nj,
-    double *primitive, // :: $.shape == (ni + 4, nj + 4, 4)
-    double *conserved, // :: $.shape == (ni + 4, nj + 4, 4)
+    double *primitive, // :: $.shape[:2] == (ni + 4, nj + 4)
+    double *conserved, // :: $.shape[:2] == (ni + 4, nj + 4)
     double gamma_law_index)
 {
     int ng = 2; // number of guard zones
-    int si = NCONS * (nj + 2 * ng);
-    int sj = NCONS;
+    index_t si = NCONS * (nj + 2 * ng);
+    index_t sj = NCONS;
 
     FOR_EACH_2D(ni, nj)
     {
-        int n = (i + ng) * si + (j + ng) * sj;
+        index_t n = (i + ng) * si + (j + ng) * sj;
 
         double *pc = &primitive[n];
         double *uc = &conserved[n];
@@ -754,8 +1028,8 @@ PUBLIC void cbdgam_2d_point_mass_source_term(
     double sink_radius2,
     int sink_model2,
     int which_mass, // :: $ in [1, 2]
-    double *primitive, // :: $.shape == (ni + 4, nj + 4, 4)
-    double *cons_rate, // :: $.shape == (ni + 4, nj + 4, 4)
+    double *primitive, // :: $.shape[:2] == (ni + 4, nj + 4)
+    double *cons_rate, // :: $.shape[:2] == (ni + 4, nj + 4)
     int constant_softening,
     double gamma_law_index)
 {
@@ -764,15 +1038,15 @@ PUBLIC void cbdgam_2d_point_mass_source_term(
     struct PointMassList mass_list = {{m1, m2}};
 
     int ng = 2; // number of guard zones
-    int si = NCONS * (nj + 2 * ng);
-    int sj = NCONS;
+    index_t si = NCONS * (nj + 2 * ng);
+    index_t sj = NCONS;
 
     double dx = (patch_xr - patch_xl) / ni;
     double dy = (patch_yr - patch_yl) / nj;
 
     FOR_EACH_2D(ni, nj)
     {
-        int ncc = (i + ng) * si + (j + ng) * sj;
+        index_t ncc = (i + ng) * si + (j + ng) * sj;
 
         double xc = patch_xl + (i + 0.5) * dx;
         double yc = patch_yl + (j + 0.5) * dy;
@@ -1,49 +1,193 @@
 """
 Energy-conserving solver for the binary accretion problem in 2D.
+
+The `artificial_viscosity` and `artificial_viscosity_linear` options add a
+von Neumann-Richtmyer viscous pressure, `rho (c2 dv^2 + c1 cs |dv|)`, at zone
+faces with a compressive velocity jump `dv`, e.g. to damp the post-shock
+oscillations of strong blast waves. Typical values are `c2 = 1` to `2` and `c1
+= 0.1` to `0.5`; they're off by default. Since the term only acts where the
+flow is compressing, smooth flows are unaffected, but it does widen shocks by
+a zone or so. Large coefficients may need a smaller CFL number.
+
+The `plm_theta` option is the parameter of the generalized minmod limiter
+used to reconstruct the face states, from 1 (most diffusive and robust) to 2
+(least diffusive). The `riemann_solver` option is `hlle` or `hllc`; both use
+the wavespeeds of the `wavespeed_estimator` option.
+
+The `two_temperature` option adds a second energy component, e.g. electrons
+in a two-temperature accretion flow, with its own adiabatic index
+`second_gamma_law_index`. Its pressure is a fifth primitive field,
+`pressure2`, which starts equal to the gas pressure. The component is
+advected with the gas and compressed adiabatically, but isn't shock heated
+and doesn't push on the gas; it only exchanges energy with it, relaxing its
+temperature toward the gas temperature at `energy_exchange_rate`. The time
+step is limited so the exchange is resolved.
+
+The `density_floor` and `pressure_floor` options are applied in the
+kernels, so a zone never holds a negative density or pressure. The floors
+alone would hide a failing solution, e.g. near the sinks, so the kernels
+also flag each zone whose updated conserved state has a negative density or
+pressure, or non-finite data, before it's floored. Every
+`invalid_state_cadence` iterations (10 by default, 0 to never check),
+`advance` inspects the flags and raises a `SolverError`, with the index and
+position of a flagged zone. In the safety mode, with `max_step_retries` set
+above zero, the flags are inspected after every iteration instead, and a
+failed iteration is taken again from the solution at its start, as two
+iterations of half the time step, each retried in the same way, up to
+`max_step_retries` times before the `SolverError` is raised. The retries
+are logged.
 """
 
+from copy import copy
+from math import exp, pi
 from typing import NamedTuple
 from logging import getLogger
-from sailfish.kernel.library import Library
-from sailfish.kernel.system import get_array_module, execution_context, num_devices
+from sailfish.boundary import BoundaryCondition, edge_conditions, fill_guard_zones
+from sailfish.clock import Clock
+from sailfish.fields import (
+    SURFACE_DENSITY,
+    VELOCITY_X,
+    VELOCITY_Y,
+    VERTICALLY_INTEGRATED_PRESSURE,
+    SECOND_PRESSURE,
+)
+from sailfish.kernel.library import Library, host_zeros
+from sailfish.kernel.system import copy_between_devices, get_array_module
+from sailfish.kernel.system import execution_context, num_devices
 from sailfish.mesh import PlanarCartesian2DMesh
 from sailfish.physics.circumbinary import (
     Physics,
+    CoolingModel,
     EquationOfState,
     ViscosityModel,
     Diagnostic,
+    LivePointMasses,
+    SofteningModel,
+    hill_mask,
 )
 from sailfish.solver_base import SolverBase
-from sailfish.subdivide import subdivide, to_host, concat_on_host, lazy_reduce
+from sailfish.solvers import advance_with_retries, check_invalid_zones
+from sailfish.subdivide import (
+    PatchReductions,
+    SolverReductions,
+    concat_on_host,
+    lazy_reduce,
+    sample_on_host,
+    to_host,
+)
 
 
 logger = getLogger(__name__)
 
+# Estimates of the fastest left and right-going waves in the HLL Riemann
+# solver. Davis uses the outer characteristic speeds of the two face states,
+# Einfeldt bounds those by the Roe-averaged characteristic speeds, and exact
+# evaluates the sound speed of each face state rather than using the zone
+# value. Wider estimates are more stable and more diffusive.
+WAVESPEED_ESTIMATORS = dict(davis=0, einfeldt=1, exact=2)
+
+
+# Approximate Riemann solvers for the face fluxes. HLLE averages the states
+# between the outer waves, and HLLC restores the contact wave, so it's less
+# diffusive for shear flows and contact discontinuities, and somewhat less
+# robust near vacuum and strong rarefactions.
+RIEMANN_SOLVERS = dict(hlle=0, hllc=1)
+
+# How the stiff relaxation terms, the sinks and the buffer, are updated in
+# each Runge-Kutta stage (see `sailfish.solvers.cbdiso_2d`).
+SOURCE_UPDATES = dict(explicit=0, subcycle=1, exponential=2)
+
 
 class Options(NamedTuple):
     pressure_floor: float = 1e-12
+    """ Lower limit of the pressure """
     density_floor: float = 1e-10
+    """ Lower limit of the surface density """
     velocity_ceiling: float = 1e16
+    """ Upper limit of the gas speed in each direction """
     mach_ceiling: float = 1e5
-
-
-def initial_condition(setup, mesh, time):
+    """ Upper limit of the Mach number, enforced by the cooling """
+    cfl_number: float = None
+    """ CFL parameter, the recommended one if None """
+    wavespeed_estimator: str = "davis"
+    """ Wavespeed estimate of the Riemann solver [davis|einfeldt|exact] """
+    riemann_solver: str = "hlle"
+    """ Approximate Riemann solver [hlle|hllc] """
+    plm_theta: float = 1.5
+    """ Parameter of the minmod limiter, from 1 to 2 """
+    boost_vx: float = 0.0
+    """ Velocity of the mesh frame along x """
+    boost_vy: float = 0.0
+    """ Velocity of the mesh frame along y """
+    artificial_viscosity: float = 0.0
+    """ Quadratic artificial viscosity coefficient """
+    artificial_viscosity_linear: float = 0.0
+    """ Linear artificial viscosity coefficient """
+    two_temperature: bool = False
+    """ Add a second energy component """
+    second_gamma_law_index: float = 5.0 / 3.0
+    """ Adiabatic index of the second energy component """
+    energy_exchange_rate: float = 0.0
+    """ Relaxation rate of the second component's temperature """
+    invalid_state_cadence: int = 10
+    """ Iterations between checks for invalid states, 0 for never """
+    max_step_retries: int = 0
+    """ Times a failed iteration is retried with half dt """
+    sink_update: str = "explicit"
+    """ Update of the sink terms [explicit|subcycle|exponential] """
+    buffer_update: str = "explicit"
+    """ Update of the buffer term [explicit|subcycle|exponential] """
+
+
+def initial_condition(setup, mesh, time, num_fields=4):
     """
     Generate a 2D array of primitive data from a mesh and a setup.
-    """
-    import numpy as np
 
+    With a fifth field, the pressure of the second energy component, the
+    setup provides the first four, and the fifth is set to the gas pressure.
+    """
     ni, nj = mesh.shape
-    primitive = np.zeros([ni, nj, 4])
+    primitive = host_zeros((ni, nj, num_fields))
 
     for i in range(ni):
         for j in range(nj):
-            setup.primitive(time, mesh.cell_coordinates(i, j), primitive[i, j])
+            setup.primitive(time, mesh.cell_coordinates(i, j), primitive[i, j, :4])
+
+    if num_fields > 4:
+        primitive[..., 4] = primitive[..., 3]
 
     return primitive
 
 
-class Patch:
+def fixed_guard_zones(setup, mesh, time, primitive, edges, num_guard, options):
+    """
+    Set the guard zones on the fixed edges of a primitive array, which has
+    guard zones, to the setup's primitive state at their coordinates.
+    """
+    ni, nj = mesh.shape
+    ng = num_guard
+    (xl, xr), (yl, yr) = edges
+    fixed = BoundaryCondition.FIXED
+    zones = set()
+
+    if xl == fixed:
+        zones.update((i, j) for i in range(-ng, 0) for j in range(-ng, nj + ng))
+    if xr == fixed:
+        zones.update((i, j) for i in range(ni, ni + ng) for j in range(-ng, nj + ng))
+    if yl == fixed:
+        zones.update((i, j) for i in range(-ng, ni + ng) for j in range(-ng, 0))
+    if yr == fixed:
+        zones.update((i, j) for i in range(-ng, ni + ng) for j in range(nj, nj + ng))
+
+    for i, j in zones:
+        p = primitive[i + ng, j + ng]
+        setup.primitive(time, mesh.cell_coordinates(i, j), p[:4])
+        p[4:] = p[3]
+        p[1] += options.boost_vx
+        p[2] += options.boost_vy
+
+
+class Patch(PatchReductions):
     """
     Holds the array buffer state for the solution on a subset of the
     solution domain.
@@ -60,6 +204,7 @@ class Patch:
         buffer_outer_radius,
         buffer_surface_density,
         buffer_surface_pressure,
+        cooling_target,
         lib,
         xp,
         execution_context,
@@ -70,7 +215,8 @@ class Patch:
         self.mesh = mesh
         self.xp = xp
         self.execution_context = execution_context
-        self.time = self.time0 = time
+        self.clock = Clock(time)
+        self.index_range = index_range
         self.shape = (i1 - i0, nj)  # not including guard zones
         self.physics = physics
         self.options = options
@@ -87,10 +233,16 @@ class Patch:
             y1 = self.yr - 0.5 * mesh.dy
             self.coordinate_array_x = xp.linspace(x0, x1, ni)[:, None]
             self.coordinate_array_y = xp.linspace(y0, y1, nj)[None, :]
-            self.wavespeeds = self.xp.zeros(primitive.shape[:2])
-            self.primitive1 = self.xp.array(primitive)
-            self.primitive2 = self.xp.array(primitive)
-            self.conserved0 = self.xp.zeros(primitive.shape)
+            self.wavespeeds = lib.zeros(primitive.shape[:2])
+            self.primitive1 = lib.array(primitive)
+            self.primitive2 = lib.array(primitive)
+            self.conserved0 = lib.zeros(primitive.shape)
+            self.invalid_zones = self.xp.zeros(self.shape)
+
+            if cooling_target is not None:
+                self.cooling_target = xp.array(cooling_target)
+            else:
+                self.cooling_target = None
 
     @property
     def cell_center_coordinate_arrays(self):
@@ -101,12 +253,61 @@ class Patch:
         """
         return self.coordinate_array_x, self.coordinate_array_y
 
+    def orbital_temperature(self, point_masses):
+        """
+        Return the temperature `-phi / mach_number^2` in each zone, where
+        `phi` is the combined potential of the point masses.
+        """
+        xp = self.xp
+        x, y = self.cell_center_coordinate_arrays
+        phi = 0.0
+
+        for m in point_masses:
+            dx, dy = x - m.position_x, y - m.position_y
+            r2 = xp.maximum(dx * dx + dy * dy + m.softening_length**2, 1e-24)
+            phi = phi - m.mass / xp.sqrt(r2)
+
+        return -phi / self.physics.mach_number**2
+
+    def cooling_step(self, dt):
+        """
+        Relax the temperature `p / sigma` toward its target over a time `dt`,
+        as a split step, with the exact exponential decay for the cooling time
+        in each zone. The pressure floor is applied.
+        """
+        ng = 2  # number of guard cells
+        physics = self.physics
+        xp = self.xp
+        m1, m2 = physics.resolved_point_masses(self.time, self.mesh)
+
+        with self.execution_context:
+            x, y = self.cell_center_coordinate_arrays
+            prim = self.primitive1[ng:-ng, ng:-ng]
+
+            if self.cooling_target is not None:
+                target = self.cooling_target
+            else:
+                target = self.orbital_temperature((m1, m2))
+
+            if physics.cooling_model == CoolingModel.BETA:
+                r2 = xp.maximum(x * x + y * y + m1.softening_length**2, 1e-24)
+                omega = xp.sqrt((m1.mass + m2.mass) / r2**1.5)
+                decay = xp.exp(-omega * dt / physics.cooling_beta)
+            else:
+                decay = exp(-dt / physics.cooling_time)
+
+            temperature = prim[..., 3] / prim[..., 0]
+            temperature = target + (temperature - target) * decay
+            prim[..., 3] = xp.maximum(
+                prim[..., 0] * temperature, self.options.pressure_floor
+            )
+
     def point_mass_source_term(self, which_mass, gravity=False, accretion=False):
         ng = 2  # number of guard cells
         if which_mass not in (1, 2):
             raise ValueError("the mass must be either 1 or 2")
 
-        m1, m2 = self.physics.point_masses(self.time)
+        m1, m2 = self.physics.resolved_point_masses(self.time, self.mesh)
 
         with self.execution_context:
             cons_rate = self.xp.zeros_like(self.conserved0)
@@ -149,7 +350,7 @@ class Patch:
                 self.wavespeeds,
                 self.physics.gamma_law_index,
             )
-            return self.wavespeeds.max()
+            return self.reduce("max", array="wavespeeds")
 
     def recompute_conserved(self):
         with self.execution_context:
@@ -160,7 +361,7 @@ class Patch:
             )
 
     def advance_rk(self, rk_param, dt):
-        m1, m2 = self.physics.point_masses(self.time)
+        m1, m2 = self.physics.resolved_point_masses(self.time, self.mesh)
         buffer_central_mass = m1.mass + m2.mass
         buffer_surface_density = self.buffer_surface_density
         buffer_surface_pressure = self.buffer_surface_pressure
@@ -182,6 +383,7 @@ class Patch:
                 self.buffer_outer_radius,
                 self.physics.buffer_onset_width,
                 int(self.physics.buffer_is_enabled),
+                SOURCE_UPDATES[self.options.buffer_update],
                 m1.position_x,
                 m1.position_y,
                 m1.velocity_x,
@@ -200,6 +402,7 @@ class Patch:
                 m2.sink_rate,
                 m2.sink_radius,
                 m2.sink_model.value,
+                SOURCE_UPDATES[self.options.sink_update],
                 self.physics.alpha,
                 rk_param,
                 dt,
@@ -208,14 +411,38 @@ class Patch:
                 self.options.mach_ceiling,
                 self.options.density_floor,
                 self.options.pressure_floor,
+                self.invalid_zones,
                 int(self.physics.constant_softening),
+                WAVESPEED_ESTIMATORS[self.options.wavespeed_estimator],
+                RIEMANN_SOLVERS[self.options.riemann_solver],
+                self.options.plm_theta,
+                self.physics.external_gravity,
+                self.options.artificial_viscosity,
+                self.options.artificial_viscosity_linear,
+                self.options.energy_exchange_rate,
             )
 
-        self.time = self.time0 * rk_param + (self.time + dt) * (1.0 - rk_param)
+        self.clock.advance_stage(rk_param, dt)
         self.primitive1, self.primitive2 = self.primitive2, self.primitive1
 
+    @property
+    def time(self):
+        return self.clock.time
+
+    def reinitialize(self):
+        """
+        Re-derive the conserved data and the second primitive buffer from the
+        primitive data, whose guard zones must already be filled.
+        """
+        self.recompute_conserved()
+
+        with self.execution_context:
+            self.primitive2[...] = self.primitive1
+            self.wavespeeds[...] = 0.0
+            self.invalid_zones[...] = 0.0
+
     def new_iteration(self):
-        self.time0 = self.time
+        self.clock.new_iteration()
         self.recompute_conserved()
 
     @property
@@ -223,7 +450,7 @@ class Patch:
         return self.primitive1
 
 
-class Solver(SolverBase):
+class Solver(SolverReductions, SolverBase):
     """
     Adapter class to drive the cbdgam_2d C extension module.
     """
@@ -248,11 +475,83 @@ class Solver(SolverBase):
         self._physics = physics = Physics(**physics)
         self._options = options = Options(**options)
 
+        if options.wavespeed_estimator not in WAVESPEED_ESTIMATORS:
+            raise ValueError(
+                f"wavespeed_estimator must be one of {list(WAVESPEED_ESTIMATORS)}"
+            )
+
+        if options.riemann_solver not in RIEMANN_SOLVERS:
+            raise ValueError(f"riemann_solver must be one of {list(RIEMANN_SOLVERS)}")
+
+        for update in (options.sink_update, options.buffer_update):
+            if update not in SOURCE_UPDATES:
+                raise ValueError(
+                    f"source updates must be one of {list(SOURCE_UPDATES)}"
+                )
+
+        if not 1.0 <= options.plm_theta <= 2.0:
+            raise ValueError(f"plm_theta must be in [1, 2], got {options.plm_theta}")
+
+        if options.cfl_number is not None and options.cfl_number > self.maximum_cfl:
+            raise ValueError(f"cfl_number must not exceed {self.maximum_cfl}")
+
+        if options.invalid_state_cadence < 0:
+            raise ValueError("invalid_state_cadence must be non-negative")
+
+        if options.max_step_retries < 0:
+            raise ValueError("max_step_retries must be non-negative")
+
         if type(mesh) is not PlanarCartesian2DMesh:
             raise ValueError("solver only supports 2D cartesian mesh")
 
-        if setup.boundary_condition != "outflow":
-            raise ValueError("solver only supports outflow boundary condition")
+        if min(options.artificial_viscosity, options.artificial_viscosity_linear) < 0.0:
+            raise ValueError("artificial viscosity coefficients must be non-negative")
+
+        if physics.probe_mass_function is not None:
+            raise ValueError("solver does not support probe masses")
+
+        if any(
+            m.softening_model != SofteningModel.PLUMMER
+            for m in physics.point_masses(time)
+        ):
+            raise ValueError("solver only supports plummer gravitational softening")
+
+        if physics.num_tracers != 0:
+            raise ValueError("solver does not support passive tracers")
+
+        if physics.reservoir is not None:
+            raise ValueError("solver does not support an inner reservoir")
+
+        if physics.custom_source is not None:
+            raise ValueError("solver does not support custom source terms")
+
+        if physics.inner_buffer_radius != 0.0:
+            raise ValueError("solver does not support an inner buffer")
+
+        if physics.self_gravity_constant != 0.0:
+            raise ValueError("solver does not support self-gravity")
+
+        if any(d.source_region is not None for d in physics.diagnostics):
+            raise ValueError("solver does not support source region diagnostics")
+
+        if setup.buffer_target(mesh) is not None:
+            raise ValueError("solver does not support a buffer target")
+
+        if options.two_temperature and options.second_gamma_law_index <= 1.0:
+            raise ValueError("second_gamma_law_index must be greater than 1")
+
+        if options.energy_exchange_rate < 0.0:
+            raise ValueError("energy_exchange_rate must be non-negative")
+
+        if physics.cooling_beta <= 0.0 or physics.cooling_time <= 0.0:
+            raise ValueError("cooling_beta and cooling_time must be positive")
+
+        cooling = physics.cooling_model != CoolingModel.NONE
+
+        if cooling and physics.cooling_target is None and physics.mach_number <= 0.0:
+            raise ValueError("the orbital cooling target needs a positive mach number")
+
+        edges = edge_conditions(setup.boundary_condition)
 
         if physics.viscosity_model not in (
             ViscosityModel.NONE,
@@ -263,33 +562,77 @@ class Solver(SolverBase):
         if physics.eos_type != EquationOfState.GAMMA_LAW:
             raise ValueError("solver only supports isothermal equation of states")
 
+        # A restarted solution of live point masses also has their state.
+        if isinstance(solution, dict):
+            two_body = solution["two_body"]
+            solution = solution["primitive"]
+        else:
+            two_body = None
+
+        if physics.live_point_masses:
+            live = LivePointMasses.start(physics, time, two_body)
+            self._physics = physics = physics._replace(point_mass_function=live)
+            logger.info("point masses are integrated by the solver")
+        else:
+            live = None
+
+        self.live_point_masses = live
+
         xp = get_array_module(mode)
         ng = 2  # number of guard zones
-        nq = 4  # number of conserved quantities
+        nq = 5 if options.two_temperature else 4  # number of conserved quantities
         with open(__file__.replace(".py", ".c")) as f:
             code = f.read()
-        lib = Library(code, mode=mode, debug=False)
+        lib = Library(
+            code,
+            mode=mode,
+            debug=False,
+            define_macros=dict(
+                NCONS=nq,
+                SECOND_ADIABATIC_GAMMA=options.second_gamma_law_index,
+            ),
+        )
 
         logger.info(f"initiate with time={time:0.4f}")
         logger.info(f"subdivide grid over {num_patches} patches")
         logger.info(f"mesh is {mesh}")
-        logger.info(f"boundary condition is outflow")
+        logger.info(
+            "boundary condition is {} / {} in x and {} / {} in y".format(
+                *(bc.value for edge in edges for bc in edge)
+            )
+        )
+
+        if options.two_temperature:
+            logger.info(
+                f"second energy component with gamma={options.second_gamma_law_index}"
+                f" and exchange rate {options.energy_exchange_rate}"
+            )
 
         self.mesh = mesh
         self.setup = setup
+        self.boundary_condition = edges
         self.num_guard = ng
         self.num_cons = nq
         self.xp = xp
         self.patches = []
+        self.num_iterations = 0
         ni, nj = mesh.shape
         self.domain_radius = self.mesh.x1
         self.buffer_onset_width = 0.1
 
         if solution is None:
-            primitive = initial_condition(setup, mesh, time)
+            primitive = initial_condition(setup, mesh, time, nq)
+            primitive[..., 1] += options.boost_vx
+            primitive[..., 2] += options.boost_vy
         else:
             primitive = solution
 
+        if options.boost_vx != 0.0 or options.boost_vy != 0.0:
+            logger.info(f"boost frame by ({options.boost_vx}, {options.boost_vy})")
+
+            if physics.num_particles > 0 or physics.buffer_is_enabled:
+                logger.warning("frame boost is not applied to point masses or buffer")
+
         if physics.buffer_is_enabled:
             # Here we sample the initial condition at the buffer onset radius
             # to determine the disk surface density at the radius where the
@@ -306,9 +649,22 @@ class Solver(SolverBase):
             buffer_surface_density = 0.0
             buffer_surface_pressure = 0.0
 
-        for n, (a, b) in enumerate(subdivide(ni, num_patches)):
-            prim = np.zeros([b - a + 2 * ng, nj + 2 * ng, nq])
-            prim[ng:-ng, ng:-ng] = primitive[a:b]
+        padded = np.zeros([ni + 2 * ng, nj + 2 * ng, nq])
+        padded[ng:-ng, ng:-ng] = primitive
+        fixed_guard_zones(setup, mesh, time, padded, edges, ng, options)
+
+        if cooling:
+            logger.info(f"temperature is relaxed with {physics.cooling_model}")
+
+        # A target temperature function is sampled once, on the host.
+        if physics.cooling_target is not None:
+            x, y = mesh.cell_center_arrays()
+            cooling_target = np.vectorize(physics.cooling_target)(x, y)
+        else:
+            cooling_target = None
+
+        for n, (a, b) in enumerate(mesh.tile(num_patches, ng)):
+            prim = padded[a : b + 2 * ng].copy()
             patch = Patch(
                 time,
                 prim,
@@ -319,25 +675,78 @@ class Solver(SolverBase):
                 buffer_outer_radius,
                 buffer_surface_density,
                 buffer_surface_pressure,
+                None if cooling_target is None else cooling_target[a:b],
                 lib,
                 xp,
                 execution_context(mode, device_id=n % num_devices(mode)),
             )
             self.patches.append(patch)
 
+        self.reinitialize()
+
     @property
     def solution(self):
-        return concat_on_host(
+        """
+        The primitive data in the frame of the kernels, or with live point
+        masses, a dict of that data (`primitive`), and the `TwoBodyProblem`
+        at the current time (`two_body`).
+        """
+        primitive = concat_on_host(
             [p.primitive for p in self.patches], (self.num_guard, self.num_guard)
         )
 
+        if self.live_point_masses is None:
+            return primitive
+
+        two_body = self.live_point_masses.two_body.at(self.time)
+        return dict(primitive=primitive, two_body=two_body)
+
+    def checkpoint_diagnostics(self):
+        if self.live_point_masses is None:
+            return dict()
+        return dict(point_masses=self.live_point_masses(self.time))
+
+    def conserved_array(self):
+        for patch in self.patches:
+            patch.recompute_conserved()
+        return concat_on_host(
+            [p.conserved0 for p in self.patches], (self.num_guard, self.num_guard)
+        )
+
+    @property
+    def primitive_fields(self):
+        fields = [
+            SURFACE_DENSITY,
+            VELOCITY_X,
+            VELOCITY_Y,
+            VERTICALLY_INTEGRATED_PRESSURE,
+        ]
+        if self._options.two_temperature:
+            fields.append(SECOND_PRESSURE)
+        return fields
+
     @property
     def primitive(self):
         """
-        This solver uses primitive data as the solution array.
+        This solver uses primitive data as the solution array. If the frame is
+        boosted, the solution is in the boosted frame, and this property
+        returns the rest-frame primitive data. It also returns the primitive
+        data with live point masses, where the solution is a dict.
         """
+        if self._options.boost_vx != 0.0 or self._options.boost_vy != 0.0:
+            return self.primitive_array()
+        if self.live_point_masses is not None:
+            return self.primitive_array()
         return None
 
+    @property
+    def reduction_names(self):
+        return [d.label for d in self._physics.diagnostics]
+
+    @property
+    def reduction_units(self):
+        return [d.units for d in self._physics.diagnostics]
+
     def reductions(self):
         """
         Generate runtime reductions on the solution data for time series.
@@ -350,7 +759,9 @@ class Solver(SolverBase):
         da = self.mesh.dx * self.mesh.dy
         ng = self.num_guard
 
-        def get_field(patch, quantity, cut, mass, gravity=False, accretion=False):
+        def get_field(
+            patch, quantity, cut, mass, gravity=False, accretion=False, mode=1
+        ):
             """
             Return one of the udot fields: for a particular patch, conserved
             variable quantity, radial cut (optional), and point mass (either
@@ -380,6 +791,11 @@ class Solver(SolverBase):
                 sin_phi = y / r
                 return sigma * (cos_phi + 1.0j * sin_phi)
 
+            if quantity == "vy_mode":
+                vy = patch.primitive[ng:-ng, ng:-ng, 2]
+                k = 2.0 * pi * mode / (self.mesh.x1 - self.mesh.x0)
+                return vy * patch.xp.exp(1.0j * k * x) * (y > 0.0)
+
             if quantity == "eccentricity_vector":
                 sigma = apply_radial_cut(patch.primitive[ng:-ng, ng:-ng, 0])
                 vx = apply_radial_cut(patch.primitive[ng:-ng, ng:-ng, 1])
@@ -391,6 +807,15 @@ class Solver(SolverBase):
                 ey = (v_dot_v * y - v_dot_r * vy) / GM - y / r
                 return sigma * (ex + 1.0j * ey)
 
+            if quantity == "angular_momentum":
+                sigma = apply_radial_cut(patch.primitive[ng:-ng, ng:-ng, 0])
+                vx = patch.primitive[ng:-ng, ng:-ng, 1] - self._options.boost_vx
+                vy = patch.primitive[ng:-ng, ng:-ng, 2] - self._options.boost_vy
+                return sigma * (x * vy - y * vx)
+
+            if quantity == "mass":
+                return apply_radial_cut(patch.primitive[ng:-ng, ng:-ng, 0])
+
             q = quantity
             i = self.patches.index(patch)
 
@@ -421,7 +846,12 @@ class Solver(SolverBase):
                         d.which_mass,
                         gravity=d.gravity,
                         accretion=d.accretion,
+                        mode=d.mode_number,
                     )
+                    if d.hill_cut is not None:
+                        x, y = p.cell_center_coordinate_arrays
+                        m1, m2 = self._physics.point_masses(self.time)
+                        f = f * hill_mask(x, y, m1, m2, d.hill_cut)
                     result.append(f.sum())
             return result
 
@@ -442,10 +872,110 @@ class Solver(SolverBase):
 
         return pass2
 
+    def primitive_array(self, guard_zones=False):
+        """
+        Return primitive data in the rest frame, i.e. with the frame boost
+        (the `boost_vx` and `boost_vy` options) subtracted from the velocity.
+        The solution array is not un-boosted, so it can be used for restarts.
+        """
+        if guard_zones:
+            self.set_bc("primitive1")
+        primitive = concat_on_host(
+            [p.primitive for p in self.patches],
+            (self.num_guard, self.num_guard),
+            outer_guard=guard_zones,
+        )
+        primitive[..., 1] -= self._options.boost_vx
+        primitive[..., 2] -= self._options.boost_vy
+        return primitive
+
+    def rest_frame_patches(self):
+        """
+        Return the rest-frame data of each patch, on its device. See
+        `SolverBase.rest_frame_patches`.
+        """
+        ng = self.num_guard
+        result = list()
+
+        for patch in self.patches:
+            with patch.execution_context:
+                x, y = patch.cell_center_coordinate_arrays
+                sigma = patch.primitive[ng:-ng, ng:-ng, 0]
+                vx = patch.primitive[ng:-ng, ng:-ng, 1] - self._options.boost_vx
+                vy = patch.primitive[ng:-ng, ng:-ng, 2] - self._options.boost_vy
+            result.append(
+                dict(
+                    sigma=sigma,
+                    vx=vx,
+                    vy=vy,
+                    x=x,
+                    y=y,
+                    xp=patch.xp,
+                    execution_context=patch.execution_context,
+                )
+            )
+        return result
+
+    def sample(self, x, y):
+        """
+        Return the rest-frame primitive data interpolated bilinearly at
+        points. See `SolverBase.sample`.
+        """
+        if not isinstance(self.mesh, PlanarCartesian2DMesh):
+            raise NotImplementedError(f"can't sample the mesh {self.mesh}")
+
+        stencil = self.mesh.interpolation_stencil(x, y)
+        self.set_bc("primitive1")
+        result = sample_on_host(self.patches, self.num_guard, stencil, self.num_cons)
+        result[:, 1] -= self._options.boost_vx
+        result[:, 2] -= self._options.boost_vy
+        return result
+
+    def set_primitive(self, primitive):
+        """
+        Replace the solution with rest-frame primitive data of shape
+        `(ni, nj, nq)`, and re-derive the guard zones and conserved data from
+        it. The guard zones of fixed edges keep the setup's values.
+        """
+        ni, nj = self.mesh.shape
+        nq = self.num_cons
+        ng = self.num_guard
+
+        if primitive.shape != (ni, nj, nq):
+            raise ValueError(
+                f"primitive data has shape {primitive.shape}, expected {(ni, nj, nq)}"
+            )
+
+        primitive = primitive.copy()
+        primitive[..., 1] += self._options.boost_vx
+        primitive[..., 2] += self._options.boost_vy
+        i0 = 0
+
+        for patch in self.patches:
+            i1 = i0 + patch.shape[0]
+            with patch.execution_context:
+                patch.primitive1[ng:-ng, ng:-ng] = self.xp.asarray(primitive[i0:i1])
+            i0 = i1
+
+        self.reinitialize()
+
+    def reinitialize(self):
+        """
+        Fill the guard zones, and re-derive the conserved data on each patch.
+        """
+        self.set_bc("primitive1")
+
+        for patch in self.patches:
+            patch.reinitialize()
+
     @property
     def time(self):
         return self.patches[0].time
 
+    @property
+    def nominal_time(self):
+        return self.patches[0].clock.nominal_time
+
     @property
     def options(self):
         return self._options._asdict()
@@ -456,7 +986,7 @@ class Solver(SolverBase):
 
     @property
     def recommended_cfl(self):
-        return 0.1
+        return self._options.cfl_number or 0.1
 
     @property
     def maximum_cfl(self):
@@ -470,11 +1000,66 @@ class Solver(SolverBase):
             (patch.execution_context for patch in self.patches),
         )
 
+    def recommended_timestep(self, min_spacing, cfl_number):
+        """
+        Return the CFL time step, limited to a fraction `cfl_number` of the
+        energy exchange time if there's a second energy component.
+        """
+        dt = super().recommended_timestep(min_spacing, cfl_number)
+        rate = self._options.energy_exchange_rate
+
+        if self._options.two_temperature and rate > 0.0:
+            dt = min(dt, cfl_number / rate)
+
+        return dt
+
     def advance(self, dt):
+        self.num_iterations += 1
+        retries = self._options.max_step_retries
+        cadence = self._options.invalid_state_cadence
+
+        if retries:
+            advance_with_retries(self, dt, retries)
+            return
+
+        self.take_step(dt)
+
+        if cadence and self.num_iterations % cadence == 0:
+            check_invalid_zones(self.patches, self.mesh, self.time)
+
+    def take_step(self, dt):
         self.new_iteration()
         self.advance_rk(0.0, dt)
         self.advance_rk(0.5, dt)
 
+        if self._physics.cooling_model != CoolingModel.NONE:
+            for patch in self.patches:
+                patch.cooling_step(dt)
+
+    def save_step(self):
+        """
+        Return copies of the patches' primitive data and clocks, from which a
+        failed step is retried.
+        """
+        saved = list()
+
+        for patch in self.patches:
+            with patch.execution_context:
+                saved.append((patch.primitive1.copy(), copy(patch.clock)))
+
+        return saved
+
+    def restore_step(self, saved):
+        """
+        Return the solution to a state from `save_step`, and discard the
+        invalid zone flags of the steps taken since.
+        """
+        for patch, (primitive, clock) in zip(self.patches, saved):
+            with patch.execution_context:
+                patch.primitive1[...] = primitive
+                patch.clock = copy(clock)
+                patch.invalid_zones[...] = 0.0
+
     def advance_rk(self, rk_param, dt):
         self.set_bc("primitive1")
         for patch in self.patches:
@@ -492,29 +1077,32 @@ class Solver(SolverBase):
             self.set_bc_patch(pl, pc, pr, i0)
 
     def set_bc_patch(self, pl, pc, pr, patch_index):
-        ni, nj = self.mesh.shape
         ng = self.num_guard
+        (bcxl, bcxr), (bcyl, bcyr) = self.boundary_condition
+        first = patch_index == 0
+        last = patch_index == len(self.patches) - 1
 
         with self.patches[patch_index].execution_context:
-            # 1. write to the guard zones of pc, the internal BC
-            pc[:+ng] = pl[-2 * ng : -ng]
-            pc[-ng:] = pr[+ng : +2 * ng]
-
-            # 2. Set outflow BC on the left/right patch edges
-            if patch_index == 0:
-                for i in range(ng):
-                    pc[i] = pc[ng]
-            if patch_index == len(self.patches) - 1:
-                for i in range(pc.shape[0] - ng, pc.shape[0]):
-                    pc[i] = pc[-ng - 1]
-
-            # 3. Set outflow BC on bottom and top edges
-            for i in range(ng):
-                pc[:, i] = pc[:, ng]
-
-            for i in range(pc.shape[1] - ng, pc.shape[1]):
-                pc[:, i] = pc[:, -ng - 1]
+            # 1. write to the guard zones of pc, the internal BC; for periodic
+            # x-boundaries this also wraps around the first and last patch
+            if not first or bcxl == BoundaryCondition.PERIODIC:
+                copy_between_devices(pc[:+ng], pl[-2 * ng : -ng])
+            if not last or bcxr == BoundaryCondition.PERIODIC:
+                copy_between_devices(pc[-ng:], pr[+ng : +2 * ng])
+
+            # 2. Set the BC on the left/right patch edges
+            if first:
+                fill_guard_zones(pc, 0, 0, bcxl, ng)
+            if last:
+                fill_guard_zones(pc, 0, 1, bcxr, ng)
+
+            # 3. Set the BC on bottom and top edges
+            fill_guard_zones(pc, 1, 0, bcyl, ng)
+            fill_guard_zones(pc, 1, 1, bcyr, ng)
 
     def new_iteration(self):
+        if self.live_point_masses is not None:
+            self.live_point_masses.new_iteration(self.time)
+
         for patch in self.patches:
             patch.new_iteration()
@@ -0,0 +1,305 @@
+"""
+Two coupled isothermal layers for the binary accretion problem in 2D.
+
+The upper and lower halves of a disk are evolved as separate 2D fluids on the
+same mesh, each with the `cbdiso_2d` solver, and half of the setup's surface
+density. This is a cheap stand-in for a mildly warped or inclined disk,
+before a full 3D solver exists. The layers interact through two terms, which
+are applied after each iteration, and integrated exactly over it:
+
+- Drag, at the physics parameter `layer_drag_rate`: the velocity difference
+  of the layers decays at this rate, conserving their total momentum.
+- Mass exchange, at `layer_exchange_rate`: the surface density difference
+  decays at this rate, and the gas moved carries its velocity and tracers.
+
+The plane of the upper layer is tilted about the x axis by
+`layer_inclination` degrees, relative to the binary and the lower layer. It
+sees the point masses projected onto its plane, with their height above it
+added to their softening (see
+:py:class:`sailfish.physics.circumbinary.InclinedPointMasses`), so with no
+coupling, each layer is the disk it would be alone at its inclination.
+
+The primitive fields are those of `cbdiso_2d` for the upper layer, followed
+by those of the lower layer, with the suffixes `_upper` and `_lower`, e.g.
+`sigma_upper`. The time series diagnostics are also recorded for each layer.
+"""
+
+from logging import getLogger
+from typing import NamedTuple
+from sailfish.fields import Field
+from sailfish.physics.circumbinary import InclinedPointMasses
+from sailfish.solver_base import SolverBase
+from sailfish.solvers import cbdiso_2d
+
+logger = getLogger(__name__)
+
+Options = cbdiso_2d.Options
+
+LAYER_NAMES = ("upper", "lower")
+
+
+class LayerCoupling(NamedTuple):
+    """
+    The physics parameters of the two-layer model, which are taken out of
+    the physics dictionary before the rest are given to each layer.
+    """
+
+    layer_drag_rate: float = 0.0
+    """ Rate at which the velocity difference of the layers decays """
+
+    layer_exchange_rate: float = 0.0
+    """ Rate at which the surface density difference of the layers decays """
+
+    layer_inclination: float = 0.0
+    """ Inclination in degrees of the upper layer to the binary plane """
+
+
+class LayerSetup:
+    """
+    A setup whose initial surface density is half that of another one, and
+    which is otherwise the same.
+    """
+
+    def __init__(self, setup):
+        self.setup = setup
+
+    def __getattr__(self, name):
+        return getattr(self.setup, name)
+
+    def primitive(self, time, coords, primitive):
+        self.setup.primitive(time, coords, primitive)
+        primitive[0] *= 0.5
+
+
+class Solver(SolverBase):
+    """
+    Couples two `cbdiso_2d` solvers, one for each layer.
+    """
+
+    def __init__(
+        self,
+        setup=None,
+        mesh=None,
+        time=0.0,
+        solution=None,
+        num_patches=1,
+        mode="cpu",
+        physics=dict(),
+        options=dict(),
+    ):
+        physics = dict(physics)
+        coupling = LayerCoupling(
+            **{k: physics.pop(k) for k in LayerCoupling._fields if k in physics}
+        )
+
+        if min(coupling.layer_drag_rate, coupling.layer_exchange_rate) < 0.0:
+            raise ValueError("layer coupling rates must be non-negative")
+
+        if not 0.0 <= coupling.layer_inclination < 90.0:
+            raise ValueError("layer_inclination must be in [0, 90)")
+
+        if physics.get("live_point_masses", False):
+            raise ValueError("solver does not support live point masses")
+
+        if physics.get("softening_zones") is not None:
+            raise ValueError("solver does not support softening_zones")
+
+        if solution is None:
+            solution = dict(upper=None, lower=None)
+
+        self.coupling = coupling
+        self.mesh = mesh
+        self.num_guard = 2
+        self._physics = dict(physics, **coupling._asdict())
+        self.layers = list()
+
+        for name, inclination in zip(LAYER_NAMES, (coupling.layer_inclination, 0.0)):
+            layer_physics = dict(physics)
+
+            if inclination != 0.0 and physics.get("point_mass_function") is not None:
+                layer_physics["point_mass_function"] = InclinedPointMasses(
+                    inclination, physics["point_mass_function"]
+                )
+
+            self.layers.append(
+                cbdiso_2d.Solver(
+                    setup=LayerSetup(setup),
+                    mesh=mesh,
+                    time=time,
+                    solution=solution[name],
+                    num_patches=num_patches,
+                    mode=mode,
+                    physics=layer_physics,
+                    options=options,
+                )
+            )
+
+        logger.info(f"layer coupling is {coupling}")
+
+    @property
+    def solution(self):
+        """
+        A dict of the solution of each layer, keyed by `upper` and `lower`.
+        """
+        return {n: layer.solution for n, layer in zip(LAYER_NAMES, self.layers)}
+
+    @property
+    def primitive_fields(self):
+        return [
+            Field(f"{f.name}_{n}", f"{f.description} in the {n} layer", f.units)
+            for n, layer in zip(LAYER_NAMES, self.layers)
+            for f in layer.primitive_fields
+        ]
+
+    @property
+    def primitive(self):
+        return self.primitive_array()
+
+    def primitive_array(self, guard_zones=False):
+        """
+        Return the rest-frame primitive data of both layers, concatenated on
+        the field axis.
+        """
+        import numpy as np
+
+        return np.concatenate(
+            [layer.primitive_array(guard_zones) for layer in self.layers], axis=-1
+        )
+
+    def conserved_array(self):
+        import numpy as np
+
+        return np.concatenate([layer.conserved_array() for layer in self.layers], -1)
+
+    def sample(self, x, y):
+        import numpy as np
+
+        return np.concatenate([layer.sample(x, y) for layer in self.layers], axis=1)
+
+    def set_primitive(self, primitive):
+        nq = self.layers[0].num_cons
+
+        for n, layer in enumerate(self.layers):
+            layer.set_primitive(primitive[..., n * nq : (n + 1) * nq])
+
+    def reset(self, primitive=None, time=None):
+        nq = self.layers[0].num_cons
+
+        for n, layer in enumerate(self.layers):
+            if primitive is None:
+                layer.reset(None, time)
+            else:
+                layer.reset(primitive[..., n * nq : (n + 1) * nq], time)
+
+    def rest_frame_patches(self):
+        """
+        Return the rest-frame patches of both layers, so the emission from
+        each of them is included in the synthetic observables.
+        """
+        return [p for layer in self.layers for p in layer.rest_frame_patches()]
+
+    def reinitialize(self):
+        for layer in self.layers:
+            layer.reinitialize()
+
+    @property
+    def reduction_names(self):
+        return [
+            f"{name}_{n}"
+            for n, layer in zip(LAYER_NAMES, self.layers)
+            for name in layer.reduction_names
+        ]
+
+    @property
+    def reduction_units(self):
+        return [u for layer in self.layers for u in layer.reduction_units]
+
+    def reductions(self):
+        return [x for layer in self.layers for x in layer.reductions()]
+
+    def deferred_reductions(self):
+        return [x for layer in self.layers for x in layer.deferred_reductions()]
+
+    @property
+    def time(self):
+        return self.layers[0].time
+
+    @property
+    def nominal_time(self):
+        return self.layers[0].nominal_time
+
+    @property
+    def options(self):
+        return self.layers[0].options
+
+    @property
+    def physics(self):
+        return self._physics
+
+    @property
+    def recommended_cfl(self):
+        return self.layers[0].recommended_cfl
+
+    @property
+    def maximum_cfl(self):
+        return self.layers[0].maximum_cfl
+
+    def maximum_wavespeed(self):
+        return max(layer.maximum_wavespeed() for layer in self.layers)
+
+    def recommended_timestep(self, min_spacing, cfl_number):
+        return min(
+            layer.recommended_timestep(min_spacing, cfl_number)
+            for layer in self.layers
+        )
+
+    def advance(self, dt):
+        for layer in self.layers:
+            layer.advance(dt)
+
+        if max(self.coupling.layer_drag_rate, self.coupling.layer_exchange_rate) > 0.0:
+            self.couple_layers(dt)
+
+    def couple_layers(self, dt):
+        """
+        Apply the mass exchange and then the drag between the layers over a
+        time `dt`, on the devices the patches are on.
+
+        The layers are subdivided in the same way, so their patches pair up
+        on the same devices. Guard zones and conserved data are re-derived
+        afterwards, keeping the recorded face fluxes.
+        """
+        from math import exp
+
+        ng = self.num_guard
+        fx = 0.5 * (1.0 - exp(-self.coupling.layer_exchange_rate * dt))
+        fv = exp(-self.coupling.layer_drag_rate * dt)
+        upper, lower = self.layers
+
+        for pu, pl in zip(upper.patches, lower.patches):
+            with pu.execution_context:
+                xp = pu.xp
+                a = pu.primitive1[ng:-ng, ng:-ng]
+                b = pl.primitive1[ng:-ng, ng:-ng]
+
+                # Mass moved from the upper to the lower layer (negative if
+                # it's the other way), and the primitive data of its donor.
+                dm = fx * (a[..., 0] - b[..., 0])
+                donor = xp.where((dm > 0.0)[..., None], a, b)
+                sa = a[..., 0] - dm
+                sb = b[..., 0] + dm
+                qa = a[..., 0, None] * a[..., 1:] - dm[..., None] * donor[..., 1:]
+                qb = b[..., 0, None] * b[..., 1:] + dm[..., None] * donor[..., 1:]
+                va = qa[..., :2] / sa[..., None]
+                vb = qb[..., :2] / sb[..., None]
+                vc = (qa[..., :2] + qb[..., :2]) / (sa + sb)[..., None]
+
+                a[..., 0] = sa
+                b[..., 0] = sb
+                a[..., 1:3] = vc + (va - vc) * fv
+                b[..., 1:3] = vc + (vb - vc) * fv
+                a[..., 3:] = qa[..., 2:] / sa[..., None]
+                b[..., 3:] = qb[..., 2:] / sb[..., None]
+
+        for layer in self.layers:
+            layer.reinitialize(discard_face_fluxes=False)
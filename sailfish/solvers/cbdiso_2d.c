@@ -7,9 +7,31 @@ DESCRIPTION: Isothermal solver for a binary accretion problem in 2D planar
 
 // ============================ PHYSICS =======================================
 // ============================================================================
-#define NCONS 3
 #define PLM_THETA 1.8
 
+// With NCONS > 3, the extra fields are passive scalars, e.g. to tag gas by
+// where it started. Their primitive values are concentrations per unit mass,
+// and their conserved values are those times the surface density. They are
+// carried by the upwinded mass flux, and removed or added with the mass by
+// the sinks and the buffer, so a uniform concentration stays uniform.
+#ifndef NCONS
+#define NCONS 3
+#endif
+#define NHYDRO 3
+
+// The terms of the conservation audit, whose time-integrated changes of the
+// mass and momentum in each zone are recorded by the RK update: the flux
+// divergence, which sums to the flux through the domain boundary and into
+// masked zones, the point mass gravity, the sinks, the Keplerian and target
+// buffers, the rotating frame forces, and the floor and ceiling.
+#define BUDGET_BOUNDARY 0
+#define BUDGET_GRAVITY 1
+#define BUDGET_SINKS 2
+#define BUDGET_BUFFERS 3
+#define BUDGET_FRAME 4
+#define BUDGET_FLOORS 5
+#define NBUDGET 6
+
 
 // ============================ MATH ==========================================
 // ============================================================================
@@ -20,15 +42,42 @@ DESCRIPTION: Isothermal solver for a binary accretion problem in 2D planar
 #define sign(x) copysign(1.0, x)
 #define minabs(a, b, c) min3(fabs(a), fabs(b), fabs(c))
 
-PRIVATE double plm_gradient_scalar(double yl, double y0, double yr)
+// The updates of the relaxation terms, the sinks and the buffers, which
+// drive the solution toward a state at some rate: explicit (the forward
+// Euler update of the RK stage), sub-cycled in steps of at most
+// SUBCYCLE_LIMIT / rate, or exponential. The term is linear in the conserved
+// state over a stage, so the sub-steps are taken in closed form, and either
+// of the last two is stable for any rate * dt.
+#define SOURCE_UPDATE_EXPLICIT 0
+#define SOURCE_UPDATE_SUBCYCLE 1
+#define SOURCE_UPDATE_EXPONENTIAL 2
+#define SUBCYCLE_LIMIT 0.5
+
+PRIVATE real relaxation_time_step(real rate, real dt, int update)
+{
+    // The time step which the explicit update of a relaxation at the given
+    // rate is taken with, to give the update chosen.
+    if (rate <= 0.0 || update == SOURCE_UPDATE_EXPLICIT)
+    {
+        return dt;
+    }
+    if (update == SOURCE_UPDATE_SUBCYCLE)
+    {
+        int n = (int) ceil(rate * dt / SUBCYCLE_LIMIT);
+        return (1.0 - pow(1.0 - rate * dt / n, n)) / rate;
+    }
+    return -expm1(-rate * dt) / rate;
+}
+
+PRIVATE real plm_gradient_scalar(real yl, real y0, real yr)
 {
-    double a = (y0 - yl) * PLM_THETA;
-    double b = (yr - yl) * 0.5;
-    double c = (yr - y0) * PLM_THETA;
+    real a = (y0 - yl) * PLM_THETA;
+    real b = (yr - yl) * 0.5;
+    real c = (yr - y0) * PLM_THETA;
     return 0.25 * fabs(sign(a) + sign(b)) * (sign(a) + sign(c)) * minabs(a, b, c);
 }
 
-PRIVATE void plm_gradient(double *yl, double *y0, double *yr, double *g)
+PRIVATE void plm_gradient(real *yl, real *y0, real *yr, real *g)
 {
     for (int q = 0; q < NCONS; ++q)
     {
@@ -40,15 +89,17 @@ PRIVATE void plm_gradient(double *yl, double *y0, double *yr, double *g)
 // ============================ INTERNAL STRUCTS ==============================
 // ============================================================================
 struct PointMass {
-    double x;
-    double y;
-    double vx;
-    double vy;
-    double mass;
-    double softening_length;
-    double sink_rate;
-    double sink_radius;
+    real x;
+    real y;
+    real vx;
+    real vy;
+    real mass;
+    real softening_length;
+    int softening_model;
+    real sink_rate;
+    real sink_radius;
     int sink_model;
+    int sink_update;
 };
 
 struct PointMassList {
@@ -56,39 +107,110 @@ struct PointMassList {
 };
 
 struct KeplerianBuffer {
-    double surface_density;
-    double central_mass;
-    double driving_rate;
-    double outer_radius;
-    double onset_width;
+    real surface_density;
+    real central_mass;
+    real driving_rate;
+    real outer_radius;
+    real onset_width;
     int is_enabled;
+    real frame_rotation;
+    int update;
 };
 
 
 // ============================ GRAVITY =======================================
 // ============================================================================
-PRIVATE double gravitational_potential(
+// The softening models of the point masses (see SofteningModel in
+// sailfish/physics/circumbinary.py): Plummer, the cubic spline kernel of
+// Springel et al. (2001), which is exactly Newtonian beyond 2.8 softening
+// lengths and has the Plummer potential depth at the point mass, or none.
+#define SOFTENING_PLUMMER 0
+#define SOFTENING_SPLINE 1
+#define SOFTENING_NONE 2
+#define SPLINE_RADIUS 2.8
+#define MIN_RADIUS_SQUARED 1e-24
+
+PRIVATE real softened_potential(real mass, real rs, int model, real r2)
+{
+    r2 = max2(r2, MIN_RADIUS_SQUARED);
+
+    switch (model)
+    {
+        case SOFTENING_SPLINE:
+        {
+            real h = SPLINE_RADIUS * rs;
+            real r = sqrt(r2);
+
+            if (r >= h)
+            {
+                return -mass / r;
+            }
+            real u = r / h;
+
+            if (u < 0.5)
+            {
+                return mass / h * (-2.8 + u * u * (16.0 / 3.0 + u * u * (6.4 * u - 9.6)));
+            }
+            return mass / h * (-3.2 + 1.0 / (15.0 * u) + u * u * (32.0 / 3.0 + u * (-16.0 + u * (9.6 - 32.0 / 15.0 * u))));
+        }
+        case SOFTENING_NONE:
+            return -mass / sqrt(r2);
+        default:
+            return -mass / sqrt(r2 + rs * rs);
+    }
+}
+
+// The magnitude of the gravitational acceleration, divided by the distance
+// from the point mass; the acceleration is -g (dx, dy), and g is the squared
+// Keplerian frequency.
+PRIVATE real softened_acceleration(real mass, real rs, int model, real r2)
+{
+    r2 = max2(r2, MIN_RADIUS_SQUARED);
+
+    switch (model)
+    {
+        case SOFTENING_SPLINE:
+        {
+            real h = SPLINE_RADIUS * rs;
+            real r = sqrt(r2);
+
+            if (r >= h)
+            {
+                return mass / (r2 * r);
+            }
+            real u = r / h;
+            real h3 = h * h * h;
+
+            if (u < 0.5)
+            {
+                return mass / h3 * (32.0 / 3.0 + u * u * (32.0 * u - 38.4));
+            }
+            return mass / h3 * (64.0 / 3.0 - 48.0 * u + 38.4 * u * u - 32.0 / 3.0 * u * u * u - 1.0 / (15.0 * u * u * u));
+        }
+        case SOFTENING_NONE:
+            return mass * pow(r2, -1.5);
+        default:
+            return mass * pow(r2 + rs * rs, -1.5);
+    }
+}
+
+PRIVATE real gravitational_potential(
     struct PointMassList *mass_list,
-    double x1,
-    double y1)
+    real x1,
+    real y1)
 {
-    double phi = 0.0;
+    real phi = 0.0;
 
     for (int p = 0; p < 2; ++p)
     {
         if (mass_list->masses[p].mass > 0.0)
         {
-            double x0 = mass_list->masses[p].x;
-            double y0 = mass_list->masses[p].y;
-            double mp = mass_list->masses[p].mass;
-            double rs = mass_list->masses[p].softening_length;
+            struct PointMass *m = &mass_list->masses[p];
+            real dx = x1 - m->x;
+            real dy = y1 - m->y;
+            real r2 = dx * dx + dy * dy;
 
-            double dx = x1 - x0;
-            double dy = y1 - y0;
-            double r2 = dx * dx + dy * dy;
-            double r2_softened = r2 + rs * rs;
-
-            phi -= mp / sqrt(r2_softened);
+            phi += softened_potential(m->mass, m->softening_length, m->softening_model, r2);
         }
     }
     return phi;
@@ -96,27 +218,29 @@ PRIVATE double gravitational_potential(
 
 PRIVATE void point_mass_source_term(
     struct PointMass *mass,
-    double x1,
-    double y1,
-    double dt,
-    double *prim,
-    double *delta_cons)
-{
-    double x0 = mass->x;
-    double y0 = mass->y;
-    double sigma = prim[0];
-    double dx = x1 - x0;
-    double dy = y1 - y0;
-    double r2 = dx * dx + dy * dy;
-    double dr = sqrt(r2);
-    double r_sink = mass->sink_radius;
-    double r_soft = mass->softening_length;
-
-    double fgrav_numerator = sigma * mass->mass * pow(r2 + r_soft * r_soft, -1.5);
-    double fx = -fgrav_numerator * dx;
-    double fy = -fgrav_numerator * dy;
-    double sink_rate = (dr < 4.0 * r_sink) ? mass->sink_rate * exp(-pow(dr / r_sink, 4.0)) : 0.0;
-    double mdot = 0.0;
+    real x1,
+    real y1,
+    real dt,
+    real *prim,
+    real *delta_grav,
+    real *delta_sink)
+{
+    real x0 = mass->x;
+    real y0 = mass->y;
+    real sigma = prim[0];
+    real dx = x1 - x0;
+    real dy = y1 - y0;
+    real r2 = dx * dx + dy * dy;
+    real dr = sqrt(r2);
+    real r_sink = mass->sink_radius;
+    real r_soft = mass->softening_length;
+
+    real fgrav_numerator = sigma * softened_acceleration(mass->mass, r_soft, mass->softening_model, r2);
+    real fx = -fgrav_numerator * dx;
+    real fy = -fgrav_numerator * dy;
+    real sink_rate = (dr < 4.0 * r_sink) ? mass->sink_rate * exp(-pow(dr / r_sink, 4.0)) : 0.0;
+    real sink_dt = relaxation_time_step(sink_rate, dt, mass->sink_update);
+    real mdot = 0.0;
 
     if (sink_rate > 0.0)
     {
@@ -128,47 +252,47 @@ PRIVATE void point_mass_source_term(
     }
 
     // gravitational force
-    delta_cons[0] += 0.0;
-    delta_cons[1] += fx * dt;
-    delta_cons[2] += fy * dt;
+    delta_grav[0] += 0.0;
+    delta_grav[1] += fx * dt;
+    delta_grav[2] += fy * dt;
 
     switch (mass->sink_model)
     {
         case 1: // acceleration-free
         {
-            delta_cons[0] += dt * mdot;
-            delta_cons[1] += dt * mdot * prim[1];
-            delta_cons[2] += dt * mdot * prim[2];
+            delta_sink[0] += sink_dt * mdot;
+            delta_sink[1] += sink_dt * mdot * prim[1];
+            delta_sink[2] += sink_dt * mdot * prim[2];
             break;
         }
         case 2: // torque-free
         {
-            double vx = prim[1];
-            double vy = prim[2];
-            double vx0 = mass->vx;
-            double vy0 = mass->vy;
-            double rhatx = dx / (dr + 1e-12);
-            double rhaty = dy / (dr + 1e-12);
-            double dvdotrhat = (vx - vx0) * rhatx + (vy - vy0) * rhaty;
-            double vxstar = dvdotrhat * rhatx + vx0;
-            double vystar = dvdotrhat * rhaty + vy0;
-            delta_cons[0] += dt * mdot;
-            delta_cons[1] += dt * mdot * vxstar;
-            delta_cons[2] += dt * mdot * vystar;
+            real vx = prim[1];
+            real vy = prim[2];
+            real vx0 = mass->vx;
+            real vy0 = mass->vy;
+            real rhatx = dx / (dr + 1e-12);
+            real rhaty = dy / (dr + 1e-12);
+            real dvdotrhat = (vx - vx0) * rhatx + (vy - vy0) * rhaty;
+            real vxstar = dvdotrhat * rhatx + vx0;
+            real vystar = dvdotrhat * rhaty + vy0;
+            delta_sink[0] += sink_dt * mdot;
+            delta_sink[1] += sink_dt * mdot * vxstar;
+            delta_sink[2] += sink_dt * mdot * vystar;
             break;
         }
         case 3: // force-free
         {
-            delta_cons[0] += dt * mdot;
-            delta_cons[1] += 0.0;
-            delta_cons[2] += 0.0;
+            delta_sink[0] += sink_dt * mdot;
+            delta_sink[1] += 0.0;
+            delta_sink[2] += 0.0;
             break;
         }
         default: // sink is inactive
         {
-            delta_cons[0] += 0.0;
-            delta_cons[1] += 0.0;
-            delta_cons[2] += 0.0;
+            delta_sink[0] += 0.0;
+            delta_sink[1] += 0.0;
+            delta_sink[2] += 0.0;
             break;
         }
     }
@@ -176,28 +300,55 @@ PRIVATE void point_mass_source_term(
 
 PRIVATE void point_masses_source_term(
     struct PointMassList *mass_list,
-    double x1,
-    double y1,
-    double dt,
-    double *prim,
-    double *delta_cons)
+    real x1,
+    real y1,
+    real dt,
+    real *prim,
+    real *delta_grav,
+    real *delta_sink)
 {
+    // The gravity and sink terms are added to separate arrays for the
+    // conservation audit, or to the same one.
     for (int p = 0; p < 2; ++p)
     {
-        point_mass_source_term(&mass_list->masses[p], x1, y1, dt, prim, delta_cons);
+        point_mass_source_term(&mass_list->masses[p], x1, y1, dt, prim, delta_grav, delta_sink);
     }
 }
 
 
 // ============================ EOS AND BUFFER ================================
 // ============================================================================
-PRIVATE double sound_speed_squared(
-    double cs2,
-    double mach_squared,
+// A table of the squared sound speed at the radii n * dr from the origin,
+// for an isothermal EOS with a radial profile. It's interpolated linearly,
+// and constant beyond its last radius.
+struct SoundSpeedProfile
+{
+    real dr;
+    int size;
+    real *cs2;
+};
+
+PRIVATE real profile_sound_speed_squared(struct SoundSpeedProfile *profile, real r)
+{
+    real s = r / profile->dr;
+    int n = (int) s;
+
+    if (n >= profile->size - 1)
+    {
+        return profile->cs2[profile->size - 1];
+    }
+    real w = s - n;
+    return profile->cs2[n] * (1.0 - w) + profile->cs2[n + 1] * w;
+}
+
+PRIVATE real sound_speed_squared(
+    real cs2,
+    real mach_squared,
     int eos_type,
-    double x,
-    double y,
-    struct PointMassList *mass_list)
+    real x,
+    real y,
+    struct PointMassList *mass_list,
+    struct SoundSpeedProfile *profile)
 {
     switch (eos_type)
     {
@@ -205,57 +356,152 @@ PRIVATE double sound_speed_squared(
             return cs2;
         case 2: // locally Isothermal
             return -gravitational_potential(mass_list, x, y) / mach_squared;
+        case 4: // isothermal with a radial profile
+            return profile_sound_speed_squared(profile, sqrt(x * x + y * y));
         default:
             return 1.0; // WARNING
     }
 }
 
+PRIVATE real kinematic_viscosity(
+    real nu,
+    real alpha,
+    real cs2,
+    real x,
+    real y,
+    struct PointMassList *mass_list)
+{
+    // With alpha > 0, nu = alpha cs h = alpha cs^2 / Omega, where Omega is
+    // the Keplerian frequency due to the point masses, with the same
+    // softening as their potential. Without point masses, it's nu.
+    if (alpha > 0.0)
+    {
+        real omega2 = 0.0;
+
+        for (int p = 0; p < 2; ++p)
+        {
+            if (mass_list->masses[p].mass > 0.0)
+            {
+                struct PointMass *m = &mass_list->masses[p];
+                real dx = x - m->x;
+                real dy = y - m->y;
+                omega2 += softened_acceleration(m->mass, m->softening_length, m->softening_model, dx * dx + dy * dy);
+            }
+        }
+
+        if (omega2 > 0.0)
+        {
+            return alpha * cs2 / sqrt(omega2);
+        }
+    }
+    return nu;
+}
+
 PRIVATE void buffer_source_term(
     struct KeplerianBuffer *buffer,
-    double xc,
-    double yc,
-    double dt,
-    double *cons,
-    double *delta_cons)
+    real xc,
+    real yc,
+    real dt,
+    real *cons,
+    real *delta_cons)
 {
     if (buffer->is_enabled)
     {
-        double rc = sqrt(xc * xc + yc * yc);
-        double surface_density = buffer->surface_density;
-        double central_mass = buffer->central_mass;
-        double driving_rate = buffer->driving_rate;
-        double outer_radius = buffer->outer_radius;
-        double onset_width = buffer->onset_width;
-        double onset_radius = outer_radius - onset_width;
+        real rc = sqrt(xc * xc + yc * yc);
+        real surface_density = buffer->surface_density;
+        real central_mass = buffer->central_mass;
+        real driving_rate = buffer->driving_rate;
+        real outer_radius = buffer->outer_radius;
+        real onset_width = buffer->onset_width;
+        real onset_radius = outer_radius - onset_width;
 
         if (rc > onset_radius)
         {
-            double v_kep = sqrt(central_mass / rc);
-            double px = surface_density * (-yc / rc) * v_kep;
-            double py = surface_density * (+xc / rc) * v_kep;
-            double u0[NCONS] = {surface_density, px, py};
-            double omega_outer = sqrt(central_mass * pow(onset_radius, -3.0));
-            double buffer_rate = driving_rate * omega_outer * (rc - onset_radius) / (outer_radius - onset_radius);
-
-            for (int q = 0; q < NCONS; ++q)
+            // The Keplerian velocity, less that of a rotating mesh.
+            real v_kep = sqrt(central_mass / rc);
+            real omega = buffer->frame_rotation;
+            real px = surface_density * ((-yc / rc) * v_kep + omega * yc);
+            real py = surface_density * ((+xc / rc) * v_kep - omega * xc);
+            real u0[NHYDRO] = {surface_density, px, py};
+            real omega_outer = sqrt(central_mass * pow(onset_radius, -3.0));
+            real buffer_rate = driving_rate * omega_outer * (rc - onset_radius) / (outer_radius - onset_radius);
+            real buffer_dt = relaxation_time_step(buffer_rate, dt, buffer->update);
+
+            for (int q = 0; q < NHYDRO; ++q)
             {
-                delta_cons[q] -= (cons[q] - u0[q]) * buffer_rate * dt;
+                delta_cons[q] -= (cons[q] - u0[q]) * buffer_rate * buffer_dt;
             }
         }
     }
 }
 
+PRIVATE void target_source_term(
+    real rate,
+    const real *target_prim,
+    real dt,
+    int update,
+    real *cons,
+    real *delta_cons)
+{
+    // Drive the hydro fields toward a target state at the given rate, e.g.
+    // to damp waves near an inner edge, or toward an arbitrary state given
+    // by the setup.
+    if (rate > 0.0)
+    {
+        real u0[NHYDRO] = {
+            target_prim[0],
+            target_prim[0] * target_prim[1],
+            target_prim[0] * target_prim[2],
+        };
+
+        for (int q = 0; q < NHYDRO; ++q)
+        {
+            delta_cons[q] -= (cons[q] - u0[q]) * rate * relaxation_time_step(rate, dt, update);
+        }
+    }
+}
+
+PRIVATE void rotating_frame_source_term(
+    real omega,
+    real xc,
+    real yc,
+    real dt,
+    real *prim,
+    real *delta_cons)
+{
+    // Coriolis and centrifugal forces in a frame rotating at angular
+    // frequency omega about the origin.
+    if (omega != 0.0)
+    {
+        real f = prim[0] * dt;
+        delta_cons[1] += f * (omega * omega * xc + 2.0 * omega * prim[2]);
+        delta_cons[2] += f * (omega * omega * yc - 2.0 * omega * prim[1]);
+    }
+}
+
+PRIVATE void tracer_source_term(
+    const real *prim,
+    real *delta_cons)
+{
+    // The mass added or removed by the other source terms has the tracer
+    // concentrations of the zone.
+    for (int q = NHYDRO; q < NCONS; ++q)
+    {
+        delta_cons[q] += delta_cons[0] * prim[q];
+    }
+}
+
 PRIVATE void shear_strain(
-    const double *gx,
-    const double *gy,
-    double dx,
-    double dy,
-    double *s)
-{
-    double sxx = 4.0 / 3.0 * gx[1] / dx - 2.0 / 3.0 * gy[2] / dy;
-    double syy =-2.0 / 3.0 * gx[1] / dx + 4.0 / 3.0 * gy[2] / dy;
-    double sxy = 1.0 / 1.0 * gx[2] / dx + 1.0 / 1.0 * gy[1] / dy;
-    double syx = sxy;
+    const real *gx,
+    const real *gy,
+    real dx,
+    real dy,
+    real *s)
+{
+    real sxx = 4.0 / 3.0 * gx[1] / dx - 2.0 / 3.0 * gy[2] / dy;
+    real syy =-2.0 / 3.0 * gx[1] / dx + 4.0 / 3.0 * gy[2] / dy;
+    real sxy = 1.0 / 1.0 * gx[2] / dx + 1.0 / 1.0 * gy[1] / dy;
+    real syx = sxy;
     s[0] = sxx;
     s[1] = sxy;
     s[2] = syx;
@@ -266,39 +512,68 @@ PRIVATE void shear_strain(
 // ============================ HYDRO =========================================
 // ============================================================================
 PRIVATE void conserved_to_primitive(
-    const double *cons,
-    double *prim,
-    double velocity_ceiling,
-    double density_floor)
+    const real *cons,
+    real *prim,
+    real velocity_ceiling,
+    real density_floor)
 {
-    double rho = max2(cons[0], density_floor);
-    double px = cons[1];
-    double py = cons[2];
-    double vx = sign(px) * min2(fabs(px / rho), velocity_ceiling);
-    double vy = sign(py) * min2(fabs(py / rho), velocity_ceiling);
+    real rho = max2(cons[0], density_floor);
+    real px = cons[1];
+    real py = cons[2];
+    real vx = sign(px) * min2(fabs(px / rho), velocity_ceiling);
+    real vy = sign(py) * min2(fabs(py / rho), velocity_ceiling);
 
     prim[0] = rho;
     prim[1] = vx;
     prim[2] = vy;
+
+    for (int q = NHYDRO; q < NCONS; ++q)
+    {
+        prim[q] = cons[q] / rho;
+    }
+}
+
+PRIVATE real invalid_state(const real *cons)
+{
+    // Returns the flag of an invalid conserved state, which the floors would
+    // otherwise hide: 1 for a negative density, and 2 for non-finite data.
+    // See sailfish.solvers.INVALID_STATES.
+    for (int q = 0; q < NCONS; ++q)
+    {
+        if (!isfinite(cons[q]))
+        {
+            return 2.0;
+        }
+    }
+    if (cons[0] < 0.0)
+    {
+        return 1.0;
+    }
+    return 0.0;
 }
 
 PRIVATE void primitive_to_conserved(
-    const double *prim,
-    double *cons)
+    const real *prim,
+    real *cons)
 {
-    double rho = prim[0];
-    double vx = prim[1];
-    double vy = prim[2];
-    double px = vx * rho;
-    double py = vy * rho;
+    real rho = prim[0];
+    real vx = prim[1];
+    real vy = prim[2];
+    real px = vx * rho;
+    real py = vy * rho;
 
     cons[0] = rho;
     cons[1] = px;
     cons[2] = py;
+
+    for (int q = NHYDRO; q < NCONS; ++q)
+    {
+        cons[q] = rho * prim[q];
+    }
 }
 
-PRIVATE double primitive_to_velocity(
-    const double *prim,
+PRIVATE real primitive_to_velocity(
+    const real *prim,
     int direction)
 {
     switch (direction)
@@ -310,15 +585,15 @@ PRIVATE double primitive_to_velocity(
 }
 
 PRIVATE void primitive_to_flux(
-    const double *prim,
-    const double *cons,
-    double *flux,
-    double cs2,
+    const real *prim,
+    const real *cons,
+    real *flux,
+    real cs2,
     int direction)
 {
-    double vn = primitive_to_velocity(prim, direction);
-    double rho = prim[0];
-    double pressure = rho * cs2;
+    real vn = primitive_to_velocity(prim, direction);
+    real rho = prim[0];
+    real pressure = rho * cs2;
 
     flux[0] = vn * cons[0];
     flux[1] = vn * cons[1] + pressure * (direction == 0);
@@ -326,57 +601,99 @@ PRIVATE void primitive_to_flux(
 }
 
 PRIVATE void primitive_to_outer_wavespeeds(
-    const double *prim,
-    double *wavespeeds,
-    double cs2,
+    const real *prim,
+    real *wavespeeds,
+    real cs2,
     int direction)
 {
-    double cs = sqrt(cs2);
-    double vn = primitive_to_velocity(prim, direction);
+    real cs = sqrt(cs2);
+    real vn = primitive_to_velocity(prim, direction);
     wavespeeds[0] = vn - cs;
     wavespeeds[1] = vn + cs;
 }
 
-PRIVATE double primitive_max_wavespeed(
-    const double *prim,
-    double cs2)
+PRIVATE real primitive_max_wavespeed(
+    const real *prim,
+    real cs2)
 {
-    double cs = sqrt(cs2);
-    double vx = prim[1];
-    double vy = prim[2];
-    double ax = max2(fabs(vx - cs), fabs(vx + cs));
-    double ay = max2(fabs(vy - cs), fabs(vy + cs));
+    real cs = sqrt(cs2);
+    real vx = prim[1];
+    real vy = prim[2];
+    real ax = max2(fabs(vx - cs), fabs(vx + cs));
+    real ay = max2(fabs(vy - cs), fabs(vy + cs));
     return max2(ax, ay);
 }
 
+PRIVATE void hll_wavespeeds(
+    const real *pl,
+    const real *pr,
+    real cs2,
+    int direction,
+    int wavespeed_estimator,
+    real *am,
+    real *ap)
+{
+    real al[2];
+    real ar[2];
+
+    primitive_to_outer_wavespeeds(pl, al, cs2, direction);
+    primitive_to_outer_wavespeeds(pr, ar, cs2, direction);
+
+    switch (wavespeed_estimator)
+    {
+        case 1: // Einfeldt: Roe-averaged velocity, bounded by the outer states
+        {
+            real sl = sqrt(pl[0]);
+            real sr = sqrt(pr[0]);
+            real vl = primitive_to_velocity(pl, direction);
+            real vr = primitive_to_velocity(pr, direction);
+            real vroe = (sl * vl + sr * vr) / (sl + sr);
+            real cs = sqrt(cs2);
+            *am = min3(0.0, al[0], vroe - cs);
+            *ap = max3(0.0, ar[1], vroe + cs);
+            break;
+        }
+        default: // Davis, and exact eigenvalues (isothermal cs2 is exact at the face)
+        {
+            *am = min3(0.0, al[0], ar[0]);
+            *ap = max3(0.0, al[1], ar[1]);
+            break;
+        }
+    }
+}
+
 PRIVATE void riemann_hlle(
-    const double *pl,
-    const double *pr,
-    double *flux,
-    double cs2,
-    int direction)
+    const real *pl,
+    const real *pr,
+    real *flux,
+    real cs2,
+    int direction,
+    int wavespeed_estimator)
 {
-    double ul[NCONS];
-    double ur[NCONS];
-    double fl[NCONS];
-    double fr[NCONS];
-    double al[2];
-    double ar[2];
+    real ul[NCONS];
+    real ur[NCONS];
+    real fl[NCONS];
+    real fr[NCONS];
+    real am;
+    real ap;
 
     primitive_to_conserved(pl, ul);
     primitive_to_conserved(pr, ur);
     primitive_to_flux(pl, ul, fl, cs2, direction);
     primitive_to_flux(pr, ur, fr, cs2, direction);
-    primitive_to_outer_wavespeeds(pl, al, cs2, direction);
-    primitive_to_outer_wavespeeds(pr, ar, cs2, direction);
-
-    const double am = min3(0.0, al[0], ar[0]);
-    const double ap = max3(0.0, al[1], ar[1]);
+    hll_wavespeeds(pl, pr, cs2, direction, wavespeed_estimator, &am, &ap);
 
-    for (int q = 0; q < NCONS; ++q)
+    for (int q = 0; q < NHYDRO; ++q)
     {
         flux[q] = (fl[q] * ap - fr[q] * am - (ul[q] - ur[q]) * ap * am) / (ap - am);
     }
+
+    // The tracers are carried by the mass flux, with the concentration of
+    // the upwind face state.
+    for (int q = NHYDRO; q < NCONS; ++q)
+    {
+        flux[q] = flux[0] * (flux[0] > 0.0 ? pl[q] : pr[q]);
+    }
 }
 
 
@@ -389,21 +706,26 @@ PUBLIC void cbdiso_2d_advance_rk(
     double patch_xr,
     double patch_yl,
     double patch_yr,
-    double *conserved_rk, // :: $.shape == (ni + 4, nj + 4, 3)
-    double *primitive_rd, // :: $.shape == (ni + 4, nj + 4, 3)
-    double *primitive_wr, // :: $.shape == (ni + 4, nj + 4, 3)
+    real *conserved_rk, // :: $.shape[:2] == (ni + 4, nj + 4)
+    real *primitive_rd, // :: $.shape[:2] == (ni + 4, nj + 4)
+    real *primitive_wr, // :: $.shape[:2] == (ni + 4, nj + 4)
     double buffer_surface_density,
     double buffer_central_mass,
     double buffer_driving_rate,
     double buffer_outer_radius,
     double buffer_onset_width,
     int buffer_is_enabled,
+    int target_is_enabled, // damping toward a target state
+    real *target_rate, // :: target_is_enabled == 0 or $.shape == (ni, nj)
+    real *target_primitive, // :: target_is_enabled == 0 or $.shape == (ni, nj, 3)
+    int buffer_update, // :: $ in [0, 1, 2]
     double x1, // point mass 1
     double y1,
     double vx1,
     double vy1,
     double mass1,
     double softening_length1,
+    int softening_model1,
     double sink_rate1,
     double sink_radius1,
     int sink_model1,
@@ -413,17 +735,33 @@ PUBLIC void cbdiso_2d_advance_rk(
     double vy2,
     double mass2,
     double softening_length2,
+    int softening_model2,
     double sink_rate2,
     double sink_radius2,
     int sink_model2,
+    int sink_update, // :: $ in [0, 1, 2]
     double cs2, // equation of state
     double mach_squared,
     int eos_type,
+    double cs2_profile_dr,
+    int cs2_profile_size,
+    real *cs2_profile, // :: $.size >= cs2_profile_size
     double nu, // kinematic viscosity coefficient
+    double alpha, // alpha viscosity coefficient, replaces nu if > 0
+    double frame_rotation, // angular frequency of a rotating mesh
     double a, // RK parameter
     double dt, // timestep
     double velocity_ceiling,
-    double density_floor)
+    double density_floor,
+    real *invalid_zones, // :: $.shape == (ni, nj)
+    int wavespeed_estimator, // :: $ in [0, 1, 2]
+    int record_face_fluxes,
+    real *face_flux_x, // :: record_face_fluxes == 0 or $.shape[:2] == (ni + 1, nj)
+    real *face_flux_y, // :: record_face_fluxes == 0 or $.shape[:2] == (ni, nj + 1)
+    int record_budget,
+    real *budget, // :: record_budget == 0 or $.shape == (ni, nj, 6, 3)
+    int mask_is_enabled,
+    real *zone_mask) // :: mask_is_enabled == 0 or $.shape == (ni, nj)
 {
     struct KeplerianBuffer buffer = {
         buffer_surface_density,
@@ -431,27 +769,84 @@ PUBLIC void cbdiso_2d_advance_rk(
         buffer_driving_rate,
         buffer_outer_radius,
         buffer_onset_width,
-        buffer_is_enabled
+        buffer_is_enabled,
+        frame_rotation,
+        buffer_update
     };
-    struct PointMass m1 = {x1, y1, vx1, vy1, mass1, softening_length1, sink_rate1, sink_radius1, sink_model1};
-    struct PointMass m2 = {x2, y2, vx2, vy2, mass2, softening_length2, sink_rate2, sink_radius2, sink_model2};
+    struct PointMass m1 = {x1, y1, vx1, vy1, mass1, softening_length1, softening_model1, sink_rate1, sink_radius1, sink_model1, sink_update};
+    struct PointMass m2 = {x2, y2, vx2, vy2, mass2, softening_length2, softening_model2, sink_rate2, sink_radius2, sink_model2, sink_update};
     struct PointMassList mass_list = {{m1, m2}};
+    struct SoundSpeedProfile profile = {cs2_profile_dr, cs2_profile_size, cs2_profile};
 
-    double dx = (patch_xr - patch_xl) / ni;
-    double dy = (patch_yr - patch_yl) / nj;
+    real dx = (patch_xr - patch_xl) / ni;
+    real dy = (patch_yr - patch_yl) / nj;
 
     int ng = 2; // number of guard zones
-    int si = NCONS * (nj + 2 * ng);
-    int sj = NCONS;
+    index_t si = NCONS * (nj + 2 * ng);
+    index_t sj = NCONS;
+
+#if (EXEC_MODE == EXEC_GPU)
+    // Stage the thread block's tile of primitives, with a halo of guard
+    // zones on each side, in shared memory. The stencils of neighboring
+    // zones overlap, so each primitive would otherwise be read from global
+    // memory by up to thirteen threads. Threads outside the patch still help
+    // load the tile, so the bounds check in FOR_EACH_2D comes after the
+    // barrier.
+    __shared__ real tile[(THREAD_BLOCK_SIZE_2D_I + 4) * (THREAD_BLOCK_SIZE_2D_J + 4) * NCONS];
+    int tile_ni = THREAD_BLOCK_SIZE_2D_I + 2 * ng;
+    int tile_nj = THREAD_BLOCK_SIZE_2D_J + 2 * ng;
+    int tsi = NCONS * tile_nj;
+    int i0 = blockIdx.x * blockDim.x;
+    int j0 = blockIdx.y * blockDim.y;
+
+    for (int n = threadIdx.x * blockDim.y + threadIdx.y; n < tile_ni * tile_nj; n += blockDim.x * blockDim.y)
+    {
+        int a = n / tile_nj;
+        int b = n % tile_nj;
+
+        if (i0 + a < ni + 2 * ng && j0 + b < nj + 2 * ng)
+        {
+            for (int q = 0; q < NCONS; ++q)
+            {
+                tile[a * tsi + b * sj + q] = primitive_rd[(i0 + a) * si + (j0 + b) * sj + q];
+            }
+        }
+    }
+    __syncthreads();
+    real *primitive_tile = tile;
+#else
+    index_t tsi = si;
+    real *primitive_tile = primitive_rd;
+#endif
 
     FOR_EACH_2D(ni, nj)
     {
-        double xl = patch_xl + (i + 0.0) * dx;
-        double xc = patch_xl + (i + 0.5) * dx;
-        double xr = patch_xl + (i + 1.0) * dx;
-        double yl = patch_yl + (j + 0.0) * dy;
-        double yc = patch_yl + (j + 0.5) * dy;
-        double yr = patch_yl + (j + 1.0) * dy;
+#if (EXEC_MODE == EXEC_GPU)
+        int ti = threadIdx.x;
+        int tj = threadIdx.y;
+#else
+        int ti = i;
+        int tj = j;
+#endif
+        // Zones outside the mask keep their primitive data, and are seen by
+        // their neighbors like the guard zones of a fixed boundary.
+        if (mask_is_enabled && zone_mask[(index_t) i * nj + j] == 0.0)
+        {
+            index_t ncc = (i + ng) * si + (j + ng) * sj;
+
+            for (int q = 0; q < NCONS; ++q)
+            {
+                primitive_wr[ncc + q] = primitive_rd[ncc + q];
+            }
+            SKIP_ZONE;
+        }
+
+        real xl = patch_xl + (i + 0.0) * dx;
+        real xc = patch_xl + (i + 0.5) * dx;
+        real xr = patch_xl + (i + 1.0) * dx;
+        real yl = patch_yl + (j + 0.0) * dy;
+        real yc = patch_yl + (j + 0.5) * dy;
+        real yr = patch_yl + (j + 1.0) * dy;
 
         // ------------------------------------------------------------------------
         //                 tj
@@ -473,54 +868,56 @@ PUBLIC void cbdiso_2d_advance_rk(
         //                 kj
         // ------------------------------------------------------------------------
 
-        int ncc = (i     + ng) * si + (j     + ng) * sj;
-        int nli = (i - 1 + ng) * si + (j     + ng) * sj;
-        int nri = (i + 1 + ng) * si + (j     + ng) * sj;
-        int nlj = (i     + ng) * si + (j - 1 + ng) * sj;
-        int nrj = (i     + ng) * si + (j + 1 + ng) * sj;
-        int nki = (i - 2 + ng) * si + (j     + ng) * sj;
-        int nti = (i + 2 + ng) * si + (j     + ng) * sj;
-        int nkj = (i     + ng) * si + (j - 2 + ng) * sj;
-        int ntj = (i     + ng) * si + (j + 2 + ng) * sj;
-        int nll = (i - 1 + ng) * si + (j - 1 + ng) * sj;
-        int nlr = (i - 1 + ng) * si + (j + 1 + ng) * sj;
-        int nrl = (i + 1 + ng) * si + (j - 1 + ng) * sj;
-        int nrr = (i + 1 + ng) * si + (j + 1 + ng) * sj;
-
-        double *un = &conserved_rk[ncc];
-        double *pcc = &primitive_rd[ncc];
-        double *pli = &primitive_rd[nli];
-        double *pri = &primitive_rd[nri];
-        double *plj = &primitive_rd[nlj];
-        double *prj = &primitive_rd[nrj];
-        double *pki = &primitive_rd[nki];
-        double *pti = &primitive_rd[nti];
-        double *pkj = &primitive_rd[nkj];
-        double *ptj = &primitive_rd[ntj];
-        double *pll = &primitive_rd[nll];
-        double *plr = &primitive_rd[nlr];
-        double *prl = &primitive_rd[nrl];
-        double *prr = &primitive_rd[nrr];
-
-        double plip[NCONS];
-        double plim[NCONS];
-        double prip[NCONS];
-        double prim[NCONS];
-        double pljp[NCONS];
-        double pljm[NCONS];
-        double prjp[NCONS];
-        double prjm[NCONS];
-
-        double gxli[NCONS];
-        double gxri[NCONS];
-        double gyli[NCONS];
-        double gyri[NCONS];
-        double gxlj[NCONS];
-        double gxrj[NCONS];
-        double gylj[NCONS];
-        double gyrj[NCONS];
-        double gxcc[NCONS];
-        double gycc[NCONS];
+        // The global index of the zone, and tile indexes of its stencil.
+        index_t ncc = (i      + ng) * si  + (j      + ng) * sj;
+        index_t tcc = (ti     + ng) * tsi + (tj     + ng) * sj;
+        index_t nli = (ti - 1 + ng) * tsi + (tj     + ng) * sj;
+        index_t nri = (ti + 1 + ng) * tsi + (tj     + ng) * sj;
+        index_t nlj = (ti     + ng) * tsi + (tj - 1 + ng) * sj;
+        index_t nrj = (ti     + ng) * tsi + (tj + 1 + ng) * sj;
+        index_t nki = (ti - 2 + ng) * tsi + (tj     + ng) * sj;
+        index_t nti = (ti + 2 + ng) * tsi + (tj     + ng) * sj;
+        index_t nkj = (ti     + ng) * tsi + (tj - 2 + ng) * sj;
+        index_t ntj = (ti     + ng) * tsi + (tj + 2 + ng) * sj;
+        index_t nll = (ti - 1 + ng) * tsi + (tj - 1 + ng) * sj;
+        index_t nlr = (ti - 1 + ng) * tsi + (tj + 1 + ng) * sj;
+        index_t nrl = (ti + 1 + ng) * tsi + (tj - 1 + ng) * sj;
+        index_t nrr = (ti + 1 + ng) * tsi + (tj + 1 + ng) * sj;
+
+        real *un = &conserved_rk[ncc];
+        real *pcc = &primitive_tile[tcc];
+        real *pli = &primitive_tile[nli];
+        real *pri = &primitive_tile[nri];
+        real *plj = &primitive_tile[nlj];
+        real *prj = &primitive_tile[nrj];
+        real *pki = &primitive_tile[nki];
+        real *pti = &primitive_tile[nti];
+        real *pkj = &primitive_tile[nkj];
+        real *ptj = &primitive_tile[ntj];
+        real *pll = &primitive_tile[nll];
+        real *plr = &primitive_tile[nlr];
+        real *prl = &primitive_tile[nrl];
+        real *prr = &primitive_tile[nrr];
+
+        real plip[NCONS];
+        real plim[NCONS];
+        real prip[NCONS];
+        real prim[NCONS];
+        real pljp[NCONS];
+        real pljm[NCONS];
+        real prjp[NCONS];
+        real prjm[NCONS];
+
+        real gxli[NCONS];
+        real gxri[NCONS];
+        real gyli[NCONS];
+        real gyri[NCONS];
+        real gxlj[NCONS];
+        real gxrj[NCONS];
+        real gylj[NCONS];
+        real gyrj[NCONS];
+        real gxcc[NCONS];
+        real gycc[NCONS];
 
         plm_gradient(pki, pli, pcc, gxli);
         plm_gradient(pli, pcc, pri, gxcc);
@@ -546,29 +943,29 @@ PUBLIC void cbdiso_2d_advance_rk(
             prjp[q] = prj[q] - 0.5 * gyrj[q];
         }
 
-        double fli[NCONS];
-        double fri[NCONS];
-        double flj[NCONS];
-        double frj[NCONS];
-        double ucc[NCONS];
+        real fli[NCONS];
+        real fri[NCONS];
+        real flj[NCONS];
+        real frj[NCONS];
+        real ucc[NCONS];
 
-        double cs2li = sound_speed_squared(cs2, mach_squared, eos_type, xl, yc, &mass_list);
-        double cs2ri = sound_speed_squared(cs2, mach_squared, eos_type, xr, yc, &mass_list);
-        double cs2lj = sound_speed_squared(cs2, mach_squared, eos_type, xc, yl, &mass_list);
-        double cs2rj = sound_speed_squared(cs2, mach_squared, eos_type, xc, yr, &mass_list);
+        real cs2li = sound_speed_squared(cs2, mach_squared, eos_type, xl, yc, &mass_list, &profile);
+        real cs2ri = sound_speed_squared(cs2, mach_squared, eos_type, xr, yc, &mass_list, &profile);
+        real cs2lj = sound_speed_squared(cs2, mach_squared, eos_type, xc, yl, &mass_list, &profile);
+        real cs2rj = sound_speed_squared(cs2, mach_squared, eos_type, xc, yr, &mass_list, &profile);
 
-        riemann_hlle(plim, plip, fli, cs2li, 0);
-        riemann_hlle(prim, prip, fri, cs2ri, 0);
-        riemann_hlle(pljm, pljp, flj, cs2lj, 1);
-        riemann_hlle(prjm, prjp, frj, cs2rj, 1);
+        riemann_hlle(plim, plip, fli, cs2li, 0, wavespeed_estimator);
+        riemann_hlle(prim, prip, fri, cs2ri, 0, wavespeed_estimator);
+        riemann_hlle(pljm, pljp, flj, cs2lj, 1, wavespeed_estimator);
+        riemann_hlle(prjm, prjp, frj, cs2rj, 1, wavespeed_estimator);
 
-        if (nu > 0.0)
+        if (nu > 0.0 || alpha > 0.0)
         {
-            double sli[4];
-            double sri[4];
-            double slj[4];
-            double srj[4];
-            double scc[4];
+            real sli[4];
+            real sri[4];
+            real slj[4];
+            real srj[4];
+            real scc[4];
 
             shear_strain(gxli, gyli, dx, dy, sli);
             shear_strain(gxri, gyri, dx, dy, sri);
@@ -576,19 +973,94 @@ PUBLIC void cbdiso_2d_advance_rk(
             shear_strain(gxrj, gyrj, dx, dy, srj);
             shear_strain(gxcc, gycc, dx, dy, scc);
 
-            fli[1] -= 0.5 * nu * (pli[0] * sli[0] + pcc[0] * scc[0]); // x-x
-            fli[2] -= 0.5 * nu * (pli[0] * sli[1] + pcc[0] * scc[1]); // x-y
-            fri[1] -= 0.5 * nu * (pcc[0] * scc[0] + pri[0] * sri[0]); // x-x
-            fri[2] -= 0.5 * nu * (pcc[0] * scc[1] + pri[0] * sri[1]); // x-y
-            flj[1] -= 0.5 * nu * (plj[0] * slj[2] + pcc[0] * scc[2]); // y-x
-            flj[2] -= 0.5 * nu * (plj[0] * slj[3] + pcc[0] * scc[3]); // y-y
-            frj[1] -= 0.5 * nu * (pcc[0] * scc[2] + prj[0] * srj[2]); // y-x
-            frj[2] -= 0.5 * nu * (pcc[0] * scc[3] + prj[0] * srj[3]); // y-y
+            // The viscosity is evaluated at the faces, like the sound speed.
+            real nuli = kinematic_viscosity(nu, alpha, cs2li, xl, yc, &mass_list);
+            real nuri = kinematic_viscosity(nu, alpha, cs2ri, xr, yc, &mass_list);
+            real nulj = kinematic_viscosity(nu, alpha, cs2lj, xc, yl, &mass_list);
+            real nurj = kinematic_viscosity(nu, alpha, cs2rj, xc, yr, &mass_list);
+
+            fli[1] -= 0.5 * nuli * (pli[0] * sli[0] + pcc[0] * scc[0]); // x-x
+            fli[2] -= 0.5 * nuli * (pli[0] * sli[1] + pcc[0] * scc[1]); // x-y
+            fri[1] -= 0.5 * nuri * (pcc[0] * scc[0] + pri[0] * sri[0]); // x-x
+            fri[2] -= 0.5 * nuri * (pcc[0] * scc[1] + pri[0] * sri[1]); // x-y
+            flj[1] -= 0.5 * nulj * (plj[0] * slj[2] + pcc[0] * scc[2]); // y-x
+            flj[2] -= 0.5 * nulj * (plj[0] * slj[3] + pcc[0] * scc[3]); // y-y
+            frj[1] -= 0.5 * nurj * (pcc[0] * scc[2] + prj[0] * srj[2]); // y-x
+            frj[2] -= 0.5 * nurj * (pcc[0] * scc[3] + prj[0] * srj[3]); // y-y
+        }
+
+        if (record_face_fluxes)
+        {
+            // Accumulate the time-integrated fluxes through each face, over
+            // the RK stages. The zone writes its left (or bottom) face, and
+            // the last zone in each direction, or the last one before a
+            // masked zone, also writes the outer face.
+            int write_ri = i == ni - 1 || (mask_is_enabled && zone_mask[(index_t) (i + 1) * nj + j] == 0.0);
+            int write_rj = j == nj - 1 || (mask_is_enabled && zone_mask[(index_t) i * nj + j + 1] == 0.0);
+            real *gli = &face_flux_x[NCONS * ((index_t) (i + 0) * nj + j)];
+            real *gri = &face_flux_x[NCONS * ((index_t) (i + 1) * nj + j)];
+            real *glj = &face_flux_y[NCONS * ((index_t) i * (nj + 1) + j + 0)];
+            real *grj = &face_flux_y[NCONS * ((index_t) i * (nj + 1) + j + 1)];
+
+            for (int q = 0; q < NCONS; ++q)
+            {
+                gli[q] = (1.0 - a) * (gli[q] + fli[q] * dt);
+                glj[q] = (1.0 - a) * (glj[q] + flj[q] * dt);
+
+                if (write_ri)
+                {
+                    gri[q] = (1.0 - a) * (gri[q] + fri[q] * dt);
+                }
+                if (write_rj)
+                {
+                    grj[q] = (1.0 - a) * (grj[q] + frj[q] * dt);
+                }
+            }
         }
-        double delta_cons[3] = {0.0, 0.0, 0.0};
+
+        real delta_cons[NCONS] = {0.0};
         primitive_to_conserved(pcc, ucc);
         buffer_source_term(&buffer, xc, yc, dt, ucc, delta_cons);
-        point_masses_source_term(&mass_list, xc, yc, dt, pcc, delta_cons);
+
+        if (target_is_enabled)
+        {
+            index_t nt = (index_t) i * nj + j;
+            target_source_term(target_rate[nt], &target_primitive[NHYDRO * nt], dt, buffer_update, ucc, delta_cons);
+        }
+        point_masses_source_term(&mass_list, xc, yc, dt, pcc, delta_cons, delta_cons);
+        rotating_frame_source_term(frame_rotation, xc, yc, dt, pcc, delta_cons);
+        tracer_source_term(pcc, delta_cons);
+
+        // The terms of the audit are evaluated again, separately, so the
+        // update is the same with or without it. The budget is combined over
+        // the RK stages like the face fluxes, and like the solution.
+        real *bn = record_budget ? &budget[(index_t) NBUDGET * NHYDRO * ((index_t) i * nj + j)] : NULL;
+
+        if (record_budget)
+        {
+            real delta_terms[NBUDGET][NHYDRO] = {{0.0}};
+            buffer_source_term(&buffer, xc, yc, dt, ucc, delta_terms[BUDGET_BUFFERS]);
+
+            if (target_is_enabled)
+            {
+                index_t nt = (index_t) i * nj + j;
+                target_source_term(target_rate[nt], &target_primitive[NHYDRO * nt], dt, buffer_update, ucc, delta_terms[BUDGET_BUFFERS]);
+            }
+            point_masses_source_term(&mass_list, xc, yc, dt, pcc, delta_terms[BUDGET_GRAVITY], delta_terms[BUDGET_SINKS]);
+            rotating_frame_source_term(frame_rotation, xc, yc, dt, pcc, delta_terms[BUDGET_FRAME]);
+
+            for (int q = 0; q < NHYDRO; ++q)
+            {
+                delta_terms[BUDGET_BOUNDARY][q] = -((fri[q] - fli[q]) / dx + (frj[q] - flj[q]) / dy) * dt;
+            }
+            for (int n = 0; n < NBUDGET; ++n)
+            {
+                for (int q = 0; q < NHYDRO; ++q)
+                {
+                    bn[n * NHYDRO + q] = (1.0 - a) * (bn[n * NHYDRO + q] + delta_terms[n][q]);
+                }
+            }
+        }
 
         for (int q = 0; q < NCONS; ++q)
         {
@@ -599,26 +1071,106 @@ PUBLIC void cbdiso_2d_advance_rk(
             ucc[q] += delta_cons[q];
             ucc[q] = (1.0 - a) * ucc[q] + a * un[q];
         }
+        real invalid = invalid_state(ucc);
+
+        if (invalid != 0.0)
+        {
+            invalid_zones[(index_t) i * nj + j] = invalid;
+        }
         conserved_to_primitive(ucc, &primitive_wr[ncc], velocity_ceiling, density_floor);
+
+        if (record_budget)
+        {
+            // The floor term is the change to the conserved state of the
+            // stored primitives, which is applied after the RK average.
+            real ufloor[NCONS];
+            primitive_to_conserved(&primitive_wr[ncc], ufloor);
+
+            for (int q = 0; q < NHYDRO; ++q)
+            {
+                real *b = &bn[BUDGET_FLOORS * NHYDRO + q];
+                *b = (1.0 - a) * *b + ufloor[q] - ucc[q];
+            }
+        }
+    }
+}
+
+PUBLIC void cbdiso_2d_fill_guard_zones(
+    int num_guard_zones, // :: $ == (ni + 4) * (nj + 4) - ni * nj
+    real *pl, // :: $.shape[:2] == (nl + 4, nj + 4)
+    real *pc, // :: $.shape[:2] == (ni + 4, nj + 4)
+    real *pr, // :: $.shape[:2] == (nr + 4, nj + 4)
+    int ni,
+    int nj,
+    int nl,
+    int nr,
+    int first_patch,
+    int last_patch)
+{
+    // Fill all guard zones of the patch pc in one launch. The x-guard zones
+    // are copied from the neighboring patches pl and pr, or have outflow
+    // values at the domain edges; the y-guard zones (including the corners)
+    // have outflow values. Only interior zones of pl, pc, and pr are read.
+    int ng = 2;
+    index_t si = NCONS * (nj + 2 * ng);
+    index_t sj = NCONS;
+    int num_x_guard = 2 * ng * (nj + 2 * ng);
+
+    FOR_EACH_1D(num_guard_zones)
+    {
+        int ig, jg;
+
+        if (i < num_x_guard)
+        {
+            int row = i / (nj + 2 * ng);
+            ig = row < ng ? row : ni + row;
+            jg = i % (nj + 2 * ng);
+        }
+        else
+        {
+            int n = i - num_x_guard;
+            int col = n % (2 * ng);
+            ig = ng + n / (2 * ng);
+            jg = col < ng ? col : nj + col;
+        }
+        int jc = min2(max2(jg, ng), nj + ng - 1);
+        real *src;
+
+        if (ig < ng)
+        {
+            src = first_patch ? &pc[ng * si + jc * sj] : &pl[(nl + ig) * si + jc * sj];
+        }
+        else if (ig >= ni + ng)
+        {
+            src = last_patch ? &pc[(ni + ng - 1) * si + jc * sj] : &pr[(ig - ni) * si + jc * sj];
+        }
+        else
+        {
+            src = &pc[ig * si + jc * sj];
+        }
+        for (int q = 0; q < NCONS; ++q)
+        {
+            pc[ig * si + jg * sj + q] = src[q];
+        }
     }
 }
 
 PUBLIC void cbdiso_2d_primitive_to_conserved(
     int ni,
     int nj,
-    double *primitive, // :: $.shape == (ni + 4, nj + 4, 3)
-    double *conserved) // :: $.shape == (ni + 4, nj + 4, 3)
+    real *primitive, // :: $.shape[:2] == (ni + 4, nj + 4)
+    real *conserved) // :: $.shape[:2] == (ni + 4, nj + 4)
 {
     int ng = 2; // number of guard zones
-    int si = NCONS * (nj + 2 * ng);
-    int sj = NCONS;
+    index_t si = NCONS * (nj + 2 * ng);
+    index_t sj = NCONS;
 
     FOR_EACH_2D(ni, nj)
     {
-        int n = (i + ng) * si + (j + ng) * sj;
+        index_t n = (i + ng) * si + (j + ng) * sj;
 
-        double *pc = &primitive[n];
-        double *uc = &conserved[n];
+        real *pc = &primitive[n];
+        real *uc = &conserved[n];
         primitive_to_conserved(pc, uc);
     }
 }
@@ -636,30 +1188,31 @@ PUBLIC void cbdiso_2d_point_mass_source_term(
     double vy1,
     double mass1,
     double softening_length1,
+    int softening_model1,
     double sink_rate1,
     double sink_radius1,
     int sink_model1,
-    double *primitive, // :: $.shape == (ni + 4, nj + 4, 3)
-    double *cons_rate) // :: $.shape == (ni + 4, nj + 4, 3)
+    real *primitive, // :: $.shape[:2] == (ni + 4, nj + 4)
+    real *cons_rate) // :: $.shape[:2] == (ni + 4, nj + 4)
 {
-    struct PointMass m1 = {x1, y1, vx1, vy1, mass1, softening_length1, sink_rate1, sink_radius1, sink_model1};
+    struct PointMass m1 = {x1, y1, vx1, vy1, mass1, softening_length1, softening_model1, sink_rate1, sink_radius1, sink_model1};
 
     int ng = 2; // number of guard zones
-    int si = NCONS * (nj + 2 * ng);
-    int sj = NCONS;
+    index_t si = NCONS * (nj + 2 * ng);
+    index_t sj = NCONS;
 
-    double dx = (patch_xr - patch_xl) / ni;
-    double dy = (patch_yr - patch_yl) / nj;
+    real dx = (patch_xr - patch_xl) / ni;
+    real dy = (patch_yr - patch_yl) / nj;
 
     FOR_EACH_2D(ni, nj)
     {
-        int ncc = (i + ng) * si + (j + ng) * sj;
+        index_t ncc = (i + ng) * si + (j + ng) * sj;
 
-        double xc = patch_xl + (i + 0.5) * dx;
-        double yc = patch_yl + (j + 0.5) * dy;
-        double *pc = &primitive[ncc];
-        double *uc = &cons_rate[ncc];
-        point_mass_source_term(&m1, xc, yc, 1.0, pc, uc);
+        real xc = patch_xl + (i + 0.5) * dx;
+        real yc = patch_yl + (j + 0.5) * dy;
+        real *pc = &primitive[ncc];
+        real *uc = &cons_rate[ncc];
+        point_mass_source_term(&m1, xc, yc, 1.0, pc, uc, uc);
     }
 }
 
@@ -673,12 +1226,16 @@ PUBLIC void cbdiso_2d_wavespeed(
     double soundspeed2, // equation of state
     double mach_squared,
     int eos_type,
+    double cs2_profile_dr,
+    int cs2_profile_size,
+    real *cs2_profile, // :: $.size >= cs2_profile_size
     double x1, // point mass 1
     double y1,
     double vx1,
     double vy1,
     double mass1,
     double softening_length1,
+    int softening_model1,
     double sink_rate1,
     double sink_radius1,
     int sink_model1,
@@ -688,35 +1245,322 @@ PUBLIC void cbdiso_2d_wavespeed(
     double vy2,
     double mass2,
     double softening_length2,
+    int softening_model2,
     double sink_rate2,
     double sink_radius2,
     int sink_model2,
-    double *primitive, // :: $.shape == (ni + 4, nj + 4, 3)
-    double *wavespeed) // :: $.shape == (ni + 4, nj + 4)
+    real *primitive, // :: $.shape[:2] == (ni + 4, nj + 4)
+    real *wavespeed, // :: $.shape == (ni + 4, nj + 4)
+    int mask_is_enabled,
+    real *zone_mask) // :: mask_is_enabled == 0 or $.shape == (ni, nj)
 {
-    struct PointMass m1 = {x1, y1, vx1, vy1, mass1, softening_length1, sink_rate1, sink_radius1, sink_model1};
-    struct PointMass m2 = {x2, y2, vx2, vy2, mass2, softening_length2, sink_rate2, sink_radius2, sink_model2};
+    struct PointMass m1 = {x1, y1, vx1, vy1, mass1, softening_length1, softening_model1, sink_rate1, sink_radius1, sink_model1};
+    struct PointMass m2 = {x2, y2, vx2, vy2, mass2, softening_length2, softening_model2, sink_rate2, sink_radius2, sink_model2};
     struct PointMassList mass_list = {{m1, m2}};
+    struct SoundSpeedProfile profile = {cs2_profile_dr, cs2_profile_size, cs2_profile};
 
     int ng = 2; // number of guard zones
-    int si = NCONS * (nj + 2 * ng);
-    int sj = NCONS;
+    index_t si = NCONS * (nj + 2 * ng);
+    index_t sj = NCONS;
     int ti = nj + 2 * ng;
     int tj = 1;
-    double dx = (patch_xr - patch_xl)/ni;
-    double dy = (patch_yr - patch_yl)/nj;
+    real dx = (patch_xr - patch_xl)/ni;
+    real dy = (patch_yr - patch_yl)/nj;
 
     FOR_EACH_2D(ni, nj)
     {
-        int np = (i + ng) * si + (j + ng) * sj;
-        int na = (i + ng) * ti + (j + ng) * tj;
+        index_t np = (i + ng) * si + (j + ng) * sj;
+        index_t na = (i + ng) * ti + (j + ng) * tj;
 
-        double x = patch_xl + (i + 0.5) * dx;
-        double y = patch_yl + (j + 0.5) * dy;
+        if (mask_is_enabled && zone_mask[(index_t) i * nj + j] == 0.0)
+        {
+            wavespeed[na] = 0.0;
+            SKIP_ZONE;
+        }
+
+        real x = patch_xl + (i + 0.5) * dx;
+        real y = patch_yl + (j + 0.5) * dy;
 
-        double *pc = &primitive[np];
-        double cs2 = sound_speed_squared(soundspeed2, mach_squared, eos_type, x, y, &mass_list);
-        double a = primitive_max_wavespeed(pc, cs2);
+        real *pc = &primitive[np];
+        real cs2 = sound_speed_squared(soundspeed2, mach_squared, eos_type, x, y, &mass_list, &profile);
+        real a = primitive_max_wavespeed(pc, cs2);
         wavespeed[na] = a;
     }
 }
+
+
+// ============================ POLAR =========================================
+// ============================================================================
+// The kernels of a log-polar mesh: the zones are (r, phi), with radii
+// patch_r0 exp(i dlogr) at the faces, and the velocities are the radial and
+// azimuthal components, vr and vphi. The flux update uses the face areas and
+// cell volumes of planar polar coordinates, and the geometric sources of the
+// momentum are the centrifugal (and pressure) term of the radial equation,
+// (sigma vphi^2 + p) / r, and the Coriolis term of the azimuthal one,
+// -sigma vr vphi / r. The point masses and the buffer act on cartesian
+// components, so they're evaluated in the cartesian basis at the zone
+// center, and their rates rotated into the polar one. There is no viscosity,
+// target buffer, rotating frame, zone mask, or face flux recording here.
+#include "geometry.c"
+
+PRIVATE void polar_to_cartesian(const real *polar, real *cartesian, real c, real s)
+{
+    cartesian[0] = polar[0];
+    cartesian[1] = polar[1] * c - polar[2] * s;
+    cartesian[2] = polar[1] * s + polar[2] * c;
+
+    for (int q = NHYDRO; q < NCONS; ++q)
+    {
+        cartesian[q] = polar[q];
+    }
+}
+
+PUBLIC void cbdiso_2d_advance_rk_polar(
+    int ni,
+    int nj,
+    double patch_r0, // mesh
+    double dlogr,
+    double dphi,
+    real *conserved_rk, // :: $.shape[:2] == (ni + 4, nj + 4)
+    real *primitive_rd, // :: $.shape[:2] == (ni + 4, nj + 4)
+    real *primitive_wr, // :: $.shape[:2] == (ni + 4, nj + 4)
+    double buffer_surface_density,
+    double buffer_central_mass,
+    double buffer_driving_rate,
+    double buffer_outer_radius,
+    double buffer_onset_width,
+    int buffer_is_enabled,
+    int buffer_update, // :: $ in [0, 1, 2]
+    double x1, // point mass 1
+    double y1,
+    double vx1,
+    double vy1,
+    double mass1,
+    double softening_length1,
+    int softening_model1,
+    double sink_rate1,
+    double sink_radius1,
+    int sink_model1,
+    double x2, // point mass 2
+    double y2,
+    double vx2,
+    double vy2,
+    double mass2,
+    double softening_length2,
+    int softening_model2,
+    double sink_rate2,
+    double sink_radius2,
+    int sink_model2,
+    int sink_update, // :: $ in [0, 1, 2]
+    double cs2, // equation of state
+    double mach_squared,
+    int eos_type,
+    double cs2_profile_dr,
+    int cs2_profile_size,
+    real *cs2_profile, // :: $.size >= cs2_profile_size
+    double a, // RK parameter
+    double dt, // timestep
+    double velocity_ceiling,
+    double density_floor,
+    real *invalid_zones, // :: $.shape == (ni, nj)
+    int wavespeed_estimator) // :: $ in [0, 1, 2]
+{
+    struct KeplerianBuffer buffer = {
+        buffer_surface_density,
+        buffer_central_mass,
+        buffer_driving_rate,
+        buffer_outer_radius,
+        buffer_onset_width,
+        buffer_is_enabled,
+        0.0,
+        buffer_update
+    };
+    struct PointMass m1 = {x1, y1, vx1, vy1, mass1, softening_length1, softening_model1, sink_rate1, sink_radius1, sink_model1, sink_update};
+    struct PointMass m2 = {x2, y2, vx2, vy2, mass2, softening_length2, softening_model2, sink_rate2, sink_radius2, sink_model2, sink_update};
+    struct PointMassList mass_list = {{m1, m2}};
+    struct SoundSpeedProfile profile = {cs2_profile_dr, cs2_profile_size, cs2_profile};
+
+    int ng = 2; // number of guard zones
+    index_t si = NCONS * (nj + 2 * ng);
+    index_t sj = NCONS;
+
+    FOR_EACH_2D(ni, nj)
+    {
+        double rl = patch_r0 * exp((i + 0.0) * dlogr);
+        double rc = patch_r0 * exp((i + 0.5) * dlogr);
+        double rr = patch_r0 * exp((i + 1.0) * dlogr);
+        double ql = (j + 0.0) * dphi;
+        double qc = (j + 0.5) * dphi;
+        double qr = (j + 1.0) * dphi;
+        real c = cos(qc);
+        real s = sin(qc);
+        real xc = rc * c;
+        real yc = rc * s;
+
+        index_t ncc = (i     + ng) * si + (j     + ng) * sj;
+        index_t nli = (i - 1 + ng) * si + (j     + ng) * sj;
+        index_t nri = (i + 1 + ng) * si + (j     + ng) * sj;
+        index_t nlj = (i     + ng) * si + (j - 1 + ng) * sj;
+        index_t nrj = (i     + ng) * si + (j + 1 + ng) * sj;
+        index_t nki = (i - 2 + ng) * si + (j     + ng) * sj;
+        index_t nti = (i + 2 + ng) * si + (j     + ng) * sj;
+        index_t nkj = (i     + ng) * si + (j - 2 + ng) * sj;
+        index_t ntj = (i     + ng) * si + (j + 2 + ng) * sj;
+
+        real *un = &conserved_rk[ncc];
+        real *pcc = &primitive_rd[ncc];
+        real *pli = &primitive_rd[nli];
+        real *pri = &primitive_rd[nri];
+        real *plj = &primitive_rd[nlj];
+        real *prj = &primitive_rd[nrj];
+        real *pki = &primitive_rd[nki];
+        real *pti = &primitive_rd[nti];
+        real *pkj = &primitive_rd[nkj];
+        real *ptj = &primitive_rd[ntj];
+
+        real plip[NCONS];
+        real plim[NCONS];
+        real prip[NCONS];
+        real prim[NCONS];
+        real pljp[NCONS];
+        real pljm[NCONS];
+        real prjp[NCONS];
+        real prjm[NCONS];
+
+        real gxli[NCONS];
+        real gxri[NCONS];
+        real gylj[NCONS];
+        real gyrj[NCONS];
+        real gxcc[NCONS];
+        real gycc[NCONS];
+
+        plm_gradient(pki, pli, pcc, gxli);
+        plm_gradient(pli, pcc, pri, gxcc);
+        plm_gradient(pcc, pri, pti, gxri);
+        plm_gradient(pkj, plj, pcc, gylj);
+        plm_gradient(plj, pcc, prj, gycc);
+        plm_gradient(pcc, prj, ptj, gyrj);
+
+        for (int q = 0; q < NCONS; ++q)
+        {
+            plim[q] = pli[q] + 0.5 * gxli[q];
+            plip[q] = pcc[q] - 0.5 * gxcc[q];
+            prim[q] = pcc[q] + 0.5 * gxcc[q];
+            prip[q] = pri[q] - 0.5 * gxri[q];
+
+            pljm[q] = plj[q] + 0.5 * gylj[q];
+            pljp[q] = pcc[q] - 0.5 * gycc[q];
+            prjm[q] = pcc[q] + 0.5 * gycc[q];
+            prjp[q] = prj[q] - 0.5 * gyrj[q];
+        }
+
+        real fli[NCONS];
+        real fri[NCONS];
+        real flj[NCONS];
+        real frj[NCONS];
+        real ucc[NCONS];
+
+        real cs2li = sound_speed_squared(cs2, mach_squared, eos_type, rl * c, rl * s, &mass_list, &profile);
+        real cs2ri = sound_speed_squared(cs2, mach_squared, eos_type, rr * c, rr * s, &mass_list, &profile);
+        real cs2lj = sound_speed_squared(cs2, mach_squared, eos_type, rc * cos(ql), rc * sin(ql), &mass_list, &profile);
+        real cs2rj = sound_speed_squared(cs2, mach_squared, eos_type, rc * cos(qr), rc * sin(qr), &mass_list, &profile);
+        real cs2cc = sound_speed_squared(cs2, mach_squared, eos_type, xc, yc, &mass_list, &profile);
+
+        riemann_hlle(plim, plip, fli, cs2li, 0, wavespeed_estimator);
+        riemann_hlle(prim, prip, fri, cs2ri, 0, wavespeed_estimator);
+        riemann_hlle(pljm, pljp, flj, cs2lj, 1, wavespeed_estimator);
+        riemann_hlle(prjm, prjp, frj, cs2rj, 1, wavespeed_estimator);
+
+        double ali = face_area_2d(COORDS_PLANAR_POLAR, rl, rl, ql, qr);
+        double ari = face_area_2d(COORDS_PLANAR_POLAR, rr, rr, ql, qr);
+        double alj = face_area_2d(COORDS_PLANAR_POLAR, rl, rr, ql, ql);
+        double arj = face_area_2d(COORDS_PLANAR_POLAR, rl, rr, qr, qr);
+        double dv = cell_volume_2d(COORDS_PLANAR_POLAR, rl, rr, ql, qr);
+        double w[2];
+        metric_source_weights_2d(COORDS_PLANAR_POLAR, rl, rr, ql, qr, w);
+
+        real delta_cons[NCONS] = {0.0};
+        primitive_to_conserved(pcc, ucc);
+
+        // The geometric source terms, with the zone center values.
+        real sigma = pcc[0];
+        real vr = pcc[1];
+        real vq = pcc[2];
+        delta_cons[1] += (sigma * vq * vq + sigma * cs2cc) * w[0] / dv * dt;
+        delta_cons[2] -= sigma * vr * vq * w[0] / dv * dt;
+
+        // The point mass and buffer terms, in the cartesian basis.
+        real pcart[NCONS];
+        real ucart[NCONS];
+        real dcart[NCONS] = {0.0};
+        polar_to_cartesian(pcc, pcart, c, s);
+        polar_to_cartesian(ucc, ucart, c, s);
+        buffer_source_term(&buffer, xc, yc, dt, ucart, dcart);
+        point_masses_source_term(&mass_list, xc, yc, dt, pcart, dcart, dcart);
+        delta_cons[0] += dcart[0];
+        delta_cons[1] += dcart[1] * c + dcart[2] * s;
+        delta_cons[2] += dcart[2] * c - dcart[1] * s;
+        tracer_source_term(pcc, delta_cons);
+
+        for (int q = 0; q < NCONS; ++q)
+        {
+            delta_cons[q] -= ((ari * fri[q] - ali * fli[q]) + (arj * frj[q] - alj * flj[q])) / dv * dt;
+        }
+        for (int q = 0; q < NCONS; ++q)
+        {
+            ucc[q] += delta_cons[q];
+            ucc[q] = (1.0 - a) * ucc[q] + a * un[q];
+        }
+        real invalid = invalid_state(ucc);
+
+        if (invalid != 0.0)
+        {
+            invalid_zones[(index_t) i * nj + j] = invalid;
+        }
+        conserved_to_primitive(ucc, &primitive_wr[ncc], velocity_ceiling, density_floor);
+    }
+}
+
+PUBLIC void cbdiso_2d_wavespeed_polar(
+    int ni,
+    int nj,
+    double patch_r0, // mesh
+    double dlogr,
+    double dphi,
+    double soundspeed2, // equation of state
+    double mach_squared,
+    int eos_type,
+    double cs2_profile_dr,
+    int cs2_profile_size,
+    real *cs2_profile, // :: $.size >= cs2_profile_size
+    double x1, // point mass 1
+    double y1,
+    double mass1,
+    double softening_length1,
+    int softening_model1,
+    double x2, // point mass 2
+    double y2,
+    double mass2,
+    double softening_length2,
+    int softening_model2,
+    real *primitive, // :: $.shape[:2] == (ni + 4, nj + 4)
+    real *wavespeed) // :: $.shape == (ni, nj)
+{
+    struct PointMass m1 = {x1, y1, 0.0, 0.0, mass1, softening_length1, softening_model1, 0.0, 0.0, 0};
+    struct PointMass m2 = {x2, y2, 0.0, 0.0, mass2, softening_length2, softening_model2, 0.0, 0.0, 0};
+    struct PointMassList mass_list = {{m1, m2}};
+    struct SoundSpeedProfile profile = {cs2_profile_dr, cs2_profile_size, cs2_profile};
+
+    int ng = 2; // number of guard zones
+    index_t si = NCONS * (nj + 2 * ng);
+    index_t sj = NCONS;
+
+    FOR_EACH_2D(ni, nj)
+    {
+        real r = patch_r0 * exp((i + 0.5) * dlogr);
+        real q = (j + 0.5) * dphi;
+        real *pc = &primitive[(i + ng) * si + (j + ng) * sj];
+        real cs2 = sound_speed_squared(soundspeed2, mach_squared, eos_type, r * cos(q), r * sin(q), &mass_list, &profile);
+        wavespeed[(index_t) i * nj + j] = primitive_max_wavespeed(pc, cs2);
+    }
+}
@@ -1,52 +1,276 @@
 """
 Isothermal solver for the binary accretion problem in 2D planar coordinates.
+
+The disk is evolved on a Cartesian mesh with point mass gravity, sinks,
+buffers, and viscosity; see the `Options` fields, and the `Solver` methods,
+for the optional physics and numerics.
 """
 
+from copy import copy
 from logging import getLogger
+from math import pi
 from typing import NamedTuple, List
-from sailfish.kernel.library import Library
-from sailfish.kernel.system import get_array_module, execution_context, num_devices
+from sailfish.boundary import BoundaryCondition, edge_conditions, fill_guard_zones
+from sailfish.clock import Clock
+from sailfish.fields import SURFACE_DENSITY, VELOCITY_X, VELOCITY_Y, tracer_fields
+from sailfish.kernel.custom import CUSTOM_MAX_FIELDS, CustomSource
+from sailfish.kernel.library import PRECISIONS, Library, host_zeros
+from sailfish.kernel.system import copy_between_devices, get_array_module
+from sailfish.kernel.system import execution_context, num_devices
 from sailfish.mesh import PlanarCartesian2DMesh
 from sailfish.physics.circumbinary import (
     Physics,
+    CoolingModel,
     EquationOfState,
     ViscosityModel,
+    SinkModel,
     Diagnostic,
+    FramePointMasses,
+    LivePointMasses,
+    RotatingFrame,
+    hill_mask,
 )
+from sailfish.physics.roche import RocheGeometry, SOURCE_REGIONS
+from sailfish.physics.self_gravity import SelfGravity
 from sailfish.solver_base import SolverBase
-from sailfish.subdivide import subdivide, to_host, concat_on_host, lazy_reduce
+from sailfish.solvers import advance_with_retries, check_invalid_zones
+from sailfish.subdivide import (
+    DeferredCopy,
+    DeferredReduction,
+    PatchReductions,
+    SolverReductions,
+    concat_on_host,
+    lazy_reduce,
+    resolve,
+    sample_on_host,
+    to_host,
+)
 
 
 logger = getLogger(__name__)
 
 
+def viscosity_coefficients(physics):
+    """
+    Return the kinematic viscosity and alpha coefficients passed to the
+    kernels for the physics' viscosity model; the one which isn't used is
+    zero.
+
+    The viscous stress is either that of a constant kinematic viscosity `nu`,
+    or of an alpha viscosity, `nu = alpha cs^2 / Omega`, where `cs` is the
+    local isothermal sound speed and `Omega` the Keplerian frequency due to
+    the point masses (with softening). In a locally isothermal disk around
+    one mass, the alpha viscosity grows outward as `r^(1/2)`.
+    """
+    if physics.viscosity_model == ViscosityModel.CONSTANT_ALPHA:
+        return 0.0, physics.alpha
+    else:
+        return physics.viscosity_coefficient, 0.0
+
+
+# Estimates of the fastest left and right-going waves in the HLL Riemann
+# solver. Davis uses the outer characteristic speeds of the two face states,
+# and Einfeldt bounds those by the Roe-averaged characteristic speeds. The
+# isothermal sound speed is exact at the face, so exact is the same as Davis
+# here. Wider estimates are more stable and more diffusive.
+WAVESPEED_ESTIMATORS = dict(davis=0, einfeldt=1, exact=2)
+
+# How the stiff relaxation terms, the sinks and the buffers, are updated in
+# each Runge-Kutta stage. The explicit update is unstable once the rate times
+# dt exceeds about one. The subcycled one takes the term in sub-steps of at
+# most half the inverse rate, and the exponential one decays the solution
+# toward that of the term exactly over the stage; both are stable at any
+# rate, and leave the term out of the source limit of `timestep_limiters`.
+SOURCE_UPDATES = dict(explicit=0, subcycle=1, exponential=2)
+
+# The terms of the conservation audit: those recorded by the kernels, in the
+# order of the third axis of a patch's budget array, and the split steps.
+BUDGET_TERMS = ("boundary", "gravity", "sinks", "buffers", "frame", "floors")
+AUDIT_TERMS = BUDGET_TERMS + ("split_steps",)
+
+
 class Options(NamedTuple):
     """
     Contains parameters which are solver specific options.
     """
 
     velocity_ceiling: float = 1e12
+    """ Upper limit of the gas speed in each direction """
     density_floor: float = 1e-12
+    """ Lower limit of the surface density """
     rk_order: int = 2
-
-
-def initial_condition(setup, mesh, time):
+    """ Order of the Runge-Kutta time stepping [1|2|3] """
+    cfl_number: float = None
+    """ CFL parameter, the recommended one if None """
+    wavespeed_estimator: str = "davis"
+    """ Wavespeed estimate of the Riemann solver [davis|einfeldt|exact] """
+    boost_vx: float = 0.0
+    """ Velocity of the mesh frame along x """
+    boost_vy: float = 0.0
+    """ Velocity of the mesh frame along y """
+    frame_rotation: float = 0.0
+    """ Angular frequency of the mesh rotation (experimental) """
+    record_face_fluxes: bool = False
+    """ Record the time-integrated fluxes through the mesh faces """
+    secular_torque: float = 0.01
+    """ Binary torque coefficient of the secular steps """
+    secular_zones: int = 200
+    """ Number of radial zones of the secular steps """
+    specialize_kernels: bool = False
+    """ Compile the run's fixed physics into the kernels """
+    invalid_state_cadence: int = 10
+    """ Iterations between checks for invalid states, 0 for never """
+    max_step_retries: int = 0
+    """ Times a failed iteration is retried with half dt """
+    sink_update: str = "explicit"
+    """ Update of the sink terms [explicit|subcycle|exponential] """
+    buffer_update: str = "explicit"
+    """ Update of the buffer terms [explicit|subcycle|exponential] """
+    precision: str = "float64"
+    """ Floating point precision of the kernels and patch arrays [float64|float32] """
+    copy_stream: bool = False
+    """ Download output data on a second stream per GPU, overlapped with steps """
+    conservation_audit: bool = False
+    """ Record the change of the mass and momentum by each kind of term """
+
+
+def initial_condition(setup, mesh, time, frame=None, num_fields=3):
     """
-    Generate a 2D array of primitive data from a mesh and a setup.
+    Generate a 2D array of primitive data from a mesh and a setup. If a
+    `RotatingFrame` is given, the mesh is in that frame. Fields after the
+    velocity, i.e. tracers, are zero unless the setup writes them.
     """
-    import numpy as np
-
     ni, nj = mesh.shape
-    primitive = np.zeros([ni, nj, 3])
+    primitive = host_zeros((ni, nj, num_fields))
 
     for i in range(ni):
         for j in range(nj):
-            setup.primitive(time, mesh.cell_coordinates(i, j), primitive[i, j])
+            sample_setup(setup, mesh, time, frame, i, j, primitive[i, j])
 
     return primitive
 
 
-class Patch:
+def sample_setup(setup, mesh, time, frame, i, j, p):
+    """
+    Write the setup's primitive state at the center of the zone `(i, j)` to
+    `p`, in the frame of the mesh if a `RotatingFrame` is given. The indexes
+    can be outside the mesh, e.g. for guard zones.
+    """
+    if frame is None:
+        setup.primitive(time, mesh.cell_coordinates(i, j), p)
+        return
+
+    x, y = mesh.cell_coordinates(i, j)
+    setup.primitive(time, frame.from_frame(time, x, y), p)
+    vx, vy = frame.to_frame(time, p[1], p[2])
+    wx, wy = frame.mesh_velocity(x, y)
+    p[1], p[2] = vx - wx, vy - wy
+
+
+def sound_speed_table(physics, mesh, num_guard=2):
+    """
+    Return the radial spacing and the squared sound speeds, at evenly spaced
+    radii from the origin, of a profile isothermal EOS. The table reaches the
+    farthest guard zone corner of the mesh with a spacing of a quarter zone,
+    and the kernels interpolate it linearly. The profile is evaluated at one
+    spacing for the origin, where it may diverge. For other EOS types, the
+    table is a single zero.
+    """
+    if physics.eos_type != EquationOfState.PROFILE_ISOTHERMAL:
+        return 1.0, [0.0]
+
+    dx, dy = mesh.dx * num_guard, mesh.dy * num_guard
+    x = max(abs(mesh.x0 - dx), abs(mesh.x1 + dx))
+    y = max(abs(mesh.y0 - dy), abs(mesh.y1 + dy))
+    dr = 0.25 * min(mesh.dx, mesh.dy)
+    size = int((x * x + y * y) ** 0.5 / dr) + 2
+    profile = physics.sound_speed_profile
+    return dr, [profile(max(n, 1) * dr) ** 2 for n in range(size)]
+
+
+def interpolate_sound_speed_table(dr, table, x, y, xp):
+    """
+    Return the squared sound speed at the given coordinates, interpolated
+    from a table returned by `sound_speed_table`, as the kernels do it.
+    """
+    r = xp.sqrt(x * x + y * y)
+    return xp.interp(r, dr * xp.arange(len(table)), xp.asarray(table))
+
+
+def reservoir_face_weights(mesh, excised, index_range, first):
+    """
+    Return the weights of the face fluxes of a patch in the inflow of mass,
+    and of angular momentum about the origin, into the excised zones.
+
+    The result is a pair `(mass, angular_momentum)` for each of the x- and
+    y-directed faces, with the shapes of the patch's face flux arrays, but
+    only the mass and momentum components. Faces between two active or two
+    excised zones have zero weight. The first face of a patch is also
+    recorded by the patch to its left, so it's left out unless the patch is
+    the `first` one.
+    """
+    import numpy as np
+
+    i0, i1 = index_range
+    faces_x = mesh.x0 + mesh.dx * np.arange(i0, i1 + 1)[:, None]
+    faces_y = mesh.y0 + mesh.dy * np.arange(mesh.shape[1] + 1)[None, :]
+    x, y = mesh.cell_center_arrays()
+    x, y = x[i0:i1], y[i0:i1]
+    e = excised.astype(float)
+
+    # A flux in the positive direction enters the excision if the zone on
+    # the right (or top) of the face is excised, and leaves it otherwise.
+    wx = np.diff(np.pad(e, ((1, 1), (0, 0))), axis=0)[i0 : i1 + 1] * mesh.dy
+    wy = np.diff(np.pad(e[i0:i1], ((0, 0), (1, 1))), axis=1) * mesh.dx
+
+    if not first:
+        wx[0] = 0.0
+
+    def weights(w, x, y):
+        zero = np.zeros_like(w)
+        mass = np.stack([w, zero, zero], axis=-1)
+        angular_momentum = np.stack([zero, -w * y, w * x], axis=-1)
+        return mass, angular_momentum
+
+    return [weights(wx, faces_x, y[:1]), weights(wy, x[:, :1], faces_y)]
+
+
+def characteristic_guard_zones(edge, far_field, cs2, axis, side, xp):
+    """
+    Return the primitive data in the guard zones of a non-reflecting edge.
+
+    The `edge` array is the outermost zones along the edge, `far_field` the
+    far-field state in the guard zones, with a leading axis over the guard
+    zones, and `cs2` the squared sound speed there. The edge is on the lower
+    (`side=0`) or upper (`side=1`) end of the given axis.
+
+    The boundary is non-reflecting, so it can replace the buffer zone. The
+    guard zones are set from the isothermal Riemann invariants normal to the
+    edge, `v_n +/- cs ln(sigma)`: those of the outgoing characteristics are
+    taken from the outermost zone, and those of the incoming ones from the
+    far field, which is the setup's initial state in the guard zones. The
+    velocity along the edge, and the tracers, are upwinded in the same way,
+    and supersonic inflows or outflows take every field from the far field or
+    the outermost zone respectively.
+    """
+    sign = -1.0 if side == 0 else 1.0
+    q = 1 + axis
+    edge = xp.broadcast_to(edge, far_field.shape)
+    cs = xp.sqrt(cs2)
+    vn_edge = sign * edge[..., q]
+    vn_far = sign * far_field[..., q]
+    w_out = vn_edge + cs * xp.log(edge[..., 0])
+    w_in = vn_far - cs * xp.log(far_field[..., 0])
+
+    upwind = xp.where((vn_edge > 0.0)[..., None], edge, far_field)
+    upwind[..., 0] = xp.exp(0.5 * (w_out - w_in) / cs)
+    upwind[..., q] = sign * 0.5 * (w_out + w_in)
+    upwind = xp.where((vn_edge >= cs)[..., None], edge, upwind)
+    upwind = xp.where((vn_edge <= -cs)[..., None], far_field, upwind)
+    return upwind
+
+
+class Patch(PatchReductions):
     """
     Holds the array buffer state for the solution on a subset of the
     solution domain.
@@ -62,17 +286,21 @@ class Patch:
         options,
         buffer_outer_radius,
         buffer_surface_density,
+        zone_mask,
+        target,
         lib,
         xp,
         execution_context,
     ):
         i0, i1 = index_range
         ni, nj = i1 - i0, mesh.shape[1]
+        nq = primitive.shape[2]
         self.lib = lib
         self.mesh = mesh
         self.xp = xp
         self.execution_context = execution_context
-        self.time = self.time0 = time
+        self.clock = Clock(time)
+        self.index_range = index_range
         self.shape = (i1 - i0, nj)  # not including guard zones
         self.physics = physics
         self.options = options
@@ -88,10 +316,117 @@ class Patch:
             y1 = self.yr - 0.5 * mesh.dy
             self.coordinate_array_x = xp.linspace(x0, x1, ni)[:, None]
             self.coordinate_array_y = xp.linspace(y0, y1, nj)[None, :]
-            self.wavespeeds = xp.zeros(primitive.shape[:2])
-            self.primitive1 = xp.array(primitive)
-            self.primitive2 = xp.array(primitive)
-            self.conserved0 = xp.zeros(primitive.shape)
+            self.wavespeeds = lib.zeros(primitive.shape[:2])
+            self.cs2_profile_dr, cs2_profile = sound_speed_table(physics, mesh)
+            self.cs2_profile = xp.array(cs2_profile, dtype=lib.precision)
+            self.primitive1 = lib.array(primitive)
+            self.primitive2 = lib.array(primitive)
+            self.conserved0 = lib.zeros(primitive.shape)
+            self.invalid_zones = xp.zeros(self.shape, dtype=lib.precision)
+
+            if options.record_face_fluxes:
+                self.face_flux_x = lib.zeros((ni + 1, nj, nq))
+                self.face_flux_y = lib.zeros((ni, nj + 1, nq))
+                self.face_flux_x_total = lib.zeros((ni + 1, nj, nq))
+                self.face_flux_y_total = lib.zeros((ni, nj + 1, nq))
+            else:
+                self.face_flux_x = xp.zeros(1, dtype=lib.precision)
+                self.face_flux_y = xp.zeros(1, dtype=lib.precision)
+
+            if options.conservation_audit:
+                self.budget = lib.zeros((ni, nj, len(BUDGET_TERMS), 3))
+                self.budget_total = xp.zeros((len(AUDIT_TERMS), 3))
+            else:
+                self.budget = xp.zeros(1, dtype=lib.precision)
+
+            self.mask_is_enabled = zone_mask is not None
+
+            if self.mask_is_enabled:
+                self.zone_mask = xp.array(zone_mask, dtype=lib.precision)
+            else:
+                self.zone_mask = xp.zeros(1, dtype=lib.precision)
+
+            self.target_is_enabled = target is not None
+
+            if self.target_is_enabled:
+                self.target_rate = xp.array(target[0], dtype=lib.precision)
+                self.target_primitive = xp.array(target[1], dtype=lib.precision)
+            else:
+                self.target_rate = xp.zeros(1, dtype=lib.precision)
+                self.target_primitive = xp.zeros(1, dtype=lib.precision)
+
+            if options.copy_stream and not lib.cpu_mode:
+                self.copy_stream = xp.cuda.Stream(non_blocking=True)
+            else:
+                self.copy_stream = None
+
+    def set_far_field(self, far_field):
+        """
+        Keep the far-field state of a characteristic boundary, an array with
+        the shape of the primitive data which is used in the guard zones, and
+        the zone center coordinates including the guard zones.
+        """
+        ng = 2  # number of guard zones
+        ni, nj = self.shape
+        dx, dy = self.mesh.dx, self.mesh.dy
+
+        with self.execution_context:
+            self.far_field = self.xp.asarray(far_field)
+            self.guard_coordinate_x = self.xp.linspace(
+                self.xl - (ng - 0.5) * dx, self.xr + (ng - 0.5) * dx, ni + 2 * ng
+            )[:, None]
+            self.guard_coordinate_y = self.xp.linspace(
+                self.yl - (ng - 0.5) * dy, self.yr + (ng - 0.5) * dy, nj + 2 * ng
+            )[None, :]
+
+    def sound_speed_squared(self, x, y):
+        """
+        Return the squared sound speed at the given coordinates, as the
+        kernels evaluate it for the equation of state.
+        """
+        if self.physics.eos_type == EquationOfState.GLOBALLY_ISOTHERMAL:
+            cs2 = self.physics.sound_speed**2
+            return self.xp.full(self.xp.broadcast(x, y).shape, cs2)
+
+        if self.physics.eos_type == EquationOfState.PROFILE_ISOTHERMAL:
+            return interpolate_sound_speed_table(
+                self.cs2_profile_dr, self.cs2_profile, x, y, self.xp
+            )
+
+        phi = 0.0
+
+        for m in self.physics.resolved_point_masses(self.time, self.mesh):
+            if m.mass > 0.0:
+                r2 = (x - m.position_x) ** 2 + (y - m.position_y) ** 2
+                phi = phi + m.potential(r2, self.xp)
+
+        return -phi / self.physics.mach_number**2
+
+    def fill_characteristic_guard_zones(self, primitive, first, last):
+        """
+        Overwrite the guard zones on the domain edges of a primitive array
+        with the characteristic boundary condition. The `first` and `last`
+        flags say whether this patch has the left and right domain edges.
+        """
+        ng = 2  # number of guard zones
+        x, y = self.guard_coordinate_x, self.guard_coordinate_y
+        edges = [(1, 0), (1, 1)] + [(0, 0)] * first + [(0, 1)] * last
+
+        with self.execution_context:
+            for axis, side in edges:
+                guard = slice(None, ng) if side == 0 else slice(-ng, None)
+
+                if axis == 0:
+                    a, f = primitive, self.far_field
+                    cs2 = self.sound_speed_squared(x[guard], y)
+                else:
+                    a, f = primitive.swapaxes(0, 1), self.far_field.swapaxes(0, 1)
+                    cs2 = self.sound_speed_squared(x.T, y.T[guard])
+
+                edge = a[ng] if side == 0 else a[-ng - 1]
+                a[guard] = characteristic_guard_zones(
+                    edge, f[guard], cs2, axis, side, self.xp
+                )
 
     @property
     def cell_center_coordinate_arrays(self):
@@ -112,7 +447,7 @@ class Patch:
         if which_mass not in (1, 2):
             raise ValueError("which_mass must be either 1 or 2")
 
-        m = self.physics.point_masses(self.time)[which_mass - 1]
+        m = self.physics.resolved_point_masses(self.time, self.mesh)[which_mass - 1]
 
         with self.execution_context:
             cons_rate = self.xp.zeros_like(self.conserved0)
@@ -128,6 +463,7 @@ class Patch:
                 m.velocity_y,
                 m.mass * gravity,
                 m.softening_length,
+                m.softening_model.value,
                 m.sink_rate * accretion,
                 m.sink_radius,
                 m.sink_model.value,
@@ -136,11 +472,100 @@ class Patch:
             )
         return cons_rate[ng:-ng, ng:-ng]
 
+    def probe_source_term(self, which_probe):
+        """
+        Return an array of the rates of conserved quantities which would
+        result from the gravity of a probe mass, if it were an ordinary point
+        mass. The rates are not applied to the solution.
+        """
+        ng = 2  # number of guard cells
+        m = self.physics.probe_masses(self.time)[which_probe]
+
+        with self.execution_context:
+            cons_rate = self.xp.zeros_like(self.conserved0)
+
+            self.lib.cbdiso_2d_point_mass_source_term[self.shape](
+                self.xl,
+                self.xr,
+                self.yl,
+                self.yr,
+                m.position_x,
+                m.position_y,
+                m.velocity_x,
+                m.velocity_y,
+                m.mass,
+                m.softening_length,
+                m.softening_model.value,
+                0.0,
+                0.0,
+                SinkModel.INACTIVE.value,
+                self.primitive1,
+                cons_rate,
+            )
+        return cons_rate[ng:-ng, ng:-ng]
+
+    def custom_source_step(self, custom_source, dt):
+        """
+        Apply a custom source term to the primitive data over a time `dt`, as
+        a split step; see `sailfish.kernel.custom`.
+        """
+        parameters = custom_source.parameters or [0.0]
+
+        with self.execution_context:
+            custom_source.lib.custom_source_step[self.shape](
+                self.xl,
+                self.xr,
+                self.yl,
+                self.yr,
+                self.time,
+                dt,
+                self.options.density_floor,
+                self.primitive1.shape[2],
+                len(custom_source.parameters),
+                self.xp.array(parameters),
+                self.primitive1,
+                int(self.mask_is_enabled),
+                self.zone_mask,
+            )
+
+    def source_function_step(self, function, dt):
+        """
+        Apply the rates of a Python source function to the primitive data
+        over a time `dt`, as a split step, like `custom_source_step`.
+        """
+        ng = 2  # number of guard zones
+        xp = self.xp
+        x, y = self.cell_center_coordinate_arrays
+
+        with self.execution_context:
+            prim = self.primitive1[ng:-ng, ng:-ng]
+            x, y = xp.broadcast_to(x, self.shape), xp.broadcast_to(y, self.shape)
+            rate = function(self.time, x, y, prim.copy())
+            rate = xp.asarray(rate, dtype=prim.dtype)
+
+            if rate.shape != prim.shape:
+                raise ValueError(
+                    f"source function rates have shape {rate.shape}, "
+                    f"expected {prim.shape}"
+                )
+
+            sigma = prim[..., 0] + dt * rate[..., 0]
+            sigma = xp.maximum(sigma, self.options.density_floor)
+            momenta = prim[..., :1] * prim[..., 1:] + dt * rate[..., 1:]
+            updated = xp.empty_like(prim)
+            updated[..., 0] = sigma
+            updated[..., 1:] = momenta / sigma[..., None]
+
+            if self.mask_is_enabled:
+                updated = xp.where(self.zone_mask[..., None] != 0.0, updated, prim)
+
+            prim[...] = updated
+
     def maximum_wavespeed(self):
         """
         Return the maximum wavespeed over a given patch.
         """
-        m1, m2 = self.physics.point_masses(self.time)
+        m1, m2 = self.physics.resolved_point_masses(self.time, self.mesh)
         with self.execution_context:
             self.lib.cbdiso_2d_wavespeed[self.shape](
                 self.xl,
@@ -150,12 +575,16 @@ class Patch:
                 self.physics.sound_speed**2,
                 self.physics.mach_number**2,
                 self.physics.eos_type.value,
+                self.cs2_profile_dr,
+                len(self.cs2_profile),
+                self.cs2_profile,
                 m1.position_x,
                 m1.position_y,
                 m1.velocity_x,
                 m1.velocity_y,
                 m1.mass,
                 m1.softening_length,
+                m1.softening_model.value,
                 m1.sink_rate,
                 m1.sink_radius,
                 m1.sink_model.value,
@@ -165,13 +594,16 @@ class Patch:
                 m2.velocity_y,
                 m2.mass,
                 m2.softening_length,
+                m2.softening_model.value,
                 m2.sink_rate,
                 m2.sink_radius,
                 m2.sink_model.value,
                 self.primitive1,
                 self.wavespeeds,
+                int(self.mask_is_enabled),
+                self.zone_mask,
             )
-            return self.wavespeeds.max()
+            return self.reduce("max", array="wavespeeds")
 
     def recompute_conserved(self):
         """
@@ -183,6 +615,45 @@ class Patch:
                 self.conserved0,
             )
 
+    def total_conserved(self):
+        """
+        Return the total mass and momentum of the patch's active zones, as an
+        array on the patch's device, in double precision.
+        """
+        ng = 2  # number of guard zones
+        self.recompute_conserved()
+
+        with self.execution_context:
+            u = self.conserved0[ng:-ng, ng:-ng, :3].astype("float64")
+
+            if self.mask_is_enabled:
+                u = u * self.zone_mask[..., None]
+
+            return u.sum(axis=(0, 1)) * (self.mesh.dx * self.mesh.dy)
+
+    def fold_budget(self):
+        """
+        Add the conservation audit terms recorded by the kernels since the
+        start of the iteration to the totals, and reset them.
+        """
+        with self.execution_context:
+            area = self.mesh.dx * self.mesh.dy
+            budget = self.budget.astype("float64").sum(axis=(0, 1))
+            self.budget_total[: len(BUDGET_TERMS)] += budget * area
+            self.budget[...] = 0.0
+
+    def take_budget(self):
+        """
+        Return the totals of the conservation audit terms since the previous
+        call, an array with a row for each of `AUDIT_TERMS`, and reset them.
+        """
+        self.fold_budget()
+
+        with self.execution_context:
+            budget = self.budget_total.copy()
+            self.budget_total[...] = 0.0
+            return budget
+
     def advance_rk(self, rk_param, dt):
         """
         Pass required parameters for time evolution of the setup.
@@ -190,9 +661,10 @@ class Patch:
         This function calls the C-module function responsible for performing time evolution using a
         RK algorithm to update the parameters of the setup.
         """
-        m1, m2 = self.physics.point_masses(self.time)
+        m1, m2 = self.physics.resolved_point_masses(self.time, self.mesh)
         buffer_central_mass = m1.mass + m2.mass
         buffer_surface_density = self.buffer_surface_density
+        nu, alpha = viscosity_coefficients(self.physics)
 
         with self.execution_context:
             self.lib.cbdiso_2d_advance_rk[self.shape](
@@ -209,12 +681,17 @@ class Patch:
                 self.buffer_outer_radius,
                 self.physics.buffer_onset_width,
                 int(self.physics.buffer_is_enabled),
+                int(self.target_is_enabled),
+                self.target_rate,
+                self.target_primitive,
+                SOURCE_UPDATES[self.options.buffer_update],
                 m1.position_x,
                 m1.position_y,
                 m1.velocity_x,
                 m1.velocity_y,
                 m1.mass,
                 m1.softening_length,
+                m1.softening_model.value,
                 m1.sink_rate,
                 m1.sink_radius,
                 m1.sink_model.value,
@@ -224,31 +701,125 @@ class Patch:
                 m2.velocity_y,
                 m2.mass,
                 m2.softening_length,
+                m2.softening_model.value,
                 m2.sink_rate,
                 m2.sink_radius,
                 m2.sink_model.value,
+                SOURCE_UPDATES[self.options.sink_update],
                 self.physics.sound_speed**2,
                 self.physics.mach_number**2,
                 self.physics.eos_type.value,
-                self.physics.viscosity_coefficient,
+                self.cs2_profile_dr,
+                len(self.cs2_profile),
+                self.cs2_profile,
+                nu,
+                alpha,
+                self.options.frame_rotation,
                 rk_param,
                 dt,
                 self.options.velocity_ceiling,
                 self.options.density_floor,
+                self.invalid_zones,
+                WAVESPEED_ESTIMATORS[self.options.wavespeed_estimator],
+                int(self.options.record_face_fluxes),
+                self.face_flux_x,
+                self.face_flux_y,
+                int(self.options.conservation_audit),
+                self.budget,
+                int(self.mask_is_enabled),
+                self.zone_mask,
             )
-        self.time = self.time0 * rk_param + (self.time + dt) * (1.0 - rk_param)
+        self.clock.advance_stage(rk_param, dt)
         self.primitive1, self.primitive2 = self.primitive2, self.primitive1
 
+    @property
+    def time(self):
+        return self.clock.time
+
+    def reinitialize(self, discard_face_fluxes=True):
+        """
+        Re-derive the conserved data and the second primitive buffer from the
+        primitive data, whose guard zones must already be filled, and discard
+        the recorded face fluxes unless `discard_face_fluxes` is false.
+        """
+        self.recompute_conserved()
+
+        with self.execution_context:
+            self.primitive2[...] = self.primitive1
+            self.wavespeeds[...] = 0.0
+            self.invalid_zones[...] = 0.0
+
+            if self.options.record_face_fluxes and discard_face_fluxes:
+                self.face_flux_x[...] = 0.0
+                self.face_flux_y[...] = 0.0
+                self.face_flux_x_total[...] = 0.0
+                self.face_flux_y_total[...] = 0.0
+
+            if self.options.conservation_audit:
+                self.budget[...] = 0.0
+                self.budget_total[...] = 0.0
+
     def new_iteration(self):
-        self.time0 = self.time
+        self.clock.new_iteration()
         self.recompute_conserved()
 
+        if self.options.conservation_audit:
+            self.fold_budget()
+
+        if self.options.record_face_fluxes:
+            self.fold_face_fluxes()
+
+    def fold_face_fluxes(self):
+        """
+        Add the face fluxes of the latest iteration to the totals, and clear
+        them for the next one.
+        """
+        with self.execution_context:
+            self.face_flux_x_total += self.face_flux_x
+            self.face_flux_y_total += self.face_flux_y
+            self.face_flux_x[...] = 0.0
+            self.face_flux_y[...] = 0.0
+
+    def take_face_fluxes(self):
+        """
+        Return the face fluxes integrated over time since the previous call,
+        including the latest iteration, as a pair of arrays for the x- and
+        y-directed faces, and reset them.
+        """
+        self.fold_face_fluxes()
+
+        with self.execution_context:
+            fx = self.face_flux_x_total.astype("float64")
+            fy = self.face_flux_y_total.astype("float64")
+            self.face_flux_x_total[...] = 0.0
+            self.face_flux_y_total[...] = 0.0
+            return fx, fy
+
+    def synchronize(self):
+        """
+        Wait for the kernels launched on the patch's device stream.
+        """
+        if not self.lib.cpu_mode:
+            with self.execution_context:
+                self.xp.cuda.get_current_stream().synchronize()
+
+    def is_done(self):
+        """
+        Return whether the kernels launched on the patch's device stream have
+        finished.
+        """
+        if self.lib.cpu_mode:
+            return True
+
+        with self.execution_context:
+            return self.xp.cuda.get_current_stream().done
+
     @property
     def primitive(self):
         return self.primitive1
 
 
-class Solver(SolverBase):
+class Solver(SolverReductions, SolverBase):
     """
     Adapter class to drive the iso_2d C extension module.
     """
@@ -273,41 +844,277 @@ class Solver(SolverBase):
         self._physics = physics = Physics(**physics)
         self._options = options = Options(**options)
 
+        if options.rk_order not in (1, 2, 3):
+            raise ValueError("solver only supports rk_order in 1, 2, 3")
+
+        if options.wavespeed_estimator not in WAVESPEED_ESTIMATORS:
+            raise ValueError(
+                f"wavespeed_estimator must be one of {list(WAVESPEED_ESTIMATORS)}"
+            )
+
+        for update in (options.sink_update, options.buffer_update):
+            if update not in SOURCE_UPDATES:
+                raise ValueError(
+                    f"source updates must be one of {list(SOURCE_UPDATES)}"
+                )
+
+        if options.precision not in PRECISIONS:
+            raise ValueError(f"precision must be one of {list(PRECISIONS)}")
+
+        if options.cfl_number is not None and options.cfl_number > self.maximum_cfl:
+            raise ValueError(f"cfl_number must not exceed {self.maximum_cfl}")
+
+        if options.invalid_state_cadence < 0:
+            raise ValueError("invalid_state_cadence must be non-negative")
+
+        if options.max_step_retries < 0:
+            raise ValueError("max_step_retries must be non-negative")
+
         if type(mesh) is not PlanarCartesian2DMesh:
             raise ValueError("solver only supports 2D cartesian mesh")
 
-        if setup.boundary_condition != "outflow":
-            raise ValueError("solver only supports outflow boundary condition")
+        if setup is None:
+            raise ValueError("solver needs a setup for its initial and boundary data")
+
+        if setup.boundary_condition == "characteristic":
+            edges = None
+        else:
+            edges = edge_conditions(setup.boundary_condition)
+
+            if not all(mode.is_outflow for axis in edges for mode in axis):
+                raise ValueError("solver supports outflow or characteristic boundaries")
 
         if physics.viscosity_model not in (
             ViscosityModel.NONE,
             ViscosityModel.CONSTANT_NU,
+            ViscosityModel.CONSTANT_ALPHA,
         ):
-            raise ValueError("solver only supports constant-nu viscosity")
+            raise ValueError(f"unknown viscosity model {physics.viscosity_model}")
+
+        if physics.viscosity_model == ViscosityModel.CONSTANT_ALPHA:
+            if physics.alpha <= 0.0:
+                raise ValueError("alpha viscosity needs alpha > 0")
 
         if physics.eos_type not in (
             EquationOfState.GLOBALLY_ISOTHERMAL,
             EquationOfState.LOCALLY_ISOTHERMAL,
+            EquationOfState.PROFILE_ISOTHERMAL,
         ):
             raise ValueError("solver only supports isothermal equation of states")
 
+        if physics.eos_type == EquationOfState.PROFILE_ISOTHERMAL:
+            if physics.sound_speed_profile is None:
+                raise ValueError("a profile isothermal EOS needs a sound_speed_profile")
+
         if physics.cooling_coefficient != 0.0:
             raise ValueError("solver does not support thermal cooling")
 
+        if physics.cooling_model != CoolingModel.NONE:
+            raise ValueError("solver does not support thermal cooling")
+
+        if physics.external_gravity != 0.0:
+            raise ValueError("solver does not support uniform external gravity")
+
         if not physics.constant_softening:
             raise ValueError("solver only supports constant gravitational softening")
 
+        if physics.num_tracers < 0:
+            raise ValueError("num_tracers must be non-negative")
+
+        for d in physics.diagnostics:
+            if d.source_region is not None and d.source_region not in SOURCE_REGIONS:
+                raise ValueError(f"source_region must be one of {SOURCE_REGIONS}")
+
+        # A restarted solution of live point masses, or of a reservoir, also
+        # has their state.
+        if isinstance(solution, dict):
+            two_body = solution.get("two_body")
+            reservoir = solution.get("reservoir", physics.reservoir)
+            solution = solution["primitive"]
+        else:
+            two_body = None
+            reservoir = physics.reservoir
+
+        if physics.live_point_masses:
+            live = LivePointMasses.start(physics, time, two_body)
+            self._physics = physics = physics._replace(point_mass_function=live)
+            logger.info("point masses are integrated by the solver")
+        else:
+            live = None
+
+        self.live_point_masses = live
+        self.frame = frame = RotatingFrame(options.frame_rotation)
+
+        if frame.omega != 0.0:
+            if options.boost_vx != 0.0 or options.boost_vy != 0.0:
+                raise ValueError("a rotating mesh can't also have a frame boost")
+
+            # Everything which reads the point masses sees them on the mesh.
+            self._physics = physics = physics._replace(
+                point_mass_function=FramePointMasses(frame, physics.point_masses),
+                probe_mass_function=FramePointMasses(frame, physics.probe_masses),
+            )
+            logger.info(f"mesh rotates at angular frequency {frame.omega}")
+
         xp = get_array_module(mode)
         ng = 2  # number of guard zones
-        nq = 3  # number of conserved quantities
+        nq = 3 + physics.num_tracers  # number of conserved quantities
         with open(__file__.replace(".py", ".c")) as f:
             code = f.read()
-        lib = Library(code, mode=mode, debug=False)
+
+        # Zones outside the setup's mask of active zones are not updated, and
+        # keep the initial (or restarted) solution; their neighbors see them
+        # as a fixed boundary.
+        zone_mask = setup.active_zones(mesh)
+
+        if zone_mask is not None:
+            zone_mask = np.asarray(zone_mask, dtype=bool)
+
+            if zone_mask.shape != mesh.shape:
+                raise ValueError(
+                    f"zone mask has shape {zone_mask.shape}, mesh is {mesh.shape}"
+                )
+
+            logger.info(f"{zone_mask.mean():.1%} of zones are active")
+
+        boosted = options.boost_vx != 0.0 or options.boost_vy != 0.0
+        rest_frame = not boosted and frame.omega == 0.0
+
+        # The setup's buffer target is in the rest frame, and is driven
+        # toward in each zone at its own rate. On a boosted or rotating mesh,
+        # its velocities are converted to the mesh frame. The target is fixed
+        # on the mesh, so with a rotating one it should be axisymmetric; the
+        # velocity at a point in the mesh is then that of the target there,
+        # less the frame velocity.
+        buffer_target = setup.buffer_target(mesh)
+
+        if buffer_target is not None:
+            target_rate, target_state = (np.array(a, float) for a in buffer_target)
+
+            if target_rate.shape != mesh.shape:
+                raise ValueError(
+                    f"buffer target rate has shape {target_rate.shape}, "
+                    f"mesh is {mesh.shape}"
+                )
+            if target_state.shape != mesh.shape + (3,):
+                raise ValueError(
+                    f"buffer target state has shape {target_state.shape}, "
+                    f"expected {mesh.shape + (3,)}"
+                )
+            if (target_rate < 0.0).any():
+                raise ValueError("buffer target rate must be non-negative")
+
+            if not rest_frame:
+                x, y = mesh.cell_center_arrays()
+                wx, wy = frame.mesh_velocity(x, y)
+                target_state[..., 1] += options.boost_vx - wx
+                target_state[..., 2] += options.boost_vy - wy
+
+        if physics.inner_buffer_radius < 0.0:
+            raise ValueError("inner_buffer_radius must be non-negative")
+
+        inner_buffer = physics.inner_buffer_radius > 0.0
+        target_is_enabled = buffer_target is not None or inner_buffer
+
+        # The zones inside a reservoir's radius must be masked, and its inflow
+        # is measured by the face fluxes, so they're always recorded.
+        if reservoir is not None:
+            if not rest_frame:
+                raise ValueError("an inner reservoir needs the mesh in the rest frame")
+
+            x, y = mesh.cell_center_arrays()
+            excised = x * x + y * y < reservoir.radius**2
+
+            if not excised.any():
+                raise ValueError("no zones are inside the reservoir radius")
+
+            if zone_mask is None or (zone_mask & excised).any():
+                raise ValueError("the zones inside the reservoir radius must be masked")
+
+            self._options = options = options._replace(record_face_fluxes=True)
+            logger.info(f"inner reservoir inside r={reservoir.radius}")
+        else:
+            excised = None
+
+        if options.specialize_kernels:
+            # Physics and options which are fixed for the run are baked into
+            # the compiled kernels; see `sailfish.kernel.library`.
+            nu, alpha = viscosity_coefficients(physics)
+            constants = dict(
+                buffer_is_enabled=int(physics.buffer_is_enabled),
+                buffer_driving_rate=physics.buffer_driving_rate,
+                buffer_onset_width=physics.buffer_onset_width,
+                target_is_enabled=int(target_is_enabled),
+                cs2=physics.sound_speed**2,
+                mach_squared=physics.mach_number**2,
+                eos_type=physics.eos_type.value,
+                nu=nu,
+                alpha=alpha,
+                frame_rotation=options.frame_rotation,
+                velocity_ceiling=options.velocity_ceiling,
+                density_floor=options.density_floor,
+                wavespeed_estimator=WAVESPEED_ESTIMATORS[options.wavespeed_estimator],
+                record_face_fluxes=int(options.record_face_fluxes),
+                mask_is_enabled=int(zone_mask is not None),
+            )
+        else:
+            constants = dict()
+
+        # In single precision the patch arrays are allocated in float32 as
+        # well, which halves the memory and on most GPUs the time per step. The
+        # solution, face fluxes, and diagnostics are still returned in double
+        # precision, and the point masses and reservoir are kept in it.
+        lib = Library(
+            code,
+            mode=mode,
+            debug=False,
+            define_macros=dict(NCONS=nq),
+            constants=constants,
+            precision=options.precision,
+        )
+
+        # A custom source term sees the velocities of the kernels' frame, so
+        # the mesh must be in the rest frame.
+        if physics.custom_source is not None:
+            if not rest_frame:
+                raise ValueError("a custom source needs the mesh in the rest frame")
+            if nq > CUSTOM_MAX_FIELDS:
+                raise ValueError("custom sources take up to 16 fields")
+            if options.precision != "float64":
+                raise ValueError("a custom source needs float64 precision")
+
+            self.custom_source = CustomSource(
+                physics.custom_source, physics.custom_source_parameters, mode
+            )
+            logger.info(f"custom source term from {physics.custom_source}")
+        else:
+            self.custom_source = None
+
+        self.source_function = None
+        self.post_advance_hook = None
+
+        # The self-gravity is computed from the surface density of the whole
+        # mesh at once, so the patches must be on one device.
+        if physics.self_gravity_constant != 0.0:
+            if mode == "gpu" and num_devices(mode) > 1:
+                raise ValueError("self-gravity needs the patches on one device")
+            if physics.self_gravity_softening < 0.0:
+                raise ValueError("self_gravity_softening must be non-negative")
+
+            self.self_gravity = SelfGravity(
+                mesh,
+                physics.self_gravity_constant,
+                physics.self_gravity_softening,
+                xp,
+            )
+            logger.info(f"self-gravity with G={physics.self_gravity_constant}")
+        else:
+            self.self_gravity = None
 
         logger.info(f"initiate with time={time:0.4f}")
         logger.info(f"subdivide grid over {num_patches} patches")
         logger.info(f"mesh is {mesh}")
-        logger.info(f"boundary condition is outflow")
+        logger.info(f"boundary condition is {setup.boundary_condition}")
 
         self.mesh = mesh
         self.setup = setup
@@ -315,19 +1122,44 @@ class Solver(SolverBase):
         self.num_cons = nq
         self.xp = xp
         self.patches = []
+        self.num_iterations = 0
+        self.zone_mask = zone_mask
+        self.face_flux_start_time = time
+        self.fused_guard_fill = mode != "gpu" or num_devices(mode) == 1
+        self.characteristic = edges is None
+        self.edge_conditions = edges
         ni, nj = mesh.shape
 
         if solution is None:
-            primitive = initial_condition(setup, mesh, time)
+            primitive = initial_condition(setup, mesh, time, frame, nq)
+            primitive[..., 1] += options.boost_vx
+            primitive[..., 2] += options.boost_vy
         else:
             primitive = solution
 
+        if primitive.shape != (ni, nj, nq):
+            raise ValueError(
+                f"solution has shape {primitive.shape}, expected {(ni, nj, nq)}; "
+                "was it written with a different number of tracers?"
+            )
+
+        # Kept on the host, so `reset` can return to it without sampling the
+        # setup again.
+        self.initial_time = time
+        self.initial_solution = primitive
+
+        if options.boost_vx != 0.0 or options.boost_vy != 0.0:
+            logger.info(f"boost frame by ({options.boost_vx}, {options.boost_vy})")
+
+            if physics.num_particles > 0 or physics.buffer_is_enabled:
+                logger.warning("frame boost is not applied to point masses or buffer")
+
         if physics.buffer_is_enabled:
             # Here we sample the initial condition at the buffer onset radius
             # to determine the disk surface density at the radius where the
             # buffer begins to ramp up. This procedure makes sense as long as
             # the initial condition is axisymmetric.
-            buffer_prim = [0.0] * 3
+            buffer_prim = [0.0] * nq
             buffer_outer_radius = mesh.x1  # this assumes the mesh is a centered squared
             buffer_onset_radius = buffer_outer_radius - physics.buffer_onset_width
             setup.primitive(time, [buffer_onset_radius, 0.0], buffer_prim)
@@ -336,9 +1168,63 @@ class Solver(SolverBase):
             buffer_outer_radius = 0.0
             buffer_surface_density = 0.0
 
-        for n, (a, b) in enumerate(subdivide(ni, num_patches)):
+        # The inner buffer damps the solution toward its initial state, at a
+        # rate which ramps up inward from zero at its radius. The setup's
+        # buffer target replaces it in the zones where the two overlap.
+        if target_is_enabled:
+            rate = np.zeros(mesh.shape)
+            state = np.zeros(mesh.shape + (3,))
+
+            if inner_buffer:
+                if solution is None:
+                    initial = primitive
+                else:
+                    initial = initial_condition(setup, mesh, time, frame, nq)
+                    initial[..., 1] += options.boost_vx
+                    initial[..., 2] += options.boost_vy
+
+                x, y = mesh.cell_center_arrays()
+                r_inner = physics.inner_buffer_radius
+                m1, m2 = physics.resolved_point_masses(time, mesh)
+                central_mass = m1.mass + m2.mass
+
+                if central_mass > 0.0:
+                    omega = (central_mass / r_inner**3) ** 0.5
+                else:
+                    omega = 1.0
+
+                ramp = np.maximum(1.0 - (x * x + y * y) ** 0.5 / r_inner, 0.0)
+                rate += physics.buffer_driving_rate * omega * ramp
+                state[...] = initial[..., :3]
+                logger.info(f"inner buffer inside r={r_inner}")
+
+            if buffer_target is not None:
+                state = np.where((target_rate > 0.0)[..., None], target_state, state)
+                rate += target_rate
+                logger.info("setup drives the solution toward a buffer target")
+
+            target = (rate, state)
+        else:
+            target = None
+
+        self.target = target
+
+        for n, (a, b) in enumerate(mesh.tile(num_patches, ng)):
             prim = np.zeros([b - a + 2 * ng, nj + 2 * ng, nq])
             prim[ng:-ng, ng:-ng] = primitive[a:b]
+
+            if self.characteristic:
+                far_field = np.zeros(prim.shape)
+
+                for i in range(a - ng, b + ng):
+                    for j in range(-ng, nj + ng):
+                        if i < 0 or i >= ni or j < 0 or j >= nj:
+                            p = far_field[i - a + ng, j + ng]
+                            sample_setup(setup, mesh, time, frame, i, j, p)
+
+                far_field[..., 1] += options.boost_vx
+                far_field[..., 2] += options.boost_vy
+
             patch = Patch(
                 time,
                 prim,
@@ -348,38 +1234,191 @@ class Solver(SolverBase):
                 options,
                 buffer_outer_radius,
                 buffer_surface_density,
+                zone_mask[a:b] if zone_mask is not None else None,
+                (target[0][a:b], target[1][a:b]) if target is not None else None,
                 lib,
                 xp,
                 execution_context(mode, device_id=n % num_devices(mode)),
             )
+
+            if self.characteristic:
+                patch.set_far_field(far_field)
+
             self.patches.append(patch)
 
+        self.reservoir = reservoir
+        self.initial_reservoir = reservoir
+
+        if reservoir is not None:
+            self.reservoir_zones = list()
+            self.reservoir_weights = list()
+
+            for n, (a, b) in enumerate(mesh.tile(num_patches, ng)):
+                patch = self.patches[n]
+                weights = reservoir_face_weights(mesh, excised, (a, b), n == 0)
+
+                with patch.execution_context:
+                    self.reservoir_zones.append(xp.asarray(excised[a:b]))
+                    self.reservoir_weights.append([xp.asarray(w) for w in weights])
+
+            self.write_reservoir()
+
+        self.reinitialize()
+
     @property
     def solution(self):
-        return concat_on_host(
+        """
+        The primitive data in the frame of the kernels, or with live point
+        masses or a reservoir, a dict of that data (`primitive`), and the
+        `TwoBodyProblem` at the current time (`two_body`), or the `Reservoir`
+        (`reservoir`).
+        """
+        primitive = concat_on_host(
             [p.primitive for p in self.patches], (self.num_guard, self.num_guard)
+        ).astype("float64", copy=False)
+
+        if self.live_point_masses is None and self.reservoir is None:
+            return primitive
+
+        solution = dict(primitive=primitive)
+
+        if self.live_point_masses is not None:
+            solution["two_body"] = self.live_point_masses.two_body.at(self.time)
+
+        if self.reservoir is not None:
+            solution["reservoir"] = self.reservoir
+
+        return solution
+
+    def checkpoint_diagnostics(self):
+        diagnostics = dict()
+
+        if self.live_point_masses is not None:
+            diagnostics["point_masses"] = self.live_point_masses(self.time)
+
+        if self.reservoir is not None:
+            diagnostics["reservoir"] = self.reservoir._asdict()
+
+        return diagnostics
+
+    def conserved_array(self):
+        for patch in self.patches:
+            patch.recompute_conserved()
+        return concat_on_host(
+            [p.conserved0 for p in self.patches], (self.num_guard, self.num_guard)
+        ).astype("float64", copy=False)
+
+    def total_conserved(self):
+        """
+        Return the total mass and momentum of the active zones, in the frame
+        of the kernels, as a host array `(mass, x momentum, y momentum)`.
+        """
+        return sum(to_host(patch.total_conserved()) for patch in self.patches)
+
+    def conservation_budget(self):
+        """
+        Return the changes of the total mass and momentum by each kind of term
+        since the previous call, if the option `conservation_audit` is
+        enabled, or `None`.
+
+        The result is a dict with the totals at the start and end of the
+        interval (`initial` and `final`, from `total_conserved`), the change
+        by each of `AUDIT_TERMS` (`terms`), the change not accounted for by
+        them (`residual`), and the start and end times. Each of these is an
+        array `(mass, x momentum, y momentum)`. The boundary term is that of
+        the fluxes into the active zones, and the others are positive when
+        they add mass or momentum. The residual is round-off for a solution
+        that's conserved up to the terms.
+
+        The kernels evaluate the terms a second time for the audit, combined
+        over the Runge-Kutta stages like the solution, so the audit doesn't
+        change the solution, but it slows the update.
+        """
+        if not self._options.conservation_audit:
+            return None
+
+        budget = sum(to_host(patch.take_budget()) for patch in self.patches)
+        final = self.total_conserved()
+        initial = self.budget_start_total
+        result = dict(
+            initial=initial,
+            final=final,
+            terms=dict(zip(AUDIT_TERMS, budget)),
+            residual=final - initial - budget.sum(axis=0),
+            start_time=self.budget_start_time,
+            end_time=self.time,
         )
+        self.budget_start_total = final
+        self.budget_start_time = self.time
+        return result
+
+    @property
+    def primitive_fields(self):
+        """
+        The surface density, the velocity, and the `num_tracers` passive
+        scalars, `tracer0`, `tracer1`, etc. A tracer is a concentration per
+        unit mass, e.g. to tag gas from the inner and outer disk; it's
+        advected with the upwinded mass flux, and its initial value is written
+        by the setup's `primitive` method.
+        """
+        fields = [SURFACE_DENSITY, VELOCITY_X, VELOCITY_Y]
+        return fields + tracer_fields(self._physics.num_tracers)
 
     @property
     def primitive(self):
         """
-        This solver uses primitive data as the solution array.
+        This solver uses primitive data as the solution array. If the frame is
+        boosted, the solution is in the boosted frame, and this property
+        returns the rest-frame primitive data. It also returns the primitive
+        data with live point masses or a reservoir, where the solution is a
+        dict.
         """
+        if self._options.boost_vx != 0.0 or self._options.boost_vy != 0.0:
+            return self.primitive_array()
+        if self.frame.omega != 0.0:
+            return self.primitive_array()
+        if self.live_point_masses is not None or self.reservoir is not None:
+            return self.primitive_array()
         return None
 
+    @property
+    def reduction_names(self):
+        return [d.label for d in self._physics.diagnostics]
+
+    @property
+    def reduction_units(self):
+        return [d.units for d in self._physics.diagnostics]
+
     def reductions(self):
         """
         Generate runtime reductions on the solution data for time series.
         """
+        return [resolve(x) for x in self.deferred_reductions()]
+
+    def deferred_reductions(self):
+        """
+        Start the runtime reductions, without waiting for their results.
+
+        The diagnostics computed on the patches are returned as deferred
+        reductions, which are read when the time series is written out.
+        """
 
         diagnostics = self._physics.diagnostics
         udots1_acc = [p.point_mass_source_term(1, accretion=True) for p in self.patches]
         udots2_acc = [p.point_mass_source_term(2, accretion=True) for p in self.patches]
         udots1_grv = [p.point_mass_source_term(1, gravity=True) for p in self.patches]
         udots2_grv = [p.point_mass_source_term(2, gravity=True) for p in self.patches]
+        udots_probe = {
+            n: [p.probe_source_term(n) for p in self.patches]
+            for n in set(d.which_probe for d in diagnostics)
+            if n is not None
+        }
         da = self.mesh.dx * self.mesh.dy
         ng = self.num_guard
 
+        if any(d.quantity.startswith("roche") or d.source_region for d in diagnostics):
+            roche = RocheGeometry(*self._physics.point_masses(self.time))
+
         def get_field(patch, quantity, cut, mass, gravity=False, accretion=False):
             """
             Return one of the udot fields: for a particular patch, conserved
@@ -418,8 +1457,7 @@ class Solver(SolverBase):
 
             if quantity == "eccentricity_vector":
                 sigma = apply_radial_cut(patch.primitive[ng:-ng, ng:-ng, 0])
-                vx = apply_radial_cut(patch.primitive[ng:-ng, ng:-ng, 1])
-                vy = apply_radial_cut(patch.primitive[ng:-ng, ng:-ng, 2])
+                vx, vy = (apply_radial_cut(v) for v in self.rest_frame_velocity(patch))
                 GM = 1.0
                 v_dot_v = vx * vx + vy * vy
                 v_dot_r = vx * x + vy * y
@@ -429,14 +1467,32 @@ class Solver(SolverBase):
 
             if quantity == "angular_momentum":
                 sigma = apply_radial_cut(patch.primitive[ng:-ng, ng:-ng, 0])
-                vx = apply_radial_cut(patch.primitive[ng:-ng, ng:-ng, 1])
-                vy = apply_radial_cut(patch.primitive[ng:-ng, ng:-ng, 2])
+                vx, vy = self.rest_frame_velocity(patch)
                 return sigma * (x * vy - y * vx)
 
             if quantity == "mass":
                 sigma = apply_radial_cut(patch.primitive[ng:-ng, ng:-ng, 0])
                 return sigma
 
+            if quantity == "roche_mass":
+                sigma = apply_radial_cut(patch.primitive[ng:-ng, ng:-ng, 0])
+                return sigma * (roche.lobe_index(x, y) == mass)
+
+            if quantity == "roche_mdot":
+                # The mass flux through the plane normal to the binary axis at
+                # L1, from the primary's lobe to the secondary's, in the
+                # co-rotating frame. It's summed over a band one zone wide,
+                # which spans the smaller lobe's Eggleton radius either side
+                # of L1.
+                sigma = patch.primitive[ng:-ng, ng:-ng, 0]
+                vx, vy = self.rest_frame_velocity(patch)
+                vfx, vfy = roche.frame_velocity(x, y)
+                nx, ny = roche.axis
+                h = max(self.mesh.dx, self.mesh.dy)
+                s, t = roche.l1_plane_coordinates(x, y)
+                band = (abs(s) < 0.5 * h) * (abs(t) < roche.smaller_lobe_radius)
+                return sigma * ((vx - vfx) * nx + (vy - vfy) * ny) * band / h
+
             if quantity == "power":
                 fx = get_field(patch, 1, cut, mass, gravity, accretion)
                 fy = get_field(patch, 2, cut, mass, gravity, accretion)
@@ -461,7 +1517,9 @@ class Solver(SolverBase):
                 udots1 = udots1_grv
                 udots2 = udots2_grv
 
-            if mass == "both":
+            if type(mass) is tuple:
+                f = udots_probe[mass[1]][i][..., q]
+            elif mass == "both":
                 f = udots1[i][..., q] + udots2[i][..., q]
             elif mass == 1:
                 f = udots1[i][..., q]
@@ -470,18 +1528,45 @@ class Solver(SolverBase):
 
             return apply_radial_cut(f)
 
+        def source_region_mask(patch, d):
+            """
+            Return a mask which is one in the zones of the diagnostic's source
+            region, and zero elsewhere.
+            """
+            x, y = patch.cell_center_coordinate_arrays
+
+            if patch.buffer_outer_radius > 0.0:
+                buffer_radius = patch.buffer_outer_radius
+                buffer_radius -= self._physics.buffer_onset_width
+            else:
+                buffer_radius = 0.0
+
+            index = roche.source_region_index(x, y, d.cavity_radius, buffer_radius)
+            return index == SOURCE_REGIONS.index(d.source_region)
+
         def get_sum_fields(d):
             result = []
+            if d.which_probe is not None:
+                mass = ("probe", d.which_probe)
+            else:
+                mass = d.which_mass
+
             for p in self.patches:
                 with p.execution_context:
                     f = get_field(
                         p,
                         d.quantity,
                         d.radial_cut,
-                        d.which_mass,
+                        mass,
                         gravity=d.gravity,
                         accretion=d.accretion,
                     )
+                    if d.hill_cut is not None:
+                        x, y = p.cell_center_coordinate_arrays
+                        m1, m2 = self._physics.point_masses(self.time)
+                        f = f * hill_mask(x, y, m1, m2, d.hill_cut)
+                    if d.source_region is not None:
+                        f = f * source_region_mask(p, d)
                     result.append(f.sum())
             return result
 
@@ -494,18 +1579,245 @@ class Solver(SolverBase):
             else:
                 pass1.append(get_sum_fields(d))
 
+        contexts = [p.execution_context for p in self.patches]
+
         for item in pass1:
             if type(item) is not float:
-                pass2.append(sum(to_host(x) for x in item) * da)
+                launches = [lambda x=x: x for x in item]
+                pass2.append(DeferredReduction(sum, to_host, launches, contexts, da))
             else:
                 pass2.append(item)
 
         return pass2
 
+    def rest_frame_velocity(self, patch):
+        """
+        Return the interior velocity of a patch in the rest frame, i.e.
+        without a frame boost, and with the velocity of a rotating mesh. The
+        arrays are on the patch's device.
+        """
+        ng = self.num_guard
+        x, y = patch.cell_center_coordinate_arrays
+        wx, wy = self.frame.mesh_velocity(x, y)
+        vx = patch.primitive[ng:-ng, ng:-ng, 1] - self._options.boost_vx + wx
+        vy = patch.primitive[ng:-ng, ng:-ng, 2] - self._options.boost_vy + wy
+        return vx, vy
+
+    def rest_frame_patches(self):
+        """
+        Return the rest-frame data of each patch, on its device. See
+        `SolverBase.rest_frame_patches`.
+        """
+        ng = self.num_guard
+        result = list()
+
+        for patch in self.patches:
+            with patch.execution_context:
+                x, y = patch.cell_center_coordinate_arrays
+                vx, vy = self.rest_frame_velocity(patch)
+                sigma = patch.primitive[ng:-ng, ng:-ng, 0]
+            result.append(
+                dict(
+                    sigma=sigma,
+                    vx=vx,
+                    vy=vy,
+                    x=x,
+                    y=y,
+                    xp=patch.xp,
+                    execution_context=patch.execution_context,
+                )
+            )
+        return result
+
+    def frame_velocity(self, guard_zones=False):
+        """
+        Return the x and y velocity of the solution array's frame relative to
+        the rest frame, in each zone: the frame boost, less the velocity of
+        a rotating mesh. The arrays include the guard zones if requested.
+
+        A mesh rotating rigidly at the `frame_rotation` frequency is slower
+        near the corotation radius, e.g. around a circular binary with its
+        orbital frequency, so it suffers less from advection errors there. The
+        kernels add the Coriolis and centrifugal forces, and the target
+        velocities of the buffers are converted to the mesh frame. Outputs are
+        in the frame of the mesh, whose axes are at an angle `frame_rotation *
+        t` to the inertial ones, but their velocities are inertial.
+        """
+        import numpy as np
+
+        mesh = self.mesh
+        ng = self.num_guard if guard_zones else 0
+        x = mesh.x0 + (np.arange(-ng, mesh.ni + ng) + 0.5) * mesh.dx
+        y = mesh.y0 + (np.arange(-ng, mesh.nj + ng) + 0.5) * mesh.dy
+        wx, wy = self.frame.mesh_velocity(x[:, None], y[None, :])
+        return self._options.boost_vx - wx, self._options.boost_vy - wy
+
+    def primitive_array(self, guard_zones=False):
+        """
+        Return primitive data in the rest frame, i.e. with the frame boost
+        (the `boost_vx` and `boost_vy` options) subtracted from the velocity,
+        and the velocity of a rotating mesh added to it. The solution array
+        is not changed, so it can be used for restarts.
+        """
+        if guard_zones:
+            self.set_bc("primitive1")
+        primitive = concat_on_host(
+            [p.primitive for p in self.patches],
+            (self.num_guard, self.num_guard),
+            outer_guard=guard_zones,
+        ).astype("float64", copy=False)
+        dvx, dvy = self.frame_velocity(guard_zones)
+        primitive[..., 1] -= dvx
+        primitive[..., 2] -= dvy
+        return primitive
+
+    def deferred_primitive_array(self):
+        """
+        Return the rest-frame primitive data of `primitive_array`, as a
+        `DeferredCopy` downloaded on each patch's copy stream, if the
+        `copy_stream` option is set in gpu mode. Otherwise, wait for the data.
+        """
+        if any(p.copy_stream is None for p in self.patches):
+            return self.primitive_array()
+
+        ng = self.num_guard
+        dvx, dvy = self.frame_velocity()
+
+        def finish(arrays):
+            primitive = concat_on_host(arrays, (ng, ng)).astype("float64")
+            primitive[..., 1] -= dvx
+            primitive[..., 2] -= dvy
+            return primitive
+
+        return DeferredCopy(
+            [p.primitive for p in self.patches],
+            [p.execution_context for p in self.patches],
+            [p.copy_stream for p in self.patches],
+            finish,
+        )
+
+    def synchronize(self):
+        """
+        Wait for the kernels of every patch.
+
+        In gpu mode, `advance` returns once the step's kernels are queued,
+        unless the step reads a result, like the maximum wavespeed or the
+        invalid-zone flags, and the reductions of `deferred_reductions` are
+        waited for only when they're read.
+        """
+        for patch in self.patches:
+            patch.synchronize()
+
+    def is_done(self):
+        return all(patch.is_done() for patch in self.patches)
+
+    def sample(self, x, y):
+        """
+        Return the rest-frame primitive data interpolated bilinearly at
+        points, in the coordinates of the mesh. See `SolverBase.sample`.
+        """
+        import numpy as np
+
+        if not isinstance(self.mesh, PlanarCartesian2DMesh):
+            raise NotImplementedError(f"can't sample the mesh {self.mesh}")
+
+        stencil = self.mesh.interpolation_stencil(x, y)
+        self.set_bc("primitive1")
+        result = sample_on_host(self.patches, self.num_guard, stencil, self.num_cons)
+        wx, wy = self.frame.mesh_velocity(*np.broadcast_arrays(x, y))
+        result[:, 1] -= self._options.boost_vx - np.ravel(wx)
+        result[:, 2] -= self._options.boost_vy - np.ravel(wy)
+        return result
+
+    def set_primitive(self, primitive):
+        """
+        Replace the solution with rest-frame primitive data of shape
+        `(ni, nj, nq)`, and re-derive the guard zones, conserved data, and face
+        flux accumulators from it. Zones outside the zone mask are replaced
+        too, and stay fixed at the new values.
+        """
+        ni, nj = self.mesh.shape
+        nq = self.num_cons
+
+        if primitive.shape != (ni, nj, nq):
+            raise ValueError(
+                f"primitive data has shape {primitive.shape}, expected {(ni, nj, nq)}"
+            )
+
+        primitive = primitive.copy()
+        dvx, dvy = self.frame_velocity()
+        primitive[..., 1] += dvx
+        primitive[..., 2] += dvy
+        self.write_interior(primitive)
+        self.reinitialize()
+
+    def reset(self, primitive=None, time=None):
+        """
+        Return to new initial data, reusing the compiled kernels, patches, and
+        device buffers; see `SolverBase.reset`. Without `primitive`, the data
+        this solver was constructed with is restored, and without `time`, the
+        time it was constructed at.
+        """
+        if self.live_point_masses is not None:
+            raise ValueError("a solver with live point masses can't be reset")
+
+        time = self.initial_time if time is None else time
+        self.reservoir = self.initial_reservoir
+
+        for patch in self.patches:
+            patch.clock = Clock(time)
+
+        if primitive is None:
+            self.write_interior(self.initial_solution)
+            self.reinitialize()
+        else:
+            self.set_primitive(primitive)
+
+        if self.reservoir is not None:
+            self.write_reservoir()
+            self.reinitialize()
+
+    def write_interior(self, primitive):
+        """
+        Copy host primitive data, in the frame of the solution array, to the
+        interior zones of the patches, without refreshing anything else.
+        """
+        ng = self.num_guard
+        i0 = 0
+
+        for patch in self.patches:
+            i1 = i0 + patch.shape[0]
+            with patch.execution_context:
+                patch.primitive1[ng:-ng, ng:-ng] = self.xp.asarray(primitive[i0:i1])
+            i0 = i1
+
+    def reinitialize(self, discard_face_fluxes=True):
+        """
+        Fill the guard zones, and re-derive the conserved data on each patch.
+        The recorded face fluxes are discarded, and their averaging interval
+        restarted, unless `discard_face_fluxes` is false.
+        """
+        self.set_bc("primitive1")
+
+        for patch in self.patches:
+            patch.reinitialize(discard_face_fluxes)
+
+        if discard_face_fluxes:
+            self.face_flux_start_time = self.time
+
+        # The conservation audit restarts from the new solution.
+        if self._options.conservation_audit:
+            self.budget_start_total = self.total_conserved()
+            self.budget_start_time = self.time
+
     @property
     def time(self):
         return self.patches[0].time
 
+    @property
+    def nominal_time(self):
+        return self.patches[0].clock.nominal_time
+
     @property
     def options(self):
         return self._options._asdict()
@@ -516,12 +1828,191 @@ class Solver(SolverBase):
 
     @property
     def recommended_cfl(self):
-        return 0.3
+        return self._options.cfl_number or 0.3
 
     @property
     def maximum_cfl(self):
         return 0.4
 
+    def host_sound_speed_squared(self, x, y):
+        """
+        Return the squared sound speed at the given host array coordinates,
+        as the kernels evaluate it for the equation of state.
+        """
+        import numpy as np
+
+        physics = self._physics
+
+        if physics.eos_type == EquationOfState.GLOBALLY_ISOTHERMAL:
+            return np.full(np.broadcast(x, y).shape, physics.sound_speed**2)
+
+        if physics.eos_type == EquationOfState.PROFILE_ISOTHERMAL:
+            dr, table = sound_speed_table(physics, self.mesh)
+            return interpolate_sound_speed_table(dr, table, x, y, np)
+
+        phi = np.zeros(np.broadcast(x, y).shape)
+
+        for m in physics.resolved_point_masses(self.time, self.mesh):
+            if m.mass > 0.0:
+                r2 = (x - m.position_x) ** 2 + (y - m.position_y) ** 2
+                phi += m.potential(r2, np)
+
+        return -phi / physics.mach_number**2
+
+    def sink_resolution(self):
+        """
+        Return the resolution statistics of each point mass with a sink, to
+        catch sinks which become under-resolved as the masses move.
+
+        Each item is a dictionary with the index (`which`, 1 or 2) and the
+        `location` of the mass, the number of zones across the sink radius
+        (`zones_per_radius`), and the number of zone centers inside it
+        (`zones_inside`). Over the sink region, within four sink radii, it
+        also has the largest Mach number of the gas relative to the mass
+        (`mach_number`), and the smallest time step limit and its term
+        (`min_dt` and `term`, see `timestep_limiters`). A sink region with no
+        active zone centers is represented by the zone nearest the mass.
+        """
+        import numpy as np
+
+        mesh = self.mesh
+        limiters = self.timestep_limiters()
+        primitive = concat_on_host(
+            [p.primitive for p in self.patches], (self.num_guard, self.num_guard)
+        )
+        x, y = mesh.cell_center_arrays()
+        cs2 = self.host_sound_speed_squared(x, y)
+        masses = self._physics.resolved_point_masses(self.time, mesh)
+        result = list()
+
+        for which, m in enumerate(masses, 1):
+            if m.sink_rate <= 0.0 or m.sink_radius <= 0.0:
+                continue
+
+            dr = ((x - m.position_x) ** 2 + (y - m.position_y) ** 2) ** 0.5
+            region = dr < 4.0 * m.sink_radius
+
+            if self.zone_mask is not None:
+                region &= self.zone_mask
+
+            if not region.any():
+                region = dr == dr.min()
+
+            vx = primitive[..., 1] - m.velocity_x
+            vy = primitive[..., 2] - m.velocity_y
+            mach = ((vx**2 + vy**2) / cs2) ** 0.5
+            dt = np.where(region, limiters["dt"], np.inf)
+            i, j = np.unravel_index(np.argmin(dt), dt.shape)
+            zone_size = mesh.zone_size(m.position_x, m.position_y)
+
+            result.append(
+                dict(
+                    which=which,
+                    location=(float(m.position_x), float(m.position_y)),
+                    zones_per_radius=m.sink_radius / zone_size,
+                    zones_inside=int((dr < m.sink_radius).sum()),
+                    mach_number=float(mach[region].max()),
+                    min_dt=float(dt[i, j]),
+                    term=limiters["names"][limiters["limiter"][i, j]],
+                )
+            )
+
+        return result
+
+    def timestep_limiters(self):
+        """
+        Return a map of the time step limit in each zone, and of the term
+        which sets it.
+
+        The terms are the advective limits `dx / (|vx| + cs)` and `dy / (|vy|
+        + cs)`, the viscous limit `min(dx, dy)^2 / (4 nu)`, and the source
+        term limit, the inverse of the combined sink and buffer rates, of the
+        terms with the explicit update (see `sink_update` and `buffer_update`). The
+        driver's time step is the CFL number times the smallest advective
+        limit; the others are not enforced, so a zone where they're smaller
+        points to a likely cause of a time step collapsing, or of a run going
+        unstable. The map is computed on the host, from the solution in the
+        frame the kernels see. The result is a dictionary with the `dt` and
+        `limiter` arrays, of shape `(ni, nj)`, the term `names`, and the
+        `zone`, `location`, `term`, and `min_dt` of the most restrictive zone.
+        Zones outside the mask, if there is one, are given an infinite `dt`.
+        """
+        import numpy as np
+
+        physics = self._physics
+        mesh = self.mesh
+        primitive = concat_on_host(
+            [p.primitive for p in self.patches], (self.num_guard, self.num_guard)
+        )
+        x, y = mesh.cell_center_arrays()
+        m1, m2 = physics.resolved_point_masses(self.time, mesh)
+        masses = [m for m in (m1, m2) if m.mass > 0.0]
+        cs2 = self.host_sound_speed_squared(x, y)
+        cs = cs2**0.5
+        rate = np.zeros(mesh.shape)
+        explicit_sinks = self._options.sink_update == "explicit"
+        explicit_buffers = self._options.buffer_update == "explicit"
+
+        for m in masses:
+            if m.sink_rate > 0.0 and explicit_sinks:
+                dr = ((x - m.position_x) ** 2 + (y - m.position_y) ** 2) ** 0.5
+                r_sink = m.sink_radius
+                sink_rate = m.sink_rate * np.exp(-((dr / r_sink) ** 4))
+                rate += (dr < 4.0 * r_sink) * sink_rate
+
+        if physics.buffer_is_enabled and explicit_buffers:
+            rc = (x**2 + y**2) ** 0.5
+            outer = self.patches[0].buffer_outer_radius
+            onset = outer - physics.buffer_onset_width
+            omega_outer = ((m1.mass + m2.mass) * onset**-3.0) ** 0.5
+            ramp = (rc - onset) / (outer - onset)
+            rate += (rc > onset) * physics.buffer_driving_rate * omega_outer * ramp
+
+        if self.target is not None and explicit_buffers:
+            rate += self.target[0]
+
+        nu, alpha = viscosity_coefficients(physics)
+        nu = np.full(mesh.shape, nu)
+
+        if alpha > 0.0 and masses:
+            omega2 = 0.0
+            for m in masses:
+                r2 = (x - m.position_x) ** 2 + (y - m.position_y) ** 2
+                omega2 = omega2 + m.acceleration(r2, np)
+            nu = alpha * cs2 / omega2**0.5
+
+        min_spacing2 = min(mesh.dx, mesh.dy) ** 2
+
+        with np.errstate(divide="ignore"):
+            dt_visc = np.where(nu > 0.0, min_spacing2 / (4.0 * nu), np.inf)
+            limits = np.stack(
+                [
+                    mesh.dx / (np.abs(primitive[..., 1]) + cs),
+                    mesh.dy / (np.abs(primitive[..., 2]) + cs),
+                    dt_visc,
+                    np.where(rate > 0.0, 1.0 / rate, np.inf),
+                ]
+            )
+
+        names = ["advective_x", "advective_y", "viscous", "source"]
+        limiter = np.argmin(limits, axis=0)
+        dt = np.min(limits, axis=0)
+
+        if self.zone_mask is not None:
+            dt[~self.zone_mask] = np.inf
+
+        i, j = np.unravel_index(np.argmin(dt), dt.shape)
+
+        return dict(
+            dt=dt,
+            limiter=limiter,
+            names=names,
+            zone=(int(i), int(j)),
+            location=(float(x[i, j]), float(y[i, j])),
+            term=names[limiter[i, j]],
+            min_dt=float(dt[i, j]),
+        )
+
     def maximum_wavespeed(self):
         """
         Return the global maximum wavespeed over the whole domain.
@@ -534,16 +2025,228 @@ class Solver(SolverBase):
         )
 
     def advance(self, dt):
+        """
+        Take one iteration of the time step `dt`.
+
+        The `density_floor` and `velocity_ceiling` are applied in the kernels,
+        but so that the floor doesn't hide a failing solution, the kernels
+        first flag each zone whose updated state has a negative density or
+        non-finite data. The flags are inspected every
+        `invalid_state_cadence` iterations, and a `SolverError` is raised
+        with the position of a flagged zone. With `max_step_retries` above
+        zero, the flags are inspected after every iteration instead, and a
+        failed iteration is retried from its start as two of half the time
+        step, up to that many times.
+        """
+        self.num_iterations += 1
+        retries = self._options.max_step_retries
+        cadence = self._options.invalid_state_cadence
+
+        if retries:
+            advance_with_retries(self, dt, retries)
+        else:
+            self.take_step(dt)
+
+            if cadence and self.num_iterations % cadence == 0:
+                check_invalid_zones(self.patches, self.mesh, self.time)
+
+        if self.post_advance_hook is not None:
+            self.run_post_advance_hook()
+
+    def set_source_function(self, function):
+        """
+        Add a source term given by a Python function, or remove it if
+        `function` is `None`.
+
+        The function is called as `function(time, x, y, primitive)` for each
+        patch after each iteration, where `x` and `y` are arrays of the zone
+        center coordinates, and `primitive` of the patch's primitive data on
+        its interior zones, with the fields on the last axis. It returns the
+        rates of change of the conserved quantities in each zone, an array of
+        the shape of `primitive`, which are applied as a split step, as for a
+        `custom_source` (see `sailfish.kernel.custom`). Masked zones are
+        unchanged. The arrays are numpy arrays, so this is for cpu modes.
+        """
+        if function is not None and not self.patches[0].lib.cpu_mode:
+            raise ValueError("a Python source function can't run on GPUs")
+
+        self.source_function = function
+
+    def set_post_advance_hook(self, hook):
+        """
+        Call `hook(patch, time)` with each patch at the end of every
+        `advance`, or stop if `hook` is `None`.
+
+        The hook can read the patch arrays, e.g. `patch.primitive1`, which
+        has the guard zones, and `patch.index_range`, to monitor a run, and can
+        modify the interior zones of `patch.primitive1` in place; the solver
+        re-derives the rest at the start of the next iteration. The change of
+        the totals by the hook is counted with the split steps in the
+        conservation audit. The arrays are numpy arrays, so this is for cpu
+        modes.
+        """
+        if hook is not None and not self.patches[0].lib.cpu_mode:
+            raise ValueError("a post-advance hook can't run on GPUs")
+
+        self.post_advance_hook = hook
+
+    def run_post_advance_hook(self):
+        """
+        Call the post-advance hook with each patch, and add the change of the
+        totals it makes to the split steps of the conservation audit.
+        """
+        audit = self._options.conservation_audit
+
+        for patch in self.patches:
+            if audit:
+                total = patch.total_conserved()
+
+            self.post_advance_hook(patch, self.time)
+
+            if audit:
+                with patch.execution_context:
+                    patch.budget_total[-1] += patch.total_conserved() - total
+
+    @property
+    def runge_kutta_parameters(self):
+        """
+        The Runge-Kutta parameter of each stage of an iteration.
+        """
+        bs_rk1 = [0 / 1]
+        bs_rk2 = [0 / 1, 1 / 2]
+        bs_rk3 = [0 / 1, 3 / 4, 1 / 3]
+        return (bs_rk1, bs_rk2, bs_rk3)[self._options.rk_order - 1]
+
+    def take_step(self, dt):
         self.new_iteration()
-        if self._options.rk_order == 1:
-            self.advance_rk(0.0, dt)
-        elif self._options.rk_order == 2:
-            self.advance_rk(0.0, dt)
-            self.advance_rk(0.5, dt)
-        elif self._options.rk_order == 3:
-            self.advance_rk(0.0, dt)
-            self.advance_rk(0.75, dt)
-            self.advance_rk(1.0 / 3.0, dt)
+
+        for b in self.runge_kutta_parameters:
+            self.advance_rk(b, dt)
+
+        split = self.custom_source is not None or self.self_gravity is not None
+        split = split or self.source_function is not None
+        audit = split and self._options.conservation_audit
+
+        if audit:
+            before = [patch.total_conserved() for patch in self.patches]
+
+        if self.custom_source is not None:
+            for patch in self.patches:
+                patch.custom_source_step(self.custom_source, dt)
+
+        if self.source_function is not None:
+            for patch in self.patches:
+                patch.source_function_step(self.source_function, dt)
+
+        if self.self_gravity is not None:
+            self.self_gravity_step(dt)
+
+        if audit:
+            for patch, total in zip(self.patches, before):
+                with patch.execution_context:
+                    patch.budget_total[-1] += patch.total_conserved() - total
+
+        if self.reservoir is not None:
+            self.advance_reservoir(dt)
+
+    def self_gravity_step(self, dt):
+        """
+        Accelerate the gas by its self-gravity over a time `dt`, as a split
+        step, with the field of the surface density at the end of the step.
+        This is enabled by the physics parameter `self_gravity_constant`; see
+        `sailfish.physics.self_gravity`.
+        """
+        ng = self.num_guard
+        xp = self.xp
+
+        with self.patches[0].execution_context:
+            sigma = [p.primitive1[ng:-ng, ng:-ng, 0] for p in self.patches]
+            gx, gy = self.self_gravity.acceleration(xp.concatenate(sigma))
+
+        for patch in self.patches:
+            i0, i1 = patch.index_range
+
+            with patch.execution_context:
+                prim = patch.primitive1[ng:-ng, ng:-ng]
+                prim[..., 1] += gx[i0:i1] * dt
+                prim[..., 2] += gy[i0:i1] * dt
+
+    def save_step(self):
+        """
+        Return copies of the patches' primitive data, clocks, and conservation
+        audit totals, and the reservoir, from which a failed step is retried.
+        """
+        audit = self._options.conservation_audit
+        patches = list()
+
+        for patch in self.patches:
+            if audit:
+                patch.fold_budget()
+
+            with patch.execution_context:
+                budget = patch.budget_total.copy() if audit else None
+                patches.append((patch.primitive1.copy(), copy(patch.clock), budget))
+
+        return patches, self.reservoir
+
+    def restore_step(self, saved):
+        """
+        Return the solution to a state from `save_step`, and discard the face
+        fluxes, audit terms, and invalid zone flags of the steps taken since.
+        """
+        patches, self.reservoir = saved
+
+        for patch, (primitive, clock, budget) in zip(self.patches, patches):
+            with patch.execution_context:
+                patch.primitive1[...] = primitive
+                patch.clock = copy(clock)
+                patch.invalid_zones[...] = 0.0
+                patch.face_flux_x[...] = 0.0
+                patch.face_flux_y[...] = 0.0
+
+                if budget is not None:
+                    patch.budget[...] = 0.0
+                    patch.budget_total[...] = budget
+
+    def advance_reservoir(self, dt):
+        """
+        Change the reservoir by the mass and angular momentum which flowed
+        into the excised zones over the latest iteration, from the recorded
+        face fluxes, and write its new state to the excised zones.
+        """
+        inflow = [0.0, 0.0]
+
+        for patch, weights in zip(self.patches, self.reservoir_weights):
+            with patch.execution_context:
+                fluxes = (patch.face_flux_x, patch.face_flux_y)
+
+                for f, (wm, wl) in zip(fluxes, weights):
+                    inflow[0] += float((wm * f[..., :3]).sum())
+                    inflow[1] += float((wl * f[..., :3]).sum())
+
+        self.reservoir = self.reservoir.advance(dt, *inflow)
+        self.write_reservoir()
+
+    def write_reservoir(self):
+        """
+        Write the reservoir's mean surface density, and its rotation about the
+        origin, to the excised zones, which its neighbors see as a boundary.
+        """
+        m1, m2 = self._physics.point_masses(self.time)
+        sigma = max(self.reservoir.surface_density, self._options.density_floor)
+        v = self.reservoir.edge_velocity(m1.mass + m2.mass)
+        ng = self.num_guard
+
+        for patch, excised in zip(self.patches, self.reservoir_zones):
+            xp = patch.xp
+
+            with patch.execution_context:
+                x, y = patch.cell_center_coordinate_arrays
+                r = xp.maximum((x * x + y * y) ** 0.5, 1e-12)
+                prim = patch.primitive1[ng:-ng, ng:-ng]
+                prim[..., 0] = xp.where(excised, sigma, prim[..., 0])
+                prim[..., 1] = xp.where(excised, -v * y / r, prim[..., 1])
+                prim[..., 2] = xp.where(excised, v * x / r, prim[..., 2])
 
     def advance_rk(self, rk_param, dt):
         self.set_bc("primitive1")
@@ -564,11 +2267,57 @@ class Solver(SolverBase):
     def set_bc_patch(self, pl, pc, pr, patch_index):
         ni, nj = self.mesh.shape
         ng = self.num_guard
+        patch = self.patches[patch_index]
+        first = patch_index == 0
+        last = patch_index == len(self.patches) - 1
+
+        if self.fused_guard_fill:
+            # All guard zones are filled by one kernel, which reads the
+            # neighboring patches directly. This requires them to be on the
+            # same device.
+            pi, pj = patch.shape
+            num_guard_zones = (pi + 2 * ng) * (pj + 2 * ng) - pi * pj
+
+            with patch.execution_context:
+                patch.lib.cbdiso_2d_fill_guard_zones[num_guard_zones](
+                    pl,
+                    pc,
+                    pr,
+                    pi,
+                    pj,
+                    pl.shape[0] - 2 * ng,
+                    pr.shape[0] - 2 * ng,
+                    int(first),
+                    int(last),
+                )
+        else:
+            self.fill_outflow_guard_zones(pl, pc, pr, patch_index)
 
-        with self.patches[patch_index].execution_context:
+        # The outflow values on the domain edges are then replaced.
+        if self.characteristic:
+            patch.fill_characteristic_guard_zones(pc, first, last)
+        else:
+            (bcxl, bcxr), (bcyl, bcyr) = self.edge_conditions
+            edges = [(0, 0, bcxl)] * first + [(0, 1, bcxr)] * last
+            edges += [(1, 0, bcyl), (1, 1, bcyr)]
+
+            with patch.execution_context:
+                for axis, side, mode in edges:
+                    if mode != BoundaryCondition.OUTFLOW:
+                        fill_guard_zones(pc, axis, side, mode, ng)
+
+    def fill_outflow_guard_zones(self, pl, pc, pr, patch_index):
+        """
+        Fill the guard zones of a patch from its neighbors, which may be on
+        other devices, and with outflow values on the domain edges.
+        """
+        ng = self.num_guard
+        patch = self.patches[patch_index]
+
+        with patch.execution_context:
             # 1. write to the guard zones of pc, the internal BC
-            pc[:+ng] = pl[-2 * ng : -ng]
-            pc[-ng:] = pr[+ng : +2 * ng]
+            copy_between_devices(pc[:+ng], pl[-2 * ng : -ng])
+            copy_between_devices(pc[-ng:], pr[+ng : +2 * ng])
 
             # 2. Set outflow BC on the left/right patch edges
             if patch_index == 0:
@@ -586,5 +2335,117 @@ class Solver(SolverBase):
                 pc[:, i] = pc[:, -ng - 1]
 
     def new_iteration(self):
+        if self.live_point_masses is not None:
+            self.live_point_masses.new_iteration(self.time)
+
         for patch in self.patches:
             patch.new_iteration()
+
+    def secular_step(self, duration):
+        """
+        Fast-forward the disk by `duration` with the 1D viscous disk solver.
+
+        The surface density is azimuthally averaged in `secular_zones`
+        logarithmic bins between `r = 0.5` and the edge of the domain, and the
+        average profile is evolved by `duration` with
+        :py:class:`sailfish.physics.viscous_disk.ViscousDisk1D`. The binary
+        torque in the 1D evolution has coefficient `secular_torque`. The 2D
+        surface density is then rescaled, in each annulus, by the ratio of the
+        new and old average profiles, and the radial velocity of the 1D model
+        is changed by the same amount in 2D. The non-axisymmetric structure
+        is kept, and has to re-adjust to the new profile; zones outside the
+        1D domain are unchanged. The solution time advances by `duration`,
+        and the point masses continue at their positions at that time.
+        """
+        import numpy as np
+        from sailfish.physics.viscous_disk import ViscousDisk1D, azimuthal_average
+
+        physics = self._physics
+        options = self._options
+
+        if physics.viscosity_model != ViscosityModel.CONSTANT_NU:
+            raise ValueError("secular steps need a viscous disk")
+
+        if self.frame.omega != 0.0:
+            raise ValueError("secular steps are not supported on a rotating mesh")
+
+        m1, m2 = physics.point_masses(self.time)
+        mass = m1.mass + m2.mass
+        disk = ViscousDisk1D(
+            viscosity=lambda r: physics.viscosity_coefficient,
+            inner_radius=0.5,
+            outer_radius=self.mesh.x1,
+            num_zones=options.secular_zones,
+            central_mass=mass,
+            mass_ratio=min(m1.mass, m2.mass) / max(m1.mass, m2.mass, 1e-300),
+            torque_coefficient=options.secular_torque,
+            aspect_ratio=1.0 / physics.mach_number,
+        )
+
+        x, y = self.mesh.cell_center_arrays()
+        r = np.maximum((x * x + y * y) ** 0.5, 1e-12)
+        rc, faces = disk.radii()
+
+        primitive = self.primitive_array()
+        sigma0 = azimuthal_average(r, primitive[..., 0], faces)
+        sigma1 = disk.evolve(sigma0, duration)
+        dvr = disk.radial_velocity(sigma1) - disk.radial_velocity(sigma0)
+        inside = (r >= faces[0]) & (r < faces[-1])
+        ratio = np.where(inside, np.interp(r, rc, sigma1 / sigma0), 1.0)
+        dvr = np.where(inside, np.interp(r, rc, dvr), 0.0)
+
+        primitive[..., 0] *= ratio
+        primitive[..., 1] += dvr * x / r + options.boost_vx
+        primitive[..., 2] += dvr * y / r + options.boost_vy
+
+        self.write_interior(primitive)
+
+        for patch in self.patches:
+            patch.clock.advance(duration)
+
+        # The fluxes recorded before the secular step are kept, and the time
+        # it skipped is left out of their averaging interval.
+        self.reinitialize(discard_face_fluxes=False)
+        self.face_flux_start_time += duration
+        dm = pi * np.sum((sigma1 - sigma0) * np.diff(faces**2))
+        logger.info(f"secular step to t={self.time:.4f}, disk mass change {dm:.4e}")
+
+    def face_fluxes(self):
+        """
+        Return the fluxes of mass, momentum, and tracers through the mesh faces,
+        averaged over time since the previous call, if the solver option
+        `record_face_fluxes` is enabled.
+
+        The result is a dictionary with keys `x` and `y`, which are arrays of
+        shape `(ni + 1, nj, nq)` and `(ni, nj + 1, nq)` respectively, and the
+        start and end times of the averaging interval. The fluxes are those of
+        the Runge-Kutta update, so the change of the conserved quantities in a
+        zone over the interval is exactly the flux difference across it (times
+        the interval and divided by the zone size), plus the source terms.
+
+        Each call takes the fluxes recorded so far, and starts a new interval,
+        so e.g. each checkpoint has the fluxes since the one before it.
+        """
+        import numpy as np
+
+        if not self._options.record_face_fluxes:
+            return None
+
+        fluxes = [patch.take_face_fluxes() for patch in self.patches]
+        duration = self.time - self.face_flux_start_time
+        scale = 1.0 / duration if duration > 0.0 else 0.0
+
+        # Neighboring patches both record the face between them; keep the
+        # copy from the patch on the left.
+        fx = [to_host(f[0]) for f in fluxes]
+        fx = np.concatenate([fx[0]] + [f[1:] for f in fx[1:]])
+        fy = np.concatenate([to_host(f[1]) for f in fluxes])
+
+        result = dict(
+            x=fx * scale,
+            y=fy * scale,
+            start_time=self.face_flux_start_time,
+            end_time=self.time,
+        )
+        self.face_flux_start_time = self.time
+        return result
@@ -516,8 +516,8 @@ PUBLIC void cbdisodg_2d_slope_limit(
    #define CK 0.1 // Troubled Cell Indicator G. Fu & C.-W. Shu (JCP, 347, 305 (2017))
 
    int ng = 1; // number of guard zones
-   int si = NCONS * ORDER * ORDER * (nj + 2 * ng);
-   int sj = NCONS * ORDER * ORDER;
+   index_t si = NCONS * ORDER * ORDER * (nj + 2 * ng);
+   index_t sj = NCONS * ORDER * ORDER;
    double dvol = 4.0; // volume in xsi coordinates [-1,1] x [-1,1]
    // double dx = (patch_xr - patch_xl) / ni;
    // double dy = (patch_yr - patch_yl) / nj;
@@ -526,11 +526,11 @@ PUBLIC void cbdisodg_2d_slope_limit(
    {
        // Get the indexes and pointers to neighbor zones
        // --------------------------------------------------------------------
-       int ncc = (i     + ng) * si + (j     + ng) * sj;
-       int nli = (i - 1 + ng) * si + (j     + ng) * sj;
-       int nri = (i + 1 + ng) * si + (j     + ng) * sj;
-       int nlj = (i     + ng) * si + (j - 1 + ng) * sj;
-       int nrj = (i     + ng) * si + (j + 1 + ng) * sj;
+       index_t ncc = (i     + ng) * si + (j     + ng) * sj;
+       index_t nli = (i - 1 + ng) * si + (j     + ng) * sj;
+       index_t nri = (i + 1 + ng) * si + (j     + ng) * sj;
+       index_t nlj = (i     + ng) * si + (j - 1 + ng) * sj;
+       index_t nrj = (i     + ng) * si + (j + 1 + ng) * sj;
 
        double *ucc = &weights1[ncc];
        double *uli = &weights1[nli];
@@ -653,8 +653,8 @@ PUBLIC void cbdisodg_2d_advance_rk(
     double cell_volume = dx * dy;
 
     int ng = 1; // number of guard zones
-    int si = NCONS * ORDER * ORDER * (nj + 2 * ng);
-    int sj = NCONS * ORDER * ORDER;
+    index_t si = NCONS * ORDER * ORDER * (nj + 2 * ng);
+    index_t sj = NCONS * ORDER * ORDER;
 
     struct KeplerianBuffer buffer = {
         buffer_surface_density,
@@ -730,11 +730,11 @@ PUBLIC void cbdisodg_2d_advance_rk(
 
         // Get the indexes and pointers to neighbor zones
         // --------------------------------------------------------------------
-        int ncc = (i     + ng) * si + (j     + ng) * sj;
-        int nli = (i - 1 + ng) * si + (j     + ng) * sj;
-        int nri = (i + 1 + ng) * si + (j     + ng) * sj;
-        int nlj = (i     + ng) * si + (j - 1 + ng) * sj;
-        int nrj = (i     + ng) * si + (j + 1 + ng) * sj;
+        index_t ncc = (i     + ng) * si + (j     + ng) * sj;
+        index_t nli = (i - 1 + ng) * si + (j     + ng) * sj;
+        index_t nri = (i + 1 + ng) * si + (j     + ng) * sj;
+        index_t nlj = (i     + ng) * si + (j - 1 + ng) * sj;
+        index_t nrj = (i     + ng) * si + (j + 1 + ng) * sj;
 
         double *ucc = &weights1[ncc];
         double *uli = &weights1[nli];
@@ -1142,8 +1142,8 @@ PUBLIC void cbdisodg_2d_wavespeed(
     struct PointMassList mass_list = {{m1, m2}};
 
     int ng = 1; // number of guard zones
-    int si = NCONS * ORDER * ORDER * (nj + 2 * ng);
-    int sj = NCONS * ORDER * ORDER;
+    index_t si = NCONS * ORDER * ORDER * (nj + 2 * ng);
+    index_t sj = NCONS * ORDER * ORDER;
     int ti = nj + 2 * ng;
     int tj = 1;
     double dx = (patch_xr - patch_xl) / ni;
@@ -1151,8 +1151,8 @@ PUBLIC void cbdisodg_2d_wavespeed(
 
     FOR_EACH_2D(ni, nj)
     {
-        int nu = (i + ng) * si + (j + ng) * sj;
-        int na = (i + ng) * ti + (j + ng) * tj;
+        index_t nu = (i + ng) * si + (j + ng) * sj;
+        index_t na = (i + ng) * ti + (j + ng) * tj;
         double x = patch_xl + (i + 0.5) * dx;
         double y = patch_yl + (j + 0.5) * dy;
 
@@ -4,12 +4,21 @@ Isothermal solver for the binary accretion problem in 2D planar coordinates.
 
 from logging import getLogger
 from typing import NamedTuple
+from sailfish.clock import Clock
+from sailfish.fields import SURFACE_DENSITY, VELOCITY_X, VELOCITY_Y
 from sailfish.kernel.library import Library
-from sailfish.kernel.system import get_array_module, execution_context, num_devices
+from sailfish.kernel.system import copy_between_devices, get_array_module
+from sailfish.kernel.system import execution_context, num_devices
 from sailfish.mesh import PlanarCartesian2DMesh
-from sailfish.physics.circumbinary import Physics, EquationOfState, ViscosityModel
+from sailfish.physics.circumbinary import (
+    Physics,
+    CoolingModel,
+    EquationOfState,
+    SofteningModel,
+    ViscosityModel,
+)
 from sailfish.solver_base import SolverBase
-from sailfish.subdivide import subdivide, concat_on_host, lazy_reduce
+from sailfish.subdivide import concat_on_host, lazy_reduce
 
 
 logger = getLogger(__name__)
@@ -106,7 +115,7 @@ class Patch:
         self.mesh = mesh
         self.xp = xp
         self.execution_context = execution_context
-        self.time = self.time0 = time
+        self.clock = Clock(time)
         self.shape = (i1 - i0, nj)  # not including guard zones
         self.physics = physics
         self.options = options
@@ -118,8 +127,8 @@ class Patch:
         with self.execution_context:
             self.wavespeeds = xp.zeros(weights.shape[:2])
             self.weights0 = xp.zeros(weights.shape)  # weights at the timestep start
-            self.weights1 = xp.array(weights)  # weights to be read from
-            self.weights2 = xp.array(weights)  # weights to be written to
+            self.weights1 = lib.array(weights)  # weights to be read from
+            self.weights2 = lib.array(weights)  # weights to be written to
 
     def point_mass_source_term(self, which_mass):
         """
@@ -131,7 +140,7 @@ class Patch:
 
         ng = GUARD  # number of guard cells
         ni, nj = self.shape
-        m1, m2 = self.physics.point_masses(self.time)
+        m1, m2 = self.physics.resolved_point_masses(self.time, self.mesh)
 
         with self.execution_context:
             cons_rate = self.xp.zeros([ni + 2 * ng, nj + 2 * ng, NCONS])
@@ -170,7 +179,7 @@ class Patch:
         """
         Returns the maximum wavespeed over a given patch.
         """
-        m1, m2 = self.physics.point_masses(self.time)
+        m1, m2 = self.physics.resolved_point_masses(self.time, self.mesh)
         with self.execution_context:
             self.lib.cbdisodg_2d_wavespeed[self.shape](
                 self.xl,
@@ -212,7 +221,7 @@ class Patch:
         """
         Limit slopes using minmodTVB
         """
-        m1, m2 = self.physics.point_masses(self.time)
+        m1, m2 = self.physics.resolved_point_masses(self.time, self.mesh)
 
         with self.execution_context:
             self.lib.cbdisodg_2d_slope_limit[self.shape](
@@ -236,7 +245,7 @@ class Patch:
         This function calls the C-module function responsible for performing time evolution using a
         RK algorithm to update the parameters of the setup.
         """
-        m1, m2 = self.physics.point_masses(self.time)
+        m1, m2 = self.physics.resolved_point_masses(self.time, self.mesh)
         buffer_central_mass = m1.mass + m2.mass
         buffer_surface_density = self.buffer_surface_density
 
@@ -281,11 +290,15 @@ class Patch:
                 dt,
                 self.options.velocity_ceiling,
             )
-        self.time = self.time0 * rk_param + (self.time + dt) * (1.0 - rk_param)
+        self.clock.advance_stage(rk_param, dt)
         self.weights1, self.weights2 = self.weights2, self.weights1
 
+    @property
+    def time(self):
+        return self.clock.time
+
     def new_iteration(self):
-        self.time0 = self.time
+        self.clock.new_iteration()
         self.copy_weights1_to_weights0()
 
     @property
@@ -341,9 +354,39 @@ class Solver(SolverBase):
         if physics.cooling_coefficient != 0.0:
             raise ValueError("solver does not support thermal cooling")
 
+        if physics.cooling_model != CoolingModel.NONE:
+            raise ValueError("solver does not support thermal cooling")
+
+        if physics.reservoir is not None:
+            raise ValueError("solver does not support an inner reservoir")
+
+        if physics.custom_source is not None:
+            raise ValueError("solver does not support custom source terms")
+
+        if physics.inner_buffer_radius != 0.0:
+            raise ValueError("solver does not support an inner buffer")
+
+        if physics.self_gravity_constant != 0.0:
+            raise ValueError("solver does not support self-gravity")
+
+        if setup.buffer_target(mesh) is not None:
+            raise ValueError("solver does not support a buffer target")
+
         if not physics.constant_softening:
             raise ValueError("solver only supports constant gravitational softening")
 
+        if any(
+            m.softening_model != SofteningModel.PLUMMER
+            for m in physics.point_masses(time)
+        ):
+            raise ValueError("solver only supports plummer gravitational softening")
+
+        if physics.live_point_masses:
+            raise ValueError("solver does not support live point masses")
+
+        if physics.num_tracers != 0:
+            raise ValueError("solver does not support passive tracers")
+
         xp = get_array_module(mode)
         ng = GUARD  # number of guard zones
         nq = NCONS  # number of conserved quantities
@@ -385,7 +428,7 @@ class Solver(SolverBase):
             buffer_outer_radius = 0.0
             buffer_surface_density = 0.0
 
-        for n, (a, b) in enumerate(subdivide(ni, num_patches)):
+        for n, (a, b) in enumerate(mesh.tile(num_patches, ng)):
             weights_patch = numpy.zeros([b - a + 2 * ng, nj + 2 * ng, nq, ORDER, ORDER])
             weights_patch[ng:-ng, ng:-ng] = weights[a:b]
             patch = Patch(
@@ -413,6 +456,10 @@ class Solver(SolverBase):
             rank=2,
         )
 
+    @property
+    def primitive_fields(self):
+        return [SURFACE_DENSITY, VELOCITY_X, VELOCITY_Y]
+
     @property
     def primitive(self):
         self.set_bc("weights1")
@@ -456,10 +503,24 @@ class Solver(SolverBase):
             )
         return point_mass_reductions
 
+    def primitive_array(self, guard_zones=False):
+        if guard_zones:
+            self.set_bc("weights1")
+        return concat_on_host(
+            [p.primitive for p in self.patches],
+            (self.num_guard, self.num_guard),
+            rank=2,
+            outer_guard=guard_zones,
+        )
+
     @property
     def time(self):
         return self.patches[0].time
 
+    @property
+    def nominal_time(self):
+        return self.patches[0].clock.nominal_time
+
     @property
     def options(self):
         return self._options._asdict()
@@ -525,8 +586,8 @@ class Solver(SolverBase):
 
         with self.patches[patch_index].execution_context:
             # 1. write to the guard zones of pc, the internal BC
-            pc[:+ng] = pl[-2 * ng : -ng]
-            pc[-ng:] = pr[+ng : +2 * ng]
+            copy_between_devices(pc[:+ng], pl[-2 * ng : -ng])
+            copy_between_devices(pc[-ng:], pr[+ng : +2 * ng])
 
             # 2. Set outflow BC on the left/right patch edges
             if patch_index == 0:
@@ -0,0 +1,665 @@
+"""
+Isothermal solver for a disk around a single mass, on a 2D log-polar mesh.
+
+The mesh is an annulus `r0 < r < r1`, with zones whose radial width grows in
+proportion to the radius (see :py:class:`sailfish.mesh.LogPolar2DMesh`), so
+the disk is resolved most finely near its inner edge, and no zones are spent
+in the corners of a square domain. The primitive fields are the surface
+density, and the radial and azimuthal velocities `vr` and `vphi`, followed
+by the tracers. The kernels are those of `cbdiso_2d`, with the face areas,
+cell volumes, and geometric source terms of planar polar coordinates; the
+point masses and the Keplerian buffer act as they do there, with their rates
+rotated into the polar basis.
+
+The setup's `primitive` method is sampled at the zone centers in cartesian
+coordinates, as it is for the cartesian solver, and its velocities are
+rotated into the polar basis. The azimuthal boundary is periodic, and the
+inner and outer ones are outflow (zero-gradient). The buffer, if enabled,
+ramps up inward from the outer radius of the mesh.
+
+This solver is meant for single-disk problems, so it leaves out the physics
+which the cartesian solver supports with a binary in mind: viscosity, live
+point masses, self-gravity, inner and target buffers, a reservoir, custom
+sources, and a rotating or boosted frame. Its diagnostics are the time, the
+total mass, and the total angular momentum about the origin.
+"""
+
+from logging import getLogger
+from math import exp
+from typing import NamedTuple
+from sailfish.clock import Clock
+from sailfish.fields import SURFACE_DENSITY, VELOCITY_PHI, VELOCITY_R, tracer_fields
+from sailfish.kernel.library import Library, host_zeros
+from sailfish.kernel.system import copy_between_devices, get_array_module
+from sailfish.kernel.system import execution_context, num_devices
+from sailfish.mesh import LogPolar2DMesh
+from sailfish.physics.circumbinary import (
+    Physics,
+    CoolingModel,
+    Diagnostic,
+    EquationOfState,
+    ViscosityModel,
+)
+from sailfish.solver_base import SolverBase
+from sailfish.solvers import check_invalid_zones
+from sailfish.solvers.cbdiso_2d import SOURCE_UPDATES, WAVESPEED_ESTIMATORS
+from sailfish.solvers.cbdiso_2d import sound_speed_table
+from sailfish.subdivide import concat_on_host, lazy_reduce
+
+logger = getLogger(__name__)
+
+DIAGNOSTIC_QUANTITIES = ("time", "mass", "angular_momentum")
+
+
+class Options(NamedTuple):
+    """
+    Contains parameters which are solver specific options.
+    """
+
+    velocity_ceiling: float = 1e12
+    """ Upper limit of the gas speed in each direction """
+    density_floor: float = 1e-12
+    """ Lower limit of the surface density """
+    rk_order: int = 2
+    """ Order of the Runge-Kutta time stepping [1|2|3] """
+    cfl_number: float = None
+    """ CFL parameter, the recommended one if None """
+    wavespeed_estimator: str = "davis"
+    """ Wavespeed estimate of the Riemann solver [davis|einfeldt|exact] """
+    invalid_state_cadence: int = 10
+    """ Iterations between checks for invalid states, 0 for never """
+    sink_update: str = "explicit"
+    """ Update of the sink terms [explicit|subcycle|exponential] """
+    buffer_update: str = "explicit"
+    """ Update of the buffer terms [explicit|subcycle|exponential] """
+
+
+class ProfileMesh(NamedTuple):
+    """
+    The extent and spacing of a square around a log-polar mesh, from which
+    `sound_speed_table` makes the table of a profile isothermal EOS.
+    """
+
+    x0: float
+    x1: float
+    y0: float
+    y1: float
+    dx: float
+    dy: float
+
+
+def initial_condition(setup, mesh, time, num_fields=3):
+    """
+    Generate a 2D array of primitive data from a mesh and a setup, with the
+    velocities in the polar basis. Fields after the velocity, i.e. tracers,
+    are zero unless the setup writes them.
+    """
+    ni, nj = mesh.shape
+    primitive = host_zeros((ni, nj, num_fields))
+
+    for i in range(ni):
+        for j in range(nj):
+            x, y = mesh.cell_coordinates(i, j)
+            r = (x * x + y * y) ** 0.5
+            p = primitive[i, j]
+            setup.primitive(time, (x, y), p)
+            vx, vy = p[1], p[2]
+            p[1] = (vx * x + vy * y) / r
+            p[2] = (vy * x - vx * y) / r
+
+    return primitive
+
+
+class Patch:
+    """
+    Holds the array buffer state for the solution on a range of radii.
+    """
+
+    def __init__(
+        self,
+        time,
+        primitive,
+        mesh,
+        index_range,
+        physics,
+        options,
+        buffer_outer_radius,
+        buffer_surface_density,
+        lib,
+        xp,
+        execution_context,
+    ):
+        i0, i1 = index_range
+        ni, nj = i1 - i0, mesh.shape[1]
+        self.lib = lib
+        self.mesh = mesh
+        self.xp = xp
+        self.execution_context = execution_context
+        self.clock = Clock(time)
+        self.index_range = index_range
+        self.shape = (ni, nj)  # not including guard zones
+        self.physics = physics
+        self.options = options
+        self.patch_r0 = mesh.r0 * exp(i0 * mesh.dlogr)
+        self.buffer_outer_radius = buffer_outer_radius
+        self.buffer_surface_density = buffer_surface_density
+
+        with self.execution_context:
+            r = xp.array(mesh.radial_faces(i0, i1))
+            rc = self.patch_r0 * xp.exp((xp.arange(ni) + 0.5) * mesh.dlogr)
+            self.cell_radius = rc[:, None]
+            self.cell_volume = (0.5 * (r[1:] ** 2 - r[:-1] ** 2) * mesh.dphi)[:, None]
+            self.cell_spacing = rc[:, None] * min(mesh.dlogr, mesh.dphi)
+            self.wavespeeds = lib.zeros(self.shape)
+            self.cs2_profile_dr, cs2_profile = sound_speed_table(
+                physics, profile_mesh(mesh)
+            )
+            self.cs2_profile = xp.array(cs2_profile, dtype=lib.precision)
+            self.primitive1 = lib.array(primitive)
+            self.primitive2 = lib.array(primitive)
+            self.conserved0 = lib.zeros(primitive.shape)
+            self.invalid_zones = xp.zeros(self.shape, dtype=lib.precision)
+
+    def maximum_wavespeed(self, per_spacing=False):
+        """
+        Return the maximum wavespeed over the patch, or the maximum of the
+        wavespeed divided by the zone spacing, the inverse of the time step
+        at a CFL number of one, if `per_spacing` is true.
+        """
+        m1, m2 = self.physics.resolved_point_masses(self.time, self.mesh)
+
+        with self.execution_context:
+            self.lib.cbdiso_2d_wavespeed_polar[self.shape](
+                self.patch_r0,
+                self.mesh.dlogr,
+                self.mesh.dphi,
+                self.physics.sound_speed**2,
+                self.physics.mach_number**2,
+                self.physics.eos_type.value,
+                self.cs2_profile_dr,
+                len(self.cs2_profile),
+                self.cs2_profile,
+                m1.position_x,
+                m1.position_y,
+                m1.mass,
+                m1.softening_length,
+                m1.softening_model.value,
+                m2.position_x,
+                m2.position_y,
+                m2.mass,
+                m2.softening_length,
+                m2.softening_model.value,
+                self.primitive1,
+                self.wavespeeds,
+            )
+            if per_spacing:
+                return (self.wavespeeds / self.cell_spacing).max()
+            else:
+                return self.wavespeeds.max()
+
+    def recompute_conserved(self):
+        """
+        Convert the most recent primitive array to conserved.
+        """
+        with self.execution_context:
+            return self.lib.cbdiso_2d_primitive_to_conserved[self.shape](
+                self.primitive1,
+                self.conserved0,
+            )
+
+    def reduce_diagnostic(self, diagnostic):
+        """
+        Return the total of a diagnostic quantity, the mass or the angular
+        momentum about the origin, over the patch's zones inside the
+        diagnostic's radial cut, if any, as a scalar on the patch's device.
+        """
+        ng = 2  # number of guard zones
+
+        with self.execution_context:
+            p = self.primitive1[ng:-ng, ng:-ng]
+            field = p[..., 0] * self.cell_volume
+
+            if diagnostic.quantity == "angular_momentum":
+                field = field * p[..., 2] * self.cell_radius
+
+            if diagnostic.radial_cut is not None:
+                r0, r1 = diagnostic.radial_cut
+                rc = self.cell_radius
+                field = field * ((rc >= r0) & (rc < r1))
+
+            return field.sum()
+
+    def advance_rk(self, rk_param, dt):
+        """
+        Advance the patch by one Runge-Kutta stage, with the point masses
+        and the buffer at the time of the stage.
+        """
+        m1, m2 = self.physics.resolved_point_masses(self.time, self.mesh)
+        buffer_central_mass = m1.mass + m2.mass
+
+        with self.execution_context:
+            self.lib.cbdiso_2d_advance_rk_polar[self.shape](
+                self.patch_r0,
+                self.mesh.dlogr,
+                self.mesh.dphi,
+                self.conserved0,
+                self.primitive1,
+                self.primitive2,
+                self.buffer_surface_density,
+                buffer_central_mass,
+                self.physics.buffer_driving_rate,
+                self.buffer_outer_radius,
+                self.physics.buffer_onset_width,
+                int(self.physics.buffer_is_enabled),
+                SOURCE_UPDATES[self.options.buffer_update],
+                m1.position_x,
+                m1.position_y,
+                m1.velocity_x,
+                m1.velocity_y,
+                m1.mass,
+                m1.softening_length,
+                m1.softening_model.value,
+                m1.sink_rate,
+                m1.sink_radius,
+                m1.sink_model.value,
+                m2.position_x,
+                m2.position_y,
+                m2.velocity_x,
+                m2.velocity_y,
+                m2.mass,
+                m2.softening_length,
+                m2.softening_model.value,
+                m2.sink_rate,
+                m2.sink_radius,
+                m2.sink_model.value,
+                SOURCE_UPDATES[self.options.sink_update],
+                self.physics.sound_speed**2,
+                self.physics.mach_number**2,
+                self.physics.eos_type.value,
+                self.cs2_profile_dr,
+                len(self.cs2_profile),
+                self.cs2_profile,
+                rk_param,
+                dt,
+                self.options.velocity_ceiling,
+                self.options.density_floor,
+                self.invalid_zones,
+                WAVESPEED_ESTIMATORS[self.options.wavespeed_estimator],
+            )
+        self.clock.advance_stage(rk_param, dt)
+        self.primitive1, self.primitive2 = self.primitive2, self.primitive1
+
+    @property
+    def time(self):
+        return self.clock.time
+
+    def new_iteration(self):
+        self.clock.new_iteration()
+        self.recompute_conserved()
+
+    @property
+    def primitive(self):
+        return self.primitive1
+
+
+def profile_mesh(mesh):
+    """
+    Return the square around a log-polar mesh, with the spacing of the
+    mesh's innermost zones, for the table of a profile isothermal EOS.
+    """
+    r0, r1 = mesh.radial_faces(0, 1)
+    dx = min(r1 - r0, r0 * mesh.dphi)
+    return ProfileMesh(-mesh.r1, mesh.r1, -mesh.r1, mesh.r1, dx, dx)
+
+
+class Solver(SolverBase):
+    """
+    Adapter class to drive the polar kernels of the iso_2d C extension module.
+    """
+
+    def __init__(
+        self,
+        setup=None,
+        mesh=None,
+        time=0.0,
+        solution=None,
+        num_patches=1,
+        mode="cpu",
+        physics=dict(),
+        options=dict(),
+    ):
+        import numpy as np
+
+        physics["diagnostics"] = [
+            Diagnostic(**v) for v in physics.get("diagnostics", [])
+        ]
+
+        self._physics = physics = Physics(**physics)
+        self._options = options = Options(**options)
+
+        if options.rk_order not in (1, 2, 3):
+            raise ValueError("solver only supports rk_order in 1, 2, 3")
+
+        if options.wavespeed_estimator not in WAVESPEED_ESTIMATORS:
+            raise ValueError(
+                f"wavespeed_estimator must be one of {list(WAVESPEED_ESTIMATORS)}"
+            )
+
+        for update in (options.sink_update, options.buffer_update):
+            if update not in SOURCE_UPDATES:
+                raise ValueError(
+                    f"source updates must be one of {list(SOURCE_UPDATES)}"
+                )
+
+        if options.cfl_number is not None and options.cfl_number > self.maximum_cfl:
+            raise ValueError(f"cfl_number must not exceed {self.maximum_cfl}")
+
+        if options.invalid_state_cadence < 0:
+            raise ValueError("invalid_state_cadence must be non-negative")
+
+        if type(mesh) is not LogPolar2DMesh:
+            raise ValueError("solver only supports 2D log-polar mesh")
+
+        if setup.boundary_condition != "outflow":
+            raise ValueError("solver only supports outflow boundaries")
+
+        if physics.viscosity_model != ViscosityModel.NONE:
+            raise ValueError("solver does not support viscosity")
+
+        if physics.eos_type not in (
+            EquationOfState.GLOBALLY_ISOTHERMAL,
+            EquationOfState.LOCALLY_ISOTHERMAL,
+            EquationOfState.PROFILE_ISOTHERMAL,
+        ):
+            raise ValueError("solver only supports isothermal equation of states")
+
+        if physics.eos_type == EquationOfState.PROFILE_ISOTHERMAL:
+            if physics.sound_speed_profile is None:
+                raise ValueError("a profile isothermal EOS needs a sound_speed_profile")
+
+        if physics.cooling_coefficient != 0.0:
+            raise ValueError("solver does not support thermal cooling")
+
+        if physics.cooling_model != CoolingModel.NONE:
+            raise ValueError("solver does not support thermal cooling")
+
+        if physics.external_gravity != 0.0:
+            raise ValueError("solver does not support uniform external gravity")
+
+        if not physics.constant_softening:
+            raise ValueError("solver only supports constant gravitational softening")
+
+        if physics.live_point_masses:
+            raise ValueError("solver does not support live point masses")
+
+        if physics.self_gravity_constant != 0.0:
+            raise ValueError("solver does not support self-gravity")
+
+        if physics.inner_buffer_radius != 0.0:
+            raise ValueError("solver does not support an inner buffer")
+
+        if physics.reservoir is not None:
+            raise ValueError("solver does not support an inner reservoir")
+
+        if physics.custom_source is not None:
+            raise ValueError("solver does not support custom sources")
+
+        if setup.buffer_target(mesh) is not None:
+            raise ValueError("solver does not support a buffer target")
+
+        if setup.active_zones(mesh) is not None:
+            raise ValueError("solver does not support a zone mask")
+
+        if physics.num_tracers < 0:
+            raise ValueError("num_tracers must be non-negative")
+
+        for d in physics.diagnostics:
+            if d.quantity not in DIAGNOSTIC_QUANTITIES:
+                raise ValueError(
+                    f"solver only supports the diagnostics {DIAGNOSTIC_QUANTITIES}"
+                )
+            if d.which_mass is not None or d.which_probe is not None:
+                raise ValueError("solver diagnostics are totals over the gas")
+
+        xp = get_array_module(mode)
+        ng = 2  # number of guard zones
+        nq = 3 + physics.num_tracers  # number of conserved quantities
+        ni, nj = mesh.shape
+
+        with open(__file__.replace("cbdisolp_2d.py", "cbdiso_2d.c")) as f:
+            code = f.read()
+
+        lib = Library(code, mode=mode, debug=False, define_macros=dict(NCONS=nq))
+
+        logger.info(f"initiate with time={time:0.4f}")
+        logger.info(f"subdivide grid over {num_patches} patches")
+        logger.info(f"mesh is {mesh}")
+
+        if solution is None:
+            primitive = initial_condition(setup, mesh, time, nq)
+        else:
+            primitive = solution
+
+        if primitive.shape != (ni, nj, nq):
+            raise ValueError(
+                f"solution has shape {primitive.shape}, expected {(ni, nj, nq)}; "
+                "was it written with a different number of tracers?"
+            )
+
+        if physics.buffer_is_enabled:
+            # As in the cartesian solver, the initial condition is sampled at
+            # the buffer onset radius, which assumes it's axisymmetric.
+            buffer_prim = [0.0] * nq
+            buffer_outer_radius = mesh.r1
+            buffer_onset_radius = buffer_outer_radius - physics.buffer_onset_width
+            setup.primitive(time, [buffer_onset_radius, 0.0], buffer_prim)
+            buffer_surface_density = buffer_prim[0]
+        else:
+            buffer_outer_radius = 0.0
+            buffer_surface_density = 0.0
+
+        self.mesh = mesh
+        self.setup = setup
+        self.num_guard = ng
+        self.num_cons = nq
+        self.xp = xp
+        self.patches = []
+        self.num_iterations = 0
+
+        for n, (a, b) in enumerate(mesh.tile(num_patches, ng)):
+            prim = np.zeros([b - a + 2 * ng, nj + 2 * ng, nq])
+            prim[ng:-ng, ng:-ng] = primitive[a:b]
+
+            patch = Patch(
+                time,
+                prim,
+                mesh,
+                (a, b),
+                physics,
+                options,
+                buffer_outer_radius,
+                buffer_surface_density,
+                lib,
+                xp,
+                execution_context(mode, device_id=n % num_devices(mode)),
+            )
+            self.patches.append(patch)
+
+        self.set_bc("primitive1")
+
+        for patch in self.patches:
+            patch.recompute_conserved()
+
+    @property
+    def solution(self):
+        return concat_on_host(
+            [p.primitive for p in self.patches], (self.num_guard, self.num_guard)
+        )
+
+    def conserved_array(self):
+        for patch in self.patches:
+            patch.recompute_conserved()
+        return concat_on_host(
+            [p.conserved0 for p in self.patches], (self.num_guard, self.num_guard)
+        )
+
+    @property
+    def primitive_fields(self):
+        fields = [SURFACE_DENSITY, VELOCITY_R, VELOCITY_PHI]
+        return fields + tracer_fields(self._physics.num_tracers)
+
+    @property
+    def primitive(self):
+        return self.primitive_array()
+
+    def primitive_array(self, guard_zones=False):
+        if guard_zones:
+            self.set_bc("primitive1")
+        return concat_on_host(
+            [p.primitive for p in self.patches],
+            (self.num_guard, self.num_guard),
+            outer_guard=guard_zones,
+        )
+
+    @property
+    def reduction_names(self):
+        return [d.label for d in self._physics.diagnostics]
+
+    @property
+    def reduction_units(self):
+        return [d.units for d in self._physics.diagnostics]
+
+    def reductions(self):
+        """
+        Return the diagnostics: the time, and the totals of the mass and the
+        angular momentum about the origin, each reduced on the patch devices.
+        """
+        result = list()
+
+        for d in self._physics.diagnostics:
+            if d.quantity == "time":
+                result.append(self.time)
+            else:
+                result.append(
+                    lazy_reduce(
+                        sum,
+                        float,
+                        (lambda p=p: p.reduce_diagnostic(d) for p in self.patches),
+                        (p.execution_context for p in self.patches),
+                    )
+                )
+
+        return result
+
+    @property
+    def time(self):
+        return self.patches[0].time
+
+    @property
+    def nominal_time(self):
+        return self.patches[0].clock.nominal_time
+
+    @property
+    def options(self):
+        return self._options._asdict()
+
+    @property
+    def physics(self):
+        return self._physics._asdict()
+
+    @property
+    def recommended_cfl(self):
+        return self._options.cfl_number or 0.3
+
+    @property
+    def maximum_cfl(self):
+        return 0.4
+
+    def maximum_wavespeed(self):
+        """
+        Return the global maximum wavespeed over the whole domain.
+        """
+        return lazy_reduce(
+            max,
+            float,
+            (patch.maximum_wavespeed for patch in self.patches),
+            (patch.execution_context for patch in self.patches),
+        )
+
+    def recommended_timestep(self, min_spacing, cfl_number):
+        """
+        Return the time step of the CFL condition in each zone, with its own
+        spacing, rather than the smallest spacing of the mesh, which is that
+        of the innermost zones.
+        """
+        rate = lazy_reduce(
+            max,
+            float,
+            (
+                lambda patch=patch: patch.maximum_wavespeed(per_spacing=True)
+                for patch in self.patches
+            ),
+            (patch.execution_context for patch in self.patches),
+        )
+        return cfl_number / rate
+
+    def advance(self, dt):
+        self.num_iterations += 1
+        cadence = self._options.invalid_state_cadence
+        bs_rk1 = [0 / 1]
+        bs_rk2 = [0 / 1, 1 / 2]
+        bs_rk3 = [0 / 1, 3 / 4, 1 / 3]
+        bs = (bs_rk1, bs_rk2, bs_rk3)[self._options.rk_order - 1]
+
+        self.new_iteration()
+
+        for b in bs:
+            self.advance_rk(b, dt)
+
+        if cadence and self.num_iterations % cadence == 0:
+            check_invalid_zones(self.patches, self.mesh, self.time)
+
+    def advance_rk(self, rk_param, dt):
+        self.set_bc("primitive1")
+        for patch in self.patches:
+            patch.advance_rk(rk_param, dt)
+
+    def set_bc(self, array):
+        num_patches = len(self.patches)
+        for i0 in range(num_patches):
+            il = (i0 + num_patches - 1) % num_patches
+            ir = (i0 + num_patches + 1) % num_patches
+            pl = getattr(self.patches[il], array)
+            pc = getattr(self.patches[i0], array)
+            pr = getattr(self.patches[ir], array)
+            self.set_bc_patch(pl, pc, pr, i0)
+
+    def set_bc_patch(self, pl, pc, pr, patch_index):
+        """
+        Fill the guard zones of a patch: from its neighbors, which may be on
+        other devices, with outflow values on the inner and outer radii, and
+        periodically in the azimuth.
+        """
+        ng = self.num_guard
+        patch = self.patches[patch_index]
+
+        with patch.execution_context:
+            # 1. write to the guard zones of pc, the internal BC
+            copy_between_devices(pc[:+ng], pl[-2 * ng : -ng])
+            copy_between_devices(pc[-ng:], pr[+ng : +2 * ng])
+
+            # 2. Set outflow BC on the inner and outer radii
+            if patch_index == 0:
+                for i in range(ng):
+                    pc[i] = pc[ng]
+            if patch_index == len(self.patches) - 1:
+                for i in range(pc.shape[0] - ng, pc.shape[0]):
+                    pc[i] = pc[-ng - 1]
+
+            # 3. Set periodic BC in the azimuth
+            pc[:, :ng] = pc[:, -2 * ng : -ng]
+            pc[:, -ng:] = pc[:, ng : 2 * ng]
+
+    def new_iteration(self):
+        for patch in self.patches:
+            patch.new_iteration()
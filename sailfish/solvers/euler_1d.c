@@ -0,0 +1,386 @@
+/*
+MODULE: euler_1d
+
+AUTHOR: Jonathan Zrake
+
+DESCRIPTION:
+  Solves the Newtonian Euler equations with a gamma-law equation of state
+  in 1D cartesian or spherical coordinates. A gamma-law index of one means
+  the gas is isothermal; the energy then only includes the kinetic part, and
+  the pressure is set from the density when converting to primitives.
+*/
+
+
+// ============================ PHYSICS =======================================
+// ============================================================================
+#define NCONS 4
+
+
+// ============================ MATH ==========================================
+// ============================================================================
+#define min2(a, b) ((a) < (b) ? (a) : (b))
+#define max2(a, b) ((a) > (b) ? (a) : (b))
+#define min3(a, b, c) min2(a, min2(b, c))
+#define max3(a, b, c) max2(a, max2(b, c))
+#define sign(x) copysign(1.0, x)
+#define minabs(a, b, c) min3(fabs(a), fabs(b), fabs(c))
+
+PRIVATE double plm_gradient_scalar(double yl, double y0, double yr, double theta)
+{
+    double a = (y0 - yl) * theta;
+    double b = (yr - yl) * 0.5;
+    double c = (yr - y0) * theta;
+    return 0.25 * fabs(sign(a) + sign(b)) * (sign(a) + sign(c)) * minabs(a, b, c);
+}
+
+PRIVATE void plm_gradient(double *yl, double *y0, double *yr, double *g, double theta)
+{
+    for (int q = 0; q < NCONS; ++q)
+    {
+        g[q] = plm_gradient_scalar(yl[q], y0[q], yr[q], theta);
+    }
+}
+
+
+// ============================ HYDRO =========================================
+// ============================================================================
+PRIVATE void primitive_to_conserved(const double *prim, double *cons, double dv, double gamma_law_index)
+{
+    const double rho = prim[0];
+    const double vel = prim[1];
+    const double pre = prim[2];
+    const double internal = gamma_law_index == 1.0 ? 0.0 : pre / (gamma_law_index - 1.0);
+
+    cons[0] = dv * rho;
+    cons[1] = dv * rho * vel;
+    cons[2] = dv * (internal + 0.5 * rho * vel * vel);
+    cons[3] = dv * rho * prim[3];
+}
+
+PRIVATE void conserved_to_primitive(
+    const double *cons,
+    double *prim,
+    double dv,
+    double density_floor,
+    double pressure_floor,
+    double gamma_law_index)
+{
+    const double rho = cons[0] / dv;
+    const double vel = cons[1] / cons[0];
+    const double pre = (cons[2] / dv - 0.5 * rho * vel * vel) * (gamma_law_index - 1.0);
+
+    if (rho < density_floor)
+    {
+        prim[0] = density_floor;
+        prim[1] = 0.0;
+        prim[2] = pressure_floor;
+        prim[3] = 0.0;
+        return;
+    }
+    prim[0] = rho;
+    prim[1] = vel;
+    prim[2] = max2(pre, pressure_floor);
+    prim[3] = cons[3] / cons[0];
+}
+
+PRIVATE void primitive_to_flux(const double *prim, const double *cons, double *flux)
+{
+    const double vn = prim[1];
+    const double pre = prim[2];
+
+    flux[0] = vn * cons[0];
+    flux[1] = vn * cons[1] + pre;
+    flux[2] = vn * (cons[2] + pre);
+    flux[3] = vn * cons[3];
+}
+
+PRIVATE double primitive_to_sound_speed_squared(const double *prim, double gamma_law_index)
+{
+    return gamma_law_index * prim[2] / prim[0];
+}
+
+PRIVATE void primitive_to_outer_wavespeeds(const double *prim, double *wavespeeds, double gamma_law_index)
+{
+    double cs = sqrt(primitive_to_sound_speed_squared(prim, gamma_law_index));
+    double vn = prim[1];
+    wavespeeds[0] = vn - cs;
+    wavespeeds[1] = vn + cs;
+}
+
+PRIVATE void riemann_hlle(const double *pl, const double *pr, double *flux, double gamma_law_index)
+{
+    double ul[NCONS];
+    double ur[NCONS];
+    double fl[NCONS];
+    double fr[NCONS];
+    double al[2];
+    double ar[2];
+
+    primitive_to_conserved(pl, ul, 1.0, gamma_law_index);
+    primitive_to_conserved(pr, ur, 1.0, gamma_law_index);
+    primitive_to_flux(pl, ul, fl);
+    primitive_to_flux(pr, ur, fr);
+    primitive_to_outer_wavespeeds(pl, al, gamma_law_index);
+    primitive_to_outer_wavespeeds(pr, ar, gamma_law_index);
+
+    const double am = min3(0.0, al[0], ar[0]);
+    const double ap = max3(0.0, al[1], ar[1]);
+
+    for (int q = 0; q < NCONS; ++q)
+    {
+        flux[q] = (fl[q] * ap - fr[q] * am - (ul[q] - ur[q]) * ap * am) / (ap - am);
+    }
+}
+
+PRIVATE void riemann_hllc(const double *pl, const double *pr, double *flux, double gamma_law_index)
+{
+    double ul[NCONS];
+    double ur[NCONS];
+    double fl[NCONS];
+    double fr[NCONS];
+    double al[2];
+    double ar[2];
+
+    primitive_to_conserved(pl, ul, 1.0, gamma_law_index);
+    primitive_to_conserved(pr, ur, 1.0, gamma_law_index);
+    primitive_to_flux(pl, ul, fl);
+    primitive_to_flux(pr, ur, fr);
+    primitive_to_outer_wavespeeds(pl, al, gamma_law_index);
+    primitive_to_outer_wavespeeds(pr, ar, gamma_law_index);
+
+    const double am = min3(0.0, al[0], ar[0]);
+    const double ap = max3(0.0, al[1], ar[1]);
+    const double ml = pl[0] * (am - pl[1]);
+    const double mr = pr[0] * (ap - pr[1]);
+    const double v_star = (pr[2] - pl[2] + ml * pl[1] - mr * pr[1]) / (ml - mr);
+
+    // The face is between the outer waves (they're bounded by zero), and on
+    // the side of the contact given by the sign of its speed.
+    const double *pk = v_star >= 0.0 ? pl : pr;
+    const double *uk = v_star >= 0.0 ? ul : ur;
+    const double *fk = v_star >= 0.0 ? fl : fr;
+    const double sk = v_star >= 0.0 ? am : ap;
+    const double mk = v_star >= 0.0 ? ml : mr;
+    const double d = mk / (sk - v_star);
+    double u_star[NCONS];
+
+    u_star[0] = d;
+    u_star[1] = d * v_star;
+    u_star[2] = d * (uk[2] / pk[0] + (v_star - pk[1]) * (v_star + pk[2] / mk));
+    u_star[3] = d * pk[3];
+
+    for (int q = 0; q < NCONS; ++q)
+    {
+        flux[q] = fk[q] + sk * (u_star[q] - uk[q]);
+    }
+}
+
+PRIVATE void riemann_flux(const double *pl, const double *pr, double *flux, int riemann_solver, double gamma_law_index)
+{
+    switch (riemann_solver)
+    {
+        case 1:
+            riemann_hllc(pl, pr, flux, gamma_law_index);
+            break;
+        default:
+            riemann_hlle(pl, pr, flux, gamma_law_index);
+            break;
+    }
+}
+
+
+// ============================ GEOMETRY ======================================
+// ============================================================================
+#include "geometry.c"
+
+PRIVATE void geometric_source_terms(int coords, double x0, double x1, const double *prim, double *source)
+{
+    source[0] = 0.0;
+    source[1] = prim[2] * pressure_source_weight_1d(coords, x0, x1);
+    source[2] = 0.0;
+    source[3] = 0.0;
+}
+
+
+// ============================ KERNELS =======================================
+// ============================================================================
+
+
+/**
+ * Converts an array of primitive data to an array of conserved data. Like in
+ * the srhd_1d solver, there are no guard zones on the input or output arrays,
+ * and the conserved data is the volume integral over each zone.
+ */
+PUBLIC void euler_1d_primitive_to_conserved(
+    int num_zones,
+    double *face_positions,  // :: $.shape == (num_zones + 1,)
+    double *primitive,       // :: $.shape == (num_zones, 4)
+    double *conserved,       // :: $.shape == (num_zones, 4)
+    int coords,              // :: $ in [0, 1]
+    double gamma_law_index)  // :: $ >= 1.0
+{
+    FOR_EACH_1D(num_zones)
+    {
+        double *p = &primitive[NCONS * i];
+        double *u = &conserved[NCONS * i];
+        double dv = cell_volume_1d(coords, face_positions[i], face_positions[i + 1]);
+        primitive_to_conserved(p, u, dv, gamma_law_index);
+    }
+}
+
+
+/**
+ * Converts an array of conserved data to an array of primitive data. If the
+ * gas is isothermal (the gamma-law index is one), the pressure is the
+ * density times the given sound speed squared.
+ */
+PUBLIC void euler_1d_conserved_to_primitive(
+    int num_zones,
+    double *face_positions, // :: $.shape == (num_zones + 1,)
+    double *conserved,      // :: $.shape == (num_zones + 4, 4)
+    double *primitive,      // :: $.shape == (num_zones + 4, 4)
+    int coords,             // :: $ in [0, 1]
+    double density_floor,   // :: $ >= 0.0
+    double pressure_floor,  // :: $ >= 0.0
+    double gamma_law_index, // :: $ >= 1.0
+    double sound_speed_squared) // :: gamma_law_index > 1.0 or $ > 0.0
+{
+    int ng = 2; // number of guard zones
+
+    FOR_EACH_1D(num_zones)
+    {
+        double *p = &primitive[NCONS * (i + ng)];
+        double *u = &conserved[NCONS * (i + ng)];
+        double dv = cell_volume_1d(coords, face_positions[i], face_positions[i + 1]);
+        conserved_to_primitive(u, p, dv, density_floor, pressure_floor, gamma_law_index);
+
+        if (gamma_law_index == 1.0)
+        {
+            p[2] = max2(p[0] * sound_speed_squared, pressure_floor);
+        }
+    }
+}
+
+
+/**
+ * Computes the maximum wavespeed in each zone.
+ */
+PUBLIC void euler_1d_max_wavespeeds(
+    int num_zones,           // number of zones, not including guard zones
+    double *primitive,       // :: $.shape == (num_zones + 4, 4)
+    double *wavespeed,       // :: $.shape == (num_zones,)
+    double gamma_law_index)  // :: $ >= 1.0
+{
+    int ng = 2; // number of guard zones
+
+    FOR_EACH_1D(num_zones)
+    {
+        double *p = &primitive[(i + ng) * NCONS];
+        double ai[2];
+        primitive_to_outer_wavespeeds(p, ai, gamma_law_index);
+        wavespeed[i] = max2(fabs(ai[0]), fabs(ai[1]));
+    }
+}
+
+
+/**
+ * Updates an array of conserved data by advancing it a single Runge-Kutta
+ * step. If `record_face_fluxes` is non-zero, the fluxes through the faces,
+ * times the face area, are accumulated over the stages in the same way as
+ * the conserved data, so that over an iteration the change in a zone is
+ * exactly the difference of the recorded fluxes, plus source terms.
+ */
+PUBLIC void euler_1d_advance_rk(
+    int num_zones,          // number of zones, not including guard zones
+    double *face_positions, // :: $.shape == (num_zones + 1,)
+    double *conserved_rk,   // :: $.shape == (num_zones + 4, 4)
+    double *primitive_rd,   // :: $.shape == (num_zones + 4, 4)
+    double *conserved_rd,   // :: $.shape == (num_zones + 4, 4)
+    double *conserved_wr,   // :: $.shape == (num_zones + 4, 4)
+    double rk_param,        // runge-kutta parameter
+    double dt,              // timestep size
+    int fix_i0,             // don't evolve the first zone in the patch
+    int fix_i1,             // don't evolve the final zone in the patch
+    int coords,             // :: $ in [0, 1]
+    int riemann_solver,     // :: $ in [0, 1]
+    double plm_theta,       // :: $ >= 1.0 and $ <= 2.0
+    double gamma_law_index, // :: $ >= 1.0
+    int record_face_fluxes,
+    double *face_flux)      // :: record_face_fluxes == 0 or $.shape == (num_zones + 1, 4)
+{
+    int ng = 2; // number of guard zones
+
+    FOR_EACH_1D(num_zones)
+    {
+        int fixed_zone = (fix_i0 && i == 0) || (fix_i1 && i == num_zones - 1);
+
+        if (!fixed_zone)
+        {
+            double xl = face_positions[i];
+            double xr = face_positions[i + 1];
+
+            double *urk = &conserved_rk[NCONS * (i + ng)];
+            double *urd = &conserved_rd[NCONS * (i + ng)];
+            double *uwr = &conserved_wr[NCONS * (i + ng)];
+            double *prd = &primitive_rd[NCONS * (i + ng)];
+            double *pli = &primitive_rd[NCONS * (i + ng - 1)];
+            double *pri = &primitive_rd[NCONS * (i + ng + 1)];
+            double *pki = &primitive_rd[NCONS * (i + ng - 2)];
+            double *pti = &primitive_rd[NCONS * (i + ng + 2)];
+
+            double plip[NCONS];
+            double plim[NCONS];
+            double prip[NCONS];
+            double prim[NCONS];
+            double gxli[NCONS];
+            double gxri[NCONS];
+            double gxcc[NCONS];
+
+            plm_gradient(pki, pli, prd, gxli, plm_theta);
+            plm_gradient(pli, prd, pri, gxcc, plm_theta);
+            plm_gradient(prd, pri, pti, gxri, plm_theta);
+
+            for (int q = 0; q < NCONS; ++q)
+            {
+                plim[q] = pli[q] + 0.5 * gxli[q];
+                plip[q] = prd[q] - 0.5 * gxcc[q];
+                prim[q] = prd[q] + 0.5 * gxcc[q];
+                prip[q] = pri[q] - 0.5 * gxri[q];
+            }
+
+            double fli[NCONS];
+            double fri[NCONS];
+            double sources[NCONS];
+            double dal = face_area_1d(coords, xl);
+            double dar = face_area_1d(coords, xr);
+
+            riemann_flux(plim, plip, fli, riemann_solver, gamma_law_index);
+            riemann_flux(prim, prip, fri, riemann_solver, gamma_law_index);
+            geometric_source_terms(coords, xl, xr, prd, sources);
+
+            if (record_face_fluxes)
+            {
+                // Each zone writes its left face, and the last zone also
+                // writes the right one.
+                double *gli = &face_flux[NCONS * (i + 0)];
+                double *gri = &face_flux[NCONS * (i + 1)];
+
+                for (int q = 0; q < NCONS; ++q)
+                {
+                    gli[q] = (1.0 - rk_param) * (gli[q] + fli[q] * dal * dt);
+
+                    if (i == num_zones - 1)
+                    {
+                        gri[q] = (1.0 - rk_param) * (gri[q] + fri[q] * dar * dt);
+                    }
+                }
+            }
+
+            for (int q = 0; q < NCONS; ++q)
+            {
+                uwr[q] = urd[q] + (fli[q] * dal - fri[q] * dar + sources[q]) * dt;
+                uwr[q] = (1.0 - rk_param) * uwr[q] + rk_param * urk[q];
+            }
+        }
+    }
+}
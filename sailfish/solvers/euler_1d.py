@@ -0,0 +1,460 @@
+"""
+One-dimensional Newtonian hydro solver, for shock tubes and spherical flows.
+
+The solver configuration is:
+
+- Planar cartesian, or spherical coordinate system in 1d (on a
+  `LogSphericalMesh` without expansion)
+- Four conserved quantities: mass, momentum, total energy, and scalar mass,
+  each integrated over the zone volume
+- Four primitive quantities: rho, v, p, x (scalar concentration)
+- Gamma-law equation of state, with the index given by the
+  `gamma_law_index` physics parameter, or an isothermal one if the
+  `isothermal_sound_speed` physics parameter is given; the total energy is
+  then only the kinetic energy, and the pressure is ignored in the initial
+  data
+
+It has the same structure, boundary conditions, and Runge-Kutta options as
+the `srhd_1d` solver, so 1D convergence tests and prototypes don't need to be
+run as thin 2D domains with the disk solvers. In spherical coordinates the
+pressure gradient picks up the geometric source term of the momentum
+equation. The `plm_theta` and `riemann_solver` options are like those of the
+`cbdgam_2d` solver. With the option `record_face_fluxes`, each patch keeps
+the time-integrated fluxes through its faces over the latest iteration, in
+`face_flux`; the solvers coupled by `sailfish.coupling` use them to match
+the fluxes through their interfaces.
+"""
+
+from logging import getLogger
+from typing import NamedTuple
+from sailfish.clock import Clock
+from sailfish.fields import DENSITY, GAS_PRESSURE, Field
+from sailfish.kernel.library import Library
+from sailfish.kernel.system import copy_between_devices, get_array_module
+from sailfish.kernel.system import execution_context, num_devices
+from sailfish.subdivide import concat_on_host, lazy_reduce
+from sailfish.mesh import PlanarCartesianMesh, LogSphericalMesh
+from sailfish.solver_base import SolverBase
+
+logger = getLogger(__name__)
+
+NUM_GUARD = 2
+NUM_CONS = 4
+
+BC_PERIODIC = 0
+BC_OUTFLOW = 1
+BC_INFLOW = 2
+BC_REFLECT = 3
+BC_FIXED = 4
+
+BC_DICT = {
+    "periodic": BC_PERIODIC,
+    "outflow": BC_OUTFLOW,
+    "inflow": BC_INFLOW,
+    "reflect": BC_REFLECT,
+    "fixed": BC_FIXED,
+}
+RIEMANN_SOLVERS = dict(hlle=0, hllc=1)
+
+
+def initial_condition(setup, mesh, i0, i1, time, xp):
+    primitive = xp.zeros([i1 - i0, NUM_CONS])
+
+    for i in range(i0, i1):
+        r = mesh.zone_center(time, i)
+        setup.primitive(time, r, primitive[i - i0])
+    return primitive
+
+
+class Options(NamedTuple):
+    rk_order: int = 2
+    plm_theta: float = 1.5
+    riemann_solver: str = "hllc"
+    density_floor: float = 1e-12
+    pressure_floor: float = 1e-14
+    record_face_fluxes: bool = False
+
+
+class Physics(NamedTuple):
+    gamma_law_index: float = 5.0 / 3.0
+    isothermal_sound_speed: float = None
+
+    @property
+    def effective_gamma_law_index(self):
+        """
+        The gamma-law index given to the kernels, which is one for an
+        isothermal gas.
+        """
+        return self.gamma_law_index if self.isothermal_sound_speed is None else 1.0
+
+    @property
+    def sound_speed_squared(self):
+        """
+        The square of the isothermal sound speed, or zero for a gamma-law gas.
+        """
+        return (self.isothermal_sound_speed or 0.0) ** 2
+
+
+class Patch:
+    """
+    Buffers for the solution on a subset of the solution domain.
+
+    This class also takes care of generating initial conditions if needed, and
+    issuing calls to the solver kernel functions.
+    """
+
+    def __init__(
+        self,
+        setup,
+        time,
+        conserved,
+        mesh,
+        index_range,
+        fix_i0,
+        fix_i1,
+        physics,
+        options,
+        lib,
+        xp,
+        execution_context,
+    ):
+        ng = NUM_GUARD
+        nq = NUM_CONS
+        i0, i1 = index_range
+        self.lib = lib
+        self.xp = xp
+        self.index_range = index_range
+        self.fix_i0 = fix_i0
+        self.fix_i1 = fix_i1
+        self.physics = physics
+        self.options = options
+        self.num_zones = num_zones = index_range[1] - index_range[0]
+        self.coordinates = coordinates = mesh.geometry.kernel_coordinates
+        self.clock = Clock(time)
+        self.execution_context = execution_context
+
+        with execution_context:
+            faces = xp.array(mesh.faces(*index_range))
+            conserved_with_guard = xp.zeros([num_zones + 2 * ng, nq])
+
+            if conserved is None:
+                primitive = initial_condition(setup, mesh, i0, i1, time, xp)
+                conserved = xp.zeros_like(primitive)
+
+                lib.euler_1d_primitive_to_conserved[num_zones](
+                    faces,
+                    primitive,
+                    conserved,
+                    coordinates,
+                    physics.effective_gamma_law_index,
+                )
+                conserved_with_guard[ng:-ng] = conserved
+            else:
+                conserved_with_guard[ng:-ng] = xp.array(conserved)
+
+            self.faces = faces
+            self.wavespeeds = xp.zeros(num_zones)
+            self.face_flux = xp.zeros(
+                [num_zones + 1, nq] if options.record_face_fluxes else 1
+            )
+            self.primitive1 = xp.zeros_like(conserved_with_guard)
+            self.conserved0 = conserved_with_guard.copy()
+            self.conserved1 = conserved_with_guard.copy()
+            self.conserved2 = conserved_with_guard.copy()
+
+    def recompute_primitive(self):
+        with self.execution_context:
+            self.lib.euler_1d_conserved_to_primitive[self.num_zones](
+                self.faces,
+                self.conserved1,
+                self.primitive1,
+                self.coordinates,
+                self.options.density_floor,
+                self.options.pressure_floor,
+                self.physics.effective_gamma_law_index,
+                self.physics.sound_speed_squared,
+            )
+
+    def advance_rk(self, rk_param, dt):
+        with self.execution_context:
+            self.lib.euler_1d_advance_rk[self.num_zones](
+                self.faces,
+                self.conserved0,
+                self.primitive1,
+                self.conserved1,
+                self.conserved2,
+                rk_param,
+                dt,
+                int(self.fix_i0),
+                int(self.fix_i1),
+                self.coordinates,
+                RIEMANN_SOLVERS[self.options.riemann_solver],
+                self.options.plm_theta,
+                self.physics.effective_gamma_law_index,
+                int(self.options.record_face_fluxes),
+                self.face_flux,
+            )
+        self.clock.advance_stage(rk_param, dt)
+        self.conserved1, self.conserved2 = self.conserved2, self.conserved1
+
+    def maximum_wavespeed(self):
+        self.recompute_primitive()
+        with self.execution_context:
+            self.lib.euler_1d_max_wavespeeds[self.num_zones](
+                self.primitive1,
+                self.wavespeeds,
+                self.physics.effective_gamma_law_index,
+            )
+            return self.wavespeeds.max()
+
+    @property
+    def time(self):
+        return self.clock.time
+
+    def new_iteration(self):
+        self.clock.new_iteration()
+        self.conserved0[...] = self.conserved1[...]
+
+        if self.options.record_face_fluxes:
+            with self.execution_context:
+                self.face_flux[...] = 0.0
+
+    @property
+    def conserved(self):
+        return self.conserved1
+
+    @property
+    def primitive(self):
+        self.recompute_primitive()
+        return self.primitive1
+
+
+class Solver(SolverBase):
+    """
+    Adapter class to drive the euler_1d C extension module.
+    """
+
+    def __init__(
+        self,
+        setup=None,
+        mesh=None,
+        time=0.0,
+        solution=None,
+        num_patches=1,
+        mode="cpu",
+        physics=dict(),
+        options=dict(),
+    ):
+        with open(__file__.replace(".py", ".c")) as f:
+            code = f.read()
+
+        xp = get_array_module(mode)
+        lib = Library(code, mode=mode, debug=False)
+
+        self._physics = physics = Physics(**physics)
+        self._options = options = Options(**options)
+
+        try:
+            bcl, bcr = setup.boundary_condition
+        except ValueError:
+            bcl = setup.boundary_condition
+            bcr = setup.boundary_condition
+        try:
+            self.boundary_condition = BC_DICT[bcl], BC_DICT[bcr]
+        except KeyError:
+            raise ValueError(f"bad boundary condition {bcl}/{bcr}")
+
+        if type(mesh) not in (PlanarCartesianMesh, LogSphericalMesh):
+            raise ValueError("solver only supports 1D cartesian or spherical meshes")
+
+        if getattr(mesh, "polar_grid", False):
+            raise ValueError("solver does not support a polar grid")
+
+        if getattr(mesh, "scale_factor_derivative", None) is not None:
+            raise ValueError("solver does not support an expanding mesh")
+
+        if options.rk_order not in (1, 2, 3):
+            raise ValueError("solver only supports rk_order in 1, 2, 3")
+
+        if options.riemann_solver not in RIEMANN_SOLVERS:
+            raise ValueError(f"riemann_solver must be one of {list(RIEMANN_SOLVERS)}")
+
+        if not 1.0 <= options.plm_theta <= 2.0:
+            raise ValueError(f"plm_theta must be in [1, 2], got {options.plm_theta}")
+
+        if physics.gamma_law_index <= 1.0:
+            raise ValueError("gamma_law_index must be greater than 1")
+
+        if physics.isothermal_sound_speed is not None:
+            if physics.isothermal_sound_speed <= 0.0:
+                raise ValueError("isothermal_sound_speed must be positive")
+
+        logger.info(f"initiate with time={time:0.4f}")
+        logger.info(f"subdivide grid over {num_patches} patches")
+        logger.info(f"mesh is {mesh}")
+        logger.info(f"boundary condition is {bcl}/{bcr}")
+        patches = list()
+
+        for n, (a, b) in enumerate(mesh.tile(num_patches, NUM_GUARD)):
+            fix_i0 = self.boundary_condition[0] == BC_FIXED and n == 0
+            fix_i1 = self.boundary_condition[1] == BC_FIXED and n == num_patches - 1
+            patch = Patch(
+                setup,
+                time,
+                solution[a:b] if solution is not None else None,
+                mesh,
+                (a, b),
+                fix_i0,
+                fix_i1,
+                physics,
+                options,
+                lib,
+                xp,
+                execution_context(mode, device_id=n % num_devices(mode)),
+            )
+            patches.append(patch)
+
+        self.mesh = mesh
+        self.setup = setup
+        self.num_guard = NUM_GUARD
+        self.num_cons = NUM_CONS
+        self.xp = xp
+        self.patches = patches
+
+    @property
+    def solution(self):
+        return concat_on_host([p.conserved for p in self.patches], self.num_guard)
+
+    def conserved_array(self):
+        return self.solution
+
+    @property
+    def primitive_fields(self):
+        return [
+            DENSITY,
+            Field("v", "velocity in the x or radial direction", "length / time"),
+            GAS_PRESSURE,
+            Field("scalar", "passive scalar concentration"),
+        ]
+
+    @property
+    def primitive(self):
+        return concat_on_host([p.primitive for p in self.patches], self.num_guard)
+
+    def primitive_array(self, guard_zones=False):
+        if guard_zones:
+            for patch in self.patches:
+                patch.recompute_primitive()
+            self.set_bc("primitive1")
+        return concat_on_host(
+            [p.primitive for p in self.patches],
+            self.num_guard,
+            outer_guard=guard_zones,
+        )
+
+    @property
+    def time(self):
+        return self.patches[0].time
+
+    @property
+    def nominal_time(self):
+        return self.patches[0].clock.nominal_time
+
+    @property
+    def options(self):
+        return self._options._asdict()
+
+    @property
+    def physics(self):
+        return self._physics._asdict()
+
+    @property
+    def recommended_cfl(self):
+        return 0.4
+
+    @property
+    def maximum_cfl(self):
+        return 0.5
+
+    def maximum_wavespeed(self):
+        return lazy_reduce(
+            max,
+            float,
+            (patch.maximum_wavespeed for patch in self.patches),
+            (patch.execution_context for patch in self.patches),
+        )
+
+    @property
+    def runge_kutta_parameters(self):
+        """
+        The Runge-Kutta parameter of each stage of an iteration.
+        """
+        bs_rk1 = [0 / 1]
+        bs_rk2 = [0 / 1, 1 / 2]
+        bs_rk3 = [0 / 1, 3 / 4, 1 / 3]
+        return (bs_rk1, bs_rk2, bs_rk3)[self._options.rk_order - 1]
+
+    def advance(self, dt):
+        self.new_iteration()
+
+        for b in self.runge_kutta_parameters:
+            self.advance_rk(b, dt)
+
+    def advance_rk(self, rk_param, dt):
+        for patch in self.patches:
+            patch.recompute_primitive()
+
+        self.set_bc("primitive1")
+
+        for patch in self.patches:
+            patch.advance_rk(rk_param, dt)
+
+    def set_bc(self, array):
+        num_patches = len(self.patches)
+        for ic in range(num_patches):
+            il = (ic + num_patches - 1) % num_patches
+            ir = (ic + num_patches + 1) % num_patches
+            pl = getattr(self.patches[il], array)
+            pc = getattr(self.patches[ic], array)
+            pr = getattr(self.patches[ir], array)
+            self.set_bc_patch(pl, pc, pr, ic)
+
+    def set_bc_patch(self, pl, pc, pr, patch_index):
+        t = self.time
+        ni = self.mesh.shape[0]
+        ng = self.num_guard
+        bcl, bcr = self.boundary_condition
+
+        with self.patches[patch_index].execution_context:
+            copy_between_devices(pc[:+ng], pl[-2 * ng : -ng])
+            copy_between_devices(pc[-ng:], pr[+ng : +2 * ng])
+
+            def negative_vel(p):
+                return self.xp.asarray([p[0], -p[1], p[2], p[3]])
+
+            if patch_index == 0:
+                if bcl == BC_OUTFLOW:
+                    pc[:+ng] = pc[+ng : +2 * ng]
+                elif bcl == BC_INFLOW:
+                    for i in range(-ng, 0):
+                        x = self.mesh.zone_center(t, i)
+                        self.setup.primitive(t, x, pc[i + ng])
+                elif bcl == BC_REFLECT:
+                    pc[0] = negative_vel(pc[3])
+                    pc[1] = negative_vel(pc[2])
+
+            if patch_index == len(self.patches) - 1:
+                if bcr == BC_OUTFLOW:
+                    pc[-ng:] = pc[-2 * ng : -ng]
+                elif bcr == BC_INFLOW:
+                    i0 = self.patches[patch_index].index_range[0]
+                    for i in range(ni, ni + ng):
+                        x = self.mesh.zone_center(t, i)
+                        self.setup.primitive(t, x, pc[i - i0 + ng])
+                elif bcr == BC_REFLECT:
+                    pc[-2] = negative_vel(pc[-3])
+                    pc[-1] = negative_vel(pc[-4])
+
+    def new_iteration(self):
+        for patch in self.patches:
+            patch.new_iteration()
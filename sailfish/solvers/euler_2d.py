@@ -0,0 +1,58 @@
+"""
+Adiabatic gas dynamics in 2D planar Cartesian coordinates.
+
+The Euler equations are solved for the density, the two components of the
+momentum density, and the total energy density, with a gamma-law equation of
+state, e.g. for blast waves and 2D Riemann problems. These are the same four
+conserved quantities the `cbdgam_2d` solver evolves for a vertically
+integrated disk, and this solver uses its kernels, options, and boundary
+conditions (periodic, one of the outflow modes, reflecting, or fixed on each
+edge; see `sailfish.boundary`). The disk physics is not available: there are
+no point masses, buffer zone, viscosity, or cooling, although a uniform
+`external_gravity` in the -y direction may be given. The primitive fields
+are named as volumetric quantities, `rho` and `pressure`, and `pressure2`
+with the `two_temperature` option.
+"""
+
+from sailfish.fields import (
+    DENSITY,
+    VELOCITY_X,
+    VELOCITY_Y,
+    GAS_PRESSURE,
+    SECOND_GAS_PRESSURE,
+)
+from sailfish.physics.circumbinary import EquationOfState, ViscosityModel
+from sailfish.solvers import cbdgam_2d
+
+Options = cbdgam_2d.Options
+
+
+class Solver(cbdgam_2d.Solver):
+    """
+    Adapts the `cbdgam_2d` solver to the plain Euler equations.
+    """
+
+    def __init__(self, physics=dict(), **kwargs):
+        physics = dict(physics)
+        physics.setdefault("eos_type", EquationOfState.GAMMA_LAW)
+
+        if physics.get("point_mass_function") is not None:
+            raise ValueError("solver does not support point masses")
+
+        if physics.get("buffer_is_enabled", False):
+            raise ValueError("solver does not support a buffer zone")
+
+        if physics.get("viscosity_model", ViscosityModel.NONE) != ViscosityModel.NONE:
+            raise ValueError("solver does not support viscosity")
+
+        if physics.get("cooling_coefficient", 0.0) != 0.0:
+            raise ValueError("solver does not support cooling")
+
+        super().__init__(physics=physics, **kwargs)
+
+    @property
+    def primitive_fields(self):
+        fields = [DENSITY, VELOCITY_X, VELOCITY_Y, GAS_PRESSURE]
+        if self._options.two_temperature:
+            fields.append(SECOND_GAS_PRESSURE)
+        return fields
@@ -3,6 +3,8 @@ An n-th order discontinuous Galerkin solver for 1D scalar advection and inviscid
 """
 
 from typing import NamedTuple
+from sailfish.clock import Clock
+from sailfish.fields import Field
 from sailfish.mesh import PlanarCartesianMesh
 from sailfish.solver_base import SolverBase
 from sailfish.kernel.library import Library
@@ -182,6 +184,7 @@ def rhs(physics, uw, cell, dx, uwdot):
 class Options(NamedTuple):
     order: int = 1
     integrator: str = "rk2"
+    reverse_at: float = None
 
 
 class Physics(NamedTuple):
@@ -199,6 +202,16 @@ class Solver(SolverBase):
     - :code:`rk2`: SSP-RK2 of Shu & Osher (1988; Eq. 2.15)
     - :code:`rk3`: SSP-RK3 of Shu & Osher (1988; Eq. 2.18)
     - :code:`rk3-sr02`: four-stage 3rd Order SSP-4RK3 of Spiteri & Ruuth (2002)
+
+    With the option :code:`reverse_at=T`, the integration is time-symmetric:
+    the solution is evolved forward to time T, and then backward, so at time
+    2T it should have returned to the initial data, and its difference from
+    them measures the irreversible numerical dissipation. The reported time
+    keeps increasing; after T, the solution is that of the physical time
+    2T - t. Time is reversed by mirroring the solution in x, since both the
+    advection and Burgers equations evolve the mirror image of a solution
+    backward in time. The solution is always reported in the original
+    orientation, so restarts work on either side of T.
     """
 
     def __init__(
@@ -276,23 +289,48 @@ class Solver(SolverBase):
         else:
             self.conserved_w = solution
 
-        self.t = time
+        self.clock = Clock(time)
         self.mesh = mesh
         self.cell = cell
         self._options = options
         self._physics = physics
+        self.reversed = options.reverse_at is not None and time >= options.reverse_at
+
+        if self.reversed:
+            self.conserved_w = self.mirror(self.conserved_w)
+
+    def mirror(self, uw):
+        """
+        Return the mirror image of DG weights in x, about the domain center.
+
+        The zones are reversed, and the weights of the odd Legendre
+        polynomials change sign.
+        """
+        parity = (-1.0) ** np.arange(uw.shape[-1])
+        return uw[::-1] * parity
 
     @property
     def solution(self):
-        return self.conserved_w
+        if self.reversed:
+            return self.mirror(self.conserved_w)
+        else:
+            return self.conserved_w
+
+    @property
+    def primitive_fields(self):
+        return [Field("u", "advected scalar, Legendre weights on the last axis")]
 
     @property
     def primitive(self):
-        return self.conserved_w[:, 0]
+        return self.solution[:, 0]
 
     @property
     def time(self):
-        return self.t
+        return self.clock.time
+
+    @property
+    def nominal_time(self):
+        return self.clock.nominal_time
 
     @property
     def maximum_cfl(self):
@@ -334,6 +372,28 @@ class Solver(SolverBase):
             return abs(self.conserved_w[:, 0]).max()
 
     def advance(self, dt):
+        t = self.clock.time
+        reverse_at = self._options.reverse_at
+
+        if not self.reversed and reverse_at is not None and reverse_at <= t + dt:
+            if reverse_at > t:
+                self.integrate(reverse_at - t)
+
+            self.conserved_w = self.mirror(self.conserved_w)
+            self.reversed = True
+
+            if reverse_at < t + dt:
+                self.integrate(t + dt - reverse_at)
+        else:
+            self.integrate(dt)
+
+        self.clock.advance(dt)
+
+    def integrate(self, dt):
+        """
+        Update the DG weights over one time step of the chosen integrator.
+        """
+
         def udot(u):
             udot = np.zeros_like(u)
             # rhs(self._physics, u, self.cell, self.mesh.dx, udot)
@@ -753,4 +813,3 @@ class Solver(SolverBase):
         # limit_troubled_cells(u)
 
         self.conserved_w = u
-        self.t += dt
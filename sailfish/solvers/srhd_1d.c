@@ -11,12 +11,9 @@ DESCRIPTION:
 
 // ============================ PHYSICS =======================================
 // ============================================================================
-#define COORDS_CARTESIAN 0
-#define COORDS_SPHERICAL 1
 #define NCONS 4
 #define PLM_THETA 2.0
 #define ADIABATIC_GAMMA (4.0 / 3.0)
-#define NOMINAL_FOUR_PI 1.0
 
 
 // ============================ MATH ==========================================
@@ -349,42 +346,14 @@ PRIVATE void riemann_hllc(const double *pl, const double *pr, double v_face, dou
 
 // ============================ GEOMETRY ======================================
 // ============================================================================
-PRIVATE double face_area(int coords, double x)
-{
-    switch (coords) {
-        case COORDS_CARTESIAN: return 1.0;
-        case COORDS_SPHERICAL: return x * x * NOMINAL_FOUR_PI;
-    }
-    return 0.0;
-}
-
-PRIVATE double cell_volume(int coords, double x0, double x1) 
-{
-    switch (coords) {
-        case COORDS_CARTESIAN: return x1 - x0;
-        case COORDS_SPHERICAL: return (pow(x1, 3.0) - pow(x0, 3.0)) * NOMINAL_FOUR_PI / 3.0;
-    }
-    return 0.0;
-}
+#include "geometry.c"
 
 PRIVATE void geometric_source_terms(int coords, double x0, double x1, const double *prim, double *source)
 {
-    switch (coords) {
-        case COORDS_SPHERICAL: {
-            double p = prim[2];
-            source[0] = 0.0;
-            source[1] = p * (x1 * x1 - x0 * x0) * NOMINAL_FOUR_PI;
-            source[2] = 0.0;
-            source[3] = 0.0;
-            break;
-        }
-        default: {
-            source[0] = 0.0;
-            source[1] = 0.0;
-            source[2] = 0.0;
-            source[3] = 0.0;
-        }   
-    }
+    source[0] = 0.0;
+    source[1] = prim[2] * pressure_source_weight_1d(coords, x0, x1);
+    source[2] = 0.0;
+    source[3] = 0.0;
 }
 
 
@@ -414,7 +383,7 @@ PUBLIC void srhd_1d_primitive_to_conserved(
         double yr = face_positions[i + 1];
         double xl = yl * scale_factor;
         double xr = yr * scale_factor;
-        double dv = cell_volume(coords, xl, xr);
+        double dv = cell_volume_1d(coords, xl, xr);
         primitive_to_conserved(p, u, dv);
     }
 }
@@ -441,7 +410,7 @@ PUBLIC void srhd_1d_conserved_to_primitive(
         double yr = face_positions[i + 1];
         double xl = yl * scale_factor;
         double xr = yr * scale_factor;
-        double dv = cell_volume(coords, xl, xr);
+        double dv = cell_volume_1d(coords, xl, xr);
         conserved_to_primitive(u, p, dv, xl);
     }
 }
@@ -493,7 +462,8 @@ PUBLIC void srhd_1d_advance_rk(
     double dt,              // timestep size
     int fix_i0,             // don't evolve the first zone in the patch
     int fix_i1,             // don't evolve the final zone in the patch
-    int coords)             // :: $ in [0, 1]
+    int coords,             // :: $ in [0, 1]
+    int scalar_advection)   // bit n set: scalar n is advected in primitive form
 {
     int ng = 2; // number of guard zones
 
@@ -540,8 +510,8 @@ PUBLIC void srhd_1d_advance_rk(
             double fli[NCONS];
             double fri[NCONS];
             double sources[NCONS];
-            double dal = face_area(coords, xl);
-            double dar = face_area(coords, xr);
+            double dal = face_area_1d(coords, xl);
+            double dar = face_area_1d(coords, xr);
 
             riemann_hllc(plim, plip, yl * adot, fli);
             riemann_hllc(prim, prip, yr * adot, fri);
@@ -552,6 +522,24 @@ PUBLIC void srhd_1d_advance_rk(
                 uwr[q] = urd[q] + (fli[q] * dal - fri[q] * dar + sources[q]) * dt;
                 uwr[q] = (1.0 - rk_param) * uwr[q] + rk_param * urk[q];
             }
+
+            // Scalars in primitive form: the concentration is advected with
+            // the upwind fluctuations at the faces and the jump inside the
+            // zone, relative to the moving mesh, and then converted to
+            // conserved form with the updated lab-frame density.
+            for (int q = 3; q < NCONS; ++q)
+            {
+                if (scalar_advection & (1 << (q - 3)))
+                {
+                    double v = primitive_to_beta_component(prd) - 0.5 * (yl + yr) * adot;
+                    double dl = plip[q] - plim[q];
+                    double dr = prip[q] - prim[q];
+                    double dxdt = -(max2(v, 0.0) * dl + min2(v, 0.0) * dr + v * gxcc[q]) / (xr - xl);
+                    double x = prd[q] + dxdt * dt;
+                    double x_rk = urk[q] / urk[0];
+                    uwr[q] = uwr[0] * ((1.0 - rk_param) * x + rk_param * x_rk);
+                }
+            }
         }
     }
 }
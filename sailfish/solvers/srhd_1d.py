@@ -12,13 +12,25 @@ The solver configuration is:
 The Python code assumes RK2 time stepping, although coefficients are written
 below for RK1 and low-storage RK3 as well. The C code hard-codes a PLM theta
 value of 2.0.
+
+With the `grow_domain` option, only an inner part of the mesh is updated: the
+zones up to the outermost one which differs from the uniform exterior state,
+plus a margin of `grow_margin` zones. Whenever a zone in the margin departs
+from the exterior state by more than `grow_threshold` (relative to the
+largest exterior value), the active region is extended by the margin. Zones
+outside it keep their initial values, which is exact as long as the exterior
+is static, so this is for explosions and outflows into a uniform medium on a
+fixed mesh.
 """
 
 from logging import getLogger
 from typing import NamedTuple
+from sailfish.clock import Clock
+from sailfish.fields import Field
 from sailfish.kernel.library import Library
-from sailfish.kernel.system import get_array_module, execution_context, num_devices
-from sailfish.subdivide import subdivide, concat_on_host, lazy_reduce
+from sailfish.kernel.system import copy_between_devices, get_array_module
+from sailfish.kernel.system import execution_context, num_devices
+from sailfish.subdivide import concat_on_host, lazy_reduce
 from sailfish.mesh import PlanarCartesianMesh, LogSphericalMesh
 from sailfish.solver_base import SolverBase
 
@@ -40,10 +52,6 @@ BC_DICT = {
     "reflect": BC_REFLECT,
     "fixed": BC_FIXED,
 }
-COORDINATES_DICT = {
-    PlanarCartesianMesh: 0,
-    LogSphericalMesh: 1,
-}
 
 
 def initial_condition(setup, mesh, i0, i1, time, xp):
@@ -55,9 +63,25 @@ def initial_condition(setup, mesh, i0, i1, time, xp):
     return primitive
 
 
+SCALAR_ADVECTION = dict(conservative=0, primitive=1)
+
+# The passive scalar can be advected in conservative form, as the lab-frame
+# scalar mass density D x, or in primitive form, as the concentration x, with
+# an upwind non-conservative update. The conservative form conserves the
+# scalar mass exactly, but x = (D x) / D can overshoot where D is near zero
+# or changes sharply. The primitive form keeps x bounded by its neighboring
+# values (e.g. a mass fraction stays in [0, 1]), but the scalar mass is not
+# conserved. The scalar_advection option is one of the keys above, for all
+# scalars, or a sequence with one key per scalar.
+
+
 class Options(NamedTuple):
     compute_wavespeed: bool = False
     rk_order: int = 2
+    scalar_advection: object = "conservative"
+    grow_domain: bool = False
+    grow_threshold: float = 1e-10
+    grow_margin: int = 16
 
 
 class Physics(NamedTuple):
@@ -81,6 +105,7 @@ class Patch:
         index_range,
         fix_i0,
         fix_i1,
+        scalar_advection,
         lib,
         xp,
         execution_context,
@@ -95,9 +120,10 @@ class Patch:
         self.index_range = index_range
         self.fix_i0 = fix_i0
         self.fix_i1 = fix_i1
+        self.scalar_advection = scalar_advection
         self.num_zones = num_zones = index_range[1] - index_range[0]
-        self.coordinates = coordinates = COORDINATES_DICT[type(mesh)]
-        self.time = self.time0 = time
+        self.coordinates = coordinates = mesh.geometry.kernel_coordinates
+        self.clock = Clock(time)
         self.execution_context = execution_context
 
         try:
@@ -128,59 +154,83 @@ class Patch:
                 conserved_with_guard[ng:-ng] = xp.array(conserved)
 
             self.faces = faces
+            self.num_active = num_zones
             self.wavespeeds = xp.zeros(num_zones)
             self.primitive1 = xp.zeros_like(conserved_with_guard)
             self.conserved0 = conserved_with_guard.copy()
             self.conserved1 = conserved_with_guard.copy()
             self.conserved2 = conserved_with_guard.copy()
 
-    def recompute_primitive(self):
+    def recompute_primitive(self, all_zones=False):
+        n = self.num_zones if all_zones else self.num_active
+
+        if n == 0:
+            return
+
         with self.execution_context:
-            self.lib.srhd_1d_conserved_to_primitive[self.num_zones](
-                self.faces,
-                self.conserved1,
-                self.primitive1,
+            self.lib.srhd_1d_conserved_to_primitive[n](
+                self.faces[: n + 1],
+                self.conserved1[: n + 4],
+                self.primitive1[: n + 4],
                 self.scale_factor,
                 self.coordinates,
             )
 
     def advance_rk(self, rk_param, dt):
-        with self.execution_context:
-            self.lib.srhd_1d_advance_rk[self.num_zones](
-                self.faces,
-                self.conserved0,
-                self.primitive1,
-                self.conserved1,
-                self.conserved2,
-                self.scale_factor_initial,
-                self.scale_factor_derivative,
-                self.time,
-                rk_param,
-                dt,
-                int(self.fix_i0),
-                int(self.fix_i1),
-                self.coordinates,
-            )
-        self.time = self.time0 * rk_param + (self.time + dt) * (1.0 - rk_param)
+        """
+        Update the active zones; the kernels are launched on the leading
+        parts of the arrays, and the first two inactive zones serve as the
+        guard zones. The final zone is only held fixed when it's active.
+        """
+        n = self.num_active
+
+        if n > 0:
+            with self.execution_context:
+                self.lib.srhd_1d_advance_rk[n](
+                    self.faces[: n + 1],
+                    self.conserved0[: n + 4],
+                    self.primitive1[: n + 4],
+                    self.conserved1[: n + 4],
+                    self.conserved2[: n + 4],
+                    self.scale_factor_initial,
+                    self.scale_factor_derivative,
+                    self.time,
+                    rk_param,
+                    dt,
+                    int(self.fix_i0),
+                    int(self.fix_i1 and n == self.num_zones),
+                    self.coordinates,
+                    self.scalar_advection,
+                )
+        self.clock.advance_stage(rk_param, dt)
         self.conserved1, self.conserved2 = self.conserved2, self.conserved1
 
     def maximum_wavespeed(self):
+        n = self.num_active
+
+        if n == 0:
+            return 0.0
+
         self.recompute_primitive()
         with self.execution_context:
-            self.lib.srhd_1d_max_wavespeeds[self.num_zones](
-                self.faces,
-                self.primitive1,
-                self.wavespeeds,
+            self.lib.srhd_1d_max_wavespeeds[n](
+                self.faces[: n + 1],
+                self.primitive1[: n + 4],
+                self.wavespeeds[:n],
                 self.scale_factor_derivative,
             )
-            return float(self.wavespeeds.max())
+            return self.wavespeeds[:n].max()
 
     @property
     def scale_factor(self):
         return self.scale_factor_initial + self.scale_factor_derivative * self.time
 
+    @property
+    def time(self):
+        return self.clock.time
+
     def new_iteration(self):
-        self.time0 = self.time
+        self.clock.new_iteration()
         self.conserved0[...] = self.conserved1[...]
 
     @property
@@ -228,16 +278,36 @@ class Solver(SolverBase):
         except KeyError:
             raise ValueError(f"bad boundary condition {bcl}/{bcr}")
 
+        if type(mesh) not in (PlanarCartesianMesh, LogSphericalMesh):
+            raise ValueError("solver only supports 1D cartesian or spherical meshes")
+
+        if getattr(mesh, "polar_grid", False):
+            raise ValueError("solver does not support a polar grid")
+
         if options.rk_order not in (1, 2, 3):
             raise ValueError("solver only supports rk_order in 1, 2, 3")
 
+        scalar_advection = options.scalar_advection
+
+        if type(scalar_advection) is str:
+            scalar_advection = [scalar_advection] * (NUM_CONS - 3)
+        if len(scalar_advection) != NUM_CONS - 3:
+            raise ValueError(f"scalar_advection needs {NUM_CONS - 3} entries")
+        if any(m not in SCALAR_ADVECTION for m in scalar_advection):
+            raise ValueError(f"scalar_advection must be in {list(SCALAR_ADVECTION)}")
+
+        scalar_advection_bits = sum(
+            SCALAR_ADVECTION[m] << n for n, m in enumerate(scalar_advection)
+        )
+
         logger.info(f"initiate with time={time:0.4f}")
         logger.info(f"subdivide grid over {num_patches} patches")
         logger.info(f"mesh is {mesh}")
         logger.info(f"boundary condition is {bcl}/{bcr}")
+        logger.info(f"scalar advection is {', '.join(scalar_advection)}")
         patches = list()
 
-        for n, (a, b) in enumerate(subdivide(mesh.shape[0], num_patches)):
+        for n, (a, b) in enumerate(mesh.tile(num_patches, NUM_GUARD)):
             fix_i0 = self.boundary_condition[0] == BC_FIXED and n == 0
             fix_i1 = self.boundary_condition[1] == BC_FIXED and n == num_patches - 1
             patch = Patch(
@@ -248,6 +318,7 @@ class Solver(SolverBase):
                 (a, b),
                 fix_i0,
                 fix_i1,
+                scalar_advection_bits,
                 lib,
                 xp,
                 execution_context(mode, device_id=n % num_devices(mode)),
@@ -260,19 +331,108 @@ class Solver(SolverBase):
         self.num_cons = NUM_CONS
         self.xp = xp
         self.patches = patches
+        self.num_active = mesh.shape[0]
+
+        if options.grow_domain:
+            if any(p.scale_factor_derivative != 0.0 for p in patches):
+                raise ValueError("grow_domain needs a mesh which is not moving")
+
+            for patch in patches:
+                patch.recompute_primitive(all_zones=True)
+
+            primitive = self.primitive
+            self.exterior = primitive[-1].copy()
+
+            for patch in patches:
+                with patch.execution_context:
+                    patch.exterior = patch.xp.asarray(self.exterior)
+
+            disturbed = self.disturbed_zones(primitive, self.exterior).nonzero()[0]
+            num_disturbed = int(disturbed.max()) + 1 if len(disturbed) else 0
+            self.set_active_zones(num_disturbed + options.grow_margin)
+
+    def disturbed_zones(self, primitive, exterior):
+        """
+        Return a boolean array of the zones in the given primitive data which
+        differ from the exterior state. The exterior state is given as an
+        array on the same device as the data, so that checking the margin
+        each iteration doesn't copy it there.
+        """
+        scale = float(abs(self.exterior).max()) * self._options.grow_threshold
+        return (abs(primitive - exterior) > scale).any(axis=1)
+
+    def set_active_zones(self, num_active):
+        num_active = min(num_active, self.mesh.shape[0])
+
+        for patch in self.patches:
+            a, b = patch.index_range
+            patch.num_active = min(max(num_active - a, 0), b - a)
+
+        self.num_active = num_active
+        logger.info(f"{num_active} active zones at t={self.time:0.4f}")
+
+    def grow_active_zones(self):
+        """
+        Extend the active region if any of the zones in its outer margin have
+        departed from the exterior state.
+        """
+        ng = self.num_guard
+        n1 = self.num_active
+        n0 = max(n1 - self._options.grow_margin, 0)
+
+        for patch in self.patches:
+            a, b = patch.index_range
+            lo, hi = max(n0, a), min(n1, b)
+
+            if lo < hi:
+                patch.recompute_primitive()
+
+                with patch.execution_context:
+                    window = patch.primitive1[lo - a + ng : hi - a + ng]
+
+                    if bool(self.disturbed_zones(window, patch.exterior).any()):
+                        self.set_active_zones(n1 + self._options.grow_margin)
+                        return
 
     @property
     def solution(self):
         return concat_on_host([p.conserved for p in self.patches], self.num_guard)
 
+    def conserved_array(self):
+        return self.solution
+
+    @property
+    def primitive_fields(self):
+        return [
+            Field("rho", "comoving mass density", "mass / length^3"),
+            Field("ur", "radial four-velocity", "c"),
+            Field("pressure", "gas pressure", "mass c^2 / length^3"),
+            Field("scalar", "passive scalar concentration"),
+        ]
+
     @property
     def primitive(self):
         return concat_on_host([p.primitive for p in self.patches], self.num_guard)
 
+    def primitive_array(self, guard_zones=False):
+        if guard_zones:
+            for patch in self.patches:
+                patch.recompute_primitive()
+            self.set_bc("primitive1")
+        return concat_on_host(
+            [p.primitive for p in self.patches],
+            self.num_guard,
+            outer_guard=guard_zones,
+        )
+
     @property
     def time(self):
         return self.patches[0].time
 
+    @property
+    def nominal_time(self):
+        return self.patches[0].clock.nominal_time
+
     @property
     def options(self):
         return self._options._asdict()
@@ -311,6 +471,9 @@ class Solver(SolverBase):
         for b in bs:
             self.advance_rk(b, dt)
 
+        if self.num_active < self.mesh.shape[0]:
+            self.grow_active_zones()
+
     def advance_rk(self, rk_param, dt):
         for patch in self.patches:
             patch.recompute_primitive()
@@ -338,8 +501,8 @@ class Solver(SolverBase):
         bcl, bcr = self.boundary_condition
 
         with self.patches[patch_index].execution_context:
-            pc[:+ng] = pl[-2 * ng : -ng]
-            pc[-ng:] = pr[+ng : +2 * ng]
+            copy_between_devices(pc[:+ng], pl[-2 * ng : -ng])
+            copy_between_devices(pc[-ng:], pr[+ng : +2 * ng])
 
             def negative_vel(p):
                 return self.xp.asarray([p[0], -p[1], p[2], p[3]])
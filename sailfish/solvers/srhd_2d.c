@@ -425,20 +425,16 @@ PRIVATE void riemann_hllc(const double *pl, const double *pr, double v_face, dou
 
 // ============================ GEOMETRY ======================================
 // ============================================================================
+#include "geometry.c"
+
 PRIVATE double face_area(double r0, double r1, double q0, double q1)
 {
-    double R0 = r0 * sin(q0);
-    double R1 = r1 * sin(q1);
-    double z0 = r0 * cos(q0);
-    double z1 = r1 * cos(q1);
-    double dR = R1 - R0;
-    double dz = z1 - z0;
-    return PI * (R0 + R1) * sqrt(dR * dR + dz * dz);
+    return face_area_2d(COORDS_SPHERICAL_POLAR, r0, r1, q0, q1);
 }
 
 PRIVATE double cell_volume(double r0, double r1, double q0, double q1)
 {
-    return -(r1 * r1 * r1 - r0 * r0 * r0) * (cos(q1) - cos(q0)) * 2.0 * PI / 3.0;
+    return cell_volume_2d(COORDS_SPHERICAL_POLAR, r0, r1, q0, q1);
 }
 
 PRIVATE void geometric_source_terms(double r0, double r1, double q0, double q1, const double *prim, double *source)
@@ -449,16 +445,15 @@ PRIVATE void geometric_source_terms(double r0, double r1, double q0, double q1,
     double pg = prim[3];
     double rhoh = primitive_to_enthalpy_density(prim);
 
-    double dcosq = cos(q1) - cos(q0);
-    double dsinq = sin(q1) - sin(q0);
-    double dr2 = r1 * r1 - r0 * r0;
+    double w[2];
+    metric_source_weights_2d(COORDS_SPHERICAL_POLAR, r0, r1, q0, q1, w);
 
     // The forumulas are A8 and A9 from Zhang & MacFadyen (2006), integrated
     // over the cell volume with finite radial and polar extent.
     // 
     // https://iopscience.iop.org/article/10.1086/500792/pdf
-    double srdot = -PI * dr2 * dcosq * (rhoh * (uq * uq + up * up) + 2 * pg);
-    double sqdot = +PI * dr2 * (dcosq * rhoh * ur * uq + dsinq * (pg + rhoh * up * up));
+    double srdot = -w[0] * (rhoh * (uq * uq + up * up) + 2 * pg);
+    double sqdot = +w[0] * rhoh * ur * uq + w[1] * (pg + rhoh * up * up);
 
     source[0] = 0.0;
     source[1] = srdot;
@@ -487,13 +482,13 @@ PUBLIC void srhd_2d_primitive_to_conserved(
     double polar_extent,
     double scale_factor)     // :: $ >= 0.0
 {
-    int si = NCONS * nj;
-    int sj = NCONS;
+    index_t si = NCONS * nj;
+    index_t sj = NCONS;
     double dq = polar_extent / nj; // polar zone spacing
 
     FOR_EACH_2D(ni, nj)
     {
-        int n = i * si + j * sj;
+        index_t n = i * si + j * sj;
         double *p = &primitive[n];
         double *u = &conserved[n];
         double x0 = face_positions[i];
@@ -522,13 +517,13 @@ PUBLIC void srhd_2d_conserved_to_primitive(
     double scale_factor)     // :: $ >= 0.0
 {
     int ng = 2; // number of guard zones in the radial direction
-    int si = NCONS * nj;
-    int sj = NCONS;
+    index_t si = NCONS * nj;
+    index_t sj = NCONS;
     double dq = polar_extent / nj; // polar zone spacing
 
     FOR_EACH_2D(ni, nj)
     {
-        int n = (i + ng) * si + j * sj;
+        index_t n = (i + ng) * si + j * sj;
         double *p = &primitive[n];
         double *u1 = &conserved1[n];
         double *u2 = &conserved2[n];
@@ -556,8 +551,8 @@ PUBLIC void srhd_2d_max_wavespeeds(
     double adot)             // :: $ >= 0.0
 {
     int ng = 2; // number of guard zones in the radial direction
-    int si = NCONS * nj;
-    int sj = NCONS;
+    index_t si = NCONS * nj;
+    index_t sj = NCONS;
     int ti = nj;
     int tj = 1;
 
@@ -603,8 +598,8 @@ PUBLIC void srhd_2d_advance_rk(
     int num_first_order_zones)
 {
     int ng = 2; // number of guard zones in the radial direction
-    int si = NCONS * nj;
-    int sj = NCONS;
+    index_t si = NCONS * nj;
+    index_t sj = NCONS;
     double dq = polar_extent / nj; // polar zone spacing
 
     FOR_EACH_2D(ni, nj)
@@ -16,9 +16,12 @@ value of 2.0.
 
 from logging import getLogger
 from typing import NamedTuple
+from sailfish.clock import Clock
+from sailfish.fields import Field
 from sailfish.kernel.library import Library
-from sailfish.kernel.system import get_array_module, execution_context, num_devices
-from sailfish.subdivide import subdivide, concat_on_host, lazy_reduce
+from sailfish.kernel.system import copy_between_devices, get_array_module
+from sailfish.kernel.system import execution_context, num_devices
+from sailfish.subdivide import concat_on_host, lazy_reduce
 from sailfish.mesh import PlanarCartesianMesh, LogSphericalMesh
 from sailfish.solver_base import SolverBase
 
@@ -105,7 +108,7 @@ class Patch:
         self.num_first_order_zones = num_first_order_zones
         self.shape = shape = (i1 - i0, mesh.shape[1])  # not including guard zones
         self.polar_extent = mesh.polar_extent
-        self.time = self.time0 = time
+        self.clock = Clock(time)
         self.execution_context = execution_context
 
         try:
@@ -174,7 +177,7 @@ class Patch:
                 self.physics.jet_duration,
                 self.num_first_order_zones,
             )
-        self.time = self.time0 * rk_param + (self.time + dt) * (1.0 - rk_param)
+        self.clock.advance_stage(rk_param, dt)
         self.conserved1, self.conserved2 = self.conserved2, self.conserved1
 
     def maximum_wavespeed(self):
@@ -186,14 +189,18 @@ class Patch:
                 self.wavespeeds,
                 self.scale_factor_derivative,
             )
-            return float(self.wavespeeds.max())
+            return self.wavespeeds.max()
 
     @property
     def scale_factor(self):
         return self.scale_factor_initial + self.scale_factor_derivative * self.time
 
+    @property
+    def time(self):
+        return self.clock.time
+
     def new_iteration(self):
-        self.time0 = self.time
+        self.clock.new_iteration()
         self.conserved0[...] = self.conserved1[...]
 
     @property
@@ -258,7 +265,7 @@ class Solver(SolverBase):
 
         patches = list()
 
-        for n, (a, b) in enumerate(subdivide(mesh.shape[0], num_patches)):
+        for n, (a, b) in enumerate(mesh.tile(num_patches, NUM_GUARD)):
             if n == 0 and bcl == "jet":
                 # introduction of some extra diffusion near the jet inlet is
                 # effective at preventing crashes
@@ -290,14 +297,41 @@ class Solver(SolverBase):
     def solution(self):
         return concat_on_host([p.conserved for p in self.patches], (self.num_guard, 0))
 
+    def conserved_array(self):
+        return self.solution
+
+    @property
+    def primitive_fields(self):
+        return [
+            Field("rho", "comoving mass density", "mass / length^3"),
+            Field("ur", "radial four-velocity", "c"),
+            Field("uq", "polar four-velocity", "c"),
+            Field("pressure", "gas pressure", "mass c^2 / length^3"),
+        ]
+
     @property
     def primitive(self):
         return concat_on_host([p.primitive for p in self.patches], (self.num_guard, 0))
 
+    def primitive_array(self, guard_zones=False):
+        if guard_zones:
+            for patch in self.patches:
+                patch.recompute_primitive()
+            self.set_bc("primitive1")
+        return concat_on_host(
+            [p.primitive for p in self.patches],
+            (self.num_guard, 0),
+            outer_guard=guard_zones,
+        )
+
     @property
     def time(self):
         return self.patches[0].time
 
+    @property
+    def nominal_time(self):
+        return self.patches[0].clock.nominal_time
+
     @property
     def options(self):
         return self._options._asdict()
@@ -369,8 +403,8 @@ class Solver(SolverBase):
         bcl, bcr = self.boundary_condition
 
         with self.patches[patch_index].execution_context:
-            pc[:+ng] = pl[-2 * ng : -ng]
-            pc[-ng:] = pr[+ng : +2 * ng]
+            copy_between_devices(pc[:+ng], pl[-2 * ng : -ng])
+            copy_between_devices(pc[-ng:], pr[+ng : +2 * ng])
 
             def negative_vel(p):
                 return self.xp.asarray([p[0], -p[1], p[2], p[3]])
@@ -0,0 +1,148 @@
+"""
+Accumulate azimuthally averaged radial profiles into spacetime diagrams.
+
+A spacetime diagram is a 2D array with a row per sample time and a column per
+radial bin, which can be plotted directly with `imshow` or `pcolormesh`. Here
+the surface density and the radial mass flux are recorded as the run goes,
+rather than being reconstructed afterwards from many checkpoints. The mass
+flux is :math:`\\dot M(r) = -2 \\pi r \\langle \\Sigma v_r \\rangle`, which is
+positive for inflow.
+
+The disk eccentricity is recorded too, a standard measure of eccentric-disk
+dynamics. Each zone's eccentricity vector is that of a test particle at its
+position and velocity, relative to the barycenter of the point masses and
+orbiting their total mass. The vectors are stored as complex numbers
+:math:`e_x + i e_y`, as a mass-weighted radial profile, and a mass-weighted
+average over the disk inside the outer bin face.
+"""
+
+from math import pi
+from logging import getLogger
+
+logger = getLogger(__name__)
+
+
+def mesh_radii(mesh):
+    """
+    Return an array of the zone center radii of a 2D Cartesian mesh, and the
+    x and y coordinate arrays.
+    """
+    x, y = mesh.cell_center_arrays()
+    return (x**2 + y**2) ** 0.5, x, y
+
+
+def eccentricity_vectors(x, y, vx, vy, point_masses=()):
+    """
+    Return the components of the eccentricity vector of test particles at
+    positions `(x, y)` with velocities `(vx, vy)`.
+
+    The orbits are about the barycenter of the given point masses, and their
+    total mass. With no point masses, they are about a unit mass at the
+    origin, which is the convention of the solvers' `eccentricity_vector`
+    diagnostic.
+    """
+    import numpy as np
+
+    point_masses = [m for m in point_masses if m.mass > 0.0]
+    GM = sum(m.mass for m in point_masses) or 1.0
+    xc = sum(m.mass * m.position_x for m in point_masses) / GM
+    yc = sum(m.mass * m.position_y for m in point_masses) / GM
+    vxc = sum(m.mass * m.velocity_x for m in point_masses) / GM
+    vyc = sum(m.mass * m.velocity_y for m in point_masses) / GM
+
+    x, y, vx, vy = x - xc, y - yc, vx - vxc, vy - vyc
+    r = np.maximum((x * x + y * y) ** 0.5, 1e-12)
+    v_dot_v = vx * vx + vy * vy
+    v_dot_r = vx * x + vy * y
+    ex = (v_dot_v * x - v_dot_r * vx) / GM - x / r
+    ey = (v_dot_v * y - v_dot_r * vy) / GM - y / r
+    return ex, ey
+
+
+class SpacetimeDiagram:
+    """
+    Radial profiles of surface density, mass flux, and eccentricity at a
+    sequence of times.
+
+    The radial bins are evenly spaced, from the origin to the largest radius
+    of a circle inscribed in the mesh. Instances are pickled into
+    checkpoints, so restarted runs keep extending the same diagram.
+    """
+
+    def __init__(self, mesh, num_bins=200):
+        import numpy as np
+
+        outer_radius = min(-mesh.x0, mesh.x1, -mesh.y0, mesh.y1)
+
+        if outer_radius <= 0.0:
+            raise ValueError("spacetime diagrams need a mesh containing the origin")
+
+        self.faces = np.linspace(0.0, outer_radius, num_bins + 1)
+        self.times = list()
+        self.sigma = list()
+        self.mdot = list()
+        self.eccentricity = list()
+        self.mean_eccentricity = list()
+
+    def __setstate__(self, state):
+        # Diagrams from checkpoints written before the eccentricity was
+        # recorded have no eccentricity at their earlier samples.
+        import numpy as np
+
+        self.__dict__.update(state)
+
+        if "eccentricity" not in state:
+            num_bins = len(self.faces) - 1
+            blank = np.full(num_bins, np.nan + 1.0j * np.nan)
+            self.eccentricity = [blank] * len(self.times)
+            self.mean_eccentricity = [blank[0]] * len(self.times)
+
+    def __repr__(self):
+        return f"<SpacetimeDiagram bins={len(self.radii)} samples={len(self.times)}>"
+
+    @property
+    def radii(self):
+        return 0.5 * (self.faces[1:] + self.faces[:-1])
+
+    def add(self, time, mesh, sigma, vx, vy, point_masses=()):
+        """
+        Add the profiles of the given surface density and velocity fields,
+        which are arrays on the 2D mesh. The eccentricity is measured relative
+        to the `point_masses`, a sequence of `PointMass` at the same time.
+        """
+        import numpy as np
+        from sailfish.physics.viscous_disk import azimuthal_average
+
+        r, x, y = mesh_radii(mesh)
+        vr = (x * vx + y * vy) / np.maximum(r, 1e-12)
+        self.times.append(time)
+        self.sigma.append(azimuthal_average(r, sigma, self.faces))
+        self.mdot.append(
+            -2.0 * pi * self.radii * azimuthal_average(r, sigma * vr, self.faces)
+        )
+
+        ex, ey = eccentricity_vectors(x, y, vx, vy, point_masses)
+        ex_profile = azimuthal_average(r, ex, self.faces, weights=sigma)
+        ey_profile = azimuthal_average(r, ey, self.faces, weights=sigma)
+        disk = sigma * (r < self.faces[-1])
+        self.eccentricity.append(ex_profile + 1.0j * ey_profile)
+        self.mean_eccentricity.append(((ex + 1.0j * ey) * disk).sum() / disk.sum())
+
+    def arrays(self):
+        """
+        Return a dictionary of the sample times, bin radii and faces, and the
+        `(num_times, num_bins)` arrays of surface density, mass flux, and
+        complex eccentricity, and the mass-weighted mean eccentricity at each
+        time.
+        """
+        import numpy as np
+
+        return dict(
+            time=np.array(self.times),
+            radius=self.radii,
+            faces=self.faces,
+            sigma=np.array(self.sigma),
+            mdot=np.array(self.mdot),
+            eccentricity=np.array(self.eccentricity).reshape(-1, len(self.radii)),
+            mean_eccentricity=np.array(self.mean_eccentricity, dtype=complex),
+        )
@@ -10,6 +10,134 @@ def to_host(a):
         return a
 
 
+class DeferredReduction:
+    """
+    The result of a reduction over patches, which may still be computing.
+
+    The reduction is started by the `launches` callables, in the same way as
+    for `lazy_reduce`. For device arrays, an event is recorded on each
+    device's current stream, and nothing waits for the result until `value`
+    is called; then only the streams that computed it are synchronized. For
+    host arrays the result is ready immediately. The value is multiplied by
+    `scale`.
+    """
+
+    def __init__(self, reduction, block, launches, contexts, scale=1.0):
+        self.reduction = reduction
+        self.block = block
+        self.scale = scale
+        self.tokens = []
+        self.contexts = list(contexts)
+        self.events = []
+
+        for launch, context in zip(launches, self.contexts):
+            with context:
+                token = launch()
+                self.tokens.append(token)
+
+                if hasattr(token, "get"):
+                    from cupy.cuda import Event
+
+                    event = Event(block=False, disable_timing=True)
+                    event.record()
+                    self.events.append(event)
+
+    def __repr__(self):
+        return f"<DeferredReduction ready={self.ready}>"
+
+    @property
+    def ready(self):
+        """
+        Whether the value can be read without waiting.
+        """
+        return all(event.done for event in self.events)
+
+    def value(self):
+        """
+        Wait for the reduction to complete, and return its value.
+        """
+        results = []
+
+        for token, context in zip(self.tokens, self.contexts):
+            with context:
+                results.append(self.block(token))
+
+        self.events = []
+        return self.reduction(results) * self.scale
+
+
+class DeferredCopy:
+    """
+    Arrays being copied to the host, which may still be in progress.
+
+    Each device array is first copied on its device's current stream, so the
+    kernels launched after this can go on to overwrite it, and the copy is
+    then downloaded to pinned host memory on the corresponding one of
+    `streams`, overlapped with those kernels. Nothing waits for the download
+    until `value` is called, which returns `finish` applied to the list of
+    host arrays. Host arrays are copied right away.
+    """
+
+    def __init__(self, arrays, contexts, streams, finish):
+        self.finish = finish
+        self.hosts = []
+        self.staging = []
+        self.events = []
+
+        for array, context, stream in zip(arrays, contexts, streams):
+            with context:
+                if not hasattr(array, "get"):
+                    self.hosts.append(array.copy())
+                    continue
+
+                from cupy.cuda import Event
+                from cupyx import empty_pinned
+
+                staging = array.copy()
+                staged = Event(block=False, disable_timing=True)
+                staged.record()
+                host = empty_pinned(array.shape, array.dtype)
+                stream.wait_event(staged)
+                staging.get(stream=stream, out=host, blocking=False)
+                done = Event(block=False, disable_timing=True)
+                done.record(stream)
+                self.hosts.append(host)
+                self.staging.append(staging)
+                self.events.append(done)
+
+    def __repr__(self):
+        return f"<DeferredCopy ready={self.ready}>"
+
+    @property
+    def ready(self):
+        """
+        Whether the value can be read without waiting.
+        """
+        return all(event.done for event in self.events)
+
+    def value(self):
+        """
+        Wait for the copies to complete, and return their value.
+        """
+        for event in self.events:
+            event.synchronize()
+
+        self.events = []
+        self.staging = []
+        return self.finish(self.hosts)
+
+
+def resolve(item):
+    """
+    Return the value of a deferred reduction or copy, or `item` if it's not
+    one.
+    """
+    if isinstance(item, (DeferredReduction, DeferredCopy)):
+        return item.value()
+    else:
+        return item
+
+
 def lazy_reduce(reduction, block, launches, contexts):
     """
     Applies a reduction over a sequence of parallelizable device operations.
@@ -32,6 +160,75 @@ def lazy_reduce(reduction, block, launches, contexts):
     return reduction(results)
 
 
+class PatchReductions:
+    """
+    Mixin for patches with `execution_context` and arrays of zones with two
+    guard zones on each side, which reduces them on the patch's device.
+    """
+
+    def reduce(self, reduction, field=None, array="primitive1"):
+        """
+        Return a min, max, or sum of one field over the patch interior zones.
+
+        The reduction is evaluated on the patch's device, and the result is a
+        scalar on that device; pass it to `float` to block and copy it to the
+        host. The `array` argument names one of the patch arrays, e.g.
+        `primitive1` or `wavespeeds`, and `field` is an index to its last axis,
+        or `None` if it has no field axis.
+        """
+        if reduction not in ("min", "max", "sum"):
+            raise ValueError(f"reduction must be min, max, or sum, got {reduction}")
+
+        ng = 2  # number of guard zones
+        with self.execution_context:
+            data = getattr(self, array)[ng:-ng, ng:-ng]
+            if field is not None:
+                data = data[..., field]
+            return getattr(data, reduction)()
+
+    def reduce_min(self, field=None, array="primitive1"):
+        return self.reduce("min", field, array)
+
+    def reduce_max(self, field=None, array="primitive1"):
+        return self.reduce("max", field, array)
+
+    def reduce_sum(self, field=None, array="primitive1"):
+        return self.reduce("sum", field, array)
+
+
+class SolverReductions:
+    """
+    Mixin for solvers with a list of `PatchReductions` patches, which reduces
+    them over the whole domain.
+    """
+
+    def reduce(self, reduction, field=None, array="primitive1"):
+        """
+        Return a min, max, or sum of one field over the whole domain.
+
+        Each patch is reduced on its own device (see `PatchReductions.reduce`),
+        and only the per-patch results are copied to the host.
+        """
+        return lazy_reduce(
+            dict(min=min, max=max, sum=sum)[reduction],
+            float,
+            (
+                lambda patch=patch: patch.reduce(reduction, field, array)
+                for patch in self.patches
+            ),
+            (patch.execution_context for patch in self.patches),
+        )
+
+    def reduce_min(self, field=None, array="primitive1"):
+        return self.reduce("min", field, array)
+
+    def reduce_max(self, field=None, array="primitive1"):
+        return self.reduce("max", field, array)
+
+    def reduce_sum(self, field=None, array="primitive1"):
+        return self.reduce("sum", field, array)
+
+
 def partition(elements, num_parts):
     """
     Equitably divide the given number of elements into `num_parts` partitions.
@@ -60,7 +257,55 @@ def subdivide(interval, num_parts):
         a += n
 
 
-def concat_on_host(arrays: list, num_guard=None, rank=None):
+def interpolate_bilinear(data, i, j, wx, wy):
+    """
+    Return 2D zone data of shape `(ni, nj, nq)` interpolated on the stencils
+    given by `PlanarCartesian2DMesh.interpolation_stencil`, as an array of
+    shape `(n, nq)`. The data and stencil arrays must be on the same device.
+    """
+    wx = wx[:, None]
+    wy = wy[:, None]
+    return (
+        (1.0 - wx) * (1.0 - wy) * data[i, j]
+        + wx * (1.0 - wy) * data[i + 1, j]
+        + (1.0 - wx) * wy * data[i, j + 1]
+        + wx * wy * data[i + 1, j + 1]
+    )
+
+
+def sample_on_host(patches, num_guard, stencil, num_fields):
+    """
+    Interpolate the primitive data of patches at points, and return it as a
+    host array of shape `(n, num_fields)`.
+
+    The patches are subdivided along the first axis, like their solver's
+    mesh, and must have filled guard zones, which hold the data of their
+    neighbors. Each point is interpolated on the device of the patch which
+    has the lower-left zone of its stencil, so only the stencil indexes and
+    the results are copied between the host and the devices.
+    """
+    import numpy as np
+
+    i, j, wx, wy = stencil
+    result = np.zeros((len(i), num_fields))
+    ng = num_guard
+    i0 = 0
+
+    for patch in patches:
+        i1 = i0 + patch.shape[0]
+        inside = (i >= i0) & (i < i1)
+
+        if inside.any():
+            with patch.execution_context:
+                xp = patch.xp
+                args = [xp.asarray(a[inside]) for a in (i - i0 + ng, j + ng, wx, wy)]
+                result[inside] = to_host(interpolate_bilinear(patch.primitive, *args))
+        i0 = i1
+
+    return result
+
+
+def concat_on_host(arrays: list, num_guard=None, rank=None, outer_guard=False):
     """
     Concatenate a list of arrays, which may be allocated on different devices.
 
@@ -71,6 +316,11 @@ def concat_on_host(arrays: list, num_guard=None, rank=None):
     or 2). If rank is None, it is assumed that only the final axis contains
     fields, so rank is inferred to be len(array.shape) - 1. If several
     trailing axes represent more fields, then rank must be given explicitly.
+
+    Guard zones are removed from each array, unless `outer_guard` is `True`,
+    in which case the guard zones on the outer edges of the concatenated
+    array are kept (the leading guard zones of the first array, the trailing
+    guard zones of the last array, and the guard zones on the second axis).
     """
     import numpy as np
 
@@ -83,40 +333,44 @@ def concat_on_host(arrays: list, num_guard=None, rank=None):
                 y = x
         return x
 
+    def trim(ng, keep_lower, keep_upper):
+        return slice(0 if keep_lower else ng, None if keep_upper or ng == 0 else -ng)
+
+    def length(s, n):
+        return len(range(*s.indices(n)))
+
     if rank is None:
         rank = len(arrays[0].shape) - 1
 
     if rank == 1:
-        # TODO: 1d case
-        ng = num_guard or 0
-        ni = sum(a.shape[0] - 2 * ng for a in arrays)
-        nq = all_equal(a.shape[1:] for a in arrays)
-        si = slice(ng, -ng) if ng > 0 else slice(None)
-        result = np.zeros((ni,) + nq)
-        i = 0
-        for array in arrays:
-            a = i
-            b = i + array.shape[0] - 2 * ng
-            i += b - a
-            result[a:b] = to_host(array[si])
-        return result
-
-    if rank == 2:
+        ngi, ngj = num_guard or 0, 0
+    elif rank == 2:
         ngi, ngj = num_guard or (0, 0)
-        ni = sum(a.shape[0] - 2 * ngi for a in arrays)
-        nj = all_equal(a.shape[1] - 2 * ngj for a in arrays)
-        nq = all_equal(a.shape[2:] for a in arrays)
-        si = slice(ngi, -ngi) if ngi > 0 else slice(None)
-        sj = slice(ngj, -ngj) if ngj > 0 else slice(None)
-        result = np.zeros((ni, nj) + nq)
+    else:
+        raise ValueError(f"concatenation for arrays of rank {rank} not supported")
 
-        i = 0
-        for array in arrays:
-            a = i
-            b = i + array.shape[0] - 2 * ngi
-            i += b - a
-            result[a:b] = to_host(array[si, sj])
+    last = len(arrays) - 1
+    si = [
+        trim(ngi, outer_guard and n == 0, outer_guard and n == last)
+        for n in range(last + 1)
+    ]
+    sj = trim(ngj, outer_guard, outer_guard)
+    sizes = [length(s, a.shape[0]) for s, a in zip(si, arrays)]
+
+    if rank == 1:
+        nq = all_equal(a.shape[1:] for a in arrays)
+        result = np.zeros((sum(sizes),) + nq)
+    else:
+        nj = all_equal(length(sj, a.shape[1]) for a in arrays)
+        nq = all_equal(a.shape[2:] for a in arrays)
+        result = np.zeros((sum(sizes), nj) + nq)
 
-        return result
+    i = 0
+    for array, s, n in zip(arrays, si, sizes):
+        if rank == 1:
+            result[i : i + n] = to_host(array[s])
+        else:
+            result[i : i + n] = to_host(array[s, sj])
+        i += n
 
-    raise ValueError(f"concatenation for arrays of rank {rank} not supported")
+    return result
@@ -0,0 +1,63 @@
+"""
+Functions to measure the mirror-symmetry error of 2D planar solutions.
+
+A problem which is initially symmetric under reflections through the x and y
+axes, and under exchange of the axes, should stay that way to round-off. A
+kernel which treats the x and y directions differently (for example due to
+the ordering of the directional sweeps, or a transposed stencil index) breaks
+that symmetry, and the symmetry error grows from round-off to order unity.
+"""
+
+from sailfish.mesh import PlanarCartesian2DMesh
+
+
+def check_symmetric_mesh(mesh):
+    """
+    Raise a `ValueError` unless the mesh is planar and centered on the origin.
+    """
+    if type(mesh) is not PlanarCartesian2DMesh:
+        raise ValueError(f"symmetry checks need a planar cartesian mesh, got {mesh}")
+    if mesh.x0 != -mesh.x1 or mesh.y0 != -mesh.y1:
+        raise ValueError("symmetry checks need a mesh centered on the origin")
+
+
+def symmetry_errors(primitive):
+    """
+    Return the largest difference between a solution and its mirror images.
+
+    The `primitive` argument is an array of shape `(ni, nj, nq)`, without
+    guard zones, with the x and y velocities in fields 1 and 2. The result is
+    a dictionary with keys `x` (reflection through the y-axis), `y`
+    (reflection through the x-axis), and `xy` (exchange of the x and y axes;
+    only present when the patch is square). Each error is normalized by the
+    largest absolute value of the respective field.
+    """
+    import numpy as np
+
+    p = np.asarray(primitive)
+    scale = np.maximum(np.max(np.abs(p), axis=(0, 1)), 1e-300)
+
+    mx = p[::-1, :, :].copy()
+    mx[..., 1] *= -1.0
+
+    my = p[:, ::-1, :].copy()
+    my[..., 2] *= -1.0
+
+    errors = dict(
+        x=np.max(np.abs(p - mx) / scale),
+        y=np.max(np.abs(p - my) / scale),
+    )
+
+    if p.shape[0] == p.shape[1]:
+        mxy = p.transpose(1, 0, 2).copy()
+        mxy[..., [1, 2]] = mxy[..., [2, 1]]
+        errors["xy"] = np.max(np.abs(p - mxy) / scale)
+
+    return errors
+
+
+def max_symmetry_error(primitive):
+    """
+    Return the largest of the symmetry errors returned by `symmetry_errors`.
+    """
+    return max(symmetry_errors(primitive).values())
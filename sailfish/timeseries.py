@@ -0,0 +1,288 @@
+"""
+A compact, append-only binary file format for time series.
+
+A time series file starts with a schema, and is followed by fixed-size
+records, one per sample. The schema is the 4-byte magic string `SFTS`, a
+4-byte version number and header length (little-endian), and a JSON header
+with the name, units, and type of each column. Each record holds the values
+of the columns as little-endian doubles (complex columns take two, the real
+part first), and ends with a CRC-32 checksum of those bytes. If the run had
+code units, the header also has their `unit_conversions`, from
+`sailfish.units.conversion_table`.
+
+Records are appended as the run goes, so the file is readable while the run
+is in progress, and after it's crashed. A record which was partly written, or
+which fails its checksum, marks the end of the readable data; it's dropped by
+readers, and overwritten when the file is next appended to.
+"""
+
+import csv
+import json
+import os
+import struct
+import zlib
+from typing import NamedTuple
+
+MAGIC = b"SFTS"
+VERSION = 1
+
+
+class Column(NamedTuple):
+    """
+    Describes one column of a time series file.
+    """
+
+    name: str
+    units: str = ""
+    dtype: str = "float64"
+
+    @property
+    def num_values(self):
+        return 2 if self.dtype == "complex128" else 1
+
+
+def sample_columns(names, units, sample):
+    """
+    Return a list of columns for the given names and units (either may be
+    empty), with the type of each column taken from the values in a sample.
+    """
+    columns = []
+
+    for n, x in enumerate(sample):
+        name = names[n] if n < len(names) else f"column{n}"
+        unit = units[n] if n < len(units) else ""
+        dtype = "complex128" if isinstance(x, complex) else "float64"
+        columns.append(Column(name, unit, dtype))
+
+    return columns
+
+
+def encode_header(columns, unit_conversions=None):
+    header = dict(columns=[c._asdict() for c in columns])
+
+    if unit_conversions is not None:
+        header["unit_conversions"] = unit_conversions
+
+    header = json.dumps(header).encode("utf-8")
+    return MAGIC + struct.pack("<II", VERSION, len(header)) + header
+
+
+def encode_record(columns, sample):
+    values = []
+
+    for column, x in zip(columns, sample):
+        if column.num_values == 2:
+            values.extend([x.real, x.imag])
+        else:
+            values.append(float(x.real))
+
+    data = struct.pack(f"<{len(values)}d", *values)
+    return data + struct.pack("<I", zlib.crc32(data))
+
+
+def record_size(columns):
+    return 8 * sum(c.num_values for c in columns) + 4
+
+
+def read_header(f):
+    """
+    Read the schema from the start of an open file, and return the list of
+    columns and the size of the schema in bytes.
+    """
+    header, size = read_schema(f)
+    return [Column(**c) for c in header["columns"]], size
+
+
+def read_schema(f):
+    """
+    Read the schema from the start of an open file, and return the decoded
+    JSON header and the size of the schema in bytes.
+    """
+    start = f.read(12)
+
+    if len(start) < 12 or start[:4] != MAGIC:
+        raise ValueError(f"{f.name} is not a time series file")
+
+    version, size = struct.unpack("<II", start[4:])
+
+    if version != VERSION:
+        raise ValueError(f"{f.name} has unsupported time series version {version}")
+
+    return json.loads(f.read(size).decode("utf-8")), 12 + size
+
+
+def read_records(f, columns):
+    """
+    Generate the samples in an open file, positioned at the first record,
+    until the end of the file or the first incomplete or corrupt record.
+    """
+    size = record_size(columns)
+    count = size // 8
+
+    while True:
+        record = f.read(size)
+
+        if len(record) < size:
+            return
+
+        (checksum,) = struct.unpack("<I", record[-4:])
+
+        if zlib.crc32(record[:-4]) != checksum:
+            return
+
+        values = iter(struct.unpack(f"<{count}d", record[:-4]))
+        yield [
+            complex(next(values), next(values)) if c.num_values == 2 else next(values)
+            for c in columns
+        ]
+
+
+class TimeseriesWriter:
+    """
+    Appends samples to a time series file.
+
+    If the file already exists, its columns must match the given ones, and
+    any incomplete or corrupt records at its end are removed. If
+    `num_records` is given, records beyond that number are also removed, so
+    a run restarted from a checkpoint continues the file from the samples
+    it has, rather than duplicating them. The `unit_conversions` are written
+    to the header of a new file.
+    """
+
+    def __init__(self, filename, columns, num_records=None, unit_conversions=None):
+        self.filename = filename
+        self.columns = list(columns)
+
+        if os.path.exists(filename) and os.path.getsize(filename) > 0:
+            with open(filename, "rb") as f:
+                existing, offset = read_header(f)
+                valid = sum(1 for _ in read_records(f, existing))
+
+            if existing != self.columns:
+                raise ValueError(
+                    f"time series file {filename} has columns "
+                    f"{[c.name for c in existing]}, "
+                    f"expected {[c.name for c in self.columns]}"
+                )
+
+            if num_records is not None:
+                valid = min(valid, num_records)
+
+            with open(filename, "r+b") as f:
+                f.truncate(offset + valid * record_size(self.columns))
+
+            self.num_records = valid
+        else:
+            with open(filename, "wb") as f:
+                f.write(encode_header(self.columns, unit_conversions))
+
+            self.num_records = 0
+
+    def append(self, samples):
+        """
+        Append a sequence of samples to the file. Each sample is a sequence
+        of values, one per column.
+        """
+        data = b"".join(encode_record(self.columns, s) for s in samples)
+
+        with open(self.filename, "ab") as f:
+            f.write(data)
+
+        self.num_records += len(samples)
+
+
+class TimeseriesFile:
+    """
+    The columns and samples of a time series file, read into memory.
+    """
+
+    def __init__(self, filename):
+        with open(filename, "rb") as f:
+            header, _ = read_schema(f)
+            self.columns = [Column(**c) for c in header["columns"]]
+            self.unit_conversions = header.get("unit_conversions")
+            self.samples = list(read_records(f, self.columns))
+
+    def __len__(self):
+        return len(self.samples)
+
+    def __repr__(self):
+        return f"<TimeseriesFile columns={len(self.columns)} samples={len(self)}>"
+
+    @property
+    def names(self):
+        return [c.name for c in self.columns]
+
+    @property
+    def units(self):
+        return [c.units for c in self.columns]
+
+    def column(self, name, system=None):
+        """
+        Return a numpy array of the values in the column with the given name,
+        in code units, or converted to the "cgs" or "si" `system` with the
+        file's unit conversions.
+        """
+        import numpy as np
+        from sailfish.units import convert
+
+        try:
+            n = self.names.index(name)
+        except ValueError:
+            raise KeyError(f"no column named {name}, the columns are {self.names}")
+
+        values = np.array([s[n] for s in self.samples], dtype=self.columns[n].dtype)
+
+        if system is None:
+            return values
+
+        return convert(self.unit_conversions, name, values, system, "diagnostics")
+
+    def arrays(self):
+        """
+        Return a dictionary of numpy arrays, keyed by column name.
+        """
+        return {name: self.column(name) for name in self.names}
+
+
+def read_timeseries(filename):
+    """
+    Read a time series file, and return a `TimeseriesFile`.
+    """
+    return TimeseriesFile(filename)
+
+
+def to_csv(filename, csv_filename=None):
+    """
+    Convert a time series file to CSV, and return the name of the CSV file.
+
+    The first row has the column names, with the units in brackets. Complex
+    columns are split in two, with `.real` and `.imag` added to the name. By
+    default the CSV file is written next to the time series file, with its
+    extension replaced by `.csv`.
+    """
+    ts = read_timeseries(filename)
+
+    if csv_filename is None:
+        csv_filename = os.path.splitext(filename)[0] + ".csv"
+
+    header = []
+
+    for c in ts.columns:
+        units = f" [{c.units}]" if c.units else ""
+        if c.num_values == 2:
+            header.extend([f"{c.name}.real{units}", f"{c.name}.imag{units}"])
+        else:
+            header.append(f"{c.name}{units}")
+
+    with open(csv_filename, "w", newline="") as f:
+        writer = csv.writer(f)
+        writer.writerow(header)
+
+        for sample in ts.samples:
+            row = []
+            for c, x in zip(ts.columns, sample):
+                row.extend([x.real, x.imag] if c.num_values == 2 else [x])
+            writer.writerow(row)
+
+    return csv_filename
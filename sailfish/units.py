@@ -0,0 +1,234 @@
+"""
+Physical constants, and the conversion of the output fields and diagnostics
+from code units to cgs and SI units.
+
+The solvers work in code units of length, mass, and time, which are given
+physical values with the driver's `--units` option, e.g. `--units
+length=1au:mass=1msun`. Any two of the three units fix the third with the
+gravitational constant equal to one, which is what the circumbinary setups
+assume, so there the time unit is `1 / 2 pi` of the binary orbital period.
+
+With units configured, each checkpoint, snapshot, and time series file has a
+`unit_conversions` item, made by `conversion_table`: the cgs values of the
+code units, the constants used, e.g. `msun`, and for each primitive field and
+diagnostic, its units in terms of the code units, and the factors and units
+which convert it to cgs and to SI. The readers in `sailfish.products` and
+`sailfish.timeseries` return fields and columns in either system from it, so
+analysis scripts don't each carry their own conversions. Diagnostics whose
+units are unknown (an empty units string) are left out of the table.
+"""
+
+from math import sqrt
+from typing import NamedTuple
+
+"""
+Physical constants and units, in cgs.
+"""
+CONSTANTS = dict(
+    G=6.67430e-8,
+    c=2.99792458e10,
+    msun=1.98841e33,
+    rsun=6.957e10,
+    au=1.495978707e13,
+    pc=3.0856775814913673e18,
+    yr=3.15576e7,
+    day=8.64e4,
+)
+
+LENGTH_UNITS = dict(cm=1.0, m=1e2, km=1e5, rsun=CONSTANTS["rsun"])
+LENGTH_UNITS.update(au=CONSTANTS["au"], pc=CONSTANTS["pc"])
+MASS_UNITS = dict(g=1.0, kg=1e3, msun=CONSTANTS["msun"])
+TIME_UNITS = dict(s=1.0, day=CONSTANTS["day"], yr=CONSTANTS["yr"])
+
+"""
+The names of the units in the units strings of fields and diagnostics, and
+the names and cgs values of the cgs and SI base units.
+"""
+BASE_UNITS = ("mass", "length", "time", "c")
+CGS_UNITS = dict(mass=("g", 1.0), length=("cm", 1.0), time=("s", 1.0))
+SI_UNITS = dict(mass=("kg", 1e3), length=("m", 1e2), time=("s", 1.0))
+
+
+def parse_quantity(text, units):
+    """
+    Return the cgs value of a quantity like `1au` or `2.5e13`, whose unit
+    name, if any, is one of the keys of `units`.
+    """
+    for name, value in sorted(units.items(), key=lambda item: -len(item[0])):
+        if text.endswith(name):
+            return float(text[: -len(name)] or 1.0) * value
+
+    return float(text)
+
+
+def parse_units(text):
+    """
+    Return the exponents of mass, length, time, and the speed of light `c`
+    in a units string, like `mass length^2 / time^2`, as a dict. The string
+    `1` is dimensionless, and `reference time` is the setup's reference time
+    scale, which counts as time.
+    """
+    exponents = dict.fromkeys(BASE_UNITS, 0.0)
+    sign = 1.0
+
+    for token in text.replace("reference time", "time").split():
+        if token == "/":
+            sign = -1.0
+            continue
+
+        if token == "1":
+            continue
+
+        name, _, power = token.partition("^")
+
+        if name not in exponents:
+            raise ValueError(f"unknown unit {name} in '{text}'")
+
+        exponents[name] += sign * float(power or 1.0)
+
+    return exponents
+
+
+def units_label(exponents, system):
+    """
+    Return the units of a quantity with the given exponents in a system of
+    base units, like `g cm^2 / s^2`.
+    """
+
+    def factor(name, power):
+        unit = system[name][0]
+        return unit if power == 1.0 else f"{unit}^{power:g}"
+
+    above = [factor(n, p) for n, p in exponents.items() if p > 0.0]
+    below = [factor(n, -p) for n, p in exponents.items() if p < 0.0]
+    label = " ".join(above) or "1"
+
+    return f"{label} / {' '.join(below)}" if below else label
+
+
+class CodeUnits(NamedTuple):
+    """
+    The cgs values of the code units of length, mass, and time.
+    """
+
+    length: float = 1.0
+    mass: float = 1.0
+    time: float = 1.0
+
+    @classmethod
+    def from_str(cls, text):
+        """
+        Parse the code units from colon-separated `length=L`, `mass=M`, and
+        `time=T` items, e.g. `length=1au:mass=1msun`, with values in cgs or
+        with a unit name appended. If one of them is left out, it's the one
+        which makes the gravitational constant equal to one.
+        """
+        parsers = dict(length=LENGTH_UNITS, mass=MASS_UNITS, time=TIME_UNITS)
+        values = dict()
+
+        for item in text.split(":"):
+            key, _, value = item.partition("=")
+
+            if key not in parsers or not value or key in values:
+                raise ValueError(f"bad code units item '{item}' in '{text}'")
+
+            values[key] = parse_quantity(value, parsers[key])
+
+        if len(values) < 2:
+            raise ValueError("code units need at least two of length, mass, time")
+
+        if any(v <= 0.0 for v in values.values()):
+            raise ValueError("code units must be positive")
+
+        G = CONSTANTS["G"]
+
+        if "time" not in values:
+            values["time"] = sqrt(values["length"] ** 3 / (G * values["mass"]))
+        elif "mass" not in values:
+            values["mass"] = values["length"] ** 3 / (G * values["time"] ** 2)
+        elif "length" not in values:
+            values["length"] = (G * values["mass"] * values["time"] ** 2) ** (1 / 3)
+
+        return cls(**values)
+
+    def __str__(self):
+        return (
+            f"length={self.length:.6g} cm, mass={self.mass:.6g} g, "
+            f"time={self.time:.6g} s"
+        )
+
+    def conversion(self, units, reference_time=1.0):
+        """
+        Return a dict with the code units string of a quantity, and the
+        factors and units which convert it to cgs and SI. The
+        `reference_time` is the setup's reference time scale, in code units.
+        Velocities in units of `c`, as in the relativistic solvers, are
+        converted with the speed of light rather than the code units.
+        """
+        exponents = parse_units(units)
+        c = exponents.pop("c")
+        cgs = CONSTANTS["c"] ** c
+
+        for name, power in exponents.items():
+            cgs *= getattr(self, name) ** power
+
+        exponents["length"] += c
+        exponents["time"] -= c
+
+        if "reference time" in units:
+            cgs *= reference_time
+
+        si = cgs
+
+        for name, power in exponents.items():
+            si /= SI_UNITS[name][1] ** power
+
+        return dict(
+            units=units,
+            cgs=cgs,
+            cgs_units=units_label(exponents, CGS_UNITS),
+            si=si,
+            si_units=units_label(exponents, SI_UNITS),
+        )
+
+
+def conversion_table(code_units, fields, diagnostics, reference_time=1.0):
+    """
+    Return the unit conversions written to output files: the cgs values of
+    the code units, the constants, and the conversion of each field and
+    diagnostic. The `fields` and `diagnostics` are sequences of `(name,
+    units)` pairs.
+    """
+
+    def table(items):
+        return {
+            name: code_units.conversion(units, reference_time)
+            for name, units in items
+            if units
+        }
+
+    return dict(
+        code_units=code_units._asdict(),
+        constants=dict(CONSTANTS),
+        fields=table(fields),
+        diagnostics=table(diagnostics),
+    )
+
+
+def convert(table, name, values, system="cgs", kind="fields"):
+    """
+    Return values of a field or diagnostic, given by name, converted with a
+    table from `conversion_table` to the "cgs" or "si" system. Raise a
+    `KeyError` if the table has no conversion for it.
+    """
+    if table is None:
+        raise KeyError("the file has no unit conversions; the run had no units")
+
+    if system not in ("cgs", "si"):
+        raise ValueError(f"unknown unit system {system}, expected cgs or si")
+
+    try:
+        return values * table[kind][name][system]
+    except KeyError:
+        raise KeyError(f"no unit conversion for {name} in the {kind}")
+
@@ -0,0 +1,225 @@
+"""
+Upload checkpoints and snapshots to object storage as they're written.
+
+On cloud machines, and on clusters with a small scratch quota, the output of
+a long run is better kept somewhere other than the local disk. With the
+driver's `--upload URL` option, each checkpoint and snapshot file is handed
+to an upload hook once it's written, and uploaded in a background thread, so
+the run doesn't wait on the network. With `--upload-prune`, the local copy of
+each file is deleted once it's uploaded, except for the newest checkpoint,
+which is kept for restarts until a newer one is uploaded, and the milestone
+checkpoints (chkpt.orbit.*.pk), which are never pruned. Failed uploads are
+logged, and their local copies are kept. Uploads which are pending when the
+driver exits with an error are abandoned.
+
+An upload hook is a subclass of `UploadHook`, chosen by the scheme of the
+URL. The hooks here copy the files to a directory (a plain path, or a
+`file://` URL), or upload them to Amazon S3 (`s3://bucket/prefix`, with the
+`boto3` package) or Google Cloud Storage (`gs://bucket/prefix`, with the
+`google-cloud-storage` package). The cloud packages are optional, and only
+imported by their hooks, which use the credentials of their usual
+configuration. Hooks defined outside the main codebase are loaded from the
+modules listed under `uploads` in the `extensions` section of the .sailfish
+file, like setups.
+"""
+
+import os
+import posixpath
+import shutil
+from abc import ABC, abstractmethod
+from logging import getLogger
+from queue import Queue
+from threading import Thread
+
+logger = getLogger(__name__)
+
+
+class UploadError(Exception):
+    """Something went wrong during upload configuration"""
+
+
+def url_scheme(url):
+    """
+    Return the scheme of a URL, or "file" for a plain path.
+    """
+    return url.split("://", 1)[0] if "://" in url else "file"
+
+
+def bucket_and_prefix(url):
+    """
+    Return the bucket name and key prefix of an object storage URL, e.g.
+    `("bucket", "runs/a")` for `s3://bucket/runs/a`.
+    """
+    bucket, _, prefix = url.split("://", 1)[1].partition("/")
+
+    if not bucket:
+        raise UploadError(f"no bucket in upload URL {url}")
+
+    return bucket, prefix.strip("/")
+
+
+class UploadHook(ABC):
+    """
+    Base class for post-write hooks, which upload files to a URL.
+    """
+
+    scheme = None
+
+    def __init__(self, url):
+        self.url = url
+
+    @classmethod
+    def find_hook_class(cls, url):
+        """
+        Finds the hook class for the scheme of a URL. If there is none, an
+        `UploadError` exception is raised.
+        """
+        scheme = url_scheme(url)
+        match = lambda s: s.scheme == scheme
+        try:
+            return next(filter(match, cls.__subclasses__()))
+        except StopIteration:
+            schemes = ", ".join(s.scheme for s in cls.__subclasses__())
+            raise UploadError(f"no upload hook for {scheme}, the schemes are {schemes}")
+
+    @abstractmethod
+    def upload(self, filename, key):
+        """
+        Upload a local file under the given key, its base name, below the
+        hook's URL. Failures are raised as exceptions of any type.
+        """
+        pass
+
+
+class DirectoryUpload(UploadHook):
+    """
+    Copies files to a directory, e.g. on a mounted network file system.
+    """
+
+    scheme = "file"
+
+    def __init__(self, url):
+        super().__init__(url)
+        self.directory = url[len("file://") :] if url.startswith("file://") else url
+
+    def upload(self, filename, key):
+        # The copy is renamed into place, so the directory never has a
+        # partial file under the final name.
+        os.makedirs(self.directory, exist_ok=True)
+        target = os.path.join(self.directory, key)
+        shutil.copyfile(filename, target + ".part")
+        os.replace(target + ".part", target)
+
+
+class S3Upload(UploadHook):
+    """
+    Uploads files to an Amazon S3 bucket, with the `boto3` package.
+    """
+
+    scheme = "s3"
+
+    def __init__(self, url):
+        super().__init__(url)
+
+        try:
+            import boto3
+        except ImportError:
+            raise UploadError("uploads to s3:// URLs need the boto3 package")
+
+        self.bucket, self.prefix = bucket_and_prefix(url)
+        self.client = boto3.client("s3")
+
+    def upload(self, filename, key):
+        key = posixpath.join(self.prefix, key)
+        self.client.upload_file(filename, self.bucket, key)
+
+
+class GoogleCloudUpload(UploadHook):
+    """
+    Uploads files to a Google Cloud Storage bucket, with the
+    `google-cloud-storage` package.
+    """
+
+    scheme = "gs"
+
+    def __init__(self, url):
+        super().__init__(url)
+
+        try:
+            from google.cloud import storage
+        except ImportError:
+            raise UploadError("uploads to gs:// URLs need google-cloud-storage")
+
+        bucket, self.prefix = bucket_and_prefix(url)
+        self.bucket = storage.Client().bucket(bucket)
+
+    def upload(self, filename, key):
+        key = posixpath.join(self.prefix, key)
+        self.bucket.blob(key).upload_from_filename(filename)
+
+
+class Uploader:
+    """
+    Uploads files with a hook, in a background thread and in the order they
+    were submitted, and deletes their local copies if `prune` is true.
+    """
+
+    def __init__(self, hook, prune=False):
+        self.hook = hook
+        self.prune = prune
+        self.num_failed = 0
+        self.kept_checkpoint = None
+        self.queue = Queue()
+        self.thread = Thread(target=self.work, name="upload", daemon=True)
+        self.thread.start()
+
+    def submit(self, filenames):
+        """
+        Queue files to be uploaded.
+        """
+        for filename in filenames:
+            self.queue.put(filename)
+
+    def close(self):
+        """
+        Wait for the queued uploads to finish, and return the number of files
+        which failed to upload.
+        """
+        self.queue.put(None)
+        self.thread.join()
+        return self.num_failed
+
+    def work(self):
+        while True:
+            filename = self.queue.get()
+
+            if filename is None:
+                return
+
+            try:
+                self.hook.upload(filename, os.path.basename(filename))
+            except Exception as e:
+                logger.warning(f"upload of {filename} failed: {e}")
+                self.num_failed += 1
+                continue
+
+            logger.info(f"uploaded {filename} to {self.hook.url}")
+
+            if self.prune:
+                self.prune_local(filename)
+
+    def prune_local(self, filename):
+        # Milestones are the branching points of later runs, so they're kept.
+        if os.path.basename(filename).startswith("chkpt.orbit."):
+            return
+
+        # The newest uploaded checkpoint is kept until a newer one replaces
+        # it, so the run can still be restarted from the local directory.
+        if os.path.basename(filename).startswith("chkpt."):
+            filename, self.kept_checkpoint = self.kept_checkpoint, filename
+
+            if filename is None:
+                return
+
+        os.remove(filename)
+        logger.info(f"removed local copy {filename}")
@@ -0,0 +1,103 @@
+"""
+Compile the CPU kernels of a solver to a WebAssembly module, for browser demos.
+
+The kernels are built in the serial CPU execution mode, which needs only the
+C math library, with emscripten (`emcc` must be on the path). The output is a
+pair of files, `<solver>.js` and `<solver>.wasm`, in the current directory.
+The JS file is an ES6 module whose default export is a factory returning a
+promise of the compiled module; each public kernel is exported with a leading
+underscore, along with `_malloc` and `_free`. The kernels take pointers into
+the module heap, so a page allocates its arrays with `_malloc`, and fills and
+reads them through `HEAPF64`:
+
+.. code-block:: javascript
+
+    import createModule from "./cbdiso_2d.js";
+
+    const lib = await createModule();
+    const primitive = lib._malloc(8 * (ni + 4) * (nj + 4) * 3);
+    const conserved = lib._malloc(8 * (ni + 4) * (nj + 4) * 3);
+    lib._cbdiso_2d_primitive_to_conserved(ni, nj, primitive, conserved);
+
+Only the kernels are compiled: the time stepping loop, boundary conditions,
+and setups are in Python, and a page has to drive the kernels itself, the way
+the solver module does.
+
+Usage: python3 scripts/build_wasm.py cbdiso_2d [emcc flags...]
+"""
+
+from pathlib import Path
+from shutil import which
+from subprocess import run
+from sys import path, argv, exit
+from tempfile import TemporaryDirectory
+
+path.append(str(Path(__file__).parent.parent))
+
+from sailfish.kernel.library import KERNEL_LIB_HEADER, expand_includes, specialize
+from sailfish.kernel.parse_api import parse_api
+
+
+def wasm_source(solver_name, constants=dict()):
+    """
+    Return the kernel code of a solver as it's compiled, with the header, the
+    included files expanded, and the given scalar arguments specialized, as
+    for a `Library`.
+    """
+    solver_source = Path(__file__).parent.parent / "sailfish" / "solvers"
+    source_file = solver_source / f"{solver_name}.c"
+
+    if not source_file.exists():
+        raise ValueError(f"solver {solver_name} has no C kernels")
+
+    code = expand_includes(source_file.read_text())
+    return f"{KERNEL_LIB_HEADER} {specialize(code, constants)}"
+
+
+def build_wasm(solver_name, extra_args=[]):
+    code = wasm_source(solver_name)
+
+    if which("emcc") is None:
+        raise ValueError("emcc (emscripten) is needed to build WebAssembly modules")
+
+    exported = ["_malloc", "_free"] + [f"_{name}" for name in parse_api(code)]
+
+    with TemporaryDirectory() as tmp:
+        c_file = Path(tmp) / f"{solver_name}.c"
+        c_file.write_text(code)
+        args = [
+            "emcc",
+            str(c_file),
+            "-O3",
+            "-D",
+            "EXEC_MODE=0",
+            "-s",
+            "MODULARIZE=1",
+            "-s",
+            "EXPORT_ES6=1",
+            "-s",
+            "ALLOW_MEMORY_GROWTH=1",
+            "-s",
+            f"EXPORTED_FUNCTIONS={','.join(exported)}",
+            "-s",
+            "EXPORTED_RUNTIME_METHODS=HEAPF64",
+            "-o",
+            f"{solver_name}.js",
+        ] + extra_args
+        run(args, check=True)
+
+    print(f"write {solver_name}.js and {solver_name}.wasm")
+
+    for name in exported[2:]:
+        print(f"+-- {name}")
+
+
+if __name__ == "__main__":
+    if len(argv) < 2:
+        print("No solver given, use 'python3 build_wasm.py cbdiso_2d'")
+        exit(1)
+    try:
+        build_wasm(argv[1], argv[2:])
+    except ValueError as e:
+        print(e)
+        exit(1)
@@ -5,11 +5,11 @@ This script is just here for backwards compatibility. Its official location is
 now bin/sailfish.
 """
 
-from sys import path
+from sys import path, exit
 from pathlib import Path
 
 path.append(str(Path(__file__).parent.parent))
 
 from sailfish.driver import main
 
-main()
+exit(main())
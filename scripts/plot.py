@@ -1,20 +1,20 @@
 import argparse
-import pickle
 import sys
 
 sys.path.insert(1, ".")
 
 
 def load_checkpoint(filename, require_solver=None):
-    with open(filename, "rb") as file:
-        chkpt = pickle.load(file)
-
-        if require_solver is not None and chkpt["solver"] != require_solver:
-            raise ValueError(
-                f"checkpoint is from a run with solver {chkpt['solver']}, "
-                f"expected {require_solver}"
-            )
-        return chkpt
+    from sailfish.products import load_product
+
+    chkpt = load_product(filename)
+
+    if require_solver is not None and chkpt["solver"] != require_solver:
+        raise ValueError(
+            f"checkpoint is from a run with solver {chkpt['solver']}, "
+            f"expected {require_solver}"
+        )
+    return chkpt
 
 
 def main_srhd_1d():
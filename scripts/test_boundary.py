@@ -0,0 +1,147 @@
+"""
+Check the guard zones of the outflow boundary conditions of the 2D solvers.
+
+Zeroth-order outflow copies the outermost zone, and first-order outflow
+continues a linear profile exactly, unless that would make the density or
+pressure negative. The clamped modes zero the normal velocity in guard zones
+only where it points into the domain, and each edge can have its own mode.
+The characteristic boundary of cbdiso_2d keeps the outgoing Riemann invariant
+of the outermost zone and the incoming one of the far field.
+"""
+
+import sys
+
+sys.path.insert(1, ".")
+
+NUM_GUARD = 2
+
+
+def linear_patch(ni=6, nj=5, slope=0.1):
+    import numpy as np
+
+    ng = NUM_GUARD
+    primitive = np.zeros([ni + 2 * ng, nj + 2 * ng, 4])
+    i = np.arange(ni + 2 * ng)[:, None] - ng
+
+    primitive[..., 0] = 1.0 + slope * i
+    primitive[..., 1] = 0.2 * (i - 2.5)
+    primitive[..., 2] = 0.3
+    primitive[..., 3] = 2.0 + slope * i
+    return primitive
+
+
+def fill(primitive, axis, side, mode):
+    from sailfish.boundary import boundary_condition, fill_guard_zones
+
+    fill_guard_zones(primitive, axis, side, boundary_condition(mode), NUM_GUARD)
+
+
+def test_extrapolation_order():
+    import numpy as np
+
+    ng = NUM_GUARD
+    expected = linear_patch()
+    zeroth, first = linear_patch(), linear_patch()
+
+    for side in (0, 1):
+        fill(zeroth, 0, side, "outflow")
+        fill(first, 0, side, "outflow-linear")
+
+    assert np.allclose(zeroth[:ng], zeroth[ng])
+    assert np.allclose(zeroth[-ng:], zeroth[-ng - 1])
+    assert np.allclose(first, expected)
+    print("pass: extrapolation order")
+
+
+def test_positive_extrapolation():
+    import numpy as np
+
+    ng = NUM_GUARD
+    primitive = linear_patch(slope=0.9)
+    fill(primitive, 0, 0, "outflow-linear")
+
+    assert np.all(primitive[:ng, :, 0] > 0.0)
+    assert np.allclose(primitive[ng - 2, :, 0], primitive[ng, :, 0])
+    assert np.allclose(primitive[ng - 1, :, 0], primitive[ng, :, 0] - 0.9)
+    assert np.allclose(primitive[:ng, :, 1], [[-0.9], [-0.7]])
+    print("pass: positive extrapolation")
+
+
+def test_velocity_clamp():
+    import numpy as np
+
+    ng = NUM_GUARD
+    primitive = linear_patch()
+    fill(primitive, 0, 0, "diode")
+    fill(primitive, 0, 1, "outflow-linear-clamped")
+    fill(primitive, 1, 0, "outflow-clamped")
+    fill(primitive, 1, 1, "outflow-clamped")
+
+    assert np.allclose(primitive[:ng, :, 1], -0.5)
+    assert np.allclose(primitive[-ng:, :, 1], [[0.7], [0.9]])
+    assert np.allclose(primitive[:, :ng, 2], 0.0)
+    assert np.allclose(primitive[:, -ng:, 2], 0.3)
+    assert np.allclose(primitive[ng:-ng, ng:-ng, 2], 0.3)
+
+    primitive = linear_patch()
+    primitive[..., 1] *= -1.0
+    fill(primitive, 0, 0, "outflow-clamped")
+    fill(primitive, 0, 1, "outflow-clamped")
+
+    assert np.allclose(primitive[:ng, :, 1], 0.0)
+    assert np.allclose(primitive[-ng:, :, 1], 0.0)
+    print("pass: velocity clamp")
+
+
+def test_characteristic_invariants():
+    import numpy as np
+    from sailfish.solvers.cbdiso_2d import characteristic_guard_zones
+
+    cs2 = np.full([NUM_GUARD, 3], 0.25)
+    far_field = np.zeros([NUM_GUARD, 3, 4])
+    far_field[...] = [2.0, -0.1, 0.3, 0.0]
+
+    for side, sign in [(0, -1.0), (1, 1.0)]:
+        edge = np.array([[1.0, sign * 0.2, -0.4, 1.0]] * 3)
+        guard = characteristic_guard_zones(edge, far_field, cs2, 0, side, np)
+        vn = sign * guard[..., 1]
+        w_out = vn + 0.5 * np.log(guard[..., 0])
+        w_in = vn - 0.5 * np.log(guard[..., 0])
+
+        assert np.allclose(w_out, 0.2)
+        assert np.allclose(w_in, -sign * 0.1 - 0.5 * np.log(2.0))
+        assert np.allclose(guard[..., 2:], [-0.4, 1.0])
+
+    guard = characteristic_guard_zones(far_field[0], far_field, cs2, 1, 1, np)
+    assert np.allclose(guard, far_field)
+    print("pass: characteristic invariants")
+
+
+def test_characteristic_supersonic():
+    import numpy as np
+    from sailfish.solvers.cbdiso_2d import characteristic_guard_zones
+
+    cs2 = np.full([NUM_GUARD, 3], 0.25)
+    far_field = np.zeros([NUM_GUARD, 3, 3])
+    far_field[...] = [2.0, 0.0, 0.1]
+
+    outflow = np.array([[1.0, 0.7, 0.3]] * 3)
+    inflow = np.array([[1.0, -0.7, 0.3]] * 3)
+    out_guard = characteristic_guard_zones(outflow, far_field, cs2, 0, 1, np)
+    in_guard = characteristic_guard_zones(inflow, far_field, cs2, 0, 1, np)
+
+    assert np.allclose(out_guard, outflow)
+    assert np.allclose(in_guard, far_field)
+    print("pass: characteristic supersonic")
+
+
+def main():
+    test_extrapolation_order()
+    test_positive_extrapolation()
+    test_velocity_clamp()
+    test_characteristic_invariants()
+    test_characteristic_supersonic()
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,137 @@
+"""
+Check the face reconstruction and the Riemann solvers of the cbdgam_2d
+solver against hand-computed values on single zones: the limiter slope
+follows the `plm_theta` parameter, the HLLC flux of a uniform state is the
+physical flux, and a stationary contact has a pure pressure flux with HLLC,
+where HLLE diffuses it. The solver rejects unknown Riemann solvers and
+limiter parameters outside [1, 2].
+"""
+
+import sys
+import logging
+
+sys.path.insert(1, ".")
+
+probes = """
+PUBLIC void probe_plm_gradient(
+    int n,
+    double *yl, // :: $.shape == (n, 4)
+    double *y0, // :: $.shape == (n, 4)
+    double *yr, // :: $.shape == (n, 4)
+    double *g,  // :: $.shape == (n, 4)
+    double theta)
+{
+    FOR_EACH_1D(n)
+    {
+        plm_gradient(&yl[4 * i], &y0[4 * i], &yr[4 * i], &g[4 * i], theta);
+    }
+}
+
+PUBLIC void probe_riemann_flux(
+    int n,
+    double *pl,   // :: $.shape == (n, 4)
+    double *pr,   // :: $.shape == (n, 4)
+    double *flux, // :: $.shape == (n, 4)
+    double cs2,
+    int direction,
+    int riemann_solver,
+    double gamma_law_index)
+{
+    FOR_EACH_1D(n)
+    {
+        riemann_flux(&pl[4 * i], &pr[4 * i], &flux[4 * i], cs2, direction, 0, riemann_solver, gamma_law_index);
+    }
+}
+"""
+
+GAMMA = 5.0 / 3.0
+
+
+def riemann_flux(lib, xp, to_host, pl, pr, direction, solver):
+    from sailfish.kernel.fixtures import zone_fixture
+    from sailfish.solvers.cbdgam_2d import RIEMANN_SOLVERS
+
+    cs2 = GAMMA * pl[3] / pl[0]
+    pl = zone_fixture(pl, xp=xp)
+    pr = zone_fixture(pr, xp=xp)
+    flux = xp.zeros_like(pl)
+    args = (cs2, direction, RIEMANN_SOLVERS[solver], GAMMA)
+    lib.probe_riemann_flux[pl.shape[:1]](pl, pr, flux, *args)
+    return to_host(flux)[0]
+
+
+def test_plm_gradient(lib, xp, to_host):
+    from sailfish.kernel.fixtures import assert_close, zone_fixture
+
+    # The slope of the profile (0, 1, 4) is the smallest of theta times the
+    # one-sided differences (1 and 3) and the centered one (2), so theta up to
+    # 2 sets it; a linear profile and an extremum don't depend on theta.
+    yl = zone_fixture([0.0, 0.0, 0.0, 1.0], xp=xp)
+    y0 = zone_fixture([1.0, 1.0, 1.0, 2.0], xp=xp)
+    yr = zone_fixture([4.0, 2.0, 0.0, 1.0], xp=xp)
+
+    for theta in [1.0, 1.5, 2.0]:
+        g = xp.zeros_like(y0)
+        lib.probe_plm_gradient[g.shape[:1]](yl, y0, yr, g, theta)
+        assert_close(to_host(g)[0], [theta, 1.0, 0.0, 0.0], f"plm theta={theta}")
+
+    print("pass: plm gradient")
+
+
+def test_riemann_hllc(lib, xp, to_host):
+    from sailfish.kernel.fixtures import assert_close
+
+    # Identical states give the physical flux, here with p / (gamma - 1) =
+    # 1.5 and a kinetic energy density of 0.145.
+    p = [1.0, 0.5, 0.2, 1.0]
+    energy_flux = 0.5 * (1.5 + 0.145 + 1.0)
+    flux = riemann_flux(lib, xp, to_host, p, p, 0, "hllc")
+    assert_close(flux, [0.5, 1.25, 0.1, energy_flux], "hllc flux, uniform state x")
+    flux = riemann_flux(lib, xp, to_host, p, p, 1, "hllc")
+    assert_close(flux, [0.2, 0.1, 1.04, 0.4 * energy_flux], "hllc flux, uniform y")
+
+    # A stationary contact, with a density jump and a shear across it in
+    # pressure balance, is resolved exactly by HLLC: there's no mass flux,
+    # and only the pressure pushes on the face. HLLE diffuses the contact.
+    pl = [1.0, 0.0, 0.3, 1.0]
+    pr = [0.125, 0.0, -0.3, 1.0]
+    flux = riemann_flux(lib, xp, to_host, pl, pr, 0, "hllc")
+    assert_close(flux, [0.0, 1.0, 0.0, 0.0], "hllc flux, stationary contact")
+    flux = riemann_flux(lib, xp, to_host, pl, pr, 0, "hlle")
+    assert flux[0] > 0.0, "the hlle mass flux diffuses the contact"
+
+    print("pass: riemann hllc")
+
+
+def test_rejects_bad_options():
+    from sailfish.solvers.cbdgam_2d import Solver
+
+    for options in [dict(riemann_solver="roe"), dict(plm_theta=2.5)]:
+        try:
+            Solver(physics=dict(), options=options)
+        except ValueError:
+            pass
+        else:
+            raise AssertionError(f"bad options {options} were accepted")
+
+    print("pass: rejects bad options")
+
+
+def main():
+    from sailfish.kernel.fixtures import library_with_probes, parse_test_args
+    from sailfish.kernel.system import get_array_module
+    from sailfish.subdivide import to_host
+
+    args = parse_test_args(modes=["cpu", "omp", "gpu", "checked"])
+    logging.basicConfig(level=logging.INFO)
+    xp = get_array_module(args.mode)
+
+    lib = library_with_probes("sailfish/solvers/cbdgam_2d.c", probes, mode=args.mode)
+
+    test_plm_gradient(lib, xp, to_host)
+    test_riemann_hllc(lib, xp, to_host)
+    test_rejects_bad_options()
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,1161 @@
+"""
+Check the individual kernel phases of the cbdiso_2d solver against
+hand-computed values, on single zones and on tiny patches, and check that a
+solver whose solution is replaced continues exactly like a restarted one,
+that a reset solver repeats its first solve, and that the outflow boundary
+modes fill the guard zones on the domain edges.
+"""
+
+import sys
+import logging
+
+sys.path.insert(1, ".")
+
+probes = """
+PUBLIC void probe_plm_gradient(
+    int n,
+    double *yl, // :: $.shape == (n, 3)
+    double *y0, // :: $.shape == (n, 3)
+    double *yr, // :: $.shape == (n, 3)
+    double *g)  // :: $.shape == (n, 3)
+{
+    FOR_EACH_1D(n)
+    {
+        plm_gradient(&yl[3 * i], &y0[3 * i], &yr[3 * i], &g[3 * i]);
+    }
+}
+
+PUBLIC void probe_riemann_hlle(
+    int n,
+    double *pl,   // :: $.shape == (n, 3)
+    double *pr,   // :: $.shape == (n, 3)
+    double *flux, // :: $.shape == (n, 3)
+    double cs2,
+    int direction)
+{
+    FOR_EACH_1D(n)
+    {
+        riemann_hlle(&pl[3 * i], &pr[3 * i], &flux[3 * i], cs2, direction, 0);
+    }
+}
+
+PUBLIC void probe_point_mass_source_term(
+    int n,
+    double *prim,       // :: $.shape == (n, 3)
+    double *delta_cons, // :: $.shape == (n, 3)
+    double x1,
+    double y1,
+    double dt,
+    double x0,
+    double y0,
+    double mass,
+    double softening_length,
+    int softening_model,
+    double sink_rate,
+    double sink_radius,
+    int sink_model)
+{
+    struct PointMass m = {x0, y0, 0.0, 0.0, mass, softening_length, softening_model, sink_rate, sink_radius, sink_model};
+
+    FOR_EACH_1D(n)
+    {
+        point_mass_source_term(&m, x1, y1, dt, &prim[3 * i], &delta_cons[3 * i], &delta_cons[3 * i]);
+    }
+}
+
+PUBLIC void probe_relaxation_time_step(
+    int n,
+    double *rate,   // :: $.shape == (n,)
+    double *result, // :: $.shape == (n,)
+    double dt,
+    int update)
+{
+    FOR_EACH_1D(n)
+    {
+        result[i] = relaxation_time_step(rate[i], dt, update);
+    }
+}
+"""
+
+
+def test_plm_gradient(lib, xp, to_host):
+    from sailfish.kernel.fixtures import assert_close, zone_fixture
+
+    # A linear profile has a slope of one per zone, and an extremum is
+    # flattened by the limiter.
+    yl = zone_fixture([0.0, 0.0, 0.0], xp=xp)
+    y0 = zone_fixture([1.0, 1.0, 1.0], xp=xp)
+    yr = zone_fixture([2.0, 0.0, 1.5], xp=xp)
+    g = xp.zeros_like(y0)
+    lib.probe_plm_gradient[g.shape[:1]](yl, y0, yr, g)
+    assert_close(to_host(g)[0], [1.0, 0.0, 0.75], "plm gradient")
+    print("pass: plm gradient")
+
+
+def test_riemann_hlle(lib, xp, to_host):
+    from sailfish.kernel.fixtures import assert_close, zone_fixture
+
+    # Identical states give the physical flux.
+    p = zone_fixture([1.0, 0.5, 0.0], xp=xp)
+    flux = xp.zeros_like(p)
+    lib.probe_riemann_hlle[p.shape[:1]](p, p, flux, 1.0, 0)
+    assert_close(to_host(flux)[0], [0.5, 1.25, 0.0], "hlle flux, uniform state")
+
+    # A density jump at rest, with cs = 1 so that am = -1 and ap = 1, gives
+    # F = (fl + fr + ul - ur) / 2.
+    pl = zone_fixture([2.0, 0.0, 0.0], xp=xp)
+    pr = zone_fixture([1.0, 0.0, 0.0], xp=xp)
+    lib.probe_riemann_hlle[pl.shape[:1]](pl, pr, flux, 1.0, 0)
+    assert_close(to_host(flux)[0], [0.5, 1.5, 0.0], "hlle flux, density jump x")
+    lib.probe_riemann_hlle[pl.shape[:1]](pl, pr, flux, 1.0, 1)
+    assert_close(to_host(flux)[0], [0.5, 0.0, 1.5], "hlle flux, density jump y")
+    print("pass: riemann hlle")
+
+
+def test_point_mass_source_term(lib, xp, to_host):
+    import math
+    from sailfish.kernel.fixtures import assert_close, zone_fixture
+
+    # A unit mass at the origin pulls on a zone of density 2 at (1, 0) with
+    # force -2 x-hat; over dt = 0.5 that's an impulse of -1.
+    prim = zone_fixture([2.0, 0.0, 0.0], xp=xp)
+    du = xp.zeros_like(prim)
+    args = (1.0, 0.0, 0.5, 0.0, 0.0, 1.0, 0.0, 0)
+    lib.probe_point_mass_source_term[prim.shape[:1]](prim, du, *args, 0.0, 0.0, 0)
+    assert_close(to_host(du)[0], [0.0, -1.0, 0.0], "point mass gravity")
+
+    # A force-free sink with rate 10 and radius 1 removes mass at the rate
+    # 10 exp(-1) sigma from a zone one sink radius away.
+    du = xp.zeros_like(prim)
+    lib.probe_point_mass_source_term[prim.shape[:1]](prim, du, *args, 10.0, 1.0, 3)
+    expected = [-0.5 * 10.0 * math.exp(-1.0) * 2.0, -1.0, 0.0]
+    assert_close(to_host(du)[0], expected, "point mass force-free sink")
+    print("pass: point mass source term")
+
+
+def test_softening_models(lib, xp, to_host):
+    import numpy as np
+    from sailfish.kernel.fixtures import assert_close, zone_fixture
+    from sailfish.physics.circumbinary import PointMass, SofteningModel
+
+    # The impulse over dt = 1 on a zone of unit density at distance r is
+    # -r g(r) x-hat. The spline kernel is Newtonian beyond 2.8 softening
+    # lengths, and otherwise agrees with the point mass's acceleration.
+    prim = zone_fixture([1.0, 0.0, 0.0], xp=xp)
+
+    for model in SofteningModel:
+        m = PointMass(mass=1.0, softening_length=0.2, softening_model=model)
+
+        for r in [0.05, 0.15, 0.4, 1.0]:
+            du = xp.zeros_like(prim)
+            args = (r, 0.0, 1.0, 0.0, 0.0, 1.0, 0.2, model.value, 0.0, 0.0, 0)
+            lib.probe_point_mass_source_term[prim.shape[:1]](prim, du, *args)
+            expected = -r * float(m.acceleration(np.array(r * r), np))
+            assert_close(to_host(du)[0, 1], expected, f"{model.name.lower()} force")
+
+            if model != SofteningModel.PLUMMER and r > 0.56:
+                assert_close(expected, -1.0 / r**2, "newtonian far field")
+
+    print("pass: softening models")
+
+
+def test_relaxation_time_step(lib, xp, to_host):
+    import math
+    from sailfish.kernel.fixtures import assert_close
+
+    # With rate * dt = 10, the explicit update overshoots the relaxed state
+    # nine times over; 20 sub-steps each halve the distance to it, and the
+    # exponential update decays it by exp(-10). A zero rate is left alone.
+    rate = xp.array([0.0, 100.0])
+    result = xp.zeros(2)
+
+    for update, expected in [
+        (0, 0.1),
+        (1, (1.0 - 0.5**20) / 100.0),
+        (2, (1.0 - math.exp(-10.0)) / 100.0),
+    ]:
+        lib.probe_relaxation_time_step[(2,)](rate, result, 0.1, update)
+        assert_close(to_host(result), [0.1, expected], f"relaxation update {update}")
+
+    print("pass: relaxation time step")
+
+
+def advance_patch(
+    lib, xp, primitive, zone_mask=None, frame_rotation=0.0, invalid_zones=None
+):
+    """
+    Take a Runge-Kutta stage with dt = 0.01 on a 2x2 patch with cs = 1, and
+    no point masses, buffer, or viscosity. Return the updated primitive data
+    and the x and y face fluxes. The kernel's flags of invalid zones are
+    written to `invalid_zones` if it's given.
+    """
+    conserved = xp.zeros_like(primitive)
+    primitive_wr = xp.zeros_like(primitive)
+    face_flux_x = xp.zeros([3, 2, 3])
+    face_flux_y = xp.zeros([2, 3, 3])
+    no_mass = (0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0, 0.0, 0.0, 0)
+
+    lib.cbdiso_2d_primitive_to_conserved[(2, 2)](primitive, conserved)
+    lib.cbdiso_2d_advance_rk[(2, 2)](
+        1.0,
+        2.0,
+        1.0,
+        2.0,
+        conserved,
+        primitive,
+        primitive_wr,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        0,
+        0,
+        xp.zeros(1),
+        xp.zeros(1),
+        0,
+        *no_mass,
+        *no_mass,
+        0,
+        1.0,
+        1.0,
+        1,
+        1.0,
+        1,
+        xp.zeros(1),
+        0.0,
+        0.0,
+        frame_rotation,
+        0.0,
+        0.01,
+        1e12,
+        0.0,
+        invalid_zones if invalid_zones is not None else xp.zeros([2, 2]),
+        0,
+        1,
+        face_flux_x,
+        face_flux_y,
+        0,
+        xp.zeros(1),
+        int(zone_mask is not None),
+        zone_mask if zone_mask is not None else xp.zeros(1),
+    )
+    return primitive_wr, face_flux_x, face_flux_y
+
+
+def test_uniform_patch(lib, xp, to_host):
+    from sailfish.kernel.fixtures import assert_close, patch_fixture
+
+    # A uniform flow on a 2x2 patch (6x6 with guard zones) is unchanged by a
+    # Runge-Kutta stage when there are no point masses and no buffer.
+    primitive = patch_fixture(
+        lambda x, y: (1.0, 0.1, 0.2), (2, 2), ((1.0, 2.0), (1.0, 2.0)), 3, xp=xp
+    )
+    primitive_wr, face_flux_x, face_flux_y = advance_patch(lib, xp, primitive)
+    interior = to_host(primitive_wr)[2:-2, 2:-2]
+    assert_close(interior, to_host(primitive)[2:-2, 2:-2], "uniform patch advance")
+
+    # The recorded face fluxes are the physical fluxes times dt = 0.01, with
+    # cs = 1.
+    expected_x = [0.1 * 0.01, (0.1 * 0.1 + 1.0) * 0.01, 0.1 * 0.2 * 0.01]
+    expected_y = [0.2 * 0.01, 0.2 * 0.1 * 0.01, (0.2 * 0.2 + 1.0) * 0.01]
+    assert_close(to_host(face_flux_x), [[expected_x] * 2] * 3, "uniform x-face fluxes")
+    assert_close(to_host(face_flux_y), [[expected_y] * 3] * 2, "uniform y-face fluxes")
+    print("pass: uniform patch")
+
+
+def test_rotating_frame(lib, xp, to_host):
+    from sailfish.kernel.fixtures import assert_close, patch_fixture
+
+    # On a rotating mesh, a uniform flow is accelerated only by the Coriolis
+    # and centrifugal forces, here with omega = 2.
+    primitive = patch_fixture(
+        lambda x, y: (1.0, 0.1, 0.2), (2, 2), ((1.0, 2.0), (1.0, 2.0)), 3, xp=xp
+    )
+    primitive_wr, _, _ = advance_patch(lib, xp, primitive, frame_rotation=2.0)
+    interior = to_host(primitive_wr)[2:-2, 2:-2]
+
+    for i, x in enumerate([1.25, 1.75]):
+        for j, y in enumerate([1.25, 1.75]):
+            vx = 0.1 + 0.01 * (4.0 * x + 4.0 * 0.2)
+            vy = 0.2 + 0.01 * (4.0 * y - 4.0 * 0.1)
+            assert_close(interior[i, j], [1.0, vx, vy], "rotating frame forces")
+
+    print("pass: rotating frame")
+
+
+def test_masked_zones(lib, xp, to_host):
+    from sailfish.kernel.fixtures import assert_close, patch_fixture
+
+    # With a density gradient every zone changes, except the masked one,
+    # whose primitive data is copied over as is. The uniform flux through
+    # the faces between it and its neighbors is still recorded.
+    primitive = patch_fixture(
+        lambda x, y: (x + y, 0.0, 0.0), (2, 2), ((1.0, 2.0), (1.0, 2.0)), 3, xp=xp
+    )
+    zone_mask = xp.array([[1.0, 1.0], [1.0, 0.0]])
+    primitive_wr, face_flux_x, face_flux_y = advance_patch(
+        lib, xp, primitive, zone_mask
+    )
+    before = to_host(primitive)[2:-2, 2:-2]
+    after = to_host(primitive_wr)[2:-2, 2:-2]
+    assert_close(after[1, 1], before[1, 1], "masked zone is unchanged")
+    assert after[0, 0, 0] != before[0, 0, 0], "unmasked zone is updated"
+
+    face_flux_x = to_host(face_flux_x)
+    face_flux_y = to_host(face_flux_y)
+    assert face_flux_x[1, 1, 1] != 0.0, "face flux into a masked zone is recorded"
+    assert face_flux_y[1, 1, 2] != 0.0, "face flux into a masked zone is recorded"
+    assert_close(face_flux_x[2, 1], [0.0, 0.0, 0.0], "masked zone's outer x-face")
+    assert_close(face_flux_y[1, 2], [0.0, 0.0, 0.0], "masked zone's outer y-face")
+    print("pass: masked zones")
+
+
+def test_invalid_zones(lib, xp, to_host):
+    from sailfish.kernel.fixtures import patch_fixture
+
+    def make_primitive():
+        return patch_fixture(
+            lambda x, y: (1.0, 0.0, 0.0), (2, 2), ((1.0, 2.0), (1.0, 2.0)), 3, xp=xp
+        )
+
+    # A zone whose density is negative before the floor is flagged, and
+    # floored, and the flags of the other zones are left as they were.
+    primitive = make_primitive()
+    primitive[2, 2, 0] = -1.0
+    invalid_zones = xp.zeros([2, 2])
+    invalid_zones[1, 1] = 4.0
+    primitive_wr = advance_patch(lib, xp, primitive, invalid_zones=invalid_zones)[0]
+    flags = to_host(invalid_zones)
+
+    assert flags[0, 0] == 1.0, "negative density is flagged"
+    assert flags[0, 1] == 0.0 and flags[1, 0] == 0.0, "valid zone is not flagged"
+    assert flags[1, 1] == 4.0, "flags are not cleared by the kernel"
+    assert to_host(primitive_wr)[2, 2, 0] == 0.0, "negative density is floored"
+
+    # Non-finite data is flagged with its own value.
+    primitive = make_primitive()
+    primitive[3, 3, 1] = float("nan")
+    invalid_zones = xp.zeros([2, 2])
+    advance_patch(lib, xp, primitive, invalid_zones=invalid_zones)
+    assert to_host(invalid_zones)[1, 1] == 2.0, "non-finite data is flagged"
+    print("pass: invalid zones")
+
+
+def test_fill_guard_zones(lib, xp, to_host):
+    import numpy as np
+    from sailfish.kernel.fixtures import assert_close
+
+    # Three patches of different widths, filled with the zone indexes. The
+    # guard zones of the middle patch are copied from its neighbors in x, and
+    # have outflow values in y; the first patch has outflow values on its
+    # left edge.
+    nj = 5
+    widths = [4, 3, 6]
+    patches = [np.random.rand(ni + 4, nj + 4, 3) for ni in widths]
+    expected = [p.copy() for p in patches]
+    device = [xp.array(p) for p in patches]
+
+    for n, ni in enumerate(widths):
+        num_guard_zones = (ni + 4) * (nj + 4) - ni * nj
+        pl, pc, pr = device[n - 1], device[n], device[(n + 1) % 3]
+        nl, nr = widths[n - 1], widths[(n + 1) % 3]
+        args = (ni, nj, nl, nr, int(n == 0), int(n == 2))
+        lib.cbdiso_2d_fill_guard_zones[num_guard_zones](pl, pc, pr, *args)
+
+        e = expected[n]
+        e[:2] = patches[n - 1][-4:-2] if n > 0 else e[2]
+        e[-2:] = patches[(n + 1) % 3][2:4] if n < 2 else e[-3]
+        e[:, :2] = e[:, 2:3]
+        e[:, -2:] = e[:, -3:-2]
+
+    for n in range(3):
+        assert_close(to_host(device[n]), expected[n], f"guard zones of patch {n}")
+
+    print("pass: fill guard zones")
+
+
+def test_set_primitive(mode):
+    import numpy as np
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.setups.circumbinary_disk import CircumbinaryDisk
+    from sailfish.solvers.cbdiso_2d import Solver
+
+    # A solver whose solution is replaced with set_primitive has to take the
+    # same next step as a solver constructed from that solution, as on a
+    # restart; this fails if any of its guard zones, conserved data, or face
+    # flux accumulators are left over from the old solution.
+    setup = CircumbinaryDisk()
+    mesh = setup.mesh(24)
+
+    def make_solver(time, solution=None):
+        return Solver(
+            setup=setup,
+            mesh=mesh,
+            time=time,
+            solution=solution,
+            num_patches=3,
+            mode=mode,
+            physics=setup.physics,
+            options=dict(record_face_fluxes=True, boost_vx=0.1),
+        )
+
+    dt = 1e-3
+    solver = make_solver(0.0)
+    solver.advance(dt)
+
+    primitive = solver.primitive_array()
+    primitive[..., 0] *= 1.0 + 0.1 * np.random.rand(*mesh.shape)
+    solver.set_primitive(primitive)
+    assert_close(solver.primitive_array(), primitive, "set_primitive round trip")
+
+    restarted = make_solver(solver.time, solver.solution)
+    assert_close(
+        solver.conserved_array(),
+        restarted.conserved_array(),
+        "conserved data after set_primitive",
+    )
+
+    for _ in range(3):
+        solver.advance(dt)
+        restarted.advance(dt)
+
+    assert_close(solver.solution, restarted.solution, "steps after set_primitive")
+
+    fluxes = solver.face_fluxes()
+    expected = restarted.face_fluxes()
+    assert np.abs(fluxes["x"]).max() > 0.0 and np.abs(fluxes["y"]).max() > 0.0
+    assert_close(fluxes["x"], expected["x"], "x-face fluxes after set_primitive")
+    assert_close(fluxes["y"], expected["y"], "y-face fluxes after set_primitive")
+    print("pass: set primitive")
+
+
+def test_outflow_modes(mode):
+    import numpy as np
+    from sailfish.boundary import BoundaryCondition, fill_guard_zones
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.setups.circumbinary_disk import CircumbinaryDisk
+    from sailfish.solvers.cbdiso_2d import Solver
+
+    # The guard zones of the other outflow modes are filled like those of
+    # cbdgam_2d, after the plain outflow values, on each domain edge.
+    setup = CircumbinaryDisk(outer_boundary="outflow-linear-clamped")
+    mesh = setup.mesh(24)
+    solver = Solver(
+        setup=setup,
+        mesh=mesh,
+        time=0.0,
+        solution=None,
+        num_patches=3,
+        mode=mode,
+        physics=setup.physics,
+        options=dict(),
+    )
+    solver.advance(1e-3)
+
+    primitive = solver.primitive_array(guard_zones=True)
+    expected = primitive.copy()
+    outflow = primitive.copy()
+    clamped = BoundaryCondition.OUTFLOW_LINEAR_CLAMPED
+
+    for axis in (0, 1):
+        for side in (0, 1):
+            fill_guard_zones(expected, axis, side, clamped, 2)
+            fill_guard_zones(outflow, axis, side, BoundaryCondition.OUTFLOW, 2)
+
+    assert_close(primitive, expected, "outflow-linear-clamped guard zones")
+    assert np.abs(primitive - outflow).max() > 0.0
+    print("pass: outflow modes")
+
+
+def test_reset(mode):
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.setups.circumbinary_disk import CircumbinaryDisk
+    from sailfish.solvers.cbdiso_2d import Solver
+
+    # A reset solver has to repeat its first solve exactly, and with new
+    # initial data it has to step like a solver constructed from that data.
+    setup = CircumbinaryDisk()
+    mesh = setup.mesh(24)
+    solver = Solver(
+        setup=setup,
+        mesh=mesh,
+        num_patches=2,
+        mode=mode,
+        physics=setup.physics,
+        options=dict(record_face_fluxes=True),
+    )
+    dt = 1e-3
+
+    for _ in range(3):
+        solver.advance(dt)
+
+    first = solver.solution
+    primitive = solver.primitive_array()
+    solver.reset()
+    assert_close(solver.time, 0.0, "time after reset")
+
+    for _ in range(3):
+        solver.advance(dt)
+
+    assert_close(solver.solution, first, "solve after reset")
+    fresh = Solver(
+        setup=setup,
+        mesh=mesh,
+        time=0.5,
+        solution=primitive,
+        mode=mode,
+        physics=setup.physics,
+        options=dict(record_face_fluxes=True),
+    )
+    solver.reset(primitive, time=0.5)
+    solver.advance(dt)
+    fresh.advance(dt)
+    assert_close(solver.solution, fresh.solution, "step after reset to new data")
+    assert_close(solver.face_fluxes()["x"], fresh.face_fluxes()["x"], "face fluxes")
+    print("pass: reset")
+
+
+def test_sample(mode):
+    import numpy as np
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.setups.circumbinary_disk import CircumbinaryDisk
+    from sailfish.solvers.cbdiso_2d import Solver
+
+    # Bilinear interpolation is exact for linear fields, at points anywhere
+    # between the outermost zone centers, including across patch edges.
+    setup = CircumbinaryDisk()
+    mesh = setup.mesh(24)
+    solver = Solver(
+        setup=setup,
+        mesh=mesh,
+        num_patches=3,
+        mode=mode,
+        physics=setup.physics,
+        options=dict(boost_vx=0.1),
+    )
+    x, y = mesh.cell_center_arrays()
+    fields = lambda x, y: np.stack([1.0 + 0.1 * x + 0.2 * y, 0.3 * x, -0.1 * y], -1)
+    solver.set_primitive(fields(x, y))
+
+    h = 0.5 * mesh.dx
+    px = np.random.uniform(mesh.x0 + h, mesh.x1 - h, 100)
+    py = np.random.uniform(mesh.y0 + h, mesh.y1 - h, 100)
+    assert_close(solver.sample(px, py), fields(px, py), "sampled primitive data")
+    print("pass: sample")
+
+
+def test_tracers(mode):
+    import numpy as np
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.setups.circumbinary_disk import CircumbinaryDisk
+    from sailfish.solvers.cbdiso_2d import Solver
+
+    # The tracer flux is the mass flux times an upwind concentration, and the
+    # sinks and buffer remove or add mass at the zone's concentration, so a
+    # uniform tracer stays uniform through the full update.
+    setup = CircumbinaryDisk(tracer_radius=2.0)
+    mesh = setup.mesh(24)
+    solver = Solver(
+        setup=setup,
+        mesh=mesh,
+        num_patches=3,
+        mode=mode,
+        physics=setup.physics,
+    )
+    names = [f.name for f in solver.primitive_fields]
+    assert names == ["sigma", "vx", "vy", "tracer0"], names
+
+    x, y = mesh.cell_center_arrays()
+    primitive = solver.primitive_array()
+    assert_close(primitive[..., 3], (x * x + y * y < 4.0), "initial tracer")
+
+    primitive[..., 3] = 1.0
+    solver.set_primitive(primitive)
+
+    for _ in range(5):
+        solver.advance(1e-3)
+
+    tracer = solver.primitive_array()[..., 3]
+    assert_close(tracer, np.ones(mesh.shape), "uniform tracer", tol=1e-10)
+    print("pass: tracers")
+
+
+def test_sound_speed_profile(mode):
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.physics.circumbinary import EquationOfState, Physics
+    from sailfish.setups.circumbinary_disk import CircumbinaryDisk
+    from sailfish.solvers.cbdiso_2d import Solver, sound_speed_table
+
+    # A flat sound speed profile has to evolve like a globally isothermal
+    # EOS with the same sound speed, and a power law is sampled exactly.
+    setup = CircumbinaryDisk(sound_speed_profile="power-law:0.1:0.0")
+    mesh = setup.mesh(24)
+    flat = dict(setup.physics, eos_type=EquationOfState.GLOBALLY_ISOTHERMAL)
+    flat.update(sound_speed=0.1)
+    solvers = [
+        Solver(setup=setup, mesh=mesh, num_patches=2, mode=mode, physics=physics)
+        for physics in (setup.physics, flat)
+    ]
+
+    for solver in solvers:
+        for _ in range(3):
+            solver.advance(1e-3)
+
+    a, b = (solver.primitive_array() for solver in solvers)
+    assert_close(a, b, "flat sound speed profile")
+
+    setup = CircumbinaryDisk(sound_speed_profile="power-law:0.1:-0.5")
+    dr, table = sound_speed_table(Physics(**setup.physics), mesh)
+    assert_close(table[40], 0.01 / (40 * dr), "sampled profile")
+    assert dr * (len(table) - 1) > 2**0.5 * 12.0
+    print("pass: sound speed profile")
+
+
+def test_buffer_target(mode):
+    import numpy as np
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.setups.circumbinary_disk import CircumbinaryDisk
+    from sailfish.solvers.cbdiso_2d import Solver
+
+    class UniformTarget(CircumbinaryDisk):
+        def buffer_target(self, mesh):
+            state = np.zeros(mesh.shape + (3,))
+            state[..., 0] = 2.0
+            return np.full(mesh.shape, 100.0), state
+
+    # The inner buffer damps toward the initial disk inside its radius, and
+    # nowhere else.
+    setup = CircumbinaryDisk(inner_buffer_radius=2.0)
+    mesh = setup.mesh(16)
+    x, y = mesh.cell_center_arrays()
+    inner = x * x + y * y < 4.0
+    solver = Solver(setup=setup, mesh=mesh, mode=mode, physics=setup.physics)
+    rate, state = solver.target
+    assert (rate[inner] > 0.0).all() and (rate[~inner] == 0.0).all()
+    assert_close(state, solver.initial_solution[..., :3], "inner buffer state")
+
+    # Driven everywhere toward a uniform gas at rest, the solution gets there
+    # in a few damping times, away from the sinks.
+    setup = UniformTarget(buffer_is_enabled=False, inner_buffer_radius=1.0)
+    solver = Solver(setup=setup, mesh=mesh, mode=mode, physics=setup.physics)
+    rate, state = solver.target
+    assert (rate >= 100.0).all() and (state[..., 0] == 2.0).all()
+
+    for _ in range(50):
+        solver.advance(1e-3)
+
+    sigma = solver.primitive_array()[..., 0]
+    assert_close(sigma[~inner], 2.0, "buffer target state", tol=0.05)
+
+    # On a rotating mesh, the target gas at rest moves against the rotation.
+    options = dict(frame_rotation=0.5)
+    solver = Solver(
+        setup=setup, mesh=mesh, mode=mode, physics=setup.physics, options=options
+    )
+    rate, state = solver.target
+    outer = rate == 100.0
+    assert_close(state[outer, 1], 0.5 * y[outer], "rotating buffer target vx")
+    assert_close(state[outer, 2], -0.5 * x[outer], "rotating buffer target vy")
+    print("pass: buffer target")
+
+
+def test_sink_resolution(mode):
+    from sailfish.driver import check_sink_resolution
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.setups.circumbinary_disk import CircumbinaryDisk
+    from sailfish.solvers.cbdiso_2d import Solver
+
+    # On a coarse mesh, neither sink radius spans a zone, and they are
+    # under-resolved until the threshold is lowered.
+    setup = CircumbinaryDisk()
+    mesh = setup.mesh(16)
+    solver = Solver(setup=setup, mesh=mesh, mode=mode, physics=setup.physics)
+    stats = solver.sink_resolution()
+
+    assert [sink["which"] for sink in stats] == [1, 2]
+
+    for sink in stats:
+        assert_close(sink["zones_per_radius"], 0.05 / 1.5, "zones per sink radius")
+        assert sink["mach_number"] > 0.0 and sink["min_dt"] > 0.0
+
+    under_resolved = check_sink_resolution(stats, 2.0, set())
+    assert under_resolved == {1, 2}
+    assert check_sink_resolution(stats, 0.01, under_resolved) == set()
+    print("pass: sink resolution")
+
+
+def test_invalid_state(mode):
+    from sailfish.setups.circumbinary_disk import CircumbinaryDisk
+    from sailfish.solvers import SolverError
+    from sailfish.solvers.cbdiso_2d import Solver
+
+    # A non-finite zone spreads over one iteration, and advance raises a
+    # SolverError for a zone near it when the flags are checked, and goes on
+    # when they're never checked.
+    setup = CircumbinaryDisk()
+    mesh = setup.mesh(16)
+
+    def make_solver(cadence):
+        options = dict(invalid_state_cadence=cadence)
+        physics = setup.physics
+        return Solver(
+            setup=setup, mesh=mesh, mode=mode, physics=physics, options=options
+        )
+
+    for cadence in (0, 1):
+        solver = make_solver(cadence)
+        primitive = solver.primitive_array()
+        primitive[5, 7, 1] = float("nan")
+        solver.set_primitive(primitive)
+
+        try:
+            solver.advance(1e-3)
+        except SolverError as e:
+            assert cadence == 1, "flags are not checked with a cadence of 0"
+            assert e.kind == "non-finite data"
+            assert abs(e.zone[0] - 5) <= 4 and abs(e.zone[1] - 7) <= 4
+            assert e.location == mesh.cell_coordinates(*e.zone)
+            continue
+
+        assert cadence == 0, "an invalid state should raise a SolverError"
+
+    print("pass: invalid state")
+
+
+def test_stiff_sink(mode):
+    from sailfish.setups.circumbinary_disk import CircumbinaryDisk
+    from sailfish.solvers import SolverError
+    from sailfish.solvers.cbdiso_2d import Solver
+
+    # A sink whose rate times dt is 100 empties the zones inside it to a
+    # negative density with the explicit update, and to nearly zero with
+    # the sub-cycled and exponential ones.
+    setup = CircumbinaryDisk(domain_radius=1.0, sink_rate=1e5, buffer_is_enabled=False)
+    mesh = setup.mesh(32)
+
+    for update in ["explicit", "subcycle", "exponential"]:
+        options = dict(sink_update=update, invalid_state_cadence=1)
+        solver = Solver(
+            setup=setup, mesh=mesh, mode=mode, physics=setup.physics, options=options
+        )
+        try:
+            solver.advance(1e-3)
+        except SolverError:
+            assert update == "explicit"
+            continue
+
+        assert update != "explicit", "the explicit update should fail"
+        assert (solver.primitive_array()[..., 0] >= 0.0).all()
+
+    # The stiff sink no longer counts toward the time step limit.
+    limiters = solver.timestep_limiters()
+    assert (limiters["limiter"] != limiters["names"].index("source")).all()
+    print("pass: stiff sink")
+
+
+def test_step_retries(mode):
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.setups.circumbinary_disk import CircumbinaryDisk
+    from sailfish.solvers import SolverError
+    from sailfish.solvers.cbdiso_2d import Solver
+
+    # A step with dt > 1e-3 is made to fail here, so a step of 4e-3 takes
+    # two retries, and is then the same as four steps of 1e-3. With one
+    # retry, the SolverError is raised.
+    class FailingSolver(Solver):
+        def take_step(self, dt):
+            super().take_step(dt)
+
+            if dt > 1e-3:
+                self.patches[0].invalid_zones[0, 0] = 1.0
+
+    setup = CircumbinaryDisk()
+    mesh = setup.mesh(16)
+
+    def make_solver(cls, retries):
+        options = dict(max_step_retries=retries, record_face_fluxes=True)
+        physics = setup.physics
+        return cls(
+            setup=setup, mesh=mesh, mode=mode, physics=physics, options=options
+        )
+
+    solver = make_solver(FailingSolver, 2)
+    solver.advance(4e-3)
+    expected = make_solver(Solver, 0)
+
+    for _ in range(4):
+        expected.advance(1e-3)
+
+    assert_close(solver.time, expected.time, "time after retries")
+    assert_close(solver.solution, expected.solution, "solution after retries")
+    fluxes = solver.face_fluxes()
+    assert_close(fluxes["x"], expected.face_fluxes()["x"], "face fluxes after retries")
+
+    try:
+        make_solver(FailingSolver, 1).advance(4e-3)
+    except SolverError:
+        print("pass: step retries")
+        return
+
+    raise AssertionError("a step which still fails should raise a SolverError")
+
+
+def test_two_layer(mode):
+    import numpy as np
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.setups.circumbinary_disk import CircumbinaryDisk
+    from sailfish.solvers.cbdiso2l_2d import Solver
+
+    # The drag and the mass exchange each conserve the total mass and
+    # momentum in every zone, and decay the difference of the layers'
+    # velocity or surface density exactly exponentially.
+    setup = CircumbinaryDisk(two_layer=True, layer_drag_rate=2.0)
+    mesh = setup.mesh(24)
+    solver = Solver(
+        setup=setup,
+        mesh=mesh,
+        num_patches=3,
+        mode=mode,
+        physics=setup.physics,
+    )
+    names = [f.name for f in solver.primitive_fields]
+    assert names[:4] == ["sigma_upper", "vx_upper", "vy_upper", "sigma_lower"], names
+
+    primitive = solver.primitive_array()
+    upper, lower = primitive[..., :3], primitive[..., 3:]
+    assert_close(upper, lower, "layers start the same")
+
+    def totals(p):
+        mass = p[..., 0] + p[..., 3]
+        px = p[..., 0] * p[..., 1] + p[..., 3] * p[..., 4]
+        py = p[..., 0] * p[..., 2] + p[..., 3] * p[..., 5]
+        return np.stack([mass, px, py], -1)
+
+    primitive[..., 0] *= 1.0 + np.random.rand(*mesh.shape)
+    primitive[..., 1] += 0.2
+    solver.set_primitive(primitive)
+    solver.couple_layers(0.5)
+    coupled = solver.primitive_array()
+    assert_close(totals(coupled), totals(primitive), "drag conserves momentum")
+    assert_close(
+        coupled[..., 1] - coupled[..., 4],
+        (primitive[..., 1] - primitive[..., 4]) * np.exp(-1.0),
+        "drag decays the velocity difference",
+    )
+
+    solver.coupling = solver.coupling._replace(layer_drag_rate=0.0)
+    solver.coupling = solver.coupling._replace(layer_exchange_rate=4.0)
+    solver.couple_layers(0.25)
+    exchanged = solver.primitive_array()
+    assert_close(totals(exchanged), totals(coupled), "exchange conserves momentum")
+    assert_close(
+        exchanged[..., 0] - exchanged[..., 3],
+        (coupled[..., 0] - coupled[..., 3]) * np.exp(-1.0),
+        "exchange decays the density difference",
+    )
+    print("pass: two layer")
+
+
+def test_single_precision(mode):
+    import numpy as np
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.setups.circumbinary_disk import CircumbinaryDisk
+    from sailfish.solvers.cbdiso_2d import Solver
+
+    # The single-precision patches hold float32 data, and a few steps agree
+    # with the double-precision ones to about the float32 round-off. The
+    # solution is still returned in double precision.
+    setup = CircumbinaryDisk(domain_radius=2.0)
+    mesh = setup.mesh(32)
+    solvers = dict()
+
+    for precision in ["float64", "float32"]:
+        solver = Solver(
+            setup=setup,
+            mesh=mesh,
+            mode=mode,
+            physics=setup.physics,
+            options=dict(precision=precision),
+        )
+        for _ in range(5):
+            solver.advance(1e-3)
+        solvers[precision] = solver
+
+    assert solvers["float32"].patches[0].primitive1.dtype == np.float32
+    assert solvers["float32"].solution.dtype == np.float64
+    assert_close(
+        solvers["float32"].solution,
+        solvers["float64"].solution,
+        "single precision",
+        tol=1e-5,
+    )
+    print("pass: single precision")
+
+
+def test_copy_stream(mode):
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.setups.circumbinary_disk import CircumbinaryDisk
+    from sailfish.solvers.cbdiso_2d import Solver
+    from sailfish.subdivide import resolve
+
+    # A deferred download on the copy stream is the data when it was
+    # started, even though the steps launched after it change the solution.
+    setup = CircumbinaryDisk(domain_radius=2.0)
+    mesh = setup.mesh(32)
+    solver = Solver(
+        setup=setup,
+        mesh=mesh,
+        num_patches=2,
+        mode=mode,
+        physics=setup.physics,
+        options=dict(copy_stream=True, boost_vx=0.1),
+    )
+    solver.advance(1e-3)
+    expected = solver.primitive_array()
+    deferred = solver.deferred_primitive_array()
+    solver.advance(1e-3)
+    solver.advance(1e-3)
+    assert_close(resolve(deferred), expected, "deferred primitive array")
+
+    solver.synchronize()
+    assert solver.is_done()
+    print("pass: copy stream")
+
+
+def test_conservation_audit(mode):
+    import numpy as np
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.setups.circumbinary_disk import CircumbinaryDisk
+    from sailfish.solvers.cbdiso_2d import AUDIT_TERMS, Solver
+
+    # The terms of the audit account for the change of the total mass and
+    # momentum up to round-off, and the audit leaves the solution unchanged.
+    setup = CircumbinaryDisk(domain_radius=2.0)
+    mesh = setup.mesh(32)
+
+    def solver(options):
+        return Solver(
+            setup=setup,
+            mesh=mesh,
+            num_patches=2,
+            mode=mode,
+            physics=setup.physics,
+            options=options,
+        )
+
+    audited = solver(dict(conservation_audit=True, max_step_retries=1))
+    plain = solver(dict(max_step_retries=1))
+    total = audited.total_conserved()
+    area = mesh.dx * mesh.dy
+    expected = audited.conserved_array()[..., :3].sum(axis=(0, 1)) * area
+    assert_close(total, expected, "total conserved")
+
+    for _ in range(10):
+        audited.advance(1e-2)
+        plain.advance(1e-2)
+
+    budget = audited.conservation_budget()
+    terms = budget["terms"]
+    assert list(terms) == list(AUDIT_TERMS)
+    assert terms["sinks"][0] < 0.0, "the sinks remove mass"
+    assert terms["buffers"][0] != 0.0, "the buffer adds or removes mass"
+    assert terms["gravity"][0] == 0.0 and terms["frame"][0] == 0.0
+    assert_close(budget["initial"], total, "audit initial totals")
+
+    scale = sum(np.abs(t) for t in terms.values()) + np.abs(budget["final"])
+    assert (np.abs(budget["residual"]) < 1e-12 * scale).all(), budget["residual"]
+    assert_close(audited.primitive_array(), plain.primitive_array(), "audit")
+
+    # The next interval starts from the end of this one.
+    audited.advance(1e-2)
+    assert_close(audited.conservation_budget()["initial"], budget["final"], "restart")
+    assert plain.conservation_budget() is None
+    print("pass: conservation audit")
+
+
+def test_callbacks(mode):
+    import numpy as np
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.setups.circumbinary_disk import CircumbinaryDisk
+    from sailfish.solvers.cbdiso_2d import Solver
+
+    # A source function which adds mass at a unit rate shows in the split
+    # steps of the audit, and the hook is called with each patch after each
+    # step, and can modify the solution, which is also audited.
+    setup = CircumbinaryDisk(domain_radius=2.0)
+    mesh = setup.mesh(32)
+    solver = Solver(
+        setup=setup,
+        mesh=mesh,
+        num_patches=2,
+        mode=mode,
+        physics=setup.physics,
+        options=dict(conservation_audit=True),
+    )
+    calls = list()
+
+    def source(time, x, y, primitive):
+        assert x.shape == y.shape == primitive.shape[:2]
+        rate = np.zeros_like(primitive)
+        rate[..., 0] = 1.0
+        return rate
+
+    def hook(patch, time):
+        calls.append((patch.index_range, time))
+        patch.primitive1[2:-2, 2:-2, 0] *= 2.0
+
+    solver.set_source_function(source)
+    solver.set_post_advance_hook(hook)
+    sigma = solver.primitive_array()[..., 0]
+    solver.advance(1e-3)
+    area = mesh.dx * mesh.dy
+    added = (2.0 * (sigma + 1e-3) - sigma).sum() * area
+    split = solver.conservation_budget()["terms"]["split_steps"][0]
+    assert_close(split / added, 1.0, "source function and hook", tol=1e-3)
+    assert [t for _, t in calls] == [solver.time] * 2
+    assert [r for r, _ in calls] == [p.index_range for p in solver.patches]
+
+    solver.set_source_function(None)
+    solver.set_post_advance_hook(None)
+    solver.advance(1e-3)
+    assert len(calls) == 2
+    print("pass: callbacks")
+
+
+def test_log_polar(mode):
+    import numpy as np
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.physics.circumbinary import EquationOfState
+    from sailfish.setups.circumbinary_disk import UniformTorus
+    from sailfish.solvers.cbdisolp_2d import Solver
+
+    class UniformRest(UniformTorus):
+        def primitive(self, t, coords, primitive):
+            primitive[:] = [1.0, 0.0, 0.0]
+
+    # The geometric source of the radial momentum balances the pressure on
+    # the faces of each zone, so a uniform state at rest stays at rest.
+    setup = UniformRest(coordinates="log_polar")
+    mesh = setup.mesh(32)
+    physics = dict(eos_type=EquationOfState.GLOBALLY_ISOTHERMAL, sound_speed=0.1)
+    solver = Solver(setup=setup, mesh=mesh, num_patches=3, mode=mode, physics=physics)
+    initial = solver.primitive_array()
+
+    for _ in range(5):
+        solver.advance(1e-2)
+
+    assert_close(solver.primitive_array(), initial, "uniform rest", tol=1e-12)
+
+    try:
+        solver.sample(1.0, 0.0)
+    except NotImplementedError:
+        pass
+    else:
+        raise AssertionError("sampling a log-polar mesh should be rejected")
+
+    # The torus's velocities are rotated into the polar basis, and its mass
+    # and angular momentum are kept up to truncation error.
+    setup = UniformTorus(coordinates="log_polar")
+    mesh = setup.mesh(64)
+    physics = dict(setup.physics)
+    physics["diagnostics"] = [dict(quantity="mass"), dict(quantity="angular_momentum")]
+    solver = Solver(setup=setup, mesh=mesh, num_patches=2, mode=mode, physics=physics)
+    names = [f.name for f in solver.primitive_fields]
+    assert names == ["sigma", "vr", "vphi"], names
+
+    x, y = mesh.cell_center_arrays()
+    r = (x * x + y * y) ** 0.5
+    omega = 1.0 / (r**3 + setup.softening_length**3) ** 0.5
+    primitive = solver.primitive_array()
+    assert_close(primitive[..., 1], np.zeros(mesh.shape), "radial velocity")
+    assert_close(primitive[..., 2], omega * r, "azimuthal velocity")
+
+    mass, angular_momentum = solver.reductions()
+    assert abs(mass / (3.0 * np.pi) - 1.0) < 0.05, mass
+
+    dt = solver.recommended_timestep(mesh.min_spacing(), 0.3)
+
+    for _ in range(10):
+        solver.advance(dt)
+
+    m, l = solver.reductions()
+    assert abs(m / mass - 1.0) < 1e-6, m / mass
+    assert abs(l / angular_momentum - 1.0) < 1e-3, l / angular_momentum
+    print("pass: log polar")
+
+
+def test_log_polar_checkpoint(mode):
+    import tempfile
+    from sailfish.driver import DriverArgs, simulate, write_checkpoint
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.products import load_checkpoint
+
+    # A checkpoint of the log-polar solver has its primitive data, for the
+    # readers, and a run restarted from it continues from the same solution.
+    driver = DriverArgs(
+        setup_name="uniform-torus",
+        model_parameters=dict(coordinates="log_polar"),
+        execution_mode=mode,
+        resolution=32,
+        end_time=0.01,
+    )
+    *_, (_, _, state) = simulate(driver)
+    primitive = state.solver.primitive_array()
+
+    with tempfile.TemporaryDirectory() as tmp:
+        filename = write_checkpoint(0, tmp, state)
+        chkpt = load_checkpoint(filename)
+        assert_close(chkpt.primitive, primitive, "checkpoint primitive data")
+        *_, (_, _, restart) = simulate(DriverArgs(chkpt_file=filename))
+
+    assert_close(restart.solver.time, state.solver.time, "restart time")
+    assert_close(restart.solver.primitive_array(), primitive, "restart solution")
+    print("pass: log polar checkpoint")
+
+
+def main():
+    from sailfish.kernel.fixtures import library_with_probes, parse_test_args
+    from sailfish.kernel.system import get_array_module
+    from sailfish.subdivide import to_host
+
+    args = parse_test_args(modes=["cpu", "omp", "gpu", "checked"])
+    logging.basicConfig(level=logging.INFO)
+    xp = get_array_module(args.mode)
+
+    lib = library_with_probes("sailfish/solvers/cbdiso_2d.c", probes, mode=args.mode)
+
+    test_plm_gradient(lib, xp, to_host)
+    test_riemann_hlle(lib, xp, to_host)
+    test_point_mass_source_term(lib, xp, to_host)
+    test_softening_models(lib, xp, to_host)
+    test_relaxation_time_step(lib, xp, to_host)
+    test_uniform_patch(lib, xp, to_host)
+    test_rotating_frame(lib, xp, to_host)
+    test_masked_zones(lib, xp, to_host)
+    test_invalid_zones(lib, xp, to_host)
+    test_fill_guard_zones(lib, xp, to_host)
+    test_set_primitive("gpu" if args.mode == "gpu" else "cpu")
+    test_outflow_modes("gpu" if args.mode == "gpu" else "cpu")
+    test_reset("gpu" if args.mode == "gpu" else "cpu")
+    test_sample("gpu" if args.mode == "gpu" else "cpu")
+    test_tracers("gpu" if args.mode == "gpu" else "cpu")
+    test_sound_speed_profile("gpu" if args.mode == "gpu" else "cpu")
+    test_buffer_target("gpu" if args.mode == "gpu" else "cpu")
+    test_sink_resolution("gpu" if args.mode == "gpu" else "cpu")
+    test_invalid_state("gpu" if args.mode == "gpu" else "cpu")
+    test_stiff_sink("gpu" if args.mode == "gpu" else "cpu")
+    test_step_retries("gpu" if args.mode == "gpu" else "cpu")
+    test_two_layer("gpu" if args.mode == "gpu" else "cpu")
+    test_single_precision("gpu" if args.mode == "gpu" else "cpu")
+    test_copy_stream("gpu" if args.mode == "gpu" else "cpu")
+    test_conservation_audit("gpu" if args.mode == "gpu" else "cpu")
+    test_log_polar("gpu" if args.mode == "gpu" else "cpu")
+    test_log_polar_checkpoint("gpu" if args.mode == "gpu" else "cpu")
+
+    if args.mode != "gpu":
+        test_callbacks("cpu")
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,104 @@
+"""
+Check the relaxation of the temperature in the gamma-law solver: the cooling
+models are parsed from their names, the temperature decays exponentially
+toward a constant target on the constant cooling time, and the setup rejects
+cooling in isothermal mode and non-positive cooling times.
+"""
+
+import sys
+
+sys.path.insert(1, ".")
+
+
+def test_cooling_model():
+    from sailfish.physics.circumbinary import CoolingModel
+
+    assert CoolingModel.from_str("none") == CoolingModel.NONE
+    assert CoolingModel.from_str("beta") == CoolingModel.BETA
+    assert CoolingModel.from_str("constant-time") == CoolingModel.CONSTANT_TIME
+
+    try:
+        CoolingModel.from_str("radiative")
+    except ValueError:
+        pass
+    else:
+        raise AssertionError("an unknown cooling model should be rejected")
+
+    print("pass: cooling model")
+
+
+def test_constant_time_relaxation(mode):
+    import numpy as np
+    from sailfish.physics.circumbinary import PointMass
+    from sailfish.setups.circumbinary_disk import CircumbinaryDisk
+    from sailfish.solvers.cbdgam_2d import Solver
+
+    # A uniform gas at rest, around massless point masses, only changes
+    # through the cooling, and its temperature p / sigma relaxes toward the
+    # target as exp(-t / cooling_time).
+    setup = CircumbinaryDisk(
+        eos="gamma-law",
+        cooling_model="constant-time",
+        cooling_time=0.1,
+        alpha=0.0,
+        buffer_is_enabled=False,
+    )
+    mesh = setup.mesh(16)
+    physics = dict(
+        setup.physics,
+        point_mass_function=lambda time: (PointMass(), PointMass()),
+        cooling_target=lambda x, y: 0.2,
+    )
+    primitive = np.zeros(mesh.shape + (4,))
+    primitive[..., 0] = 1.0
+    primitive[..., 3] = 1.0
+
+    solver = Solver(
+        setup=setup,
+        mesh=mesh,
+        solution=primitive,
+        mode=mode,
+        physics=physics,
+    )
+    dt = 1e-3
+
+    for _ in range(100):
+        solver.advance(dt)
+
+    p = solver.primitive_array()
+    expected = 0.2 + 0.8 * np.exp(-solver.time / (2.0 * np.pi * 0.1))
+    assert np.allclose(p[..., 3] / p[..., 0], expected, rtol=1e-10)
+    print("pass: constant-time relaxation")
+
+
+def test_rejects_bad_setups():
+    from sailfish.setup_base import SetupError
+    from sailfish.setups.circumbinary_disk import CircumbinaryDisk
+
+    for parameters in [
+        dict(cooling_model="beta"),
+        dict(cooling_model="newtonian", eos="gamma-law"),
+        dict(cooling_model="beta", eos="gamma-law", cooling_beta=0.0),
+        dict(cooling_model="constant-time", eos="gamma-law", cooling_time=-1.0),
+    ]:
+        try:
+            CircumbinaryDisk(**parameters)
+        except SetupError:
+            continue
+        raise AssertionError(f"{parameters} should be rejected")
+
+    CircumbinaryDisk(cooling_model="beta", eos="gamma-law")
+    print("pass: rejects bad setups")
+
+
+def main():
+    from sailfish.kernel.fixtures import parse_test_args
+
+    args = parse_test_args()
+    test_cooling_model()
+    test_rejects_bad_setups()
+    test_constant_time_relaxation(args.mode)
+
+
+if __name__ == "__main__":
+    main()
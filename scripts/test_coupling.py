@@ -0,0 +1,83 @@
+"""
+Check the coupling of solvers with different physics across an interface.
+
+Two coupled regions with the same physics give the solution of one solver,
+since the halos they exchange make the stencils next to the interface the
+same, and their interface fluxes then agree. With an isothermal outer region
+the fluxes differ, and matching them has to conserve the total mass in a
+closed tube to round-off.
+"""
+
+import sys
+
+sys.path.insert(1, ".")
+
+
+def test_same_physics():
+    import numpy as np
+    from sailfish.interactive import Simulation
+
+    coupled = Simulation(
+        "nested-shocktube",
+        model_parameters=dict(outer_eos="gamma-law"),
+        resolution=200,
+    )
+    single = Simulation(
+        "shocktube", model_parameters=dict(newtonian=True), resolution=200
+    )
+
+    for sim in (coupled, single):
+        sim.step(until=0.15)
+
+    assert coupled.iteration == single.iteration
+    expected = single.solver.primitive_array()
+    assert np.allclose(coupled.solver.primitive_array(), expected, atol=1e-12)
+    print("pass: same physics")
+
+
+def test_mass_conservation():
+    import numpy as np
+    from sailfish.interactive import Simulation
+
+    sim = Simulation("nested-shocktube", resolution=400)
+    mass = sim.solver.conserved_array()[:, 0].sum()
+    sim.step(until=0.25)
+    primitive = sim.solver.primitive_array()
+    i = sim.solver.ranges[1][0]
+
+    assert abs(sim.solver.conserved_array()[:, 0].sum() - mass) < 1e-12 * mass
+    assert np.allclose(primitive[i:, 2], 1.25 * primitive[i:, 0])
+    assert primitive[i:, 1].max() > 0.1, "the shock should reach the outer region"
+    print("pass: mass conservation")
+
+
+def test_rejects_bad_regions():
+    from sailfish.coupling import Region, interface_indexes
+    from sailfish.mesh import PlanarCartesianMesh
+
+    mesh = PlanarCartesianMesh(0.0, 1.0, 100)
+    assert interface_indexes(mesh, [Region(0.25), Region(0.5), Region()]) == [25, 50]
+
+    for regions in [
+        [Region()],
+        [Region(0.5), Region(0.25), Region()],
+        [Region(0.0), Region()],
+        [Region(), Region()],
+    ]:
+        try:
+            interface_indexes(mesh, regions)
+        except ValueError:
+            continue
+        raise AssertionError(f"{regions} should be rejected")
+
+    print("pass: rejects bad regions")
+
+
+def main():
+    test_same_physics()
+    test_mass_conservation()
+    test_rejects_bad_regions()
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,180 @@
+"""
+Check the custom physics ABI: a source term given as a source file or as a
+shared library is applied in every active zone as a split step, a library
+with the wrong ABI version is rejected, and the cbdiso_2d solver applies the
+source term of its physics after each iteration.
+"""
+
+import sys
+import logging
+
+sys.path.insert(1, ".")
+
+source = """
+#define SAILFISH_CUSTOM_ABI_VERSION 1
+
+PRIVATE void sailfish_custom_source_term(
+    double time,
+    double x,
+    double y,
+    int num_fields,
+    const double *primitive,
+    double *rate,
+    int num_parameters,
+    const double *parameters)
+{
+    rate[0] = parameters[0];
+    rate[1] = parameters[1] * x;
+}
+"""
+
+library = """
+int sailfish_custom_abi_version(void)
+{
+    return VERSION;
+}
+
+void sailfish_custom_source_term(
+    double time,
+    double x,
+    double y,
+    int num_fields,
+    const double *primitive,
+    double *rate,
+    int num_parameters,
+    const double *parameters)
+{
+    rate[0] = parameters[0];
+    rate[1] = parameters[1] * x;
+}
+"""
+
+
+def build_library(directory, version=1):
+    import os
+    import subprocess
+
+    c_file = os.path.join(directory, f"custom{version}.c")
+    so_file = os.path.join(directory, f"custom{version}.so")
+
+    with open(c_file, "w") as f:
+        f.write(library)
+
+    command = ["cc", "-shared", "-fPIC", f"-DVERSION={version}", c_file, "-o"]
+    subprocess.run(command + [so_file], check=True)
+    return so_file
+
+
+def check_step(custom_source):
+    import numpy as np
+
+    # On a 2x3 patch in [0, 2] x [0, 3], the mass rate is 0.5, and the x
+    # momentum rate is 2x, with the middle zone masked.
+    primitive = np.ones([6, 7, 3])
+    zone_mask = np.ones([2, 3])
+    zone_mask[0, 1] = 0.0
+    lib = custom_source.lib
+    lib.custom_source_step[2, 3](
+        0.0,
+        2.0,
+        0.0,
+        3.0,
+        0.0,
+        0.1,
+        1e-12,
+        3,
+        2,
+        np.array(custom_source.parameters),
+        primitive,
+        1,
+        zone_mask,
+    )
+    sigma = primitive[2:-2, 2:-2, 0]
+    momentum = sigma * primitive[2:-2, 2:-2, 1]
+    x = np.array([0.5, 1.5])[:, None] * np.ones([2, 3])
+
+    assert np.allclose(sigma, np.where(zone_mask, 1.05, 1.0))
+    assert np.allclose(momentum, np.where(zone_mask, 1.0 + 0.2 * x, 1.0))
+    assert np.allclose(primitive[2:-2, 2:-2, 2] * sigma, 1.0)
+    assert (primitive[:2] == 1.0).all() and (primitive[:, :2] == 1.0).all()
+
+
+def test_source_file(mode):
+    import os
+    import tempfile
+    from sailfish.kernel.custom import CustomSource
+
+    with tempfile.TemporaryDirectory() as directory:
+        filename = os.path.join(directory, "custom.c")
+
+        with open(filename, "w") as f:
+            f.write(source)
+
+        check_step(CustomSource(filename, [0.5, 2.0], mode))
+
+    print(f"pass: custom source file ({mode})")
+
+
+def test_shared_library(mode):
+    import tempfile
+    from sailfish.kernel.custom import CustomSource
+
+    with tempfile.TemporaryDirectory() as directory:
+        check_step(CustomSource(build_library(directory), [0.5, 2.0], mode))
+
+        try:
+            CustomSource(build_library(directory, version=2), mode=mode)
+        except ValueError:
+            print(f"pass: custom source library ({mode})")
+            return
+
+    raise AssertionError("a library with the wrong ABI version should be rejected")
+
+
+def test_solver(mode):
+    import os
+    import tempfile
+    import numpy as np
+    from sailfish.setups.circumbinary_disk import CircumbinaryDisk
+    from sailfish.solvers.cbdiso_2d import Solver
+
+    setup = CircumbinaryDisk(buffer_is_enabled=False)
+    mesh = setup.mesh(16)
+
+    def make_solver(physics):
+        return Solver(setup=setup, mesh=mesh, mode=mode, physics=physics)
+
+    with tempfile.TemporaryDirectory() as directory:
+        filename = os.path.join(directory, "custom.c")
+
+        with open(filename, "w") as f:
+            f.write(source)
+
+        plain = make_solver(setup.physics)
+        physics = dict(setup.physics, custom_source=filename)
+        physics.update(custom_source_parameters=[1.0, 0.0])
+        custom = make_solver(physics)
+
+    plain.advance(1e-3)
+    custom.advance(1e-3)
+    sigma0 = plain.primitive_array()[..., 0]
+    sigma1 = custom.primitive_array()[..., 0]
+    assert np.allclose(sigma1 - sigma0, 1e-3)
+    print(f"pass: custom source in cbdiso_2d ({mode})")
+
+
+def main():
+    from sailfish.kernel.fixtures import parse_test_args
+
+    args = parse_test_args(modes=["cpu", "omp", "checked", "gpu"])
+    logging.basicConfig(level=logging.INFO)
+
+    if args.mode != "gpu":
+        test_source_file(args.mode)
+        test_shared_library(args.mode)
+
+    test_solver("gpu" if args.mode == "gpu" else "cpu")
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,115 @@
+"""
+Check the euler_2d solver: a uniform flow is unchanged by the steps, and the
+Sod shock tube along y is that along x, with the axes and the velocities
+exchanged. With the `two_temperature` option, the second energy component of
+a gas at rest relaxes exponentially toward the gas temperature, and the total
+energy is conserved.
+"""
+
+import sys
+
+sys.path.insert(1, ".")
+
+
+def euler_solver(primitive, mode, options=dict()):
+    from sailfish.mesh import PlanarCartesian2DMesh
+    from sailfish.setups.simple2d import RiemannQuadrants
+    from sailfish.solvers.euler_2d import Solver
+
+    setup = RiemannQuadrants()
+    mesh = PlanarCartesian2DMesh.centered_square(0.5, primitive.shape[0])
+    return Solver(
+        setup=setup,
+        mesh=mesh,
+        solution=primitive,
+        mode=mode,
+        physics=setup.physics,
+        options=options,
+    )
+
+
+def test_uniform_state(mode):
+    import numpy as np
+    from sailfish.kernel.fixtures import assert_close
+
+    primitive = np.zeros([16, 16, 4])
+    primitive[...] = [1.0, 0.3, -0.2, 1.0]
+    solver = euler_solver(primitive, mode)
+
+    for _ in range(10):
+        solver.advance(0.004)
+
+    assert_close(solver.primitive_array(), primitive, "uniform state")
+    print("pass: uniform state")
+
+
+def test_sod_symmetry(mode):
+    import numpy as np
+    from sailfish.kernel.fixtures import assert_close
+
+    # The tube along x, and the same tube along y, with the first two axes
+    # of the primitive data exchanged, and the velocity components.
+    left = [1.0, 0.0, 0.0, 1.0]
+    right = [0.125, 0.0, 0.0, 0.1]
+    along_x = np.zeros([32, 32, 4])
+    along_x[:16] = left
+    along_x[16:] = right
+    along_y = along_x.transpose(1, 0, 2)[..., [0, 2, 1, 3]].copy()
+
+    solver_x = euler_solver(along_x, mode)
+    solver_y = euler_solver(along_y, mode)
+
+    for _ in range(20):
+        solver_x.advance(0.004)
+        solver_y.advance(0.004)
+
+    px = solver_x.primitive_array()
+    py = solver_y.primitive_array()
+    assert not np.allclose(px, along_x), "the shock tube evolves"
+    assert_close(py, px.transpose(1, 0, 2)[..., [0, 2, 1, 3]], "sod x-y symmetry")
+    print("pass: sod symmetry")
+
+
+def test_two_temperature(mode):
+    import numpy as np
+    from sailfish.kernel.fixtures import assert_close
+
+    # The pressures relax as dp2/dt = k (p - p2), and the gas loses the
+    # energy the second component gains, so the total energy density is
+    # constant, and in a gas at rest p - p2 decays at the rate
+    # k (1 + (gamma - 1) / (gamma2 - 1)).
+    gamma, gamma2, k = 1.4, 5.0 / 3.0, 2.0
+    primitive = np.zeros([8, 8, 5])
+    primitive[...] = [1.0, 0.0, 0.0, 1.0, 0.5]
+    options = dict(
+        two_temperature=True,
+        second_gamma_law_index=gamma2,
+        energy_exchange_rate=k,
+    )
+    solver = euler_solver(primitive, mode, options)
+    assert solver.primitive_fields[-1].name == "pressure2"
+
+    for _ in range(200):
+        solver.advance(1e-3)
+
+    p, p2 = solver.primitive_array()[..., 3:].transpose(2, 0, 1)
+    energy = p / (gamma - 1.0) + p2 / (gamma2 - 1.0)
+    rate = k * (1.0 + (gamma - 1.0) / (gamma2 - 1.0))
+    expected = 0.5 * np.exp(-rate * solver.time)
+    assert_close(energy, 1.0 / (gamma - 1.0) + 0.5 / (gamma2 - 1.0), "total energy")
+    assert_close(p - p2, expected, "pressure relaxation", tol=1e-5)
+    print("pass: two temperature")
+
+
+def main():
+    from sailfish.kernel.fixtures import parse_test_args
+
+    args = parse_test_args()
+
+    test_uniform_state(args.mode)
+    test_sod_symmetry(args.mode)
+    test_two_temperature(args.mode)
+
+
+if __name__ == "__main__":
+    main()
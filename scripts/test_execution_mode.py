@@ -0,0 +1,65 @@
+"""
+Check the resolution of execution modes: an available mode is used as it is,
+an unavailable one is rejected, or replaced by the best available mode if the
+fallback is enabled, and the build info of a run which fell back records the
+requested mode along with the one it ran in.
+"""
+
+import sys
+
+sys.path.insert(1, ".")
+
+
+def test_available_mode():
+    from sailfish.kernel.system import execution_mode_problem, resolve_execution_mode
+
+    assert execution_mode_problem("cpu") is None
+    assert execution_mode_problem("warp-drive").startswith("unknown")
+    assert resolve_execution_mode("cpu") == "cpu"
+    assert resolve_execution_mode("threads", fallback=True) == "threads"
+    print("pass: available mode")
+
+
+def test_fallback():
+    from sailfish.kernel import system
+
+    original = system.execution_mode_problem
+    missing = dict(gpu="no gpu device is visible")
+    system.execution_mode_problem = lambda mode: missing.get(mode)
+
+    try:
+        try:
+            system.resolve_execution_mode("gpu")
+        except ValueError as e:
+            assert "no gpu device is visible" in str(e), e
+        else:
+            raise AssertionError("gpu mode should be rejected")
+
+        assert system.resolve_execution_mode("gpu", fallback=True) == "omp"
+        missing["omp"] = "the C compiler doesn't support OpenMP"
+        assert system.resolve_execution_mode("gpu", fallback=True) == "cpu"
+        assert system.resolve_execution_mode("omp", fallback=True) == "cpu"
+    finally:
+        system.execution_mode_problem = original
+
+    print("pass: fallback")
+
+
+def test_build_info():
+    from sailfish.kernel.system import build_info
+
+    assert "requested_execution_mode" not in build_info("cpu", "cpu")
+    info = build_info("cpu", "gpu")
+    assert info["execution_mode"] == "cpu"
+    assert info["requested_execution_mode"] == "gpu"
+    print("pass: build info")
+
+
+def main():
+    test_available_mode()
+    test_fallback()
+    test_build_info()
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,174 @@
+"""
+Check the snapshot files which can be read without sailfish.
+
+The metadata of a snapshot is made of plain values, with enum members by
+name and unwritable items left out, and an `.npz` file reads back with the
+same primitive data and metadata. The VTK and XDMF files for ParaView have
+the primitive data at the right zones, and the XDMF vertex coordinates are
+those of the mesh.
+"""
+
+import sys
+
+sys.path.insert(1, ".")
+
+
+def fake_snapshot(primitive=None):
+    from sailfish.fields import SURFACE_DENSITY, VELOCITY_X, VELOCITY_Y, field_dicts
+    from sailfish.mesh import PlanarCartesian2DMesh
+    from sailfish.physics.circumbinary import PointMass, SinkModel
+
+    return dict(
+        time=1.5,
+        iteration=12,
+        primitive=primitive,
+        primitive_fields=field_dicts([SURFACE_DENSITY, VELOCITY_X, VELOCITY_Y]),
+        mesh=PlanarCartesian2DMesh(-1.0, -1.0, 1.0, 1.0, 4, 2),
+        setup_name="kitp-code-comparison",
+        solver="cbdiso_2d",
+        model_parameters=dict(mach_number=10.0, sink_model="torque_free"),
+        point_masses=(
+            PointMass(mass=0.5, position_x=0.5, sink_model=SinkModel.TORQUE_FREE),
+            PointMass(mass=0.5, position_x=-0.5, sink_model=SinkModel.TORQUE_FREE),
+        ),
+    )
+
+
+def test_metadata():
+    from sailfish.io import snapshot_metadata
+    from sailfish.physics.circumbinary import EquationOfState
+
+    physics = dict(
+        eos_type=EquationOfState.LOCALLY_ISOTHERMAL,
+        mach_number=10.0,
+        point_mass_function=print,
+    )
+    metadata = snapshot_metadata(fake_snapshot(), physics)
+
+    assert metadata["fields"] == ["sigma", "vx", "vy"]
+    assert metadata["mesh"]["shape"] == [4, 2]
+    assert metadata["mesh"]["spacing"] == [0.5, 1.0]
+    assert metadata["eos"] == dict(eos_type="LOCALLY_ISOTHERMAL", mach_number=10.0)
+    assert [m["position_x"] for m in metadata["point_masses"]] == [0.5, -0.5]
+    assert metadata["point_masses"][0]["sink_model"] == "TORQUE_FREE"
+    print("pass: metadata")
+
+
+def test_npz_round_trip():
+    import os
+    import tempfile
+    import numpy as np
+    from sailfish.io import read_snapshot_file, snapshot_metadata, write_npz
+
+    primitive = np.random.default_rng(1).uniform(size=[4, 2, 3])
+    snapshot = fake_snapshot(primitive)
+
+    with tempfile.TemporaryDirectory() as directory:
+        filename = os.path.join(directory, "snap.0000.npz")
+        write_npz(filename, snapshot)
+        result = read_snapshot_file(filename)
+
+    assert np.array_equal(result.pop("primitive"), primitive)
+    assert result == snapshot_metadata(snapshot)
+    print("pass: npz round trip")
+
+
+def test_mesh_vertices():
+    from math import pi
+    import numpy as np
+    from sailfish.io import mesh_vertices
+    from sailfish.mesh import LogSphericalMesh, PlanarCartesianMesh
+
+    x, y = mesh_vertices(fake_snapshot()["mesh"], 0.0)
+    assert x.shape == y.shape == (5, 3)
+    assert np.allclose(x[:, 0], [-1.0, -0.5, 0.0, 0.5, 1.0])
+    assert np.allclose(y[0], [-1.0, 0.0, 1.0])
+
+    x, y = mesh_vertices(PlanarCartesianMesh(0.0, 1.0, 4), 0.0)
+    assert np.allclose(x, [0.0, 0.25, 0.5, 0.75, 1.0]) and not y.any()
+
+    mesh = LogSphericalMesh(1.0, 10.0, 10, polar_grid=True, polar_extent=pi)
+    x, y = mesh_vertices(mesh, 0.0)
+    assert x.shape == (mesh.shape[0] + 1, mesh.shape[1] + 1)
+    assert np.allclose(x[:, 0], 0.0) and np.allclose(y[:, 0], mesh.faces())
+    assert np.allclose(x * x + y * y, np.array(mesh.faces())[:, None] ** 2)
+    print("pass: mesh vertices")
+
+
+def test_vti():
+    import os
+    import tempfile
+    import numpy as np
+    from base64 import b64decode
+    from xml.etree import ElementTree
+    from sailfish.io import write_vti
+
+    primitive = np.random.default_rng(2).uniform(size=[4, 2, 3])
+
+    with tempfile.TemporaryDirectory() as directory:
+        filename = os.path.join(directory, "snap.0000.vti")
+        write_vti(filename, fake_snapshot(primitive))
+        root = ElementTree.parse(filename).getroot()
+
+    image = root.find("ImageData")
+    assert image.get("WholeExtent") == "0 4 0 2 0 0"
+    assert image.get("Origin") == "-1.0 -1.0 0.0"
+    assert image.get("Spacing") == "0.5 1.0 1.0"
+
+    for n, array in enumerate(root.iter("DataArray")):
+        if array.get("Name") == "TimeValue":
+            assert float(array.text) == 1.5
+            continue
+        data = b64decode(array.text)[8:]
+        field = np.frombuffer(data, dtype="<f8").reshape(2, 4).T
+        assert np.array_equal(field, primitive[..., n - 1])
+
+    print("pass: vti")
+
+
+def test_xdmf():
+    import os
+    import tempfile
+    import numpy as np
+    from xml.etree import ElementTree
+    from sailfish.io import write_xdmf
+
+    primitive = np.random.default_rng(3).uniform(size=[4, 2, 3])
+    snapshot = fake_snapshot(primitive)
+
+    def read(item, binary):
+        offset = int(item.get("Seek"))
+        shape = [int(n) for n in item.get("Dimensions").split()]
+        count = int(np.prod(shape))
+        return np.frombuffer(binary, "<f8", count, offset).reshape(shape)
+
+    with tempfile.TemporaryDirectory() as directory:
+        filename = os.path.join(directory, "snap.0000.xdmf")
+        write_xdmf(filename, snapshot)
+        grid = ElementTree.parse(filename).getroot().find("Domain/Grid")
+
+        with open(os.path.join(directory, "snap.0000.bin"), "rb") as f:
+            binary = f.read()
+
+    assert grid.find("Topology").get("Dimensions") == "5 3"
+    assert float(grid.find("Time").get("Value")) == 1.5
+    x, y = [read(item, binary) for item in grid.find("Geometry")]
+    assert np.allclose(x[:, 0], [-1.0, -0.5, 0.0, 0.5, 1.0])
+
+    for n, attribute in enumerate(grid.iter("Attribute")):
+        assert attribute.get("Name") == ["sigma", "vx", "vy"][n]
+        assert np.array_equal(read(attribute[0], binary), primitive[..., n])
+
+    print("pass: xdmf")
+
+
+def main():
+    test_metadata()
+    test_npz_round_trip()
+    test_mesh_vertices()
+    test_vti()
+    test_xdmf()
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,243 @@
+"""
+Check the Kepler equation solver and the two-body orbits built on it.
+
+The solver is checked over a grid of mean anomalies and eccentricities up to
+0.999, including mean anomalies in other orbits than the first. The orbits
+are checked for conservation of energy and angular momentum, and against the
+component trajectories of `OrbitalElements`, as are the live point masses
+which solvers integrate themselves.
+"""
+
+import sys
+from math import pi, sin, sqrt
+
+sys.path.insert(1, ".")
+
+
+def test_solve_kepler_equation():
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.physics.kepler import solve_kepler_equation
+
+    for e in [0.0, 0.1, 0.5, 0.9, 0.99, 0.999]:
+        for n in range(-200, 201):
+            m = n * 0.0631
+            E = solve_kepler_equation(m, e)
+            assert_close(E - e * sin(E), m, f"Kepler equation e={e} M={m}")
+            assert abs(E - m) <= e + 1e-12
+    print("pass: Kepler equation")
+
+
+def test_relative_orbit():
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.physics.kepler import relative_orbit
+
+    a = 2.0
+    m = 3.0
+
+    for e in [0.0, 0.5, 0.95]:
+        energy = -m / (2.0 * a)
+        angular_momentum = sqrt(m * a * (1.0 - e * e))
+
+        for n in range(100):
+            x, y, vx, vy = relative_orbit(n * 0.1, e, a, m)
+            r = sqrt(x * x + y * y)
+            what = f"relative orbit e={e}"
+            assert_close(0.5 * (vx * vx + vy * vy) - m / r, energy, f"{what} energy")
+            assert_close(x * vy - y * vx, angular_momentum, f"{what} ang. momentum")
+
+        x, y, _, _ = relative_orbit(0.0, e, a, m)
+        assert_close(x, -a * (1.0 - e), f"relative orbit e={e} periapse")
+    print("pass: relative orbit")
+
+
+def test_orbital_elements():
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.physics.kepler import OrbitalElements, relative_orbit
+
+    elements = OrbitalElements(
+        semimajor_axis=1.0, total_mass=1.0, mass_ratio=0.5, eccentricity=0.7
+    )
+
+    for n in range(50):
+        t = n * 0.3
+        c1, c2 = elements.orbital_state(t)
+        x, y, vx, vy = relative_orbit(elements.omega * t, 0.7)
+        assert_close(c2.position_x - c1.position_x, x, "orbital state x")
+        assert_close(c2.position_y - c1.position_y, y, "orbital state y")
+        assert_close(c2.velocity_x - c1.velocity_x, vx, "orbital state vx")
+        assert_close(c2.velocity_y - c1.velocity_y, vy, "orbital state vy")
+
+    for t in [0.0, elements.period]:
+        state = elements.orbital_state(t)
+        assert_close(state.separation, 0.3, f"periapse separation at t={t}")
+        assert_close(state.total_mass, 1.0, "total mass")
+        assert_close(state.mass_ratio, 0.5, "mass ratio")
+        assert_close(state.total_energy, -(2.0 / 3.0) * (1.0 / 3.0) / 2.0, "energy")
+    print("pass: orbital elements")
+
+
+def test_evolving_orbit():
+    from math import pi, sqrt
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.physics.kepler import EvolvingOrbit, OrbitalElements
+
+    elements = OrbitalElements(
+        semimajor_axis=1.0, total_mass=1.0, mass_ratio=0.5, eccentricity=0.3
+    )
+
+    # Without drift, it's the Kepler orbit of the elements.
+    fixed = EvolvingOrbit(elements)
+
+    for t in [0.0, 1.7, 40.0]:
+        for c, d in zip(fixed.orbital_state(t), elements.orbital_state(t)):
+            for u, v in zip(c, d):
+                assert_close(u, v, "fixed orbit", tol=1e-10)
+
+    # A shrinking orbit speeds up: its mean anomaly is the integral of the
+    # frequency, 2 / (adot sqrt(a)) + constant, and the periapse separation
+    # follows the elements at each time.
+    orbit = EvolvingOrbit(elements, semimajor_axis_rate=-1e-3, eccentricity_rate=1e-3)
+    t = 200.0
+    a = 1.0 - 1e-3 * t
+    assert_close(orbit.mean_anomaly(t), 2.0 / 1e-3 * (1.0 / sqrt(a) - 1.0), "phase")
+
+    t_peri = t
+
+    for _ in range(3):
+        m = orbit.mean_anomaly(t_peri)
+        dm = 2.0 * pi * round(m / (2.0 * pi)) - m
+        t_peri += dm / orbit.elements_at(t_peri).omega
+
+    state = orbit.orbital_state(t_peri)
+    e = orbit.elements_at(t_peri).eccentricity
+    a = orbit.elements_at(t_peri).semimajor_axis
+    assert_close(state.separation, a * (1.0 - e), "evolving periapse", tol=1e-10)
+
+    for time in [-1.0 / 3e-3, 1001.0]:
+        try:
+            orbit.orbital_state(time)
+        except ValueError:
+            continue
+        raise AssertionError(f"the orbit at t={time} should be rejected")
+
+    print("pass: evolving orbit")
+
+
+def test_sub_stepped_orbit():
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.physics.kepler import OrbitalElements
+
+    # An orbit with e = 0.9 is integrated over one period in 10 large steps,
+    # and compared with the exact orbit, which has its periapse passage at
+    # the end of the period.
+    elements = OrbitalElements(
+        semimajor_axis=1.0, total_mass=1.0, mass_ratio=0.5, eccentricity=0.9
+    )
+    state0 = elements.orbital_state(0.0)
+    state = state0
+
+    for _ in range(10):
+        state = state.advance(0.1 * elements.period)
+
+    e0, l0 = state0.total_energy, state0.angular_momentum
+    assert_close(state.total_energy, e0, "sub-stepped orbit energy", tol=1e-5)
+    assert_close(state.angular_momentum, l0, "sub-stepped orbit ang. mom.")
+    assert_close(state.separation, 0.1, "sub-stepped orbit periapse", tol=1e-2)
+
+    # A uniform external acceleration g moves the center of mass by g t^2 /
+    # 2, and leaves the relative orbit as it was.
+    g = (0.1, -0.2, 0.1, -0.2)
+    state = state0.advance(1.0, external_acceleration=(g, g))
+    exact = elements.orbital_state(1.0)
+
+    for c, c0, what in zip(state, exact, ["primary", "secondary"]):
+        assert_close(c.position_x, c0.position_x + 0.05, f"{what} x", tol=1e-4)
+        assert_close(c.position_y, c0.position_y - 0.10, f"{what} y", tol=1e-4)
+        assert_close(c.velocity_x, c0.velocity_x + 0.1, f"{what} vx", tol=1e-4)
+    print("pass: sub-stepped orbit")
+
+
+def test_live_point_masses():
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.physics.circumbinary import LivePointMasses, Physics, PointMass
+    from sailfish.physics.circumbinary import SinkModel
+    from sailfish.physics.kepler import OrbitalElements
+
+    elements = OrbitalElements(1.0, 1.0, 0.5, 0.3)
+
+    def point_mass_function(time):
+        return tuple(
+            PointMass(sink_model=SinkModel.TORQUE_FREE, sink_rate=10.0, **c._asdict())
+            for c in elements.orbital_state(time)
+        )
+
+    physics = Physics(point_mass_function=point_mass_function)
+    live = LivePointMasses.start(physics, 0.25)
+
+    # The live masses follow the prescribed orbit, over a few iterations and
+    # the stages in them, and keep their sink parameters.
+    t = 0.25
+    for _ in range(4):
+        live.new_iteration(t)
+        for stage in [t, t + 0.5]:
+            for c, c0 in zip(live(stage), point_mass_function(stage)):
+                assert_close(c.position_x, c0.position_x, "live x", tol=1e-4)
+                assert_close(c.velocity_y, c0.velocity_y, "live vy", tol=1e-4)
+                assert c.sink_model == SinkModel.TORQUE_FREE
+        t += 0.5
+
+    # Earlier times give the state at the start of the iteration.
+    assert live(0.0) == live.two_body.point_masses
+
+    try:
+        LivePointMasses.start(Physics(), 0.0)
+    except ValueError:
+        pass
+    else:
+        raise AssertionError("expected an error without point masses")
+
+    print("pass: live point masses")
+
+
+def test_rotating_frame():
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.physics.circumbinary import FramePointMasses, PointMass
+    from sailfish.physics.circumbinary import RotatingFrame
+    from sailfish.physics.kepler import OrbitalElements
+
+    # A circular binary is at rest in the frame rotating with it, in the
+    # frame axes it had at time zero.
+    elements = OrbitalElements(1.0, 1.0, 0.5, 0.0)
+    frame = RotatingFrame(elements.omega)
+
+    def point_mass_function(time):
+        return tuple(PointMass(**c._asdict()) for c in elements.orbital_state(time))
+
+    point_masses = FramePointMasses(frame, point_mass_function)
+
+    for t in [0.0, 0.7, 3.1, 10.0]:
+        for c, c0 in zip(point_masses(t), point_mass_function(0.0)):
+            assert_close(c.position_x, c0.position_x, "frame x")
+            assert_close(c.position_y, c0.position_y, "frame y")
+            assert_close(c.velocity_x, 0.0, "frame vx")
+            assert_close(c.velocity_y, 0.0, "frame vy")
+
+    # Rotating to the frame axes and back is the identity.
+    x, y = frame.from_frame(2.0, *frame.to_frame(2.0, 0.3, -0.4))
+    assert_close(x, 0.3, "rotated x")
+    assert_close(y, -0.4, "rotated y")
+    print("pass: rotating frame")
+
+
+def main():
+    test_solve_kepler_equation()
+    test_relative_orbit()
+    test_orbital_elements()
+    test_evolving_orbit()
+    test_sub_stepped_orbit()
+    test_live_point_masses()
+    test_rotating_frame()
+
+
+if __name__ == "__main__":
+    main()
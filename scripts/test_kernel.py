@@ -36,6 +36,49 @@ PUBLIC void my_3d_kernel(
         data[i * nj * nk + j * nk + k] = i + j + k;
     }
 }
+
+PUBLIC void my_scaled_kernel(
+    int ni,
+    double *data, // :: $.shape == (ni,)
+    double scale)
+{
+    FOR_EACH_1D(ni)
+    {
+        data[i] = scale * i;
+    }
+}
+
+PUBLIC void my_offset_kernel(
+    int ni,
+    double *data, // :: $.shape == (ni,)
+    int nj,
+    int nk)
+{
+    FOR_EACH_1D(ni)
+    {
+        index_t sk = nk;
+        index_t sj = nj * sk;
+        data[i] = (nj - 1) * sj + (nj - 1) * sk + (nk - 1) - i;
+    }
+}
+
+#include "geometry.c"
+
+PUBLIC void my_geometry_kernel(
+    int ni,
+    double *data) // :: $.shape == (ni, 5)
+{
+    FOR_EACH_1D(ni)
+    {
+        double q0 = GEOMETRY_PI * i / ni;
+        double q1 = GEOMETRY_PI * (i + 1) / ni;
+        data[5 * i + 0] = cell_volume_2d(COORDS_SPHERICAL_POLAR, 1.0, 2.0, q0, q1);
+        data[5 * i + 1] = face_area_2d(COORDS_SPHERICAL_POLAR, 2.0, 2.0, q0, q1);
+        data[5 * i + 2] = cell_volume_1d(COORDS_SPHERICAL, 1.0, 2.0);
+        data[5 * i + 3] = cell_volume_2d(COORDS_PLANAR_POLAR, 1.0, 2.0, 2.0 * q0, 2.0 * q1);
+        data[5 * i + 4] = face_area_2d(COORDS_PLANAR_POLAR, 2.0, 2.0, 2.0 * q0, 2.0 * q1);
+    }
+}
 """
 
 
@@ -49,7 +92,9 @@ def main():
     logging.basicConfig(level=logging.INFO)
 
     parser = argparse.ArgumentParser()
-    parser.add_argument("--mode", default="cpu", choices=["cpu", "omp", "gpu"])
+    parser.add_argument(
+        "--mode", default="cpu", choices=["cpu", "omp", "threads", "gpu", "checked"]
+    )
     args = parser.parse_args()
 
     if args.mode == "gpu":
@@ -80,6 +125,79 @@ def main():
     for (i, j, k), x in np.ndenumerate(data_3d):
         assert i + j + k == x
 
+    # A kernel argument baked in as a constant must be given that value.
+    specialized = Library(code, mode=args.mode, constants=dict(scale=2.0))
+    data_1d = xp.zeros([10])
+    specialized.my_scaled_kernel[data_1d.shape](data_1d, 2.0)
+
+    for (i,), x in np.ndenumerate(data_1d.get() if args.mode == "gpu" else data_1d):
+        assert 2.0 * i == x
+
+    try:
+        specialized.my_scaled_kernel[data_1d.shape](data_1d, 3.0)
+        assert False, "expected a ValueError for a mismatched constant"
+    except ValueError:
+        pass
+
+    # Offsets into arrays of more than 2^31 values must not overflow; the
+    # offsets are computed without allocating such an array.
+    from sailfish.mesh import MeshTopology
+
+    for nj, nk in [(46340, 4), (46341, 4), (50000, 4)]:
+        offset = xp.zeros([2])
+        library.my_offset_kernel[offset.shape](offset, nj, nk)
+        offset = offset.get() if args.mode == "gpu" else offset
+        assert offset[0] == nj * nj * nk - 1
+        assert offset[1] == nj * nj * nk - 2
+
+    MeshTopology(shape=(50000, 50000), num_guard=2).validate(num_fields=4)
+
+    # The included geometry functions: polar cells tile a spherical shell,
+    # and their outer faces its surface, 1D volumes are per steradian, and
+    # planar polar cells tile an annulus, and their outer faces its rim.
+    from math import pi
+
+    geometry = xp.zeros([100, 5])
+    library.my_geometry_kernel[geometry.shape[0]](geometry)
+    geometry = geometry.get() if args.mode == "gpu" else geometry
+    assert abs(geometry[:, 0].sum() - 4.0 * pi * 7.0 / 3.0) < 1e-10
+    assert abs(geometry[:, 1].sum() / (16.0 * pi) - 1.0) < 1e-3
+    assert np.allclose(geometry[:, 2], 7.0 / 3.0)
+    assert abs(geometry[:, 3].sum() - 3.0 * pi) < 1e-10
+    assert abs(geometry[:, 4].sum() - 4.0 * pi) < 1e-10
+
+    # The WebAssembly build compiles the solvers' kernels with the included
+    # geometry functions expanded, as a library does.
+    from scripts.build_wasm import wasm_source
+    from sailfish.kernel.library import INCLUDE_DIRECTIVE
+
+    for solver in ["cbdiso_2d", "euler_1d", "srhd_1d", "srhd_2d"]:
+        source = wasm_source(solver)
+        assert not any(INCLUDE_DIRECTIVE.match(l) for l in source.splitlines())
+        assert "COORDS_PLANAR_POLAR" in source, solver
+
+    try:
+        MeshTopology(shape=(2**31, 1), num_guard=2).validate(num_fields=4)
+        assert False, "expected a ValueError for too many zones on an axis"
+    except ValueError:
+        pass
+
+    # With timing enabled, each kernel's calls are counted since the last
+    # report, and the zones it updated give its throughput.
+    from sailfish.kernel.library import enable_kernel_timing, kernel_timings
+
+    enable_kernel_timing()
+    library.my_2d_kernel[data_2d.shape](xp.zeros([10, 20]))
+
+    for _ in range(3):
+        library.my_1d_kernel[data_1d.shape](xp.zeros([10]))
+
+    timings = {t["kernel"]: t for t in kernel_timings()}
+    assert timings["module.my_1d_kernel"]["calls"] == 3
+    assert timings["module.my_2d_kernel"]["calls"] == 1
+    assert all(t["seconds"] >= 0.0 for t in timings.values())
+    assert kernel_timings() == []
+
 
 if __name__ == "__main__":
     main()
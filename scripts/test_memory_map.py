@@ -0,0 +1,88 @@
+"""
+Check the memory-mapped host arrays: with a memory map directory, large
+arrays are backed by files there, which are unlinked so nothing is left
+behind, and a solver with its patches mapped takes the same steps as one in
+memory.
+"""
+
+import sys
+
+sys.path.insert(1, ".")
+
+
+def is_mapped(a):
+    import mmap
+
+    while a is not None and not isinstance(a, mmap.mmap):
+        a = getattr(a, "base", None)
+
+    return a is not None
+
+
+def test_host_zeros(directory):
+    from os import listdir
+    from sailfish.kernel import library
+    from sailfish.kernel.system import configure_build
+
+    configure_build(memory_map_directory=directory)
+    shape = (1024, 1024, 3)
+    a = library.host_zeros(shape)
+    assert is_mapped(a)
+    assert a.shape == shape and not a.any()
+    a[...] = 1.0
+    assert a.sum() == a.size
+    assert listdir(directory) == []
+
+    small = library.host_zeros((16, 16, 3))
+    assert not is_mapped(small)
+
+    try:
+        configure_build(memory_map_directory=f"{directory}/missing")
+    except ValueError:
+        pass
+    else:
+        raise AssertionError("a missing directory should be rejected")
+
+    configure_build()
+    assert not library.is_memory_mapped(shape)
+    print("pass: host zeros")
+
+
+def test_mapped_solver(directory):
+    import numpy as np
+    from sailfish.driver import user_build_config
+    from sailfish.interactive import Simulation
+    from sailfish.kernel import library
+    from sailfish.kernel.system import configure_build
+
+    # The simulation configures the build from the user's build options.
+    def run(memory_map_directory):
+        user_build_config["memory_map_directory"] = memory_map_directory
+        sim = Simulation("circumbinary-disk", resolution=64)
+        assert is_mapped(sim.solver.patches[0].conserved0) == bool(memory_map_directory)
+        sim.step(10)
+        return sim.solver.primitive_array()
+
+    threshold = library.MEMORY_MAP_MIN_BYTES
+    library.MEMORY_MAP_MIN_BYTES = 0
+
+    try:
+        assert np.array_equal(run(directory), run(None))
+    finally:
+        library.MEMORY_MAP_MIN_BYTES = threshold
+        user_build_config.pop("memory_map_directory")
+        configure_build()
+
+    print("pass: mapped solver")
+
+
+def main():
+    from tempfile import TemporaryDirectory
+
+    with TemporaryDirectory() as directory:
+        test_host_zeros(directory)
+        test_mapped_solver(directory)
+
+
+if __name__ == "__main__":
+    main()
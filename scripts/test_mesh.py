@@ -0,0 +1,106 @@
+"""
+Check the decomposition of meshes into patches.
+
+The patches of a tiled mesh are contiguous, cover its first axis, and differ
+in size by at most one zone. A mesh can't be tiled into patches narrower than
+the guard zones which are exchanged between neighbors. The meshes of the
+ranks of a distributed run fit together into the whole mesh.
+"""
+
+import sys
+
+sys.path.insert(1, ".")
+
+
+def test_tile():
+    from sailfish.mesh import LogSphericalMesh, PlanarCartesianMesh
+    from sailfish.mesh import LogPolar2DMesh, PlanarCartesian2DMesh
+
+    meshes = [
+        PlanarCartesianMesh(num_zones=103),
+        PlanarCartesian2DMesh(ni=50, nj=7),
+        LogSphericalMesh(r0=1.0, r1=10.0, num_zones_per_decade=64),
+        LogPolar2DMesh(r0=0.1, r1=10.0, nj=64),
+    ]
+
+    for mesh in meshes:
+        for num_patches in (1, 3, 8):
+            tiles = mesh.tile(num_patches)
+            sizes = [i1 - i0 for i0, i1 in tiles]
+            assert len(tiles) == num_patches
+            assert tiles[0][0] == 0 and tiles[-1][1] == mesh.shape[0]
+            assert all(a[1] == b[0] for a, b in zip(tiles[:-1], tiles[1:]))
+            assert max(sizes) - min(sizes) <= 1
+
+    print("pass: tile")
+
+
+def test_tile_rejects_narrow_patches():
+    from sailfish.mesh import PlanarCartesianMesh
+
+    mesh = PlanarCartesianMesh(num_zones=10)
+    assert len(mesh.tile(10, num_guard=1)) == 10
+
+    for num_patches, num_guard in [(6, 2), (0, 2), (2.5, 2)]:
+        try:
+            mesh.tile(num_patches, num_guard)
+        except ValueError:
+            continue
+        raise AssertionError(f"{num_patches} patches should be rejected")
+
+    print("pass: tile rejects narrow patches")
+
+
+def test_rank_meshes():
+    from sailfish.distributed import rank_mesh
+    from sailfish.mesh import PlanarCartesianMesh
+
+    mesh = PlanarCartesianMesh(x0=-1.0, x1=2.0, num_zones=101)
+    faces = [mesh.x0]
+
+    for index_range in mesh.tile(4):
+        sub = rank_mesh(mesh, index_range)
+        assert abs(sub.x0 - faces[-1]) < 1e-14
+        faces.extend(sub.faces()[1:])
+
+    assert all(abs(a - b) < 1e-12 for a, b in zip(faces, mesh.faces()))
+    assert len(faces) == len(mesh.faces())
+    print("pass: rank meshes")
+
+
+def test_log_polar_mesh():
+    from math import log, pi
+    from sailfish.mesh import LogPolar2DMesh
+
+    # The zones are nearly square, and the faces of each ring, and the rings
+    # themselves, add up to the circles and the annulus.
+    mesh = LogPolar2DMesh(r0=0.5, r1=8.0, nj=128).validate()
+    assert mesh.shape == (round(log(16.0) / mesh.dphi), 128)
+    assert abs(mesh.dlogr / mesh.dphi - 1.0) < 0.5 / mesh.shape[0]
+    assert abs(mesh.radial_faces()[-1] - 8.0) < 1e-12
+
+    arcs = sum(f.area for f in mesh.iter_faces(axis=0) if f.index[0] == 0)
+    assert abs(arcs - pi) < 1e-12
+
+    x, y = mesh.cell_coordinates(0, 0)
+    assert abs((x * x + y * y) ** 0.5 - mesh.zone_radius(0)) < 1e-12
+    assert 0.5 < mesh.zone_radius(0) < mesh.radial_faces(0, 1)[1]
+
+    try:
+        LogPolar2DMesh(r0=0.0, r1=1.0).validate()
+    except ValueError:
+        print("pass: log polar mesh")
+        return
+
+    raise AssertionError("a log-polar mesh needs a positive inner radius")
+
+
+def main():
+    test_tile()
+    test_tile_rejects_narrow_patches()
+    test_rank_meshes()
+    test_log_polar_mesh()
+
+
+if __name__ == "__main__":
+    main()
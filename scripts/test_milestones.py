@@ -0,0 +1,108 @@
+"""
+Check the milestone checkpoints: they fall on whole multiples of the
+interval, a run restarted on one doesn't write it again, restarts from a
+directory skip them, and the driver shortens the last step before each one to
+write it at the exact orbit. The driver arguments of older checkpoints are
+restored into the same fields.
+"""
+
+import sys
+
+sys.path.insert(1, ".")
+
+
+def test_next_milestone_time():
+    from math import pi
+    from sailfish.driver import next_milestone_time
+
+    interval = 2.0 * pi * 5
+    assert next_milestone_time(0.0, interval) == interval
+    assert next_milestone_time(1.5 * interval, interval) == 2.0 * interval
+    assert next_milestone_time(3.0 * interval, interval) == 4.0 * interval
+    assert next_milestone_time(3.0 * interval * (1.0 - 1e-15), interval) == (
+        4.0 * interval
+    )
+    print("pass: next milestone time")
+
+
+def test_restart_directory():
+    import os
+    import pickle
+    import tempfile
+    from sailfish.driver import newest_chkpt_in_directory
+
+    with tempfile.TemporaryDirectory() as tmp:
+        for name in ["chkpt.0001.pk", "chkpt.0002.pk", "chkpt.orbit.0500.pk"]:
+            with open(os.path.join(tmp, name), "wb") as f:
+                pickle.dump(dict(), f)
+
+        assert newest_chkpt_in_directory(tmp).endswith("chkpt.0002.pk")
+
+    print("pass: restart directory")
+
+
+def test_driver_args_layout():
+    from sailfish.driver import DriverArgs
+
+    # Checkpoints pickle the driver arguments, which are restored by position,
+    # so the fields of older versions have to keep their places.
+    old_fields = (
+        "setup_name",
+        "chkpt_file",
+        "model_parameters",
+        "solver_options",
+        "cfl_number",
+        "end_time",
+        "execution_mode",
+        "fold",
+        "resolution",
+        "num_patches",
+        "events",
+        "new_timestep_cadence",
+        "verbose_output",
+    )
+    assert DriverArgs._fields[: len(old_fields)] == old_fields
+    assert DriverArgs(*range(len(old_fields))).verbose_output == len(old_fields) - 1
+    print("pass: driver args layout")
+
+
+def test_milestone_times(mode):
+    from math import pi
+    from sailfish.driver import DriverArgs, simulate
+    import sailfish.setups
+
+    driver = DriverArgs(
+        setup_name="circumbinary-disk",
+        model_parameters=dict(domain_radius=2.0),
+        execution_mode=mode,
+        resolution=32,
+        end_time=2.1,
+        fold=50,
+        milestone_orbits=1,
+    )
+    milestones = [
+        (number, state.solver.time)
+        for name, number, state in simulate(driver)
+        if name == "milestone"
+    ]
+    assert [number for number, _ in milestones] == [1, 2], milestones
+
+    for number, time in milestones:
+        assert abs(time - 2.0 * pi * number) < 1e-12 * time, (number, time)
+
+    print("pass: milestone times")
+
+
+def main():
+    from sailfish.kernel.fixtures import parse_test_args
+
+    args = parse_test_args()
+
+    test_next_milestone_time()
+    test_restart_directory()
+    test_driver_args_layout()
+    test_milestone_times(args.mode)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,92 @@
+"""
+Check the synthetic observables of a 2D disk.
+
+A thin ring in circular rotation, seen edge-on, has a line profile which is
+symmetric about zero velocity and peaks at the orbital speed (the double horn
+of a rotating disk), and seen face-on its line is unshifted. The image and
+the line profile both sum to the luminosity, when no emission falls outside
+the velocity range.
+"""
+
+import sys
+from contextlib import nullcontext
+
+sys.path.insert(1, ".")
+
+
+def ring_patch(mesh, radius=2.0, width=0.2, speed=0.7):
+    import numpy as np
+
+    x, y = mesh.cell_center_arrays()
+    r = (x * x + y * y) ** 0.5
+    sigma = np.exp(-(((r - radius) / width) ** 2))
+    vx = -speed * y / r
+    vy = +speed * x / r
+    return dict(
+        sigma=sigma,
+        vx=vx,
+        vy=vy,
+        x=x,
+        y=y,
+        xp=np,
+        execution_context=nullcontext(),
+    )
+
+
+def test_line_profiles():
+    import numpy as np
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.mesh import PlanarCartesian2DMesh
+    from sailfish.observables import Observer, SyntheticObservables
+
+    mesh = PlanarCartesian2DMesh(x0=-4.0, y0=-4.0, x1=4.0, y1=4.0, ni=200, nj=200)
+    patch = ring_patch(mesh)
+    luminosity = (patch["sigma"] ** 2).sum() * mesh.dx * mesh.dy
+
+    edge_on = SyntheticObservables(mesh, Observer(inclination=90.0, azimuth=30.0))
+    face_on = SyntheticObservables(mesh, Observer(inclination=0.0))
+    edge_on.add(0.0, [patch])
+    face_on.add(0.0, [patch])
+
+    for obs in (edge_on, face_on):
+        arrays = obs.arrays()
+        assert_close(arrays["luminosity"][0], luminosity, "luminosity")
+        assert_close(arrays["image"][0].sum(), luminosity, "image sum")
+        assert_close(arrays["line_profile"][0].sum(), luminosity, "line sum")
+
+    edges = edge_on.velocity_edges
+    v = 0.5 * (edges[1:] + edges[:-1])
+    line = edge_on.arrays()["line_profile"][0]
+    assert_close(np.abs(v[np.argmax(line)]), 0.7, "edge-on peak", tol=0.03)
+    assert_close((v * line).sum() / line.sum(), 0.0, "edge-on centroid", tol=1e-3)
+
+    line = face_on.arrays()["line_profile"][0]
+    assert_close(line.max(), line.sum(), "face-on line in one bin")
+    print("pass: line profiles")
+
+
+def test_observer():
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.observables import Observer
+
+    x, y, v = Observer(inclination=90.0, azimuth=0.0).project(0.0, 1.0, -1.0, 0.0)
+    assert_close(x, 1.0, "sky x on the line of nodes")
+    assert_close(v, 1.0, "gas moving away from the observer is red-shifted")
+
+    for bad in [dict(inclination=120.0), dict(image_bins=0), dict(velocity_max=0)]:
+        try:
+            Observer(**bad).validate()
+        except ValueError:
+            continue
+        raise AssertionError(f"observer {bad} should be rejected")
+
+    print("pass: observer")
+
+
+def main():
+    test_observer()
+    test_line_profiles()
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,91 @@
+"""
+Check the runtime parameter reflection: named tuple fields are described by
+their attribute docstrings, the driver, setup, and solver parameters are
+listed with their current values, and misspelled or mistyped values are
+rejected.
+"""
+
+import sys
+from typing import NamedTuple
+
+sys.path.insert(1, ".")
+
+
+class Example(NamedTuple):
+    """
+    A named tuple with documented fields.
+    """
+
+    density_floor: float = 1e-12
+    """ Lower limit of the density """
+    rk_order: int = 2
+    """ Order of the time stepping """
+    boost: bool = False
+
+
+def test_named_tuple_parameters():
+    from sailfish.parameters import named_tuple_parameters
+
+    parameters = named_tuple_parameters(Example, "solver", dict(rk_order=3))
+    assert [p.name for p in parameters] == ["density_floor", "rk_order", "boost"]
+    assert [p.type for p in parameters] == ["float", "int", "bool"]
+    assert [p.value for p in parameters] == [1e-12, 3, False]
+    assert parameters[1].default == 2
+    assert parameters[0].about == "Lower limit of the density"
+    assert parameters[2].about == ""
+    print("pass: named tuple parameters")
+
+
+def test_check_parameters():
+    from sailfish.parameters import check_parameters, named_tuple_parameters
+
+    parameters = named_tuple_parameters(Example, "solver")
+    check_parameters(parameters, dict(density_floor=1, rk_order=3, boost=True))
+
+    for values, message in [
+        (dict(density_flor=1e-8), "unknown option density_flor, did you mean"),
+        (dict(rk_order=2.5), "option rk_order has type int (got float)"),
+        (dict(rk_order=True), "option rk_order has type int (got bool)"),
+    ]:
+        try:
+            check_parameters(parameters, values, "option")
+        except ValueError as e:
+            assert str(e).startswith(message), e
+            continue
+
+        raise AssertionError(f"{values} should be rejected")
+
+    print("pass: check parameters")
+
+
+def test_runtime_parameters():
+    from sailfish.driver import DriverArgs
+    from sailfish.parameters import format_parameters, runtime_parameters
+    from sailfish.setups.circumbinary_disk import CircumbinaryDisk
+
+    setup = CircumbinaryDisk(mach_number=20.0)
+    driver = DriverArgs(fold=50)
+    parameters = runtime_parameters(setup, driver, dict(rk_order=3))
+    by_name = {(p.scope, p.name): p for p in parameters}
+
+    assert by_name["driver", "fold"].value == 50
+    assert by_name["driver", "fold"].about != ""
+    assert by_name["setup", "mach_number"].value == 20.0
+    assert by_name["solver", "rk_order"].value == 3
+    assert by_name["solver", "density_floor"].about != ""
+    assert {p.scope for p in parameters} == {"driver", "setup", "solver"}
+
+    lines = format_parameters(parameters)
+    assert lines[0] == "driver parameters:"
+    assert "setup parameters:" in lines and "solver parameters:" in lines
+    print("pass: runtime parameters")
+
+
+def main():
+    test_named_tuple_parameters()
+    test_check_parameters()
+    test_runtime_parameters()
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,131 @@
+"""
+Check the static mesh refinement: the regions are snapped to the parent
+faces and rejected if they are badly nested, the prolongation is exact for
+linear data and undone by the restriction, and the refluxing conserves the
+mass of a disk with refined levels around the binary, up to the fluxes
+through the edges of the domain.
+"""
+
+import sys
+
+sys.path.insert(1, ".")
+
+
+def test_snap_region():
+    from sailfish.mesh import PlanarCartesian2DMesh
+    from sailfish.refinement import RefinedRegion, boxes_overlap, snap_region
+
+    mesh = PlanarCartesian2DMesh.centered_square(1.0, 20)
+    box, child = snap_region(mesh, RefinedRegion((-0.25, -0.35), (0.31, 0.3)))
+    assert box == (7, 14, 6, 13), box
+    assert (child.ni, child.nj) == (14, 14)
+    assert abs(child.x0 + 0.3) < 1e-12 and abs(child.x1 - 0.4) < 1e-12
+    assert abs(child.dx - 0.5 * mesh.dx) < 1e-12
+
+    for region in [
+        RefinedRegion((-0.95, -0.5), (0.5, 0.5)),
+        RefinedRegion((0.5, 0.5), (0.2, 0.7)),
+    ]:
+        try:
+            snap_region(mesh, region)
+        except ValueError:
+            continue
+        raise AssertionError(f"{region} should be rejected")
+
+    assert boxes_overlap((2, 6, 2, 6), (7, 9, 2, 6), 2)
+    assert not boxes_overlap((2, 6, 2, 6), (8, 10, 2, 6), 2)
+    print("pass: snap region")
+
+
+def test_prolong_restrict():
+    import numpy as np
+    from sailfish.refinement import (
+        conserved_to_primitive,
+        primitive_to_conserved,
+        prolong,
+    )
+
+    x = np.arange(6.0)[:, None, None]
+    y = np.arange(5.0)[None, :, None]
+    coarse = np.concatenate([1.0 + 0.1 * x + 0.05 * y] * 3, axis=2)
+    fine = prolong(coarse, np)
+    xf = 0.75 + 0.5 * np.arange(8.0)[:, None]
+    yf = 0.75 + 0.5 * np.arange(6.0)[None, :]
+    assert fine.shape == (8, 6, 3)
+    assert np.allclose(fine[..., 0], 1.0 + 0.1 * xf + 0.05 * yf)
+
+    # The conserved data of the prolonged zones average to that of the coarse
+    # zones, so the restriction returns the coarse solution.
+    u = primitive_to_conserved(fine, np)
+    u = u.reshape(4, 2, 3, 2, 3).mean(axis=(1, 3))
+    p = conserved_to_primitive(u, 1e-12, 1e12, np)
+    assert np.allclose(p[..., 0], coarse[1:-1, 1:-1, 0])
+    print("pass: prolong restrict")
+
+
+def test_rejects_bad_setups():
+    from sailfish.setup_base import SetupError
+    from sailfish.setups.circumbinary_disk import CircumbinaryDisk
+
+    for parameters in [
+        dict(refinement_levels=-1),
+        dict(refinement_levels=1, eos="gamma-law"),
+        dict(refinement_levels=1, live_binary=True),
+        dict(refinement_levels=3, refinement_radius=4.0),
+    ]:
+        try:
+            CircumbinaryDisk(**parameters)
+        except SetupError:
+            continue
+        raise AssertionError(f"{parameters} should be rejected")
+
+    regions = CircumbinaryDisk(refinement_levels=2).refinement
+    assert [r.level for r in regions] == [1, 2]
+    assert regions[0].upper == (3.0, 3.0) and regions[1].upper == (1.5, 1.5)
+    print("pass: rejects bad setups")
+
+
+def test_mass_conservation(mode):
+    from sailfish.interactive import Simulation
+
+    sim = Simulation(
+        "circumbinary-disk",
+        model_parameters=dict(
+            domain_radius=4.0,
+            buffer_is_enabled=False,
+            sink_rate=0.0,
+            refinement_levels=2,
+            refinement_radius=0.75,
+        ),
+        execution_mode=mode,
+        resolution=64,
+    )
+    base = sim.solver.levels[0].solver
+    dx, dy = base.mesh.dx, base.mesh.dy
+    mass = sim.solver.conserved_array()[..., 0].sum() * dx * dy
+    sim.step(20)
+
+    # The mass flowing out through the edges of the domain, from the face
+    # fluxes integrated over the iterations.
+    patch = base.patches[0]
+    fx = patch.face_flux_x_total + patch.face_flux_x
+    fy = patch.face_flux_y_total + patch.face_flux_y
+    outflow = (fx[-1, :, 0] - fx[0, :, 0]).sum() * dy
+    outflow += (fy[:, -1, 0] - fy[:, 0, 0]).sum() * dx
+    final = sim.solver.conserved_array()[..., 0].sum() * dx * dy
+    assert abs(final + outflow - mass) < 1e-10 * mass, (final + outflow, mass)
+    print("pass: mass conservation")
+
+
+def main():
+    from sailfish.kernel.fixtures import parse_test_args
+
+    args = parse_test_args()
+    test_snap_region()
+    test_prolong_restrict()
+    test_rejects_bad_setups()
+    test_mass_conservation(args.mode)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,154 @@
+"""
+Check the replay of analyses from the checkpoints of a run.
+
+The checkpoints of a directory are replayed in the order they were written,
+and each one's products are written next to it. The edge fluxes are sums of
+the recorded face fluxes over the mesh edges, and an analysis which needs
+face fluxes is skipped for checkpoints without them. In a run without mass
+sources, the edge fluxes of each checkpoint account for the change of the
+total mass since the previous one, including the last step before it.
+"""
+
+import sys
+
+sys.path.insert(1, ".")
+
+
+def fake_checkpoint(directory, number, face_fluxes):
+    import os
+    import pickle
+    import numpy as np
+    from sailfish.fields import SURFACE_DENSITY, VELOCITY_X, VELOCITY_Y, field_dicts
+    from sailfish.mesh import PlanarCartesian2DMesh
+
+    mesh = PlanarCartesian2DMesh(-1.0, -1.0, 1.0, 1.0, 8, 4)
+    contents = dict(
+        iteration=number,
+        time=float(number),
+        solution=np.ones([8, 4, 3]),
+        primitive=np.ones([8, 4, 3]),
+        primitive_fields=field_dicts([SURFACE_DENSITY, VELOCITY_X, VELOCITY_Y]),
+        face_fluxes=face_fluxes,
+        mesh=mesh,
+    )
+    filename = os.path.join(directory, f"chkpt.{number}.pk")
+
+    with open(filename, "wb") as f:
+        pickle.dump(contents, f)
+
+    return filename
+
+
+def test_checkpoint_order():
+    import os
+    import tempfile
+    from sailfish.replay import checkpoint_files
+
+    with tempfile.TemporaryDirectory() as directory:
+        names = ["chkpt.final.pk", "chkpt.0010.pk", "chkpt.0002.pk", "snap.0001.pk"]
+
+        for name in names:
+            open(os.path.join(directory, name), "w").close()
+
+        names = [os.path.basename(f) for f in checkpoint_files(directory)]
+        assert names == ["chkpt.0002.pk", "chkpt.0010.pk", "chkpt.final.pk"]
+
+    print("pass: checkpoint order")
+
+
+def test_edge_fluxes():
+    import pickle
+    import tempfile
+    import numpy as np
+    from sailfish.replay import replay
+
+    fx = np.zeros([9, 4, 3])
+    fy = np.zeros([8, 5, 3])
+    fx[0, :, 0] = -1.0
+    fx[-1, :, 0] = 2.0
+    fy[:, -1, 1] = 0.5
+    fluxes = dict(x=fx, y=fy, start_time=0.0, end_time=1.0)
+
+    with tempfile.TemporaryDirectory() as directory:
+        fake_checkpoint(directory, "0000", None)
+        fake_checkpoint(directory, "0001", fluxes)
+        written = replay(directory, ["edge-fluxes", "radial-profiles"])
+
+        assert [f.split("/")[-1] for f in written] == [
+            "chkpt.0000.radial-profiles.pk",
+            "chkpt.0001.edge-fluxes.pk",
+            "chkpt.0001.radial-profiles.pk",
+        ]
+
+        with open(written[1], "rb") as f:
+            products = pickle.load(f)
+
+    assert np.allclose(products["left"], [2.0, 0.0, 0.0])
+    assert np.allclose(products["right"], [4.0, 0.0, 0.0])
+    assert np.allclose(products["top"], [0.0, 1.0, 0.0])
+    assert np.allclose(products["total"], [6.0, 1.0, 0.0])
+    print("pass: edge fluxes")
+
+
+def test_mass_budget(mode):
+    import pickle
+    import tempfile
+    from sailfish.driver import DriverArgs, simulate, write_checkpoint
+    from sailfish.event import Recurrence
+    from sailfish.products import load_checkpoint
+    from sailfish.replay import checkpoint_files, replay
+
+    driver = DriverArgs(
+        setup_name="circumbinary-disk",
+        model_parameters=dict(
+            domain_radius=2.0, sink_rate=0.0, buffer_is_enabled=False
+        ),
+        solver_options=dict(record_face_fluxes=True),
+        execution_mode=mode,
+        resolution=32,
+        end_time=0.05,
+        events=dict(checkpoint=Recurrence.from_str("0.02")),
+    )
+
+    with tempfile.TemporaryDirectory() as directory:
+        for name, number, state in simulate(driver):
+            if name == "checkpoint":
+                write_checkpoint(number, directory, state)
+            elif name == "end":
+                write_checkpoint("final", directory, state)
+
+        replay(directory, ["edge-fluxes"])
+        chkpts = [load_checkpoint(f) for f in checkpoint_files(directory)]
+        products = list()
+
+        for f in checkpoint_files(directory)[1:]:
+            with open(f.replace(".pk", ".edge-fluxes.pk"), "rb") as g:
+                products.append(pickle.load(g))
+
+    assert len(chkpts) >= 3
+
+    for c0, c1, p in zip(chkpts[:-1], chkpts[1:], products):
+        mesh = c0["mesh"]
+        m0 = c0.primitive[..., 0].sum() * mesh.dx * mesh.dy
+        m1 = c1.primitive[..., 0].sum() * mesh.dx * mesh.dy
+        outflow = p["total"][0] * (p["end_time"] - p["start_time"])
+        assert p["start_time"] == c0["time"]
+        assert p["end_time"] == c1["time"]
+        assert abs(outflow) > 0.0
+        assert abs(m0 - m1 - outflow) < 1e-12 * m0, (m0 - m1, outflow)
+
+    print("pass: mass budget")
+
+
+def main():
+    from sailfish.kernel.fixtures import parse_test_args
+
+    args = parse_test_args()
+
+    test_checkpoint_order()
+    test_edge_fluxes()
+    test_mass_budget(args.mode)
+
+
+if __name__ == "__main__":
+    main()
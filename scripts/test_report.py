@@ -0,0 +1,109 @@
+"""
+Check the HTML run report, from a recorded log and a stand-in driver state.
+
+The recorder keeps the step and event records and warnings of the log, and
+the report has a plot for each time series column but the time, escapes the
+text it includes, and is one file with no external references.
+"""
+
+import sys
+
+sys.path.insert(1, ".")
+
+
+class FakeSetup:
+    reference_time_scale = 2.0
+
+    def dash_case_class_name(self):
+        return "fake-setup"
+
+    def model_parameter_dict(self):
+        return dict(mach_number=10.0, label="<b>")
+
+
+class FakeSolver:
+    time = 3.0
+    options = dict(cfl_number=0.3)
+    reduction_names = ["time", "mdot", "torque"]
+
+
+class FakeMesh:
+    num_total_zones = 10_000
+
+
+class FakeState:
+    def __init__(self):
+        from sailfish.driver import DriverArgs
+
+        self.setup = FakeSetup()
+        self.solver = FakeSolver()
+        self.mesh = FakeMesh()
+        self.driver = DriverArgs(setup_name="fake-setup", resolution=100)
+        self.iteration = 40
+        self.timeseries = [[0.1 * n, n**2, complex(n, 1)] for n in range(10)]
+
+    def synchronize(self):
+        return self.timeseries
+
+
+def test_recorder():
+    from logging import getLogger, INFO
+    from sailfish.report import RunRecorder
+
+    logger = getLogger("sailfish.test_report")
+    logger.setLevel(INFO)
+    recorder = RunRecorder()
+    logger.addHandler(recorder)
+
+    for n in range(3):
+        data = dict(kind="step", iteration=10 * n, time=0.1 * n, dt=0.01, Mzps=n)
+        logger.info(f"step {n}", extra=dict(data=data))
+
+    event = dict(kind="event", name="checkpoint", number=0, time=0.1)
+    logger.info("event checkpoint 0", extra=dict(data=event, json_only=True))
+    logger.warning("something looks off")
+    logger.removeHandler(recorder)
+
+    assert [s["iteration"] for s in recorder.steps] == [0, 10, 20]
+    assert recorder.events == [event]
+    assert recorder.warnings == ["[test_report] something looks off"]
+    print("pass: recorder")
+    return recorder
+
+
+def test_report(recorder):
+    import os
+    import tempfile
+    from sailfish.report import write_report
+
+    with tempfile.TemporaryDirectory() as directory:
+        filename = os.path.join(directory, "report.html")
+        write_report(filename, FakeState(), recorder, dict(exit_code=0))
+
+        with open(filename) as f:
+            text = f.read()
+
+    assert text.count("<svg") == 3, "a plot for Mzps, mdot, and torque"
+    assert "model.label</td><td>&lt;b&gt;" in text
+    assert "something looks off" in text and "checkpoint" in text
+    assert "mean Mzps</td><td>1.000" in text
+    assert "href=" not in text and "src=" not in text
+    print("pass: report")
+
+
+def test_line_plot():
+    from sailfish.report import line_plot
+
+    flat = line_plot([0.0, 1.0, 2.0], [5.0, 5.0, float("nan")], "flat")
+    assert flat.count(",") == 2, "the point with a nan is left out"
+    assert "no data" in line_plot([], [], "empty")
+    print("pass: line plot")
+
+
+def main():
+    test_report(test_recorder())
+    test_line_plot()
+
+
+if __name__ == "__main__":
+    main()
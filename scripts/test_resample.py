@@ -0,0 +1,147 @@
+"""
+Check the resampling of solutions onto meshes of other resolutions: the
+restriction and the `plm` prolongation conserve the totals, restricting a
+prolongation restores the data, linear profiles are kept by both methods away
+from the edges, bad pairs of meshes are rejected, and the driver restarts a
+checkpoint at a new resolution with `--resample`.
+"""
+
+import sys
+
+sys.path.insert(1, ".")
+
+
+def random_primitive(mesh, seed=1):
+    import numpy as np
+
+    rng = np.random.default_rng(seed)
+    primitive = rng.uniform(-1.0, 1.0, mesh.shape + (4,))
+    primitive[..., 0] = rng.uniform(0.5, 1.5, mesh.shape)
+    return primitive
+
+
+def totals(primitive):
+    u = primitive.copy()
+    u[..., 1:] *= primitive[..., :1]
+    return u.sum(axis=(0, 1))
+
+
+def test_conservation():
+    import numpy as np
+    from sailfish.mesh import PlanarCartesian2DMesh
+    from sailfish.resample import resample
+
+    mesh = PlanarCartesian2DMesh(x0=-1.0, x1=1.0, y0=-0.5, y1=1.0, ni=12, nj=8)
+    fine = mesh._replace(ni=36, nj=16)
+    coarse = mesh._replace(ni=6, nj=4)
+    p = random_primitive(mesh)
+
+    q = resample(p, mesh, fine, "plm")
+    assert q.shape == (36, 16, 4)
+    assert np.allclose(totals(q) / 6.0, totals(p), rtol=1e-12, atol=1e-12)
+    assert np.allclose(resample(q, fine, mesh), p, rtol=1e-12, atol=1e-12)
+    assert (q[..., 0] > 0.0).all()
+    assert q[..., 0].min() >= p[..., 0].min() - 1e-12
+    assert q[..., 0].max() <= p[..., 0].max() + 1e-12
+
+    q = resample(p, mesh, coarse)
+    assert q.shape == (6, 4, 4)
+    assert np.allclose(totals(q) * 4.0, totals(p), rtol=1e-12, atol=1e-12)
+
+    # Refining one axis while coarsening the other.
+    q = resample(p, mesh, mesh._replace(ni=24, nj=4))
+    assert np.allclose(totals(q), totals(p), rtol=1e-12, atol=1e-12)
+    print("pass: conservation")
+
+
+def test_linear_profiles():
+    import numpy as np
+    from sailfish.mesh import PlanarCartesian2DMesh
+    from sailfish.resample import resample
+
+    mesh = PlanarCartesian2DMesh(x0=0.0, x1=1.0, y0=0.0, y1=1.0, ni=8, nj=8)
+    fine = mesh._replace(ni=32, nj=32)
+
+    def linear(m):
+        x, y = m.cell_center_arrays()
+        return np.stack([2.0 + x - 0.5 * y, np.ones_like(x)], axis=-1)
+
+    for method in ["plm", "bilinear"]:
+        q = resample(linear(mesh), mesh, fine, method)
+        assert np.allclose(q[4:-4, 4:-4], linear(fine)[4:-4, 4:-4], rtol=1e-12)
+
+    print("pass: linear profiles")
+
+
+def test_bad_meshes():
+    from sailfish.mesh import LogPolar2DMesh, PlanarCartesian2DMesh
+    from sailfish.resample import resample
+
+    mesh = PlanarCartesian2DMesh(x0=-1.0, x1=1.0, y0=-1.0, y1=1.0, ni=8, nj=8)
+    p = random_primitive(mesh)
+
+    for new_mesh, method in [
+        (mesh._replace(ni=12), "plm"),
+        (mesh._replace(x1=2.0, ni=16, nj=16), "plm"),
+        (LogPolar2DMesh(r0=0.1, r1=1.0, nj=16), "plm"),
+        (mesh._replace(ni=16, nj=16), "spline"),
+    ]:
+        try:
+            resample(p, mesh, new_mesh, method)
+        except ValueError:
+            continue
+        raise AssertionError(f"resampling onto {new_mesh} should be rejected")
+
+    print("pass: bad meshes")
+
+
+def test_restart(mode):
+    import os
+    import tempfile
+    import numpy as np
+    from sailfish.driver import ConfigurationError, DriverArgs, simulate
+    from sailfish.driver import write_checkpoint
+    from sailfish.resample import resample
+
+    driver = DriverArgs(
+        setup_name="circumbinary-disk",
+        model_parameters=dict(domain_radius=2.0),
+        execution_mode=mode,
+        resolution=32,
+        end_time=0.05,
+    )
+    *_, (_, _, state) = simulate(driver)
+
+    with tempfile.TemporaryDirectory() as tmp:
+        write_checkpoint(0, tmp, state)
+        chkpt_file = os.path.join(tmp, "chkpt.0000.pk")
+
+        try:
+            list(simulate(DriverArgs(chkpt_file=chkpt_file, resolution=64)))
+        except ConfigurationError:
+            pass
+        else:
+            raise AssertionError("a restart at a new resolution needs --resample")
+
+        driver = DriverArgs(chkpt_file=chkpt_file, resolution=64, resample="plm")
+        *_, (_, _, restart) = simulate(driver)
+
+    expected = resample(state.solver.solution, state.mesh, restart.mesh)
+    assert restart.mesh.shape == (64, 64)
+    assert restart.solver.time == state.solver.time
+    assert np.allclose(restart.solver.solution, expected, rtol=1e-14, atol=0.0)
+    print("pass: restart")
+
+
+def main():
+    from sailfish.kernel.fixtures import parse_test_args
+
+    args = parse_test_args()
+    test_conservation()
+    test_linear_profiles()
+    test_bad_meshes()
+    test_restart(args.mode)
+
+
+if __name__ == "__main__":
+    main()
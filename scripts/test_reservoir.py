@@ -0,0 +1,97 @@
+"""
+Check the 0D inner disk reservoir: its drain conserves mass and angular
+momentum, its edge speed is capped at the Keplerian speed, and coupled to
+the cbdiso_2d solver, it's written to the excised zones, restored by a
+reset, and carried through a restart.
+"""
+
+import sys
+
+sys.path.insert(1, ".")
+
+
+def test_advance():
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.physics.reservoir import Reservoir
+
+    r = Reservoir(1.0, 2.0, accreted_specific_angular_momentum=0.5, mass=1.0)
+    total_mass = r.mass
+    total_l = r.angular_momentum
+
+    for _ in range(10):
+        r = r.advance(0.1, 0.01, 0.02)
+        total_mass += 0.01
+        total_l += 0.02
+
+    assert_close(r.mass + r.accreted_mass, total_mass, "mass")
+    assert_close(r.angular_momentum + r.accreted_angular_momentum, total_l, "l")
+    assert_close(r.accreted_angular_momentum, 0.5 * r.accreted_mass, "drained l")
+    assert 0.0 < r.mass < total_mass
+    print("pass: reservoir advance")
+
+
+def test_edge_velocity():
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.physics.reservoir import Reservoir
+
+    r = Reservoir(radius=0.5, viscous_time=1.0, mass=2.0, angular_momentum=0.2)
+    assert_close(r.edge_velocity(1.0), 0.2, "edge speed")
+    fast = r._replace(angular_momentum=10.0)
+    assert_close(fast.edge_velocity(1.0), 2**0.5, "capped speed")
+    fast = r._replace(angular_momentum=-10.0)
+    assert_close(fast.edge_velocity(1.0), -(2**0.5), "capped speed")
+    assert r._replace(mass=0.0).edge_velocity(1.0) == 0.0
+    print("pass: reservoir edge velocity")
+
+
+def test_coupled_solver(mode):
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.setups.circumbinary_disk import CircumbinaryDisk
+    from sailfish.solvers.cbdiso_2d import Solver
+    from sailfish.spacetime import mesh_radii
+
+    setup = CircumbinaryDisk(reservoir_radius=1.5, reservoir_accreted_l=0.7)
+    mesh = setup.mesh(24)
+    r, _, _ = mesh_radii(mesh)
+    excised = r < 1.5
+
+    def make_solver(**kwargs):
+        return Solver(
+            setup=setup,
+            mesh=mesh,
+            num_patches=2,
+            mode=mode,
+            physics=setup.physics,
+            zone_mask=setup.active_zones(mesh),
+            **kwargs,
+        )
+
+    solver = make_solver()
+    initial = solver.reservoir
+
+    for _ in range(3):
+        solver.advance(1e-3)
+
+    sigma = solver.primitive[..., 0][excised]
+    assert solver.reservoir.mass != initial.mass and solver.reservoir.accreted_mass > 0
+    assert_close(sigma.min(), solver.reservoir.surface_density, "excised sigma")
+    assert_close(sigma.max(), solver.reservoir.surface_density, "excised sigma")
+
+    restarted = make_solver(time=solver.time, solution=solver.solution)
+    assert restarted.reservoir == solver.reservoir
+
+    solver.reset()
+    assert solver.reservoir == initial
+    sigma = solver.primitive[..., 0][excised]
+    assert_close(sigma.max(), initial.surface_density, "sigma after reset")
+    print("pass: coupled reservoir")
+
+
+def main():
+    test_advance()
+    test_edge_velocity()
+    test_coupled_solver("cpu")
+
+
+if __name__ == "__main__":
+    main()
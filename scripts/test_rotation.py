@@ -0,0 +1,87 @@
+"""
+Check the rotation curves used for initial disk velocities.
+
+The Keplerian, power-law, and tabulated curves are checked against their
+formulas, and the pressure-supported speed against the analytic speed of a
+locally isothermal disk of constant surface density, which is sub-Keplerian
+by a factor sqrt(1 - 1 / M^2).
+"""
+
+import os
+import sys
+import tempfile
+from math import sqrt
+
+sys.path.insert(1, ".")
+
+
+def test_circular_speed():
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.physics.rotation import RotationCurve
+
+    keplerian = RotationCurve.from_str("keplerian")
+    power_law = RotationCurve.from_str("power-law:2.0:-0.25")
+
+    for r in [0.1, 1.0, 3.5]:
+        assert_close(keplerian.circular_speed(r), sqrt(1.0 / r), "keplerian")
+        assert_close(power_law.circular_speed(r), 2.0 * r**-0.25, "power-law")
+
+    assert str(power_law) == "power-law:2.0:-0.25"
+    print("pass: circular speed")
+
+
+def test_rotation_table():
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.physics.rotation import RotationCurve, RotationCurveError
+
+    with tempfile.TemporaryDirectory() as d:
+        filename = os.path.join(d, "curve.dat")
+
+        with open(filename, "w") as f:
+            f.write("# r v\n1.0 1.0\n2.0 0.8\n4.0 0.6\n")
+
+        curve = RotationCurve.from_str(f"table:{filename}")
+        assert_close(curve.circular_speed(0.5), 1.0, "inside the table")
+        assert_close(curve.circular_speed(1.5), 0.9, "interpolated")
+        assert_close(curve.circular_speed(3.0), 0.7, "interpolated")
+        assert_close(curve.circular_speed(16.0), 0.3, "outside the table")
+
+        with open(filename, "w") as f:
+            f.write("1.0 1.0\n1.0 0.8\n")
+
+        for spec in [f"table:{filename}", "table:no-such-file", "power-law:1"]:
+            try:
+                RotationCurve.from_str(spec)
+            except RotationCurveError:
+                pass
+            else:
+                raise AssertionError(f"expected an error for {spec}")
+
+    print("pass: rotation table")
+
+
+def test_pressure_supported_speed():
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.physics.rotation import RotationCurve
+
+    curve = RotationCurve.from_str("keplerian")
+    mach = 10.0
+    sigma = lambda r: 1.0
+    pressure = lambda r: 1.0 / r / mach**2
+
+    for r in [0.2, 1.0, 5.0]:
+        v = curve.azimuthal_speed(r, sigma, pressure)
+        exact = sqrt(1.0 / r * (1.0 - 1.0 / mach**2))
+        assert_close(v, exact, f"pressure supported speed r={r}", tol=1e-7)
+
+    print("pass: pressure supported speed")
+
+
+def main():
+    test_circular_speed()
+    test_rotation_table()
+    test_pressure_supported_speed()
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,202 @@
+"""
+Check that primitive -> conserved -> primitive recovers the state to
+round-off, for each of the solver equations of state.
+
+States are drawn at random over a wide parameter range, including densities
+just above the floor. Each solver's conversion functions are reached through
+probe kernels (see `sailfish.kernel.fixtures`), so the checks cover the same
+code the solvers run, in any execution mode.
+"""
+
+import sys
+import logging
+
+sys.path.insert(1, ".")
+
+DENSITY_FLOOR = 1e-10
+PRESSURE_FLOOR = 1e-16
+GAMMA_LAW_INDEX = 5.0 / 3.0
+
+cbdiso_2d_probes = """
+PUBLIC void probe_round_trip(
+    int n,
+    double *prim,  // :: $.shape == (n, 3)
+    double *cons,  // :: $.shape == (n, 3)
+    double *prim2, // :: $.shape == (n, 3)
+    double density_floor)
+{
+    FOR_EACH_1D(n)
+    {
+        primitive_to_conserved(&prim[3 * i], &cons[3 * i]);
+        conserved_to_primitive(&cons[3 * i], &prim2[3 * i], 1e12, density_floor);
+    }
+}
+"""
+
+cbdgam_2d_probes = """
+PUBLIC void probe_round_trip(
+    int n,
+    double *prim,  // :: $.shape == (n, 4)
+    double *cons,  // :: $.shape == (n, 4)
+    double *prim2, // :: $.shape == (n, 4)
+    double density_floor,
+    double pressure_floor,
+    double gamma_law_index)
+{
+    FOR_EACH_1D(n)
+    {
+        primitive_to_conserved(&prim[4 * i], &cons[4 * i], gamma_law_index);
+        conserved_to_primitive(&cons[4 * i], &prim2[4 * i], 1e12, density_floor, pressure_floor, gamma_law_index);
+    }
+}
+"""
+
+srhd_1d_probes = """
+PUBLIC void probe_round_trip(
+    int n,
+    double *prim,  // :: $.shape == (n, 4)
+    double *cons,  // :: $.shape == (n, 4)
+    double *prim2) // :: $.shape == (n, 4)
+{
+    FOR_EACH_1D(n)
+    {
+        primitive_to_conserved(&prim[4 * i], &cons[4 * i], 1.0);
+        conserved_to_primitive(&cons[4 * i], &prim2[4 * i], 1.0, 0.0);
+    }
+}
+"""
+
+euler_1d_probes = """
+PUBLIC void probe_round_trip(
+    int n,
+    double *prim,  // :: $.shape == (n, 4)
+    double *cons,  // :: $.shape == (n, 4)
+    double *prim2, // :: $.shape == (n, 4)
+    double density_floor,
+    double pressure_floor,
+    double gamma_law_index)
+{
+    FOR_EACH_1D(n)
+    {
+        primitive_to_conserved(&prim[4 * i], &cons[4 * i], 1.0, gamma_law_index);
+        conserved_to_primitive(&cons[4 * i], &prim2[4 * i], 1.0, density_floor, pressure_floor, gamma_law_index);
+    }
+}
+"""
+
+
+def random_states(rng, n, num_fields, xp):
+    """
+    Return an array of `n` random primitive states. Densities are
+    log-uniform between just above the density floor and 1e3, velocities (or
+    four-velocities) are uniform in [-10, 10], and the specific internal
+    energy spans five decades. Any fourth field is a pressure.
+    """
+    import numpy as np
+
+    prim = np.zeros([n, num_fields])
+    prim[:, 0] = 10 ** rng.uniform(np.log10(DENSITY_FLOOR) + 1e-6, 3.0, n)
+    prim[:, 1:3] = rng.uniform(-10.0, 10.0, [n, 2])
+
+    if num_fields == 4:
+        prim[:, 3] = prim[:, 0] * 10 ** rng.uniform(-3.0, 2.0, n)
+
+    return xp.array(prim)
+
+
+def relative_error(a, b):
+    import numpy as np
+
+    return np.max(np.abs(a - b) / np.maximum(np.abs(b), 1e-300), axis=0)
+
+
+def check(name, actual, expected, rtol):
+    import numpy as np
+
+    err = relative_error(actual, expected)
+
+    if np.any(err > rtol):
+        raise AssertionError(f"{name}: relative error {err} exceeds {rtol}")
+    print(f"pass: {name} (max relative error per field {err})")
+
+
+def test_cbdiso_2d(mode, rng, n, xp, to_host):
+    from sailfish.kernel.fixtures import library_with_probes
+
+    lib = library_with_probes("sailfish/solvers/cbdiso_2d.c", cbdiso_2d_probes, mode)
+    prim = random_states(rng, n, 3, xp)
+    cons = xp.zeros_like(prim)
+    prim2 = xp.zeros_like(prim)
+    lib.probe_round_trip[prim.shape[:1]](prim, cons, prim2, DENSITY_FLOOR)
+    check("cbdiso_2d round trip", to_host(prim2), to_host(prim), 1e-13)
+
+
+def test_cbdgam_2d(mode, rng, n, xp, to_host):
+    from sailfish.kernel.fixtures import library_with_probes
+
+    lib = library_with_probes("sailfish/solvers/cbdgam_2d.c", cbdgam_2d_probes, mode)
+    prim = random_states(rng, n, 4, xp)
+    cons = xp.zeros_like(prim)
+    prim2 = xp.zeros_like(prim)
+    lib.probe_round_trip[prim.shape[:1]](
+        prim, cons, prim2, DENSITY_FLOOR, PRESSURE_FLOOR, GAMMA_LAW_INDEX
+    )
+    # The pressure is recovered by subtracting the kinetic energy from the
+    # total, so its error grows with the Mach number squared (up to ~1e5).
+    check("cbdgam_2d round trip", to_host(prim2), to_host(prim), 1e-9)
+
+
+def test_srhd_1d(mode, rng, n, xp, to_host):
+    from sailfish.kernel.fixtures import library_with_probes
+
+    lib = library_with_probes("sailfish/solvers/srhd_1d.c", srhd_1d_probes, mode)
+    prim = random_states(rng, n, 4, xp)
+    prim[:, 2] = prim[:, 3]  # pressure lives in field 2 for this solver
+    prim[:, 3] = 1.0  # and field 3 is a passive scalar
+    cons = xp.zeros_like(prim)
+    prim2 = xp.zeros_like(prim)
+    prim2[:, 2] = 2.0 * prim[:, 2]  # the root-finder's initial pressure guess
+    lib.probe_round_trip[prim.shape[:1]](prim, cons, prim2)
+    # The root-finder tolerance is relative to the total energy, so the
+    # pressure of a cold state is recovered less precisely.
+    check("srhd_1d round trip", to_host(prim2), to_host(prim), 1e-8)
+
+
+def test_euler_1d(mode, rng, n, xp, to_host):
+    from sailfish.kernel.fixtures import library_with_probes
+
+    lib = library_with_probes("sailfish/solvers/euler_1d.c", euler_1d_probes, mode)
+    prim = random_states(rng, n, 4, xp)
+    prim[:, 2] = prim[:, 3]  # pressure lives in field 2 for this solver
+    prim[:, 3] = 1.0  # and field 3 is a passive scalar
+    cons = xp.zeros_like(prim)
+    prim2 = xp.zeros_like(prim)
+    lib.probe_round_trip[prim.shape[:1]](
+        prim, cons, prim2, DENSITY_FLOOR, PRESSURE_FLOOR, GAMMA_LAW_INDEX
+    )
+    check("euler_1d round trip", to_host(prim2), to_host(prim), 1e-9)
+
+
+def main():
+    import argparse
+    import numpy as np
+    from sailfish.kernel.fixtures import parse_test_args
+    from sailfish.kernel.system import get_array_module
+    from sailfish.subdivide import to_host
+
+    parser = argparse.ArgumentParser()
+    parser.add_argument("--samples", type=int, default=100000)
+    parser.add_argument("--seed", type=int, default=0)
+    args = parse_test_args(modes=["cpu", "omp", "gpu", "checked"], parser=parser)
+    logging.basicConfig(level=logging.INFO)
+    xp = get_array_module(args.mode)
+
+    rng = np.random.default_rng(args.seed)
+    test_cbdiso_2d(args.mode, rng, args.samples, xp, to_host)
+    test_cbdgam_2d(args.mode, rng, args.samples, xp, to_host)
+    test_srhd_1d(args.mode, rng, args.samples, xp, to_host)
+    test_euler_1d(args.mode, rng, args.samples, xp, to_host)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,112 @@
+"""
+Check the reading of run configuration files.
+
+The items of a run configuration are the command line options they're named
+after, so a file and the equivalent command line run the same simulation.
+TOML, YAML, and JSON files with the same items are read the same way.
+"""
+
+import sys
+
+sys.path.insert(1, ".")
+
+TOML = """
+setup = "circumbinary-disk"
+resolution = 256
+mode = "omp"
+end_time = 10.0
+checkpoint = 1.0
+final_chkpt = true
+check_symmetry = false
+analysis = ["radial-profiles", "torques"]
+
+[model]
+mach_number = 20.0
+sink_model = "torque_free"
+"""
+
+JSON = """
+{
+    "setup": "circumbinary-disk",
+    "resolution": 256,
+    "mode": "omp",
+    "end_time": 10.0,
+    "checkpoint": 1.0,
+    "final_chkpt": true,
+    "check_symmetry": false,
+    "analysis": ["radial-profiles", "torques"],
+    "model": {"mach_number": 20.0, "sink_model": "torque_free"}
+}
+"""
+
+EXPECTED = [
+    "circumbinary-disk",
+    "--resolution",
+    "256",
+    "--mode",
+    "omp",
+    "--end-time",
+    "10.0",
+    "--checkpoint",
+    "1.0",
+    "--final-chkpt",
+    "--analysis",
+    "radial-profiles",
+    "--analysis",
+    "torques",
+    "--model",
+    "mach_number=20.0",
+    "sink_model='torque_free'",
+]
+
+
+def test_arguments(tmp):
+    import os
+    from sailfish.driver import load_run_config, run_config_arguments
+
+    for name, text in [("run.toml", TOML), ("run.json", JSON)]:
+        filename = os.path.join(tmp, name)
+
+        with open(filename, "w") as f:
+            f.write(text)
+
+        assert run_config_arguments(load_run_config(filename)) == EXPECTED
+
+    print("pass: arguments")
+
+
+def test_rejects_bad_items(tmp):
+    import os
+    from sailfish.driver import ConfigurationError
+    from sailfish.driver import load_run_config, run_config_arguments
+
+    filename = os.path.join(tmp, "bad.toml")
+
+    with open(filename, "w") as f:
+        f.write("setup = [")
+
+    for attempt in [
+        lambda: load_run_config(filename),
+        lambda: load_run_config(os.path.join(tmp, "missing.json")),
+        lambda: run_config_arguments(dict(resolution=8)),
+        lambda: run_config_arguments(dict(setup="shocktube", size=8), {"--mode"}),
+    ]:
+        try:
+            attempt()
+        except ConfigurationError:
+            continue
+        raise AssertionError("the configuration should be rejected")
+
+    print("pass: rejects bad items")
+
+
+def main():
+    from tempfile import TemporaryDirectory
+
+    with TemporaryDirectory() as tmp:
+        test_arguments(tmp)
+        test_rejects_bad_items(tmp)
+
+
+if __name__ == "__main__":
+    main()
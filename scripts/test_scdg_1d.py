@@ -0,0 +1,60 @@
+"""
+Check the time-symmetric integration mode of the scdg_1d solver.
+
+Until the reversal time, a time-symmetric run is the same as an ordinary one.
+Mirroring the solution reverses time exactly, so a smooth wave which is
+advected forward and then backward returns to its initial data, up to the
+dissipation of the scheme, which converges away with resolution.
+"""
+
+import sys
+
+sys.path.insert(1, ".")
+
+
+def run(num_zones, until, options=dict()):
+    from sailfish.interactive import Simulation
+
+    sim = Simulation(
+        "advection",
+        solver_options=dict(order=3, integrator="rk3", **options),
+        resolution=num_zones,
+    )
+    initial = sim.solver.solution.copy()
+
+    while sim.time < until - 1e-12:
+        sim.solver.advance(min(0.2 / num_zones, until - sim.time))
+
+    return initial, sim.solver.solution
+
+
+def test_forward_until_reversal():
+    import numpy as np
+
+    _, expected = run(32, 0.25)
+    _, actual = run(32, 0.25, dict(reverse_at=0.5))
+    assert np.array_equal(actual, expected)
+    print("pass: forward until reversal")
+
+
+def test_returns_to_initial_data():
+    import numpy as np
+
+    errors = list()
+
+    for num_zones in (16, 32):
+        initial, final = run(num_zones, 0.5, dict(reverse_at=0.25))
+        errors.append(np.abs(final - initial)[..., 0].mean())
+
+    assert errors[0] < 1e-3
+    assert errors[1] < errors[0]
+    print(f"pass: returns to initial data (errors {errors[0]:.2e}, {errors[1]:.2e})")
+
+
+def main():
+    test_forward_until_reversal()
+    test_returns_to_initial_data()
+
+
+if __name__ == "__main__":
+    main()
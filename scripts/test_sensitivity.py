@@ -0,0 +1,68 @@
+"""
+Check the tangent-linear derivatives of a short disk simulation.
+
+Over a few iterations the trajectories of two simulations with slightly
+different viscosities barely separate, so the finite difference of their
+solutions has to agree with the derivative evolved by the tangent-linear
+mode, when they take the same time steps.
+"""
+
+import sys
+
+sys.path.insert(1, ".")
+
+
+def test_viscosity_derivative():
+    import numpy as np
+    from sailfish.interactive import Simulation
+    from sailfish.sensitivity import TangentLinear
+
+    nu, relative_step = 1e-3, 1e-4
+    h = nu * relative_step
+    sim = TangentLinear(
+        "circumbinary-disk",
+        ["nu"],
+        model_parameters=dict(nu=nu),
+        relative_step=relative_step,
+        resolution=24,
+    )
+    plus, minus = (
+        Simulation("circumbinary-disk", model_parameters=dict(nu=p), resolution=24)
+        for p in (nu + h, nu - h)
+    )
+
+    for _ in range(5):
+        sim.step()
+        plus.solver.advance(sim.dt)
+        minus.solver.advance(sim.dt)
+
+    expected = (plus.solver.primitive_array() - minus.solver.primitive_array()) / h
+    expected *= 0.5
+    actual = sim.derivative("nu")
+    scale = np.abs(expected).max()
+
+    assert scale > 0.0, "the solution should depend on the viscosity"
+    assert np.abs(actual - expected).max() < 1e-4 * scale
+    print("pass: viscosity derivative")
+
+
+def test_rejects_bad_parameters():
+    from sailfish.sensitivity import TangentLinear
+
+    for name in ["eos", "not_a_parameter"]:
+        try:
+            TangentLinear("circumbinary-disk", [name], resolution=8)
+        except ValueError:
+            continue
+        raise AssertionError(f"{name} should be rejected")
+
+    print("pass: rejects bad parameters")
+
+
+def main():
+    test_viscosity_derivative()
+    test_rejects_bad_parameters()
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,86 @@
+"""
+Check the sampling of setups on meshes, with and without guard zones.
+
+The padded array of a setup's primitive data has the layout of a solver's
+`primitive_array(guard_zones=True)`, and the guard zones are sampled at
+their zone centers, outside the mesh. The built-in setups validate their
+default model parameters.
+"""
+
+import sys
+
+sys.path.insert(1, ".")
+
+
+def test_padded_1d():
+    from sailfish.mesh import PlanarCartesianMesh
+    from sailfish.setups import Shocktube
+
+    setup = Shocktube()
+    mesh = PlanarCartesianMesh(0.0, 1.0, 10)
+    interior = setup.primitive_array(mesh, 3)
+    padded = setup.primitive_array(mesh, 3, num_guard=2)
+
+    assert interior.shape == (10, 3) and padded.shape == (14, 3)
+    assert (padded[2:-2] == interior).all()
+    assert (padded[:2, 0] == 1.0).all() and (padded[-2:, 0] == 0.1).all()
+    print("pass: padded 1d")
+
+
+def test_padded_2d():
+    import numpy as np
+    from sailfish.setups import KelvinHelmholtz
+
+    setup = KelvinHelmholtz()
+    mesh = setup.mesh(8)
+    padded = setup.primitive_array(mesh, 4, time=0.0, num_guard=2)
+    expected = np.zeros(4)
+
+    assert padded.shape == (12, 12, 4)
+
+    for i, j in [(0, 0), (1, 11), (5, 6), (11, 3)]:
+        setup.primitive(0.0, mesh.cell_coordinates(i - 2, j - 2), expected)
+        assert (padded[i, j] == expected).all()
+
+    print("pass: padded 2d")
+
+
+def test_uniform_torus():
+    from sailfish.setups import UniformTorus
+    from sailfish.setup_base import SetupError
+
+    setup = UniformTorus()
+    mesh = setup.mesh(16)
+    sigma = setup.primitive_array(mesh, 3)[..., 0]
+    i, j = mesh.cell_at(1.5, 0.1)
+
+    assert sigma[i, j] == 1.0 and sigma[0, 0] == setup.sigma
+
+    try:
+        UniformTorus(inner_radius=3.0).validate()
+    except SetupError:
+        print("pass: uniform torus")
+        return
+
+    raise AssertionError("an inner radius beyond the outer one should be rejected")
+
+
+def test_defaults_validate():
+    from sailfish.setup_base import SetupBase
+    import sailfish.setups
+
+    for cls in SetupBase.__subclasses__():
+        cls().validate()
+
+    print("pass: defaults validate")
+
+
+def main():
+    test_padded_1d()
+    test_padded_2d()
+    test_uniform_torus()
+    test_defaults_validate()
+
+
+if __name__ == "__main__":
+    main()
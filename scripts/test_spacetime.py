@@ -0,0 +1,71 @@
+"""
+Check the eccentricity recorded in spacetime diagrams.
+
+A test particle at the pericenter of an orbit has its eccentricity vector
+pointing toward it, with the orbital eccentricity as its length. A ring in
+circular Keplerian rotation about a binary's barycenter has no eccentricity,
+wherever the barycenter is and however it moves.
+"""
+
+import sys
+
+sys.path.insert(1, ".")
+
+
+def binary(xc, yc, vxc, vyc):
+    from sailfish.physics.kepler import PointMass
+
+    m1 = PointMass(0.6, xc - 0.2, yc, vxc, vyc - 0.4)
+    m2 = PointMass(0.4, xc + 0.3, yc, vxc, vyc + 0.6)
+    return m1, m2
+
+
+def test_pericenter():
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.spacetime import eccentricity_vectors
+
+    e, q = 0.5, 0.8
+    v = ((1.0 + e) / q) ** 0.5
+    ex, ey = eccentricity_vectors(0.0, q, -v, 0.0)
+    assert_close(ex, 0.0, "pericenter ex")
+    assert_close(ey, e, "pericenter ey")
+
+    point_masses = binary(1.0, 2.0, 0.1, 0.2)
+    ex, ey = eccentricity_vectors(1.0 + q, 2.0, 0.1, 0.2 + v, point_masses)
+    assert_close(ex, e, "pericenter ex about a binary")
+    assert_close(ey, 0.0, "pericenter ey about a binary")
+    print("pass: pericenter")
+
+
+def test_circular_ring():
+    import numpy as np
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.mesh import PlanarCartesian2DMesh
+    from sailfish.spacetime import SpacetimeDiagram
+
+    mesh = PlanarCartesian2DMesh(x0=-4.0, y0=-4.0, x1=4.0, y1=4.0, ni=200, nj=200)
+    x, y = mesh.cell_center_arrays()
+    xc, yc, vxc, vyc = 0.3, -0.2, 0.05, 0.1
+    dx, dy = x - xc, y - yc
+    r = (dx * dx + dy * dy) ** 0.5
+    sigma = np.exp(-(((r - 2.0) / 0.3) ** 2))
+    vx = vxc - dy * r**-1.5
+    vy = vyc + dx * r**-1.5
+
+    diagram = SpacetimeDiagram(mesh, num_bins=40)
+    diagram.add(0.0, mesh, sigma, vx, vy, point_masses=binary(xc, yc, vxc, vyc))
+    arrays = diagram.arrays()
+
+    assert arrays["eccentricity"].shape == (1, 40)
+    assert_close(abs(arrays["mean_eccentricity"][0]), 0.0, "ring mean", tol=1e-9)
+    assert np.abs(arrays["eccentricity"][0]).max() < 1e-9
+    print("pass: circular ring")
+
+
+def main():
+    test_pericenter()
+    test_circular_ring()
+
+
+if __name__ == "__main__":
+    main()
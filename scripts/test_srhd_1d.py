@@ -0,0 +1,48 @@
+"""
+Check the growing domain mode of the srhd_1d solver: the active region
+starts at the disturbed zones plus a margin, follows the shock of a
+relativistic shock tube out into the uniform exterior, and the solution
+agrees with that of the whole mesh.
+"""
+
+import sys
+import logging
+
+sys.path.insert(1, ".")
+
+
+def test_grow_domain(mode):
+    from sailfish.kernel.fixtures import assert_close
+    from sailfish.setups.simple1d import Shocktube
+    from sailfish.solvers.srhd_1d import Solver
+
+    # The left half of the tube differs from the exterior, the right state,
+    # so it's active at the start, with the margin. The grown solver is split
+    # over two patches, so the active region crosses into the second one.
+    setup = Shocktube()
+    mesh = setup.mesh(400)
+    options = dict(grow_domain=True, grow_margin=16)
+    full = Solver(setup=setup, mesh=mesh, mode=mode)
+    grown = Solver(setup=setup, mesh=mesh, mode=mode, num_patches=2, options=options)
+    assert grown.num_active == 216, grown.num_active
+
+    for _ in range(100):
+        full.advance(0.3 / 400)
+        grown.advance(0.3 / 400)
+
+    assert 216 < grown.num_active < 400, grown.num_active
+    assert_close(grown.primitive, full.primitive, "grown domain solution", tol=1e-8)
+    print("pass: grow domain")
+
+
+def main():
+    from sailfish.kernel.fixtures import parse_test_args
+
+    args = parse_test_args()
+    logging.basicConfig(level=logging.INFO)
+
+    test_grow_domain(args.mode)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,133 @@
+"""
+Check the time series file format: round trips of real and complex columns,
+recovery from truncated and corrupt records, continuing a file from a given
+number of records, and conversion to CSV.
+"""
+
+import sys
+
+sys.path.insert(1, ".")
+
+
+def columns():
+    from sailfish.timeseries import Column
+
+    return [
+        Column("time", "reference time"),
+        Column("mdot_1_acc", "mass / time"),
+        Column("sigma_m1", "mass", "complex128"),
+    ]
+
+
+def samples(n0, n1):
+    return [[0.1 * n, -2.0 * n, complex(n, -n)] for n in range(n0, n1)]
+
+
+def test_round_trip():
+    import os
+    import tempfile
+    from sailfish.timeseries import TimeseriesWriter, read_timeseries
+
+    with tempfile.TemporaryDirectory() as directory:
+        filename = os.path.join(directory, "round_trip.sfts")
+        writer = TimeseriesWriter(filename, columns())
+        writer.append(samples(0, 3))
+        writer.append(samples(3, 5))
+        ts = read_timeseries(filename)
+
+    assert ts.columns == columns()
+    assert ts.units == ["reference time", "mass / time", "mass"]
+    assert ts.samples == samples(0, 5)
+    print("pass: round trip")
+
+
+def test_crash_tolerance():
+    import os
+    import tempfile
+    from sailfish.timeseries import TimeseriesWriter, read_timeseries
+
+    with tempfile.TemporaryDirectory() as directory:
+        filename = os.path.join(directory, "crash.sfts")
+        TimeseriesWriter(filename, columns()).append(samples(0, 4))
+
+        with open(filename, "ab") as f:
+            f.write(b"\x00" * 13)
+
+        assert read_timeseries(filename).samples == samples(0, 4)
+
+        size = os.path.getsize(filename)
+        record_size = 8 * 4 + 4
+
+        with open(filename, "r+b") as f:
+            f.seek(size - 13 - record_size + 8)
+            f.write(b"\xff")
+
+        assert read_timeseries(filename).samples == samples(0, 3)
+
+        writer = TimeseriesWriter(filename, columns())
+        assert writer.num_records == 3
+        writer.append(samples(3, 6))
+        assert read_timeseries(filename).samples == samples(0, 6)
+
+    print("pass: crash tolerance")
+
+
+def test_continue_from_checkpoint():
+    import os
+    import tempfile
+    from sailfish.timeseries import TimeseriesWriter, read_timeseries
+
+    with tempfile.TemporaryDirectory() as directory:
+        filename = os.path.join(directory, "restart.sfts")
+        TimeseriesWriter(filename, columns()).append(samples(0, 8))
+        writer = TimeseriesWriter(filename, columns(), num_records=5)
+        assert writer.num_records == 5
+        writer.append(samples(5, 7))
+        assert read_timeseries(filename).samples == samples(0, 7)
+
+        try:
+            TimeseriesWriter(filename, columns()[:2])
+        except ValueError:
+            pass
+        else:
+            raise AssertionError("mismatched columns were accepted")
+
+    print("pass: continue from checkpoint")
+
+
+def test_to_csv():
+    import csv
+    import os
+    import tempfile
+    from sailfish.timeseries import TimeseriesWriter, to_csv
+
+    with tempfile.TemporaryDirectory() as directory:
+        filename = os.path.join(directory, "convert.sfts")
+        TimeseriesWriter(filename, columns()).append(samples(0, 3))
+
+        with open(to_csv(filename)) as f:
+            rows = list(csv.reader(f))
+
+    assert rows[0] == [
+        "time [reference time]",
+        "mdot_1_acc [mass / time]",
+        "sigma_m1.real [mass]",
+        "sigma_m1.imag [mass]",
+    ]
+    assert [float(x) for x in rows[3]] == [0.2, -4.0, 2.0, -2.0]
+    print("pass: to csv")
+
+
+def main():
+    from sailfish.kernel.fixtures import parse_test_args
+
+    parse_test_args()
+
+    test_round_trip()
+    test_crash_tolerance()
+    test_continue_from_checkpoint()
+    test_to_csv()
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,96 @@
+"""
+Check the decomposition of the torques on the binary by the region of the gas
+exerting them: the zones are classified into the minidisks, the cavity, the
+outer disk, and the buffer, and the torques from the regions add up to the
+total gravitational torque on each point mass.
+"""
+
+import sys
+
+sys.path.insert(1, ".")
+
+
+def test_source_regions():
+    from sailfish.physics.circumbinary import Diagnostic, PointMass
+    from sailfish.physics.roche import RocheGeometry, SOURCE_REGIONS
+
+    m1 = PointMass(mass=0.5, position_x=-0.5)
+    m2 = PointMass(mass=0.5, position_x=0.5)
+    roche = RocheGeometry(m1, m2)
+    points = dict(
+        minidisks=[(-0.5, 0.05), (0.45, -0.1)],
+        cavity=[(0.0, 0.9), (-1.5, 0.5)],
+        disk=[(2.5, 0.0), (0.0, -3.0)],
+        buffer=[(4.0, 1.0), (-3.0, -3.0)],
+    )
+
+    for region, xys in points.items():
+        for x, y in xys:
+            index = roche.source_region_index(x, y, 2.0, 3.5)
+            assert SOURCE_REGIONS[index] == region, (x, y, SOURCE_REGIONS[index])
+
+    # Without a buffer, the disk extends to the edge of the domain.
+    assert SOURCE_REGIONS[roche.source_region_index(4.0, 1.0, 2.0)] == "disk"
+
+    d = Diagnostic(quantity="torque", which_mass=2, gravity=True, source_region="disk")
+    assert d.label == "torque_2_grav_disk"
+    print("pass: source regions")
+
+
+def test_setup_diagnostics():
+    from sailfish.setup_base import SetupError
+    from sailfish.setups.circumbinary_disk import CircumbinaryDisk
+
+    diagnostics = CircumbinaryDisk(which_diagnostics="torques").diagnostics
+    labels = [d["source_region"] for d in diagnostics if "source_region" in d]
+    assert labels == ["minidisks", "cavity", "disk", "buffer"] * 2, labels
+
+    try:
+        CircumbinaryDisk(which_diagnostics="torques", eos="gamma-law")
+    except SetupError:
+        pass
+    else:
+        raise AssertionError("gamma-law torque regions should be rejected")
+
+    print("pass: setup diagnostics")
+
+
+def test_torques_add_up(mode):
+    from sailfish.interactive import Simulation
+
+    sim = Simulation(
+        "circumbinary-disk",
+        model_parameters=dict(
+            domain_radius=4.0,
+            buffer_is_enabled=True,
+            which_diagnostics="torques",
+        ),
+        execution_mode=mode,
+        resolution=64,
+    )
+    sim.step(5)
+    values = dict(zip(sim.solver.reduction_names, sim.solver.reductions()))
+
+    for which_mass in (1, 2):
+        total = values[f"torque_{which_mass}_grav"]
+        parts = [
+            values[f"torque_{which_mass}_grav_{region}"]
+            for region in ["minidisks", "cavity", "disk", "buffer"]
+        ]
+        assert abs(sum(parts) - total) < 1e-10 * sum(abs(p) for p in parts)
+        assert all(p != 0.0 for p in parts), parts
+
+    print("pass: torques add up")
+
+
+def main():
+    from sailfish.kernel.fixtures import parse_test_args
+
+    args = parse_test_args()
+    test_source_regions()
+    test_setup_diagnostics()
+    test_torques_add_up(args.mode)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,118 @@
+"""
+Check the unit conversions: code units are parsed with unit names, and the
+missing one follows from G = 1, the units strings of fields and diagnostics
+convert to cgs and SI, and the readers return time series columns and fields
+in either system from the conversions in the file.
+"""
+
+import sys
+
+sys.path.insert(1, ".")
+
+
+def test_code_units():
+    from math import pi
+    from sailfish.units import CONSTANTS, CodeUnits
+
+    units = CodeUnits.from_str("length=1au:mass=1msun")
+    assert units.length == CONSTANTS["au"] and units.mass == CONSTANTS["msun"]
+    assert abs(2.0 * pi * units.time / CONSTANTS["yr"] - 1.0) < 1e-4
+
+    units = CodeUnits.from_str("length=2e13:time=1yr")
+    G = units.length**3 / (units.mass * units.time**2)
+    assert abs(G / CONSTANTS["G"] - 1.0) < 1e-12
+    assert CodeUnits.from_str("mass=1kg:length=1km:time=1s") == (1e5, 1e3, 1.0)
+
+    for text in ["length=1au", "length=1au:mass=-1msun", "size=1:mass=1g"]:
+        try:
+            CodeUnits.from_str(text)
+        except ValueError:
+            continue
+        raise AssertionError(f"{text} should be rejected")
+
+    print("pass: code units")
+
+
+def test_conversion():
+    from sailfish.units import CONSTANTS, CodeUnits, conversion_table
+
+    units = CodeUnits(length=10.0, mass=2.0, time=5.0)
+    torque = units.conversion("mass length^2 / time^2")
+    assert abs(torque["cgs"] - 2.0 * 100.0 / 25.0) < 1e-12
+    assert abs(torque["si"] - torque["cgs"] * 1e-7) < 1e-18
+    assert torque["cgs_units"] == "g cm^2 / s^2"
+    assert torque["si_units"] == "kg m^2 / s^2"
+
+    assert units.conversion("reference time", 2.0)["cgs"] == 10.0
+    assert units.conversion("c")["cgs"] == CONSTANTS["c"]
+    assert units.conversion("c")["si_units"] == "m / s"
+    assert units.conversion("1")["cgs_units"] == "1"
+
+    table = conversion_table(
+        units,
+        fields=[("sigma", "mass / length^2"), ("tracer0", "1")],
+        diagnostics=[("time", "reference time"), ("unknown", "")],
+        reference_time=2.0,
+    )
+    assert table["code_units"] == dict(length=10.0, mass=2.0, time=5.0)
+    assert table["fields"]["sigma"]["cgs"] == 0.02
+    assert list(table["diagnostics"]) == ["time"]
+    print("pass: conversion")
+
+
+def test_readers():
+    import os
+    import tempfile
+    import numpy as np
+    from sailfish.products import Product
+    from sailfish.timeseries import Column, TimeseriesWriter, read_timeseries
+    from sailfish.units import CodeUnits, conversion_table
+
+    units = CodeUnits(length=10.0, mass=2.0, time=5.0)
+    table = conversion_table(
+        units,
+        fields=[("sigma", "mass / length^2")],
+        diagnostics=[("mdot", "mass / time")],
+    )
+
+    with tempfile.TemporaryDirectory() as tmp:
+        filename = os.path.join(tmp, "timeseries.sfts")
+        columns = [Column("mdot", "mass / time")]
+        TimeseriesWriter(filename, columns, None, table).append([[1.0], [2.0]])
+        ts = read_timeseries(filename)
+        assert ts.unit_conversions == table
+        assert np.allclose(ts.column("mdot", system="cgs"), [0.4, 0.8])
+        assert np.allclose(ts.column("mdot", system="si"), [4e-4, 8e-4])
+
+    snapshot = Product(
+        "snap.0000.pk",
+        dict(
+            time=0.0,
+            iteration=0,
+            primitive=np.ones((4, 4, 1)),
+            primitive_fields=[dict(name="sigma")],
+            unit_conversions=table,
+        ),
+    )
+    assert (snapshot.field("sigma", system="cgs") == 0.02).all()
+    assert (snapshot.field(0) == 1.0).all()
+
+    snapshot.contents["unit_conversions"] = None
+
+    try:
+        snapshot.field("sigma", system="cgs")
+    except KeyError:
+        print("pass: readers")
+        return
+
+    raise AssertionError("a file without unit conversions can't convert fields")
+
+
+def main():
+    test_code_units()
+    test_conversion()
+    test_readers()
+
+
+if __name__ == "__main__":
+    main()
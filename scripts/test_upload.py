@@ -0,0 +1,99 @@
+"""
+Check the upload of checkpoints and snapshots: hooks are chosen by the URL
+scheme, files are copied to a directory in the background, local copies are
+pruned except for the newest checkpoint and the milestones, and failed uploads
+are kept.
+"""
+
+import sys
+
+sys.path.insert(1, ".")
+
+
+def write_files(directory, names):
+    import os
+
+    filenames = [os.path.join(directory, name) for name in names]
+
+    for filename in filenames:
+        with open(filename, "w") as f:
+            f.write(os.path.basename(filename))
+
+    return filenames
+
+
+def test_find_hook_class():
+    from sailfish.upload import DirectoryUpload, GoogleCloudUpload, S3Upload
+    from sailfish.upload import UploadError, UploadHook, bucket_and_prefix
+
+    assert UploadHook.find_hook_class("s3://bucket/run") is S3Upload
+    assert UploadHook.find_hook_class("gs://bucket") is GoogleCloudUpload
+    assert UploadHook.find_hook_class("file:///scratch") is DirectoryUpload
+    assert UploadHook.find_hook_class("archive/run") is DirectoryUpload
+    assert bucket_and_prefix("s3://bucket/runs/a/") == ("bucket", "runs/a")
+    assert bucket_and_prefix("gs://bucket") == ("bucket", "")
+
+    try:
+        UploadHook.find_hook_class("ftp://host/run")
+    except UploadError:
+        print("pass: find upload hook class")
+        return
+
+    raise AssertionError("a URL with an unknown scheme should be rejected")
+
+
+def test_directory_upload():
+    import os
+    import tempfile
+    from sailfish.upload import DirectoryUpload, Uploader
+
+    with tempfile.TemporaryDirectory() as tmp:
+        local = os.path.join(tmp, "local")
+        remote = os.path.join(tmp, "remote")
+        os.mkdir(local)
+        names = ["chkpt.0000.pk", "snap.0000.pk", "chkpt.0001.pk", "snap.0001.pk"]
+        names += ["chkpt.orbit.0010.pk", "chkpt.0002.pk"]
+
+        uploader = Uploader(DirectoryUpload(f"file://{remote}"), prune=True)
+        uploader.submit(write_files(local, names))
+        assert uploader.close() == 0
+
+        assert sorted(os.listdir(remote)) == sorted(names)
+        assert sorted(os.listdir(local)) == ["chkpt.0002.pk", "chkpt.orbit.0010.pk"]
+
+        with open(os.path.join(remote, "snap.0001.pk")) as f:
+            assert f.read() == "snap.0001.pk"
+
+    print("pass: directory upload")
+
+
+def test_failed_upload():
+    import os
+    import tempfile
+    from sailfish.upload import UploadHook, Uploader
+
+    class FailingUpload(UploadHook):
+        scheme = "test-failing"
+
+        def upload(self, filename, key):
+            if key.startswith("snap."):
+                raise OSError("network is unreachable")
+
+    with tempfile.TemporaryDirectory() as tmp:
+        names = ["chkpt.0000.pk", "snap.0000.pk", "chkpt.0001.pk"]
+        uploader = Uploader(FailingUpload("test-failing://"), prune=True)
+        uploader.submit(write_files(tmp, names))
+        assert uploader.close() == 1
+        assert sorted(os.listdir(tmp)) == ["chkpt.0001.pk", "snap.0000.pk"]
+
+    print("pass: failed upload")
+
+
+def main():
+    test_find_hook_class()
+    test_directory_upload()
+    test_failed_upload()
+
+
+if __name__ == "__main__":
+    main()
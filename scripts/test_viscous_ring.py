@@ -0,0 +1,83 @@
+"""
+Check the viscous ring test of Lynden-Bell & Pringle (1974): the analytic
+solution conserves the ring's mass and angular momentum, and the 1D viscous
+disk solver and the 2D cbdiso_2d solver spread a ring as it does.
+"""
+
+import sys
+
+sys.path.insert(1, ".")
+
+
+def test_analytic_ring():
+    from math import pi
+    from sailfish.physics.viscous_disk import viscous_ring_surface_density
+
+    # Until tau approaches one, the ring hardly drains onto the origin, and
+    # its angular momentum is conserved for all time.
+    n, r_max = 20000, 12.0
+    radii = [(i + 0.5) * r_max / n for i in range(n)]
+
+    for tau in [0.02, 0.1, 0.5]:
+        sigma = [viscous_ring_surface_density(r, tau / 12.0) for r in radii]
+        mass = sum(2.0 * pi * r * s * r_max / n for r, s in zip(radii, sigma))
+        spin = sum(2.0 * pi * r**1.5 * s * r_max / n for r, s in zip(radii, sigma))
+        assert abs(spin - 1.0) < 1e-8, spin
+        assert abs(mass - 1.0) < (1e-5 if tau < 0.2 else 0.02), mass
+
+    # The solution is finite at the origin.
+    s0 = viscous_ring_surface_density(0.0, 0.01)
+    assert abs(viscous_ring_surface_density(1e-8, 0.01) / s0 - 1.0) < 1e-6
+    print("pass: analytic ring")
+
+
+def test_viscous_ring_1d():
+    from sailfish.setups.viscous_ring import ViscousRing
+
+    setup = ViscousRing()
+    time = setup.default_end_time
+    r, sigma = setup.evolve_1d(time)
+    error = setup.profile_error(r, sigma, time)
+    assert error < 1e-2, error
+    print(f"pass: viscous ring 1d (error {error:.2e})")
+
+
+def test_viscous_ring_2d(mode):
+    from sailfish.setups.viscous_ring import ViscousRing
+    from sailfish.solvers.cbdiso_2d import Solver
+
+    # The ring starts wide enough to be resolved by a few zones, and spreads
+    # to twice its viscous time. The error is mostly that of the numerical
+    # diffusion at this resolution, which lowers the peak.
+    setup = ViscousRing(initial_tau=0.05, final_tau=0.1)
+    mesh = setup.mesh(128)
+    solver = Solver(
+        setup=setup,
+        mesh=mesh,
+        time=setup.start_time,
+        mode=mode,
+        physics=setup.physics,
+    )
+    end_time = setup.default_end_time
+
+    while solver.time < end_time:
+        dt = solver.recommended_timestep(mesh.min_spacing(), solver.recommended_cfl)
+        solver.advance(min(dt, end_time - solver.time))
+
+    r, sigma = setup.radial_profile(mesh, solver.primitive_array()[..., 0])
+    error = setup.profile_error(r, sigma, solver.time)
+    assert error < 0.1, error
+    print(f"pass: viscous ring 2d (error {error:.2e})")
+
+
+def main():
+    from sailfish.kernel.fixtures import parse_test_args
+
+    args = parse_test_args()
+    test_analytic_ring()
+    test_viscous_ring_1d()
+    test_viscous_ring_2d(args.mode)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,15 @@
+import argparse
+import sys
+
+sys.path.insert(1, ".")
+
+from sailfish.timeseries import to_csv
+
+parser = argparse.ArgumentParser(
+    description="convert time series files (timeseries.sfts) to CSV"
+)
+parser.add_argument("filenames", nargs="+")
+args = parser.parse_args()
+
+for filename in args.filenames:
+    print(f"{filename} -> {to_csv(filename)}")
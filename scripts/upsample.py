@@ -9,9 +9,12 @@ def scale_by_n(infile, n):
     with open(infile, "rb") as inf:
         c = load(inf)
 
-    c["solution"] = c["solution"].repeat(n, axis=0).repeat(n, axis=1)
+    from sailfish.resample import resample_solution
+
+    mesh = c["mesh"]._replace(ni=c["mesh"].ni * n, nj=c["mesh"].nj * n)
+    c["solution"] = resample_solution(c["solution"], c["mesh"], mesh)
     c["driver"] = c["driver"]._replace(resolution=c["driver"].resolution * n)
-    c["mesh"] = c["mesh"]._replace(ni=c["mesh"].ni * n, nj=c["mesh"].nj * n)
+    c["mesh"] = mesh
     outfile = infile.replace(".pk", "_upsampled.pk")
 
     with open(outfile, "wb") as f: